@@ -0,0 +1,37 @@
+#include "your_header_file.h"
+
+#ifndef __AFL_FUZZ_TESTCASE_LEN
+    // The following definitions allow compilation without the AFL++ compiler.
+    ssize_t fuzz_len;
+    #define __AFL_FUZZ_TESTCASE_LEN fuzz_len
+    const uint8_t fuzz_buf[1024000];
+    #define __AFL_FUZZ_TESTCASE_BUF fuzz_buf
+    #define __AFL_FUZZ_INIT() void sync(void);
+    #define __AFL_LOOP(x) ((fuzz_len = read(0, fuzz_buf, sizeof(fuzz_buf))) > 0 ? 1 : 0)
+    #define __AFL_INIT() sync()
+#endif
+
+__AFL_FUZZ_INIT();
+
+int main(int argc, const char* argv[]) {
+
+    #ifdef __AFL_HAVE_MANUAL_CONTROL
+      __AFL_INIT();
+    #endif
+
+    // [Initialization]
+    // Perform one-time initialization for the target LSP server.
+    // Or call `LLVMFuzzerInitialize(argc, argv)` here.
+
+    const uint8_t *buf = __AFL_FUZZ_TESTCASE_BUF;
+    while (__AFL_LOOP(10000)) {
+        ssize_t len = __AFL_FUZZ_TESTCASE_LEN;
+        // [Input Processing]
+        // Process an input here:
+        //   1. Read `len` bytes from `buf` for LSP inputs, as if they were read from `stdin`.
+        //   2. Process the LSP inputs. Note that the input contains the `Content-Length` header.
+        //   3. Release resources and reset states.
+        // Or call `LLVMFuzzerTestOneInput(buf, len)` here.
+    }
+    return 0;
+}
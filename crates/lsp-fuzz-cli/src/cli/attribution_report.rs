@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use lsp_fuzz::attribution::{operator_effectiveness, read_attribution_log};
+
+use super::GlobalOptions;
+
+/// Summarizes the attribution log of a campaign (`fuzz --attribution-log`) per mutation operator.
+///
+/// For each operator, the table lists the corpus entries it helped to produce, how many of them
+/// added edges, and the edges they added. An entry produced by stacked operators counts for
+/// each of them, so the edges of the rows do not add up to the edges of the campaign.
+#[derive(Debug, clap::Parser)]
+pub(super) struct AttributionReportCommand {
+    /// The attribution log written by the campaign.
+    attribution_log: PathBuf,
+
+    /// The number of operators listed.
+    #[clap(long, default_value_t = 50)]
+    top: usize,
+}
+
+impl AttributionReportCommand {
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "The counts are far smaller than 2^52"
+    )]
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let records =
+            read_attribution_log(&self.attribution_log).context("Reading attribution log")?;
+        let edges: usize = records.iter().map(|it| it.edge_delta).sum();
+        let without_operators = records.iter().filter(|it| it.operators.is_empty()).count();
+        println!(
+            "{} corpus entries adding {edges} edges, {without_operators} without recorded operators",
+            records.len()
+        );
+
+        let table = operator_effectiveness(&records);
+        if table.is_empty() {
+            return Ok(());
+        }
+        println!(
+            "  {:<40} {:>8} {:>10} {:>8} {:>12}",
+            "operator", "entries", "new edges", "edges", "edges/entry"
+        );
+        for row in table.iter().take(self.top) {
+            println!(
+                "  {:<40} {:>8} {:>10} {:>8} {:>12.2}",
+                row.operator,
+                row.entries,
+                row.entries_with_new_edges,
+                row.edges,
+                row.edges as f64 / row.entries as f64
+            );
+        }
+        if table.len() > self.top {
+            println!("  ... {} more operators", table.len() - self.top);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use itertools::Itertools;
+use libafl::inputs::Input;
+use lsp_fuzz::{
+    lsp::{LspMessage, code_context::CodeContextRef},
+    lsp_input::{
+        LspInput,
+        messages::{SequenceElement, TransportFault},
+        uri,
+    },
+    text_document::{GrammarBasedMutation, TextDocument},
+};
+
+use super::GlobalOptions;
+
+/// Prints an input as a readable conversation, or the differences between two inputs.
+#[derive(Debug, clap::Parser)]
+pub(super) struct CatInputCommand {
+    /// The path to the input file.
+    input_file: PathBuf,
+
+    /// Print the differences from this input instead, message by message and line by line.
+    #[clap(long)]
+    diff: Option<PathBuf>,
+
+    /// Print whole documents instead of their first lines.
+    #[clap(long)]
+    full_documents: bool,
+}
+
+impl CatInputCommand {
+    const MAX_PARAMS_LEN: usize = 160;
+    const MAX_DOCUMENT_LINES: usize = 40;
+
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let input = LspInput::from_file(&self.input_file).context("Loading input file")?;
+        match &self.diff {
+            None => self.print(&input),
+            Some(other_file) => {
+                let other = LspInput::from_file(other_file).context("Loading input to diff")?;
+                print_diff(&input, &other);
+            }
+        }
+        Ok(())
+    }
+
+    fn print(&self, input: &LspInput) {
+        println!("Workspace:");
+        for (path, doc) in documents(input) {
+            let lines = document_lines(doc);
+            println!(
+                "  {} ({}, {} bytes, {} lines)",
+                path,
+                doc.language(),
+                doc.content().len(),
+                lines.len()
+            );
+            let shown = if self.full_documents {
+                lines.len()
+            } else {
+                Self::MAX_DOCUMENT_LINES
+            };
+            for (idx, line) in lines.iter().take(shown).enumerate() {
+                println!("    {:>4} | {line}", idx + 1);
+            }
+            if lines.len() > shown {
+                println!("         ... {} more lines", lines.len() - shown);
+            }
+        }
+        println!("Messages:");
+        for (index, entry) in conversation(input) {
+            let entry = entry.replace('\n', "\n        ");
+            match index {
+                Some(index) => println!("  #{index:<4} {entry}"),
+                None => println!("        {entry}"),
+            }
+        }
+    }
+}
+
+/// The source files of the workspace of `input` with their paths.
+fn documents(input: &LspInput) -> Vec<(String, &TextDocument)> {
+    input
+        .workspace
+        .iter_files()
+        .filter_map(|(path, entry)| {
+            let doc = entry.as_source_file()?;
+            Some((path.to_string_lossy().into_owned(), doc))
+        })
+        .collect()
+}
+
+fn document_lines(doc: &TextDocument) -> Vec<String> {
+    doc.to_string_lossy().lines().map(str::to_owned).collect()
+}
+
+/// One entry per message or transport fault of `input`, with the indices of the messages.
+///
+/// The entries of messages with a position include an excerpt of the targeted document.
+fn conversation(input: &LspInput) -> Vec<(Option<usize>, String)> {
+    let mut index = 0;
+    input
+        .messages
+        .elements()
+        .map(|element| match element {
+            SequenceElement::Message(message) => {
+                let rendered = render_message(input, message);
+                index += 1;
+                (Some(index - 1), rendered)
+            }
+            SequenceElement::Close { partial: None } => (None, "(close connection)".to_owned()),
+            SequenceElement::Close {
+                partial: Some((message, len)),
+            } => {
+                let rendered = render_message(input, message);
+                index += 1;
+                (
+                    Some(index - 1),
+                    format!("{rendered} (close connection after {len} bytes)"),
+                )
+            }
+        })
+        .chain(match input.messages.transport_fault() {
+            Some(TransportFault::HalfCloseAfterShutdown) => {
+                Some((None, "(close connection after shutdown)".to_owned()))
+            }
+            Some(TransportFault::PartialFinalMessage { bytes }) => Some((
+                None,
+                format!("(close connection after {bytes} bytes of exit)"),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn render_message(input: &LspInput, message: &LspMessage) -> String {
+    let mut rendered = message.method().to_owned();
+    let document = message
+        .document()
+        .filter(|it| uri::path_from_virtual_uri(&it.uri).is_some());
+    let position = message
+        .position()
+        .or_else(|| message.range().map(|it| &it.start));
+    if let Some(document) = document {
+        rendered.push(' ');
+        rendered.push_str(document.uri.as_str());
+        if let Some(position) = position {
+            rendered.push_str(&format!(
+                ":{}:{}",
+                position.line + 1,
+                position.character + 1
+            ));
+        }
+    }
+    rendered.push(' ');
+    rendered.push_str(&abbreviated_params(message));
+    let excerpt = document
+        .and_then(|it| input.get_text_document(&it.uri))
+        .zip(position)
+        .and_then(|(doc, position)| {
+            let line = usize::try_from(position.line).ok()?;
+            doc.to_string_lossy().lines().nth(line).map(str::to_owned)
+        });
+    if let Some(excerpt) = excerpt {
+        rendered.push_str(&format!("\n  > {}", excerpt.trim_end()));
+    }
+    rendered
+}
+
+/// The parameters of `message` as JSON, truncated to a readable length.
+fn abbreviated_params(message: &LspMessage) -> String {
+    // Messages are serialized as `{ "Variant": params }`.
+    let params = serde_json::to_value(message)
+        .ok()
+        .and_then(|it| it.as_object()?.values().next().cloned())
+        .unwrap_or_default();
+    let params = params.to_string();
+    if params.chars().count() > CatInputCommand::MAX_PARAMS_LEN {
+        let truncated: String = params
+            .chars()
+            .take(CatInputCommand::MAX_PARAMS_LEN)
+            .collect();
+        format!("{truncated}...")
+    } else {
+        params
+    }
+}
+
+fn print_diff(input: &LspInput, other: &LspInput) {
+    let docs = documents(input);
+    let other_docs = documents(other);
+    println!("Workspace:");
+    for (path, doc) in &docs {
+        match other_docs.iter().find(|(it, _)| it == path) {
+            None => println!("  - {path}"),
+            Some((_, other_doc)) if other_doc.content() == doc.content() => {}
+            Some((_, other_doc)) => {
+                println!("  ~ {path}");
+                let lines = document_lines(doc);
+                let other_lines = document_lines(other_doc);
+                for change in diff(&lines, &other_lines) {
+                    match change {
+                        Change::Same(..) => {}
+                        Change::Removed(idx) => println!("    {:>4} - {}", idx + 1, lines[idx]),
+                        Change::Added(idx) => {
+                            println!("    {:>4} + {}", idx + 1, other_lines[idx]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    for (path, _) in other_docs
+        .iter()
+        .filter(|(path, _)| docs.iter().all(|(it, _)| it != path))
+    {
+        println!("  + {path}");
+    }
+
+    println!("Messages:");
+    // Indices are left out, so that an added message does not change all the following ones.
+    let messages: Vec<_> = conversation(input).into_iter().map(|(_, it)| it).collect();
+    let other_messages: Vec<_> = conversation(other).into_iter().map(|(_, it)| it).collect();
+    for change in diff(&messages, &other_messages) {
+        let (marker, message) = match change {
+            Change::Same(idx, _) => (' ', &messages[idx]),
+            Change::Removed(idx) => ('-', &messages[idx]),
+            Change::Added(idx) => ('+', &other_messages[idx]),
+        };
+        println!("{marker} {}", message.lines().join(&format!("\n{marker} ")));
+    }
+}
+
+/// A line of a diff, with the indices of the line in the compared sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// A line-based diff of `lhs` and `rhs` following their longest common subsequence.
+///
+/// Common prefixes and suffixes are matched first. Middles too long to compare are reported
+/// as removed and added as a whole.
+fn diff<T: PartialEq>(lhs: &[T], rhs: &[T]) -> Vec<Change> {
+    const MAX_MIDDLE_LEN: usize = 2000;
+
+    let prefix = lhs.iter().zip(rhs).take_while(|(l, r)| l == r).count();
+    let suffix = lhs[prefix..]
+        .iter()
+        .rev()
+        .zip(rhs[prefix..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let lhs_mid = &lhs[prefix..lhs.len() - suffix];
+    let rhs_mid = &rhs[prefix..rhs.len() - suffix];
+
+    let mut changes: Vec<_> = (0..prefix).map(|idx| Change::Same(idx, idx)).collect();
+    if lhs_mid.len() > MAX_MIDDLE_LEN || rhs_mid.len() > MAX_MIDDLE_LEN {
+        changes.extend((0..lhs_mid.len()).map(|idx| Change::Removed(prefix + idx)));
+        changes.extend((0..rhs_mid.len()).map(|idx| Change::Added(prefix + idx)));
+    } else {
+        // `lcs[i][j]` is the length of the longest common subsequence of `lhs_mid[i..]` and
+        // `rhs_mid[j..]`.
+        let mut lcs = vec![vec![0_usize; rhs_mid.len() + 1]; lhs_mid.len() + 1];
+        for i in (0..lhs_mid.len()).rev() {
+            for j in (0..rhs_mid.len()).rev() {
+                lcs[i][j] = if lhs_mid[i] == rhs_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < lhs_mid.len() || j < rhs_mid.len() {
+            if i < lhs_mid.len() && j < rhs_mid.len() && lhs_mid[i] == rhs_mid[j] {
+                changes.push(Change::Same(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if j == rhs_mid.len() || (i < lhs_mid.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                changes.push(Change::Removed(prefix + i));
+                i += 1;
+            } else {
+                changes.push(Change::Added(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let lhs_suffix = lhs.len() - suffix;
+    let rhs_suffix = rhs.len() - suffix;
+    changes.extend((0..suffix).map(|idx| Change::Same(lhs_suffix + idx, rhs_suffix + idx)));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, diff};
+
+    #[test]
+    fn diff_matches_common_prefix_and_suffix() {
+        assert_eq!(
+            diff(&["a", "b", "c"], &["a", "x", "c"]),
+            [
+                Change::Same(0, 0),
+                Change::Removed(1),
+                Change::Added(1),
+                Change::Same(2, 2)
+            ]
+        );
+        assert_eq!(
+            diff(&["a", "c"], &["a", "b", "c"]),
+            [Change::Same(0, 0), Change::Added(1), Change::Same(1, 2)]
+        );
+        assert_eq!(diff::<&str>(&[], &[]), []);
+    }
+
+    #[test]
+    fn diff_follows_longest_common_subsequence() {
+        assert_eq!(
+            diff(&[1, 2, 3, 4], &[5, 2, 4, 6]),
+            [
+                Change::Removed(0),
+                Change::Added(0),
+                Change::Same(1, 1),
+                Change::Removed(2),
+                Change::Same(3, 2),
+                Change::Added(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_replaces_long_middles_as_a_whole() {
+        let lhs: Vec<_> = (0..2001).collect();
+        let rhs: Vec<_> = (2001..4002).collect();
+        let changes = diff(&lhs, &rhs);
+        assert_eq!(changes.len(), 4002);
+        assert!(
+            changes[..2001]
+                .iter()
+                .all(|it| matches!(it, Change::Removed(_)))
+        );
+        assert!(
+            changes[2001..]
+                .iter()
+                .all(|it| matches!(it, Change::Added(_)))
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use std::io;
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+use super::{Cli, GlobalOptions};
+
+/// Prints the completion script of a shell to stdout.
+///
+/// For example, `lsp-fuzz-cli completions bash > /etc/bash_completion.d/lsp-fuzz-cli`.
+#[derive(Debug, clap::Parser)]
+pub(super) struct CompletionsCommand {
+    /// The shell to generate the completion script for.
+    #[clap(value_enum)]
+    shell: Shell,
+}
+
+impl CompletionsCommand {
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let mut command = Cli::command();
+        let bin_name = command.get_name().to_owned();
+        clap_complete::generate(self.shell, &mut command, bin_name, &mut io::stdout());
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use lsp_fuzz::lsp_input::{LspInput, format};
+use tracing::{info, warn};
+
+use super::{GlobalOptions, corpus_files};
+
+/// Converts inputs between the structured format of the fuzzer and raw LSP byte streams.
+///
+/// Structured inputs are converted into the byte stream they are sent as, and byte streams
+/// of LSP-framed JSON-RPC messages (e.g., the inputs of byte-level fuzzers) are converted into
+/// structured inputs, where the documents opened by `textDocument/didOpen` become the workspace.
+/// The direction is chosen for each input, so corpora of either kind can be converted at once.
+#[derive(Debug, clap::Parser)]
+pub(super) struct ConvertInputCommand {
+    /// An input file, or a corpus directory whose inputs are converted.
+    input: PathBuf,
+
+    /// The converted input file, or the directory of the converted inputs of a corpus.
+    output: PathBuf,
+
+    /// The workspace directory referred to by the byte streams converted from structured inputs.
+    #[clap(long, default_value = "/tmp/lsp-fuzz-workspace")]
+    workspace_dir: PathBuf,
+}
+
+/// The direction in which an input is converted.
+#[derive(Debug, Clone, Copy)]
+enum Conversion {
+    ToBytes,
+    ToStructured,
+}
+
+impl ConvertInputCommand {
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        if !self.input.is_dir() {
+            let conversion = self.convert(&self.input, &self.output)?;
+            info!(?conversion, output = %self.output.display(), "Converted input");
+            return Ok(());
+        }
+        fs::create_dir_all(&self.output).context("Creating output directory")?;
+        let (mut to_bytes, mut to_structured, mut failed) = (0, 0, 0);
+        for input_file in corpus_files(&self.input)? {
+            let output_file = self.output.join(input_file.file_name().unwrap_or_default());
+            match self.convert(&input_file, &output_file) {
+                Ok(Conversion::ToBytes) => to_bytes += 1,
+                Ok(Conversion::ToStructured) => to_structured += 1,
+                Err(err) => {
+                    warn!(file = %input_file.display(), "Failed to convert input: {err:#}");
+                    failed += 1;
+                }
+            }
+        }
+        info!(to_bytes, to_structured, failed, "Conversion completed");
+        Ok(())
+    }
+
+    fn convert(&self, input_file: &Path, output_file: &Path) -> anyhow::Result<Conversion> {
+        let file = File::open(input_file).context("Opening input file")?;
+        if let Ok((input, _)) = format::read_input(BufReader::new(file)) {
+            let bytes = input.request_bytes(&self.workspace_dir);
+            fs::write(output_file, bytes).context("Writing byte stream")?;
+            return Ok(Conversion::ToBytes);
+        }
+
+        let bytes = fs::read(input_file).context("Reading input file")?;
+        let (input, report) = LspInput::from_lsp_stream(&bytes).context("Parsing byte stream")?;
+        if input.workspace.iter_files().next().is_none() {
+            bail!("The byte stream opens no documents in the workspace");
+        }
+        if report.undecodable > 0 || report.foreign_documents > 0 || report.truncated {
+            warn!(
+                file = %input_file.display(),
+                undecodable = report.undecodable,
+                foreign_documents = report.foreign_documents,
+                truncated = report.truncated,
+                "Parts of the byte stream are left out"
+            );
+        }
+        let writer = BufWriter::new(File::create(output_file).context("Creating output file")?);
+        format::write_input(&input, writer).context("Writing input")?;
+        Ok(Conversion::ToStructured)
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use libafl::inputs::Input;
+use lsp_fuzz::{
+    lsp_input::LspInput,
+    text_document::{
+        GrammarBasedMutation,
+        grammar::Grammar,
+        grammar_coverage::{GrammarCoverage, visible_rules},
+    },
+};
+use lsp_fuzz_grammars::Language;
+use tracing::warn;
+
+use super::{GlobalOptions, corpus_files};
+use crate::language_fragments::GrammarOverrides;
+
+/// Reports which derivation rules of the grammars the documents of a corpus exercise.
+///
+/// The documents are reparsed and each node is mapped back to the first derivation of its
+/// symbol that derives its children, as the last column of the stats file of `fuzz` counts them.
+/// The derivations of hidden symbols (e.g., `_expression`) are not counted. For each language,
+/// the derivations never exercised are listed in the order of the grammar.
+#[derive(Debug, clap::Parser)]
+pub(super) struct CorpusStatsCommand {
+    /// The corpus directory of a campaign.
+    corpus: PathBuf,
+
+    #[clap(flatten)]
+    grammar_overrides: GrammarOverrides,
+
+    /// Only report these languages (comma-separated).
+    #[clap(long, value_delimiter = ',')]
+    languages: Vec<Language>,
+
+    /// The number of derivations never exercised listed for each language.
+    #[clap(long, default_value_t = 50)]
+    top: usize,
+}
+
+impl CorpusStatsCommand {
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "The counts are far smaller than 2^52"
+    )]
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let mut grammars: HashMap<Language, Grammar> = HashMap::new();
+        let mut documents: HashMap<Language, usize> = HashMap::new();
+        let mut coverage = GrammarCoverage::default();
+        for file in corpus_files(&self.corpus)? {
+            let input = match LspInput::from_file(&file) {
+                Ok(input) => input,
+                Err(err) => {
+                    warn!(file = %file.display(), "Skipping input: {err}");
+                    continue;
+                }
+            };
+            for (_, entry) in input.workspace.iter_files() {
+                let Some(doc) = entry.as_source_file() else {
+                    continue;
+                };
+                let language = doc.language();
+                if !self.languages.is_empty() && !self.languages.contains(&language) {
+                    continue;
+                }
+                let grammar = match grammars.entry(language) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let grammar_json = self.grammar_overrides.grammar_json(language)?;
+                        let grammar =
+                            Grammar::from_tree_sitter_grammar_json(language, &grammar_json)
+                                .with_context(|| format!("Loading the grammar of {language}"))?;
+                        entry.insert(grammar)
+                    }
+                };
+                coverage.record(grammar, doc);
+                *documents.entry(language).or_default() += 1;
+            }
+        }
+
+        let mut languages: Vec<_> = grammars.keys().copied().collect();
+        languages.sort_by_key(ToString::to_string);
+        for language in languages {
+            let grammar = &grammars[&language];
+            let total = visible_rules(grammar).count();
+            let uncovered = coverage.uncovered(grammar);
+            let covered = total - uncovered.len();
+            println!(
+                "{language}: {covered} of {total} derivations exercised by {} documents ({:.1}%)",
+                documents[&language],
+                100.0 * covered as f64 / total.max(1) as f64
+            );
+            for (symbol, derivation) in uncovered.iter().take(self.top) {
+                println!("  <{symbol}> ::= {derivation}");
+            }
+            if uncovered.len() > self.top {
+                println!("  ... {} more derivations", uncovered.len() - self.top);
+            }
+        }
+        Ok(())
+    }
+}
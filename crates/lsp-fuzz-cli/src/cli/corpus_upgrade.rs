@@ -0,0 +1,71 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use lsp_fuzz::lsp_input::format::{self, FORMAT_VERSION};
+use tracing::{info, warn};
+
+use super::{GlobalOptions, corpus_files};
+
+/// Rewrites the inputs of corpus directories in the current format.
+///
+/// Inputs written by older versions of the fuzzer are still loaded, but upgrading them once
+/// saves the migration on every load.
+#[derive(Debug, clap::Parser)]
+pub(super) struct CorpusUpgradeCommand {
+    /// The corpus directories, e.g., the `corpus` and `solutions` directories of a state directory.
+    #[clap(required = true)]
+    corpus_dirs: Vec<PathBuf>,
+
+    /// Only report the versions of the inputs without rewriting them.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+impl CorpusUpgradeCommand {
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let (mut upgraded, mut current, mut failed) = (0, 0, 0);
+        for corpus_dir in &self.corpus_dirs {
+            for input_file in corpus_files(corpus_dir)? {
+                let file = File::open(&input_file).context("Opening input file")?;
+                let (input, version) = match format::read_input(BufReader::new(file)) {
+                    Ok(it) => it,
+                    Err(err) => {
+                        warn!(file = %input_file.display(), "Failed to load input: {err}");
+                        failed += 1;
+                        continue;
+                    }
+                };
+                if version == FORMAT_VERSION {
+                    current += 1;
+                    continue;
+                }
+                info!(file = %input_file.display(), version, "Upgrading input");
+                upgraded += 1;
+                if self.dry_run {
+                    continue;
+                }
+                // Write to a hidden temporary file first, so that an interrupted upgrade
+                // leaves neither a truncated input nor an extra entry in the corpus.
+                let file_name = input_file.file_name().unwrap_or_default().to_string_lossy();
+                let temp_file = input_file.with_file_name(format!(".{file_name}.upgrading"));
+                let writer = BufWriter::new(
+                    File::create(&temp_file).context("Creating upgraded input file")?,
+                );
+                format::write_input(&input, writer).context("Writing upgraded input")?;
+                fs::rename(&temp_file, &input_file).context("Replacing input file")?;
+            }
+        }
+        info!(
+            upgraded,
+            current,
+            failed,
+            version = FORMAT_VERSION,
+            "Corpus upgrade completed"
+        );
+        Ok(())
+    }
+}
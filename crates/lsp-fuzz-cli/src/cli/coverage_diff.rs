@@ -0,0 +1,190 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use itertools::Itertools;
+use libafl::{
+    ExecutesInput, NopInputFilter, StdFuzzerBuilder,
+    corpus::InMemoryCorpus,
+    events::NopEventManager,
+    executors::HasObservers,
+    feedbacks::ConstFeedback,
+    inputs::Input,
+    observers::{MapObserver, StdMapObserver},
+    schedulers::QueueScheduler,
+    state::StdState,
+};
+use libafl_bolts::{
+    AsSliceMut, HasLen,
+    rands::StdRand,
+    shmem::{ShMem, ShMemProvider, StdShMemProvider},
+    tuples::Handled,
+};
+use lsp_fuzz::{
+    campaign::INPUT_SHM_SIZE,
+    execution::{
+        FuzzExecutionConfig, FuzzInput, LspExecutor, responses::LspOutputObserver,
+        workspace_observer::WorkspaceObserver,
+    },
+    fuzz_target,
+    lsp_input::{LspInput, LspInputBytesConverter},
+};
+use serde::Serialize;
+use tracing::info;
+use tuple_list::tuple_list;
+
+use super::{GlobalOptions, corpus_files};
+use crate::fuzzing::{ExecutorOptions, common};
+
+/// Replays two corpora and compares the coverage map edges they reach.
+///
+/// Edges are reported as indices into the coverage map of the target,
+/// so both corpora must be replayed against the same build.
+#[derive(Debug, clap::Parser)]
+pub(super) struct CoverageDiffCommand {
+    /// The corpus directory to compare, e.g., from a campaign with a new mutation strategy.
+    #[clap(long)]
+    corpus_a: PathBuf,
+
+    /// The baseline corpus directory.
+    #[clap(long)]
+    corpus_b: PathBuf,
+
+    #[clap(flatten)]
+    execution: ExecutorOptions,
+
+    /// The path to the temporary directory.
+    #[clap(long, env = "AFL_TMPDIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Write the edges covered by only one of the corpora to this file as JSON.
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct CoverageDiff {
+    edges_a: usize,
+    edges_b: usize,
+    common: usize,
+    only_a: Vec<usize>,
+    only_b: Vec<usize>,
+}
+
+impl CoverageDiffCommand {
+    pub(super) fn run(self, global_options: GlobalOptions) -> anyhow::Result<()> {
+        let mut shmem_provider =
+            StdShMemProvider::new().context("Creating shared memory provider")?;
+        let binary_file =
+            fs::read(&self.execution.lsp_executable).context("Reading fuzz target")?;
+        let binary_info = common::analyze_fuzz_target(&binary_file)?;
+        let map_size = match self.execution.coverage_map_size {
+            Some(map_size) => map_size,
+            None => fuzz_target::dump_map_size(&self.execution.lsp_executable)
+                .context("Dumping map size")?,
+        };
+        let mut coverage_shmem = shmem_provider
+            .new_shmem(map_size)
+            .context("Creating shared memory")?;
+        let coverage_map_shmem_id = coverage_shmem.id();
+        let coverage_map_observer = {
+            let shmem_buf = coverage_shmem.as_slice_mut();
+            // SAFETY: We never move the piece of the shared memory.
+            unsafe { StdMapObserver::new("edges", shmem_buf) }
+        };
+        let coverage_handle = coverage_map_observer.handle();
+        let temp_dir = self.temp_dir.unwrap_or_else(std::env::temp_dir);
+        let instance_id = std::process::id().to_string();
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let random_seed = global_options
+            .random_seed
+            .unwrap_or_else(libafl_bolts::current_nanos);
+        let mut state = StdState::new(
+            StdRand::with_seed(random_seed),
+            InMemoryCorpus::<LspInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .context("Creating state")?;
+        let mut fuzzer = StdFuzzerBuilder::new()
+            .input_filter(NopInputFilter)
+            .target_bytes_converter(LspInputBytesConverter::new(
+                temp_dir.clone(),
+                instance_id.clone(),
+            ))
+            .scheduler(QueueScheduler::new())
+            .feedback(feedback)
+            .objective(objective)
+            .build();
+        let mut event_manager = NopEventManager::new();
+
+        let mut executor = {
+            let test_case_shmem = shmem_provider
+                .new_shmem(INPUT_SHM_SIZE)
+                .context("Creating shared memory for test case passing")?;
+            let mut target_info = common::create_target_info(&self.execution, &binary_info);
+            if let Some(container) = target_info.container.as_mut() {
+                container.mounts.push(temp_dir.clone());
+            }
+            let exec_config = FuzzExecutionConfig {
+                debug_child: self.execution.debug_child,
+                debug_afl: self.execution.debug_afl,
+                fuzz_input: FuzzInput::SharedMemory(test_case_shmem),
+                auto_tokens: None,
+                coverage_shm_info: (coverage_map_shmem_id, coverage_map_observer.len()),
+                map_observer: coverage_map_observer,
+                responses_observer: LspOutputObserver::new(),
+                asan_observer: None,
+                subprocess_observer: None,
+                lifecycle_observer: None,
+                other_observers: tuple_list![WorkspaceObserver::new(temp_dir, instance_id)],
+                companion: None,
+            };
+            LspExecutor::start(target_info, exec_config).context("Starting executor")?
+        };
+
+        let mut replay = |corpus_dir: &Path| -> anyhow::Result<BTreeSet<usize>> {
+            let mut edges = BTreeSet::new();
+            for input_file in corpus_files(corpus_dir)? {
+                let input = LspInput::from_file(&input_file)
+                    .with_context(|| format!("Loading input file: {}", input_file.display()))?;
+                fuzzer
+                    .execute_input(&mut state, &mut executor, &mut event_manager, &input)
+                    .with_context(|| format!("Replaying {}", input_file.display()))?;
+                let map = executor.observers()[&coverage_handle].to_vec();
+                edges.extend(map.iter().positions(|&hits| hits != 0));
+            }
+            info!(corpus = %corpus_dir.display(), edges = edges.len(), "Replayed corpus");
+            Ok(edges)
+        };
+        let edges_a = replay(&self.corpus_a).context("Replaying corpus A")?;
+        let edges_b = replay(&self.corpus_b).context("Replaying corpus B")?;
+
+        let diff = CoverageDiff {
+            edges_a: edges_a.len(),
+            edges_b: edges_b.len(),
+            common: edges_a.intersection(&edges_b).count(),
+            only_a: edges_a.difference(&edges_b).copied().collect(),
+            only_b: edges_b.difference(&edges_a).copied().collect(),
+        };
+        info!(
+            edges_a = diff.edges_a,
+            edges_b = diff.edges_b,
+            common = diff.common,
+            only_a = diff.only_a.len(),
+            only_b = diff.only_b.len(),
+            "Coverage difference"
+        );
+        if let Some(output) = self.output {
+            let output_file = File::create(&output).context("Creating output file")?;
+            serde_json::to_writer_pretty(output_file, &diff).context("Writing output file")?;
+        }
+        Ok(())
+    }
+}
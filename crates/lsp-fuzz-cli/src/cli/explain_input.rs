@@ -0,0 +1,239 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+use libafl::inputs::Input;
+use lsp_fuzz::{execution::workspace_observer::HasWorkspace, lsp_input::LspInput};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::{GlobalOptions, corpus_files, reproduce::TargetOptions};
+use crate::fuzzing::{FuzzerStateDir, TargetInvocation};
+
+/// Explains what code an input reaches that the rest of the corpus does not.
+///
+/// The input and the corpus are replayed against a build of the target instrumented for
+/// source-based coverage (`-fprofile-instr-generate -fcoverage-mapping`),
+/// and the functions covered only by the input are listed with their files.
+#[derive(Debug, clap::Parser)]
+pub(super) struct ExplainInputCommand {
+    /// The path to the input file.
+    #[clap(long, short)]
+    input_file: PathBuf,
+
+    /// The corpus the input is compared against. Defaults to the directory of the input.
+    #[clap(long)]
+    corpus: Option<PathBuf>,
+
+    /// The target, which must be built with source-based coverage.
+    /// The arguments and environment variables recorded by the fuzzer are reused if not given.
+    #[clap(flatten)]
+    target: TargetOptions,
+
+    /// Seconds to wait for the target to exit after its input is closed.
+    #[clap(long, default_value_t = 30)]
+    timeout: u64,
+
+    /// The `llvm-profdata` executable matching the compiler of the target.
+    #[clap(long, default_value = "llvm-profdata")]
+    llvm_profdata: PathBuf,
+
+    /// The `llvm-cov` executable matching the compiler of the target.
+    #[clap(long, default_value = "llvm-cov")]
+    llvm_cov: PathBuf,
+}
+
+impl ExplainInputCommand {
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let corpus_dir = match self.corpus {
+            Some(corpus) => corpus,
+            None => self
+                .input_file
+                .parent()
+                .context("The input file has no parent directory")?
+                .to_owned(),
+        };
+        let state_dir = FuzzerStateDir::of_solution_dir(&corpus_dir);
+        let target = self.target.resolve(state_dir).context("Resolving target")?;
+        let timeout = Duration::from_secs(self.timeout);
+        let profile_dir = tempfile::tempdir().context("Creating profile directory")?;
+
+        let input_file = fs::canonicalize(&self.input_file).context("Resolving input file")?;
+        let mut corpus_profiles = Vec::new();
+        for (idx, corpus_file) in corpus_files(&corpus_dir)?.into_iter().enumerate() {
+            if fs::canonicalize(&corpus_file).is_ok_and(|it| it == input_file) {
+                continue;
+            }
+            let profile = profile_dir.path().join(format!("corpus_{idx}.profraw"));
+            replay_with_profile(&target, &corpus_file, &profile, timeout)
+                .with_context(|| format!("Replaying {}", corpus_file.display()))?;
+            corpus_profiles.push(profile);
+        }
+        // Targets killed by a crash or a timeout may not write their profiles.
+        corpus_profiles.retain(|it| it.exists());
+        info!(inputs = corpus_profiles.len(), "Replayed corpus");
+        let input_profile = profile_dir.path().join("input.profraw");
+        replay_with_profile(&target, &input_file, &input_profile, timeout)
+            .context("Replaying input")?;
+        if !input_profile.exists() {
+            bail!("The target wrote no coverage profile, is it built with source-based coverage?");
+        }
+
+        let tools = LlvmTools {
+            profdata: &self.llvm_profdata,
+            cov: &self.llvm_cov,
+            binary: &target.executable,
+        };
+        let corpus_profdata = profile_dir.path().join("corpus.profdata");
+        let input_profdata = profile_dir.path().join("input.profdata");
+        let corpus_functions = if corpus_profiles.is_empty() {
+            BTreeSet::new()
+        } else {
+            tools.merge(&corpus_profiles, &corpus_profdata)?;
+            tools.covered_functions(&corpus_profdata)?
+        };
+        tools.merge(&[input_profile], &input_profdata)?;
+        let input_functions = tools.covered_functions(&input_profdata)?;
+
+        let newly_covered: Vec<_> = input_functions.difference(&corpus_functions).collect();
+        if newly_covered.is_empty() {
+            println!("The input covers no function beyond the rest of the corpus.");
+            return Ok(());
+        }
+        let files: BTreeSet<_> = newly_covered.iter().map(|(file, _)| file).collect();
+        println!(
+            "The input newly covers {} function(s) in {} file(s):",
+            newly_covered.len(),
+            files.len()
+        );
+        for file in files {
+            println!("{file}");
+            for (_, function) in newly_covered.iter().filter(|(it, _)| it == file) {
+                println!("    {function}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the target on the input at `input_file`, writing its coverage profile to `profile`.
+///
+/// The whole session is written to the target at once, and the target is killed if it does not
+/// exit within `timeout` after its input is closed.
+fn replay_with_profile(
+    target: &TargetInvocation,
+    input_file: &Path,
+    profile: &Path,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let input = LspInput::from_file(input_file).context("Loading input file")?;
+    let workspace = tempfile::tempdir().context("Creating workspace directory")?;
+    input
+        .setup_workspace(workspace.path())
+        .context("Setting up workspace")?;
+    let mut child = target
+        .command(workspace.path(), [("LLVM_PROFILE_FILE", profile)])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Starting target process")?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("Child should have its stdin piped")?;
+    match stdin.write_all(&input.request_bytes(workspace.path())) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+        Err(e) => Err(e).context("Sending messages to target")?,
+    }
+    drop(stdin);
+
+    let deadline = Instant::now() + timeout;
+    while child.try_wait().context("Waiting for target")?.is_none() {
+        if Instant::now() >= deadline {
+            warn!(input = %input_file.display(), "Target timed out, its profile may be incomplete");
+            child.kill().context("Killing target")?;
+            child.wait().context("Waiting for target")?;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+struct LlvmTools<'a> {
+    profdata: &'a Path,
+    cov: &'a Path,
+    binary: &'a Path,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoverageExport {
+    data: Vec<CoverageExportData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoverageExportData {
+    functions: Vec<CoveredFunction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoveredFunction {
+    name: String,
+    count: u64,
+    filenames: Vec<String>,
+}
+
+impl LlvmTools<'_> {
+    fn merge(&self, profiles: &[PathBuf], output: &Path) -> anyhow::Result<()> {
+        let status = Command::new(self.profdata)
+            .arg("merge")
+            .arg("-sparse")
+            .args(profiles)
+            .arg("-o")
+            .arg(output)
+            .status()
+            .context("Running llvm-profdata")?;
+        if !status.success() {
+            bail!("llvm-profdata exited with {status}");
+        }
+        Ok(())
+    }
+
+    /// The `(file, function)` pairs executed at least once according to `profdata`.
+    fn covered_functions(&self, profdata: &Path) -> anyhow::Result<BTreeSet<(String, String)>> {
+        let output = Command::new(self.cov)
+            .arg("export")
+            .arg("-format=text")
+            .arg("-skip-expansions")
+            .arg("-instr-profile")
+            .arg(profdata)
+            .arg(self.binary)
+            .stderr(Stdio::inherit())
+            .output()
+            .context("Running llvm-cov")?;
+        if !output.status.success() {
+            bail!("llvm-cov exited with {}", output.status);
+        }
+        let export: CoverageExport =
+            serde_json::from_slice(&output.stdout).context("Parsing llvm-cov export")?;
+        let functions = export
+            .data
+            .into_iter()
+            .flat_map(|it| it.functions)
+            .filter(|it| it.count > 0)
+            .map(|it| {
+                let file = it.filenames.into_iter().next().unwrap_or_default();
+                (file, it.name)
+            })
+            .collect();
+        Ok(functions)
+    }
+}
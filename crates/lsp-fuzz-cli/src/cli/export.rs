@@ -6,7 +6,13 @@ use std::{
 
 use anyhow::Context;
 use libafl::inputs::Input;
-use lsp_fuzz::{execution::workspace_observer::HasWorkspace, lsp_input::LspInput};
+use lsp_fuzz::{
+    execution::workspace_observer::HasWorkspace,
+    lsp_input::{
+        LspInput,
+        messages::{SequenceElement, partial_payload},
+    },
+};
 use tracing::info;
 
 use super::GlobalOptions;
@@ -33,6 +39,7 @@ impl ExportCommand {
             .map(Result::unwrap)
             .filter(|it| {
                 it.metadata().is_ok_and(|it| it.is_file())
+                    && !it.file_name().to_string_lossy().starts_with('.')
                     && self
                         .input_prefix
                         .as_ref()
@@ -53,7 +60,7 @@ impl ExportCommand {
     }
 }
 
-fn export_input(input: &Path, output_dir: &Path) -> Result<(), anyhow::Error> {
+pub(super) fn export_input(input: &Path, output_dir: &Path) -> Result<(), anyhow::Error> {
     let input = LspInput::from_file(input).context("Deserializing input")?;
     if fs::exists(output_dir).context("Checking workspace directory")? {
         fs::remove_dir_all(output_dir).context("Removing workspace directory")?;
@@ -67,13 +74,27 @@ fn export_input(input: &Path, output_dir: &Path) -> Result<(), anyhow::Error> {
     let requests_dir = output_dir.join("requests");
     fs::create_dir_all(&requests_dir).context("Creating requests dir")?;
     let mut id = 0;
-    for (idx, message) in input.message_sequence().enumerate() {
+    let payloads = input
+        .session_elements()
+        .filter_map(|element| match element {
+            SequenceElement::Message(message) => Some((message, None)),
+            SequenceElement::Close { partial: None } => None,
+            SequenceElement::Close {
+                partial: Some((message, len)),
+            } => Some((message, Some(len))),
+        });
+    for (idx, (message, partial)) in payloads.enumerate() {
         let message_file = requests_dir.join(format!("message_{idx:0>5}"));
         let json_msg = message.into_json_rpc(&mut id, Some(&workspace_url));
+        let payload = json_msg.to_lsp_payload_with(input.messages.header_line_ending(idx));
+        let payload = match partial {
+            Some(len) => partial_payload(&payload, len),
+            None => &payload,
+        };
         let message_file = File::create(message_file).context("Creating message file")?;
         let mut writer = BufWriter::new(message_file);
         writer
-            .write_all(json_msg.to_lsp_payload().as_ref())
+            .write_all(payload)
             .context("Writing to message file")?;
     }
     Ok(())
@@ -1,66 +1,50 @@
 use std::{
     collections::HashMap,
-    fs::{File, OpenOptions},
-    io::BufWriter,
+    fs::{self, File},
     ops::Not,
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use clap::builder::BoolishValueParser;
-use libafl::{
-    Fuzzer, NopInputFilter, StdFuzzerBuilder,
-    corpus::Corpus,
-    events::SimpleEventManager,
-    feedback_or,
-    feedbacks::{MaxMapFeedback, TimeFeedback},
-    monitors::SimpleMonitor,
-    mutators::HavocScheduledMutator,
-    observers::{
-        AsanBacktraceObserver, CanTrack, HitcountsMapObserver, StdMapObserver, TimeObserver,
-    },
-    schedulers::powersched::BaseSchedule,
-    stages::{CalibrationStage, StdPowerMutationalStage},
-    state::{HasCorpus, StdState},
-};
-use libafl_bolts::{
-    AsSliceMut, HasLen,
-    rands::StdRand,
-    shmem::{ShMem, ShMemProvider, StdShMemProvider},
-};
+use libafl::schedulers::powersched::BaseSchedule;
+use libafl_bolts::shmem::{ShMem, ShMemProvider, StdShMemProvider};
 use lsp_fuzz::{
-    corpus::{TestCaseFileNameFeedback, corpus_kind::CORPUS},
+    campaign::{CampaignBuilder, Companion, INPUT_SHM_SIZE},
     execution::{
-        FuzzExecutionConfig, FuzzInput, LspExecutor, responses::LspOutputObserver,
-        workspace_observer::WorkspaceObserver,
+        self, FuzzInput,
+        side_channel::{ClangdLogParser, LinePrefixParser, SideChannelParser},
     },
     fuzz_target,
     lsp::GeneratorsConfig,
     lsp_input::{
-        LspInputBytesConverter, LspInputGenerator, LspInputMutator, messages::message_mutations,
-        server_response::LspResponseFeedback,
+        WorkspaceTemplate,
+        configuration::ConfigurationMatrix,
+        experimental_capabilities::{ExperimentalCapabilitiesGen, builtin_profiles},
+    },
+    stages::DiskLimits,
+    text_document::{
+        diversity::DEFAULT_SEED_SIMILARITY,
+        generation::{GrammarContextLookup, RuleWeighting, WeightedRuleSelectionStrategy},
+        mutations::node_filters::NodeWeighting,
     },
-    stages::{StatsStage, TimeoutStopStage},
-    text_document::text_document_mutations,
-    utf8::UTF8Tokens,
 };
 use lsp_fuzz_grammars::Language;
 use memmap2::Mmap;
-use tracing::info;
-use tuple_list::tuple_list;
+use tracing::{info, warn};
 
-use super::{GlobalOptions, parse_hash_map};
+use super::{GlobalOptions, parse_duration, parse_hash_map, parse_size};
 use crate::{
     fuzzing::{
-        ExecutorOptions, FuzzerStateDir,
+        CompanionInvocation, ExecutorOptions, FuzzerStateDir, NegotiatedMapSize, TargetInvocation,
         common::{self},
     },
-    language_fragments::load_grammar_lookup,
+    language_fragments::{
+        GrammarOverrides, load_cached_grammar_lookup, load_grammar_lookup, mine_grammar_context,
+    },
 };
 
-const INPUT_SHM_SIZE: usize = 15 * 1024 * 1024 * 1024;
-
 /// Fuzz a Language Server Protocol (LSP) server.
 #[derive(Debug, clap::Parser)]
 pub(super) struct FuzzCommand {
@@ -83,6 +67,26 @@ pub(super) struct FuzzCommand {
     #[clap(long, env = "AFL_TMPDIR")]
     temp_dir: Option<PathBuf>,
 
+    /// Identifier of this fuzzer instance in the names of its workspace directories,
+    /// so that parallel instances can share the temporary directory.
+    /// Defaults to the process ID.
+    #[clap(long)]
+    instance_id: Option<String>,
+
+    /// Throttle the campaign while the free space on the file system of the state
+    /// or the temporary directory is below this size.
+    #[clap(long, value_parser = parse_size, default_value = "1G")]
+    min_free_disk: usize,
+
+    /// Throttle the campaign while the state and the temporary directories take more than
+    /// this size in total.
+    #[clap(long, value_parser = parse_size)]
+    max_disk_usage: Option<usize>,
+
+    /// Seconds after which workspace directories left behind by failed executions are removed.
+    #[clap(long, default_value_t = 600)]
+    workspace_max_age: u64,
+
     /// Power schedule to use for fuzzing.
     #[clap(long, short, value_enum, default_value_t = BaseSchedule::FAST)]
     power_schedule: BaseSchedule,
@@ -96,213 +100,614 @@ pub(super) struct FuzzCommand {
     cpu_affinity: Option<usize>,
 
     /// Stop fuzzing after a certain number of hours.
+    #[clap(long, required_unless_present_any = ["max_duration", "max_execs"])]
+    time_budget: Option<u64>,
+
+    /// Stop fuzzing after a duration, in seconds or with a unit suffix (e.g., `30m` or `2h`).
+    #[clap(long, value_parser = parse_duration, conflicts_with = "time_budget")]
+    max_duration: Option<Duration>,
+
+    /// Switch to structure-agnostic mutations (byte havoc on documents, framing variations,
+    /// and message shuffles) when the coverage has not grown for this long,
+    /// in seconds or with a unit suffix (e.g., `30m`).
+    #[clap(long, value_parser = parse_duration)]
+    stagnation_fallback: Option<Duration>,
+
+    /// How long the structure-agnostic mutations run before the regular ones resume.
+    #[clap(long, value_parser = parse_duration, default_value = "10m")]
+    stagnation_fallback_duration: Duration,
+
+    /// Evaluate freshly generated inputs every this many executions, so that the campaign
+    /// keeps reaching constructs its corpus has drifted away from. Disabled if not given.
     #[clap(long)]
-    time_budget: u64,
+    regeneration_interval: Option<u64>,
+
+    /// The number of inputs generated every `--regeneration-interval` executions.
+    #[clap(long, default_value_t = 8)]
+    regeneration_batch: usize,
+
+    /// Stop fuzzing after a certain number of executions.
+    #[clap(long)]
+    max_execs: Option<u64>,
+
+    /// Exit with a non-zero status if the campaign found any solution, e.g., a crash, a hang,
+    /// or a conformance violation, so that bounded campaigns can serve as regression gates.
+    #[clap(long)]
+    fail_on_crash: bool,
 
     #[clap(long)]
     no_asan: bool,
 
-    #[clap(long, value_parser = parse_hash_map::<Language, PathBuf>)]
+    /// Save inputs on which the target breaks LSP framing or JSON-RPC discipline as solutions.
+    #[clap(long)]
+    conformance_checks: bool,
+
+    /// Save inputs after which the target leaves malformed index artifacts, i.e., LSIF dumps
+    /// and JSON index caches it creates or modifies in the workspace, as solutions.
+    #[clap(long)]
+    validate_index_artifacts: bool,
+
+    /// Check the positions of the messages after each mutation of a document against positions
+    /// derived from the edited bytes, and record divergences to `calibration_divergences.jsonl`
+    /// in the state directory. Always enabled in debug builds.
+    #[clap(long)]
+    check_calibration: bool,
+
+    /// Log the full notifications and requests of the server with this method,
+    /// e.g., `textDocument/publishDiagnostics`. Can be given several times.
+    #[clap(long, value_name = "METHOD")]
+    log_notifications: Vec<String>,
+
+    /// Parse the output of the target outside the LSP payloads with a built-in side-channel
+    /// parser. Unrecognized output is attributed to the `unknown` channel.
+    #[clap(long, value_enum)]
+    side_channel: Vec<SideChannelArg>,
+
+    /// Attribute the lines of output outside the LSP payloads starting with a prefix to a
+    /// side channel, as comma-separated `channel=prefix` pairs.
+    #[clap(long, value_parser = parse_hash_map::<String, String>, default_value = "")]
+    side_channel_prefix: HashMap<String, String>,
+
+    /// Log the lines of output outside the LSP payloads of this side channel,
+    /// e.g., `clangd-log` or `unknown`. Can be given several times.
+    #[clap(long, value_name = "CHANNEL")]
+    log_side_channel: Vec<String>,
+
+    /// Record corpus additions, solutions, and stats samples to a SQLite database.
+    #[clap(long)]
+    sqlite_db: Option<PathBuf>,
+
+    /// Seconds between two stats samples recorded to the SQLite database.
+    #[clap(long, default_value_t = 60)]
+    sqlite_sample_interval: u64,
+
+    /// Write campaign events (new corpus entries, new solutions, stats samples,
+    /// and phase changes) to a file as JSON lines.
+    #[clap(long)]
+    events_jsonl: Option<PathBuf>,
+
+    /// Seconds between two stats samples written to the event stream.
+    #[clap(long, default_value_t = 60)]
+    events_sample_interval: u64,
+
+    /// Write each new corpus entry with its parent, the mutation operators applied,
+    /// the edges it added, and the methods it contains to a file as JSON lines.
+    /// Summarize the file with the `attribution-report` command.
+    #[clap(long)]
+    attribution_log: Option<PathBuf>,
+
+    /// URL receiving a JSON notification on each new solution and when the campaign stops.
+    #[clap(long, env = "LSP_FUZZ_WEBHOOK_URL")]
+    webhook_url: Option<String>,
+
+    /// Name of the campaign reported in webhook notifications.
+    /// Defaults to the name of the state directory.
+    #[clap(long)]
+    campaign_name: Option<String>,
+
+    /// Fraction of the requests whose positions are sent in UTF-16 code units when UTF-8 is
+    /// negotiated, or in UTF-8 code units when UTF-16 is negotiated.
+    #[clap(long, default_value_t = 0.0)]
+    position_encoding_mismatch: f64,
+
+    /// Probability of opening a source file as another language, by sending the `languageId`
+    /// of another language or by adding a copy with the extension of another language.
+    #[clap(long, default_value_t = 0.0)]
+    language_mismatch: f64,
+
+    /// Probability of ending a session early by closing the input of the target, possibly in the
+    /// middle of a message, instead of sending `exit`.
+    #[clap(long, default_value_t = 0.0)]
+    transport_faults: f64,
+
+    /// Probability that an input advertises almost no client capabilities in `initialize`,
+    /// forcing the target down its fallback paths. One makes the whole campaign use them.
+    #[clap(long, default_value_t = 0.0)]
+    minimal_capabilities: f64,
+
+    /// Probability of replacing `capabilities.experimental` in `initialize` with the experimental
+    /// capabilities of a profile, a corrupted copy of them, or a random structure.
+    #[clap(long, default_value_t = 0.0)]
+    experimental_capabilities: f64,
+
+    /// A JSON file with the experimental client capabilities read by the target, e.g.,
+    /// those of rust-analyzer. Can be repeated. Those of well-known servers are used if not given.
+    #[clap(long)]
+    experimental_profile: Vec<PathBuf>,
+
+    /// Probability of appending a request for a method the server declares unsupported in its
+    /// `InitializeResult`. One appends requests regardless of the capabilities of the server.
+    #[clap(long, default_value_t = 0.1)]
+    unsupported_methods: f64,
+
+    /// Probability of building a pathological but valid structure in a document, e.g., deeply
+    /// nested expressions, thousands of parameters, or very long identifiers.
+    #[clap(long, default_value_t = 0.0)]
+    stress_patterns: f64,
+
+    /// Settings of the target toggled across inputs, sent as `initializationOptions` or
+    /// in `workspace/didChangeConfiguration`. Either the name of a built-in matrix
+    /// (`rust-analyzer`, `deno`, or `yaml-language-server`) or a JSON file with a `section`
+    /// and the values of each setting by its dotted path.
+    #[clap(long)]
+    configuration_matrix: Option<String>,
+
+    /// How the nodes replaced by mutations are chosen: `Uniform`, `SubtreeSize` (favor large
+    /// subtrees), `DepthBand` (equalize shallow and deep nodes), or `KindRarity` (favor rare
+    /// node kinds).
+    #[clap(long, default_value_t = NodeWeighting::Uniform)]
+    node_weighting: NodeWeighting,
+
+    /// Weights of the mutations of text documents by name (e.g., `ReplaceNode:ExpandGrammar=3`).
+    /// Unlisted mutations keep their default weights, and a weight of 0 disables a mutation.
+    /// Can be repeated.
+    #[clap(long, value_parser = parse_hash_map::<String, usize>)]
+    mutation_weight: Vec<HashMap<String, usize>>,
+
+    /// Choose the derivations of grammar rules by weight instead of uniformly when generating
+    /// code, favoring derivations with few non-terminals to avoid tiny or gigantic outputs.
+    #[clap(long)]
+    weighted_rules: bool,
+
+    /// A JSON file of the weights of the derivations of grammar rules by language, rule, and
+    /// derivation as displayed in the grammar,
+    /// e.g., `{"Rust": {"_expression": {"<binary_expression>": 4, "<unary_expression>": 0}}}`.
+    /// Derivations not listed keep their default weights. Implies `--weighted-rules`.
+    #[clap(long, conflicts_with = "coverage_guided_rules")]
+    rule_weights: Option<PathBuf>,
+
+    /// Choose the derivations of grammar rules by how often they produced corpus entries
+    /// when generating code, trying derivations not yet chosen first.
+    #[clap(long, conflicts_with = "weighted_rules")]
+    coverage_guided_rules: bool,
+
+    #[clap(long, value_parser = parse_hash_map::<Language, PathBuf>, default_value = "")]
     language_fragments: HashMap<Language, PathBuf>,
+
+    /// A file caching the grammars and fragments loaded from `--language-fragments`,
+    /// which is rebuilt when the grammars or the fragments change.
+    #[clap(long)]
+    grammar_cache: Option<PathBuf>,
+
+    /// A source file whose fragments, mined at startup, are the only fragments used
+    /// to generate documents, focusing the campaign on the constructs it contains.
+    /// Replaces `--language-fragments`.
+    #[clap(long, conflicts_with = "language_fragments")]
+    seed_file: Option<PathBuf>,
+
+    /// Merge the curated fragments shipped for languages whose mined fragments are rarely
+    /// accepted by servers, e.g., MLIR dialect operations and QML imports.
+    #[clap(long)]
+    seed_fragment_packs: bool,
+
+    #[clap(flatten)]
+    grammar_overrides: GrammarOverrides,
+
+    /// Relative weights of the languages of generated documents (e.g., `C=9,CPlusPlus=1`).
+    /// Languages not listed are not generated. All languages are equally likely if not given.
+    #[clap(long, value_parser = parse_hash_map::<Language, usize>, default_value = "")]
+    language_weights: HashMap<Language, usize>,
+
+    /// Regenerate seed documents whose node kinds are at least this similar to those of an
+    /// earlier seed (1 for the same number of nodes of each kind), so that the initial corpus
+    /// is not made of copies of the same trivial program. Values above 1 keep all documents.
+    #[clap(long, default_value_t = DEFAULT_SEED_SIMILARITY)]
+    seed_similarity: f64,
+
+    /// Keep the identifiers of generated documents as generated instead of rewriting them
+    /// to the identifiers declared in the document.
+    #[clap(long)]
+    no_identifier_resolution: bool,
+
+    /// A directory whose files are added to every generated workspace, at the same relative
+    /// paths, e.g., `.bsp/*.json` discovery files needed by the server at startup.
+    /// `{language_id}` and `{main_file}` in their content are replaced with the language
+    /// identifier and the path of the generated document.
+    #[clap(long)]
+    workspace_templates: Option<PathBuf>,
+
+    /// A second LSP server run against the same workspace as the target in each execution,
+    /// e.g., a CMake or TOML server next to a C server, to find bugs in how the servers react
+    /// to each other. Its coverage is merged into that of the target and its crashes are
+    /// reported as crashes of the input.
+    #[clap(long)]
+    companion_executable: Option<PathBuf>,
+
+    /// Arguments to pass to the companion server.
+    #[clap(long, requires = "companion_executable")]
+    companion_args: Vec<String>,
+
+    /// The file extensions or file names of the documents sent to the companion server
+    /// (e.g., `toml,CMakeLists.txt`). Messages about other documents are only sent to the target.
+    #[clap(long, value_delimiter = ',', requires = "companion_executable")]
+    companion_documents: Vec<String>,
 }
 
 impl FuzzCommand {
     #[allow(
         clippy::too_many_lines,
-        reason = "Need to put in one method for type inference"
+        reason = "Maps each option to the campaign builder"
     )]
     pub(super) fn run(self, global_options: GlobalOptions) -> Result<(), anyhow::Error> {
         self.state.create().context("Crating state dir")?;
-        let mut shmem_provider =
-            StdShMemProvider::new().context("Creating shared memory provider")?;
-
-        let binary_info = self.check_binary().context("Checking binary")?;
-        let map_size = fuzz_target::dump_map_size(&self.execution.lsp_executable)
-            .context("Dumping map size")?;
-        info!("Detected coverage map size: {}", map_size);
+        let mut invocation = TargetInvocation::from(&self.execution);
+        invocation.companion =
+            self.companion_executable
+                .clone()
+                .map(|executable| CompanionInvocation {
+                    executable,
+                    args: self.companion_args.clone(),
+                    documents: self.companion_documents.clone(),
+                });
+        invocation
+            .save(&self.state.target_file())
+            .context("Saving target invocation")?;
+
+        let binary_info =
+            check_binary(&self.execution.lsp_executable).context("Checking binary")?;
+        let map_size = self
+            .coverage_map_size(&binary_info)
+            .context("Negotiating coverage map size")?;
+        let companion = self
+            .companion_executable
+            .as_deref()
+            .map(|executable| self.companion(executable))
+            .transpose()
+            .context("Configuring companion server")?;
+
+        self.grammar_overrides
+            .apply_highlights()
+            .context("Overriding highlight queries")?;
+        let grammar_ctx = if let Some(seed_file) = &self.seed_file {
+            info!(seed_file = %seed_file.display(), "Mining fragments of seed file");
+            let grammar_ctx =
+                mine_grammar_context(seed_file, self.seed_fragment_packs, &self.grammar_overrides)
+                    .context("Mining seed file")?;
+            GrammarContextLookup::from_iter([grammar_ctx])
+        } else if self.language_fragments.is_empty() {
+            bail!("Either --language-fragments or --seed-file is required");
+        } else {
+            info!("Loading grammar context");
+            if let Some(cache) = &self.grammar_cache {
+                load_cached_grammar_lookup(
+                    &self.language_fragments,
+                    self.seed_fragment_packs,
+                    &self.grammar_overrides,
+                    cache,
+                )
+            } else {
+                load_grammar_lookup(
+                    &self.language_fragments,
+                    self.seed_fragment_packs,
+                    &self.grammar_overrides,
+                )
+            }
+            .context("Creating grammar context")?
+        };
+        for language in self.language_weights.keys() {
+            if grammar_ctx.get(*language).is_none() {
+                warn!(%language, "Weighted language has no fragments and is not generated");
+            }
+        }
 
-        let mut coverage_shmem = shmem_provider
-            .new_shmem(map_size)
-            .context("Creating shared memory")?;
-        let coverage_map_shmem_id = coverage_shmem.id();
+        let generators_config = self.generators_config().context("Configuring generators")?;
+        if let RuleWeighting::Weighted(strategy) = &generators_config.rule_weighting {
+            for (language, rule, derivation) in strategy.unmatched_overrides(&grammar_ctx) {
+                warn!(%language, rule, derivation, "Weighted derivation is not in the grammar");
+            }
+        }
+        let workspace_templates = match &self.workspace_templates {
+            Some(dir) => load_workspace_templates(dir).context("Loading workspace templates")?,
+            None => Vec::new(),
+        };
+        let target_info = common::create_target_info(&self.execution, &binary_info);
+        let temp_dir = self.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let instance_id = self
+            .instance_id
+            .clone()
+            .unwrap_or_else(|| std::process::id().to_string());
+        let time_budget = self
+            .max_duration
+            .or(self.time_budget.map(Duration::from_hours))
+            .unwrap_or(Duration::MAX);
+        let asan_enabled = binary_info.uses_address_sanitizer && self.no_asan.not();
 
-        info!("Loading grammar context");
-        let grammar_ctx =
-            load_grammar_lookup(&self.language_fragments).context("Creating grammar context")?;
+        let mut campaign = CampaignBuilder::new(target_info, &grammar_ctx, self.state.path())
+            .with_generators_config(generators_config)
+            .with_language_weights(self.language_weights.clone())
+            .with_workspace_templates(workspace_templates)
+            .with_seed_similarity(Some(self.seed_similarity))
+            .with_coverage_map_size(map_size)
+            .with_workspace_dir(temp_dir, instance_id)
+            .with_workspace_max_age(Duration::from_secs(self.workspace_max_age))
+            .with_disk_limits(DiskLimits {
+                min_free_bytes: self.min_free_disk as u64,
+                max_total_bytes: self.max_disk_usage.map(|it| it as u64),
+            })
+            .with_seeds(self.generate_seeds)
+            .with_power_schedule(self.power_schedule, self.cycle_power_schedule)
+            .with_asan(asan_enabled)
+            .with_subprocess_tracking(self.execution.track_subprocesses)
+            .with_conformance_checks(self.conformance_checks)
+            .with_index_validation(self.validate_index_artifacts)
+            .with_calibration_check(self.check_calibration)
+            .with_auto_tokens(self.no_auto_dict.not())
+            .with_debug_output(self.execution.debug_child, self.execution.debug_afl)
+            .with_side_channels(self.side_channel_parsers())
+            .with_logged_notifications(
+                self.log_notifications.clone(),
+                self.log_side_channel.clone(),
+            )
+            .with_stop_signal(common::stop_signal()?)
+            .with_time_budget(time_budget);
+        if let Some(max_executions) = self.max_execs {
+            campaign = campaign.with_max_executions(max_executions);
+        }
+        if let Some(random_seed) = global_options.random_seed {
+            campaign = campaign.with_random_seed(random_seed);
+        }
+        if let Some(interval) = self.regeneration_interval {
+            campaign = campaign.with_regeneration(interval, self.regeneration_batch);
+        }
+        if let Some(threshold) = self.stagnation_fallback {
+            campaign =
+                campaign.with_stagnation_fallback(threshold, self.stagnation_fallback_duration);
+        }
+        if let Some(companion) = companion {
+            campaign = campaign.with_companion(companion);
+        }
+        if let Some(path) = &self.attribution_log {
+            campaign = campaign.with_attribution_log(path.clone());
+        }
+        if let Some(path) = &self.events_jsonl {
+            campaign = campaign.with_event_stream(
+                path.clone(),
+                Duration::from_secs(self.events_sample_interval),
+            );
+        }
+        if let Some(path) = &self.sqlite_db {
+            campaign = campaign.with_database(
+                path.clone(),
+                Duration::from_secs(self.sqlite_sample_interval),
+            );
+        }
+        if let Some(url) = &self.webhook_url {
+            let campaign_name = self
+                .campaign_name
+                .clone()
+                .unwrap_or_else(|| self.state.name());
+            campaign = campaign.with_webhook(url.clone(), campaign_name);
+        }
 
-        let coverage_map_observer = {
-            let shmem_buf = coverage_shmem.as_slice_mut();
-            // SAFETY: We never move the piece of the shared memory.
-            unsafe { StdMapObserver::new("edges", shmem_buf) }
-        };
+        common::set_cpu_affinity(self.cpu_affinity);
 
-        let lsp_response_observer = LspOutputObserver::new();
-        let asan_observer = AsanBacktraceObserver::new("asan_stacktrace");
+        let summary = campaign
+            .run_with(|state, coverage_feedback_name| {
+                common::CampaignSummary::from_state(state, coverage_feedback_name)
+            })
+            .context("In fuzz loop")?
+            .context("Summarizing campaign")?;
+        summary
+            .report(&self.state.summary_file())
+            .context("Writing campaign summary")?;
+        info!("Fuzzing stopped. {} will now exit.", crate::PROGRAM_NAME);
+        let solutions = summary.solutions;
+        if self.fail_on_crash && solutions > 0 {
+            bail!(
+                "Found {solutions} solution(s), see {}",
+                self.state.solution_dir().display()
+            );
+        }
+        Ok(())
+    }
 
-        let asan_enabled = binary_info.uses_address_sanitizer && self.no_asan.not();
-        let cov_observer = HitcountsMapObserver::new(coverage_map_observer).track_indices();
-
-        // Create an observation channel to keep track of the execution time
-        let time_observer = TimeObserver::new("time");
-
-        let map_feedback = MaxMapFeedback::new(&cov_observer);
-        let calibration_stage = CalibrationStage::new(&map_feedback);
-        let stats_stage = {
-            let stats_writer = self
-                .create_stats_writer()
-                .context("Creating stats writer")?;
-            StatsStage::new(stats_writer, &map_feedback)
+    /// The generators configured by the options of the command.
+    fn generators_config(&self) -> Result<GeneratorsConfig, anyhow::Error> {
+        let mut generators_config = GeneratorsConfig::full();
+        generators_config.invalid_input.position_encoding_mismatch =
+            self.position_encoding_mismatch;
+        generators_config.invalid_input.language_mismatch = self.language_mismatch;
+        generators_config.invalid_input.transport_fault = self.transport_faults;
+        generators_config.minimal_capabilities = self.minimal_capabilities;
+        generators_config.resolve_identifiers = self.no_identifier_resolution.not();
+        generators_config.unsupported_methods = Some(self.unsupported_methods);
+        generators_config.stress_patterns = self.stress_patterns;
+        generators_config.configuration_matrix = self
+            .configuration_matrix
+            .as_deref()
+            .map(load_configuration_matrix)
+            .transpose()
+            .context("Loading configuration matrix")?;
+        if self.experimental_capabilities > 0.0 {
+            let profiles = load_experimental_profiles(&self.experimental_profile)
+                .context("Loading experimental capability profiles")?;
+            generators_config.experimental_capabilities = Some(ExperimentalCapabilitiesGen {
+                profiles,
+                probability: self.experimental_capabilities,
+            });
+        }
+        generators_config.node_weighting = self.node_weighting;
+        generators_config.rule_weighting = if self.coverage_guided_rules {
+            RuleWeighting::CoverageGuided
+        } else {
+            load_rule_weighting(self.weighted_rules, self.rule_weights.as_deref())
+                .context("Loading rule weights")?
         };
+        generators_config.mutation_weights = self
+            .mutation_weight
+            .iter()
+            .flatten()
+            .map(|(name, &weight)| (name.clone(), weight))
+            .collect();
+        Ok(generators_config)
+    }
 
-        let mut feedback = feedback_or!(
-            map_feedback,
-            LspResponseFeedback::new(&lsp_response_observer),
-            TestCaseFileNameFeedback::<CORPUS>::new(),
-            TimeFeedback::new(&time_observer)
-        );
-
-        let mut objective = common::objective(asan_enabled, &asan_observer);
-
-        let (corpus, solutions) =
-            common::create_corpus(&self.state.corpus_dir(), &self.state.solution_dir())
-                .context("Creating corpus")?;
-
-        let random_seed = global_options
-            .random_seed
-            .unwrap_or_else(libafl_bolts::current_nanos);
-        let rand = StdRand::with_seed(random_seed);
-        let mut state = StdState::new(rand, corpus, solutions, &mut feedback, &mut objective)
-            .context("Creating state")?;
-
-        let mut tokens = self.no_auto_dict.not().then(UTF8Tokens::new);
-
-        let scheduler = common::scheduler(
-            &mut state,
-            &cov_observer,
-            self.power_schedule,
-            self.cycle_power_schedule,
-        );
-        let temp_dir = self.temp_dir.unwrap_or_else(std::env::temp_dir);
-
-        // A fuzzer with feedback and a corpus scheduler
-        let mut fuzzer = StdFuzzerBuilder::new()
-            .input_filter(NopInputFilter)
-            .target_bytes_converter(LspInputBytesConverter::new(temp_dir.clone()))
-            .scheduler(scheduler)
-            .feedback(feedback)
-            .objective(objective)
-            .build();
-
-        let mut fuzz_stages = {
-            let mutation_stage = {
-                let generators_config = GeneratorsConfig::full();
-                let text_document_mutator = HavocScheduledMutator::with_max_stack_pow(
-                    text_document_mutations(&grammar_ctx, &generators_config),
-                    6,
-                );
-                let messages_mutator = HavocScheduledMutator::with_max_stack_pow(
-                    message_mutations(&generators_config),
-                    3,
-                );
-                let mutator = LspInputMutator::new(text_document_mutator, messages_mutator);
-                StdPowerMutationalStage::new(mutator)
-            };
-            let trigger_stop = common::trigger_stop_stage()?;
-            let timeout_stop = TimeoutStopStage::new(Duration::from_hours(self.time_budget));
-            tuple_list![
-                calibration_stage,
-                mutation_stage,
-                stats_stage,
-                timeout_stop,
-                trigger_stop,
-            ]
-        };
+    /// Determines the size of the coverage map, in order of precedence, from the command line,
+    /// from the size saved by an earlier run against the same binary, or by asking the target.
+    ///
+    /// The target is asked by dumping its map size and then running the fork server handshake
+    /// with a map of that size, which reports the size the target actually requires.
+    fn coverage_map_size(
+        &self,
+        binary_info: &fuzz_target::StaticTargetBinaryInfo,
+    ) -> Result<usize, anyhow::Error> {
+        if let Some(map_size) = self.execution.coverage_map_size {
+            return Ok(map_size);
+        }
+        let executable = &self.execution.lsp_executable;
+        let map_size_file = self.state.map_size_file();
+        if let Some(negotiated) = NegotiatedMapSize::load(&map_size_file)?
+            && negotiated.is_valid_for(executable)
+        {
+            info!("Using the coverage map size negotiated by an earlier run");
+            return Ok(negotiated.map_size);
+        }
 
-        let asan_observer = asan_enabled.then_some(asan_observer);
-        if asan_observer.is_some() {
-            info!("Crash stack hashing will be enabled");
+        let dumped = fuzz_target::dump_map_size(executable).context("Dumping map size")?;
+        let mut shmem_provider =
+            StdShMemProvider::new().context("Creating shared memory provider")?;
+        let fuzz_input = shmem_provider
+            .new_shmem(INPUT_SHM_SIZE)
+            .context("Creating shared memory for test case passing")?;
+        let probe_shmem = shmem_provider
+            .new_shmem(dumped)
+            .context("Creating shared memory")?;
+        let target_info = common::create_target_info(&self.execution, binary_info);
+        let reported = execution::probe_map_size(
+            &target_info,
+            &FuzzInput::SharedMemory(fuzz_input),
+            (probe_shmem.id(), probe_shmem.len()),
+        )
+        .context("Probing map size")?;
+        let map_size = reported.unwrap_or(dumped);
+        if map_size != dumped {
+            info!(
+                dumped,
+                reported = map_size,
+                "Using the map size reported by the fork server"
+            );
         }
-        let mut executor = {
-            let test_case_shmem = shmem_provider
-                .new_shmem(INPUT_SHM_SIZE)
-                .context("Creating shared memory for test case passing")?;
-            let fuzz_input = FuzzInput::SharedMemory(test_case_shmem);
-            let target_info = common::create_target_info(&self.execution, &binary_info);
-            let workspace_observer = WorkspaceObserver::new(temp_dir);
-            let exec_config = FuzzExecutionConfig {
-                debug_child: self.execution.debug_child,
-                debug_afl: self.execution.debug_afl,
-                fuzz_input,
-                auto_tokens: tokens.as_mut(),
-                coverage_shm_info: (coverage_map_shmem_id, cov_observer.as_ref().len()),
-                map_observer: cov_observer,
-                responses_observer: lsp_response_observer,
-                asan_observer,
-                other_observers: tuple_list![workspace_observer, time_observer],
-            };
-            LspExecutor::start(target_info, exec_config).context("Starting executor")?
-        };
+        NegotiatedMapSize::new(executable, map_size)
+            .save(&map_size_file)
+            .context("Saving negotiated map size")?;
+        Ok(map_size)
+    }
 
-        common::process_tokens(&mut state, tokens);
+    /// The companion server running `executable` with the execution options of the target.
+    fn companion(&self, executable: &Path) -> Result<Companion, anyhow::Error> {
+        let binary_info = check_binary(executable).context("Checking companion server")?;
+        let mut target = common::create_target_info(&self.execution, &binary_info);
+        target.path = executable.to_owned();
+        target.args.clone_from(&self.companion_args);
+        Ok(Companion {
+            target,
+            documents: self.companion_documents.clone(),
+        })
+    }
 
-        let mut event_manager = {
-            let monitor = SimpleMonitor::new(|it| info!("{}", it));
-            SimpleEventManager::new(monitor)
-        };
+    fn side_channel_parsers(&self) -> Vec<Box<dyn SideChannelParser>> {
+        let builtin = self.side_channel.iter().map(|it| match it {
+            SideChannelArg::ClangdLog => Box::new(ClangdLogParser) as Box<dyn SideChannelParser>,
+        });
+        let prefixes = self.side_channel_prefix.iter().map(|(channel, prefix)| {
+            Box::new(LinePrefixParser::new(channel.clone(), prefix.clone()))
+                as Box<dyn SideChannelParser>
+        });
+        builtin.chain(prefixes).collect()
+    }
+}
 
-        // In case the corpus is empty (on first run), reset
-        if state.must_load_initial_inputs() {
-            info!("Generating seeds");
-            let mut generator = LspInputGenerator::new(&grammar_ctx);
-            state
-                .generate_initial_inputs_forced(
-                    &mut fuzzer,
-                    &mut executor,
-                    &mut generator,
-                    &mut event_manager,
-                    self.generate_seeds,
-                )
-                .context("Generating initial input")?;
-            info!(seeds = %state.corpus().count(), "Seed generation completed");
-        }
+/// The built-in parsers of the output of the target outside the LSP payloads.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum SideChannelArg {
+    /// The log lines of clangd, e.g., `I[12:34:56.789] ...`.
+    ClangdLog,
+}
 
-        common::set_cpu_affinity(self.cpu_affinity);
+fn check_binary(executable: &Path) -> Result<fuzz_target::StaticTargetBinaryInfo, anyhow::Error> {
+    let binary_file = File::open(executable).context("Opening fuzz target")?;
+    // SAFETY: we are assuming that the file is not touched externally.
+    let binary_file = unsafe { Mmap::map(&binary_file) }.context("Mapping fuzz target")?;
+    common::analyze_fuzz_target(&binary_file)
+}
 
-        let fuzz_result = fuzzer.fuzz_loop(
-            &mut fuzz_stages,
-            &mut executor,
-            &mut state,
-            &mut event_manager,
-        );
-
-        match fuzz_result {
-            Ok(()) => unreachable!("The fuzz loop will never exit with Ok"),
-            Err(libafl::Error::ShuttingDown) => {
-                info!(
-                    "Stop requested by user. {} will now exit.",
-                    crate::PROGRAM_NAME
-                );
-                Ok(())
-            }
-            err @ Err(_) => err.context("In fuzz loop"),
+/// The files in `dir` as templates for the files at the same paths in workspaces.
+fn load_workspace_templates(dir: &Path) -> anyhow::Result<Vec<WorkspaceTemplate>> {
+    let mut templates = Vec::new();
+    for entry in walkdir::WalkDir::new(dir) {
+        let entry = entry.context("Listing workspace templates")?;
+        if !entry.file_type().is_file() {
+            continue;
         }
+        let path = entry
+            .path()
+            .strip_prefix(dir)
+            .context("Listing workspace templates")?
+            .to_str()
+            .context("The path of a workspace template is not valid UTF-8")?
+            .to_owned();
+        let content = fs::read_to_string(entry.path())
+            .with_context(|| format!("Reading workspace template {path}"))?;
+        templates.push(WorkspaceTemplate { path, content });
     }
+    info!(count = templates.len(), "Loaded workspace templates");
+    Ok(templates)
+}
 
-    fn check_binary(&self) -> Result<fuzz_target::StaticTargetBinaryInfo, anyhow::Error> {
-        let binary_file =
-            File::open(&self.execution.lsp_executable).context("Opening fuzz target")?;
-        // SAFETY: we are assuming that the file is not touched externally.
-        let binary_file = unsafe { Mmap::map(&binary_file) }.context("Mapping fuzz target")?;
-        common::analyze_fuzz_target(&binary_file)
+fn load_experimental_profiles(paths: &[PathBuf]) -> anyhow::Result<Vec<serde_json::Value>> {
+    if paths.is_empty() {
+        return Ok(builtin_profiles());
     }
+    paths
+        .iter()
+        .map(|path| {
+            let content =
+                fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+            serde_json::from_str(&content).with_context(|| format!("Parsing {}", path.display()))
+        })
+        .collect()
+}
 
-    fn create_stats_writer(&self) -> Result<BufWriter<File>, anyhow::Error> {
-        let stats_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(self.state.stats_file())
-            .context("Creating stats file")?;
-        Ok(BufWriter::new(stats_file))
+fn load_configuration_matrix(name_or_path: &str) -> anyhow::Result<ConfigurationMatrix> {
+    if let Some(matrix) = ConfigurationMatrix::builtin(name_or_path) {
+        return Ok(matrix);
     }
+    let content =
+        fs::read_to_string(name_or_path).with_context(|| format!("Reading {name_or_path}"))?;
+    serde_json::from_str(&content).with_context(|| format!("Parsing {name_or_path}"))
+}
+
+fn load_rule_weighting(weighted: bool, path: Option<&Path>) -> anyhow::Result<RuleWeighting> {
+    let Some(path) = path else {
+        return Ok(if weighted {
+            RuleWeighting::Weighted(WeightedRuleSelectionStrategy::default())
+        } else {
+            RuleWeighting::Uniform
+        });
+    };
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let strategy =
+        serde_json::from_str(&content).with_context(|| format!("Parsing {}", path.display()))?;
+    Ok(RuleWeighting::Weighted(strategy))
 }
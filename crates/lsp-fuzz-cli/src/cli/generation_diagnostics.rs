@@ -0,0 +1,118 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Context;
+use libafl::{
+    HasMetadata, corpus::InMemoryCorpus, feedbacks::ConstFeedback, generators::Generator,
+    state::StdState,
+};
+use libafl_bolts::rands::StdRand;
+use lsp_fuzz::{
+    lsp_input::{DEFAULT_RETRY_BUDGET, LspInput, LspInputGenerator},
+    text_document::generation::GenerationFailureStats,
+};
+use lsp_fuzz_grammars::Language;
+use tracing::warn;
+
+use super::{GlobalOptions, parse_hash_map};
+use crate::language_fragments::{GrammarOverrides, load_grammar_lookup};
+
+/// Generates documents from mined fragments and reports why derivations fail.
+///
+/// Each failure names the node kind that could not be derived: either the grammar has no rule
+/// for it and the corpus no fragment (`no rule`), or it reached the recursion limit or is a
+/// terminal without fragments (`no fragment`). Frequent kinds point at code the mined corpus
+/// is missing.
+#[derive(Debug, clap::Parser)]
+pub(super) struct GenerationDiagnosticsCommand {
+    #[clap(long, value_parser = parse_hash_map::<Language, PathBuf>)]
+    language_fragments: HashMap<Language, PathBuf>,
+
+    /// Merge the curated seed fragments of the languages, as `fuzz --seed-fragment-packs` does.
+    #[clap(long)]
+    seed_fragment_packs: bool,
+
+    #[clap(flatten)]
+    grammar_overrides: GrammarOverrides,
+
+    /// The number of documents to generate for each language.
+    #[clap(long, default_value_t = 100)]
+    documents: usize,
+
+    /// The number of derivations tried for each document.
+    #[clap(long, default_value_t = DEFAULT_RETRY_BUDGET)]
+    retry_budget: usize,
+
+    /// Regenerate documents at least this similar in shape to an earlier one, as
+    /// `fuzz --seed-similarity` does, and report how often that happened.
+    #[clap(long)]
+    seed_similarity: Option<f64>,
+
+    /// The number of node kinds listed for each language.
+    #[clap(long, default_value_t = 20)]
+    top: usize,
+}
+
+impl GenerationDiagnosticsCommand {
+    pub(super) fn run(self, global_options: GlobalOptions) -> anyhow::Result<()> {
+        self.grammar_overrides
+            .apply_highlights()
+            .context("Overriding highlight queries")?;
+        let grammar_lookup = load_grammar_lookup(
+            &self.language_fragments,
+            self.seed_fragment_packs,
+            &self.grammar_overrides,
+        )
+        .context("Creating grammar context")?;
+        let random_seed = global_options
+            .random_seed
+            .unwrap_or_else(libafl_bolts::current_nanos);
+
+        let mut languages: Vec<_> = self.language_fragments.keys().copied().collect();
+        languages.sort_by_key(ToString::to_string);
+        for language in languages {
+            let mut feedback = ConstFeedback::new(false);
+            let mut objective = ConstFeedback::new(false);
+            let mut state = StdState::new(
+                StdRand::with_seed(random_seed),
+                InMemoryCorpus::<LspInput>::new(),
+                InMemoryCorpus::new(),
+                &mut feedback,
+                &mut objective,
+            )
+            .context("Creating state")?;
+            let mut generator = LspInputGenerator::new(&grammar_lookup)
+                .with_language_weights(HashMap::from([(language, 1)]))
+                .with_retry_budget(self.retry_budget)
+                .with_seed_similarity(self.seed_similarity);
+            for _ in 0..self.documents {
+                if let Err(err) = generator.generate(&mut state) {
+                    warn!(%language, "{err}");
+                }
+            }
+
+            let stats = state.metadata_or_insert_with(GenerationFailureStats::default);
+            let failures = stats.failures();
+            println!(
+                "{language}: {} attempts for {} documents, {} abandoned, {} too similar",
+                stats.attempts, self.documents, stats.exhausted, stats.similar
+            );
+            if failures.is_empty() {
+                continue;
+            }
+            println!(
+                "  {:<40} {:>10} {:>12}",
+                "node kind", "no rule", "no fragment"
+            );
+            for (_, node_kind, counts) in failures.iter().take(self.top) {
+                println!(
+                    "  {node_kind:<40} {:>10} {:>12}",
+                    counts.no_rule, counts.no_fragment
+                );
+            }
+            if failures.len() > self.top {
+                println!("  ... {} more node kinds", failures.len() - self.top);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+};
+
+use anyhow::{Context, bail};
+use lsp_fuzz::lsp_input::{LspInput, format, import::ImportError};
+use tracing::{info, warn};
+
+use super::GlobalOptions;
+
+/// Imports a session recorded from a real editor as an input, e.g., as a seed.
+///
+/// The session is either a trace log of VS Code (recorded with
+/// `"<server>.trace.server": "verbose"`) or a raw byte stream captured from the standard input
+/// of a server. The documents opened by the editor become the workspace, and the requests
+/// sent afterwards become the messages.
+#[derive(Debug, clap::Parser)]
+pub(super) struct ImportSessionCommand {
+    /// The trace log or captured byte stream of the session.
+    session: PathBuf,
+
+    /// The file to write the input to, e.g., in the `corpus` directory of a state directory.
+    #[clap(long, short)]
+    output: PathBuf,
+}
+
+impl ImportSessionCommand {
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let session = fs::read(&self.session).context("Reading session")?;
+        let (input, report) = match LspInput::from_lsp_stream(&session) {
+            Err(ImportError::NotJsonRpc(_)) => {
+                let log = String::from_utf8_lossy(&session);
+                LspInput::from_trace_log(&log).context("Parsing trace log")?
+            }
+            imported => imported.context("Parsing byte stream")?,
+        };
+        if input.workspace.iter_files().next().is_none() {
+            bail!("The session opens no documents in the workspace");
+        }
+        if report.undecodable > 0 || report.foreign_documents > 0 || report.truncated {
+            warn!(
+                undecodable = report.undecodable,
+                foreign_documents = report.foreign_documents,
+                truncated = report.truncated,
+                "Parts of the session are left out"
+            );
+        }
+        let writer = BufWriter::new(File::create(&self.output).context("Creating output file")?);
+        format::write_input(&input, writer).context("Writing input")?;
+        info!(
+            documents = input.workspace.iter_files().count(),
+            messages = input.messages.len(),
+            applied_changes = report.applied_changes,
+            "Imported session"
+        );
+        Ok(())
+    }
+}
@@ -1,28 +1,35 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
-    fs::File,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
+    hash::{BuildHasher, RandomState},
     io::BufWriter,
     ops::Range,
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use anyhow::Context;
 use itertools::Itertools;
 use lsp_fuzz::text_document::{
     generation::DerivationFragments,
-    grammar::fragment_extraction::{self, extract_derivation_fragments},
+    grammar::{
+        Grammar,
+        fragment_extraction::{self, extract_language_fragments},
+    },
 };
 use lsp_fuzz_grammars::Language;
 use rayon::prelude::*;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 use super::GlobalOptions;
+use crate::language_fragments::GrammarOverrides;
 
 /// Extracts derivation fragments from a set of source files
 #[derive(Debug, clap::Parser)]
 pub(super) struct MineCodeFragments {
-    /// The directory to search for source files
+    /// The directory to search for source files, recursively and in parallel.
+    /// Hidden files and directories (e.g., `.git`) are skipped.
     #[clap(long, short)]
     search_directory: PathBuf,
 
@@ -33,6 +40,13 @@ pub(super) struct MineCodeFragments {
     /// The output file to write the extracted fragments to
     #[clap(long, short, default_value = "fragments.cbor.zst")]
     output: PathBuf,
+
+    /// Write the number of distinct fragments of each node kind to this file as JSON.
+    #[clap(long)]
+    report: Option<PathBuf>,
+
+    #[clap(flatten)]
+    grammar_overrides: GrammarOverrides,
 }
 
 impl MineCodeFragments {
@@ -42,15 +56,44 @@ impl MineCodeFragments {
             search_directory,
             language,
             output,
+            report,
+            grammar_overrides,
         } = self;
+        grammar_overrides
+            .apply_highlights()
+            .context("Overriding highlight queries")?;
+        // Only the supplied grammars are checked against the mined fragments, since the
+        // bundled ones are known to match the parsers.
+        let grammar = if grammar_overrides.overrides_grammar(language) {
+            Some(
+                grammar_overrides
+                    .grammar(language)
+                    .context("Loading grammar")?,
+            )
+        } else {
+            None
+        };
         let zstd_threads = global_options.parallel_workers();
-        let source_files = find_source_files(&search_directory, language)?;
+        let source_files = find_source_files(&search_directory, language);
 
         info!("Found {} source files", source_files.len());
+        // Projects often vendor copies of the same files, which add no new fragments.
+        let hasher = RandomState::new();
+        let parsed_files = Mutex::new(HashSet::new());
         let extracted_fragments: Vec<_> = source_files
             .into_par_iter()
-            .inspect(|source_file_path| info!("Parsing: {}", source_file_path.display()))
-            .map(|source_file| extract_fragments(&source_file, language))
+            .filter_map(|source_file| {
+                let file_content = read_source_file(&source_file)?;
+                let is_new = parsed_files
+                    .lock()
+                    .expect("The parsed files are poisoned")
+                    .insert(hasher.hash_one(&file_content));
+                is_new.then_some((source_file, file_content))
+            })
+            .inspect(|(source_file_path, _)| debug!("Parsing: {}", source_file_path.display()))
+            .map(|(source_file, file_content)| {
+                extract_fragments(&source_file, file_content, language)
+            })
             .filter_map(Result::transpose)
             .collect::<Result<_, _>>()?;
         let mut code = Vec::new();
@@ -71,12 +114,18 @@ impl MineCodeFragments {
             }
         }
 
+        if let Some(grammar) = &grammar {
+            warn_unknown_node_kinds(grammar, fragments.keys());
+        }
+
         info!("Deduplicating fragments");
         fragments.values_mut().par_bridge().for_each(|ranges| {
             ranges.sort_by_key(|it| &code[it.clone()]);
             ranges.dedup_by_key(|it| &code[it.clone()]);
         });
 
+        report_fragment_counts(&fragments, report.as_deref())?;
+
         info!("Serializing fragments");
         let result = DerivationFragments::new(code, fragments);
         write_output(&output, &result, zstd_threads).context("Writing output")?;
@@ -85,25 +134,95 @@ impl MineCodeFragments {
     }
 }
 
-fn find_source_files(
-    search_directory: &Path,
-    language: Language,
-) -> Result<Vec<PathBuf>, anyhow::Error> {
+fn warn_unknown_node_kinds<'a>(
+    grammar: &Grammar,
+    node_kinds: impl Iterator<Item = &'a Cow<'a, str>>,
+) {
+    let unknown_kinds: Vec<_> = node_kinds
+        .filter(|it| !grammar.derivation_rules().contains_key::<str>(it))
+        .sorted()
+        .collect();
+    if !unknown_kinds.is_empty() {
+        warn!(
+            "The grammar has no rules for {} mined node kinds, which are never generated: {}",
+            unknown_kinds.len(),
+            unknown_kinds.iter().join(", ")
+        );
+    }
+}
+
+/// Logs the node kinds with the most distinct fragments, and writes the counts of all of them
+/// to `report` if given.
+fn report_fragment_counts(
+    fragments: &HashMap<Cow<'_, str>, Vec<Range<usize>>>,
+    report: Option<&Path>,
+) -> anyhow::Result<()> {
+    let counts: BTreeMap<&str, usize> = fragments
+        .iter()
+        .map(|(node_kind, ranges)| (node_kind.as_ref(), ranges.len()))
+        .collect();
+    info!(
+        "Mined {} fragments of {} node kinds, the most of {}",
+        counts.values().sum::<usize>(),
+        counts.len(),
+        counts
+            .iter()
+            .sorted_by_key(|&(_, count)| std::cmp::Reverse(count))
+            .take(10)
+            .map(|(node_kind, count)| format!("{node_kind} ({count})"))
+            .join(", ")
+    );
+    if let Some(report) = report {
+        let report_file = File::create(report).context("Creating report file")?;
+        serde_json::to_writer_pretty(BufWriter::new(report_file), &counts)
+            .context("Writing report")?;
+    }
+    Ok(())
+}
+
+/// Finds the files of `language` under `search_directory`, walking the subdirectories in
+/// parallel. Hidden entries and symbolic links are skipped, except `search_directory` itself,
+/// and so are unreadable directories.
+fn find_source_files(search_directory: &Path, language: Language) -> Vec<PathBuf> {
     let extensions = language.file_extensions();
-    let source_files: Vec<_> = walkdir::WalkDir::new(search_directory)
-        .into_iter()
-        .filter_ok(|it| {
-            it.metadata().is_ok_and(|it| it.is_file())
-                && it
-                    .path()
-                    .extension()
-                    .map(|it| it.to_string_lossy())
-                    .is_some_and(|ext| extensions.contains(ext.as_ref()))
-        })
-        .map_ok(walkdir::DirEntry::into_path)
-        .try_collect()
-        .context("Searching for source file")?;
-    Ok(source_files)
+    let mut source_files: Vec<_> = rayon::iter::walk_tree(search_directory.to_owned(), |path| {
+        directory_entries(path, path == search_directory)
+    })
+    .filter(|path| {
+        path.extension()
+            .map(|it| it.to_string_lossy())
+            .is_some_and(|ext| extensions.contains(ext.as_ref()))
+            && fs::symlink_metadata(path).is_ok_and(|it| it.is_file())
+    })
+    .collect();
+    source_files.sort_unstable();
+    source_files.dedup();
+    source_files
+}
+
+/// The entries of `path` if it is a directory, except the hidden ones.
+///
+/// `path` is followed if it is a symbolic link and `follow_link` is set.
+fn directory_entries(path: &Path, follow_link: bool) -> Vec<PathBuf> {
+    let metadata = if follow_link {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    };
+    if !metadata.is_ok_and(|it| it.is_dir()) {
+        return Vec::new();
+    }
+    match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter(|it| !it.file_name().to_string_lossy().starts_with('.'))
+            .map(|it| it.path())
+            .collect(),
+        Err(err) => {
+            warn!(dir = %path.display(), %err, "Failed to list directory");
+            Vec::new()
+        }
+    }
 }
 
 fn write_output(
@@ -125,14 +244,19 @@ fn write_output(
 
 type ExtractedFragments<'a> = (Vec<u8>, HashMap<Cow<'a, str>, Vec<Range<usize>>>);
 
+/// The content of the source file at `path`, or `None` if it cannot be read.
+fn read_source_file(path: &Path) -> Option<Vec<u8>> {
+    fs::read(path)
+        .inspect_err(|err| warn!(file = %path.display(), %err, "Failed to read source file"))
+        .ok()
+}
+
 fn extract_fragments<'a>(
     source_file_path: &Path,
+    file_content: Vec<u8>,
     language: Language,
 ) -> anyhow::Result<Option<ExtractedFragments<'a>>> {
-    let file_content = std::fs::read(source_file_path)
-        .with_context(|| format!("Reading: {}", source_file_path.display()))?;
-    let mut parser = language.tree_sitter_parser();
-    match extract_derivation_fragments(&file_content, &mut parser) {
+    match extract_language_fragments(&file_content, language) {
         Ok(fragemnts) => Ok(Some((file_content, fragemnts))),
         Err(fragment_extraction::Error::DotGraphParsing(msg)) => {
             warn!(
@@ -1,16 +1,53 @@
+mod attribution_report;
+mod cat_input;
+mod completions;
+mod convert_input;
+mod corpus_stats;
+mod corpus_upgrade;
+mod coverage_diff;
+mod explain_input;
 mod export;
 mod fuzz;
+mod generation_diagnostics;
+mod import_session;
 mod mine_code_fragments;
+mod postprocess;
 mod reproduce;
+mod sanity_replay;
+mod soak;
+mod target_build;
 
-use std::{cmp::max, collections::HashMap, str::FromStr};
+use std::{
+    cmp::max,
+    collections::HashMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
 use anyhow::{Context, bail};
+use attribution_report::AttributionReportCommand;
+use cat_input::CatInputCommand;
+use completions::CompletionsCommand;
+use convert_input::ConvertInputCommand;
+use corpus_stats::CorpusStatsCommand;
+use corpus_upgrade::CorpusUpgradeCommand;
+use coverage_diff::CoverageDiffCommand;
+use explain_input::ExplainInputCommand;
 use export::ExportCommand;
 use fuzz::FuzzCommand;
+use generation_diagnostics::GenerationDiagnosticsCommand;
+use import_session::ImportSessionCommand;
+use lsp_fuzz_grammars::{Language, dynamic::DynamicLanguage};
 use mine_code_fragments::MineCodeFragments;
+use postprocess::PostprocessCommand;
 use reproduce::{reproduce_all::ReproduceAll, reproduce_one::ReproduceOne};
-use tracing::level_filters::LevelFilter;
+use sanity_replay::SanityReplayCommand;
+use soak::SoakCommand;
+use target_build::TargetBuildCommand;
+use tracing::{info, level_filters::LevelFilter};
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
 #[derive(Debug, clap::Parser)]
@@ -19,21 +56,72 @@ pub struct Cli {
     #[clap(flatten)]
     global_options: GlobalOptions,
 
+    /// Manifests of tree-sitter grammars to load from shared libraries.
+    /// The languages can then be used wherever a language name is expected.
+    /// See `lsp_fuzz_grammars::dynamic::DynamicLanguage` for the manifest format.
+    #[clap(long, global = true)]
+    dynamic_language: Vec<PathBuf>,
+
     #[command(subcommand)]
     command: Command,
 }
+
+/// Loads the languages passed with `--dynamic-language` in `args`.
+///
+/// This has to happen before parsing the command line, since the arguments naming the
+/// languages are parsed along with it.
+pub fn load_dynamic_languages(args: &[OsString]) -> anyhow::Result<()> {
+    let mut manifests = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "--dynamic-language" {
+            manifests.extend(args.next().map(PathBuf::from));
+        } else if let Some(manifest) = arg
+            .to_str()
+            .and_then(|it| it.strip_prefix("--dynamic-language="))
+        {
+            manifests.push(PathBuf::from(manifest));
+        }
+    }
+    for manifest in manifests {
+        DynamicLanguage::from_manifest(&manifest)
+            .and_then(|it| it.load())
+            .with_context(|| format!("Loading dynamic language from {}", manifest.display()))?;
+    }
+    Ok(())
+}
 impl Cli {
     pub(super) fn run(self) -> anyhow::Result<()> {
         self.global_options
             .setup_rayon()
             .context("Setting up rayon")?;
         setup_logger(&self.global_options).context("Setting up logger")?;
+        // The languages are loaded before the logger is set up, see `load_dynamic_languages`.
+        for language in Language::registered() {
+            info!(%language, "Loaded dynamic language");
+        }
         match self.command {
             Command::MineCodeFragments(cmd) => cmd.run(self.global_options),
             Command::Fuzz(cmd) => cmd.run(self.global_options),
             Command::Export(cmd) => cmd.run(self.global_options),
             Command::ReproduceOne(cmd) => cmd.run(self.global_options),
             Command::ReproduceAll(cmd) => cmd.run(self.global_options),
+            Command::TargetBuild(cmd) => cmd.run(self.global_options),
+            Command::CoverageDiff(cmd) => cmd.run(self.global_options),
+            Command::ExplainInput(cmd) => cmd.run(self.global_options),
+            Command::CatInput(cmd) => cmd.run(self.global_options),
+            Command::Postprocess(cmd) => cmd.run(self.global_options),
+            Command::CorpusUpgrade(cmd) => cmd.run(self.global_options),
+            Command::CorpusStats(cmd) => cmd.run(self.global_options),
+            Command::GenerationDiagnostics(cmd) => cmd.run(self.global_options),
+            Command::AttributionReport(cmd) => cmd.run(self.global_options),
+            Command::SanityReplay(cmd) => cmd.run(self.global_options),
+            Command::Soak(cmd) => cmd.run(self.global_options),
+            Command::Completions(cmd) => cmd.run(self.global_options),
+            Command::ConvertInput(cmd) => cmd.run(self.global_options),
+            Command::ImportSession(cmd) => cmd.run(self.global_options),
         }
     }
 }
@@ -70,6 +158,20 @@ enum Command {
     Export(ExportCommand),
     ReproduceAll(ReproduceAll),
     ReproduceOne(ReproduceOne),
+    TargetBuild(TargetBuildCommand),
+    CoverageDiff(Box<CoverageDiffCommand>),
+    ExplainInput(Box<ExplainInputCommand>),
+    CatInput(CatInputCommand),
+    Postprocess(Box<PostprocessCommand>),
+    CorpusUpgrade(CorpusUpgradeCommand),
+    CorpusStats(CorpusStatsCommand),
+    GenerationDiagnostics(GenerationDiagnosticsCommand),
+    AttributionReport(AttributionReportCommand),
+    SanityReplay(Box<SanityReplayCommand>),
+    Soak(Box<SoakCommand>),
+    Completions(CompletionsCommand),
+    ConvertInput(ConvertInputCommand),
+    ImportSession(ImportSessionCommand),
 }
 
 fn setup_logger(global_opts: &GlobalOptions) -> anyhow::Result<()> {
@@ -108,7 +210,7 @@ where
 
 pub fn parse_size(s: &str) -> Result<usize, anyhow::Error> {
     if s.chars().last().is_some_and(char::is_alphabetic) {
-        let (size, unit) = s.split_at(s.len() - 1);
+        let (size, unit) = s.split_at_checked(s.len() - 1).context("Invalid unit")?;
         let multiplier = match unit.to_uppercase().as_str() {
             "B" => 1 << 0,
             "K" => 1 << 10,
@@ -123,3 +225,77 @@ pub fn parse_size(s: &str) -> Result<usize, anyhow::Error> {
         Ok(s.parse()?)
     }
 }
+
+pub fn parse_duration(s: &str) -> Result<Duration, anyhow::Error> {
+    if let Some(millis) = s.strip_suffix("ms") {
+        Ok(Duration::from_millis(millis.parse()?))
+    } else if s.chars().last().is_some_and(char::is_alphabetic) {
+        let (duration, unit) = s.split_at_checked(s.len() - 1).context("Invalid unit")?;
+        let multiplier = match unit.to_lowercase().as_str() {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => bail!("Invalid unit"),
+        };
+        let base_duration: u64 = duration.parse()?;
+        let secs = base_duration
+            .checked_mul(multiplier)
+            .context("The duration is too long")?;
+        Ok(Duration::from_secs(secs))
+    } else {
+        Ok(Duration::from_secs(s.parse()?))
+    }
+}
+
+/// The input files of a corpus directory, skipping hidden files such as metadata.
+fn corpus_files(corpus_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(corpus_dir).context("Reading corpus directory")? {
+        let entry = entry.context("Reading corpus directory")?;
+        if entry.metadata().is_ok_and(|it| it.is_file())
+            && !entry.file_name().to_string_lossy().starts_with('.')
+        {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_duration, parse_size};
+
+    #[test]
+    fn parse_durations_with_units() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("2H").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86_400));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn reject_invalid_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("10é").is_err());
+        assert!(parse_duration("1µ").is_err());
+        assert!(parse_duration(&format!("{}d", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn parse_sizes_with_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("4k").unwrap(), 4 << 10);
+        assert_eq!(parse_size("2G").unwrap(), 2 << 30);
+        assert!(parse_size("1é").is_err());
+        assert!(parse_size("1x").is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use itertools::Itertools;
+use libafl::inputs::Input;
+use lsp_fuzz::lsp_input::LspInput;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tracing::{info, warn};
+
+use super::{
+    GlobalOptions, corpus_files,
+    export::export_input,
+    reproduce::{
+        ASAN_LOG_FN, Failure, PacingOptions, ReproductionInfo, TargetOptions, TimeoutOptions,
+        asan_options, parse_asan_log, reproduce, session_bytes,
+    },
+};
+use crate::fuzzing::{FuzzerStateDir, TargetInvocation};
+
+/// Minimizes, reproduces, deduplicates, and bundles all the solutions of a campaign.
+///
+/// Each unique crash gets a bundle directory in the output directory with its smallest
+/// reproducing input, the exported session, and the reproduction report.
+/// A `summary.md` lists the crashes with their duplicates.
+#[derive(Debug, clap::Parser)]
+pub(super) struct PostprocessCommand {
+    /// The state directory of the campaign.
+    #[clap(long, short)]
+    campaign_dir: PathBuf,
+
+    #[clap(flatten)]
+    target: TargetOptions,
+
+    #[clap(flatten)]
+    pacing: PacingOptions,
+
+    #[clap(flatten)]
+    timeouts: TimeoutOptions,
+
+    /// The directory to write the bundles and the summary to.
+    #[clap(long, short)]
+    output_dir: PathBuf,
+
+    /// Reproduce the solutions as they are, without minimizing them first.
+    #[clap(long)]
+    no_minimize: bool,
+
+    /// Seconds to wait for the target to exit when checking whether a reduced input crashes.
+    #[clap(long, default_value_t = 30)]
+    minimization_timeout: u64,
+}
+
+/// A solution that was reproduced.
+struct Solution {
+    input_id: String,
+    original_messages: usize,
+    info: ReproductionInfo,
+}
+
+impl Solution {
+    fn messages(&self) -> usize {
+        self.info
+            .input
+            .as_ref()
+            .map_or(self.original_messages, |it| it.messages.len())
+    }
+}
+
+impl PostprocessCommand {
+    /// The number of top stack frames telling crashes apart.
+    const DEDUP_FRAMES: usize = 3;
+
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let state_dir = FuzzerStateDir::from(&self.campaign_dir);
+        let solution_files = corpus_files(&state_dir.solution_dir())?;
+        let target = self
+            .target
+            .clone()
+            .resolve(Some(state_dir))
+            .context("Resolving target")?;
+        fs::create_dir_all(&self.output_dir).context("Creating output directory")?;
+
+        let total = solution_files.len();
+        let done = AtomicUsize::new(0);
+        let results: Vec<_> = solution_files
+            .into_par_iter()
+            .map(|solution_file| {
+                let result = self.process(&solution_file, &target);
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                info!(
+                    "[{done}/{total}] Processed {}",
+                    solution_file.file_name().unwrap_or_default().display()
+                );
+                (solution_file, result)
+            })
+            .collect();
+
+        let mut not_reproduced = Vec::new();
+        let mut crashes: BTreeMap<String, Vec<Solution>> = BTreeMap::new();
+        for (solution_file, result) in results {
+            match result {
+                Ok(Some(solution)) => crashes
+                    .entry(dedup_key(&solution.info))
+                    .or_default()
+                    .push(solution),
+                Ok(None) => not_reproduced.push(solution_file),
+                Err(err) => {
+                    warn!(file = %solution_file.display(), "Failed to process solution: {err:?}");
+                    not_reproduced.push(solution_file);
+                }
+            }
+        }
+        info!(
+            unique = crashes.len(),
+            not_reproduced = not_reproduced.len(),
+            "Deduplicated crashes"
+        );
+
+        let mut summary = String::new();
+        writeln!(summary, "# Crashes of {}\n", self.campaign_dir.display())?;
+        for (idx, (key, solutions)) in crashes.iter_mut().enumerate() {
+            solutions.sort_by_key(Solution::messages);
+            let bundle_name = format!("crash_{idx:03}");
+            self.write_bundle(&bundle_name, &solutions[0])
+                .with_context(|| format!("Writing bundle {bundle_name}"))?;
+            write_summary_entry(&mut summary, &bundle_name, key, solutions)?;
+        }
+        if !not_reproduced.is_empty() {
+            writeln!(summary, "## Not reproduced\n")?;
+            for solution_file in &not_reproduced {
+                writeln!(summary, "- `{}`", solution_file.display())?;
+            }
+        }
+        fs::write(self.output_dir.join("summary.md"), summary).context("Writing summary")?;
+        Ok(())
+    }
+
+    /// Minimizes and reproduces the solution in `solution_file`.
+    ///
+    /// Returns `None` if the crash does not reproduce.
+    fn process(
+        &self,
+        solution_file: &Path,
+        target: &TargetInvocation,
+    ) -> anyhow::Result<Option<Solution>> {
+        let input_id = solution_file
+            .file_name()
+            .context("The solution has no file name")?
+            .to_string_lossy()
+            .into_owned();
+        let mut input = LspInput::from_file(solution_file).context("Loading input file")?;
+        let original_messages = input.messages.len();
+        if !self.no_minimize {
+            let timeout = Duration::from_secs(self.minimization_timeout);
+            match crash_signature(&input, target, timeout)? {
+                Some(signature) => {
+                    input = minimize(&input, target, &signature, timeout)?;
+                    info!(
+                        input_id,
+                        before = original_messages,
+                        after = input.messages.len(),
+                        "Minimized solution"
+                    );
+                }
+                None => info!(
+                    input_id,
+                    "The solution does not crash at once, not minimizing"
+                ),
+            }
+        }
+        let info = reproduce(
+            input_id.clone(),
+            input,
+            target,
+            self.pacing.into(),
+            self.timeouts,
+            false,
+        )?;
+        Ok(info.map(|info| Solution {
+            input_id,
+            original_messages,
+            info,
+        }))
+    }
+
+    fn write_bundle(&self, bundle_name: &str, solution: &Solution) -> anyhow::Result<()> {
+        let bundle_dir = self.output_dir.join(bundle_name);
+        fs::create_dir_all(&bundle_dir).context("Creating bundle directory")?;
+        let input_file = bundle_dir.join(&solution.input_id);
+        solution
+            .info
+            .input
+            .as_ref()
+            .context("The reproduction has no input")?
+            .to_file(&input_file)
+            .context("Writing input")?;
+        export_input(&input_file, &bundle_dir.join("session")).context("Exporting session")?;
+        let report = File::create(bundle_dir.join("reproduction.json"))
+            .context("Creating reproduction report")?;
+        serde_json::to_writer_pretty(report, &solution.info)
+            .context("Writing reproduction report")?;
+        Ok(())
+    }
+}
+
+/// Crashes with the same key are considered duplicates.
+fn dedup_key(info: &ReproductionInfo) -> String {
+    let kind = match &info.failure {
+        Failure::Sanitizer => info
+            .asan_classification
+            .as_ref()
+            .map_or_else(|| "sanitizer".to_owned(), |it| it.short_description.clone()),
+        Failure::Signal { name, .. } => name.clone(),
+        Failure::ExitCode { code } => format!("exit code {code}"),
+        Failure::Hang {
+            last_sent, phase, ..
+        } => {
+            let method = last_sent.as_ref().and_then(|it| it.method());
+            let method = method.map_or("nothing", |it| &**it);
+            if phase.is_teardown() {
+                format!("shutdown hang after {method}")
+            } else {
+                format!("hang after {method}")
+            }
+        }
+    };
+    let frames = info
+        .stack_trace
+        .iter()
+        .take(PostprocessCommand::DEDUP_FRAMES)
+        .map(|it| it.function.as_str())
+        .join(" < ");
+    // The two servers do not share code, so their crashes are never duplicates.
+    let kind = if info.companion {
+        format!("companion {kind}")
+    } else {
+        kind
+    };
+    if frames.is_empty() {
+        kind
+    } else {
+        format!("{kind} in {frames}")
+    }
+}
+
+fn write_summary_entry(
+    summary: &mut String,
+    bundle_name: &str,
+    key: &str,
+    solutions: &[Solution],
+) -> std::fmt::Result {
+    let representative = &solutions[0];
+    writeln!(summary, "## `{bundle_name}`: {key}\n")?;
+    if let Some(class) = &representative.info.asan_classification {
+        writeln!(summary, "- Severity: {}", class.severity)?;
+    }
+    if representative.info.companion {
+        writeln!(summary, "- Server: companion")?;
+    }
+    if let Some(idx) = representative.info.crashing_request_idx {
+        writeln!(summary, "- Crashing message: #{idx}")?;
+    }
+    let timed_out = &representative.info.timed_out_requests;
+    if !timed_out.is_empty() {
+        let requests = timed_out
+            .iter()
+            .map(|it| format!("#{} `{}`", it.idx, it.method))
+            .join(", ");
+        writeln!(summary, "- Requests without a response in time: {requests}")?;
+    }
+    if let Some(frame) = representative.info.stack_trace.first() {
+        writeln!(
+            summary,
+            "- Location: `{}` at {}:{}",
+            frame.function, frame.debug.file, frame.debug.line
+        )?;
+    }
+    writeln!(
+        summary,
+        "- Input: `{}` ({} messages, {} before minimization)",
+        representative.input_id,
+        representative.messages(),
+        representative.original_messages
+    )?;
+    let duplicates = solutions[1..]
+        .iter()
+        .map(|it| format!("`{}`", it.input_id))
+        .join(", ");
+    if !duplicates.is_empty() {
+        writeln!(summary, "- Duplicates: {duplicates}")?;
+    }
+    writeln!(summary)
+}
+
+/// Removes the messages of `input` that the target does not need to crash with `signature`.
+fn minimize(
+    input: &LspInput,
+    target: &TargetInvocation,
+    signature: &[String],
+    timeout: Duration,
+) -> anyhow::Result<LspInput> {
+    let mut minimized = input.clone();
+    for idx in (0..minimized.messages.len()).rev() {
+        let mut candidate = minimized.clone();
+        candidate.remove_message(idx);
+        if crash_signature(&candidate, target, timeout)?.as_deref() == Some(signature) {
+            minimized = candidate;
+        }
+    }
+    Ok(minimized)
+}
+
+/// The top frames of the sanitizer report of the target on `input`, or of its companion
+/// if only the companion crashes.
+///
+/// The whole session is written to the servers at once, which is much faster than
+/// the paced reproduction.
+fn crash_signature(
+    input: &LspInput,
+    target: &TargetInvocation,
+    timeout: Duration,
+) -> anyhow::Result<Option<Vec<String>>> {
+    if let Some(signature) = server_crash_signature(input, target, None, timeout)? {
+        return Ok(Some(signature));
+    }
+    let (Some(companion), Some(invocation)) = (target.companion_target(), &target.companion) else {
+        return Ok(None);
+    };
+    server_crash_signature(input, &companion, Some(&invocation.documents), timeout)
+}
+
+/// The top frames of the sanitizer report of `server` on `input`, if it crashes.
+///
+/// The server is a companion serving `documents` if given.
+fn server_crash_signature(
+    input: &LspInput,
+    server: &TargetInvocation,
+    documents: Option<&[String]>,
+    timeout: Duration,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let temp_directory = tempfile::tempdir().context("Creating temporary working directory")?;
+    let workspace_dir = temp_directory.path();
+    let asan_log_file_prefix = workspace_dir.join(ASAN_LOG_FN);
+    input
+        .setup_workspace(workspace_dir)
+        .context("Setting up workspace")?;
+    let mut child = server
+        .command(
+            workspace_dir,
+            [(
+                "ASAN_OPTIONS",
+                asan_options(&asan_log_file_prefix).join(":"),
+            )],
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Starting target process")?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("Child should have its stdin piped")?;
+    match stdin.write_all(&session_bytes(input, workspace_dir, documents)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+        Err(e) => Err(e).context("Sending messages to target")?,
+    }
+    drop(stdin);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait().context("Waiting for target")? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill().context("Killing target")?;
+            child.wait().context("Waiting for target")?;
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    if status.success() {
+        return Ok(None);
+    }
+    let pid = child.id();
+    let mut asan_log = match File::open(asan_log_file_prefix.with_extension(pid.to_string())) {
+        Ok(file) => BufReader::new(file),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("Opening ASAN log file"),
+    };
+    let (_, _, stack_trace) = parse_asan_log(&mut asan_log, pid).context("Parsing ASAN logs")?;
+    Ok(Some(
+        stack_trace
+            .into_iter()
+            .take(PostprocessCommand::DEDUP_FRAMES)
+            .map(|it| it.function)
+            .collect(),
+    ))
+}
@@ -1,96 +1,544 @@
 use std::{
     borrow::Cow,
+    collections::HashMap,
     ffi::CStr,
     fs::File,
-    io::{self, BufReader, ErrorKind, Read, Write},
+    io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
     os::unix::process::ExitStatusExt,
-    path::Path,
-    process::{Child, Command, Stdio},
-    time::Duration,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
 };
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use itertools::Itertools;
+use libafl_bolts::rands::StdRand;
 use libcasr::{
     asan::{AsanContext, AsanStacktrace},
     execution_class::ExecutionClass,
+    gdb::GdbStacktrace,
     severity::Severity,
     stacktrace::ParseStacktrace,
 };
 use lsp_fuzz::{
-    execution::workspace_observer::HasWorkspace, lsp::json_rpc::JsonRPCMessage, lsp_input::LspInput,
+    execution::{
+        pacing::{Pacer, PacingConfig},
+        phases::ExecutionPhase,
+        polyglot::companion_stream,
+        workspace_observer::HasWorkspace,
+    },
+    lsp::json_rpc::{JsonRPCMessage, MessageId},
+    lsp_input::{
+        LspInput,
+        messages::{SequenceElement, partial_payload},
+    },
 };
 use nix::libc;
 use serde::Serialize;
 use tracing::{info, warn};
 
+use crate::{
+    cli::parse_hash_map,
+    fuzzing::{FuzzerStateDir, TargetInvocation},
+};
+
 pub mod reproduce_all;
 pub mod reproduce_one;
 
 fn json_rpc_messages<'a>(
     lsp_input: &'a LspInput,
     workspace_url: &'a str,
-) -> impl Iterator<Item = JsonRPCMessage> + use<'a> {
+) -> impl Iterator<Item = SequenceElement<JsonRPCMessage>> + use<'a> {
     let mut msg_id = 0;
     lsp_input
-        .message_sequence()
-        .map(move |msg| msg.into_json_rpc(&mut msg_id, Some(workspace_url)))
+        .session_elements()
+        .map(move |element| element.map(|msg| msg.into_json_rpc(&mut msg_id, Some(workspace_url))))
+}
+
+/// How the target behaved during a session.
+struct SessionOutcome {
+    /// The message after which the target exited with a failure.
+    crashing_request: Option<(usize, JsonRPCMessage)>,
+    /// Set if the target was still running long after the whole session was sent.
+    hang: Option<Hang>,
+    /// The requests the target did not respond to in time.
+    timed_out_requests: Vec<TimedOutRequest>,
+}
+
+struct Hang {
+    last_sent_idx: Option<usize>,
+    last_sent: Option<JsonRPCMessage>,
+    phase: ExecutionPhase,
+    /// The stacks of the threads of the target taken before it was killed.
+    backtrace: Option<String>,
+}
+
+/// A request the target did not respond to within the response timeout.
+#[derive(Debug, Serialize)]
+pub struct TimedOutRequest {
+    pub idx: usize,
+    pub id: MessageId,
+    pub method: String,
+}
+
+/// Reads the responses of the target from `stdout` on a separate thread and
+/// forwards their IDs.
+///
+/// The channel disconnects once the target closes its output, e.g., when it exits.
+fn forward_response_ids(stdout: ChildStdout) -> Receiver<MessageId> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            match JsonRPCMessage::read_lsp_payload(&mut reader) {
+                Ok(JsonRPCMessage::Response { id: Some(id), .. }) => {
+                    if sender.send(id).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => {
+                    warn!("Failed to read message from target, ignoring its output: {e}");
+                    io::copy(&mut reader, &mut io::sink()).ok();
+                    break;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+/// Waits until the response to the request `id` is received, the target closes its output,
+/// or `timeout` elapses.
+///
+/// Returns whether the target did not respond in time while still running.
+fn wait_for_response(responses: &Receiver<MessageId>, id: &MessageId, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match responses.recv_timeout(remaining) {
+            Ok(received) if received == *id => return false,
+            Ok(_) => {}
+            Err(RecvTimeoutError::Disconnected) => return false,
+            Err(RecvTimeoutError::Timeout) => return true,
+        }
+    }
 }
 
 fn find_crashing_request(
     input: &LspInput,
     workspace_url: &str,
     child: &mut Child,
-) -> Result<Option<(usize, JsonRPCMessage)>, anyhow::Error> {
+    pacer: &mut Pacer<StdRand>,
+    timeouts: TimeoutOptions,
+) -> Result<SessionOutcome, anyhow::Error> {
     let mut target_stdin = child
         .stdin
         .take()
         .context("Child should have its stdin piped")?;
+    let responses = forward_response_ids(
+        child
+            .stdout
+            .take()
+            .context("Child should have its stdout piped")?,
+    );
     let mut crashing_request = None;
-    for (idx, jsonrpc) in json_rpc_messages(input, workspace_url).enumerate() {
+    let mut last_sent = None;
+    let mut timed_out_requests = Vec::new();
+    let mut shutdown_sent = false;
+    let mut exited = false;
+    // The message at which the transport fault of the input happened, if any.
+    let mut fault = None;
+    let mut idx = 0;
+    for element in json_rpc_messages(input, workspace_url) {
+        let jsonrpc = match element {
+            SequenceElement::Message(jsonrpc) => jsonrpc,
+            SequenceElement::Close { partial } => {
+                fault = last_sent.clone();
+                if let Some((jsonrpc, len)) = partial {
+                    info!(
+                        id = ?jsonrpc.id(),
+                        method = ?jsonrpc.method(),
+                        len,
+                        "Sending part of message to target"
+                    );
+                    let payload =
+                        jsonrpc.to_lsp_payload_with(input.messages.header_line_ending(idx));
+                    send_to_target(&mut target_stdin, partial_payload(&payload, len))?;
+                    fault = Some((idx, jsonrpc));
+                }
+                info!("Closing the input of the target");
+                drop(target_stdin);
+                break;
+            }
+        };
         info!(
             id = ?jsonrpc.id(),
             method = ?jsonrpc.method(),
             "Sending message to target"
         );
-        match target_stdin.write_all(&jsonrpc.to_lsp_payload()) {
-            Ok(()) => {}
-            Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
-            Err(e) => Err(e).context("Sending message to target")?,
+        pacer.pace();
+        let line_ending = input.messages.header_line_ending(idx);
+        send_to_target(&mut target_stdin, &jsonrpc.to_lsp_payload_with(line_ending))?;
+        if let Some(method) = jsonrpc.method() {
+            pacer.record_sent(method);
         }
-        let sleep_duration = if jsonrpc
-            .method()
-            .is_some_and(|it| it == "textDocument/didOpen")
-        {
-            Duration::from_secs(20)
+        if let JsonRPCMessage::Request { id, method, .. } = &jsonrpc {
+            shutdown_sent |= method == "shutdown";
+            let timeout = Duration::from_secs(timeouts.response_timeout);
+            if wait_for_response(&responses, id, timeout) {
+                warn!(%id, %method, "No response in time, skipping the request");
+                if timeouts.cancel_timed_out_requests {
+                    let cancel = JsonRPCMessage::notification(
+                        Cow::Borrowed("$/cancelRequest"),
+                        serde_json::json!({ "id": id }),
+                    );
+                    send_to_target(&mut target_stdin, &cancel.to_lsp_payload())?;
+                }
+                timed_out_requests.push(TimedOutRequest {
+                    idx,
+                    id: id.clone(),
+                    method: method.clone().into_owned(),
+                });
+            } else {
+                pacer
+                    .idle(
+                        Duration::from_secs(timeouts.response_wait),
+                        &mut target_stdin,
+                    )
+                    .context("Sending keep-alive to target")?;
+            }
         } else {
-            Duration::from_secs(15)
-        };
-        std::thread::sleep(sleep_duration);
+            let wait = if jsonrpc
+                .method()
+                .is_some_and(|it| it == "textDocument/didOpen")
+            {
+                timeouts.did_open_wait
+            } else {
+                timeouts.notification_wait
+            };
+            pacer
+                .idle(Duration::from_secs(wait), &mut target_stdin)
+                .context("Sending keep-alive to target")?;
+        }
         if let Some(status) = child.try_wait().context("Waiting child")? {
             if !status.success() {
                 crashing_request = Some((idx, jsonrpc));
             }
+            exited = true;
             break;
         }
+        last_sent = Some((idx, jsonrpc));
+        idx += 1;
     }
-    if crashing_request.is_none() {
-        std::thread::sleep(Duration::from_secs(30));
+    let mut hang = None;
+    if !exited {
+        let exit_timeout = Duration::from_secs(timeouts.exit_timeout);
+        match wait_for_exit(child, exit_timeout).context("Waiting child")? {
+            // A crash on the truncated input is a crash at the fault.
+            Some(status) if !status.success() => crashing_request = fault,
+            Some(_) => {}
+            None => {
+                warn!("Target is still running after the session, treating it as a hang");
+                let (last_sent_idx, last_sent) = last_sent.unzip();
+                hang = Some(Hang {
+                    last_sent_idx,
+                    last_sent,
+                    phase: hang_phase(&timed_out_requests, shutdown_sent),
+                    backtrace: attach_backtrace(child.id()),
+                });
+            }
+        }
         child.kill().context("Killing child")?;
     }
-    Ok(crashing_request)
+    Ok(SessionOutcome {
+        crashing_request,
+        hang,
+        timed_out_requests,
+    })
+}
+
+/// Waits until `child` exits or `timeout` elapses, and returns its exit status if it exited.
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        std::thread::sleep(remaining.min(POLL_INTERVAL));
+    }
 }
 
-const ASAN_LOG_FN: &str = "lsp-fuzz-asan";
+/// The phase of the session in which the target hung, judging from the first request
+/// it did not respond to in time.
+fn hang_phase(timed_out_requests: &[TimedOutRequest], shutdown_sent: bool) -> ExecutionPhase {
+    match timed_out_requests.first().map(|it| it.method.as_str()) {
+        Some("initialize") => ExecutionPhase::Init,
+        Some("shutdown") => ExecutionPhase::ShutdownRequest,
+        Some(_) => ExecutionPhase::Requests,
+        None if shutdown_sent => ExecutionPhase::Shutdown,
+        None => ExecutionPhase::Requests,
+    }
+}
+
+/// Writes `payload` to the target, ignoring that the target has closed its input.
+fn send_to_target(target_stdin: &mut ChildStdin, payload: &[u8]) -> anyhow::Result<()> {
+    match target_stdin.write_all(payload) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        Err(e) => Err(e).context("Sending message to target"),
+    }
+}
+
+/// Takes the stacks of the threads of the running process `pid` with `eu-stack`, or with `gdb`
+/// if `eu-stack` is not available or fails.
+///
+/// `gdb` is killed if it does not finish in time, e.g., when it is stuck on loading symbols.
+fn attach_backtrace(pid: u32) -> Option<String> {
+    const GDB_TIMEOUT: Duration = Duration::from_secs(30);
+    let pid = pid.to_string();
+    let eu_stack = || {
+        let output = Command::new("eu-stack").args(["-p", &pid]).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let gdb = || {
+        // Written to a file rather than a pipe, which could fill up while gdb is waited for.
+        let mut stdout = tempfile::tempfile().ok()?;
+        let mut child = Command::new("gdb")
+            .args(["-batch", "-nx", "-p", &pid, "-ex", "thread apply all bt"])
+            .stdin(Stdio::null())
+            .stdout(stdout.try_clone().ok()?)
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let Some(status) = wait_for_exit(&mut child, GDB_TIMEOUT).ok()? else {
+            warn!("gdb did not finish in time, no backtrace is taken");
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        };
+        let mut backtrace = Vec::new();
+        stdout.seek(SeekFrom::Start(0)).ok()?;
+        stdout.read_to_end(&mut backtrace).ok()?;
+        status
+            .success()
+            .then(|| String::from_utf8_lossy(&backtrace).into_owned())
+    };
+    eu_stack().or_else(gdb)
+}
+
+/// Runs the target under `gdb` on the whole session and takes its stack when it stops,
+/// for crashes without a sanitizer report. The session is that of a companion serving
+/// `documents` if given.
+///
+/// Returns `None` if `gdb` is not available.
+fn gdb_backtrace(
+    input: &LspInput,
+    target: &TargetInvocation,
+    documents: Option<&[String]>,
+) -> anyhow::Result<Option<String>> {
+    let temp_directory = tempfile::tempdir().context("Creating temporary working directory")?;
+    let workspace_dir = temp_directory.path().join("workspace");
+    input
+        .setup_workspace(&workspace_dir)
+        .context("Setting up workspace_dir")?;
+    let gdb_output = temp_directory.path().join("gdb.log");
+    let mut gdb = Command::new("gdb");
+    gdb.args([
+        "-batch",
+        "-nx",
+        "-ex",
+        "run",
+        "-ex",
+        "thread apply all bt",
+        "--args",
+    ])
+    .arg(&target.executable)
+    .args(&target.args)
+    .envs(&target.env)
+    .current_dir(&workspace_dir);
+    let spawned = target
+        .containerize(gdb)
+        .stdin(Stdio::piped())
+        .stdout(File::create(&gdb_output).context("Creating gdb log")?)
+        .stderr(Stdio::null())
+        .spawn();
+    let mut gdb = match spawned {
+        Ok(gdb) => gdb,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            warn!("gdb is not available, no backtrace is taken");
+            return Ok(None);
+        }
+        Err(e) => return Err(e).context("Starting gdb"),
+    };
+    let mut stdin = gdb
+        .stdin
+        .take()
+        .context("Child should have its stdin piped")?;
+    match stdin.write_all(&session_bytes(input, &workspace_dir, documents)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+        Err(e) => Err(e).context("Sending messages to target")?,
+    }
+    drop(stdin);
+    let deadline = std::time::Instant::now() + Duration::from_secs(60);
+    while gdb.try_wait().context("Waiting for gdb")?.is_none() {
+        if std::time::Instant::now() >= deadline {
+            gdb.kill().context("Killing gdb")?;
+            gdb.wait().context("Waiting for gdb")?;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let log = std::fs::read(&gdb_output).context("Reading gdb log")?;
+    Ok(Some(String::from_utf8_lossy(&log).into_owned()))
+}
+
+pub(in crate::cli) const ASAN_LOG_FN: &str = "lsp-fuzz-asan";
+
+/// Options controlling how fast messages are sent to the target.
+/// Options pacing the messages sent to the target while replaying inputs.
+///
+/// Campaigns do not take these options, as their target receives the whole session at once.
+#[derive(Debug, Clone, Copy, clap::Parser)]
+pub struct PacingOptions {
+    /// Limit the rate of messages sent to the target (messages per second).
+    #[clap(long)]
+    messages_per_second: Option<f64>,
+
+    /// Number of messages that can be sent back to back before the rate limit applies.
+    #[clap(long, default_value_t = 1)]
+    message_burst: u32,
+
+    /// Upper bound of the random delay added before each message (in milliseconds).
+    #[clap(long, default_value_t = 0)]
+    message_jitter: u64,
+
+    /// Send a `$/setTrace` notification after this many seconds without a message while
+    /// waiting on the target, for servers that end silent sessions.
+    #[clap(long)]
+    keep_alive_interval: Option<u64>,
+}
+
+impl From<PacingOptions> for PacingConfig {
+    fn from(options: PacingOptions) -> Self {
+        Self {
+            messages_per_second: options.messages_per_second,
+            burst: options.message_burst,
+            jitter: Duration::from_millis(options.message_jitter),
+            keep_alive: options.keep_alive_interval.map(Duration::from_secs),
+        }
+    }
+}
+
+/// Options controlling how long the target is waited on for each message.
+#[derive(Debug, Clone, Copy, clap::Parser)]
+pub struct TimeoutOptions {
+    /// Seconds to wait for the response to each request before skipping it and
+    /// continuing with the next message.
+    #[clap(long, default_value_t = 15)]
+    response_timeout: u64,
+
+    /// Seconds to wait after the response to each request before checking whether the target
+    /// is still running, so that crashes while processing the request asynchronously are
+    /// attributed to it.
+    #[clap(long, default_value_t = 1)]
+    response_wait: u64,
+
+    /// Send `$/cancelRequest` for the requests skipped after the response timeout.
+    #[clap(long)]
+    cancel_timed_out_requests: bool,
 
-#[tracing::instrument(skip(input, target_executable, target_args))]
-fn reproduce(
+    /// Seconds to wait after each notification.
+    #[clap(long, default_value_t = 15)]
+    notification_wait: u64,
+
+    /// Seconds to wait after each `textDocument/didOpen` notification.
+    #[clap(long, default_value_t = 20)]
+    did_open_wait: u64,
+
+    /// Seconds to wait for the target to exit after the session before treating it as a hang.
+    #[clap(long, default_value_t = 30)]
+    exit_timeout: u64,
+}
+
+/// Options specifying the target, overriding the invocation recorded by the fuzzer.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct TargetOptions {
+    /// The path to the target executable.
+    #[clap(long, short)]
+    target_executable: Option<PathBuf>,
+
+    /// Arguments to pass to the target.
+    #[clap(long)]
+    target_args: Option<Vec<String>>,
+
+    /// Environment variables to pass to the target, in addition to the recorded ones.
+    /// Format: KEY=VALUE
+    #[clap(long, value_parser = parse_hash_map::<String, String>, default_value = "")]
+    target_env: HashMap<String, String>,
+}
+
+impl TargetOptions {
+    /// Resolves the target invocation from the options and the one recorded in `state_dir`.
+    ///
+    /// The target executable must be given if the state directory has no recorded invocation.
+    pub(in crate::cli) fn resolve(
+        self,
+        state_dir: Option<FuzzerStateDir>,
+    ) -> anyhow::Result<TargetInvocation> {
+        let target_file = state_dir
+            .map(|it| it.target_file())
+            .filter(|it| it.exists());
+        let mut invocation = match (target_file, self.target_executable) {
+            (Some(path), executable) => {
+                info!("Using the target invocation recorded in {}", path.display());
+                let mut invocation = TargetInvocation::load(&path)?;
+                if let Some(executable) = executable {
+                    invocation.executable = executable;
+                }
+                invocation
+            }
+            (None, Some(executable)) => TargetInvocation {
+                executable,
+                args: Vec::new(),
+                env: HashMap::new(),
+                companion: None,
+                container: None,
+            },
+            (None, None) => {
+                bail!("No target invocation is recorded, please specify --target-executable")
+            }
+        };
+        if let Some(args) = self.target_args {
+            invocation.args = args;
+        }
+        invocation.env.extend(self.target_env);
+        Ok(invocation)
+    }
+}
+
+#[tracing::instrument(skip(input, target))]
+pub(in crate::cli) fn reproduce(
     input_id: String,
     input: LspInput,
-    target_executable: &Path,
-    target_args: &[String],
+    target: &TargetInvocation,
+    pacing: PacingConfig,
+    timeouts: TimeoutOptions,
     show_stderr: bool,
 ) -> Result<Option<ReproductionInfo>, anyhow::Error> {
+    let mut pacer = Pacer::new(pacing, StdRand::with_seed(libafl_bolts::current_nanos()))
+        .context("Pacing messages")?;
     let temp_directory = tempfile::tempdir().context("Creating temporary working directory")?;
     let workspace_dir = temp_directory.path();
     let asan_log_file_prefix = workspace_dir.join(ASAN_LOG_FN);
@@ -98,11 +546,8 @@ fn reproduce(
     input
         .setup_workspace(workspace_dir)
         .context("Setting up workspace_dir")?;
-    let mut target = Command::new(target_executable);
-    target
-        .args(target_args)
-        .env("ASAN_OPTIONS", asan_options_env)
-        .current_dir(workspace_dir)
+    let mut command = target.command(workspace_dir, [("ASAN_OPTIONS", asan_options_env)]);
+    command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(if show_stderr {
@@ -110,33 +555,195 @@ fn reproduce(
         } else {
             Stdio::null()
         });
-    let mut child = target.spawn().context("Starting target process")?;
+    let mut child = command.spawn().context("Starting target process")?;
     let workspace_url = format!(
         "file://{}/",
         workspace_dir
             .to_str()
             .expect("The workspace_dir is not valid UTF-8")
     );
-    let crashing_request = find_crashing_request(&input, &workspace_url, &mut child)?;
+    let outcome = find_crashing_request(&input, &workspace_url, &mut child, &mut pacer, timeouts)?;
     let status = child.wait().context("Waiting for target to exit")?;
     info!("Target exited with status: {:?}", status);
 
+    if let Some(hang) = outcome.hang {
+        info!(last_sent = ?hang.last_sent_idx, phase = ?hang.phase, "Target hung");
+        return Ok(Some(ReproductionInfo {
+            input_id,
+            input: Some(input),
+            crashing_request_idx: None,
+            crashing_request: None,
+            companion: false,
+            failure: Failure::Hang {
+                last_sent_idx: hang.last_sent_idx,
+                last_sent: hang.last_sent,
+                phase: hang.phase,
+            },
+            asan_summary: String::new(),
+            asan_classification: None,
+            stack_trace: Vec::new(),
+            backtrace: hang.backtrace,
+            timed_out_requests: outcome.timed_out_requests,
+        }));
+    }
     if status.success() {
         info!("Target exited successfully");
+        let (Some(companion), Some(invocation)) = (target.companion_target(), &target.companion)
+        else {
+            return Ok(None);
+        };
+        return reproduce_companion(input_id, input, &companion, &invocation.documents, timeouts);
+    }
+    let (crashing_request_idx, crashing_request) = outcome.crashing_request.unzip();
+    let crash = analyze_crash(status, child.id(), &asan_log_file_prefix, || {
+        gdb_backtrace(&input, target, None)
+    })?;
+    Ok(Some(ReproductionInfo {
+        input_id,
+        input: Some(input),
+        crashing_request_idx,
+        crashing_request,
+        companion: false,
+        failure: crash.failure,
+        asan_summary: crash.asan_summary,
+        asan_classification: crash.asan_classification,
+        stack_trace: crash.stack_trace,
+        backtrace: crash.backtrace,
+        timed_out_requests: outcome.timed_out_requests,
+    }))
+}
+
+/// Runs the companion server serving `documents` on the whole session of `input` at once,
+/// as the fuzzer does, and reports its crash.
+///
+/// Like in the fuzzer, the companion failing to exit in time is not reported.
+fn reproduce_companion(
+    input_id: String,
+    input: LspInput,
+    companion: &TargetInvocation,
+    documents: &[String],
+    timeouts: TimeoutOptions,
+) -> Result<Option<ReproductionInfo>, anyhow::Error> {
+    let documents = Some(documents);
+    let temp_directory = tempfile::tempdir().context("Creating temporary working directory")?;
+    let workspace_dir = temp_directory.path();
+    let asan_log_file_prefix = workspace_dir.join(ASAN_LOG_FN);
+    input
+        .setup_workspace(workspace_dir)
+        .context("Setting up workspace_dir")?;
+    let mut child = companion
+        .command(
+            workspace_dir,
+            [(
+                "ASAN_OPTIONS",
+                asan_options(&asan_log_file_prefix).join(":"),
+            )],
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Starting companion process")?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .context("Child should have its stdin piped")?;
+    send_to_target(&mut stdin, &session_bytes(&input, workspace_dir, documents))?;
+    drop(stdin);
+    let exit_timeout = Duration::from_secs(timeouts.exit_timeout);
+    let Some(status) = wait_for_exit(&mut child, exit_timeout).context("Waiting companion")? else {
+        warn!("Companion is still running after the session");
+        child.kill().context("Killing companion")?;
+        child.wait().context("Waiting companion")?;
         return Ok(None);
-    } else if let Some(signal) = status.signal() {
-        let signal_name = unsafe { CStr::from_ptr(libc::strsignal(signal)) };
-        let signal_name = signal_name.to_string_lossy();
-        info!("Target exited with signal: {}", signal_name);
+    };
+    info!("Companion exited with status: {:?}", status);
+    if status.success() {
+        info!("Companion exited successfully");
+        return Ok(None);
+    }
+    let crash = analyze_crash(status, child.id(), &asan_log_file_prefix, || {
+        gdb_backtrace(&input, companion, documents)
+    })?;
+    Ok(Some(ReproductionInfo {
+        input_id,
+        input: Some(input),
+        crashing_request_idx: None,
+        crashing_request: None,
+        companion: true,
+        failure: crash.failure,
+        asan_summary: crash.asan_summary,
+        asan_classification: crash.asan_classification,
+        stack_trace: crash.stack_trace,
+        backtrace: crash.backtrace,
+        timed_out_requests: Vec::new(),
+    }))
+}
+
+/// The bytes of the whole session of `input`, keeping only the messages for a companion
+/// serving `documents` if given.
+pub(in crate::cli) fn session_bytes(
+    input: &LspInput,
+    workspace_dir: &Path,
+    documents: Option<&[String]>,
+) -> Vec<u8> {
+    let bytes = input.request_bytes(workspace_dir);
+    match documents {
+        Some(documents) => companion_stream(&bytes, documents),
+        None => bytes,
     }
+}
+
+/// How a server crashed, told from its exit status and its sanitizer report.
+struct CrashAnalysis {
+    failure: Failure,
+    asan_summary: String,
+    asan_classification: Option<ExecutionClass>,
+    stack_trace: Vec<StacktraceEntry>,
+    backtrace: Option<String>,
+}
 
-    let pid = child.id();
-    let asan_log_file_path = asan_log_file_prefix.with_extension(child.id().to_string());
+/// Analyzes the crash of the process `pid` exiting with `status`, taking a backtrace with
+/// `gdb_backtrace` if it was killed by a signal without a sanitizer report.
+fn analyze_crash(
+    status: ExitStatus,
+    pid: u32,
+    asan_log_file_prefix: &Path,
+    gdb_backtrace: impl FnOnce() -> anyhow::Result<Option<String>>,
+) -> anyhow::Result<CrashAnalysis> {
+    let signal = status.signal().map(|signal| {
+        let signal_name = unsafe { CStr::from_ptr(libc::strsignal(signal)) };
+        let signal_name = signal_name.to_string_lossy().into_owned();
+        info!("Server exited with signal: {}", signal_name);
+        (signal, signal_name)
+    });
+
+    let asan_log_file_path = asan_log_file_prefix.with_extension(pid.to_string());
     let mut asan_log = match File::open(&asan_log_file_path) {
         Ok(file) => BufReader::new(file),
         Err(e) if e.kind() == io::ErrorKind::NotFound => {
             warn!("ASAN log file not found");
-            return Ok(None);
+            let (failure, backtrace) = match signal {
+                Some((signal, name)) => {
+                    let backtrace = gdb_backtrace().context("Running gdb")?;
+                    (Failure::Signal { signal, name }, backtrace)
+                }
+                None => {
+                    let code = status.code().unwrap_or_default();
+                    (Failure::ExitCode { code }, None)
+                }
+            };
+            let stack_trace = backtrace
+                .as_deref()
+                .map(parse_gdb_backtrace)
+                .unwrap_or_default();
+            return Ok(CrashAnalysis {
+                failure,
+                asan_summary: String::new(),
+                asan_classification: None,
+                stack_trace,
+                backtrace,
+            });
         }
         Err(e) => {
             return Err(e).context("Opening ASAN log file");
@@ -146,19 +753,24 @@ fn reproduce(
         parse_asan_log(&mut asan_log, pid).context("Parsing ASAN logs")?;
     info!(?classification);
     info!(location = ?stack_trace.first());
-    let (crashing_request_idx, crashing_request) = crashing_request.unzip();
-    Ok(Some(ReproductionInfo {
-        input_id,
-        input: Some(input),
-        crashing_request_idx,
-        crashing_request,
+    Ok(CrashAnalysis {
+        failure: Failure::Sanitizer,
         asan_summary,
         asan_classification: classification,
         stack_trace,
-    }))
+        backtrace: None,
+    })
 }
 
-fn parse_asan_log<R: Read>(
+/// Parses the frames of a `gdb` backtrace, or returns no frames if it has none.
+fn parse_gdb_backtrace(backtrace: &str) -> Vec<StacktraceEntry> {
+    GdbStacktrace::extract_stacktrace(backtrace)
+        .and_then(|it| GdbStacktrace::parse_stacktrace(&it))
+        .map(|it| it.into_iter().map(Into::into).collect())
+        .unwrap_or_default()
+}
+
+pub(in crate::cli) fn parse_asan_log<R: Read>(
     asan_log: &mut R,
     pid: u32,
 ) -> Result<(String, Option<ExecutionClass>, Vec<StacktraceEntry>), anyhow::Error> {
@@ -188,7 +800,7 @@ fn parse_asan_log<R: Read>(
     Ok((asan_summary, classification, stack_trace))
 }
 
-fn asan_options(asan_log_file: &Path) -> Vec<Cow<'_, str>> {
+pub(in crate::cli) fn asan_options(asan_log_file: &Path) -> Vec<Cow<'_, str>> {
     let asan_log_file = asan_log_file
         .to_str()
         .expect("The temp path is not valid UTF-8");
@@ -214,15 +826,43 @@ fn asan_options(asan_log_file: &Path) -> Vec<Cow<'_, str>> {
     .collect()
 }
 
+/// How the target failed.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Failure {
+    /// The sanitizer reported an error.
+    Sanitizer,
+    /// The target was killed by a signal without a sanitizer report.
+    Signal { signal: i32, name: String },
+    /// The target exited with a non-zero status without a sanitizer report.
+    ExitCode { code: i32 },
+    /// The target did not exit after the whole session was sent.
+    Hang {
+        /// The last message sent to the target.
+        last_sent_idx: Option<usize>,
+        last_sent: Option<JsonRPCMessage>,
+        /// The phase of the session the target hung in.
+        phase: ExecutionPhase,
+    },
+}
+
 #[derive(Debug, Serialize)]
 pub struct ReproductionInfo {
     pub input_id: String,
     pub input: Option<LspInput>,
     pub crashing_request_idx: Option<usize>,
     pub crashing_request: Option<JsonRPCMessage>,
+    /// Whether the crash is of the companion server rather than of the target.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub companion: bool,
+    pub failure: Failure,
     pub asan_summary: String,
     pub asan_classification: Option<ExecutionClass>,
     pub stack_trace: Vec<StacktraceEntry>,
+    /// The raw stacks taken with a debugger if there is no sanitizer report.
+    pub backtrace: Option<String>,
+    /// The requests skipped because the target did not respond to them in time.
+    pub timed_out_requests: Vec<TimedOutRequest>,
 }
 
 #[derive(Debug, Serialize)]
@@ -6,7 +6,13 @@ use lsp_fuzz::lsp_input::LspInput;
 use rayon::iter::{ParallelBridge, ParallelIterator};
 use tracing::info;
 
-use crate::cli::{GlobalOptions, reproduce::reproduce};
+use crate::{
+    cli::{
+        GlobalOptions,
+        reproduce::{PacingOptions, TargetOptions, TimeoutOptions, reproduce},
+    },
+    fuzzing::FuzzerStateDir,
+};
 
 /// Reproduces crashes found during fuzzing (for a directory containing the inputs).
 #[derive(Debug, clap::Parser)]
@@ -15,18 +21,19 @@ pub struct ReproduceAll {
     #[clap(long, short)]
     solution_dir: PathBuf,
 
-    /// The path to the target executable.
-    #[clap(long, short)]
-    target_executable: PathBuf,
-
-    /// The path to the target executable.
-    #[clap(long, short)]
-    target_args: Vec<String>,
+    #[clap(flatten)]
+    target: TargetOptions,
 
     /// The path to the output file.
     #[clap(long, short)]
     output_file: PathBuf,
 
+    #[clap(flatten)]
+    pacing: PacingOptions,
+
+    #[clap(flatten)]
+    timeouts: TimeoutOptions,
+
     #[clap(long)]
     no_parallel: bool,
 
@@ -37,6 +44,8 @@ pub struct ReproduceAll {
 impl ReproduceAll {
     pub fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
         info!(?self);
+        let state_dir = FuzzerStateDir::of_solution_dir(&self.solution_dir);
+        let target = self.target.resolve(state_dir).context("Resolving target")?;
         let input_files =
             self.solution_dir
                 .read_dir()
@@ -44,6 +53,7 @@ impl ReproduceAll {
                 .map(Result::unwrap)
                 .filter(|it| {
                     it.metadata().is_ok_and(|it| it.is_file())
+                        && !it.file_name().to_string_lossy().starts_with('.')
                         && self.input_prefix.as_ref().is_none_or(|prefix| {
                             it.file_name().to_string_lossy().starts_with(prefix)
                         })
@@ -63,8 +73,9 @@ impl ReproduceAll {
             reproduce(
                 input_id,
                 lsp_input,
-                &self.target_executable,
-                &self.target_args,
+                &target,
+                self.pacing.into(),
+                self.timeouts,
                 false,
             )
             .with_context(|| format!("Reproducing crash for {}", input_file.display()))
@@ -5,7 +5,13 @@ use libafl::inputs::Input;
 use lsp_fuzz::lsp_input::LspInput;
 use tracing::info;
 
-use crate::cli::{GlobalOptions, reproduce::reproduce};
+use crate::{
+    cli::{
+        GlobalOptions,
+        reproduce::{PacingOptions, TargetOptions, TimeoutOptions, reproduce},
+    },
+    fuzzing::FuzzerStateDir,
+};
 
 /// Reproduces crashes found during fuzzing (for a directory containing the inputs).
 #[derive(Debug, clap::Parser)]
@@ -14,17 +20,18 @@ pub struct ReproduceOne {
     #[clap(long, short)]
     input_file: PathBuf,
 
-    /// The path to the target executable.
-    #[clap(long, short)]
-    target_executable: PathBuf,
-
-    /// The path to the target executable.
-    #[clap(long, short)]
-    target_args: Vec<String>,
+    #[clap(flatten)]
+    target: TargetOptions,
 
     /// The path to the output file.
     #[clap(long, short)]
     output_file: PathBuf,
+
+    #[clap(flatten)]
+    pacing: PacingOptions,
+
+    #[clap(flatten)]
+    timeouts: TimeoutOptions,
 }
 
 impl ReproduceOne {
@@ -36,13 +43,19 @@ impl ReproduceOne {
             .to_str()
             .context("The file name is not valid UTF-8")?
             .to_owned();
+        let state_dir = self
+            .input_file
+            .parent()
+            .and_then(FuzzerStateDir::of_solution_dir);
+        let target = self.target.resolve(state_dir).context("Resolving target")?;
         let lsp_input = LspInput::from_file(&self.input_file).context("Loading input file")?;
         info!("Reproducing crash for input {}", input_id);
         let result = reproduce(
             input_id,
             lsp_input,
-            &self.target_executable,
-            &self.target_args,
+            &target,
+            self.pacing.into(),
+            self.timeouts,
             true,
         )
         .with_context(|| format!("Reproducing crash for {}", self.input_file.display()))?;
@@ -0,0 +1,259 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use libafl::inputs::Input;
+use lsp_fuzz::lsp_input::LspInput;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tracing::{info, warn};
+
+use super::{
+    GlobalOptions,
+    reproduce::{
+        Failure, PacingOptions, ReproductionInfo, TargetOptions, TimeoutOptions, reproduce,
+    },
+};
+use crate::fuzzing::{FuzzerStateDir, TargetInvocation};
+
+/// The report of a bundle written by `postprocess`.
+const REPRODUCTION_REPORT: &str = "reproduction.json";
+
+/// The report of the replay against the release build written into each bundle.
+const RELEASE_REPORT: &str = "release_reproduction.json";
+
+/// Replays the crash bundles written by `postprocess` against a release build of the target,
+/// e.g., one built without sanitizers, to tell crashes reproducible in production builds
+/// from sanitizer-only findings.
+///
+/// The replay of each reproduced crash is written to `release_reproduction.json` in its bundle,
+/// and each crash in `summary.md` is annotated with whether it reproduces on the release build.
+#[derive(Debug, clap::Parser)]
+pub(super) struct SanityReplayCommand {
+    /// The output directory of `postprocess`.
+    #[clap(long, short)]
+    triage_dir: PathBuf,
+
+    /// The state directory of the campaign, whose recorded target invocation is used
+    /// with the executable replaced by `--target-executable`.
+    #[clap(long, short)]
+    campaign_dir: Option<PathBuf>,
+
+    /// The release build is given with `--target-executable`.
+    #[clap(flatten)]
+    target: TargetOptions,
+
+    #[clap(flatten)]
+    pacing: PacingOptions,
+
+    #[clap(flatten)]
+    timeouts: TimeoutOptions,
+}
+
+impl SanityReplayCommand {
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        let state_dir = self.campaign_dir.as_ref().map(FuzzerStateDir::from);
+        let target = self
+            .target
+            .clone()
+            .resolve(state_dir)
+            .context("Resolving release target")?;
+        let bundles = crash_bundles(&self.triage_dir)?;
+        info!(
+            bundles = bundles.len(),
+            "Replaying crashes on the release build"
+        );
+
+        let statuses: BTreeMap<_, _> = bundles
+            .into_par_iter()
+            .map(|bundle_dir| {
+                let bundle_name = bundle_dir
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                let status = match self.replay(&bundle_dir, &target) {
+                    Ok(status) => status,
+                    Err(err) => {
+                        warn!(bundle = bundle_name, "Failed to replay crash: {err:?}");
+                        "replay failed".to_owned()
+                    }
+                };
+                info!(bundle = bundle_name, status, "Replayed crash");
+                (bundle_name, status)
+            })
+            .collect();
+
+        let summary_file = self.triage_dir.join("summary.md");
+        let summary = fs::read_to_string(&summary_file).context("Reading summary")?;
+        fs::write(&summary_file, annotate_summary(&summary, &statuses))
+            .context("Writing summary")?;
+        Ok(())
+    }
+
+    /// Replays the crash of `bundle_dir` against `target` and describes the outcome.
+    fn replay(&self, bundle_dir: &Path, target: &TargetInvocation) -> anyhow::Result<String> {
+        let input_file = bundle_input(bundle_dir)?;
+        let input_id = input_file
+            .file_name()
+            .context("The input has no file name")?
+            .to_string_lossy()
+            .into_owned();
+        let input = LspInput::from_file(&input_file).context("Loading input file")?;
+        let release_report = bundle_dir.join(RELEASE_REPORT);
+        let Some(info) = reproduce(
+            input_id,
+            input,
+            target,
+            self.pacing.into(),
+            self.timeouts,
+            false,
+        )?
+        else {
+            if release_report.exists() {
+                fs::remove_file(&release_report).context("Removing stale release report")?;
+            }
+            return Ok("not reproducible (sanitizer-only)".to_owned());
+        };
+        let report = File::create(&release_report).context("Creating release report")?;
+        serde_json::to_writer_pretty(report, &info).context("Writing release report")?;
+        Ok(format!("reproducible ({})", failure_description(&info)))
+    }
+}
+
+/// The bundle directories in `triage_dir`, i.e., those with a reproduction report.
+fn crash_bundles(triage_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut bundles = Vec::new();
+    for entry in fs::read_dir(triage_dir).context("Reading triage directory")? {
+        let path = entry.context("Reading triage directory")?.path();
+        if path.join(REPRODUCTION_REPORT).is_file() {
+            bundles.push(path);
+        }
+    }
+    bundles.sort();
+    Ok(bundles)
+}
+
+/// The reproducing input of a bundle, i.e., its only file that is not a report.
+///
+/// Should the bundle have several such files, the first one by name is taken.
+fn bundle_input(bundle_dir: &Path) -> anyhow::Result<PathBuf> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(bundle_dir).context("Reading bundle directory")? {
+        let entry = entry.context("Reading bundle directory")?;
+        let name = entry.file_name();
+        if entry.metadata().is_ok_and(|it| it.is_file())
+            && name != REPRODUCTION_REPORT
+            && name != RELEASE_REPORT
+        {
+            inputs.push(entry.path());
+        }
+    }
+    inputs.sort();
+    inputs.into_iter().next().context("The bundle has no input")
+}
+
+fn failure_description(info: &ReproductionInfo) -> String {
+    let description = match &info.failure {
+        Failure::Sanitizer => "sanitizer report".to_owned(),
+        Failure::Signal { name, .. } => name.clone(),
+        Failure::ExitCode { code } => format!("exit code {code}"),
+        Failure::Hang { phase, .. } if phase.is_teardown() => "shutdown hang".to_owned(),
+        Failure::Hang { .. } => "hang".to_owned(),
+    };
+    if info.companion {
+        format!("companion {description}")
+    } else {
+        description
+    }
+}
+
+/// Adds the release status of each crash after its heading in the `summary.md` of
+/// `postprocess`, replacing the statuses of an earlier replay.
+fn annotate_summary(summary: &str, statuses: &BTreeMap<String, String>) -> String {
+    const STATUS_PREFIX: &str = "- Release build: ";
+    let mut annotated = String::with_capacity(summary.len());
+    let mut pending = None;
+    for line in summary.lines() {
+        if line.starts_with(STATUS_PREFIX) {
+            continue;
+        }
+        annotated.push_str(line);
+        annotated.push('\n');
+        if let Some(bundle_name) = line
+            .strip_prefix("## `")
+            .and_then(|it| it.split_once('`'))
+            .map(|(name, _)| name)
+        {
+            pending = statuses.get(bundle_name);
+        } else if line.is_empty()
+            && let Some(status) = pending.take()
+        {
+            annotated.push_str(STATUS_PREFIX);
+            annotated.push_str(status);
+            annotated.push('\n');
+        }
+    }
+    annotated
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::annotate_summary;
+
+    const SUMMARY: &str = "\
+# Crashes
+
+## `crash-1` (heap-buffer-overflow)
+
+- Input: `crash-1/input`
+
+## `crash-2` (SIGSEGV)
+
+- Input: `crash-2/input`
+";
+
+    fn statuses(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|&(name, status)| (name.to_owned(), status.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn annotate_crashes_with_release_status() {
+        let annotated = annotate_summary(SUMMARY, &statuses(&[("crash-1", "reproduces")]));
+        assert_eq!(
+            annotated,
+            "\
+# Crashes
+
+## `crash-1` (heap-buffer-overflow)
+
+- Release build: reproduces
+- Input: `crash-1/input`
+
+## `crash-2` (SIGSEGV)
+
+- Input: `crash-2/input`
+"
+        );
+    }
+
+    #[test]
+    fn replace_statuses_of_earlier_replay() {
+        let earlier = annotate_summary(
+            SUMMARY,
+            &statuses(&[("crash-1", "reproduces"), ("crash-2", "reproduces")]),
+        );
+        let latest = statuses(&[("crash-2", "does not reproduce")]);
+        assert_eq!(
+            annotate_summary(&earlier, &latest),
+            annotate_summary(SUMMARY, &latest)
+        );
+    }
+}
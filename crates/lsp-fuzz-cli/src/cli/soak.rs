@@ -0,0 +1,697 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    mem,
+    num::NonZero,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+use libafl::{
+    corpus::InMemoryCorpus,
+    feedbacks::ConstFeedback,
+    generators::Generator,
+    state::{HasRand, StdState},
+};
+use libafl_bolts::rands::{Rand, StdRand};
+use lsp_fuzz::{
+    execution::workspace_observer::HasWorkspace,
+    lsp::json_rpc::{JsonRPCMessage, MessageId},
+    lsp_input::{LspInput, LspInputGenerator},
+    text_document::generation::GrammarContextLookup,
+};
+use lsp_fuzz_grammars::Language;
+use serde::Serialize;
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use super::{
+    GlobalOptions, parse_duration, parse_hash_map,
+    reproduce::{ASAN_LOG_FN, TargetOptions, asan_options},
+};
+use crate::{
+    fuzzing::{FuzzerStateDir, TargetInvocation},
+    language_fragments::{GrammarOverrides, load_grammar_lookup},
+};
+
+type SoakState = StdState<InMemoryCorpus<LspInput>, LspInput, StdRand, InMemoryCorpus<LspInput>>;
+
+/// Drives a single session of the target for hours with a growing workspace,
+/// tracking its memory and latency over time.
+///
+/// Every `--add-interval`, a generated workspace is added to a new subdirectory of the session
+/// workspace and its messages are sent; every `--edit-interval`, an open document is edited and
+/// queried. Leaks and fragmentation show as growth in the samples written to `samples.jsonl`
+/// in the output directory, which per-input executions are too short to reveal.
+#[derive(Debug, clap::Parser)]
+pub(super) struct SoakCommand {
+    #[clap(long, value_parser = parse_hash_map::<Language, PathBuf>)]
+    language_fragments: HashMap<Language, PathBuf>,
+
+    /// Merge the curated seed fragments of the languages, as `fuzz --seed-fragment-packs` does.
+    #[clap(long)]
+    seed_fragment_packs: bool,
+
+    #[clap(flatten)]
+    grammar_overrides: GrammarOverrides,
+
+    /// Relative weights of the languages of the added files (e.g., `C=9,CPlusPlus=1`).
+    #[clap(long, value_parser = parse_hash_map::<Language, usize>, default_value = "")]
+    language_weights: HashMap<Language, usize>,
+
+    /// The state directory of a campaign, whose recorded target invocation is used.
+    #[clap(long, short)]
+    campaign_dir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    target: TargetOptions,
+
+    /// The directory to write the samples, the summary, and the output of the target to.
+    #[clap(long, short)]
+    output_dir: PathBuf,
+
+    /// How long to drive the session (e.g., `6h`).
+    #[clap(long, value_parser = parse_duration, default_value = "4h")]
+    duration: Duration,
+
+    /// How often to add a generated workspace to the session (e.g., `1m`).
+    #[clap(long, value_parser = parse_duration, default_value = "1m")]
+    add_interval: Duration,
+
+    /// How often to edit an open document (e.g., `500ms`).
+    #[clap(long, value_parser = parse_duration, default_value = "500ms")]
+    edit_interval: Duration,
+
+    /// How often to sample the memory and latency of the target (e.g., `10s`).
+    #[clap(long, value_parser = parse_duration, default_value = "10s")]
+    sample_interval: Duration,
+
+    /// How long to wait for the response to each request before counting it as timed out
+    /// and cancelling it (e.g., `30s`).
+    #[clap(long, value_parser = parse_duration, default_value = "30s")]
+    response_timeout: Duration,
+}
+
+/// A measurement of the target during the session.
+#[derive(Debug, Clone, Default, Serialize)]
+struct SoakSample {
+    elapsed_secs: u64,
+    /// Resident set size in KiB.
+    rss_kib: Option<u64>,
+    /// Peak resident set size in KiB.
+    peak_rss_kib: Option<u64>,
+    threads: Option<u64>,
+    open_fds: Option<usize>,
+    files_added: usize,
+    open_documents: usize,
+    edits: usize,
+    /// Latencies of the requests answered since the previous sample, in milliseconds.
+    latency_p50_ms: Option<u64>,
+    latency_p95_ms: Option<u64>,
+    latency_max_ms: Option<u64>,
+    /// Requests not answered within the response timeout since the previous sample.
+    timed_out: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct SoakSummary {
+    elapsed_secs: u64,
+    files_added: usize,
+    edits: usize,
+    requests: usize,
+    timed_out: usize,
+    /// How the target exited, if it did before the end of the session.
+    exit: Option<String>,
+    first_sample: Option<SoakSample>,
+    last_sample: Option<SoakSample>,
+    /// The growth of the resident set size between the first and the last sample.
+    rss_growth_kib_per_hour: Option<f64>,
+}
+
+/// A document opened during the session, which is edited with full-content changes.
+struct OpenDocument {
+    uri: String,
+    content: Vec<u8>,
+    version: u64,
+}
+
+impl SoakCommand {
+    /// Versions of the edits start above those of the generated messages.
+    const FIRST_EDIT_VERSION: u64 = 1 << 20;
+    /// The requests sent after each edit to measure the latency of the target.
+    const PROBE_METHODS: &[&str] = &[
+        "textDocument/documentSymbol",
+        "textDocument/foldingRange",
+        "textDocument/hover",
+        "textDocument/completion",
+        "textDocument/semanticTokens/full",
+    ];
+
+    pub(super) fn run(self, global_options: GlobalOptions) -> anyhow::Result<()> {
+        let state_dir = self.campaign_dir.as_ref().map(FuzzerStateDir::from);
+        let target = self
+            .target
+            .clone()
+            .resolve(state_dir)
+            .context("Resolving target")?;
+        self.grammar_overrides
+            .apply_highlights()
+            .context("Overriding highlight queries")?;
+        let grammar_lookup = load_grammar_lookup(
+            &self.language_fragments,
+            self.seed_fragment_packs,
+            &self.grammar_overrides,
+        )
+        .context("Creating grammar context")?;
+        fs::create_dir_all(&self.output_dir).context("Creating output directory")?;
+        let random_seed = global_options
+            .random_seed
+            .unwrap_or_else(libafl_bolts::current_nanos);
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state: SoakState = StdState::new(
+            StdRand::with_seed(random_seed),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .context("Creating state")?;
+        let mut session = SoakSession::start(&target, &self.output_dir)?;
+        let summary = match self.drive(&mut session, &grammar_lookup, &mut state) {
+            Ok(summary) => session.finish(summary)?,
+            Err(err) => {
+                session.child.kill().ok();
+                return Err(err);
+            }
+        };
+
+        let summary_file =
+            File::create(self.output_dir.join("summary.json")).context("Creating summary file")?;
+        serde_json::to_writer_pretty(summary_file, &summary).context("Writing summary")?;
+        if let Some(growth) = summary.rss_growth_kib_per_hour {
+            info!("RSS grew by {growth:.0} KiB per hour");
+        }
+        if let Some(exit) = summary.exit {
+            bail!("The target exited during the session: {exit}");
+        }
+        Ok(())
+    }
+
+    fn drive(
+        &self,
+        session: &mut SoakSession,
+        grammar_lookup: &GrammarContextLookup,
+        state: &mut SoakState,
+    ) -> anyhow::Result<SoakSummary> {
+        let mut generator = LspInputGenerator::new(grammar_lookup)
+            .with_language_weights(self.language_weights.clone());
+        let initial = generator.generate(state).context("Generating input")?;
+        for msg in initial.message_sequence() {
+            let jsonrpc = msg.into_json_rpc(&mut session.next_id, Some(&session.workspace_uri));
+            if matches!(
+                jsonrpc.method().map(AsRef::as_ref),
+                Some("initialize" | "initialized")
+            ) {
+                session.send(&jsonrpc)?;
+            }
+        }
+
+        let mut samples = BufWriter::new(
+            File::create(self.output_dir.join("samples.jsonl")).context("Creating samples file")?,
+        );
+        let mut documents = Vec::new();
+        let mut summary = SoakSummary {
+            elapsed_secs: 0,
+            files_added: 0,
+            edits: 0,
+            requests: 0,
+            timed_out: 0,
+            exit: None,
+            first_sample: None,
+            last_sample: None,
+            rss_growth_kib_per_hour: None,
+        };
+        let mut next_add = session.started;
+        let mut next_edit = session.started + self.edit_interval;
+        let mut next_sample = session.started + self.sample_interval;
+        let mut latencies = Vec::new();
+        let mut timed_out = 0;
+        while session.started.elapsed() < self.duration {
+            if let Some(status) = session.child.try_wait().context("Waiting target")? {
+                summary.exit = Some(status.to_string());
+                break;
+            }
+            let now = Instant::now();
+            if now >= next_add {
+                match generator.generate(state) {
+                    Ok(input) => {
+                        summary.files_added +=
+                            session.add_workspace(&input, summary.files_added, &mut documents)?;
+                    }
+                    Err(err) => warn!("Failed to generate a workspace: {err}"),
+                }
+                next_add = now + self.add_interval;
+            }
+            if now >= next_edit && !documents.is_empty() {
+                edit_document(state.rand_mut(), &mut documents, session)?;
+                summary.edits += 1;
+                next_edit = now + self.edit_interval;
+            }
+            timed_out += session.expire_requests(self.response_timeout)?;
+            if now >= next_sample {
+                let sample = SoakSample {
+                    elapsed_secs: session.started.elapsed().as_secs(),
+                    files_added: summary.files_added,
+                    open_documents: documents.len(),
+                    edits: summary.edits,
+                    timed_out: mem::take(&mut timed_out),
+                    ..latency_sample(mem::take(&mut latencies))
+                };
+                record_sample(&mut samples, sample, session.child.id(), &mut summary)?;
+                next_sample = now + self.sample_interval;
+            }
+
+            let deadline = [next_add, next_edit, next_sample]
+                .into_iter()
+                .min()
+                .unwrap_or(now);
+            session.receive_until(deadline, &mut latencies);
+        }
+        summary.timed_out += timed_out;
+        summary.elapsed_secs = session.started.elapsed().as_secs();
+        summary.requests = session.requests;
+        summary.rss_growth_kib_per_hour =
+            rss_growth(summary.first_sample.as_ref(), summary.last_sample.as_ref());
+        Ok(summary)
+    }
+}
+
+/// Completes `sample` with the usage of the process `pid`, then writes and logs it.
+fn record_sample(
+    samples: &mut impl Write,
+    sample: SoakSample,
+    pid: u32,
+    summary: &mut SoakSummary,
+) -> anyhow::Result<()> {
+    let usage = process_usage(pid);
+    let sample = SoakSample {
+        rss_kib: usage.rss_kib,
+        peak_rss_kib: usage.peak_rss_kib,
+        threads: usage.threads,
+        open_fds: usage.open_fds,
+        ..sample
+    };
+    info!(
+        elapsed = sample.elapsed_secs,
+        rss_kib = sample.rss_kib,
+        p95_ms = sample.latency_p95_ms,
+        files = sample.files_added,
+        "Soak sample"
+    );
+    serde_json::to_writer(&mut *samples, &sample).context("Writing sample")?;
+    writeln!(samples).context("Writing sample")?;
+    samples.flush().context("Writing sample")?;
+    summary.timed_out += sample.timed_out;
+    summary.first_sample.get_or_insert_with(|| sample.clone());
+    summary.last_sample = Some(sample);
+    Ok(())
+}
+
+/// Replaces, duplicates, or deletes a random line of a random open document,
+/// then sends a request on it to measure the latency of the target.
+fn edit_document(
+    rand: &mut StdRand,
+    documents: &mut [OpenDocument],
+    session: &mut SoakSession,
+) -> anyhow::Result<()> {
+    let donor_idx = rand.below(NonZero::new(documents.len()).context("No open document")?);
+    let donor: Vec<u8> = random_line(rand, &documents[donor_idx].content).to_vec();
+    let idx = rand.below(NonZero::new(documents.len()).context("No open document")?);
+    let document = &mut documents[idx];
+    let mut lines: Vec<&[u8]> = document.content.split(|&it| it == b'\n').collect();
+    let line = rand.below(NonZero::new(lines.len()).context("Empty line list")?);
+    match rand.below(NonZero::new(3).unwrap()) {
+        0 => lines[line] = &donor,
+        1 => lines.insert(line, lines[line]),
+        _ if lines.len() > 1 => {
+            lines.remove(line);
+        }
+        _ => {}
+    }
+    document.content = lines.join(&b'\n');
+    document.version += 1;
+    let did_change = JsonRPCMessage::notification(
+        Cow::Borrowed("textDocument/didChange"),
+        json!({
+            "textDocument": { "uri": document.uri, "version": document.version },
+            "contentChanges": [{ "text": String::from_utf8_lossy(&document.content) }],
+        }),
+    );
+    session.send(&did_change)?;
+
+    let method = *rand
+        .choose(SoakCommand::PROBE_METHODS)
+        .context("No probe method")?;
+    let line_count = document.content.split(|&it| it == b'\n').count();
+    let position_line = rand.below(NonZero::new(line_count).context("Empty document")?);
+    let params = json!({
+        "textDocument": { "uri": document.uri },
+        "position": { "line": position_line, "character": 0 },
+    });
+    session.request(method, params)
+}
+
+/// The document opened by `jsonrpc` if it is a `textDocument/didOpen` notification.
+fn opened_document(jsonrpc: &JsonRPCMessage) -> Option<OpenDocument> {
+    let JsonRPCMessage::Notification { method, params, .. } = jsonrpc else {
+        return None;
+    };
+    if method != "textDocument/didOpen" {
+        return None;
+    }
+    let document = &params["textDocument"];
+    Some(OpenDocument {
+        uri: document["uri"].as_str()?.to_owned(),
+        content: document["text"].as_str()?.as_bytes().to_vec(),
+        version: SoakCommand::FIRST_EDIT_VERSION,
+    })
+}
+
+fn random_line<'a>(rand: &mut StdRand, content: &'a [u8]) -> &'a [u8] {
+    let lines: Vec<_> = content.split(|&it| it == b'\n').collect();
+    rand.choose(lines).unwrap_or_default()
+}
+
+/// A sample with the latency percentiles of `latencies`.
+fn latency_sample(mut latencies: Vec<u64>) -> SoakSample {
+    latencies.sort_unstable();
+    SoakSample {
+        latency_p50_ms: percentile(&latencies, 50),
+        latency_p95_ms: percentile(&latencies, 95),
+        latency_max_ms: latencies.last().copied(),
+        ..SoakSample::default()
+    }
+}
+
+/// The `pct`-th percentile of the sorted `values`.
+fn percentile(values: &[u64], pct: usize) -> Option<u64> {
+    let idx = (values.len() * pct / 100).min(values.len().checked_sub(1)?);
+    values.get(idx).copied()
+}
+
+#[allow(
+    clippy::cast_precision_loss,
+    reason = "Memory sizes and durations are far below 2^52"
+)]
+fn rss_growth(first: Option<&SoakSample>, last: Option<&SoakSample>) -> Option<f64> {
+    let (first, last) = (first?, last?);
+    let hours = last.elapsed_secs.checked_sub(first.elapsed_secs)? as f64 / 3600.0;
+    if hours <= 0.0 {
+        return None;
+    }
+    Some((last.rss_kib? as f64 - first.rss_kib? as f64) / hours)
+}
+
+/// Reads the memory usage, the threads, and the open files of the process `pid` from `/proc`.
+fn process_usage(pid: u32) -> SoakSample {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).unwrap_or_default();
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|it| it.strip_prefix(name))
+            .and_then(|it| it.trim().trim_end_matches("kB").trim().parse().ok())
+    };
+    SoakSample {
+        rss_kib: field("VmRSS:"),
+        peak_rss_kib: field("VmHWM:"),
+        threads: field("Threads:"),
+        open_fds: fs::read_dir(format!("/proc/{pid}/fd"))
+            .ok()
+            .map(Iterator::count),
+        ..SoakSample::default()
+    }
+}
+
+/// A running session of the target.
+struct SoakSession {
+    child: Child,
+    stdin: Arc<Mutex<ChildStdin>>,
+    responses: Receiver<(MessageId, Instant)>,
+    pending: HashMap<MessageId, Instant>,
+    workspace: tempfile::TempDir,
+    workspace_uri: String,
+    next_id: usize,
+    requests: usize,
+    started: Instant,
+}
+
+impl SoakSession {
+    fn start(target: &TargetInvocation, output_dir: &Path) -> anyhow::Result<Self> {
+        let workspace = tempfile::tempdir().context("Creating workspace")?;
+        let asan_log_file_prefix = output_dir.join(ASAN_LOG_FN);
+        let stderr = File::create(output_dir.join("stderr.log")).context("Creating log")?;
+        let mut child = target
+            .command(
+                workspace.path(),
+                [(
+                    "ASAN_OPTIONS",
+                    asan_options(&asan_log_file_prefix).join(":"),
+                )],
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr)
+            .spawn()
+            .context("Starting target process")?;
+        let stdin = Arc::new(Mutex::new(
+            child
+                .stdin
+                .take()
+                .context("Child should have its stdin piped")?,
+        ));
+        let stdout = child
+            .stdout
+            .take()
+            .context("Child should have its stdout piped")?;
+        let workspace_uri = format!(
+            "file://{}/",
+            workspace
+                .path()
+                .to_str()
+                .context("The workspace path is not valid UTF-8")?
+        );
+        let (sender, responses) = mpsc::channel();
+        let reply_to = Arc::clone(&stdin);
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            loop {
+                match JsonRPCMessage::read_lsp_payload(&mut reader) {
+                    Ok(JsonRPCMessage::Response { id: Some(id), .. }) => {
+                        if sender.send((id, Instant::now())).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(JsonRPCMessage::Request {
+                        id, method, params, ..
+                    }) => {
+                        // Servers may block on their requests, e.g., `workspace/configuration`.
+                        let result = if method == "workspace/configuration" {
+                            let items = params["items"].as_array().map_or(0, Vec::len);
+                            Value::Array(vec![Value::Null; items])
+                        } else {
+                            Value::Null
+                        };
+                        let reply = JsonRPCMessage::response(Some(id), Some(result), None);
+                        if let Ok(mut stdin) = reply_to.lock() {
+                            stdin.write_all(&reply.to_lsp_payload()).ok();
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                    Err(e) => {
+                        warn!("Failed to read message from target, ignoring its output: {e}");
+                        std::io::copy(&mut reader, &mut std::io::sink()).ok();
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            responses,
+            pending: HashMap::new(),
+            workspace,
+            workspace_uri,
+            next_id: 0,
+            requests: 0,
+            started: Instant::now(),
+        })
+    }
+
+    /// Sends `jsonrpc`, tracking the response if it is a request.
+    fn send(&mut self, jsonrpc: &JsonRPCMessage) -> anyhow::Result<()> {
+        if let JsonRPCMessage::Request { id, .. } = jsonrpc {
+            self.pending.insert(id.clone(), Instant::now());
+            self.requests += 1;
+        }
+        let mut stdin = self
+            .stdin
+            .lock()
+            .map_err(|_| anyhow::anyhow!("The stdin of the target is poisoned"))?;
+        match stdin.write_all(&jsonrpc.to_lsp_payload()) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+            Err(e) => Err(e).context("Sending message to target"),
+        }
+    }
+
+    fn request(&mut self, method: &'static str, params: Value) -> anyhow::Result<()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&JsonRPCMessage::request(id, Cow::Borrowed(method), params))
+    }
+
+    /// Writes the workspace of `input` to a new subdirectory and sends its messages,
+    /// returning the number of files added.
+    fn add_workspace(
+        &mut self,
+        input: &LspInput,
+        serial: usize,
+        documents: &mut Vec<OpenDocument>,
+    ) -> anyhow::Result<usize> {
+        let relative = format!("soak/{serial}/");
+        input
+            .setup_workspace(&self.workspace.path().join(&relative))
+            .context("Setting up workspace")?;
+        let uri = format!("{}{relative}", self.workspace_uri);
+        for msg in input.message_sequence() {
+            let jsonrpc = msg.into_json_rpc(&mut self.next_id, Some(&uri));
+            if matches!(
+                jsonrpc.method().map(AsRef::as_ref),
+                Some("initialize" | "initialized" | "shutdown" | "exit")
+            ) {
+                continue;
+            }
+            self.send(&jsonrpc)?;
+            // Only the documents opened by the messages are edited afterwards.
+            if let Some(document) = opened_document(&jsonrpc) {
+                documents.push(document);
+            }
+        }
+        Ok(input.workspace.iter_files().count())
+    }
+
+    /// Receives responses until `deadline`, recording their latencies in milliseconds.
+    fn receive_until(&mut self, deadline: Instant, latencies: &mut Vec<u64>) {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.responses.recv_timeout(remaining) {
+                Ok((id, received)) => {
+                    if let Some(sent) = self.pending.remove(&id) {
+                        let latency = received.saturating_duration_since(sent).as_millis();
+                        latencies.push(u64::try_from(latency).unwrap_or(u64::MAX));
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    // The target closed its output; keep the pace until it is reaped.
+                    std::thread::sleep(remaining);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Cancels the requests pending for longer than `timeout`, returning how many there were.
+    fn expire_requests(&mut self, timeout: Duration) -> anyhow::Result<usize> {
+        let expired: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, sent)| sent.elapsed() > timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            self.pending.remove(id);
+            let cancel =
+                JsonRPCMessage::notification(Cow::Borrowed("$/cancelRequest"), json!({ "id": id }));
+            self.send(&cancel)?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Shuts the target down, recording in `summary` how it exited if it failed.
+    fn finish(mut self, mut summary: SoakSummary) -> anyhow::Result<SoakSummary> {
+        if summary.exit.is_none() {
+            self.request("shutdown", Value::Null)?;
+            self.send(&JsonRPCMessage::notification(
+                Cow::Borrowed("exit"),
+                Value::Null,
+            ))?;
+            let deadline = Instant::now() + Duration::from_secs(30);
+            while self.child.try_wait().context("Waiting target")?.is_none() {
+                if Instant::now() >= deadline {
+                    warn!("Target is still running after exit, killing it");
+                    self.child.kill().context("Killing target")?;
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            let status = self.child.wait().context("Waiting target")?;
+            if !status.success() {
+                summary.exit = Some(status.to_string());
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SoakSample, percentile, rss_growth};
+
+    fn sample(elapsed_secs: u64, rss_kib: Option<u64>) -> SoakSample {
+        SoakSample {
+            elapsed_secs,
+            rss_kib,
+            ..SoakSample::default()
+        }
+    }
+
+    #[test]
+    fn percentile_of_sorted_values() {
+        let values: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&values, 50), Some(51));
+        assert_eq!(percentile(&values, 95), Some(96));
+        assert_eq!(percentile(&values, 100), Some(100));
+        assert_eq!(percentile(&[7], 95), Some(7));
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn rss_growth_per_hour() {
+        let first = sample(0, Some(1000));
+        let last = sample(1800, Some(1500));
+        assert_eq!(rss_growth(Some(&first), Some(&last)), Some(1000.0));
+        let shrunk = sample(3600, Some(400));
+        assert_eq!(rss_growth(Some(&first), Some(&shrunk)), Some(-600.0));
+    }
+
+    #[test]
+    fn rss_growth_needs_two_measured_samples() {
+        let first = sample(0, Some(1000));
+        assert_eq!(rss_growth(Some(&first), Some(&first)), None);
+        assert_eq!(rss_growth(Some(&first), None), None);
+        assert_eq!(rss_growth(Some(&first), Some(&sample(60, None))), None);
+        assert_eq!(rss_growth(Some(&sample(60, Some(1))), Some(&first)), None);
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, bail};
+use tracing::info;
+
+use super::GlobalOptions;
+
+const FUZZ_TARGET_TEMPLATE: &str = include_str!("../../res/fuzz_target_template.cc");
+
+const ASAN_FLAGS: &str = "-fsanitize=address -fno-omit-frame-pointer";
+const DEBUG_FLAGS: &str = "-g -O2";
+
+/// Builds an LSP server with AFL++ instrumentation
+#[derive(Debug, clap::Parser)]
+pub(super) struct TargetBuildCommand {
+    /// The source directory of the LSP server
+    #[clap(long, short)]
+    source_dir: PathBuf,
+
+    /// The directory to build in (CMake only)
+    #[clap(long, short)]
+    build_dir: Option<PathBuf>,
+
+    /// A preset for a well-known LSP server
+    #[clap(long, value_enum)]
+    profile: Option<TargetProfile>,
+
+    /// The build system of the LSP server, overriding the one of the profile
+    #[clap(long, value_enum)]
+    build_system: Option<BuildSystem>,
+
+    /// Build targets (CMake targets, Cargo packages, or Bazel labels)
+    #[clap(long)]
+    targets: Vec<String>,
+
+    /// The AFL++ compiler used for instrumentation
+    #[clap(long, value_enum, default_value_t = AflCompiler::ClangLto)]
+    compiler: AflCompiler,
+
+    /// The directory containing the AFL++ compilers, if they are not in `PATH`
+    #[clap(long, env = "AFL_PATH")]
+    afl_path: Option<PathBuf>,
+
+    /// Build without AddressSanitizer
+    #[clap(long)]
+    no_asan: bool,
+
+    /// Print the build commands without running them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Write a fuzz target template for persistent mode to the given path and exit
+    #[clap(long)]
+    write_template: Option<PathBuf>,
+
+    /// Extra arguments passed to the build system
+    #[clap(last = true)]
+    extra_args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum TargetProfile {
+    /// clangd from an LLVM monorepo checkout
+    Clangd,
+    /// rust-analyzer from its repository checkout
+    RustAnalyzer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum BuildSystem {
+    Cmake,
+    Cargo,
+    Bazel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum AflCompiler {
+    ClangFast,
+    ClangLto,
+}
+
+impl AflCompiler {
+    const fn executables(self) -> (&'static str, &'static str) {
+        match self {
+            Self::ClangFast => ("afl-clang-fast", "afl-clang-fast++"),
+            Self::ClangLto => ("afl-clang-lto", "afl-clang-lto++"),
+        }
+    }
+}
+
+/// A command of a build recipe, together with its environment.
+#[derive(Debug)]
+struct BuildStep {
+    program: String,
+    args: Vec<OsString>,
+    env: Vec<(&'static str, String)>,
+    current_dir: PathBuf,
+}
+
+impl BuildStep {
+    fn new(program: &str, current_dir: &Path) -> Self {
+        Self {
+            program: program.to_owned(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: current_dir.to_owned(),
+        }
+    }
+
+    fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    fn args<A: Into<OsString>>(mut self, args: impl IntoIterator<Item = A>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    fn env(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.env.push((key, value.into()));
+        self
+    }
+
+    fn run(&self) -> anyhow::Result<()> {
+        let status = Command::new(&self.program)
+            .args(&self.args)
+            .envs(self.env.iter().map(|(key, value)| (*key, value)))
+            .current_dir(&self.current_dir)
+            .status()
+            .with_context(|| format!("Running {}", self.program))?;
+        if !status.success() {
+            bail!("{} exited with {status}", self.program);
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for BuildStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(cd {} && ", self.current_dir.display())?;
+        for (key, value) in &self.env {
+            write!(f, "{key}='{value}' ")?;
+        }
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " '{}'", arg.to_string_lossy())?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl TargetBuildCommand {
+    #[allow(clippy::needless_pass_by_value)]
+    pub(super) fn run(self, _global_options: GlobalOptions) -> anyhow::Result<()> {
+        if let Some(template_path) = &self.write_template {
+            fs::write(template_path, FUZZ_TARGET_TEMPLATE)
+                .context("Writing fuzz target template")?;
+            info!(
+                "Fuzz target template written to {}",
+                template_path.display()
+            );
+            return Ok(());
+        }
+        let steps = self.recipe()?;
+        for step in &steps {
+            info!("{step}");
+            if !self.dry_run {
+                step.run()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compilers(&self) -> (String, String) {
+        let (cc, cxx) = self.compiler.executables();
+        match &self.afl_path {
+            Some(afl_path) => (
+                afl_path.join(cc).to_string_lossy().into_owned(),
+                afl_path.join(cxx).to_string_lossy().into_owned(),
+            ),
+            None => (cc.to_owned(), cxx.to_owned()),
+        }
+    }
+
+    fn compile_flags(&self) -> String {
+        if self.no_asan {
+            DEBUG_FLAGS.to_owned()
+        } else {
+            format!("{DEBUG_FLAGS} {ASAN_FLAGS}")
+        }
+    }
+
+    fn targets(&self, profile_default: &[&str]) -> Vec<String> {
+        if self.targets.is_empty() {
+            profile_default.iter().map(|&it| it.to_owned()).collect()
+        } else {
+            self.targets.clone()
+        }
+    }
+
+    fn recipe(&self) -> anyhow::Result<Vec<BuildStep>> {
+        let build_system = match (self.build_system, self.profile) {
+            (Some(build_system), _) => build_system,
+            (None, Some(TargetProfile::Clangd)) => BuildSystem::Cmake,
+            (None, Some(TargetProfile::RustAnalyzer)) => BuildSystem::Cargo,
+            (None, None) => bail!("Either --profile or --build-system must be specified"),
+        };
+        let steps = match build_system {
+            BuildSystem::Cmake => self.cmake_recipe(),
+            BuildSystem::Cargo => self.cargo_recipe(),
+            BuildSystem::Bazel => self.bazel_recipe(),
+        };
+        Ok(steps)
+    }
+
+    fn cmake_recipe(&self) -> Vec<BuildStep> {
+        let (cc, cxx) = self.compilers();
+        let flags = self.compile_flags();
+        let build_dir = self
+            .build_dir
+            .clone()
+            .unwrap_or_else(|| self.source_dir.join("build-afl"));
+        let (cmake_source, profile_options, default_targets): (_, &[&str], &[&str]) =
+            match self.profile {
+                Some(TargetProfile::Clangd) => (
+                    self.source_dir.join("llvm"),
+                    &[
+                        "-DLLVM_ENABLE_PROJECTS=clang;clang-tools-extra",
+                        "-DLLVM_TARGETS_TO_BUILD=Native",
+                        "-DLLVM_INCLUDE_TESTS=OFF",
+                        "-DLLVM_INCLUDE_BENCHMARKS=OFF",
+                    ],
+                    &["clangd"],
+                ),
+                _ => (self.source_dir.clone(), &[], &[]),
+            };
+        let linker_flags = if self.no_asan {
+            ""
+        } else {
+            "-fsanitize=address"
+        };
+        let configure = BuildStep::new("cmake", &self.source_dir)
+            .arg("-S")
+            .arg(cmake_source)
+            .arg("-B")
+            .arg(&build_dir)
+            .args(["-G", "Ninja", "-DCMAKE_BUILD_TYPE=RelWithDebInfo"])
+            .arg(format!("-DCMAKE_C_COMPILER={cc}"))
+            .arg(format!("-DCMAKE_CXX_COMPILER={cxx}"))
+            .arg(format!("-DCMAKE_C_FLAGS={flags}"))
+            .arg(format!("-DCMAKE_CXX_FLAGS={flags}"))
+            .arg(format!("-DCMAKE_EXE_LINKER_FLAGS={linker_flags}"))
+            .args(profile_options.iter().copied())
+            .args(&self.extra_args);
+        let targets = self.targets(default_targets);
+        let build = BuildStep::new("cmake", &self.source_dir)
+            .arg("--build")
+            .arg(&build_dir)
+            .args(
+                targets
+                    .iter()
+                    .flat_map(|target| ["--target", target.as_str()]),
+            );
+        vec![configure, build]
+    }
+
+    fn cargo_recipe(&self) -> Vec<BuildStep> {
+        let default_targets: &[&str] = match self.profile {
+            Some(TargetProfile::RustAnalyzer) => &["rust-analyzer"],
+            _ => &[],
+        };
+        let packages = self.targets(default_targets);
+        let mut step =
+            BuildStep::new("cargo", &self.source_dir).args(["afl", "build", "--release"]);
+        if !self.no_asan {
+            // Sanitizers for Rust code are only available on nightly toolchains.
+            step = step
+                .arg("-Zbuild-std")
+                .arg("--target=x86_64-unknown-linux-gnu")
+                .env("RUSTFLAGS", "-Zsanitizer=address -Cdebuginfo=2")
+                .env("AFL_USE_ASAN", "1");
+        }
+        vec![
+            step.args(packages.iter().flat_map(|it| ["-p", it.as_str()]))
+                .args(&self.extra_args),
+        ]
+    }
+
+    fn bazel_recipe(&self) -> Vec<BuildStep> {
+        let (cc, cxx) = self.compilers();
+        let mut step = BuildStep::new("bazel", &self.source_dir)
+            .arg("build")
+            .arg("--compilation_mode=opt")
+            .arg("--copt=-g")
+            .arg(format!("--action_env=CC={cc}"))
+            .arg(format!("--action_env=CXX={cxx}"));
+        if !self.no_asan {
+            step = step.args(
+                ASAN_FLAGS
+                    .split_whitespace()
+                    .map(|flag| format!("--copt={flag}"))
+                    .chain(["--linkopt=-fsanitize=address".to_owned()]),
+            );
+        }
+        let targets = self.targets(&[]);
+        vec![step.args(&self.extra_args).args(targets)]
+    }
+}
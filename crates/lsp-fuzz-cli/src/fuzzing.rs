@@ -1,7 +1,24 @@
-use std::{collections::HashMap, fs, io, path::PathBuf};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
 
+use anyhow::Context;
 use clap::builder::BoolishValueParser;
+use lsp_fuzz::{
+    campaign,
+    execution::{
+        container::{ContainerConfig, ContainerRuntime},
+        phases::PhaseTimeouts,
+    },
+};
 use nix::sys::signal::Signal;
+use serde::{Deserialize, Serialize};
 
 use crate::cli::{parse_hash_map, parse_size};
 
@@ -21,16 +38,37 @@ impl FuzzerStateDir {
         fs::create_dir_all(&self.0)
     }
 
-    pub fn corpus_dir(&self) -> PathBuf {
-        self.0.join("corpus")
+    pub fn path(&self) -> &Path {
+        &self.0
     }
 
     pub fn solution_dir(&self) -> PathBuf {
-        self.0.join("solutions")
+        self.0.join(campaign::SOLUTION_DIR)
     }
 
-    pub fn stats_file(&self) -> PathBuf {
-        self.0.join("stats")
+    pub fn summary_file(&self) -> PathBuf {
+        self.0.join("summary.json")
+    }
+
+    pub fn target_file(&self) -> PathBuf {
+        self.0.join("target.json")
+    }
+
+    pub fn map_size_file(&self) -> PathBuf {
+        self.0.join("map_size.json")
+    }
+
+    /// The state directory containing `solution_dir`, if it is laid out as by the fuzzer.
+    pub fn of_solution_dir(solution_dir: &Path) -> Option<Self> {
+        solution_dir.parent().map(Self::from)
+    }
+
+    pub fn name(&self) -> String {
+        self.0
+            .file_name()
+            .unwrap_or(self.0.as_os_str())
+            .to_string_lossy()
+            .into_owned()
     }
 }
 
@@ -50,6 +88,7 @@ pub struct ExecutorOptions {
     pub target_env: HashMap<String, String>,
 
     /// Size of the coverage map.
+    /// Detected from the target and saved to the state directory if not given.
     #[clap(long, short, env = "AFL_MAP_SIZE", value_parser = parse_size)]
     pub coverage_map_size: Option<usize>,
 
@@ -61,6 +100,20 @@ pub struct ExecutorOptions {
     #[clap(long, short, default_value_t = 1200)]
     pub exec_timeout: u64,
 
+    /// Budget for starting the target and answering `initialize` in milliseconds.
+    /// If given, the timeout of each execution is the sum of the phase budgets
+    /// instead of `--exec-timeout`.
+    #[clap(long)]
+    pub init_timeout: Option<u64>,
+
+    /// Budget for each message of an execution in milliseconds, with `--init-timeout`.
+    #[clap(long, default_value_t = 50)]
+    pub message_timeout: u64,
+
+    /// Budget for answering `shutdown` and exiting in milliseconds, with `--init-timeout`.
+    #[clap(long, default_value_t = 500)]
+    pub shutdown_timeout: u64,
+
     /// Signal to send to terminate the child process.
     #[clap(long, short, env = "AFL_KILL_SIGNAL", default_value_t = Signal::SIGKILL)]
     pub kill_signal: Signal,
@@ -72,4 +125,191 @@ pub struct ExecutorOptions {
     /// Enable debugging for AFL itself.
     #[clap(long, env = "AFL_DEBUG", value_parser = BoolishValueParser::new())]
     pub debug_afl: bool,
+
+    /// Run the fuzz target inside a container created from this image.
+    /// The path of the LSP executable is resolved inside the container.
+    #[clap(long)]
+    pub container_image: Option<String>,
+
+    /// The container runtime used with `--container-image`.
+    #[clap(long, value_enum, default_value_t = ContainerRuntimeArg::Docker)]
+    pub container_runtime: ContainerRuntimeArg,
+
+    /// Additional host paths to bind-mount into the container at the same location.
+    #[clap(long)]
+    pub container_mount: Vec<PathBuf>,
+
+    /// Additional arguments passed to the `run` command of the container runtime.
+    #[clap(long, allow_hyphen_values = true)]
+    pub container_arg: Vec<String>,
+
+    /// Report crashes of processes spawned by the LSP server (e.g., proc-macro servers).
+    /// The spawned processes must be built with AddressSanitizer.
+    #[clap(long)]
+    pub track_subprocesses: bool,
+}
+
+/// How the fuzz target is invoked, saved to the state directory so that
+/// the reproduce commands need not be told again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetInvocation {
+    pub executable: PathBuf,
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// The server run alongside the target, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub companion: Option<CompanionInvocation>,
+    /// The container the target runs in, if any. The companion runs in it as well.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<ContainerConfig>,
+}
+
+/// How the companion server is invoked. It shares the environment of the target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanionInvocation {
+    pub executable: PathBuf,
+    pub args: Vec<String>,
+    /// The file extensions or file names of the documents sent to the companion.
+    pub documents: Vec<String>,
+}
+
+impl TargetInvocation {
+    /// The companion server invoked like a target, with the environment of the target.
+    pub fn companion_target(&self) -> Option<Self> {
+        self.companion.as_ref().map(|companion| Self {
+            executable: companion.executable.clone(),
+            args: companion.args.clone(),
+            env: self.env.clone(),
+            companion: None,
+            container: self.container.clone(),
+        })
+    }
+
+    /// The command running the target in `dir` with the additional environment variables `envs`,
+    /// inside the container if the target runs in one.
+    pub fn command<K, V>(&self, dir: &Path, envs: impl IntoIterator<Item = (K, V)>) -> Command
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let mut command = Command::new(&self.executable);
+        command
+            .args(&self.args)
+            .envs(&self.env)
+            .envs(envs)
+            .current_dir(dir);
+        self.containerize(command)
+    }
+
+    /// Wraps `command` in the container the target runs in, if any.
+    pub fn containerize(&self, command: Command) -> Command {
+        match &self.container {
+            Some(container) => container.containerize(&command),
+            None => command,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path).context("Creating target invocation file")?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .context("Writing target invocation")
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).context("Opening target invocation file")?;
+        serde_json::from_reader(BufReader::new(file)).context("Parsing target invocation")
+    }
+}
+
+impl From<&ExecutorOptions> for TargetInvocation {
+    fn from(options: &ExecutorOptions) -> Self {
+        Self {
+            executable: options.lsp_executable.clone(),
+            args: options.target_args.clone(),
+            env: options.target_env.clone(),
+            companion: None,
+            container: options.container_config(),
+        }
+    }
+}
+
+/// The coverage map size negotiated with a target binary, saved to the state directory
+/// so that later runs against the same binary skip the negotiation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NegotiatedMapSize {
+    pub executable: PathBuf,
+    /// The modification time of the executable, telling whether it has been rebuilt since.
+    pub modified: Option<SystemTime>,
+    pub map_size: usize,
+}
+
+impl NegotiatedMapSize {
+    pub fn new(executable: &Path, map_size: usize) -> Self {
+        Self {
+            executable: executable.to_owned(),
+            modified: modification_time(executable),
+            map_size,
+        }
+    }
+
+    /// Loads the negotiated map size from `path` if it exists.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context("Opening negotiated map size file"),
+        };
+        serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .context("Parsing negotiated map size")
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path).context("Creating negotiated map size file")?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .context("Writing negotiated map size")
+    }
+
+    /// Whether the size was negotiated with `executable` as it is now.
+    pub fn is_valid_for(&self, executable: &Path) -> bool {
+        self.executable == executable
+            && self.modified.is_some()
+            && self.modified == modification_time(executable)
+    }
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|it| it.modified()).ok()
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ContainerRuntimeArg {
+    Docker,
+    Podman,
+}
+
+impl ExecutorOptions {
+    pub fn phase_timeouts(&self) -> Option<PhaseTimeouts> {
+        let init = self.init_timeout?;
+        Some(PhaseTimeouts {
+            init: Duration::from_millis(init),
+            per_message: Duration::from_millis(self.message_timeout),
+            shutdown: Duration::from_millis(self.shutdown_timeout),
+        })
+    }
+
+    pub fn container_config(&self) -> Option<ContainerConfig> {
+        let image = self.container_image.clone()?;
+        let runtime = match self.container_runtime {
+            ContainerRuntimeArg::Docker => ContainerRuntime::Docker,
+            ContainerRuntimeArg::Podman => ContainerRuntime::Podman,
+        };
+        Some(ContainerConfig {
+            runtime,
+            image,
+            mounts: self.container_mount.clone(),
+            extra_args: self.container_arg.clone(),
+        })
+    }
 }
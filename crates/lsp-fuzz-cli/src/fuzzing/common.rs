@@ -1,91 +1,27 @@
-use std::{hash::Hash, iter, path::Path, sync::mpsc, time::Duration};
+use std::{
+    collections::HashMap, fs::File, io::BufWriter, iter, path::Path, sync::mpsc, time::Duration,
+};
 
 use anyhow::Context;
 use core_affinity::CoreId;
+use itertools::Itertools;
 use libafl::{
     HasMetadata, HasNamedMetadata,
-    corpus::{CachedOnDiskCorpus, HasTestcase, OnDiskCorpus},
-    feedback_and_fast, feedback_or, feedback_or_fast,
-    feedbacks::{ConstFeedback, CrashFeedback, Feedback, NewHashFeedback},
-    inputs::Input,
-    observers::{AsanBacktraceObserver, CanTrack},
-    schedulers::{
-        IndexesLenTimeMinimizerScheduler, Scheduler, StdWeightedScheduler,
-        powersched::{BaseSchedule, PowerSchedule},
-    },
-    state::{HasCorpus, HasExecutions, HasRand, HasSolutions, HasStartTime},
+    corpus::Corpus,
+    feedbacks::MapFeedbackMetadata,
+    state::{HasCorpus, HasExecutions, HasSolutions, HasStartTime},
 };
-use libafl_bolts::{HasLen, Named, tuples::MatchName};
 use lsp_fuzz::{
-    corpus::{TestCaseFileNameFeedback, corpus_kind::SOLUTION},
-    execution::FuzzTargetInfo,
+    execution::{FuzzTargetInfo, phases::PhaseTimeoutStats},
     fuzz_target::StaticTargetBinaryInfo,
-    stages::StopOnReceived,
-    utf8::UTF8Tokens,
+    lsp_input::{LspInput, configuration::ConfigurationCoverage, messages::DuplicationStats},
 };
 use rayon::prelude::*;
+use serde::Serialize;
 use tracing::{info, warn};
 
 use crate::fuzzing::ExecutorOptions;
 
-pub fn scheduler<State, I, C, O>(
-    state: &mut State,
-    cov_observer: &C,
-    power_schedule: BaseSchedule,
-    cycle_power_schedule: bool,
-) -> impl Scheduler<I, State> + use<State, I, C, O>
-where
-    C: Named + CanTrack + AsRef<O>,
-    I: HasLen,
-    State: HasMetadata + HasCorpus<I> + HasRand + HasTestcase<I>,
-    O: Hash,
-{
-    let power_schedule = PowerSchedule::new(power_schedule);
-    let mut weighted_scheduler =
-        StdWeightedScheduler::with_schedule(state, cov_observer, Some(power_schedule));
-    if cycle_power_schedule {
-        weighted_scheduler = weighted_scheduler.cycling_scheduler();
-    }
-    IndexesLenTimeMinimizerScheduler::new(cov_observer, weighted_scheduler)
-}
-
-pub fn objective<EM, I, Observers, State>(
-    asan_enabled: bool,
-    asan_observer: &AsanBacktraceObserver,
-) -> impl Feedback<EM, I, Observers, State> + use<EM, I, Observers, State>
-where
-    Observers: MatchName,
-    State: HasNamedMetadata + HasSolutions<I> + HasExecutions + HasStartTime,
-{
-    feedback_or!(
-        TestCaseFileNameFeedback::<SOLUTION>::new(),
-        feedback_and_fast!(
-            CrashFeedback::new(),
-            feedback_or_fast!(
-                ConstFeedback::new(!asan_enabled),
-                NewHashFeedback::new(asan_observer),
-            )
-        )
-    )
-}
-
-pub fn create_corpus<I>(
-    corpus_path: &Path,
-    solution_path: &Path,
-) -> anyhow::Result<(CachedOnDiskCorpus<I>, OnDiskCorpus<I>)>
-where
-    I: Input,
-{
-    const CACHE_SIZE: usize = 4096;
-    let corpus =
-        CachedOnDiskCorpus::with_meta_format_and_prefix(corpus_path, CACHE_SIZE, None, None, false)
-            .context("Creating corpus")?;
-    let solutions = OnDiskCorpus::with_meta_format_and_prefix(solution_path, None, None, false)
-        .context("Creating solution corpus")?;
-
-    Ok((corpus, solutions))
-}
-
 /// Creates a target info struct from execution options and binary info.
 pub fn create_target_info(
     options: &ExecutorOptions,
@@ -98,8 +34,10 @@ pub fn create_target_info(
         defer_fork_server: binary_info.is_defer_fork_server,
         crash_exit_code: options.crash_exit_code,
         timeout: Duration::from_millis(options.exec_timeout).into(),
+        phase_timeouts: options.phase_timeouts(),
         kill_signal: options.kill_signal,
         env: options.target_env.clone(),
+        container: options.container_config(),
     }
 }
 
@@ -115,33 +53,157 @@ pub fn set_cpu_affinity(core_id: Option<usize>) {
     }
 }
 
-/// Creates a stop stage that triggers when Ctrl+C is pressed.
-pub fn trigger_stop_stage<I>() -> Result<StopOnReceived<I>, anyhow::Error> {
+/// Creates a receiver of the stop requests sent by `SIGINT` (e.g., Ctrl+C) or `SIGTERM`.
+pub fn stop_signal() -> Result<mpsc::Receiver<()>, anyhow::Error> {
     let (tx, rx) = mpsc::channel();
-    let mut is_control_c_pressed = false;
+    let mut is_stop_requested = false;
     ctrlc::try_set_handler(move || {
-        if is_control_c_pressed {
+        if is_stop_requested {
             const EXIT_CODE: i32 = 128 + (nix::sys::signal::SIGINT as i32);
-            info!("Control-C pressed again. Exiting immediately.");
+            info!("Stop signal received again. Exiting immediately.");
             std::process::exit(EXIT_CODE);
         }
-        is_control_c_pressed = true;
-        info!("Control-C pressed. The fuzzer will stop after this cycle.");
+        is_stop_requested = true;
+        info!("Stop signal received. The fuzzer will stop after this cycle.");
         tx.send(()).expect("Failed to send stop signal");
     })
-    .context("Setting Control-C handler")?;
+    .context("Setting stop signal handler")?;
 
-    Ok(StopOnReceived::new(rx))
+    Ok(rx)
 }
 
-/// Process tokens extracted during fuzzing.
-pub fn process_tokens<S>(state: &mut S, tokens: Option<UTF8Tokens>)
-where
-    S: HasMetadata,
-{
-    if let Some(tokens) = tokens {
-        info!("Extracted {} UTF-8 token(s) from the target.", tokens.len());
-        state.add_metadata(tokens);
+/// A summary of a fuzzing campaign, written when the campaign stops.
+#[derive(Debug, Serialize)]
+pub struct CampaignSummary {
+    pub elapsed_secs: u64,
+    pub executions: u64,
+    pub corpus: usize,
+    pub solutions: usize,
+    pub edges_covered: usize,
+    /// The most frequent methods among the messages in the corpus.
+    pub top_methods: Vec<(&'static str, usize)>,
+    /// The number of timeouts in each phase of the executions.
+    pub timeouts: PhaseTimeoutStats,
+    /// How many inputs with duplicated requests were executed and added to the corpus.
+    pub duplication: DuplicationStats,
+    /// The executions and the edges covered under each configuration of the target,
+    /// by the settings sent in them.
+    pub configurations: Vec<ConfigurationSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigurationSummary {
+    pub configuration: String,
+    pub executions: u64,
+    pub edges_covered: usize,
+}
+
+impl CampaignSummary {
+    const TOP_METHODS: usize = 10;
+
+    /// Summarizes the campaign from the fuzzer state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the coverage metadata or a corpus entry cannot be loaded.
+    pub fn from_state<State>(state: &State, coverage_feedback_name: &str) -> anyhow::Result<Self>
+    where
+        State: HasCorpus<LspInput>
+            + HasSolutions<LspInput>
+            + HasExecutions
+            + HasStartTime
+            + HasMetadata
+            + HasNamedMetadata,
+    {
+        let edges_covered = state
+            .named_metadata::<MapFeedbackMetadata<u8>>(coverage_feedback_name)
+            .context("Getting coverage metadata")?
+            .num_covered_map_indexes;
+        let mut method_counts: HashMap<&'static str, usize> = HashMap::new();
+        let corpus = state.corpus();
+        for id in corpus.ids() {
+            let input = corpus
+                .cloned_input_for_id(id)
+                .context("Loading corpus entry")?;
+            for message in input.messages.iter() {
+                *method_counts.entry(message.method()).or_default() += 1;
+            }
+        }
+        let top_methods = method_counts
+            .into_iter()
+            .sorted_unstable_by(|(lhs_method, lhs), (rhs_method, rhs)| {
+                rhs.cmp(lhs).then(lhs_method.cmp(rhs_method))
+            })
+            .take(Self::TOP_METHODS)
+            .collect();
+        Ok(Self {
+            elapsed_secs: libafl_bolts::current_time()
+                .checked_sub(*state.start_time())
+                .unwrap_or_default()
+                .as_secs(),
+            executions: *state.executions(),
+            corpus: corpus.count(),
+            solutions: state.solutions().count(),
+            edges_covered,
+            top_methods,
+            timeouts: state
+                .metadata_map()
+                .get::<PhaseTimeoutStats>()
+                .copied()
+                .unwrap_or_default(),
+            duplication: state
+                .metadata_map()
+                .get::<DuplicationStats>()
+                .copied()
+                .unwrap_or_default(),
+            configurations: state
+                .metadata_map()
+                .get::<ConfigurationCoverage>()
+                .into_iter()
+                .flat_map(|it| &it.configurations)
+                .map(|(configuration, stats)| ConfigurationSummary {
+                    configuration: configuration.clone(),
+                    executions: stats.executions,
+                    edges_covered: stats.edges.len(),
+                })
+                .sorted_unstable_by(|lhs, rhs| rhs.edges_covered.cmp(&lhs.edges_covered))
+                .collect(),
+        })
+    }
+
+    /// Logs the summary and writes it to `path` as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the summary cannot be written.
+    pub fn report(&self, path: &Path) -> anyhow::Result<()> {
+        info!(
+            elapsed_secs = self.elapsed_secs,
+            executions = self.executions,
+            corpus = self.corpus,
+            solutions = self.solutions,
+            edges_covered = self.edges_covered,
+            init_timeouts = self.timeouts.init,
+            request_timeouts = self.timeouts.requests,
+            shutdown_request_timeouts = self.timeouts.shutdown_request,
+            shutdown_timeouts = self.timeouts.shutdown,
+            duplication_executions = self.duplication.executions,
+            duplication_new_entries = self.duplication.new_corpus_entries,
+            "Campaign summary"
+        );
+        for (method, count) in &self.top_methods {
+            info!("  {method}: {count}");
+        }
+        for it in &self.configurations {
+            info!(
+                executions = it.executions,
+                edges_covered = it.edges_covered,
+                "  {}",
+                it.configuration
+            );
+        }
+        let file = File::create(path).context("Creating summary file")?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self).context("Writing summary")
     }
 }
 
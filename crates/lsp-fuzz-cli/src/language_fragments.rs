@@ -1,37 +1,118 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fs::File,
-    io::BufReader,
+    io::{BufReader, BufWriter},
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Ok};
+use anyhow::{Context, Ok, bail};
 use lsp_fuzz::text_document::{
-    generation::{GrammarContext, GrammarContextLookup},
-    grammar::Grammar,
+    generation::{DerivationFragments, GrammarContext, GrammarContextLookup},
+    grammar::{Grammar, fragment_extraction::extract_language_fragments},
 };
 use lsp_fuzz_grammars::Language;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::cli::parse_hash_map;
+
+/// Grammars and highlight queries to use instead of the ones shipped with the grammars,
+/// e.g., to fuzz with a newer revision of a grammar than the bundled one.
+#[derive(Debug, Default, clap::Parser)]
+pub struct GrammarOverrides {
+    /// Comma-separated `Language=<grammar.json>` pairs replacing the bundled `grammar.json`
+    /// of the languages. The grammars must match the parsers linked into the fuzzer.
+    #[clap(long, value_parser = parse_hash_map::<Language, PathBuf>, default_value = "")]
+    grammar_json: HashMap<Language, PathBuf>,
+
+    /// Comma-separated `Language=<highlights.scm>` pairs replacing the bundled highlight
+    /// queries of the languages.
+    #[clap(long, value_parser = parse_hash_map::<Language, PathBuf>, default_value = "")]
+    highlights: HashMap<Language, PathBuf>,
+}
+
+impl GrammarOverrides {
+    /// Installs the highlight queries, which has to happen before any document is parsed.
+    pub fn apply_highlights(&self) -> Result<(), anyhow::Error> {
+        for (&lang, path) in &self.highlights {
+            let query_src = std::fs::read_to_string(path)
+                .with_context(|| format!("Reading highlight query: {}", path.display()))?;
+            lang.override_highlight_query(&query_src)
+                .with_context(|| format!("Overriding highlight query of {lang}"))?;
+        }
+        Ok(())
+    }
+
+    /// Whether a `grammar.json` is supplied for `lang`.
+    pub fn overrides_grammar(&self, lang: Language) -> bool {
+        self.grammar_json.contains_key(&lang)
+    }
+
+    /// The `grammar.json` of `lang`, the supplied one if there is one.
+    pub fn grammar_json(&self, lang: Language) -> Result<Cow<'static, str>, anyhow::Error> {
+        let grammar_json = if let Some(path) = self.grammar_json.get(&lang) {
+            let grammar_json = std::fs::read_to_string(path)
+                .with_context(|| format!("Reading grammar: {}", path.display()))?;
+            Cow::Owned(grammar_json)
+        } else {
+            Cow::Borrowed(lang.grammar_json())
+        };
+        Ok(grammar_json)
+    }
+
+    /// The grammar of `lang`, from the supplied `grammar.json` if there is one.
+    pub fn grammar(&self, lang: Language) -> Result<Grammar, anyhow::Error> {
+        let grammar = if let Some(path) = self.grammar_json.get(&lang) {
+            let grammar_json = self.grammar_json(lang)?;
+            let grammar = Grammar::from_tree_sitter_grammar_json(lang, &grammar_json)
+                .with_context(|| format!("Loading grammar: {}", path.display()))?;
+            grammar
+                .validate()
+                .with_context(|| format!("Validating grammar: {}", path.display()))?;
+            grammar
+        } else {
+            Grammar::from_tree_sitter_grammar_json(lang, lang.grammar_json())?
+        };
+        Ok(grammar)
+    }
+}
 
 pub fn load_grammar_context(
     lang: Language,
     derivation_fragment_file: &Path,
+    with_seed_fragments: bool,
+    overrides: &GrammarOverrides,
 ) -> Result<GrammarContext, anyhow::Error> {
     let file = File::open(derivation_fragment_file).context("Opening derivation fragment")?;
     let reader = zstd::Decoder::new(BufReader::new(file))?;
-    let frags = ciborium::from_reader(reader).context("Deserializing derivation fragments")?;
-    let grammar = Grammar::from_tree_sitter_grammar_json(lang, lang.grammar_json())?;
+    let mut frags: DerivationFragments =
+        ciborium::from_reader(reader).context("Deserializing derivation fragments")?;
+    if with_seed_fragments {
+        merge_seed_fragments(lang, &mut frags)?;
+    }
+    let grammar = overrides.grammar(lang)?;
     let grammar_ctx = GrammarContext::new(grammar, frags);
     Ok(grammar_ctx)
 }
 
+/// Loads the mined fragments of each language.
+///
+/// If `with_seed_fragments` is set, the fragments of the curated seeds shipped with the grammars
+/// (see [`Language::seed_fragments`]) are merged into the mined ones.
 pub fn load_grammar_lookup(
     lang_and_files: &HashMap<Language, PathBuf>,
+    with_seed_fragments: bool,
+    overrides: &GrammarOverrides,
 ) -> Result<GrammarContextLookup, anyhow::Error> {
     let contexts: Vec<_> = lang_and_files
         .iter()
         .par_bridge()
-        .map(|(&lang, frag_path)| load_grammar_context(lang, frag_path))
+        .map(|(&lang, frag_path)| {
+            load_grammar_context(lang, frag_path, with_seed_fragments, overrides)
+        })
         .try_fold(Vec::new, |mut acc, res| {
             res.map(|it| {
                 acc.push(it);
@@ -45,3 +126,176 @@ pub fn load_grammar_lookup(
     let grammar_ctx = GrammarContextLookup::from_iter(contexts);
     Ok(grammar_ctx)
 }
+
+/// The version of the format of grammar caches.
+///
+/// Bump it whenever a change to [`GrammarContext`] or the types it contains breaks the
+/// deserialization of existing caches, or a change to how the lookup is built from the same
+/// grammars and fragments, e.g., to pruning or minimal expansion, makes existing caches stale.
+const GRAMMAR_CACHE_VERSION: u32 = 3;
+
+/// Written before the lookup in a grammar cache, so that stale caches are detected
+/// without deserializing the lookup.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct GrammarCacheHeader {
+    version: u32,
+    fuzzer_version: Cow<'static, str>,
+    fingerprint: u64,
+}
+
+/// Loads the mined fragments of each language like [`load_grammar_lookup`], from the cache file
+/// `cache` if it was built from the same grammars and fragments.
+///
+/// Otherwise, the lookup is built and saved to `cache` for the next runs.
+/// Building the grammars and pruning them against the fragments takes a while for large
+/// grammars, e.g., that of C++.
+pub fn load_cached_grammar_lookup(
+    lang_and_files: &HashMap<Language, PathBuf>,
+    with_seed_fragments: bool,
+    overrides: &GrammarOverrides,
+    cache: &Path,
+) -> Result<GrammarContextLookup, anyhow::Error> {
+    let header = GrammarCacheHeader {
+        version: GRAMMAR_CACHE_VERSION,
+        fuzzer_version: Cow::Borrowed(env!("CARGO_PKG_VERSION")),
+        fingerprint: grammar_fingerprint(lang_and_files, with_seed_fragments, overrides)
+            .context("Fingerprinting grammars")?,
+    };
+    if cache.exists() {
+        match read_grammar_cache(cache, &header) {
+            Result::Ok(Some(lookup)) => {
+                info!(cache = %cache.display(), "Loaded grammar context from cache");
+                return Ok(lookup);
+            }
+            Result::Ok(None) => info!(cache = %cache.display(), "Grammar cache is stale"),
+            Err(err) => warn!(cache = %cache.display(), "Failed to read grammar cache: {err:#}"),
+        }
+    }
+    let lookup = load_grammar_lookup(lang_and_files, with_seed_fragments, overrides)?;
+    match write_grammar_cache(cache, &header, &lookup) {
+        Result::Ok(()) => info!(cache = %cache.display(), "Saved grammar context to cache"),
+        Err(err) => warn!(cache = %cache.display(), "Failed to write grammar cache: {err:#}"),
+    }
+    Ok(lookup)
+}
+
+/// A hash of everything the lookup is built from: the grammars, the fragment files,
+/// and the seed fragments if they are merged.
+///
+/// The hash is stable across builds of the fuzzer, unlike [`std::hash::DefaultHasher`].
+fn grammar_fingerprint(
+    lang_and_files: &HashMap<Language, PathBuf>,
+    with_seed_fragments: bool,
+    overrides: &GrammarOverrides,
+) -> Result<u64, anyhow::Error> {
+    let mut hasher = Xxh3::new();
+    // Prefix every field with its length, so that the fields cannot run into each other.
+    let mut update = |bytes: &[u8]| {
+        hasher.update(&(bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    };
+    update(&[u8::from(with_seed_fragments)]);
+    let mut languages: Vec<_> = lang_and_files.iter().collect();
+    languages.sort_by_key(|(lang, _)| lang.to_string());
+    for (&lang, frag_path) in languages {
+        update(lang.to_string().as_bytes());
+        update(overrides.grammar_json(lang)?.as_bytes());
+        update(
+            &std::fs::read(frag_path).with_context(|| {
+                format!("Reading derivation fragments: {}", frag_path.display())
+            })?,
+        );
+        if with_seed_fragments {
+            update(lang.seed_fragments().unwrap_or_default().as_bytes());
+        }
+    }
+    Ok(hasher.digest())
+}
+
+/// Reads the lookup in `cache`, or `None` if it was not written with `header`.
+fn read_grammar_cache(
+    cache: &Path,
+    header: &GrammarCacheHeader,
+) -> Result<Option<GrammarContextLookup>, anyhow::Error> {
+    let file = File::open(cache).context("Opening grammar cache")?;
+    let mut reader = zstd::Decoder::new(BufReader::new(file))?;
+    let cached_header: GrammarCacheHeader =
+        ciborium::from_reader(&mut reader).context("Deserializing grammar cache header")?;
+    if cached_header != *header {
+        return Ok(None);
+    }
+    let lookup = ciborium::from_reader(reader).context("Deserializing grammar cache")?;
+    Ok(Some(lookup))
+}
+
+fn write_grammar_cache(
+    cache: &Path,
+    header: &GrammarCacheHeader,
+    lookup: &GrammarContextLookup,
+) -> Result<(), anyhow::Error> {
+    // Written next to the cache and renamed over it, so that concurrent runs sharing the cache
+    // never read a partially written one.
+    let cache_dir = cache
+        .parent()
+        .filter(|it| !it.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file = tempfile::NamedTempFile::new_in(cache_dir).context("Creating grammar cache")?;
+    let mut encoder =
+        zstd::Encoder::new(BufWriter::new(file), 3).context("Creating zstd encoder")?;
+    ciborium::into_writer(header, &mut encoder).context("Serializing grammar cache header")?;
+    ciborium::into_writer(lookup, &mut encoder).context("Serializing grammar cache")?;
+    let file = encoder
+        .finish()
+        .context("Writing grammar cache")?
+        .into_inner()
+        .context("Flushing grammar cache")?;
+    file.persist(cache).context("Replacing grammar cache")?;
+    Ok(())
+}
+
+/// Mines the derivation fragments of a single source file into a grammar context,
+/// so that the file is the only fragment pool of its language.
+///
+/// The language is determined by the extension of the file.
+pub fn mine_grammar_context(
+    source_file: &Path,
+    with_seed_fragments: bool,
+    overrides: &GrammarOverrides,
+) -> Result<GrammarContext, anyhow::Error> {
+    let Some(lang) = source_file
+        .extension()
+        .and_then(|it| it.to_str())
+        .and_then(Language::from_file_extension)
+    else {
+        bail!("Unknown language of seed file: {}", source_file.display());
+    };
+    let code = std::fs::read(source_file).context("Reading seed file")?;
+    let mut fragments = mine_fragments(lang, code)?;
+    if with_seed_fragments {
+        merge_seed_fragments(lang, &mut fragments)?;
+    }
+    let grammar = overrides.grammar(lang)?;
+    Ok(GrammarContext::new(grammar, fragments))
+}
+
+fn mine_fragments(lang: Language, code: Vec<u8>) -> Result<DerivationFragments, anyhow::Error> {
+    let mut fragments = extract_language_fragments(&code, lang).context("Extracting fragments")?;
+    for ranges in fragments.values_mut() {
+        ranges.sort_by_key(|it| &code[it.clone()]);
+        ranges.dedup_by_key(|it| &code[it.clone()]);
+    }
+    Ok(DerivationFragments::new(code, fragments))
+}
+
+/// Merges the fragments of the curated seed of `lang` into `fragments`, if there is one.
+fn merge_seed_fragments(
+    lang: Language,
+    fragments: &mut DerivationFragments,
+) -> Result<(), anyhow::Error> {
+    if let Some(seed) = lang.seed_fragments() {
+        let seed_fragments =
+            mine_fragments(lang, seed.as_bytes().to_vec()).context("Mining seed fragments")?;
+        fragments.merge(seed_fragments);
+    }
+    Ok(())
+}
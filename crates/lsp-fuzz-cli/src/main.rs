@@ -14,7 +14,9 @@ fn main() -> anyhow::Result<()> {
         eprintln!("Please use a release build for better performance.");
     }
 
-    let cli = cli::Cli::parse();
+    let args: Vec<_> = std::env::args_os().collect();
+    cli::load_dynamic_languages(&args).context("Loading dynamic languages")?;
+    let cli = cli::Cli::parse_from(args);
     cli.run().context("Running CLI")?;
     Ok(())
 }
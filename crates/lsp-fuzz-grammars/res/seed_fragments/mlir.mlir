@@ -0,0 +1,105 @@
+// Operations of the common upstream dialects, in both the custom and the generic form.
+
+#map = affine_map<(d0, d1) -> (d1, d0)>
+#set = affine_set<(d0) : (d0 - 1 >= 0)>
+!memref_ty = memref<4x8xf32>
+
+module attributes {dlti.dl_spec = #dlti.dl_spec<#dlti.dl_entry<index, 64 : i32>>} {
+  func.func private @external(i32, f64) -> (i1)
+
+  func.func @arith(%a: i32, %b: i32, %x: f32, %y: f32) -> i32 attributes {llvm.emit_c_interface} {
+    %c0 = arith.constant 0 : i32
+    %c1 = arith.constant 1 : index
+    %f = arith.constant 1.000000e+00 : f32
+    %sum = arith.addi %a, %b : i32
+    %prod = arith.muli %sum, %c0 overflow<nsw> : i32
+    %div = arith.divsi %prod, %b : i32
+    %cmp = arith.cmpi slt, %a, %b : i32
+    %sel = arith.select %cmp, %a, %div : i32
+    %fsum = arith.addf %x, %y fastmath<fast> : f32
+    %fcmp = arith.cmpf olt, %fsum, %f : f32
+    %ext = arith.extsi %sel : i32 to i64
+    %trunc = arith.trunci %ext : i64 to i16
+    %idx = arith.index_cast %a : i32 to index
+    %generic = "arith.addi"(%a, %b) : (i32, i32) -> i32
+    return %sel : i32
+  }
+
+  func.func @control_flow(%cond: i1, %n: index, %buf: memref<?xf32>) -> f32 {
+    %c0 = arith.constant 0 : index
+    %c1 = arith.constant 1 : index
+    %init = arith.constant 0.0 : f32
+    %r = scf.for %i = %c0 to %n step %c1 iter_args(%acc = %init) -> (f32) {
+      %v = memref.load %buf[%i] : memref<?xf32>
+      %next = arith.addf %acc, %v : f32
+      scf.yield %next : f32
+    }
+    %s = scf.if %cond -> (f32) {
+      scf.yield %r : f32
+    } else {
+      scf.yield %init : f32
+    }
+    scf.while (%arg = %c0) : (index) -> index {
+      %lt = arith.cmpi ult, %arg, %n : index
+      scf.condition(%lt) %arg : index
+    } do {
+    ^bb0(%arg: index):
+      %inc = arith.addi %arg, %c1 : index
+      scf.yield %inc : index
+    }
+    cf.cond_br %cond, ^bb1(%s : f32), ^bb2
+  ^bb1(%out: f32):
+    return %out : f32
+  ^bb2:
+    cf.br ^bb1(%init : f32)
+  }
+
+  func.func @memory(%m: !memref_ty, %t: tensor<4x8xf32>) -> tensor<8x4xf32> {
+    %alloc = memref.alloc() {alignment = 64 : i64} : memref<4x8xf32>
+    %alloca = memref.alloca() : memref<16xi8>
+    %view = memref.subview %alloc[0, 0] [2, 4] [1, 1] : memref<4x8xf32> to memref<2x4xf32, strided<[8, 1]>>
+    memref.copy %m, %alloc : memref<4x8xf32> to memref<4x8xf32>
+    memref.dealloc %alloc : memref<4x8xf32>
+    %empty = tensor.empty() : tensor<8x4xf32>
+    %c0 = arith.constant 0 : index
+    %e = tensor.extract %t[%c0, %c0] : tensor<4x8xf32>
+    %dim = tensor.dim %t, %c0 : tensor<4x8xf32>
+    %transposed = linalg.transpose ins(%t : tensor<4x8xf32>) outs(%empty : tensor<8x4xf32>) permutation = [1, 0]
+    %filled = linalg.fill ins(%e : f32) outs(%empty : tensor<8x4xf32>) -> tensor<8x4xf32>
+    %res = linalg.generic {indexing_maps = [#map, affine_map<(d0, d1) -> (d0, d1)>], iterator_types = ["parallel", "parallel"]} ins(%t : tensor<4x8xf32>) outs(%filled : tensor<8x4xf32>) {
+    ^bb0(%in: f32, %out: f32):
+      %add = arith.addf %in, %out : f32
+      linalg.yield %add : f32
+    } -> tensor<8x4xf32>
+    return %res : tensor<8x4xf32>
+  }
+
+  func.func @loops(%A: memref<100x100xf32>, %v: vector<4xf32>) {
+    affine.for %i = 0 to 100 step 2 {
+      affine.for %j = #map(%i) to min affine_map<(d0) -> (d0 + 10, 100)>(%i) {
+        %val = affine.load %A[%i, %j] : memref<100x100xf32>
+        affine.if #set(%i) {
+          affine.store %val, %A[%j, %i] : memref<100x100xf32>
+        }
+      }
+    }
+    %c0 = arith.constant 0 : index
+    %splat = vector.broadcast %c0 : index to vector<4xindex>
+    %red = vector.reduction <add>, %v : vector<4xf32> into f32
+    %shuf = vector.shuffle %v, %v [0, 4, 1, 5] : vector<4xf32>, vector<4xf32>
+    return
+  }
+
+  llvm.func @llvm_ops(%p: !llvm.ptr, %n: i64) -> i32 {
+    %0 = llvm.mlir.constant(42 : i32) : i32
+    %1 = llvm.getelementptr %p[%n] : (!llvm.ptr, i64) -> !llvm.ptr, i32
+    llvm.store %0, %1 : i32, !llvm.ptr
+    %2 = llvm.load %1 : !llvm.ptr -> i32
+    llvm.return %2 : i32
+  }
+
+  "test.generic_op"() ({
+  ^bb0:
+    "test.terminator"() : () -> ()
+  }) {attr = dense<[1, 2, 3]> : tensor<3xi32>, unit, str = "s"} : () -> ()
+}
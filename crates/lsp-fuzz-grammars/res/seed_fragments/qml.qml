@@ -0,0 +1,126 @@
+// Import statements and object declarations common in Qt Quick applications.
+
+pragma Singleton
+pragma ComponentBehavior: Bound
+
+import QtQuick
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Controls.Material
+import QtQuick.Layouts 1.15
+import QtQuick.Window 2.15
+import QtQml 2.15
+import QtQml.Models
+import QtQuick.Dialogs
+import Qt.labs.platform 1.1 as Platform
+import Qt5Compat.GraphicalEffects
+import "components"
+import "utils.js" as Utils
+import "../shared" as Shared
+
+ApplicationWindow {
+    id: root
+    width: 640
+    height: 480
+    visible: true
+    title: qsTr("Hello %1").arg(Qt.application.name)
+
+    property int counter: 0
+    property real ratio: width / height
+    property string label: "count: " + counter
+    property bool active
+    property var model: [1, 2, 3]
+    property list<Item> items
+    property alias text: field.text
+    readonly property color accent: Material.accent
+    required property QtObject backend
+    default property list<QtObject> children
+
+    signal clicked(int x, int y)
+    signal reset
+
+    enum Mode { Idle, Running = 4, Stopped }
+
+    function increment(step: int): int {
+        counter += step
+        return counter
+    }
+
+    onCounterChanged: console.log("counter", counter)
+    Component.onCompleted: {
+        Utils.init(root)
+        increment(1)
+    }
+
+    Connections {
+        target: root.backend
+        function onUpdated(value) { root.counter = value }
+    }
+
+    Binding on ratio {
+        when: root.active
+        value: 1.0
+    }
+
+    ColumnLayout {
+        anchors.fill: parent
+        anchors.margins: 8
+        spacing: 4
+
+        TextField {
+            id: field
+            Layout.fillWidth: true
+            placeholderText: "Type here"
+            validator: IntValidator { bottom: 0; top: 100 }
+            onAccepted: root.label = text
+        }
+
+        Repeater {
+            model: ListModel {
+                ListElement { name: "one"; value: 1 }
+                ListElement { name: "two"; value: 2 }
+            }
+            delegate: Text {
+                required property string name
+                text: name
+            }
+        }
+
+        ListView {
+            Layout.fillHeight: true
+            model: root.model
+            delegate: Rectangle {
+                width: ListView.view.width
+                height: 20
+                color: index % 2 ? "white" : Qt.rgba(0.9, 0.9, 0.9, 1)
+                Behavior on color { ColorAnimation { duration: 200 } }
+            }
+        }
+
+        Button {
+            text: "Reset"
+            enabled: root.counter > 0
+            onClicked: root.reset()
+        }
+    }
+
+    states: [
+        State {
+            name: "running"
+            when: root.active
+            PropertyChanges { target: field; opacity: 0.5 }
+        }
+    ]
+
+    transitions: Transition {
+        NumberAnimation { properties: "opacity"; easing.type: Easing.InOutQuad }
+    }
+
+    Timer {
+        interval: 500; running: root.active; repeat: true
+        onTriggered: root.increment(1)
+    }
+
+    Shared.Footer {}
+    Platform.MenuBar {}
+}
@@ -0,0 +1,163 @@
+//! Tree-sitter grammars loaded from shared libraries at runtime.
+//!
+//! A [`DynamicLanguage`] describes a grammar that is not shipped with this crate.
+//! Once loaded, it is registered (see [`crate::registry`]) for the rest of the process.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tree_sitter_language::LanguageFn;
+
+use crate::{
+    Language,
+    registry::{self, LanguageSpec, RegistrationError},
+};
+
+/// A tree-sitter grammar compiled into a shared library.
+///
+/// The library must export the `tree_sitter_<name>` function, where `<name>` is the `name`
+/// of the grammar in `grammar_json`. The name is also how the language is referred to,
+/// e.g., in `--language-fragments`. Relative paths are resolved by [`Self::from_manifest`]
+/// against the directory of the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynamicLanguage {
+    /// The shared library, e.g., `libtree-sitter-elixir.so`.
+    pub library: PathBuf,
+    /// The `grammar.json` generated by `tree-sitter generate`, i.e., `src/grammar.json`.
+    pub grammar_json: PathBuf,
+    /// The extensions of the source files of the language, without the leading dot.
+    pub extensions: Vec<String>,
+    /// The language identifier used by the Language Server Protocol.
+    /// Defaults to the name of the grammar.
+    #[serde(default)]
+    pub lsp_language_id: Option<String>,
+    /// The highlight query of the grammar, usually `queries/highlights.scm`.
+    /// Without it, highlight-based node filters select nothing in documents of the language.
+    #[serde(default)]
+    pub highlight_query: Option<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DynamicLanguageError {
+    #[error("Fail to read {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Invalid grammar JSON: {0}")]
+    GrammarJson(#[from] serde_json::Error),
+    #[error("Fail to load the grammar library: {0}")]
+    Library(#[from] libloading::Error),
+    #[error(transparent)]
+    Registration(#[from] RegistrationError),
+}
+
+/// A grammar loaded from a shared library.
+struct LoadedGrammar {
+    name: String,
+    extensions: Vec<&'static str>,
+    ts_language_fn: LanguageFn,
+    grammar_json: String,
+    lsp_language_id: String,
+    highlight_query: String,
+}
+
+impl LanguageSpec for LoadedGrammar {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn file_extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+
+    fn ts_language_fn(&self) -> LanguageFn {
+        self.ts_language_fn
+    }
+
+    fn grammar_json(&self) -> &str {
+        &self.grammar_json
+    }
+
+    fn lsp_language_id(&self) -> &str {
+        &self.lsp_language_id
+    }
+
+    fn highlight_query(&self) -> &str {
+        &self.highlight_query
+    }
+}
+
+#[derive(Deserialize)]
+struct GrammarName {
+    name: String,
+}
+
+fn read(path: &Path) -> Result<String, DynamicLanguageError> {
+    fs::read_to_string(path).map_err(|source| DynamicLanguageError::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+impl DynamicLanguage {
+    /// Reads the description of a language from the JSON file `manifest`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest cannot be read or is invalid.
+    pub fn from_manifest(manifest: &Path) -> Result<Self, DynamicLanguageError> {
+        let mut language: Self = serde_json::from_str(&read(manifest)?)?;
+        let base_dir = manifest.parent().unwrap_or(Path::new("."));
+        language.library = base_dir.join(&language.library);
+        language.grammar_json = base_dir.join(&language.grammar_json);
+        language.highlight_query = language.highlight_query.map(|it| base_dir.join(it));
+        Ok(language)
+    }
+
+    /// Loads the library and registers the language, which stays loaded until the process exits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read, the library does not export the language,
+    /// or a language with the same name already exists.
+    pub fn load(&self) -> Result<Language, DynamicLanguageError> {
+        let grammar_json = read(&self.grammar_json)?;
+        let GrammarName { name } = serde_json::from_str(&grammar_json)?;
+        if name.parse::<Language>().is_ok() {
+            return Err(RegistrationError::DuplicateName(name).into());
+        }
+        let highlight_query = match &self.highlight_query {
+            Some(path) => read(path)?,
+            None => String::new(),
+        };
+
+        // SAFETY: Loading the library runs its initializers, which the user vouches for
+        //         by passing a tree-sitter grammar.
+        let library = unsafe { libloading::Library::new(&self.library)? };
+        let symbol = format!("tree_sitter_{name}");
+        // SAFETY: The symbol is the language function generated by tree-sitter.
+        let ts_language_fn = unsafe {
+            let symbol = library.get::<unsafe extern "C" fn() -> *const ()>(symbol.as_bytes())?;
+            LanguageFn::from_raw(*symbol)
+        };
+        // Parsers and trees refer to the language, so the library is never unloaded.
+        std::mem::forget(library);
+
+        let lsp_language_id = self.lsp_language_id.clone().unwrap_or_else(|| name.clone());
+        let extensions = self.extensions.iter().cloned().map(leak).collect();
+        let language = registry::register(LoadedGrammar {
+            name,
+            extensions,
+            ts_language_fn,
+            grammar_json,
+            lsp_language_id,
+            highlight_query,
+        })?;
+        Ok(language)
+    }
+}
@@ -5,18 +5,31 @@ use tree_sitter_language::LanguageFn;
 use super::Language;
 use crate::language_data;
 
+#[derive(Clone, Copy)]
 pub(super) struct LanguageInfo {
     pub extensions: &'static [&'static str],
     pub highlight_query: &'static str,
     pub grammar_json: &'static str,
     pub lsp_language_id: &'static str,
     pub ts_language_fn: LanguageFn,
+    /// Source code exercising the constructs that servers expect,
+    /// for languages whose mined fragments are rarely accepted on their own.
+    pub seed_fragments: Option<&'static str>,
+    /// The grammar of the content of some leaves, for grammars split in two like Markdown's.
+    pub inline_grammar: Option<InlineGrammar>,
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct InlineGrammar {
+    /// The kind of the leaves of the main grammar whose content the inline grammar parses.
+    pub node_kind: &'static str,
+    pub ts_language_fn: LanguageFn,
 }
 
 impl Language {
     #[inline]
     #[must_use]
-    const fn info(self) -> LanguageInfo {
+    fn info(self) -> LanguageInfo {
         match self {
             Language::C => language_data::C,
             Language::CPlusPlus => language_data::CPP,
@@ -30,6 +43,17 @@ impl Language {
             Language::Solidity => language_data::SOLIDITY,
             Language::MLIR => language_data::MLIR,
             Language::QML => language_data::QML,
+            Language::Go => language_data::GO,
+            Language::TypeScript => language_data::TYPESCRIPT,
+            Language::TSX => language_data::TSX,
+            Language::Java => language_data::JAVA,
+            Language::Json => language_data::JSON,
+            Language::Yaml => language_data::YAML,
+            Language::Markdown => language_data::MARKDOWN,
+            Language::Zig => language_data::ZIG,
+            Language::Html => language_data::HTML,
+            Language::Css => language_data::CSS,
+            Language::Registered(id) => id.entry().info,
         }
     }
 
@@ -38,6 +62,17 @@ impl Language {
         self.info().extensions.iter().copied().collect()
     }
 
+    /// The language of source files with the extension `ext`, if any.
+    ///
+    /// The built-in languages take precedence over the registered ones.
+    #[must_use]
+    pub fn from_file_extension(ext: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .chain(Self::registered())
+            .find(|lang| lang.info().extensions.contains(&ext))
+    }
+
     /// Build a parser configured for this language.
     ///
     /// # Panics
@@ -63,19 +98,44 @@ impl Language {
     /// Panics if the bundled highlight query for this language is invalid.
     #[must_use]
     pub fn ts_highlight_query(self) -> &'static tree_sitter::Query {
-        const VARIANT_COUNT: usize = 12;
+        self.highlight_query_cell().get_or_init(|| {
+            let query_src = self.info().highlight_query;
+            tree_sitter::Query::new(&self.ts_language(), query_src)
+                .expect("The query provided by tree-sitter should be correct")
+        })
+    }
+
+    /// Replaces the bundled highlight query with `query_src` for the rest of the process,
+    /// e.g., with a newer query from the upstream repository of the grammar.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query is invalid or the highlight query is already in use.
+    pub fn override_highlight_query(self, query_src: &str) -> Result<(), HighlightQueryError> {
+        let query = tree_sitter::Query::new(&self.ts_language(), query_src)?;
+        self.highlight_query_cell()
+            .set(query)
+            .map_err(|_| HighlightQueryError::AlreadyInUse)
+    }
+
+    fn highlight_query_cell(self) -> &'static OnceLock<tree_sitter::Query> {
+        const VARIANT_COUNT: usize = Language::ALL.len();
         // Use `variant_count` when stabilized.
         // static QUERIES: [OnceLock<tree_sitter::Query>; variant_count::<Language>()] =
         //     [const { OnceLock::new() }; variant_count::<Language>()];
         static QUERIES: [OnceLock<tree_sitter::Query>; VARIANT_COUNT] =
             [const { OnceLock::new() }; VARIANT_COUNT];
 
-        let query_idx = (self as u8) as usize;
-        QUERIES[query_idx].get_or_init(|| {
-            let query_src = self.info().highlight_query;
-            tree_sitter::Query::new(&self.ts_language(), query_src)
-                .expect("The query provided by tree-sitter should be correct")
-        })
+        match self {
+            Language::Registered(id) => &id.entry().highlight_query,
+            builtin => {
+                let query_idx = Self::ALL
+                    .iter()
+                    .position(|it| *it == builtin)
+                    .expect("Every built-in language is in `ALL`");
+                &QUERIES[query_idx]
+            }
+        }
     }
 
     #[must_use]
@@ -83,15 +143,50 @@ impl Language {
         tree_sitter::Language::new(self.info().ts_language_fn)
     }
 
+    /// The grammar parsing the content of the leaves of the given kind, e.g., the `inline` leaves
+    /// of Markdown, whose emphasis, code spans and links are left to a separate inline grammar.
     #[must_use]
-    pub const fn grammar_json<'a>(self) -> &'a str {
+    pub fn inline_grammar(self) -> Option<(&'static str, tree_sitter::Language)> {
+        self.info()
+            .inline_grammar
+            .map(|it| (it.node_kind, tree_sitter::Language::new(it.ts_language_fn)))
+    }
+
+    #[must_use]
+    pub fn grammar_json<'a>(self) -> &'a str {
         self.info().grammar_json
     }
 
+    /// Curated source code whose fragments can be merged into the mined ones,
+    /// e.g., the common dialect operations of MLIR and the import statements of QML.
+    #[must_use]
+    pub fn seed_fragments(self) -> Option<&'static str> {
+        self.info().seed_fragments
+    }
+
+    /// Files placed next to every generated document of a registered language
+    /// (see [`crate::registry::LanguageSpec::skeleton_files`]).
+    /// The workspaces of the built-in languages are set up by the fuzzer instead.
+    #[must_use]
+    pub fn skeleton_files(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::Registered(id) => id.entry().spec.skeleton_files(),
+            _ => &[],
+        }
+    }
+
     /// The language identifier used by the Language Server Protocol
     /// See <https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#textDocumentItem>
     #[must_use]
-    pub const fn lsp_language_id<'a>(self) -> &'a str {
+    pub fn lsp_language_id<'a>(self) -> &'a str {
         self.info().lsp_language_id
     }
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum HighlightQueryError {
+    #[error("Invalid highlight query: {0}")]
+    Invalid(#[from] tree_sitter::QueryError),
+    #[error("The highlight query is already in use")]
+    AlreadyInUse,
+}
@@ -1,4 +1,16 @@
-use crate::language::LanguageInfo;
+use const_format::concatcp;
+
+use crate::language::{InlineGrammar, LanguageInfo};
+
+macro_rules! include_seed_fragments {
+    ($name: literal) => {
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/res/seed_fragments/",
+            $name
+        ))
+    };
+}
 
 macro_rules! include_grammar_json {
     ($name: literal) => {
@@ -18,6 +30,8 @@ pub const C: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_c::HIGHLIGHT_QUERY,
     lsp_language_id: "c",
     ts_language_fn: tree_sitter_c::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The C++ language information
@@ -27,6 +41,8 @@ pub const CPP: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_cpp::HIGHLIGHT_QUERY,
     lsp_language_id: "cpp",
     ts_language_fn: tree_sitter_cpp::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The JavaScript language information
@@ -36,6 +52,8 @@ pub const JAVASCRIPT: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_javascript::HIGHLIGHT_QUERY,
     lsp_language_id: "javascript",
     ts_language_fn: tree_sitter_javascript::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The Ruby language information
@@ -45,6 +63,8 @@ pub const RUBY: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_ruby::HIGHLIGHTS_QUERY,
     lsp_language_id: "ruby",
     ts_language_fn: tree_sitter_ruby::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The Rust language information
@@ -54,6 +74,8 @@ pub const RUST: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_rust::HIGHLIGHTS_QUERY,
     lsp_language_id: "rust",
     ts_language_fn: tree_sitter_rust::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The Toml language information
@@ -63,6 +85,8 @@ pub const TOML: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
     lsp_language_id: "toml",
     ts_language_fn: tree_sitter_toml_ng::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The LaTeX language information
@@ -75,6 +99,8 @@ pub const LATEX: LanguageInfo = LanguageInfo {
     )),
     lsp_language_id: "latex",
     ts_language_fn: tree_sitter_latex::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The BibTeX language information
@@ -84,6 +110,8 @@ pub const BIBTEX: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_bibtex::HIGHLIGHTS_QUERY,
     lsp_language_id: "bibtex",
     ts_language_fn: tree_sitter_bibtex::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The Verilog language information
@@ -97,6 +125,8 @@ pub const VERILOG: LanguageInfo = LanguageInfo {
     )),
     lsp_language_id: "verilog",
     ts_language_fn: tree_sitter_systemverilog::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The Solidity language information
@@ -106,6 +136,8 @@ pub const SOLIDITY: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_solidity::HIGHLIGHT_QUERY,
     lsp_language_id: "solidity",
     ts_language_fn: tree_sitter_solidity::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
 
 /// The MLIR language information
@@ -115,6 +147,8 @@ pub const MLIR: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_mlir::HIGHLIGHTS_QUERY,
     lsp_language_id: "mlir",
     ts_language_fn: tree_sitter_mlir::LANGUAGE,
+    seed_fragments: Some(include_seed_fragments!("mlir.mlir")),
+    inline_grammar: None,
 };
 
 /// The QML language information
@@ -124,4 +158,140 @@ pub const QML: LanguageInfo = LanguageInfo {
     highlight_query: tree_sitter_qmljs::HIGHLIGHTS_QUERY,
     lsp_language_id: "qml",
     ts_language_fn: tree_sitter_qmljs::LANGUAGE,
+    seed_fragments: Some(include_seed_fragments!("qml.qml")),
+    inline_grammar: None,
+};
+
+/// The Go language information
+pub const GO: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("go"),
+    extensions: &["go"],
+    highlight_query: tree_sitter_go::HIGHLIGHTS_QUERY,
+    lsp_language_id: "go",
+    ts_language_fn: tree_sitter_go::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
+};
+
+/// The TypeScript language information
+pub const TYPESCRIPT: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("typescript"),
+    extensions: &["ts", "mts", "cts"],
+    // The TypeScript highlights only extend those of JavaScript.
+    highlight_query: concatcp!(
+        tree_sitter_javascript::HIGHLIGHT_QUERY,
+        "\n",
+        tree_sitter_typescript::HIGHLIGHTS_QUERY
+    ),
+    lsp_language_id: "typescript",
+    ts_language_fn: tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+    seed_fragments: None,
+    inline_grammar: None,
+};
+
+/// The TSX language information
+pub const TSX: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("tsx"),
+    extensions: &["tsx"],
+    highlight_query: concatcp!(
+        tree_sitter_javascript::JSX_HIGHLIGHT_QUERY,
+        "\n",
+        tree_sitter_javascript::HIGHLIGHT_QUERY,
+        "\n",
+        tree_sitter_typescript::HIGHLIGHTS_QUERY
+    ),
+    lsp_language_id: "typescriptreact",
+    ts_language_fn: tree_sitter_typescript::LANGUAGE_TSX,
+    seed_fragments: None,
+    inline_grammar: None,
+};
+
+/// The Java language information
+pub const JAVA: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("java"),
+    extensions: &["java"],
+    highlight_query: tree_sitter_java::HIGHLIGHTS_QUERY,
+    lsp_language_id: "java",
+    ts_language_fn: tree_sitter_java::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
+};
+
+/// The JSON language information
+pub const JSON: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("json"),
+    extensions: &["json"],
+    highlight_query: tree_sitter_json::HIGHLIGHTS_QUERY,
+    lsp_language_id: "json",
+    ts_language_fn: tree_sitter_json::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
+};
+
+/// The YAML language information
+pub const YAML: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("yaml"),
+    extensions: &["yaml", "yml"],
+    highlight_query: tree_sitter_yaml::HIGHLIGHTS_QUERY,
+    lsp_language_id: "yaml",
+    ts_language_fn: tree_sitter_yaml::LANGUAGE,
+    seed_fragments: Some(include_seed_fragments!("yaml.yaml")),
+    inline_grammar: None,
+};
+
+/// The Markdown language information
+///
+/// Documents are parsed with the block grammar, in which paragraphs and headings are `inline`
+/// leaves whose content is parsed with the inline grammar. The inline grammar is merged into the
+/// grammar JSON to generate their content.
+pub const MARKDOWN: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("markdown"),
+    extensions: &["md", "markdown"],
+    highlight_query: tree_sitter_md::HIGHLIGHT_QUERY_BLOCK,
+    lsp_language_id: "markdown",
+    ts_language_fn: tree_sitter_md::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: Some(InlineGrammar {
+        node_kind: "inline",
+        ts_language_fn: tree_sitter_md::INLINE_LANGUAGE,
+    }),
+};
+
+/// The Zig language information
+pub const ZIG: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("zig"),
+    extensions: &["zig"],
+    highlight_query: tree_sitter_zig::HIGHLIGHTS_QUERY,
+    lsp_language_id: "zig",
+    ts_language_fn: tree_sitter_zig::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
+};
+
+/// The HTML language information
+///
+/// The grammar JSON enumerates common elements, whose start and end tags
+/// tree-sitter-html matches in its external scanner.
+pub const HTML: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("html"),
+    extensions: &["html", "htm"],
+    highlight_query: tree_sitter_html::HIGHLIGHTS_QUERY,
+    lsp_language_id: "html",
+    ts_language_fn: tree_sitter_html::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
+};
+
+/// The CSS language information
+///
+/// The grammar JSON spells the whitespace of descendant selectors, which tree-sitter-css
+/// recognizes in its external scanner, as a literal space.
+pub const CSS: LanguageInfo = LanguageInfo {
+    grammar_json: include_grammar_json!("css"),
+    extensions: &["css"],
+    highlight_query: tree_sitter_css::HIGHLIGHTS_QUERY,
+    lsp_language_id: "css",
+    ts_language_fn: tree_sitter_css::LANGUAGE,
+    seed_fragments: None,
+    inline_grammar: None,
 };
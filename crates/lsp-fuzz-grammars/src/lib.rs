@@ -1,12 +1,24 @@
-use derive_more::{Display, FromStr};
+use std::{fmt, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
+pub mod dynamic;
 mod language;
 mod language_data;
+pub mod registry;
+
+pub use language::HighlightQueryError;
+use registry::LanguageId;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, Display, FromStr)]
+/// The languages of the generated documents.
+///
+/// Besides the built-in languages, languages can be defined outside this crate with
+/// [`registry::LanguageSpec`], e.g., grammars loaded at runtime by [`dynamic::DynamicLanguage`].
+/// Languages are serialized by name, so inputs with registered languages can only be read
+/// back once the same languages are registered.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(into = "String", try_from = "String")]
 #[non_exhaustive]
-#[repr(u8)]
 pub enum Language {
     C,
     CPlusPlus,
@@ -20,6 +32,120 @@ pub enum Language {
     Solidity,
     MLIR,
     QML,
+    Go,
+    TypeScript,
+    TSX,
+    Java,
+    Json,
+    Yaml,
+    Markdown,
+    Zig,
+    Html,
+    Css,
+    /// A language registered with [`registry::register`].
+    Registered(LanguageId),
+}
+
+impl Language {
+    /// All the built-in languages.
+    pub const ALL: [Language; 22] = [
+        Language::C,
+        Language::CPlusPlus,
+        Language::JavaScript,
+        Language::Ruby,
+        Language::Rust,
+        Language::Toml,
+        Language::LaTeX,
+        Language::BibTeX,
+        Language::Verilog,
+        Language::Solidity,
+        Language::MLIR,
+        Language::QML,
+        Language::Go,
+        Language::TypeScript,
+        Language::TSX,
+        Language::Java,
+        Language::Json,
+        Language::Yaml,
+        Language::Markdown,
+        Language::Zig,
+        Language::Html,
+        Language::Css,
+    ];
+
+    /// The languages registered with [`registry::register`] so far.
+    #[must_use]
+    pub fn registered() -> Vec<Language> {
+        registry::all()
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::Registered(id) => f.write_str(id.entry().spec.name()),
+            builtin => fmt::Debug::fmt(builtin, f),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Unknown language: {name}{}",
+    .suggestion.map_or_else(String::new, |it| format!(" (did you mean {it}?)"))
+)]
+pub struct UnknownLanguage {
+    name: String,
+    suggestion: Option<Language>,
+}
+
+impl UnknownLanguage {
+    /// The languages whose names are at least this similar to an unknown name are suggested.
+    const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+    fn new(name: &str) -> Self {
+        let suggestion = Language::ALL
+            .into_iter()
+            .chain(Language::registered())
+            .map(|it| {
+                let similarity =
+                    strsim::jaro_winkler(&name.to_lowercase(), &it.to_string().to_lowercase());
+                (it, similarity)
+            })
+            .filter(|&(_, similarity)| similarity >= Self::SUGGESTION_THRESHOLD)
+            .max_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs))
+            .map(|(it, _)| it);
+        Self {
+            name: name.to_owned(),
+            suggestion,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = UnknownLanguage;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|it| it.to_string() == s)
+            .or_else(|| registry::find(s))
+            .ok_or_else(|| UnknownLanguage::new(s))
+    }
+}
+
+impl From<Language> for String {
+    fn from(language: Language) -> Self {
+        language.to_string()
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = UnknownLanguage;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
 }
 
 /// Well-known highlight capture names.
@@ -39,3 +165,29 @@ pub const WELL_KNOWN_HIGHLIGHT_CAPTURE_NAMES: [&str; 9] = [
     "constant",
     "variable",
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::Language;
+
+    #[test]
+    fn suggest_similar_language_names() {
+        let err = "rust".parse::<Language>().unwrap_err();
+        assert_eq!(err.suggestion, Some(Language::Rust));
+        assert_eq!(
+            err.to_string(),
+            "Unknown language: rust (did you mean Rust?)"
+        );
+        let err = "Javascript".parse::<Language>().unwrap_err();
+        assert_eq!(err.suggestion, Some(Language::JavaScript));
+        let err = "CPlusPlsu".parse::<Language>().unwrap_err();
+        assert_eq!(err.suggestion, Some(Language::CPlusPlus));
+    }
+
+    #[test]
+    fn no_suggestion_for_dissimilar_names() {
+        let err = "Haskell".parse::<Language>().unwrap_err();
+        assert_eq!(err.suggestion, None);
+        assert_eq!(err.to_string(), "Unknown language: Haskell");
+    }
+}
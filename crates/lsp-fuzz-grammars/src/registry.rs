@@ -0,0 +1,150 @@
+//! Languages defined outside this crate.
+//!
+//! Implement [`LanguageSpec`] for a language and [`register`] it at startup to generate and
+//! fuzz documents of it like the built-in languages, without forking this crate.
+
+use std::sync::{Mutex, OnceLock};
+
+use tree_sitter_language::LanguageFn;
+
+use crate::{Language, language::LanguageInfo};
+
+/// The definition of a language.
+///
+/// The name identifies the language, e.g., in `--language-fragments` and in serialized inputs,
+/// so it must not change between the runs sharing a corpus.
+pub trait LanguageSpec: Send + Sync {
+    /// The name of the language, which must be unique among all the languages.
+    fn name(&self) -> &str;
+
+    /// The extensions of the source files of the language, without the leading dot.
+    fn file_extensions(&self) -> &[&str];
+
+    /// The tree-sitter language, e.g., the `LANGUAGE` of a tree-sitter grammar crate.
+    fn ts_language_fn(&self) -> LanguageFn;
+
+    /// The `grammar.json` generated by `tree-sitter generate` for the tree-sitter language.
+    fn grammar_json(&self) -> &str;
+
+    /// The language identifier used by the Language Server Protocol.
+    fn lsp_language_id(&self) -> &str {
+        self.name()
+    }
+
+    /// Query for tree-sitter syntax highlighting (see [`Language::ts_highlight_query`]).
+    /// Without it, highlight-based node filters select nothing in documents of the language.
+    fn highlight_query(&self) -> &str {
+        ""
+    }
+
+    /// Curated source code whose fragments can be merged into the mined ones.
+    fn seed_fragments(&self) -> Option<&str> {
+        None
+    }
+
+    /// Files placed next to every generated document of the language, as pairs of paths
+    /// relative to the workspace root and contents, e.g., the project file of a build tool
+    /// without which the server does not analyze the document.
+    fn skeleton_files(&self) -> &[(&str, &str)] {
+        &[]
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistrationError {
+    #[error("A language named {0} already exists")]
+    DuplicateName(String),
+    #[error("Too many registered languages")]
+    TooMany,
+    #[error("The grammar is incompatible with the tree-sitter runtime: {0}")]
+    Incompatible(#[from] tree_sitter::LanguageError),
+    #[error("Invalid highlight query: {0}")]
+    HighlightQuery(#[from] tree_sitter::QueryError),
+}
+
+/// The handle of a registered [`LanguageSpec`], wrapped in [`Language::Registered`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LanguageId(u8);
+
+pub(crate) struct Entry {
+    pub spec: &'static dyn LanguageSpec,
+    pub info: LanguageInfo,
+    pub highlight_query: OnceLock<tree_sitter::Query>,
+}
+
+/// The registered languages indexed by their IDs, which are never removed so that their entries
+/// can be `'static` and looked up without locking, e.g., whenever the info of a language is read.
+static REGISTRY: [OnceLock<&'static Entry>; 1 << u8::BITS] =
+    [const { OnceLock::new() }; 1 << u8::BITS];
+
+/// Serializes the registrations, so that the languages take the IDs in order.
+static REGISTRATION: Mutex<()> = Mutex::new(());
+
+impl LanguageId {
+    pub(crate) fn entry(self) -> &'static Entry {
+        REGISTRY[usize::from(self.0)]
+            .get()
+            .expect("Language IDs are only handed out once registered")
+    }
+}
+
+/// The entries of the registered languages, in the order they were registered.
+fn entries() -> impl Iterator<Item = &'static Entry> {
+    REGISTRY.iter().map_while(|it| it.get().copied())
+}
+
+/// Registers `spec`, which stays registered until the process exits.
+///
+/// # Errors
+///
+/// Returns an error if a language with the same name already exists, or the tree-sitter
+/// language or the highlight query of `spec` is invalid.
+pub fn register(spec: impl LanguageSpec + 'static) -> Result<Language, RegistrationError> {
+    if spec.name().parse::<Language>().is_ok() {
+        return Err(RegistrationError::DuplicateName(spec.name().to_owned()));
+    }
+    let ts_language = tree_sitter::Language::new(spec.ts_language_fn());
+    tree_sitter::Parser::new().set_language(&ts_language)?;
+    tree_sitter::Query::new(&ts_language, spec.highlight_query())?;
+
+    let _registration = REGISTRATION.lock().expect("The registry is poisoned");
+    if entries().any(|it| it.spec.name() == spec.name()) {
+        return Err(RegistrationError::DuplicateName(spec.name().to_owned()));
+    }
+    let id = u8::try_from(entries().count()).map_err(|_| RegistrationError::TooMany)?;
+    let spec: &'static dyn LanguageSpec = Box::leak(Box::new(spec));
+    let info = LanguageInfo {
+        extensions: spec.file_extensions(),
+        highlight_query: spec.highlight_query(),
+        grammar_json: spec.grammar_json(),
+        lsp_language_id: spec.lsp_language_id(),
+        ts_language_fn: spec.ts_language_fn(),
+        seed_fragments: spec.seed_fragments(),
+        inline_grammar: None,
+    };
+    let entry = Box::leak(Box::new(Entry {
+        spec,
+        info,
+        highlight_query: OnceLock::new(),
+    }));
+    REGISTRY[usize::from(id)]
+        .set(entry)
+        .unwrap_or_else(|_| unreachable!("Registrations are serialized"));
+    Ok(Language::Registered(LanguageId(id)))
+}
+
+/// The registered language named `name`, if any.
+pub(crate) fn find(name: &str) -> Option<Language> {
+    entries()
+        .position(|it| it.spec.name() == name)
+        .and_then(|it| u8::try_from(it).ok())
+        .map(|it| Language::Registered(LanguageId(it)))
+}
+
+/// All the registered languages, in the order they were registered.
+pub(crate) fn all() -> Vec<Language> {
+    (0..entries().count())
+        .filter_map(|it| u8::try_from(it).ok())
+        .map(|it| Language::Registered(LanguageId(it)))
+        .collect()
+}
@@ -0,0 +1,326 @@
+//! Attributing new corpus entries to the mutations that produced them.
+//!
+//! When enabled, each corpus entry found by the fuzz loop is written to an [`AttributionLog`]
+//! as a JSON line with its parent, the mutation operators applied to the parent, the number
+//! of edges it added, and the methods of its messages. [`operator_effectiveness`] summarizes
+//! such a log per operator.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+use itertools::Itertools;
+use libafl::{
+    HasMetadata, HasNamedMetadata,
+    corpus::CorpusId,
+    feedbacks::{MapFeedback, MapFeedbackMetadata},
+    mutators::{MutationResult, Mutator},
+    observers::MapObserver,
+    state::{HasCorpus, HasExecutions},
+};
+use libafl_bolts::{Named, SerdeAny};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::lsp_input::LspInput;
+
+/// The names of the mutation operators applied to the current input, in the order applied.
+///
+/// The metadata is present only while an [`AttributeMutations`] with a log mutates an input,
+/// so that mutators record their names only when the names are used.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct MutationTrail {
+    pub operators: Vec<String>,
+}
+
+impl MutationTrail {
+    /// Records that the operator `name` mutated the current input, if the trail is recorded.
+    pub fn record<State: HasMetadata>(state: &mut State, name: &str) {
+        if let Some(trail) = state.metadata_map_mut().get_mut::<Self>() {
+            trail.operators.push(name.to_owned());
+        }
+    }
+}
+
+/// A corpus entry and the mutations that produced it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributionRecord {
+    pub corpus_id: usize,
+    /// The corpus entry that was mutated, if any.
+    pub parent_id: Option<usize>,
+    /// The names of the operators applied to the parent, in the order applied.
+    pub operators: Vec<String>,
+    /// The number of edges covered by the campaign after adding the entry.
+    pub edges: usize,
+    /// The number of edges the entry added to the coverage of the campaign.
+    pub edge_delta: usize,
+    /// The distinct methods of the messages of the entry.
+    pub methods: Vec<String>,
+    pub executions: u64,
+}
+
+/// A file receiving [`AttributionRecord`]s as JSON lines, with the coverage they are
+/// measured against.
+#[derive(Debug)]
+pub struct AttributionLog<O> {
+    writer: BufWriter<File>,
+    coverage_feedback_name: String,
+    _observer: PhantomData<O>,
+}
+
+impl<O> AttributionLog<O> {
+    /// Creates the file at `path`, truncating it if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created.
+    pub fn create<C, N, R>(
+        path: &Path,
+        map_feedback: &MapFeedback<C, N, O, R>,
+    ) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(Self {
+            writer,
+            coverage_feedback_name: map_feedback.name().clone().into_owned(),
+            _observer: PhantomData,
+        })
+    }
+
+    /// Appends `record` to the log and flushes it, so that readers see complete lines.
+    ///
+    /// Write failures are logged instead of interrupting the campaign.
+    pub fn write(&mut self, record: &AttributionRecord) {
+        let result = serde_json::to_writer(&mut self.writer, record)
+            .map_err(io::Error::from)
+            .and_then(|()| self.writer.write_all(b"\n"))
+            .and_then(|()| self.writer.flush());
+        if let Err(err) = result {
+            warn!("Failed to write attribution record: {err}");
+        }
+    }
+
+    fn covered_edges<State>(&self, state: &State) -> usize
+    where
+        State: HasNamedMetadata,
+        O: MapObserver,
+        MapFeedbackMetadata<O::Entry>: SerdeAny,
+    {
+        state
+            .named_metadata::<MapFeedbackMetadata<O::Entry>>(&self.coverage_feedback_name)
+            .map_or(0, |it| it.num_covered_map_indexes)
+    }
+}
+
+/// Reads the records of the attribution log at `path`, skipping blank lines.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or a line is not a record.
+pub fn read_attribution_log(path: &Path) -> io::Result<Vec<AttributionRecord>> {
+    let reader = io::BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(records)
+}
+
+/// The mutation before an execution, to be attributed if the input is added to the corpus.
+#[derive(Debug)]
+struct PendingAttribution {
+    parent_id: Option<usize>,
+    edges: usize,
+    operators: Vec<String>,
+    methods: Vec<String>,
+}
+
+/// Writes an [`AttributionRecord`] for each input mutated by the inner mutator
+/// that is added to the corpus.
+///
+/// The operators are those recorded in the [`MutationTrail`] by the inner mutators.
+/// The mutator only delegates to the inner one if no log is given.
+#[derive(Debug)]
+pub struct AttributeMutations<M, O> {
+    inner: M,
+    log: Option<AttributionLog<O>>,
+    pending: Option<PendingAttribution>,
+}
+
+impl<M, O> AttributeMutations<M, O> {
+    pub const fn new(inner: M, log: Option<AttributionLog<O>>) -> Self {
+        Self {
+            inner,
+            log,
+            pending: None,
+        }
+    }
+}
+
+impl<M, O> Named for AttributeMutations<M, O> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("AttributeMutations");
+        &NAME
+    }
+}
+
+impl<M, O, State> Mutator<LspInput, State> for AttributeMutations<M, O>
+where
+    M: Mutator<LspInput, State>,
+    O: MapObserver,
+    MapFeedbackMetadata<O::Entry>: SerdeAny,
+    State: HasMetadata + HasNamedMetadata + HasCorpus<LspInput> + HasExecutions,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some(log) = self.log.as_ref() else {
+            return self.inner.mutate(state, input);
+        };
+        state.add_metadata(MutationTrail::default());
+        let parent_id = state.corpus().current().map(|it| it.0);
+        let edges = log.covered_edges(state);
+        let result = self.inner.mutate(state, input)?;
+        let operators = state
+            .metadata_map_mut()
+            .remove::<MutationTrail>()
+            .map(|it| it.operators)
+            .unwrap_or_default();
+        let methods = input
+            .messages
+            .iter()
+            .map(|it| it.method().to_owned())
+            .unique()
+            .collect();
+        self.pending = Some(PendingAttribution {
+            parent_id,
+            edges,
+            operators,
+            methods,
+        });
+        Ok(result)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut State,
+        new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        if let (Some(log), Some(pending), Some(corpus_id)) =
+            (self.log.as_mut(), self.pending.take(), new_corpus_id)
+        {
+            let edges = log.covered_edges(state);
+            let record = AttributionRecord {
+                corpus_id: corpus_id.0,
+                parent_id: pending.parent_id,
+                operators: pending.operators,
+                edges,
+                edge_delta: edges.saturating_sub(pending.edges),
+                methods: pending.methods,
+                executions: *state.executions(),
+            };
+            log.write(&record);
+        }
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+/// How productive a mutation operator was over an attribution log.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OperatorEffectiveness {
+    pub operator: String,
+    /// The number of corpus entries the operator was applied to produce.
+    pub entries: usize,
+    /// The number of those entries that added edges.
+    pub entries_with_new_edges: usize,
+    /// The edges added by those entries, each entry counted once for every operator applied.
+    pub edges: usize,
+}
+
+/// Summarizes `records` per operator, most edges first.
+///
+/// An operator applied several times to produce an entry is counted once for it.
+#[must_use]
+pub fn operator_effectiveness(records: &[AttributionRecord]) -> Vec<OperatorEffectiveness> {
+    let mut table: HashMap<&str, OperatorEffectiveness> = HashMap::new();
+    for record in records {
+        for operator in record.operators.iter().unique() {
+            let row = table
+                .entry(operator.as_str())
+                .or_insert_with(|| OperatorEffectiveness {
+                    operator: operator.clone(),
+                    ..OperatorEffectiveness::default()
+                });
+            row.entries += 1;
+            if record.edge_delta > 0 {
+                row.entries_with_new_edges += 1;
+            }
+            row.edges += record.edge_delta;
+        }
+    }
+    table
+        .into_values()
+        .sorted_by(|a, b| {
+            b.edges
+                .cmp(&a.edges)
+                .then(b.entries.cmp(&a.entries))
+                .then_with(|| a.operator.cmp(&b.operator))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributionRecord, operator_effectiveness};
+
+    fn record(operators: &[&str], edge_delta: usize) -> AttributionRecord {
+        AttributionRecord {
+            corpus_id: 0,
+            parent_id: None,
+            operators: operators.iter().map(ToString::to_string).collect(),
+            edges: 0,
+            edge_delta,
+            methods: Vec::new(),
+            executions: 0,
+        }
+    }
+
+    #[test]
+    fn effectiveness_per_operator() {
+        let records = [
+            record(&["SwapRequests", "ExpandGrammar", "ExpandGrammar"], 4),
+            record(&["ExpandGrammar"], 0),
+            record(&["DropRandomMessage"], 1),
+        ];
+        let table = operator_effectiveness(&records);
+        let rows: Vec<_> = table
+            .iter()
+            .map(|it| {
+                (
+                    it.operator.as_str(),
+                    it.entries,
+                    it.entries_with_new_edges,
+                    it.edges,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("ExpandGrammar", 2, 1, 4),
+                ("SwapRequests", 1, 1, 4),
+                ("DropRandomMessage", 1, 1, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1212 @@
+//! A fuzzing campaign against an LSP server, for embedding in other binaries and tests.
+//!
+//! [`CampaignBuilder`] wires the executor, the feedbacks, the corpora, and the stages
+//! of a campaign. The `fuzz` command of the CLI is built on it.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    hash::Hash,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use libafl::{
+    Evaluator, Fuzzer, HasMetadata, HasNamedMetadata, NopInputFilter, StdFuzzerBuilder,
+    corpus::{CachedOnDiskCorpus, Corpus, HasTestcase, OnDiskCorpus},
+    events::SimpleEventManager,
+    feedback_and_fast, feedback_or, feedback_or_fast,
+    feedbacks::{
+        ConstFeedback, CrashFeedback, Feedback, MaxMapFeedback, NewHashFeedback, TimeFeedback,
+    },
+    generators::Generator,
+    inputs::Input,
+    monitors::SimpleMonitor,
+    mutators::HavocScheduledMutator,
+    observers::{
+        AsanBacktraceObserver, CanTrack, HitcountsMapObserver, StdMapObserver, TimeObserver,
+    },
+    schedulers::{
+        IndexesLenTimeMinimizerScheduler, Scheduler, StdWeightedScheduler,
+        powersched::{BaseSchedule, PowerSchedule},
+    },
+    stages::{CalibrationStage, StdPowerMutationalStage},
+    state::{HasCorpus, HasExecutions, HasRand, HasSolutions, HasStartTime, StdState},
+};
+use libafl_bolts::{
+    AsSliceMut, HasLen, Named,
+    fs::InputFile,
+    rands::StdRand,
+    shmem::{ShMem, ShMemProvider, StdShMemProvider},
+    tuples::MatchName,
+};
+use lsp_fuzz_grammars::Language;
+use nix::sys::signal::Signal;
+use tracing::info;
+use tuple_list::tuple_list;
+
+use crate::{
+    attribution::{AttributeMutations, AttributionLog},
+    corpus::{
+        TestCaseFileNameFeedback,
+        corpus_kind::{CORPUS, SOLUTION},
+    },
+    execution::{
+        FuzzExecutionConfig, FuzzInput, FuzzTargetInfo, LspExecutor,
+        index_artifacts::IndexArtifactFeedback,
+        phases::ShutdownHangFeedback,
+        polyglot::CompanionConfig,
+        responses::{LspOutputObserver, MalformedFramingFeedback, ResponseTranscriptFeedback},
+        side_channel::SideChannelParser,
+        subprocess::{SubprocessCrashFeedback, SubprocessObserver},
+        workspace_observer::WorkspaceObserver,
+    },
+    fuzz_target::{self, StaticTargetBinaryInfo},
+    lsp::GeneratorsConfig,
+    lsp_input::{
+        LspInput, LspInputBytesConverter, LspInputGenerator, LspInputMutator, WorkspaceTemplate,
+        calibration_check::CalibrationCheck,
+        configuration::ConfigurationCoverageFeedback,
+        messages::message_mutations,
+        sequence_length::TrackSequenceLength,
+        server_response::{
+            LspResponseFeedback,
+            conformance::ConformanceFeedback,
+            dictionary::ResponseDictionaryFeedback,
+            learned_fragments::LearnedFragmentsFeedback,
+            minted_ids::MintedIdsFeedback,
+            notifications::NotificationLogFeedback,
+            recovery::ParserRecoveryFeedback,
+            supported_methods::{SupportedMethods, SupportedMethodsFeedback},
+        },
+        stagnation_mutations,
+    },
+    mutators::{MutationRegistry, WeightedHavocMutator},
+    stages::{
+        CampaignDatabase, CampaignEvent, CampaignPhase, CampaignRecorderStage,
+        CleanupWorkspaceDirs, CoverageStagnation, DiskLimits, DiskWatchdogStage, EventStream,
+        EventStreamStage, ExecutionsStopStage, MapDensityStage, NotificationStatsStage,
+        RegenerationStage, StagnationFallbackStage, StatsStage, StopOnReceived, TimeoutStopStage,
+        Webhook, WebhookStage,
+    },
+    text_document::{
+        diversity::DEFAULT_SEED_SIMILARITY, generation::GrammarContextLookup,
+        grammar_coverage::GrammarCoverageFeedback,
+        mutations::node_filters::NodeKindFrequencyFeedback, rule_scheduling::RuleCoverageFeedback,
+        text_document_mutator,
+    },
+    utf8::UTF8Tokens,
+    utils::AflContext,
+};
+
+/// The size of the shared memory passing inputs to the target.
+pub const INPUT_SHM_SIZE: usize = 15 * 1024 * 1024 * 1024;
+
+/// The directory of the corpus in the state directory of a campaign.
+pub const CORPUS_DIR: &str = "corpus";
+/// The directory of the solutions in the state directory of a campaign.
+pub const SOLUTION_DIR: &str = "solutions";
+/// The file of the stats samples in the state directory of a campaign.
+pub const STATS_FILE: &str = "stats";
+/// The file of the capabilities of the server in the state directory of a campaign,
+/// see [`SupportedMethodsFeedback`].
+pub const SERVER_CAPABILITIES_FILE: &str = "server_capabilities.json";
+/// The file of the divergences found by [`CalibrationCheck`] in the state directory
+/// of a campaign.
+pub const CALIBRATION_DIVERGENCES_FILE: &str = "calibration_divergences.jsonl";
+
+/// How inputs are passed to the target.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// Through shared memory, which requires a target built with the AFL++ compilers.
+    #[default]
+    SharedMemory,
+    /// Through the standard input of the target.
+    Stdin,
+}
+
+/// The state of a campaign, e.g., for registering mutations with
+/// [`CampaignBuilder::with_text_document_mutations`].
+pub type CampaignState =
+    StdState<CachedOnDiskCorpus<LspInput>, LspInput, StdRand, OnDiskCorpus<LspInput>>;
+
+/// What a campaign found when it stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CampaignReport {
+    pub executions: u64,
+    pub corpus: usize,
+    pub solutions: usize,
+}
+
+impl CampaignReport {
+    fn from_state(state: &CampaignState) -> Self {
+        Self {
+            executions: *state.executions(),
+            corpus: state.corpus().count(),
+            solutions: state.solutions().count(),
+        }
+    }
+}
+
+/// A second LSP server run against the same workspace as the target, see [`polyglot`].
+///
+/// Its coverage map follows that of the target in the map observed by the campaign.
+///
+/// [`polyglot`]: crate::execution::polyglot
+#[derive(Debug)]
+pub struct Companion {
+    pub target: FuzzTargetInfo,
+    /// The file extensions (e.g., `toml`) or file names (e.g., `CMakeLists.txt`) of the documents
+    /// sent to the companion.
+    pub documents: Vec<String>,
+}
+
+/// Builds a fuzzing campaign against an LSP server.
+///
+/// The corpus and the solutions are stored in the [`CORPUS_DIR`] and [`SOLUTION_DIR`]
+/// subdirectories of the state directory. Seeds are generated when the campaign starts;
+/// the inputs already in the corpus directory are not loaded again.
+/// The campaign runs until its time budget or its execution budget is spent,
+/// or a stop signal is received.
+#[derive(Debug)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "Each switch is set by its own builder method"
+)]
+pub struct CampaignBuilder<'g> {
+    target: FuzzTargetInfo,
+    grammar_lookup: &'g GrammarContextLookup,
+    state_dir: PathBuf,
+    transport: Transport,
+    generators_config: GeneratorsConfig,
+    language_weights: HashMap<Language, usize>,
+    workspace_templates: Vec<WorkspaceTemplate>,
+    seed_similarity: Option<f64>,
+    regeneration: Option<(u64, usize)>,
+    stagnation_fallback: Option<(Duration, Duration)>,
+    coverage_map_size: Option<usize>,
+    temp_dir: PathBuf,
+    instance_id: String,
+    workspace_max_age: Duration,
+    disk_limits: DiskLimits,
+    seeds: usize,
+    power_schedule: BaseSchedule,
+    cycle_power_schedule: bool,
+    asan: bool,
+    track_subprocesses: bool,
+    conformance_checks: bool,
+    index_validation: bool,
+    calibration_check: bool,
+    auto_tokens: bool,
+    debug_output: (bool, bool),
+    side_channels: Vec<Box<dyn SideChannelParser>>,
+    logged_methods: Vec<String>,
+    logged_side_channels: Vec<String>,
+    companion: Option<Companion>,
+    attribution_log: Option<PathBuf>,
+    event_stream: Option<(PathBuf, Duration)>,
+    database: Option<(PathBuf, Duration)>,
+    webhook: Option<(String, String)>,
+    stop_signal: Option<Receiver<()>>,
+    time_budget: Option<Duration>,
+    max_executions: Option<u64>,
+    random_seed: Option<u64>,
+    text_document_mutations: MutationRegistry<'g, LspInput, CampaignState>,
+}
+
+impl<'g> CampaignBuilder<'g> {
+    const DEFAULT_SEEDS: usize = 32;
+    const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1200);
+    const DEFAULT_FALLBACK_DURATION: Duration = Duration::from_secs(600);
+    const WORKSPACE_CLEANUP_INTERVAL: u64 = 10_000;
+    const WORKSPACE_MAX_AGE: Duration = Duration::from_secs(600);
+    const NOTIFICATION_STATS_INTERVAL: Duration = Duration::from_secs(60);
+    /// The havoc stack of the message mutator, as in the havoc mutator of libafl.
+    const MESSAGE_MUTATION_STACK_POW: usize = 3;
+    const FALLBACK_MUTATION_STACK_POW: usize = 6;
+
+    /// Creates a campaign against `target`, generating documents from `grammar_lookup`
+    /// and storing its state in `state_dir`.
+    #[must_use]
+    pub fn new(
+        target: FuzzTargetInfo,
+        grammar_lookup: &'g GrammarContextLookup,
+        state_dir: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            target,
+            grammar_lookup,
+            state_dir: state_dir.into(),
+            transport: Transport::default(),
+            generators_config: GeneratorsConfig::full(),
+            language_weights: HashMap::new(),
+            workspace_templates: Vec::new(),
+            seed_similarity: Some(DEFAULT_SEED_SIMILARITY),
+            regeneration: None,
+            stagnation_fallback: None,
+            coverage_map_size: None,
+            temp_dir: std::env::temp_dir(),
+            instance_id: std::process::id().to_string(),
+            workspace_max_age: Self::WORKSPACE_MAX_AGE,
+            disk_limits: DiskLimits {
+                min_free_bytes: 0,
+                max_total_bytes: None,
+            },
+            seeds: Self::DEFAULT_SEEDS,
+            power_schedule: BaseSchedule::FAST,
+            cycle_power_schedule: false,
+            asan: false,
+            track_subprocesses: false,
+            conformance_checks: false,
+            index_validation: false,
+            calibration_check: cfg!(debug_assertions),
+            auto_tokens: true,
+            debug_output: (false, false),
+            side_channels: Vec::new(),
+            logged_methods: Vec::new(),
+            logged_side_channels: Vec::new(),
+            companion: None,
+            attribution_log: None,
+            event_stream: None,
+            database: None,
+            webhook: None,
+            stop_signal: None,
+            time_budget: None,
+            max_executions: None,
+            random_seed: None,
+            text_document_mutations: MutationRegistry::new(),
+        }
+    }
+
+    /// Creates a campaign against the fuzz target `binary`, with the execution mode and
+    /// the sanitizer detected from the binary and the default timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the binary cannot be read or is not instrumented with AFL++.
+    pub fn for_binary(
+        binary: &Path,
+        grammar_lookup: &'g GrammarContextLookup,
+        state_dir: impl Into<PathBuf>,
+    ) -> Result<Self, libafl::Error> {
+        let binary_file = fs::read(binary).afl_context("Reading fuzz target")?;
+        let binary_info =
+            StaticTargetBinaryInfo::scan(&binary_file).afl_context("Analyzing fuzz target")?;
+        if !binary_info.is_afl_instrumented {
+            return Err(libafl::Error::illegal_argument(
+                "The fuzz target is not instrumented with AFL++",
+            ));
+        }
+        let target = FuzzTargetInfo {
+            path: binary.to_owned(),
+            args: Vec::new(),
+            persistent_fuzzing: binary_info.is_persistent_mode,
+            defer_fork_server: binary_info.is_defer_fork_server,
+            crash_exit_code: None,
+            timeout: Self::DEFAULT_TIMEOUT.into(),
+            phase_timeouts: None,
+            kill_signal: Signal::SIGKILL,
+            env: HashMap::new(),
+            container: None,
+        };
+        Ok(Self::new(target, grammar_lookup, state_dir)
+            .with_asan(binary_info.uses_address_sanitizer))
+    }
+
+    /// Passes the inputs to the target with `transport` instead of shared memory.
+    #[must_use]
+    pub fn with_transport(self, transport: Transport) -> Self {
+        Self { transport, ..self }
+    }
+
+    /// Generates and mutates messages with `generators_config` instead of
+    /// [`GeneratorsConfig::full`].
+    #[must_use]
+    pub fn with_generators_config(self, generators_config: GeneratorsConfig) -> Self {
+        Self {
+            generators_config,
+            ..self
+        }
+    }
+
+    /// Picks the languages of generated documents in proportion to `weights`,
+    /// see [`LspInputGenerator::with_language_weights`].
+    #[must_use]
+    pub fn with_language_weights(self, language_weights: HashMap<Language, usize>) -> Self {
+        Self {
+            language_weights,
+            ..self
+        }
+    }
+
+    /// Adds the files of `templates` to every generated workspace,
+    /// see [`LspInputGenerator::with_workspace_templates`].
+    #[must_use]
+    pub fn with_workspace_templates(self, workspace_templates: Vec<WorkspaceTemplate>) -> Self {
+        Self {
+            workspace_templates,
+            ..self
+        }
+    }
+
+    /// Regenerates seed documents at least `threshold` similar to an earlier seed, or keeps
+    /// all of them if `None`, see [`LspInputGenerator::with_seed_similarity`].
+    #[must_use]
+    pub fn with_seed_similarity(self, threshold: Option<f64>) -> Self {
+        Self {
+            seed_similarity: threshold,
+            ..self
+        }
+    }
+
+    /// Evaluates `batch_size` freshly generated inputs every `interval` executions,
+    /// see [`RegenerationStage`].
+    #[must_use]
+    pub fn with_regeneration(self, interval: u64, batch_size: usize) -> Self {
+        Self {
+            regeneration: Some((interval, batch_size)),
+            ..self
+        }
+    }
+
+    /// Switches to structure-agnostic mutations for `duration` when the coverage has not grown
+    /// for `threshold`, see [`StagnationFallbackStage`].
+    #[must_use]
+    pub fn with_stagnation_fallback(self, threshold: Duration, duration: Duration) -> Self {
+        Self {
+            stagnation_fallback: Some((threshold, duration)),
+            ..self
+        }
+    }
+
+    /// Uses a coverage map of `size` instead of the size dumped by the target.
+    #[must_use]
+    pub fn with_coverage_map_size(self, size: usize) -> Self {
+        Self {
+            coverage_map_size: Some(size),
+            ..self
+        }
+    }
+
+    /// Materializes the workspaces of the inputs in `temp_dir`, in directories named after
+    /// `instance_id` so that parallel campaigns can share it.
+    #[must_use]
+    pub fn with_workspace_dir(self, temp_dir: impl Into<PathBuf>, instance_id: String) -> Self {
+        Self {
+            temp_dir: temp_dir.into(),
+            instance_id,
+            ..self
+        }
+    }
+
+    /// Removes the workspace directories left behind by failed executions after `max_age`.
+    #[must_use]
+    pub fn with_workspace_max_age(self, workspace_max_age: Duration) -> Self {
+        Self {
+            workspace_max_age,
+            ..self
+        }
+    }
+
+    /// Throttles the campaign while the disk usage exceeds `limits`, see [`DiskWatchdogStage`].
+    #[must_use]
+    pub fn with_disk_limits(self, disk_limits: DiskLimits) -> Self {
+        Self {
+            disk_limits,
+            ..self
+        }
+    }
+
+    /// Generates `seeds` inputs when the campaign starts.
+    #[must_use]
+    pub fn with_seeds(self, seeds: usize) -> Self {
+        Self { seeds, ..self }
+    }
+
+    #[must_use]
+    pub fn with_power_schedule(self, power_schedule: BaseSchedule, cycle: bool) -> Self {
+        Self {
+            power_schedule,
+            cycle_power_schedule: cycle,
+            ..self
+        }
+    }
+
+    /// Only keeps crashes with new AddressSanitizer stack traces as solutions.
+    #[must_use]
+    pub fn with_asan(self, asan: bool) -> Self {
+        Self { asan, ..self }
+    }
+
+    /// Also keeps the inputs crashing processes spawned by the target as solutions.
+    /// Requires AddressSanitizer.
+    #[must_use]
+    pub fn with_subprocess_tracking(self, track_subprocesses: bool) -> Self {
+        Self {
+            track_subprocesses,
+            ..self
+        }
+    }
+
+    /// Also keeps the inputs on which the target breaks the protocol as solutions.
+    #[must_use]
+    pub fn with_conformance_checks(self, conformance_checks: bool) -> Self {
+        Self {
+            conformance_checks,
+            ..self
+        }
+    }
+
+    /// Also keeps the inputs after which the target leaves malformed index artifacts,
+    /// e.g., LSIF dumps, in the workspace as solutions.
+    #[must_use]
+    pub fn with_index_validation(self, index_validation: bool) -> Self {
+        Self {
+            index_validation,
+            ..self
+        }
+    }
+
+    /// Checks the positions of the messages after each mutation of a document and records
+    /// divergences to [`CALIBRATION_DIVERGENCES_FILE`], see [`CalibrationCheck`].
+    /// Always enabled in debug builds.
+    #[must_use]
+    pub fn with_calibration_check(self, calibration_check: bool) -> Self {
+        Self {
+            calibration_check: calibration_check || cfg!(debug_assertions),
+            ..self
+        }
+    }
+
+    /// Whether to extract tokens from the target for the mutators.
+    #[must_use]
+    pub fn with_auto_tokens(self, auto_tokens: bool) -> Self {
+        Self {
+            auto_tokens,
+            ..self
+        }
+    }
+
+    /// Shows the output of the target and of the fork server.
+    #[must_use]
+    pub fn with_debug_output(self, debug_child: bool, debug_afl: bool) -> Self {
+        Self {
+            debug_output: (debug_child, debug_afl),
+            ..self
+        }
+    }
+
+    /// Parses the output of the target outside the LSP payloads with `parsers`,
+    /// see [`LspOutputObserver::with_side_channels`].
+    #[must_use]
+    pub fn with_side_channels(self, side_channels: Vec<Box<dyn SideChannelParser>>) -> Self {
+        Self {
+            side_channels,
+            ..self
+        }
+    }
+
+    /// Logs the full notifications of the server with one of `methods` and the lines of
+    /// the side channels among `side_channels`, see [`NotificationLogFeedback`].
+    #[must_use]
+    pub fn with_logged_notifications(
+        self,
+        methods: Vec<String>,
+        side_channels: Vec<String>,
+    ) -> Self {
+        Self {
+            logged_methods: methods,
+            logged_side_channels: side_channels,
+            ..self
+        }
+    }
+
+    /// Runs `companion` against the same workspace as the target in each execution.
+    #[must_use]
+    pub fn with_companion(self, companion: Companion) -> Self {
+        Self {
+            companion: Some(companion),
+            ..self
+        }
+    }
+
+    /// Writes each new corpus entry with the mutations that produced it to `path`,
+    /// see [`AttributionLog`].
+    #[must_use]
+    pub fn with_attribution_log(self, path: PathBuf) -> Self {
+        Self {
+            attribution_log: Some(path),
+            ..self
+        }
+    }
+
+    /// Writes the events of the campaign to `path` as JSON lines, with a stats sample every
+    /// `sample_interval`, see [`EventStreamStage`].
+    #[must_use]
+    pub fn with_event_stream(self, path: PathBuf, sample_interval: Duration) -> Self {
+        Self {
+            event_stream: Some((path, sample_interval)),
+            ..self
+        }
+    }
+
+    /// Records the campaign to the SQLite database at `path`, with a stats sample every
+    /// `sample_interval`, see [`CampaignRecorderStage`].
+    #[must_use]
+    pub fn with_database(self, path: PathBuf, sample_interval: Duration) -> Self {
+        Self {
+            database: Some((path, sample_interval)),
+            ..self
+        }
+    }
+
+    /// Notifies the webhook at `url` of the solutions of the campaign named `campaign`
+    /// and of its end, see [`Webhook`].
+    #[must_use]
+    pub fn with_webhook(self, url: String, campaign: String) -> Self {
+        Self {
+            webhook: Some((url, campaign)),
+            ..self
+        }
+    }
+
+    /// Stops the campaign after the stage in progress when `receiver` receives a message.
+    #[must_use]
+    pub fn with_stop_signal(self, receiver: Receiver<()>) -> Self {
+        Self {
+            stop_signal: Some(receiver),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_time_budget(self, time_budget: Duration) -> Self {
+        Self {
+            time_budget: Some(time_budget),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_max_executions(self, max_executions: u64) -> Self {
+        Self {
+            max_executions: Some(max_executions),
+            ..self
+        }
+    }
+
+    /// Seeds the random number generator, which is seeded from the clock otherwise.
+    #[must_use]
+    pub fn with_random_seed(self, random_seed: u64) -> Self {
+        Self {
+            random_seed: Some(random_seed),
+            ..self
+        }
+    }
+
+    /// Mutates the text documents with the mutations of `registry` besides the built-in ones,
+    /// see [`text_document_mutator`].
+    #[must_use]
+    pub fn with_text_document_mutations(
+        self,
+        registry: MutationRegistry<'g, LspInput, CampaignState>,
+    ) -> Self {
+        Self {
+            text_document_mutations: registry,
+            ..self
+        }
+    }
+
+    /// Runs the campaign until it stops.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the campaign cannot be set up or the fuzz loop fails.
+    pub fn run(self) -> Result<CampaignReport, libafl::Error> {
+        self.run_with(|state, _| CampaignReport::from_state(state))
+    }
+
+    /// Runs the campaign until it stops, and then passes its state and the name of its
+    /// coverage feedback to `finish`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the campaign cannot be set up or the fuzz loop fails.
+    #[allow(
+        clippy::too_many_lines,
+        reason = "Need to put in one method for type inference"
+    )]
+    pub fn run_with<R>(
+        self,
+        finish: impl FnOnce(&CampaignState, &str) -> R,
+    ) -> Result<R, libafl::Error> {
+        let Self {
+            mut target,
+            grammar_lookup,
+            state_dir,
+            transport,
+            generators_config,
+            language_weights,
+            workspace_templates,
+            seed_similarity,
+            regeneration,
+            stagnation_fallback,
+            coverage_map_size,
+            temp_dir,
+            instance_id,
+            workspace_max_age,
+            disk_limits,
+            seeds,
+            power_schedule,
+            cycle_power_schedule,
+            asan,
+            track_subprocesses,
+            conformance_checks,
+            index_validation,
+            calibration_check,
+            auto_tokens,
+            debug_output: (debug_child, debug_afl),
+            side_channels,
+            logged_methods,
+            logged_side_channels,
+            companion,
+            attribution_log,
+            event_stream,
+            database,
+            webhook,
+            stop_signal,
+            time_budget,
+            max_executions,
+            random_seed,
+            text_document_mutations,
+        } = self;
+        fs::create_dir_all(&state_dir).afl_context("Creating state directory")?;
+        let corpus_dir = state_dir.join(CORPUS_DIR);
+        let solution_dir = state_dir.join(SOLUTION_DIR);
+
+        let mut shmem_provider =
+            StdShMemProvider::new().afl_context("Creating shared memory provider")?;
+        let mut fuzz_input = |name: &str| -> Result<_, libafl::Error> {
+            match transport {
+                Transport::SharedMemory => Ok(FuzzInput::SharedMemory(
+                    shmem_provider
+                        .new_shmem(INPUT_SHM_SIZE)
+                        .afl_context("Creating shared memory for test case passing")?,
+                )),
+                Transport::Stdin => {
+                    let input_file = temp_dir.join(format!(".lsp-fuzz-{name}-{instance_id}"));
+                    Ok(FuzzInput::Stdin(
+                        InputFile::create(input_file).afl_context("Creating input file")?,
+                    ))
+                }
+            }
+        };
+        let target_input = fuzz_input("input")?;
+        let companion_input = companion
+            .as_ref()
+            .map(|_| fuzz_input("companion-input"))
+            .transpose()?;
+        let map_size = match coverage_map_size {
+            Some(map_size) => map_size,
+            None => fuzz_target::dump_map_size(&target.path).afl_context("Dumping map size")?,
+        };
+        info!("Coverage map size: {}", map_size);
+        let companion_map_size = companion
+            .as_ref()
+            .map(|it| fuzz_target::dump_map_size(&it.target.path))
+            .transpose()
+            .afl_context("Dumping map size of the companion server")?;
+
+        let mut coverage_shmem = shmem_provider
+            .new_shmem(map_size + companion_map_size.unwrap_or_default())
+            .afl_context("Creating shared memory")?;
+        let coverage_map_shmem_id = coverage_shmem.id();
+        let companion = match (companion, companion_input, companion_map_size) {
+            (Some(companion), Some(fuzz_input), Some(map_size)) => {
+                info!(
+                    executable = %companion.target.path.display(),
+                    map_size,
+                    documents = ?companion.documents,
+                    "Running a companion server"
+                );
+                let coverage_shmem_of_companion = shmem_provider
+                    .new_shmem(map_size)
+                    .afl_context("Creating shared memory")?;
+                Some(CompanionConfig {
+                    target_info: companion.target,
+                    fuzz_input,
+                    coverage_shmem: coverage_shmem_of_companion,
+                    documents: companion.documents,
+                })
+            }
+            _ => None,
+        };
+        let coverage_map_observer = {
+            let shmem_buf = coverage_shmem.as_slice_mut();
+            // SAFETY: We never move the piece of the shared memory.
+            unsafe { StdMapObserver::new("edges", shmem_buf) }
+        };
+
+        let responses_observer = LspOutputObserver::new().with_side_channels(side_channels);
+        let asan_observer = AsanBacktraceObserver::new("asan_stacktrace");
+        let subprocess_observer = SubprocessObserver::new();
+        let cov_observer = HitcountsMapObserver::new(coverage_map_observer).track_indices();
+        let time_observer = TimeObserver::new("time");
+        let workspace_observer = WorkspaceObserver::new(temp_dir.clone(), instance_id.clone())
+            .with_change_tracking(index_validation);
+
+        let map_feedback = MaxMapFeedback::new(&cov_observer);
+        let coverage_feedback_name = map_feedback.name().clone();
+        let calibration_stage = CalibrationStage::new(&map_feedback);
+        let stats_stage = {
+            let stats_file =
+                File::create(state_dir.join(STATS_FILE)).afl_context("Creating stats file")?;
+            StatsStage::new(BufWriter::new(stats_file), &map_feedback)
+        };
+        let density_stage = MapDensityStage::new(&map_feedback);
+        let notification_stage = NotificationStatsStage::new(Self::NOTIFICATION_STATS_INTERVAL);
+        let stagnation = {
+            let (threshold, duration) =
+                stagnation_fallback.unwrap_or((Duration::MAX, Self::DEFAULT_FALLBACK_DURATION));
+            CoverageStagnation::new(&map_feedback, threshold, duration)
+        };
+        let (event_stream, event_sample_interval) = match event_stream {
+            Some((path, sample_interval)) => (
+                Some(EventStream::open(&path).afl_context("Opening event stream")?),
+                sample_interval,
+            ),
+            None => (None, Duration::MAX),
+        };
+        let event_stage =
+            EventStreamStage::new(event_stream.clone(), &map_feedback, event_sample_interval);
+        let attribution_log = attribution_log
+            .map(|path| AttributionLog::create(&path, &map_feedback))
+            .transpose()
+            .afl_context("Creating attribution log")?;
+        let webhook = || {
+            webhook
+                .clone()
+                .map(|(url, campaign)| Webhook::new(url, campaign))
+        };
+        let webhook_stage = WebhookStage::new(webhook());
+        let stop_webhook = webhook();
+        let recorder_stage = match database {
+            Some((path, sample_interval)) => CampaignRecorderStage::new(
+                Some(
+                    CampaignDatabase::open(&path, instance_id.clone())
+                        .afl_context("Opening SQLite database")?,
+                ),
+                &map_feedback,
+                sample_interval,
+            ),
+            None => CampaignRecorderStage::new(None, &map_feedback, Duration::MAX),
+        };
+
+        let mut feedback = feedback_or!(
+            map_feedback,
+            ConfigurationCoverageFeedback::new(&cov_observer),
+            LspResponseFeedback::new(&responses_observer),
+            ParserRecoveryFeedback::new(&responses_observer),
+            ResponseDictionaryFeedback::new(&responses_observer),
+            LearnedFragmentsFeedback::new(&responses_observer),
+            SupportedMethodsFeedback::new(
+                &responses_observer,
+                Some(state_dir.join(SERVER_CAPABILITIES_FILE))
+            ),
+            MintedIdsFeedback::new(&responses_observer),
+            NotificationLogFeedback::new(&responses_observer)
+                .with_logged_methods(logged_methods)
+                .with_logged_side_channels(logged_side_channels),
+            RuleCoverageFeedback::new(),
+            NodeKindFrequencyFeedback::new(),
+            GrammarCoverageFeedback::new(grammar_lookup),
+            TestCaseFileNameFeedback::<CORPUS>::new(),
+            TimeFeedback::new(&time_observer)
+        );
+        let mut objective = objective(
+            asan,
+            &asan_observer,
+            conformance_checks,
+            &responses_observer,
+            index_validation,
+            &workspace_observer,
+            &subprocess_observer,
+            solution_dir.clone(),
+        );
+
+        let (corpus, solutions) = create_corpus(&corpus_dir, &solution_dir)?;
+        let rand = StdRand::with_seed(random_seed.unwrap_or_else(libafl_bolts::current_nanos));
+        let mut state: CampaignState =
+            StdState::new(rand, corpus, solutions, &mut feedback, &mut objective)
+                .afl_context("Creating state")?;
+        let mut tokens = auto_tokens.then(UTF8Tokens::new);
+
+        let scheduler = scheduler(
+            &mut state,
+            &cov_observer,
+            power_schedule,
+            cycle_power_schedule,
+        );
+        let mut fuzzer = StdFuzzerBuilder::new()
+            .input_filter(NopInputFilter)
+            .target_bytes_converter(LspInputBytesConverter::new(
+                temp_dir.clone(),
+                instance_id.clone(),
+            ))
+            .scheduler(scheduler)
+            .feedback(feedback)
+            .objective(objective)
+            .build();
+
+        let input_generator = || {
+            LspInputGenerator::new(grammar_lookup)
+                .with_language_weights(language_weights.clone())
+                .with_workspace_templates(workspace_templates.clone())
+                .with_minimal_capabilities(generators_config.minimal_capabilities)
+                .with_configuration_matrix(generators_config.configuration_matrix.clone())
+                .with_rule_weighting(generators_config.rule_weighting.clone())
+                .with_identifier_resolution(generators_config.resolve_identifiers)
+        };
+        let mut stages = {
+            let mutation_stage = {
+                let text_document_mutator = text_document_mutator(
+                    grammar_lookup,
+                    &generators_config,
+                    text_document_mutations,
+                )
+                .afl_context("Registering text document mutations")?;
+                let mut text_document_mutator =
+                    CalibrationCheck::new(text_document_mutator, calibration_check);
+                if calibration_check {
+                    text_document_mutator = text_document_mutator
+                        .with_divergence_log(&state_dir.join(CALIBRATION_DIVERGENCES_FILE))
+                        .afl_context("Opening calibration divergence log")?;
+                }
+                // Redrawing the capability profile cannot change an input pinned to one profile.
+                let minimal_capabilities = generators_config.minimal_capabilities;
+                let message_weights: &[(&str, usize)] =
+                    if minimal_capabilities > 0.0 && minimal_capabilities < 1.0 {
+                        &[]
+                    } else {
+                        &[("RedrawCapabilityProfile", 0)]
+                    };
+                // Stacked like the havoc mutator of libafl, but recording the applied
+                // mutations for the attribution log.
+                let messages_mutator = WeightedHavocMutator::new(
+                    message_mutations(&generators_config),
+                    Self::MESSAGE_MUTATION_STACK_POW,
+                    message_weights,
+                    &HashMap::new(),
+                )
+                .afl_context("Creating message mutator")?;
+                let mutator = AttributeMutations::new(
+                    TrackSequenceLength::new(LspInputMutator::new(
+                        text_document_mutator,
+                        messages_mutator,
+                    )),
+                    attribution_log,
+                );
+                let fallback_mutator = HavocScheduledMutator::with_max_stack_pow(
+                    stagnation_mutations(),
+                    Self::FALLBACK_MUTATION_STACK_POW,
+                );
+                StagnationFallbackStage::new(
+                    StdPowerMutationalStage::new(mutator),
+                    StdPowerMutationalStage::new(fallback_mutator),
+                    stagnation,
+                )
+            };
+            let (regeneration_interval, regeneration_batch) = regeneration.unwrap_or((u64::MAX, 0));
+            let regeneration_stage = RegenerationStage::new(
+                input_generator(),
+                regeneration_interval,
+                regeneration_batch,
+            );
+            let cleanup_stage = CleanupWorkspaceDirs::new(
+                temp_dir.to_string_lossy().into_owned(),
+                instance_id.clone(),
+                Self::WORKSPACE_CLEANUP_INTERVAL,
+                workspace_max_age,
+            );
+            let disk_watchdog = DiskWatchdogStage::new(
+                state_dir.clone(),
+                temp_dir.clone(),
+                &instance_id,
+                disk_limits,
+                webhook(),
+            );
+            // Without a signal, the sender is dropped at once and nothing is ever received.
+            let stop_signal = stop_signal.unwrap_or_else(|| mpsc::channel().1);
+            tuple_list![
+                calibration_stage,
+                mutation_stage,
+                regeneration_stage,
+                stats_stage,
+                density_stage,
+                notification_stage,
+                cleanup_stage,
+                disk_watchdog,
+                recorder_stage,
+                event_stage,
+                webhook_stage,
+                TimeoutStopStage::new(time_budget.unwrap_or(Duration::MAX)),
+                ExecutionsStopStage::new(max_executions.unwrap_or(u64::MAX)),
+                StopOnReceived::new(stop_signal),
+            ]
+        };
+
+        let asan_observer = asan.then_some(asan_observer);
+        if asan_observer.is_some() {
+            info!("Crash stack hashing will be enabled");
+        }
+        let mut executor = {
+            // Workspaces are materialized under the temporary directory.
+            if let Some(container) = target.container.as_mut() {
+                container.mounts.push(temp_dir.clone());
+            }
+            let mut companion = companion;
+            if let Some(container) = companion
+                .as_mut()
+                .and_then(|it| it.target_info.container.as_mut())
+            {
+                container.mounts.push(temp_dir.clone());
+            }
+            let exec_config = FuzzExecutionConfig {
+                debug_child,
+                debug_afl,
+                fuzz_input: target_input,
+                auto_tokens: tokens.as_mut(),
+                // The coverage of the companion, if any, follows the map of the target.
+                coverage_shm_info: (coverage_map_shmem_id, map_size),
+                map_observer: cov_observer,
+                responses_observer,
+                asan_observer,
+                subprocess_observer: track_subprocesses.then_some(subprocess_observer),
+                lifecycle_observer: None,
+                other_observers: tuple_list![workspace_observer, time_observer],
+                companion,
+            };
+            LspExecutor::start(target, exec_config).afl_context("Starting executor")?
+        };
+        if let Some(tokens) = tokens {
+            info!("Extracted {} UTF-8 token(s) from the target.", tokens.len());
+            state.add_metadata(tokens);
+        }
+
+        let mut event_manager = SimpleEventManager::new(SimpleMonitor::new(|it| info!("{}", it)));
+        if !state.has_metadata::<SupportedMethods>() {
+            // The inputs draw capability profiles, experimental capabilities and settings,
+            // which may change the capabilities the server declares.
+            let mut probe = input_generator()
+                .generate(&mut state)
+                .afl_context("Generating capability probe")?;
+            probe.messages.make_initialize_plain();
+            fuzzer
+                .evaluate_input(&mut state, &mut executor, &mut event_manager, &probe)
+                .afl_context("Probing server capabilities")?;
+        }
+        if state.must_load_initial_inputs() {
+            info!("Generating seeds");
+            emit_phase(event_stream.as_ref(), CampaignPhase::SeedGeneration, &state);
+            let mut generator = input_generator().with_seed_similarity(seed_similarity);
+            state
+                .generate_initial_inputs_forced(
+                    &mut fuzzer,
+                    &mut executor,
+                    &mut generator,
+                    &mut event_manager,
+                    seeds,
+                )
+                .afl_context("Generating initial input")?;
+            info!(seeds = %state.corpus().count(), "Seed generation completed");
+        }
+
+        emit_phase(event_stream.as_ref(), CampaignPhase::Fuzzing, &state);
+        let fuzz_result =
+            fuzzer.fuzz_loop(&mut stages, &mut executor, &mut state, &mut event_manager);
+
+        let reason = match &fuzz_result {
+            Ok(()) | Err(libafl::Error::ShuttingDown) => "stop requested".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        let stop_event = CampaignEvent::CampaignStopped {
+            reason,
+            solutions: state.solutions().count(),
+            executions: *state.executions(),
+            elapsed_secs: elapsed_secs(&state),
+        };
+        if let Some(stream) = event_stream {
+            stream.emit(&stop_event);
+        }
+        if let Some(webhook) = stop_webhook {
+            webhook.notify(&stop_event);
+        }
+
+        match fuzz_result {
+            Ok(()) => unreachable!("The fuzz loop will never exit with Ok"),
+            Err(libafl::Error::ShuttingDown) => Ok(finish(&state, &coverage_feedback_name)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn elapsed_secs(state: &impl HasStartTime) -> u64 {
+    libafl_bolts::current_time()
+        .checked_sub(*state.start_time())
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn emit_phase(stream: Option<&EventStream>, phase: CampaignPhase, state: &impl HasStartTime) {
+    if let Some(stream) = stream {
+        stream.emit(&CampaignEvent::PhaseStarted {
+            phase,
+            elapsed_secs: elapsed_secs(state),
+        });
+    }
+}
+
+/// The corpus scheduler of campaigns, favoring small and fast inputs covering each edge.
+pub fn scheduler<State, I, C, O>(
+    state: &mut State,
+    cov_observer: &C,
+    power_schedule: BaseSchedule,
+    cycle_power_schedule: bool,
+) -> impl Scheduler<I, State> + use<State, I, C, O>
+where
+    C: Named + CanTrack + AsRef<O>,
+    I: HasLen,
+    State: HasMetadata + HasCorpus<I> + HasRand + HasTestcase<I>,
+    O: Hash,
+{
+    let power_schedule = PowerSchedule::new(power_schedule);
+    let mut weighted_scheduler =
+        StdWeightedScheduler::with_schedule(state, cov_observer, Some(power_schedule));
+    if cycle_power_schedule {
+        weighted_scheduler = weighted_scheduler.cycling_scheduler();
+    }
+    IndexesLenTimeMinimizerScheduler::new(cov_observer, weighted_scheduler)
+}
+
+/// The objective of campaigns.
+///
+/// Crashes are solutions if they have a new AddressSanitizer stack trace, or always if
+/// `asan_enabled` is false. Protocol violations are solutions if `conformance_checks` is set,
+/// and malformed index artifacts if `index_validation` is set.
+#[allow(
+    clippy::too_many_arguments,
+    reason = "Each objective is switched separately"
+)]
+pub fn objective<EM, Observers, State>(
+    asan_enabled: bool,
+    asan_observer: &AsanBacktraceObserver,
+    conformance_checks: bool,
+    responses_observer: &LspOutputObserver,
+    index_validation: bool,
+    workspace_observer: &WorkspaceObserver,
+    subprocess_observer: &SubprocessObserver,
+    solution_dir: PathBuf,
+) -> impl Feedback<EM, LspInput, Observers, State> + use<EM, Observers, State>
+where
+    Observers: MatchName,
+    State: HasMetadata + HasNamedMetadata + HasSolutions<LspInput> + HasExecutions + HasStartTime,
+{
+    feedback_or!(
+        TestCaseFileNameFeedback::<SOLUTION>::new(),
+        feedback_and_fast!(
+            CrashFeedback::new(),
+            feedback_or_fast!(
+                ConstFeedback::new(!asan_enabled),
+                NewHashFeedback::new(asan_observer),
+            )
+        ),
+        feedback_and_fast!(
+            ConstFeedback::new(conformance_checks),
+            feedback_or!(
+                ConformanceFeedback::new(responses_observer),
+                MalformedFramingFeedback::new(responses_observer)
+            )
+        ),
+        feedback_and_fast!(
+            ConstFeedback::new(index_validation),
+            IndexArtifactFeedback::new(workspace_observer)
+        ),
+        SubprocessCrashFeedback::new(subprocess_observer),
+        ShutdownHangFeedback::new(),
+        ResponseTranscriptFeedback::new(responses_observer, solution_dir)
+    )
+}
+
+/// Opens the corpus in `corpus_path` and the solutions in `solution_path`.
+///
+/// # Errors
+///
+/// Returns an error if either directory cannot be created.
+pub fn create_corpus<I>(
+    corpus_path: &Path,
+    solution_path: &Path,
+) -> Result<(CachedOnDiskCorpus<I>, OnDiskCorpus<I>), libafl::Error>
+where
+    I: Input,
+{
+    const CACHE_SIZE: usize = 4096;
+    let corpus =
+        CachedOnDiskCorpus::with_meta_format_and_prefix(corpus_path, CACHE_SIZE, None, None, false)
+            .afl_context("Creating corpus")?;
+    let solutions = OnDiskCorpus::with_meta_format_and_prefix(solution_path, None, None, false)
+        .afl_context("Creating solution corpus")?;
+    Ok((corpus, solutions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_document::{
+        generation::{DerivationFragments, GrammarContext},
+        grammar::Grammar,
+    };
+
+    #[test]
+    fn campaign_is_wired_up_to_the_executor() {
+        let grammar =
+            Grammar::from_tree_sitter_grammar_json(Language::C, Language::C.grammar_json())
+                .unwrap();
+        let fragments = DerivationFragments::new(Vec::new(), HashMap::new());
+        let grammar_lookup =
+            GrammarContextLookup::from_iter([GrammarContext::new(grammar, fragments)]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state_dir = temp_dir.path().join("state");
+        let target = FuzzTargetInfo {
+            path: temp_dir.path().join("no-such-server"),
+            args: Vec::new(),
+            persistent_fuzzing: false,
+            defer_fork_server: false,
+            crash_exit_code: None,
+            timeout: Duration::from_millis(100).into(),
+            phase_timeouts: None,
+            kill_signal: Signal::SIGKILL,
+            env: HashMap::new(),
+            container: None,
+        };
+
+        let result = CampaignBuilder::new(target, &grammar_lookup, &state_dir)
+            .with_transport(Transport::Stdin)
+            .with_coverage_map_size(1 << 16)
+            .with_workspace_dir(temp_dir.path(), "campaign-test".to_owned())
+            .with_attribution_log(state_dir.join("attribution.jsonl"))
+            .with_event_stream(state_dir.join("events.jsonl"), Duration::from_secs(1))
+            .with_logged_notifications(vec!["window/logMessage".to_owned()], Vec::new())
+            .with_seeds(1)
+            .with_max_executions(1)
+            .run();
+
+        // Everything up to the executor is set up before the missing target is started.
+        let err = result.expect_err("The target does not exist");
+        assert!(err.to_string().contains("Starting executor"), "{err}");
+        assert!(state_dir.join(CORPUS_DIR).is_dir());
+        assert!(state_dir.join(STATS_FILE).is_file());
+        assert!(state_dir.join("events.jsonl").is_file());
+    }
+}
@@ -0,0 +1,172 @@
+//! Running the fork server target inside a container.
+//!
+//! Container runtimes do not pass arbitrary file descriptors to the containerized process,
+//! so the fork server control and status pipes are relayed through FIFOs in a directory
+//! shared with the container. A shell inside the container opens the FIFOs as the descriptors
+//! the AFL++ runtime expects and then executes the target.
+
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    fs::OpenOptions,
+    io,
+    os::fd::OwnedFd,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use nix::sys::stat::Mode;
+use os_pipe::{PipeReader, PipeWriter};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+/// The container runtime used to run the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    #[must_use]
+    pub const fn executable(self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+        }
+    }
+}
+
+/// Configuration of the container running the target.
+///
+/// The target executable and its arguments are resolved inside the container.
+/// `/tmp` (which holds the workspaces and sanitizer logs) and `/dev/shm` are always bind-mounted
+/// at the same paths, and the container shares the IPC and PID namespaces with the host so that
+/// shared memory and child process IDs reported by the fork server stay valid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerConfig {
+    pub runtime: ContainerRuntime,
+    pub image: String,
+    /// Additional host paths to bind-mount at the same path in the container.
+    pub mounts: Vec<PathBuf>,
+    /// Additional arguments passed to the `run` command of the runtime.
+    pub extra_args: Vec<String>,
+}
+
+impl ContainerConfig {
+    /// Wraps `target` in a `run` command of the runtime, with the standard streams of `target`
+    /// attached to the container, e.g., to talk to the target over its standard input and output.
+    ///
+    /// The environment variables and the working directory of `target` are forwarded into the
+    /// container.
+    #[must_use]
+    pub fn containerize(&self, target: &Command) -> Command {
+        let mut command = self.run_command(target, &[]);
+        command.arg(target.get_program()).args(target.get_args());
+        command
+    }
+
+    /// A `run` command of the runtime for `target`, up to the image, with `mounts` bind-mounted
+    /// in addition to those of the configuration.
+    fn run_command(&self, target: &Command, mounts: &[&Path]) -> Command {
+        let mounts: BTreeSet<&Path> = [Path::new("/tmp"), Path::new("/dev/shm")]
+            .into_iter()
+            .chain(mounts.iter().copied())
+            .chain(target.get_current_dir())
+            .chain(self.mounts.iter().map(PathBuf::as_path))
+            .collect();
+
+        let mut command = Command::new(self.runtime.executable());
+        command.args(["run", "--rm", "--interactive", "--ipc=host", "--pid=host"]);
+        for mount in mounts {
+            let mut volume = OsString::from(mount);
+            volume.push(":");
+            volume.push(mount);
+            command.arg("--volume").arg(volume);
+        }
+        if let Some(dir) = target.get_current_dir() {
+            command.arg("--workdir").arg(dir);
+        }
+        for (key, value) in target.get_envs() {
+            command.arg("--env").arg(key);
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        command.args(&self.extra_args).arg(&self.image);
+        command
+    }
+}
+
+/// Opens the relayed FIFOs as descriptors 198 (control) and 199 (status) and runs the target.
+const RELAY_SCRIPT: &str = r#"exec 198<"$1" 199>"$2" && shift 2 && exec "$@""#;
+
+/// FIFOs standing in for the fork server pipes.
+#[derive(Debug)]
+pub struct PipeRelay {
+    dir: TempDir,
+}
+
+impl PipeRelay {
+    const CONTROL: &str = "control";
+    const STATUS: &str = "status";
+
+    /// Creates the FIFOs in a new temporary directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or the FIFOs cannot be created.
+    pub fn new() -> io::Result<Self> {
+        let dir = TempDir::with_prefix("lsp-fuzz-relay_")?;
+        for fifo in [Self::CONTROL, Self::STATUS] {
+            nix::unistd::mkfifo(&dir.path().join(fifo), Mode::S_IRUSR | Mode::S_IWUSR)?;
+        }
+        Ok(Self { dir })
+    }
+
+    fn control_path(&self) -> PathBuf {
+        self.dir.path().join(Self::CONTROL)
+    }
+
+    fn status_path(&self) -> PathBuf {
+        self.dir.path().join(Self::STATUS)
+    }
+
+    /// Opens the host ends of the FIFOs, returning the status reader and the control writer.
+    ///
+    /// The FIFOs are opened for both reading and writing, so opening does not block until the
+    /// container opens the other ends.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the FIFOs cannot be opened.
+    pub fn open(&self) -> io::Result<(PipeReader, PipeWriter)> {
+        let open = |path: PathBuf| -> io::Result<OwnedFd> {
+            let file = OpenOptions::new().read(true).write(true).open(path)?;
+            Ok(file.into())
+        };
+        let status = PipeReader::from(open(self.status_path())?);
+        let control = PipeWriter::from(open(self.control_path())?);
+        Ok((status, control))
+    }
+
+    /// Wraps `target` in a `run` command of the container runtime, relaying the fork server
+    /// pipes.
+    ///
+    /// The environment variables and the working directory of `target` are forwarded into the
+    /// container.
+    #[must_use]
+    pub fn containerize(&self, config: &ContainerConfig, target: &Command) -> Command {
+        let mut command = config.run_command(target, &[self.dir.path()]);
+        command
+            .args(["sh", "-c", RELAY_SCRIPT, "lsp-fuzz-relay"])
+            .arg(self.control_path())
+            .arg(self.status_path())
+            .arg(target.get_program())
+            .args(target.get_args());
+        command
+    }
+}
@@ -33,7 +33,10 @@ use nix::{
 use os_pipe::{PipeReader, PipeWriter};
 use tracing::{debug, info, warn};
 
-use super::FuzzInput;
+use super::{
+    FuzzInput,
+    container::{ContainerConfig, PipeRelay},
+};
 use crate::utils::AflContext;
 
 /// Represents the different ways to set up fuzzing input for the target.
@@ -128,6 +131,8 @@ mod fd {
 pub struct NeoForkServer {
     /// The persistent fork server process we initially spawn
     fork_server_child: Child,
+    /// Whether the fork server process has been reaped, after which its PID may be reused
+    fork_server_reaped: bool,
     /// Status pipe reader - receives messages from the fork server
     rx: PipeReader,
     /// Control pipe writer - sends commands to the fork server
@@ -138,6 +143,8 @@ pub struct NeoForkServer {
     last_run_timed_out: bool,
     /// The signal to use when killing child processes that time out
     kill_signal: Signal,
+    /// The FIFOs relaying the fork server pipes when the target runs in a container
+    _pipe_relay: Option<PipeRelay>,
 }
 
 impl Drop for NeoForkServer {
@@ -159,7 +166,10 @@ impl Drop for NeoForkServer {
             }
         }
 
-        // Then, clean up the fork server process
+        // Then, clean up the fork server process, unless it died and was reaped already
+        if self.fork_server_reaped {
+            return;
+        }
         if let Err(err) = self.fork_server_child.kill() {
             warn!(%err, "Failed to kill fork server process");
         } else if let Err(err) = self.fork_server_child.wait() {
@@ -196,6 +206,8 @@ pub struct NeoForkServerOptions<'a> {
     pub kill_signal: Signal,
     /// File descriptor used to capture the target's stdout stream.
     pub stdout_capture_fd: BorrowedFd<'a>,
+    /// The container to run the target in, if any
+    pub container: Option<ContainerConfig>,
 }
 
 impl NeoForkServer {
@@ -223,6 +235,7 @@ impl NeoForkServer {
             debug_output,
             kill_signal,
             stdout_capture_fd,
+            container,
         } = options;
 
         // Create bidirectional pipes for communication with the fork server
@@ -236,10 +249,7 @@ impl NeoForkServer {
 
         // Create and configure the command
         let mut command = process::Command::new(target);
-        command
-            .args(args)
-            .stdin(Stdio::null()) // Will be overridden by input_setup if necessary
-            .stderr(stderr);
+        command.args(args);
 
         command.env("__AFL_SHM_ID", shm_id.to_string());
         command.env("__AFL_SHM_ID_SIZE", map_size.to_string());
@@ -267,6 +277,20 @@ impl NeoForkServer {
         forkserver::ConfigTarget::setlimit(&mut command, memlimit);
         forkserver::ConfigTarget::set_coredump(&mut command, afl_debug);
         command.env("LD_BIND_NOW", "1").envs(envs);
+        input_setup.setup_child_cmd(&mut command);
+
+        let pipe_relay = container
+            .map(|config| {
+                let relay = PipeRelay::new().afl_context("Failed to create pipe relay")?;
+                command = relay.containerize(&config, &command);
+                info!(image = %config.image, "Running the target in a container");
+                Ok::<_, libafl::Error>(relay)
+            })
+            .transpose()?;
+
+        command
+            .stdin(Stdio::null()) // Will be overridden by input_setup if necessary
+            .stderr(stderr);
         // Use the standard libtary one when stabilized.
         ConfigTarget::setsid(&mut command);
 
@@ -326,16 +350,29 @@ impl NeoForkServer {
             libafl::Error::illegal_state(format!("Could not spawn the fork server: {err:#?}"))
         })?;
 
+        let (rx, tx) = match &pipe_relay {
+            Some(relay) => relay.open().afl_context("Failed to open pipe relay")?,
+            None => (rx, tx),
+        };
+
         Ok(Self {
             fork_server_child,
+            fork_server_reaped: false,
             rx,
             tx,
             child_pid: None,
             last_run_timed_out: false,
             kill_signal,
+            _pipe_relay: pipe_relay,
         })
     }
 
+    /// The process ID of the fork server.
+    #[must_use]
+    pub fn pid(&self) -> Pid {
+        Pid::from_raw(self.fork_server_child.id().cast_signed())
+    }
+
     /// Perform initial handshake with the fork server.
     ///
     /// This exchanges protocol version information and validates compatibility.
@@ -451,10 +488,27 @@ impl NeoForkServer {
     ///
     /// Returns an error if fork server communication fails or a timed-out child cannot be killed.
     pub fn run_child(&mut self, timeout: &TimeSpec) -> Result<(Pid, Option<i32>), libafl::Error> {
-        while nix::sys::wait::waitpid(None, Some(WaitPidFlag::WNOHANG))
-            .afl_context("Waiting for child processes")?
-            != WaitStatus::StillAlive
-        {}
+        let pid = self.start_child()?;
+        let status = self.wait_child(pid, timeout)?;
+        Ok((pid, status))
+    }
+
+    /// Request a new child process from the fork server without waiting for it.
+    ///
+    /// The child must be waited for with [`Self::wait_child`] before the next one is started.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if fork server communication fails.
+    pub fn start_child(&mut self) -> Result<Pid, libafl::Error> {
+        loop {
+            match nix::sys::wait::waitpid(None, Some(WaitPidFlag::WNOHANG))
+                .afl_context("Waiting for child processes")?
+            {
+                WaitStatus::StillAlive => break,
+                status => self.fork_server_reaped |= status.pid() == Some(self.pid()),
+            }
+        }
 
         // Notify fork server if the previous run timed out
         let notification = u32::from(self.last_run_timed_out);
@@ -474,7 +528,21 @@ impl NeoForkServer {
 
         let pid = Pid::from_raw(child_pid);
         self.child_pid = Some(pid);
+        Ok(pid)
+    }
 
+    /// Wait for the child `pid` started with [`Self::start_child`], killing it on timeout.
+    ///
+    /// Returns the exit status of the child, or `None` if it timed out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if fork server communication fails or a timed-out child cannot be killed.
+    pub fn wait_child(
+        &mut self,
+        pid: Pid,
+        timeout: &TimeSpec,
+    ) -> Result<Option<i32>, libafl::Error> {
         // Wait for the child to complete, with timeout
         let status = self.read_st_timed(timeout)?;
         self.last_run_timed_out = status.is_none();
@@ -503,7 +571,7 @@ impl NeoForkServer {
             self.child_pid = None;
         }
 
-        Ok((pid, status))
+        Ok(status)
     }
 
     /// Read a 32-bit unsigned integer from the status pipe.
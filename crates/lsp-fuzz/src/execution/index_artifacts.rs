@@ -0,0 +1,388 @@
+//! Validating the index artifacts that servers write into the workspace.
+//!
+//! Some servers dump LSIF or persist their index caches next to the sources they index.
+//! The artifacts are read back by other tools or by later runs of the server,
+//! so malformed ones are findings even if the server itself does not crash.
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::workspace_observer::WorkspaceObserver;
+use crate::{lsp_input::LspInput, utils::AflContext};
+
+/// The formats of index artifacts, told apart by their file extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ArtifactFormat {
+    /// An LSIF dump, either as JSON lines or as a JSON array of elements.
+    Lsif,
+    /// A JSON document, e.g., the index caches of TypeScript and Deno.
+    Json,
+    /// JSON lines.
+    JsonLines,
+}
+
+impl ArtifactFormat {
+    /// The format of the artifact at `path`, or `None` if it is not validated.
+    #[must_use]
+    pub fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "lsif" => Some(Self::Lsif),
+            "json" => Some(Self::Json),
+            "jsonl" | "ndjson" => Some(Self::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// The way an index artifact is malformed.
+///
+/// Lines are 1-based, or the 1-based index of the element for LSIF dumps in a JSON array.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum ArtifactDefect {
+    #[error("Invalid JSON at line {line}: {message}")]
+    InvalidJson { line: usize, message: String },
+    #[error("The LSIF dump does not start with a `metaData` vertex")]
+    MissingMetaData,
+    #[error("Malformed LSIF element at line {line}: {reason}")]
+    MalformedElement {
+        line: usize,
+        reason: Cow<'static, str>,
+    },
+    #[error("Duplicate LSIF element id at line {line}")]
+    DuplicateId { line: usize },
+    #[error("LSIF edge at line {line} references an element that was not emitted before")]
+    DanglingEdge { line: usize },
+}
+
+impl ArtifactDefect {
+    /// The kind of the defect, regardless of where it is.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidJson { .. } => "InvalidJson",
+            Self::MissingMetaData => "MissingMetaData",
+            Self::MalformedElement { .. } => "MalformedElement",
+            Self::DuplicateId { .. } => "DuplicateId",
+            Self::DanglingEdge { .. } => "DanglingEdge",
+        }
+    }
+}
+
+/// Checks `content` as an artifact of `format`, returning its first defect.
+#[must_use]
+pub fn validate_artifact(format: ArtifactFormat, content: &[u8]) -> Option<ArtifactDefect> {
+    match format {
+        ArtifactFormat::Json => {
+            serde_json::from_slice::<Value>(content)
+                .err()
+                .map(|err| ArtifactDefect::InvalidJson {
+                    line: err.line(),
+                    message: err.to_string(),
+                })
+        }
+        ArtifactFormat::JsonLines => parse_json_lines(content).err(),
+        ArtifactFormat::Lsif => {
+            let elements = if content.trim_ascii_start().starts_with(b"[") {
+                serde_json::from_slice::<Vec<Value>>(content)
+                    .map(|it| (1..).zip(it).collect())
+                    .map_err(|err| ArtifactDefect::InvalidJson {
+                        line: err.line(),
+                        message: err.to_string(),
+                    })
+            } else {
+                parse_json_lines(content)
+            };
+            let elements = match elements {
+                Ok(elements) => elements,
+                Err(defect) => return Some(defect),
+            };
+            validate_lsif(&elements).err()
+        }
+    }
+}
+
+fn parse_json_lines(content: &[u8]) -> Result<Vec<(usize, Value)>, ArtifactDefect> {
+    content
+        .split(|&it| it == b'\n')
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim_ascii().is_empty())
+        .map(|(line_number, line)| {
+            serde_json::from_slice(line)
+                .map(|it| (line_number, it))
+                .map_err(|err| ArtifactDefect::InvalidJson {
+                    line: line_number,
+                    message: err.to_string(),
+                })
+        })
+        .collect()
+}
+
+/// Checks the structure of an LSIF dump.
+///
+/// Every element must have an id, a type, and a label; the first one must be the `metaData`
+/// vertex; and edges may only reference elements emitted before them.
+fn validate_lsif(elements: &[(usize, Value)]) -> Result<(), ArtifactDefect> {
+    let is_meta_data = |it: &Value| it.get("label").and_then(Value::as_str) == Some("metaData");
+    if !elements.first().is_some_and(|(_, it)| is_meta_data(it)) {
+        return Err(ArtifactDefect::MissingMetaData);
+    }
+    let mut emitted = HashSet::new();
+    for (line, element) in elements {
+        let line = *line;
+        let malformed = |reason| ArtifactDefect::MalformedElement {
+            line,
+            reason: Cow::Borrowed(reason),
+        };
+        let id = element
+            .get("id")
+            .and_then(element_id)
+            .ok_or_else(|| malformed("missing or invalid `id`"))?;
+        if element.get("label").and_then(Value::as_str).is_none() {
+            return Err(malformed("missing or invalid `label`"));
+        }
+        match element.get("type").and_then(Value::as_str) {
+            Some("vertex") => {}
+            Some("edge") => {
+                let out_v = element
+                    .get("outV")
+                    .and_then(element_id)
+                    .ok_or_else(|| malformed("edge without `outV`"))?;
+                let in_vs = match (element.get("inV"), element.get("inVs")) {
+                    (Some(in_v), None) => vec![in_v],
+                    (None, Some(Value::Array(in_vs))) if !in_vs.is_empty() => {
+                        in_vs.iter().collect()
+                    }
+                    _ => return Err(malformed("edge without either `inV` or `inVs`")),
+                };
+                let mut in_vs = in_vs.into_iter().map(element_id);
+                if !emitted.contains(&out_v)
+                    || !in_vs.all(|it| it.is_some_and(|it| emitted.contains(&it)))
+                {
+                    return Err(ArtifactDefect::DanglingEdge { line });
+                }
+            }
+            _ => return Err(malformed("`type` is neither `vertex` nor `edge`")),
+        }
+        if !emitted.insert(id) {
+            return Err(ArtifactDefect::DuplicateId { line });
+        }
+    }
+    Ok(())
+}
+
+/// The id of an LSIF element, which is either a number or a string.
+fn element_id(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(it) => Some(it.to_string()),
+        Value::String(it) => Some(it.clone()),
+        _ => None,
+    }
+}
+
+/// An index artifact that failed validation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MalformedArtifact {
+    /// The path of the artifact relative to the workspace root.
+    pub path: PathBuf,
+    pub format: ArtifactFormat,
+    pub defect: ArtifactDefect,
+}
+
+impl MalformedArtifact {
+    fn signature(&self) -> (ArtifactFormat, Cow<'static, str>) {
+        (self.format, Cow::Borrowed(self.defect.kind()))
+    }
+}
+
+/// The malformed artifacts attached to the solution that wrote them.
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct IndexArtifactFinding {
+    pub artifacts: Vec<MalformedArtifact>,
+}
+
+/// Defects that have already been reported, keyed by artifact format and kind of defect.
+#[derive(Debug, Default, Serialize, Deserialize, SerdeAny)]
+pub struct ReportedArtifactDefects {
+    seen: HashSet<(ArtifactFormat, Cow<'static, str>)>,
+}
+
+/// An objective flagging inputs after which the target leaves malformed index artifacts
+/// in the workspace.
+///
+/// The artifacts are the files the [`WorkspaceObserver`] sees created or modified,
+/// so the observer must track changes. Only executions that exit normally are checked,
+/// as the target may be killed halfway through writing an artifact,
+/// and each kind of defect is reported once per artifact format.
+#[derive(Debug)]
+pub struct IndexArtifactFeedback {
+    observer_handle: Handle<WorkspaceObserver>,
+    malformed: Vec<MalformedArtifact>,
+}
+
+impl IndexArtifactFeedback {
+    #[must_use]
+    pub fn new(observer: &WorkspaceObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            malformed: Vec::new(),
+        }
+    }
+}
+
+impl Named for IndexArtifactFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("IndexArtifactFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for IndexArtifactFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        state.add_metadata(ReportedArtifactDefects::default());
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for IndexArtifactFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _input: &LspInput,
+        observers: &Observers,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        self.malformed.clear();
+        if *exit_kind != ExitKind::Ok {
+            return Ok(false);
+        }
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("WorkspaceObserver not attached")?;
+        self.malformed = observer
+            .changed_files()
+            .iter()
+            .filter_map(|file| {
+                let format = ArtifactFormat::of(&file.path)?;
+                let defect = validate_artifact(format, &file.content)?;
+                Some(MalformedArtifact {
+                    path: file.path.clone(),
+                    format,
+                    defect,
+                })
+            })
+            .collect();
+        let reported: &ReportedArtifactDefects = state
+            .metadata()
+            .afl_context("ReportedArtifactDefects not initialized")?;
+        Ok(self
+            .malformed
+            .iter()
+            .any(|it| !reported.seen.contains(&it.signature())))
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _observers: &Observers,
+        testcase: &mut Testcase<LspInput>,
+    ) -> Result<(), libafl::Error> {
+        if self.malformed.is_empty() {
+            return Ok(());
+        }
+        let reported: &mut ReportedArtifactDefects = state
+            .metadata_mut()
+            .afl_context("ReportedArtifactDefects not initialized")?;
+        reported
+            .seen
+            .extend(self.malformed.iter().map(MalformedArtifact::signature));
+        testcase.add_metadata(IndexArtifactFinding {
+            artifacts: std::mem::take(&mut self.malformed),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lsif(lines: &[&str]) -> Option<ArtifactDefect> {
+        validate_artifact(ArtifactFormat::Lsif, lines.join("\n").as_bytes())
+    }
+
+    const META_DATA: &str = r#"{"id":1,"type":"vertex","label":"metaData","version":"0.6.0"}"#;
+    const DOCUMENT: &str = r#"{"id":2,"type":"vertex","label":"document","uri":"file:///a"}"#;
+    const RANGE: &str = r#"{"id":3,"type":"vertex","label":"range"}"#;
+    const CONTAINS: &str = r#"{"id":4,"type":"edge","label":"contains","outV":2,"inVs":[3]}"#;
+
+    #[test]
+    fn well_formed_lsif() {
+        assert_eq!(lsif(&[META_DATA, DOCUMENT, RANGE, CONTAINS]), None);
+        let array = format!("[{META_DATA},{DOCUMENT},{RANGE},{CONTAINS}]");
+        assert_eq!(
+            validate_artifact(ArtifactFormat::Lsif, array.as_bytes()),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_lsif() {
+        assert_eq!(
+            lsif(&[DOCUMENT, META_DATA]),
+            Some(ArtifactDefect::MissingMetaData)
+        );
+        assert_eq!(
+            lsif(&[META_DATA, DOCUMENT, CONTAINS, RANGE]),
+            Some(ArtifactDefect::DanglingEdge { line: 3 })
+        );
+        assert_eq!(
+            lsif(&[META_DATA, DOCUMENT, DOCUMENT]),
+            Some(ArtifactDefect::DuplicateId { line: 3 })
+        );
+        assert!(matches!(
+            lsif(&[META_DATA, r#"{"id":2,"type":"vertex","label":"doc"#]),
+            Some(ArtifactDefect::InvalidJson { line: 2, .. })
+        ));
+        assert!(matches!(
+            lsif(&[META_DATA, r#"{"id":2,"type":"node","label":"document"}"#]),
+            Some(ArtifactDefect::MalformedElement { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn artifact_formats() {
+        assert_eq!(
+            ArtifactFormat::of(Path::new("dump.lsif")),
+            Some(ArtifactFormat::Lsif)
+        );
+        assert_eq!(ArtifactFormat::of(Path::new("src/main.rs")), None);
+        assert!(validate_artifact(ArtifactFormat::Json, b"{\"a\": [1, 2]}").is_none());
+        assert!(validate_artifact(ArtifactFormat::Json, b"{\"a\": [1, 2}").is_some());
+    }
+}
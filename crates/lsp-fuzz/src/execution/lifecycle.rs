@@ -0,0 +1,90 @@
+//! Events in the lifecycle of the processes of the target.
+//!
+//! The executor hands the events to its [`LifecycleObserver`] as they happen, i.e.,
+//! [`LifecycleEvent::ChildSpawned`] while the child is still running. Stages and feedbacks read
+//! the events of the last execution from the observer, while the subscribers of
+//! [`LifecycleObserver::subscribe`] receive them on another thread, e.g., to sample `/proc/<pid>`
+//! or attach a profiler to the child.
+
+use std::{borrow::Cow, sync::mpsc};
+
+use libafl::observers::Observer;
+use libafl_bolts::Named;
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+
+/// An event in the lifecycle of the processes of the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// The fork server forked a child to run an input. The child runs the companion server
+    /// rather than the target if `companion` is set.
+    ChildSpawned { pid: Pid, companion: bool },
+    /// A child finished with the wait status `status`, or was killed after timing out if `None`.
+    ChildReaped {
+        pid: Pid,
+        companion: bool,
+        status: Option<i32>,
+    },
+    /// The fork server died and a new one was started, with the process ID `pid`.
+    ForkServerRestarted { pid: Pid },
+}
+
+/// The processes spawned by the executor in `events`, i.e., those whose sanitizer logs are
+/// not written by subprocesses.
+pub(super) fn spawned_pids(events: &[LifecycleEvent]) -> impl Iterator<Item = Pid> + '_ {
+    events.iter().filter_map(|event| match *event {
+        LifecycleEvent::ChildSpawned { pid, .. } => Some(pid),
+        _ => None,
+    })
+}
+
+/// Records the [`LifecycleEvent`]s of an execution.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LifecycleObserver {
+    #[serde(skip)]
+    events: Vec<LifecycleEvent>,
+    #[serde(skip)]
+    subscribers: Vec<mpsc::Sender<LifecycleEvent>>,
+}
+
+impl Named for LifecycleObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("LifecycleObserver");
+        &NAME
+    }
+}
+
+impl LifecycleObserver {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The events of the last execution, in the order they happened.
+    #[must_use]
+    pub fn events(&self) -> &[LifecycleEvent] {
+        &self.events
+    }
+
+    /// Subscribes to the events, delivered as they happen.
+    ///
+    /// The subscription ends when the receiver is dropped.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<LifecycleEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    pub(super) fn record(&mut self, event: LifecycleEvent) {
+        self.events.push(event);
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event).is_ok());
+    }
+}
+
+impl<I, State> Observer<I, State> for LifecycleObserver {
+    fn pre_exec(&mut self, _state: &mut State, _input: &I) -> Result<(), libafl::Error> {
+        self.events.clear();
+        Ok(())
+    }
+}
@@ -1,14 +1,16 @@
 use std::{
     collections::HashMap,
+    ffi::OsString,
     fs,
-    io::{self, BufReader, Seek, Write},
+    io::{self, Read, Seek, Write},
     marker::PhantomData,
     mem,
     os::fd::AsFd,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use fork_server::{FuzzInputSetup, NeoForkServer, NeoForkServerOptions};
+use container::ContainerConfig;
+use fork_server::{ForkServerTargetInfo, FuzzInputSetup, NeoForkServer, NeoForkServerOptions};
 use libafl::{
     HasMetadata, HasTargetBytesConverter,
     executors::{Executor, ExitKind, HasObservers},
@@ -22,24 +24,41 @@ use libafl_bolts::{
     shmem::{ShMem, ShMemId},
     tuples::{MatchName, RefIndexable, type_eq},
 };
+use lifecycle::{LifecycleEvent, LifecycleObserver};
 use nix::{
     sys::{signal::Signal, time::TimeSpec},
     unistd::Pid,
 };
+use phases::{ExecutionPhase, PhaseTimeoutStats, PhaseTimeouts};
+use polyglot::{CompanionConfig, CompanionServer};
 use responses::LspOutputObserver;
 use serde::{Deserialize, Serialize};
-use tempfile::NamedTempFile;
-use tracing::info;
+use subprocess::SubprocessObserver;
+use tempfile::{NamedTempFile, TempDir};
+use tracing::{info, warn};
 
 use crate::{utf8::UTF8Tokens, utils::AflContext};
 
+pub mod container;
 pub mod fork_server;
+pub mod index_artifacts;
+pub mod lifecycle;
+pub mod pacing;
+pub mod phases;
+pub mod polyglot;
 pub mod responses;
 pub mod sanitizers;
+pub mod side_channel;
+pub mod subprocess;
 mod test;
 pub mod workspace_observer;
 
-const ASAN_LOG_PATH: &str = "/tmp/asan";
+/// The directory under which each executor creates its own directory for sanitizer logs.
+///
+/// It is always mounted into containers, see [`ContainerConfig`].
+const ASAN_LOG_ROOT: &str = "/tmp";
+/// The file name prefix of sanitizer logs, followed by the process ID.
+const ASAN_LOG_PREFIX: &str = "asan";
 
 /// Describes how the fuzz input is sent to the target.
 #[derive(Debug)]
@@ -99,8 +118,11 @@ pub struct FuzzTargetInfo {
     pub defer_fork_server: bool,
     pub crash_exit_code: Option<i8>,
     pub timeout: TimeSpec,
+    /// Budgets replacing `timeout` with one computed for each input.
+    pub phase_timeouts: Option<PhaseTimeouts>,
     pub kill_signal: Signal,
     pub env: HashMap<String, String>,
+    pub container: Option<ContainerConfig>,
 }
 
 #[derive(Debug)]
@@ -113,17 +135,34 @@ pub struct FuzzExecutionConfig<'a, SHM, MO, OBS> {
     pub map_observer: MO,
     pub responses_observer: LspOutputObserver,
     pub asan_observer: Option<AsanBacktraceObserver>,
+    /// Tracks crashes of processes spawned by the target. Requires `asan_observer`.
+    pub subprocess_observer: Option<SubprocessObserver>,
+    /// Records the [`LifecycleEvent`]s of the processes of the target.
+    pub lifecycle_observer: Option<LifecycleObserver>,
     pub other_observers: OBS,
+    /// A second server run against the same workspace, see [`polyglot`].
+    pub companion: Option<CompanionConfig<SHM>>,
 }
 
 #[derive(Debug)]
 pub struct LspExecutor<State, MO, OBS, I, SHM> {
     fork_server: NeoForkServer,
+    /// How to start the fork server again if it dies.
+    launch: ForkServerLaunch,
+    /// The lifecycle events of the current execution, kept even without a lifecycle observer
+    /// to tell the sanitizer logs of the spawned processes from those of their subprocesses.
+    lifecycle_events: Vec<LifecycleEvent>,
     crash_exit_code: Option<i8>,
     timeout: TimeSpec,
+    phase_timeouts: Option<PhaseTimeouts>,
     fuzz_input: FuzzInput<SHM>,
     output_capture_file: NamedTempFile,
+    /// Holds the sanitizer logs of this executor only, and is removed when the executor is dropped.
+    asan_log_dir: TempDir,
     observers: Observers<MO, OBS>,
+    companion: Option<CompanionServer<SHM>>,
+    /// Writes the coverage of the companion at an offset of the map observer.
+    merge_companion_coverage: fn(&mut MO, usize, &[u8]),
     _state: PhantomData<(State, I)>,
 }
 
@@ -145,6 +184,7 @@ where
     where
         MO: AsRef<A> + AsMut<A>,
         A: Truncate + HasLen + MapObserver,
+        A::Entry: From<u8>,
     {
         let args = target_info.args.into_iter().map(Into::into).collect();
 
@@ -164,11 +204,28 @@ where
             "malloc_context_size=0",
         ];
 
+        if config.subprocess_observer.is_some() && config.asan_observer.is_none() {
+            Err(libafl::Error::illegal_argument(
+                "Tracking subprocesses requires the ASAN observer",
+            ))?;
+        }
+
+        let asan_log_dir = tempfile::Builder::new()
+            .prefix("lsp-fuzz-asan-")
+            .tempdir_in(ASAN_LOG_ROOT)
+            .afl_context("Creating ASAN log directory")?;
+        let mut asan_options = asan_options.join(":");
         if config.asan_observer.is_some() {
-            asan_options.push(const_str::concat!("log_path=", ASAN_LOG_PATH));
+            let log_path = asan_log_dir.path().join(ASAN_LOG_PREFIX);
+            asan_options.push_str(":log_path=");
+            asan_options.push_str(
+                log_path
+                    .to_str()
+                    .afl_context("The ASAN log path is not valid UTF-8")?,
+            );
         }
 
-        let mut envs = vec![("ASAN_OPTIONS".into(), asan_options.join(":").into())];
+        let mut envs = vec![("ASAN_OPTIONS".into(), asan_options.clone().into())];
 
         envs.extend(
             target_info
@@ -180,31 +237,42 @@ where
         let output_capture_file =
             NamedTempFile::new().afl_context("Creating output capture file")?;
 
-        let opts = NeoForkServerOptions {
-            target: target_info.path.as_os_str().to_owned(),
+        let launch = ForkServerLaunch {
+            target: target_info.path.into_os_string(),
             args,
             envs,
-            input_setup: FuzzInputSetup::from(&config.fuzz_input),
-            memlimit: 0,
             persistent_fuzzing: target_info.persistent_fuzzing,
             deferred: target_info.defer_fork_server,
             coverage_map_info: config.coverage_shm_info,
             afl_debug: config.debug_afl,
             debug_output: config.debug_child,
             kill_signal: target_info.kill_signal,
-            stdout_capture_fd: output_capture_file.as_fd(),
+            container: target_info.container,
         };
-        let mut fork_server = fork_server::NeoForkServer::new(opts)?;
-
-        let options = fork_server
-            .initialize()
-            .afl_context("Initializing fork server")?;
-
+        let (fork_server, options) = launch.launch(&config.fuzz_input, &output_capture_file)?;
+
+        // The coverage of the companion follows that of the target.
+        let companion_map_size = config
+            .companion
+            .as_ref()
+            .map_or(0, CompanionConfig::map_size);
+        if config.map_observer.as_ref().len() < companion_map_size {
+            Err(libafl::Error::illegal_argument(
+                "The coverage map has no room for the coverage of the companion server",
+            ))?;
+        }
+        let mut target_map_size = config
+            .map_observer
+            .as_ref()
+            .len()
+            .saturating_sub(companion_map_size);
         if let Some(fsrv_map_size) = options.map_size {
-            match config.map_observer.as_ref().len() {
+            match target_map_size {
                 map_size if map_size > fsrv_map_size => {
-                    config.map_observer.as_mut().truncate(fsrv_map_size);
-                    info!(new_size = fsrv_map_size, "Coverage map truncated");
+                    let new_size = fsrv_map_size + companion_map_size;
+                    config.map_observer.as_mut().truncate(new_size);
+                    target_map_size = fsrv_map_size;
+                    info!(new_size, "Coverage map truncated");
                 }
                 map_size if map_size < fsrv_map_size => {
                     Err(libafl::Error::illegal_argument(format!(
@@ -226,24 +294,83 @@ where
             auto_dict.parse_auto_dict(auto_dict_payload);
         }
 
+        let companion = config
+            .companion
+            .map(|companion| {
+                CompanionServer::start(
+                    companion,
+                    &asan_options,
+                    target_map_size,
+                    config.debug_child,
+                    config.debug_afl,
+                )
+            })
+            .transpose()
+            .afl_context("Starting companion server")?;
+
         let observers = Observers {
             map_observer: config.map_observer,
             responses_observer: config.responses_observer,
             asan_observer: config.asan_observer,
+            subprocess_observer: config.subprocess_observer,
+            lifecycle_observer: config.lifecycle_observer,
             extra: config.other_observers,
         };
 
         Ok(Self {
             fork_server,
+            launch,
+            lifecycle_events: Vec::new(),
             crash_exit_code: target_info.crash_exit_code,
             timeout: target_info.timeout,
+            phase_timeouts: target_info.phase_timeouts,
             fuzz_input: config.fuzz_input,
             output_capture_file,
+            asan_log_dir,
             observers,
+            companion,
+            merge_companion_coverage: |map_observer, offset, coverage| {
+                let map_observer: &mut A = map_observer.as_mut();
+                for (idx, &hits) in coverage.iter().enumerate().filter(|(_, hits)| **hits != 0) {
+                    map_observer.set(offset + idx, hits.into());
+                }
+            },
             _state: PhantomData,
         })
     }
 
+    fn emit_lifecycle(&mut self, event: LifecycleEvent) {
+        self.lifecycle_events.push(event);
+        if let Some(ref mut lifecycle_observer) = self.observers.lifecycle_observer {
+            lifecycle_observer.record(event);
+        }
+    }
+
+    /// Starts a child through the fork server, starting a new fork server if the current one
+    /// died, e.g., because it was killed by the OOM killer.
+    fn start_child(&mut self) -> Result<Pid, libafl::Error> {
+        let pid = match self.fork_server.start_child() {
+            Ok(pid) => pid,
+            Err(err) => {
+                warn!(%err, "The fork server is gone, restarting it");
+                let (fork_server, _) = self
+                    .launch
+                    .launch(&self.fuzz_input, &self.output_capture_file)
+                    .afl_context("Restarting fork server")?;
+                self.fork_server = fork_server;
+                self.emit_lifecycle(LifecycleEvent::ForkServerRestarted {
+                    pid: self.fork_server.pid(),
+                });
+                self.fork_server.start_child()?
+            }
+        };
+        self.emit_lifecycle(LifecycleEvent::ChildSpawned {
+            pid,
+            companion: false,
+        });
+        Ok(pid)
+    }
+
     fn clear_output_capture_file(&mut self) -> io::Result<()> {
         let output_capture_file = self.output_capture_file.as_file_mut();
         output_capture_file.rewind()?;
@@ -259,6 +386,8 @@ where
 pub struct Observers<MO, OBS> {
     map_observer: MO,
     asan_observer: Option<AsanBacktraceObserver>,
+    subprocess_observer: Option<SubprocessObserver>,
+    lifecycle_observer: Option<LifecycleObserver>,
     responses_observer: LspOutputObserver,
     extra: OBS,
 }
@@ -276,6 +405,16 @@ where
             && asan_observer.name() == name
         {
             Some(unsafe { &*std::ptr::from_ref(asan_observer).cast::<T>() })
+        } else if let Some(ref subprocess_observer) = self.subprocess_observer
+            && type_eq::<T, SubprocessObserver>()
+            && subprocess_observer.name() == name
+        {
+            Some(unsafe { &*std::ptr::from_ref(subprocess_observer).cast::<T>() })
+        } else if let Some(ref lifecycle_observer) = self.lifecycle_observer
+            && type_eq::<T, LifecycleObserver>()
+            && lifecycle_observer.name() == name
+        {
+            Some(unsafe { &*std::ptr::from_ref(lifecycle_observer).cast::<T>() })
         } else if type_eq::<T, LspOutputObserver>() && self.responses_observer.name() == name {
             Some(unsafe { &*(&raw const self.responses_observer).cast::<T>() })
         } else {
@@ -292,6 +431,16 @@ where
             && asan_observer.name() == name
         {
             Some(unsafe { &mut *std::ptr::from_mut(asan_observer).cast::<T>() })
+        } else if let Some(ref mut subprocess_observer) = self.subprocess_observer
+            && type_eq::<T, SubprocessObserver>()
+            && subprocess_observer.name() == name
+        {
+            Some(unsafe { &mut *std::ptr::from_mut(subprocess_observer).cast::<T>() })
+        } else if let Some(ref mut lifecycle_observer) = self.lifecycle_observer
+            && type_eq::<T, LifecycleObserver>()
+            && lifecycle_observer.name() == name
+        {
+            Some(unsafe { &mut *std::ptr::from_mut(lifecycle_observer).cast::<T>() })
         } else if type_eq::<T, LspOutputObserver>() && self.responses_observer.name() == name {
             Some(unsafe { &mut *(&raw mut self.responses_observer).cast::<T>() })
         } else {
@@ -312,6 +461,12 @@ where
         if let Some(ref mut asan_observer) = self.asan_observer {
             asan_observer.pre_exec(state, input)?;
         }
+        if let Some(ref mut subprocess_observer) = self.subprocess_observer {
+            subprocess_observer.pre_exec(state, input)?;
+        }
+        if let Some(ref mut lifecycle_observer) = self.lifecycle_observer {
+            lifecycle_observer.pre_exec(state, input)?;
+        }
         self.extra.pre_exec_all(state, input)?;
         Ok(())
     }
@@ -323,6 +478,12 @@ where
         exit_kind: &ExitKind,
     ) -> Result<(), libafl::Error> {
         self.extra.post_exec_all(state, input, exit_kind)?;
+        if let Some(ref mut lifecycle_observer) = self.lifecycle_observer {
+            lifecycle_observer.post_exec(state, input, exit_kind)?;
+        }
+        if let Some(ref mut subprocess_observer) = self.subprocess_observer {
+            subprocess_observer.post_exec(state, input, exit_kind)?;
+        }
         if let Some(ref mut asan_observer) = self.asan_observer {
             asan_observer.post_exec(state, input, exit_kind)?;
         }
@@ -337,6 +498,12 @@ where
         if let Some(ref mut asan_observer) = self.asan_observer {
             asan_observer.pre_exec_child(state, input)?;
         }
+        if let Some(ref mut subprocess_observer) = self.subprocess_observer {
+            subprocess_observer.pre_exec_child(state, input)?;
+        }
+        if let Some(ref mut lifecycle_observer) = self.lifecycle_observer {
+            lifecycle_observer.pre_exec_child(state, input)?;
+        }
         self.extra.pre_exec_child_all(state, input)?;
         Ok(())
     }
@@ -348,6 +515,12 @@ where
         exit_kind: &ExitKind,
     ) -> Result<(), libafl::Error> {
         self.extra.post_exec_child_all(state, input, exit_kind)?;
+        if let Some(ref mut lifecycle_observer) = self.lifecycle_observer {
+            lifecycle_observer.post_exec_child(state, input, exit_kind)?;
+        }
+        if let Some(ref mut subprocess_observer) = self.subprocess_observer {
+            subprocess_observer.post_exec_child(state, input, exit_kind)?;
+        }
         if let Some(ref mut asan_observer) = self.asan_observer {
             asan_observer.post_exec_child(state, input, exit_kind)?;
         }
@@ -393,53 +566,212 @@ where
         let bytes = fuzzer.target_bytes_converter_mut().to_target_bytes(input);
         let input_bytes = bytes;
         self.fuzz_input.send(&input_bytes)?;
+        let timeout = self
+            .phase_timeouts
+            .map_or(self.timeout, |it| it.timeout_for(&input_bytes).into());
 
         self.clear_output_capture_file()
             .afl_context("Clearing output capture file")?;
 
         self.observers.pre_exec_child_all(state, input)?;
-        let (child_pid, status) = self.fork_server.run_child(&self.timeout)?;
-
-        let exit_kind = if let Some(status) = status {
-            let exitcode_is_crash = self
-                .crash_exit_code
-                .filter(|_| libc::WIFEXITED(status))
-                .is_some_and(|it| libc::WEXITSTATUS(status) == i32::from(it));
-            if libc::WIFSIGNALED(status) || exitcode_is_crash {
-                ExitKind::Crash
-            } else {
-                ExitKind::Ok
-            }
-        } else {
-            ExitKind::Timeout
+        self.lifecycle_events.clear();
+        let child_pid = self.start_child()?;
+        let companion_pid = match self.companion.as_mut() {
+            Some(companion) => Some(companion.start_child(&input_bytes)?),
+            None => None,
         };
+        if let Some(pid) = companion_pid {
+            self.emit_lifecycle(LifecycleEvent::ChildSpawned {
+                pid,
+                companion: true,
+            });
+        }
+        let status = self.fork_server.wait_child(child_pid, &timeout)?;
+        self.emit_lifecycle(LifecycleEvent::ChildReaped {
+            pid: child_pid,
+            companion: false,
+            status,
+        });
+
+        let mut exit_kind = exit_kind_of(status, self.crash_exit_code);
+        let mut crashed_pid = child_pid;
+        if let (Some(companion), Some(companion_pid)) = (self.companion.as_mut(), companion_pid) {
+            let map_observer = &mut self.observers.map_observer;
+            let merge_coverage = self.merge_companion_coverage;
+            let (status, companion_exit_kind) =
+                companion.wait_child(companion_pid, |offset, coverage| {
+                    merge_coverage(map_observer, offset, coverage);
+                })?;
+            self.emit_lifecycle(LifecycleEvent::ChildReaped {
+                pid: companion_pid,
+                companion: true,
+                status,
+            });
+            // Only the crashes of the companion are reported, as its timeouts say nothing about
+            // the phase reached by the target.
+            if companion_exit_kind == ExitKind::Crash && exit_kind == ExitKind::Ok {
+                exit_kind = ExitKind::Crash;
+                crashed_pid = companion_pid;
+            }
+        }
         self.observers
             .post_exec_child_all(state, input, &exit_kind)?;
-        if exit_kind == ExitKind::Ok {
-            self.output_capture_file
-                .rewind()
-                .afl_context("Rewinding output capture file")?;
-            let output_reader = BufReader::new(&mut self.output_capture_file);
-            self.observers
-                .responses_observer
-                .capture_stdout_content(output_reader)
-                .afl_context("Capturing target output")?;
+        let output = capture_output(
+            &mut self.output_capture_file,
+            &mut self.observers.responses_observer,
+        )
+        .afl_context("Capturing target output")?;
+        if exit_kind == ExitKind::Timeout {
+            let phase = ExecutionPhase::reached(&input_bytes, &output);
+            state
+                .metadata_or_insert_with(PhaseTimeoutStats::default)
+                .record(phase);
         }
         if exit_kind == ExitKind::Crash
             && let Some(ref mut asan_observer) = self.observers.asan_observer
-            && let Some(ref asan_log_content) = read_asan_log(child_pid)?
+            && let Some(ref asan_log_content) =
+                read_asan_log(self.asan_log_dir.path(), crashed_pid)?
         {
             let log_content = String::from_utf8_lossy(asan_log_content);
             asan_observer.parse_asan_output(log_content.as_ref());
         }
+        if let Some(ref mut subprocess_observer) = self.observers.subprocess_observer {
+            // Logs of subprocesses outliving the target are picked up by a later execution.
+            let spawned: Vec<_> = lifecycle::spawned_pids(&self.lifecycle_events).collect();
+            for (pid, log) in subprocess::take_subprocess_logs(self.asan_log_dir.path(), &spawned)?
+            {
+                subprocess_observer.record_crash(pid);
+                if exit_kind == ExitKind::Ok {
+                    exit_kind = ExitKind::Crash;
+                    if let Some(ref mut asan_observer) = self.observers.asan_observer {
+                        asan_observer.parse_asan_output(String::from_utf8_lossy(&log).as_ref());
+                    }
+                }
+            }
+        }
 
         *state.executions_mut() += 1;
         Ok(exit_kind)
     }
 }
 
-fn read_asan_log(child_pid: Pid) -> Result<Option<Vec<u8>>, libafl::Error> {
-    let asan_log_file = format!("{ASAN_LOG_PATH}.{child_pid}");
+/// Reads the output the target wrote in an execution into `responses_observer`, returning it.
+///
+/// The output is captured whatever the exit kind, so that the transcripts of crashes and timeouts
+/// hold the messages sent before them. Feedbacks judging the output itself, e.g., for malformed
+/// framing, only consider executions that exited normally.
+fn capture_output<F: Read + Seek>(
+    output_capture_file: &mut F,
+    responses_observer: &mut LspOutputObserver,
+) -> io::Result<Vec<u8>> {
+    output_capture_file.rewind()?;
+    let mut output = Vec::new();
+    output_capture_file.read_to_end(&mut output)?;
+    responses_observer.capture_output(&output);
+    Ok(output)
+}
+
+/// The options of the fork server of the target that outlive its start.
+#[derive(Debug)]
+struct ForkServerLaunch {
+    target: OsString,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    persistent_fuzzing: bool,
+    deferred: bool,
+    coverage_map_info: (ShMemId, usize),
+    afl_debug: bool,
+    debug_output: bool,
+    kill_signal: Signal,
+    container: Option<ContainerConfig>,
+}
+
+impl ForkServerLaunch {
+    /// Starts and initializes a fork server reading inputs from `fuzz_input` and writing
+    /// to `output_capture_file`.
+    fn launch<SHM: ShMem>(
+        &self,
+        fuzz_input: &FuzzInput<SHM>,
+        output_capture_file: &NamedTempFile,
+    ) -> Result<(NeoForkServer, ForkServerTargetInfo), libafl::Error> {
+        let opts = NeoForkServerOptions {
+            target: self.target.clone(),
+            args: self.args.clone(),
+            envs: self.envs.clone(),
+            input_setup: FuzzInputSetup::from(fuzz_input),
+            memlimit: 0,
+            persistent_fuzzing: self.persistent_fuzzing,
+            deferred: self.deferred,
+            coverage_map_info: self.coverage_map_info,
+            afl_debug: self.afl_debug,
+            debug_output: self.debug_output,
+            kill_signal: self.kill_signal,
+            stdout_capture_fd: output_capture_file.as_fd(),
+            container: self.container.clone(),
+        };
+        let mut fork_server = NeoForkServer::new(opts)?;
+        let options = fork_server
+            .initialize()
+            .afl_context("Initializing fork server")?;
+        Ok((fork_server, options))
+    }
+}
+
+/// The exit kind of a child with `status`, or of a child that timed out if `None`.
+fn exit_kind_of(status: Option<i32>, crash_exit_code: Option<i8>) -> ExitKind {
+    let Some(status) = status else {
+        return ExitKind::Timeout;
+    };
+    let exitcode_is_crash = crash_exit_code
+        .filter(|_| libc::WIFEXITED(status))
+        .is_some_and(|it| libc::WEXITSTATUS(status) == i32::from(it));
+    if libc::WIFSIGNALED(status) || exitcode_is_crash {
+        ExitKind::Crash
+    } else {
+        ExitKind::Ok
+    }
+}
+
+/// Runs the fork server handshake with the target once and returns the coverage map size
+/// the target reports, if any.
+///
+/// # Errors
+///
+/// Returns an error if the fork server cannot be started or the handshake fails.
+pub fn probe_map_size<SHM: ShMem>(
+    target_info: &FuzzTargetInfo,
+    fuzz_input: &FuzzInput<SHM>,
+    coverage_shm_info: (ShMemId, usize),
+) -> Result<Option<usize>, libafl::Error> {
+    let output_capture_file = NamedTempFile::new().afl_context("Creating output capture file")?;
+    let opts = NeoForkServerOptions {
+        target: target_info.path.as_os_str().to_owned(),
+        args: target_info.args.iter().map(Into::into).collect(),
+        envs: target_info
+            .env
+            .iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect(),
+        input_setup: FuzzInputSetup::from(fuzz_input),
+        memlimit: 0,
+        persistent_fuzzing: target_info.persistent_fuzzing,
+        deferred: target_info.defer_fork_server,
+        coverage_map_info: coverage_shm_info,
+        afl_debug: false,
+        debug_output: false,
+        kill_signal: target_info.kill_signal,
+        stdout_capture_fd: output_capture_file.as_fd(),
+        container: target_info.container.clone(),
+    };
+    let mut fork_server = NeoForkServer::new(opts)?;
+    let options = fork_server
+        .initialize()
+        .afl_context("Initializing fork server")?;
+    Ok(options.map_size)
+}
+
+fn read_asan_log(log_dir: &Path, child_pid: Pid) -> Result<Option<Vec<u8>>, libafl::Error> {
+    let asan_log_file = log_dir.join(format!("{ASAN_LOG_PREFIX}.{child_pid}"));
     let log = if fs::exists(&asan_log_file)? {
         let asan_log = fs::read(&asan_log_file).afl_context("Reading ASAN log file")?;
         fs::remove_file(asan_log_file).afl_context("Fail to cleanup ASAN log file")?;
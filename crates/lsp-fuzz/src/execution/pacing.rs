@@ -0,0 +1,191 @@
+//! Pacing the messages written to targets replaying inputs, e.g., by the `reproduce` command.
+//!
+//! Campaigns are not paced: the fork server hands the whole session to the target at once,
+//! so there is no write between messages to delay.
+
+use std::{
+    borrow::Cow,
+    io::{self, ErrorKind, Write},
+    time::{Duration, Instant, TryFromFloatSecsError},
+};
+
+use libafl_bolts::rands::Rand;
+use serde::{Deserialize, Serialize};
+
+use crate::lsp::json_rpc::JsonRPCMessage;
+
+/// How fast messages are written to a target that reads them as a stream.
+///
+/// Many language servers debounce incoming requests,
+/// so flooding them coalesces most requests before they are ever processed.
+/// Pacing only applies to clients that stream messages one by one when replaying inputs,
+/// not to the fork server, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PacingConfig {
+    /// The sustained rate of messages, or `None` for no rate limit.
+    pub messages_per_second: Option<f64>,
+    /// The number of messages that can be sent back to back before the rate limit applies.
+    pub burst: u32,
+    /// The upper bound of the random delay added before each message.
+    pub jitter: Duration,
+    /// How long the target may be left without a message while it is waited on
+    /// before a keep-alive notification is sent, or `None` for no keep-alive.
+    ///
+    /// Some servers end sessions that are silent for too long,
+    /// which would be mistaken for the behavior of the server under test.
+    #[serde(default)]
+    pub keep_alive: Option<Duration>,
+}
+
+/// A token bucket refilled at a constant rate.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+    /// The wait for a token when the bucket is empty.
+    max_wait: Duration,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket refilled with `rate` tokens per second and holding at most `burst`
+    /// tokens (at least one).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the wait for a token at `rate` is not a valid [`Duration`],
+    /// e.g., if `rate` is negative, NaN, or so low that the wait overflows.
+    pub fn new(rate: f64, burst: u32, now: Instant) -> Result<Self, TryFromFloatSecsError> {
+        let capacity = f64::from(burst.max(1));
+        Ok(Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: now,
+            max_wait: Duration::try_from_secs_f64(rate.recip())?,
+        })
+    }
+
+    /// Takes one token from the bucket and returns how long to wait before it is available.
+    pub fn acquire(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = elapsed
+            .as_secs_f64()
+            .mul_add(self.rate, self.tokens)
+            .min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Duration::ZERO
+        } else {
+            // The bucket holds between zero and one token, so the wait is at most `max_wait`.
+            let wait = Duration::try_from_secs_f64((1.0 - self.tokens) / self.rate)
+                .map_or(self.max_wait, |it| it.min(self.max_wait));
+            self.tokens = 0.0;
+            self.last_refill = now + wait;
+            wait
+        }
+    }
+}
+
+/// Computes the delay before each message according to a [`PacingConfig`].
+#[derive(Debug)]
+pub struct Pacer<R> {
+    bucket: Option<TokenBucket>,
+    jitter: Duration,
+    keep_alive: Option<Duration>,
+    /// Whether the session is between the `initialized` notification and the `shutdown` request,
+    /// the only part of it where keep-alive notifications are allowed.
+    in_session: bool,
+    rand: R,
+}
+
+impl<R: Rand> Pacer<R> {
+    /// # Errors
+    ///
+    /// Returns an error if the rate of `config` is too low to wait for, see [`TokenBucket::new`].
+    pub fn new(config: PacingConfig, rand: R) -> Result<Self, TryFromFloatSecsError> {
+        let bucket = config
+            .messages_per_second
+            .filter(|&rate| rate > 0.0)
+            .map(|rate| TokenBucket::new(rate, config.burst, Instant::now()))
+            .transpose()?;
+        Ok(Self {
+            bucket,
+            jitter: config.jitter,
+            keep_alive: config.keep_alive.filter(|it| !it.is_zero()),
+            in_session: false,
+            rand,
+        })
+    }
+
+    /// Returns how long to wait before sending the next message.
+    pub fn next_delay(&mut self, now: Instant) -> Duration {
+        let rate_limit = self
+            .bucket
+            .as_mut()
+            .map_or(Duration::ZERO, |it| it.acquire(now));
+        let max_jitter = usize::try_from(self.jitter.as_micros()).unwrap_or(usize::MAX);
+        let jitter = self.rand.below_or_zero(max_jitter.saturating_add(1));
+        rate_limit + Duration::from_micros(u64::try_from(jitter).unwrap_or(u64::MAX))
+    }
+
+    /// Blocks the current thread until the next message may be sent.
+    pub fn pace(&mut self) {
+        let delay = self.next_delay(Instant::now());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Records that the message with `method` was sent to the target,
+    /// which tells whether keep-alive notifications may be sent.
+    pub fn record_sent(&mut self, method: &str) {
+        match method {
+            "initialized" => self.in_session = true,
+            "shutdown" | "exit" => self.in_session = false,
+            _ => {}
+        }
+    }
+
+    /// Blocks the current thread for `duration` while the target processes the messages,
+    /// writing keep-alive notifications to `target` if configured.
+    ///
+    /// Keep-alives are only sent between the `initialized` notification and
+    /// the `shutdown` request (see [`Self::record_sent`]), as the protocol allows no other
+    /// messages outside that part of the session.
+    /// They stop once the target closes its input.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O errors raised while writing keep-alives other than broken pipes.
+    pub fn idle(&self, duration: Duration, target: &mut impl Write) -> io::Result<()> {
+        let Some(interval) = self.keep_alive.filter(|_| self.in_session) else {
+            std::thread::sleep(duration);
+            return Ok(());
+        };
+        let keep_alive = JsonRPCMessage::notification(
+            Cow::Borrowed("$/setTrace"),
+            serde_json::json!({ "value": "off" }),
+        )
+        .to_lsp_payload();
+        let deadline = Instant::now() + duration;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining <= interval {
+                std::thread::sleep(remaining);
+                return Ok(());
+            }
+            std::thread::sleep(interval);
+            match target.write_all(&keep_alive).and_then(|()| target.flush()) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
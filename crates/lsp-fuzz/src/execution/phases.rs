@@ -0,0 +1,229 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    time::Duration,
+};
+
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{Named, SerdeAny};
+use serde::{Deserialize, Serialize};
+
+use crate::{lsp::json_rpc::JsonRPCMessage, lsp_input::LspInput};
+
+/// The time budgets of the phases of an execution.
+///
+/// The timeout of an execution is the sum of the budgets of its phases,
+/// so that slow server startups do not force a timeout large enough to hide hangs
+/// in request handling. The target reads the whole session as one stream,
+/// so the per-message budget is only an approximation of the time spent on each message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseTimeouts {
+    /// Starting the server and answering `initialize`.
+    pub init: Duration,
+    /// Each message of the session.
+    pub per_message: Duration,
+    /// Answering `shutdown` and exiting.
+    pub shutdown: Duration,
+}
+
+impl PhaseTimeouts {
+    /// The timeout of an execution of the LSP stream `session`.
+    #[must_use]
+    pub fn timeout_for(&self, session: &[u8]) -> Duration {
+        let messages = u32::try_from(count_frames(session)).unwrap_or(u32::MAX);
+        self.init + self.per_message.saturating_mul(messages) + self.shutdown
+    }
+}
+
+/// The phases of an execution, as far as they can be told apart by the responses of the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionPhase {
+    /// `initialize` was not answered.
+    Init,
+    /// `initialize` was answered, but a request before `shutdown` was not.
+    Requests,
+    /// Every request before `shutdown` was answered, but `shutdown` was not.
+    ShutdownRequest,
+    /// `shutdown` was answered, but the target did not exit.
+    Shutdown,
+}
+
+impl ExecutionPhase {
+    /// The phase in which an execution of `session` was stopped,
+    /// judging from the LSP stream `output` written by the target.
+    #[must_use]
+    pub fn reached(session: &[u8], output: &[u8]) -> Self {
+        let messages = JsonRPCMessage::scan_lsp_stream(session).messages;
+        // Servers may write other text to stdout between their payloads.
+        let responses = JsonRPCMessage::scan_lsp_stream(output).messages;
+        let answered = |request: Option<&JsonRPCMessage>| {
+            request.and_then(JsonRPCMessage::id).is_some_and(|id| {
+                responses
+                    .iter()
+                    .any(|it| it.method().is_none() && it.id() == Some(id))
+            })
+        };
+        let mut requests = messages
+            .iter()
+            .filter(|it| it.id().is_some() && it.method().is_some());
+        let initialize = requests.next();
+        let shutdown = requests
+            .next_back()
+            .filter(|it| it.method().is_some_and(|method| method == "shutdown"));
+        if !answered(initialize) {
+            Self::Init
+        } else if answered(shutdown) {
+            Self::Shutdown
+        } else if shutdown.is_some() && requests.all(|it| answered(Some(it))) {
+            Self::ShutdownRequest
+        } else {
+            Self::Requests
+        }
+    }
+
+    /// Whether the target was stopped while tearing down the session,
+    /// i.e., after every request other than `shutdown` was answered.
+    #[must_use]
+    pub const fn is_teardown(self) -> bool {
+        matches!(self, Self::ShutdownRequest | Self::Shutdown)
+    }
+}
+
+/// The number of timeouts in each phase of the executions.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, SerdeAny)]
+pub struct PhaseTimeoutStats {
+    pub init: u64,
+    pub requests: u64,
+    #[serde(default)]
+    pub shutdown_request: u64,
+    pub shutdown: u64,
+    /// The phase of the latest timeout.
+    #[serde(default)]
+    pub last: Option<ExecutionPhase>,
+}
+
+impl PhaseTimeoutStats {
+    pub const fn record(&mut self, phase: ExecutionPhase) {
+        let count = match phase {
+            ExecutionPhase::Init => &mut self.init,
+            ExecutionPhase::Requests => &mut self.requests,
+            ExecutionPhase::ShutdownRequest => &mut self.shutdown_request,
+            ExecutionPhase::Shutdown => &mut self.shutdown,
+        };
+        *count += 1;
+        self.last = Some(phase);
+    }
+}
+
+/// Marks solutions that timed out while the target was tearing down the session.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, SerdeAny)]
+pub struct ShutdownHang {
+    pub phase: ExecutionPhase,
+}
+
+/// The request histories of the shutdown hangs found so far.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct ShutdownHangHistories {
+    histories: HashSet<u64>,
+}
+
+/// Deems timeouts in the teardown of a session interesting, telling deadlocks in
+/// `shutdown` and `exit` handling apart from hangs of request handlers.
+///
+/// The request history of an input is the set of methods of its requests.
+/// Only the first shutdown hang of each history is a solution,
+/// which is marked with [`ShutdownHang`].
+#[derive(Debug, Default)]
+pub struct ShutdownHangFeedback {
+    phase: Option<ExecutionPhase>,
+}
+
+impl ShutdownHangFeedback {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Named for ShutdownHangFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ShutdownHangFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for ShutdownHangFeedback {}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for ShutdownHangFeedback
+where
+    State: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        input: &LspInput,
+        _observers: &Observers,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        self.phase = None;
+        if *exit_kind != ExitKind::Timeout {
+            return Ok(false);
+        }
+        let Some(phase) = state
+            .metadata_map()
+            .get::<PhaseTimeoutStats>()
+            .and_then(|it| it.last)
+            .filter(|it| it.is_teardown())
+        else {
+            return Ok(false);
+        };
+        let mut hasher = DefaultHasher::new();
+        input
+            .messages
+            .iter()
+            .filter(|it| it.is_request())
+            .map(|it| it.method())
+            .collect::<BTreeSet<_>>()
+            .hash(&mut hasher);
+        let novel = state
+            .metadata_or_insert_with(ShutdownHangHistories::default)
+            .histories
+            .insert(hasher.finish());
+        if novel {
+            self.phase = Some(phase);
+        }
+        Ok(novel)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        _observers: &Observers,
+        testcase: &mut Testcase<LspInput>,
+    ) -> Result<(), libafl::Error> {
+        if let Some(phase) = self.phase.take() {
+            testcase.add_metadata(ShutdownHang { phase });
+        }
+        Ok(())
+    }
+}
+
+/// The number of framed messages in the LSP stream `session`, including malformed ones.
+///
+/// Headers within the bodies of well-framed messages, e.g., in the text of a document,
+/// are not counted.
+fn count_frames(session: &[u8]) -> usize {
+    // The first part holds the bytes before the first header.
+    JsonRPCMessage::split_at_headers(session).len() - 1
+}
@@ -0,0 +1,273 @@
+//! Running a second server against the same workspace as the target.
+//!
+//! Real projects mix languages served by different servers (e.g., C sources next to
+//! `CMakeLists.txt` or TOML configuration), which watch and sometimes write the same files.
+//! A companion server, configured with [`CompanionConfig`], runs alongside the target in each
+//! execution: it receives the messages of the input except those about documents it does not
+//! serve, and its coverage is merged into the coverage map observed by the executor after the
+//! coverage of the target, so that the feedbacks treat the two servers as one.
+//! The target still receives all the messages.
+
+use std::{
+    fs::OpenOptions,
+    os::fd::AsFd,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use libafl::executors::ExitKind;
+use libafl_bolts::{AsSlice, AsSliceMut, shmem::ShMem};
+use nix::{sys::time::TimeSpec, unistd::Pid};
+
+use super::{
+    FuzzInput, FuzzTargetInfo, exit_kind_of,
+    fork_server::{FuzzInputSetup, NeoForkServer, NeoForkServerOptions},
+};
+use crate::{lsp::json_rpc::JsonRPCMessage, utils::AflContext};
+
+/// The configuration of a [`CompanionServer`].
+#[derive(Debug)]
+pub struct CompanionConfig<SHM> {
+    pub target_info: FuzzTargetInfo,
+    pub fuzz_input: FuzzInput<SHM>,
+    /// The coverage map of the companion, merged into the map observer of the executor after
+    /// each execution. The map observer must have room for it after the map of the target.
+    pub coverage_shmem: SHM,
+    /// The file extensions (e.g., `toml`) or file names (e.g., `CMakeLists.txt`) of the documents
+    /// sent to the companion.
+    pub documents: Vec<String>,
+}
+
+impl<SHM: ShMem> CompanionConfig<SHM> {
+    /// The size of the coverage map of the companion.
+    #[must_use]
+    pub fn map_size(&self) -> usize {
+        self.coverage_shmem.len()
+    }
+}
+
+/// A server run alongside the target by [`LspExecutor`](super::LspExecutor).
+#[derive(Debug)]
+pub(super) struct CompanionServer<SHM> {
+    fork_server: NeoForkServer,
+    crash_exit_code: Option<i8>,
+    timeout: Duration,
+    /// When the last child was started.
+    started: Instant,
+    fuzz_input: FuzzInput<SHM>,
+    coverage_shmem: SHM,
+    /// Where the coverage of the companion starts in the map observer of the executor.
+    map_offset: usize,
+    documents: Vec<String>,
+}
+
+impl<SHM: ShMem> CompanionServer<SHM> {
+    /// Starts the fork server of the companion, whose coverage is merged at `map_offset`.
+    ///
+    /// The output of the companion is discarded.
+    pub(super) fn start(
+        config: CompanionConfig<SHM>,
+        asan_options: &str,
+        map_offset: usize,
+        debug_child: bool,
+        debug_afl: bool,
+    ) -> Result<Self, libafl::Error> {
+        let CompanionConfig {
+            target_info,
+            fuzz_input,
+            coverage_shmem,
+            documents,
+        } = config;
+
+        let mut envs = vec![("ASAN_OPTIONS".into(), asan_options.into())];
+        envs.extend(
+            target_info
+                .env
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into())),
+        );
+        let output = OpenOptions::new()
+            .write(true)
+            .open("/dev/null")
+            .afl_context("Opening /dev/null")?;
+        let opts = NeoForkServerOptions {
+            target: target_info.path.as_os_str().to_owned(),
+            args: target_info.args.into_iter().map(Into::into).collect(),
+            envs,
+            input_setup: FuzzInputSetup::from(&fuzz_input),
+            memlimit: 0,
+            persistent_fuzzing: target_info.persistent_fuzzing,
+            deferred: target_info.defer_fork_server,
+            coverage_map_info: (coverage_shmem.id(), coverage_shmem.len()),
+            afl_debug: debug_afl,
+            debug_output: debug_child,
+            kill_signal: target_info.kill_signal,
+            stdout_capture_fd: output.as_fd(),
+            container: target_info.container,
+        };
+        let mut fork_server = NeoForkServer::new(opts)?;
+        let options = fork_server
+            .initialize()
+            .afl_context("Initializing the fork server of the companion server")?;
+        if let Some(map_size) = options.map_size
+            && map_size > coverage_shmem.len()
+        {
+            Err(libafl::Error::illegal_argument(format!(
+                "The map size is too small. {map_size} is required for the companion server."
+            )))?;
+        }
+        if matches!(fuzz_input, FuzzInput::SharedMemory(_) if !options.shmem_fuzz) {
+            Err(libafl::Error::unknown(
+                "Companion server requested sharedmem fuzzing, but you didn't prepare shmem",
+            ))?;
+        }
+
+        Ok(Self {
+            fork_server,
+            crash_exit_code: target_info.crash_exit_code,
+            timeout: target_info.timeout.into(),
+            started: Instant::now(),
+            fuzz_input,
+            coverage_shmem,
+            map_offset,
+            documents,
+        })
+    }
+
+    /// Sends the messages of `input_bytes` meant for the companion and starts a child,
+    /// without waiting for it.
+    pub(super) fn start_child(&mut self, input_bytes: &[u8]) -> Result<Pid, libafl::Error> {
+        let bytes = companion_stream(input_bytes, &self.documents);
+        self.fuzz_input.send(&bytes)?;
+        self.coverage_shmem.as_slice_mut().fill(0);
+        let pid = self.fork_server.start_child()?;
+        self.started = Instant::now();
+        Ok(pid)
+    }
+
+    /// Waits for the child `pid` and hands its coverage to `merge_coverage` with the offset
+    /// where it starts in the merged map.
+    ///
+    /// Returns the wait status of the child, `None` if it timed out, and how it exited.
+    /// The child runs alongside the target, so its timeout counts from its start rather than
+    /// from the end of the target.
+    pub(super) fn wait_child(
+        &mut self,
+        pid: Pid,
+        merge_coverage: impl FnOnce(usize, &[u8]),
+    ) -> Result<(Option<i32>, ExitKind), libafl::Error> {
+        let remaining = self.timeout.saturating_sub(self.started.elapsed());
+        let status = self
+            .fork_server
+            .wait_child(pid, &TimeSpec::from_duration(remaining))?;
+        merge_coverage(self.map_offset, self.coverage_shmem.as_slice());
+        Ok((status, exit_kind_of(status, self.crash_exit_code)))
+    }
+}
+
+/// The bytes of the LSP stream `stream` sent to a companion serving `documents`.
+///
+/// The payloads about the documents of other servers are dropped, while the others, including
+/// the lifecycle messages and the changes of watched files, are forwarded as they are, so that
+/// the companion sees the same framing variations and transport faults as the target.
+/// The bytes outside the payloads and the malformed payloads are forwarded as well.
+#[must_use]
+pub fn companion_stream(stream: &[u8], documents: &[String]) -> Vec<u8> {
+    JsonRPCMessage::split_at_headers(stream)
+        .into_iter()
+        .filter(|payload| document_uri(payload).is_none_or(|uri| is_served(&uri, documents)))
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// The URI of the text document of the request or notification in `payload`, if any.
+///
+/// The body is taken to start at the first `{`, so that payloads with unusual framing are
+/// routed like well-framed ones.
+fn document_uri(payload: &[u8]) -> Option<String> {
+    let body = &payload[memchr::memchr(b'{', payload)?..];
+    let message = serde_json::Deserializer::from_slice(body)
+        .into_iter::<serde_json::Value>()
+        .next()?
+        .ok()?;
+    let uri = message.pointer("/params/textDocument/uri")?.as_str()?;
+    Some(uri.to_owned())
+}
+
+fn is_served(uri: &str, documents: &[String]) -> bool {
+    let path = Path::new(uri);
+    let file_name = path.file_name().and_then(|it| it.to_str());
+    let extension = path.extension().and_then(|it| it.to_str());
+    documents
+        .iter()
+        .any(|it| Some(it.as_str()) == file_name || Some(it.as_str()) == extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::lsp::json_rpc::HeaderLineEnding;
+
+    #[test]
+    fn companion_stream_drops_other_documents() {
+        let messages = [
+            JsonRPCMessage::request(0_usize, "initialize".into(), json!({})),
+            JsonRPCMessage::notification(
+                "textDocument/didOpen".into(),
+                json!({"textDocument": {"uri": "file:///ws/main.c"}}),
+            ),
+            JsonRPCMessage::notification(
+                "textDocument/didOpen".into(),
+                json!({"textDocument": {"uri": "file:///ws/CMakeLists.txt"}}),
+            ),
+            JsonRPCMessage::request(
+                1_usize,
+                "textDocument/hover".into(),
+                json!({"textDocument": {"uri": "file:///ws/Cargo.toml"}}),
+            ),
+        ];
+        let stream: Vec<u8> = messages
+            .iter()
+            .flat_map(JsonRPCMessage::to_lsp_payload)
+            .collect();
+        let documents = ["CMakeLists.txt".to_owned(), "toml".to_owned()];
+        let routed = companion_stream(&stream, &documents);
+        let uris: Vec<_> = JsonRPCMessage::split_at_headers(&routed)
+            .into_iter()
+            .skip(1)
+            .map(document_uri)
+            .collect();
+        assert_eq!(
+            uris,
+            [
+                None,
+                Some("file:///ws/CMakeLists.txt".to_owned()),
+                Some("file:///ws/Cargo.toml".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn companion_stream_forwards_raw_bytes() {
+        let open = |uri: &str| {
+            JsonRPCMessage::notification(
+                "textDocument/didOpen".into(),
+                json!({"textDocument": {"uri": uri}}),
+            )
+        };
+        let served = open("file:///ws/Cargo.toml").to_lsp_payload_with(HeaderLineEnding::Lf);
+        let dropped = open("file:///ws/main.c").to_lsp_payload();
+        let noise = b"noise".to_vec();
+        let truncated = open("file:///ws/main.c").to_lsp_payload();
+        let truncated = &truncated[..truncated.len() - 3];
+        let stream = [&noise, &served, &dropped, truncated].concat();
+        let documents = ["toml".to_owned()];
+        assert_eq!(
+            companion_stream(&stream, &documents),
+            [&noise, &served, truncated].concat()
+        );
+    }
+}
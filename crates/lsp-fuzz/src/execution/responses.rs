@@ -1,17 +1,36 @@
 use std::{
     borrow::Cow,
-    io::{self, BufRead},
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
 };
 
-use libafl::observers::Observer;
-use libafl_bolts::Named;
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::Observer,
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
 use serde::{Deserialize, Serialize};
 
-use crate::lsp::json_rpc::JsonRPCMessage;
+use super::side_channel::{SideChannelLine, SideChannelParser, parse_side_channels};
+use crate::{
+    lsp::json_rpc::{FramingErrorKind, JsonRPCMessage, MalformedFrame},
+    utils::AflContext,
+};
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct LspOutputObserver {
     captured_messages: Vec<JsonRPCMessage>,
+    malformed_frame: Option<MalformedFrame>,
+    side_output: Vec<SideChannelLine>,
+    #[serde(skip)]
+    side_channels: Vec<Box<dyn SideChannelParser>>,
 }
 
 impl Named for LspOutputObserver {
@@ -26,6 +45,19 @@ impl LspOutputObserver {
     pub fn new() -> Self {
         Self {
             captured_messages: Vec::new(),
+            malformed_frame: None,
+            side_output: Vec::new(),
+            side_channels: Vec::new(),
+        }
+    }
+
+    /// Attributes the output of the target outside the LSP payloads to `parsers`,
+    /// see [`parse_side_channels`].
+    #[must_use]
+    pub fn with_side_channels(self, parsers: Vec<Box<dyn SideChannelParser>>) -> Self {
+        Self {
+            side_channels: parsers,
+            ..self
         }
     }
 
@@ -34,22 +66,213 @@ impl LspOutputObserver {
         &self.captured_messages
     }
 
-    /// Captures every complete LSP payload available from `reader`.
-    ///
-    /// # Errors
+    /// The first payload in the captured output that violates the LSP framing, if any.
+    #[must_use]
+    pub const fn malformed_frame(&self) -> Option<&MalformedFrame> {
+        self.malformed_frame.as_ref()
+    }
+
+    /// The lines the target wrote outside the LSP payloads, by side channel.
+    #[must_use]
+    pub fn side_output(&self) -> &[SideChannelLine] {
+        &self.side_output
+    }
+
+    /// Captures every complete LSP payload in `content`, the output of the target.
     ///
-    /// Returns any I/O error encountered while reading from `reader`.
-    pub fn capture_stdout_content<R: BufRead>(&mut self, mut reader: R) -> io::Result<()> {
-        while let Ok(message) = JsonRPCMessage::read_lsp_payload(&mut reader) {
-            self.captured_messages.push(message);
-        }
-        Ok(())
+    /// The output outside the payloads is skipped and recorded as [`Self::side_output`].
+    pub fn capture_output(&mut self, content: &[u8]) {
+        let scanned = JsonRPCMessage::scan_lsp_stream(content);
+        self.captured_messages = scanned.messages;
+        self.malformed_frame = scanned.malformed;
+        self.side_output = parse_side_channels(&scanned.noise, &self.side_channels);
     }
 }
 
 impl<I, State> Observer<I, State> for LspOutputObserver {
     fn pre_exec(&mut self, _state: &mut State, _input: &I) -> Result<(), libafl::Error> {
         self.captured_messages.clear();
+        self.malformed_frame = None;
+        self.side_output.clear();
+        Ok(())
+    }
+}
+
+/// Kinds of malformed framing that have already been reported.
+#[derive(Debug, Default, Serialize, Deserialize, SerdeAny)]
+pub struct ReportedFramingErrors {
+    seen: HashSet<FramingErrorKind>,
+}
+
+/// The malformed payload attached to the solution that produced it.
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct MalformedFrameFinding {
+    pub frame: MalformedFrame,
+}
+
+/// An objective flagging executions in which the target writes malformed LSP framing.
+///
+/// Each kind of framing error is reported once. Output between the payloads that does not start
+/// with a header is side-channel output rather than malformed framing, see
+/// [`JsonRPCMessage::scan_lsp_stream`].
+#[derive(Debug)]
+pub struct MalformedFramingFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+}
+
+impl MalformedFramingFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+        }
+    }
+}
+
+impl Named for MalformedFramingFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MalformedFramingFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for MalformedFramingFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        state.add_metadata(ReportedFramingErrors::default());
+        Ok(())
+    }
+}
+
+impl<EM, I, Observers, State> Feedback<EM, I, Observers, State> for MalformedFramingFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &Observers,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        // Output of crashed or killed targets is expected to be truncated.
+        if *exit_kind != ExitKind::Ok {
+            return Ok(false);
+        }
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        let Some(frame) = observer.malformed_frame() else {
+            return Ok(false);
+        };
+        let reported: &ReportedFramingErrors = state
+            .metadata()
+            .afl_context("ReportedFramingErrors not initialized")?;
+        Ok(!reported.seen.contains(&frame.kind))
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        observers: &Observers,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), libafl::Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        if let Some(frame) = observer.malformed_frame() {
+            let reported: &mut ReportedFramingErrors = state
+                .metadata_mut()
+                .afl_context("ReportedFramingErrors not initialized")?;
+            reported.seen.insert(frame.kind);
+            testcase.add_metadata(MalformedFrameFinding {
+                frame: frame.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The path of the response transcript saved for the solution at `input_path`.
+///
+/// The transcript is a hidden file next to the input, so that tools iterating
+/// over the solutions can skip it together with the other hidden files.
+#[must_use]
+pub fn transcript_path(input_path: &Path) -> PathBuf {
+    let file_name = input_path.file_name().unwrap_or_default().to_string_lossy();
+    input_path.with_file_name(format!(".{file_name}.responses.json"))
+}
+
+/// Saves the messages the target wrote before each solution as a JSON array next to the solution,
+/// see [`transcript_path`].
+///
+/// The transcript is written when the metadata of the solution is appended, so this feedback
+/// must come after the one naming the test case. It never deems an input interesting on its own.
+#[derive(Debug)]
+pub struct ResponseTranscriptFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+    solution_dir: PathBuf,
+}
+
+impl ResponseTranscriptFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver, solution_dir: PathBuf) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            solution_dir,
+        }
+    }
+}
+
+impl Named for ResponseTranscriptFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ResponseTranscriptFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for ResponseTranscriptFeedback {}
+
+impl<EM, I, Observers, State> Feedback<EM, I, Observers, State> for ResponseTranscriptFeedback
+where
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        observers: &Observers,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), libafl::Error> {
+        let Some(file_name) = testcase.filename() else {
+            return Ok(());
+        };
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        let transcript = serde_json::to_vec_pretty(observer.captured_messages())
+            .afl_context("Serializing response transcript")?;
+        fs::write(
+            transcript_path(&self.solution_dir.join(file_name)),
+            transcript,
+        )
+        .afl_context("Writing response transcript")?;
         Ok(())
     }
 }
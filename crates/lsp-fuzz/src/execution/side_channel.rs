@@ -0,0 +1,142 @@
+//! Parsing the output of the target outside the LSP payloads.
+//!
+//! Some servers write auxiliary text to stdout alongside the JSON-RPC messages, e.g., progress
+//! text, banners, or the log lines of clangd when `--log` is routed to stdout.
+//! [`LspOutputObserver`](super::responses::LspOutputObserver) skips such noise when it captures
+//! the messages, and attributes each line of it to the first [`SideChannelParser`] recognizing
+//! it, or to [`UNKNOWN_CHANNEL`].
+
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+/// The channel of the lines of noise that no parser recognizes.
+pub const UNKNOWN_CHANNEL: &str = "unknown";
+
+/// A line of the output of the target outside the LSP payloads.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SideChannelLine {
+    /// The channel of the parser that recognized the line.
+    pub channel: String,
+    /// The content of the line as extracted by the parser.
+    pub text: String,
+}
+
+/// A parser of the lines of an auxiliary protocol the target writes to stdout.
+pub trait SideChannelParser: Debug + Send + Sync {
+    /// The name of the channel, e.g., `clangd-log`.
+    fn channel(&self) -> &str;
+
+    /// The content of `line` if it belongs to the channel.
+    fn parse(&self, line: &str) -> Option<String>;
+}
+
+/// The log lines of clangd, e.g., `I[12:34:56.789] Indexed file.c`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClangdLogParser;
+
+impl SideChannelParser for ClangdLogParser {
+    fn channel(&self) -> &str {
+        "clangd-log"
+    }
+
+    fn parse(&self, line: &str) -> Option<String> {
+        let (level, rest) = line.split_at_checked(1)?;
+        if !matches!(level, "E" | "I" | "V" | "D") {
+            return None;
+        }
+        let (timestamp, message) = rest.strip_prefix('[')?.split_once("] ")?;
+        let is_timestamp = timestamp
+            .bytes()
+            .all(|it| it.is_ascii_digit() || it == b':' || it == b'.');
+        is_timestamp.then(|| format!("{level} {message}"))
+    }
+}
+
+/// The lines starting with a fixed prefix, which is stripped.
+#[derive(Debug, Clone)]
+pub struct LinePrefixParser {
+    channel: String,
+    prefix: String,
+}
+
+impl LinePrefixParser {
+    #[must_use]
+    pub const fn new(channel: String, prefix: String) -> Self {
+        Self { channel, prefix }
+    }
+}
+
+impl SideChannelParser for LinePrefixParser {
+    fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    fn parse(&self, line: &str) -> Option<String> {
+        line.strip_prefix(&self.prefix).map(str::to_owned)
+    }
+}
+
+/// Splits `noise` into lines attributed to the first of `parsers` recognizing them.
+///
+/// Blank lines are dropped.
+#[must_use]
+pub fn parse_side_channels(
+    noise: &[Vec<u8>],
+    parsers: &[Box<dyn SideChannelParser>],
+) -> Vec<SideChannelLine> {
+    noise
+        .iter()
+        .flat_map(|it| it.split(|&byte| byte == b'\n'))
+        .map(String::from_utf8_lossy)
+        .map(|line| line.trim_end_matches('\r').to_owned())
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            parsers
+                .iter()
+                .find_map(|parser| {
+                    parser.parse(&line).map(|text| SideChannelLine {
+                        channel: parser.channel().to_owned(),
+                        text,
+                    })
+                })
+                .unwrap_or_else(|| SideChannelLine {
+                    channel: UNKNOWN_CHANNEL.to_owned(),
+                    text: line,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_lines_to_channels() {
+        let parsers: Vec<Box<dyn SideChannelParser>> = vec![
+            Box::new(ClangdLogParser),
+            Box::new(LinePrefixParser::new(
+                "progress".to_owned(),
+                "Progress: ".to_owned(),
+            )),
+        ];
+        let noise = [
+            b"I[12:34:56.789] Indexed main.c\r\n\n".to_vec(),
+            b"Progress: 50%\nWelcome!\n".to_vec(),
+        ];
+        let lines = parse_side_channels(&noise, &parsers);
+        let lines: Vec<_> = lines
+            .iter()
+            .map(|it| (it.channel.as_str(), it.text.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("clangd-log", "I Indexed main.c"),
+                ("progress", "50%"),
+                (UNKNOWN_CHANNEL, "Welcome!"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Tracking crashes of auxiliary processes spawned by the target.
+//!
+//! Language servers delegate work to subprocesses (e.g., the proc-macro server of rust-analyzer
+//! or clang-tidy invoked by clangd) and often tolerate their crashes silently.
+//! Subprocesses inherit the environment of the target, including the coverage map
+//! and `ASAN_OPTIONS`, so instrumented subprocesses contribute coverage and write sanitizer logs
+//! named after their own process IDs.
+//! These logs are collected after each execution and reported as crashes of the input.
+
+use std::{borrow::Cow, fs, path::Path};
+
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::Observer,
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::AflContext;
+
+/// Records the auxiliary processes that crashed during an execution.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SubprocessObserver {
+    crashed_pids: Vec<i32>,
+}
+
+impl Named for SubprocessObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SubprocessObserver");
+        &NAME
+    }
+}
+
+impl SubprocessObserver {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn crashed_pids(&self) -> &[i32] {
+        &self.crashed_pids
+    }
+
+    pub(super) fn record_crash(&mut self, pid: Pid) {
+        self.crashed_pids.push(pid.as_raw());
+    }
+}
+
+impl<I, State> Observer<I, State> for SubprocessObserver {
+    fn pre_exec(&mut self, _state: &mut State, _input: &I) -> Result<(), libafl::Error> {
+        self.crashed_pids.clear();
+        Ok(())
+    }
+}
+
+/// Collects the sanitizer logs in `log_dir` written by processes other than the `spawned` ones,
+/// i.e., the children forked by the fork servers.
+///
+/// `log_dir` must be the log directory of this executor, which no other fuzzer instance
+/// writes to, as every sanitizer log in it is taken. Other files in it are left alone.
+/// The logs are removed after being read, and those of the `spawned` processes are removed
+/// so that a later process reusing their IDs is not blamed for them.
+pub(super) fn take_subprocess_logs(
+    log_dir: &Path,
+    spawned: &[Pid],
+) -> Result<Vec<(Pid, Vec<u8>)>, libafl::Error> {
+    let prefix = format!("{}.", super::ASAN_LOG_PREFIX);
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(log_dir).afl_context("Listing sanitizer logs")? {
+        let entry = entry.afl_context("Listing sanitizer logs")?;
+        let file_name = entry.file_name();
+        let Some(pid) = file_name
+            .to_str()
+            .and_then(|it| it.strip_prefix(&prefix))
+            .and_then(|it| it.parse().ok())
+            .map(Pid::from_raw)
+        else {
+            continue;
+        };
+        if spawned.contains(&pid) {
+            fs::remove_file(entry.path()).afl_context("Removing sanitizer log")?;
+            continue;
+        }
+        let log = fs::read(entry.path()).afl_context("Reading subprocess sanitizer log")?;
+        fs::remove_file(entry.path()).afl_context("Removing subprocess sanitizer log")?;
+        logs.push((pid, log));
+    }
+    Ok(logs)
+}
+
+/// The auxiliary processes that crashed on a solution.
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct SubprocessCrash {
+    pub pids: Vec<i32>,
+}
+
+/// Attaches [`SubprocessCrash`] to solutions on which auxiliary processes crashed,
+/// telling them apart from crashes of the target itself.
+///
+/// This feedback never deems an input interesting on its own.
+#[derive(Debug)]
+pub struct SubprocessCrashFeedback {
+    observer_handle: Handle<SubprocessObserver>,
+}
+
+impl SubprocessCrashFeedback {
+    #[must_use]
+    pub fn new(observer: &SubprocessObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+        }
+    }
+}
+
+impl Named for SubprocessCrashFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SubprocessCrashFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for SubprocessCrashFeedback {}
+
+impl<EM, I, Observers, State> Feedback<EM, I, Observers, State> for SubprocessCrashFeedback
+where
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        observers: &Observers,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), libafl::Error> {
+        // The observer is absent when subprocess tracking is disabled.
+        if let Some(observer) = observers.get(&self.observer_handle)
+            && !observer.crashed_pids().is_empty()
+        {
+            testcase.add_metadata(SubprocessCrash {
+                pids: observer.crashed_pids().to_vec(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::ASAN_LOG_PREFIX;
+
+    #[test]
+    fn take_subprocess_logs_skips_spawned_processes() {
+        let log_dir = tempfile::tempdir().unwrap();
+        for pid in [100, 200, 300] {
+            let log_file = log_dir.path().join(format!("{ASAN_LOG_PREFIX}.{pid}"));
+            fs::write(log_file, format!("log of {pid}")).unwrap();
+        }
+        let spawned = [Pid::from_raw(100), Pid::from_raw(300)];
+        let logs = take_subprocess_logs(log_dir.path(), &spawned).unwrap();
+        assert_eq!(logs, [(Pid::from_raw(200), b"log of 200".to_vec())]);
+        assert_eq!(fs::read_dir(log_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn take_subprocess_logs_keeps_other_files() {
+        let log_dir = tempfile::tempdir().unwrap();
+        let other_files = [
+            format!("{ASAN_LOG_PREFIX}.summary"),
+            format!("{ASAN_LOG_PREFIX}-100"),
+            "stderr.log".to_owned(),
+        ];
+        for file_name in &other_files {
+            fs::write(log_dir.path().join(file_name), "not a sanitizer log").unwrap();
+        }
+        let logs = take_subprocess_logs(log_dir.path(), &[]).unwrap();
+        assert!(logs.is_empty());
+        for file_name in &other_files {
+            assert!(log_dir.path().join(file_name).exists());
+        }
+    }
+}
@@ -1,6 +1,32 @@
 #![cfg(test)]
 
-use libafl::observers::{AsanBacktraceObserver, ObserverWithHashField};
+use std::{
+    fs,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use libafl::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::Feedback,
+    observers::{AsanBacktraceObserver, ObserverWithHashField},
+    state::NopState,
+};
+
+use libafl_bolts::rands::StdRand;
+use serde_json::json;
+use tuple_list::tuple_list;
+
+use super::{
+    capture_output,
+    pacing::{Pacer, PacingConfig, TokenBucket},
+    phases::{ExecutionPhase, PhaseTimeouts},
+    responses::{
+        LspOutputObserver, MalformedFramingFeedback, ResponseTranscriptFeedback, transcript_path,
+    },
+};
+use crate::lsp::json_rpc::JsonRPCMessage;
 
 #[test]
 fn rust_asan() {
@@ -47,3 +73,179 @@ fn rust_asan() {
     let partial_hash = obs.hash();
     assert_eq!(partial_hash, full_hash);
 }
+
+#[test]
+fn token_bucket_rate_limit() {
+    let start = Instant::now();
+    let mut bucket = TokenBucket::new(2.0, 2, start).unwrap();
+    assert_eq!(bucket.acquire(start), Duration::ZERO);
+    assert_eq!(bucket.acquire(start), Duration::ZERO);
+    assert_eq!(bucket.acquire(start), Duration::from_millis(500));
+    let later = start + Duration::from_secs(10);
+    assert_eq!(bucket.acquire(later), Duration::ZERO);
+    assert_eq!(bucket.acquire(later), Duration::ZERO);
+    assert!(bucket.acquire(later) > Duration::ZERO);
+}
+
+#[test]
+fn token_bucket_rejects_invalid_rates() {
+    let now = Instant::now();
+    assert!(TokenBucket::new(f64::MIN_POSITIVE, 1, now).is_err());
+    assert!(TokenBucket::new(f64::NAN, 1, now).is_err());
+    assert!(TokenBucket::new(-1.0, 1, now).is_err());
+    let config = PacingConfig {
+        messages_per_second: Some(f64::MIN_POSITIVE),
+        ..PacingConfig::default()
+    };
+    assert!(Pacer::new(config, StdRand::with_seed(0)).is_err());
+}
+
+#[test]
+fn pacer_keep_alive() {
+    let config = PacingConfig {
+        keep_alive: Some(Duration::from_millis(1)),
+        ..PacingConfig::default()
+    };
+    let mut pacer = Pacer::new(config, StdRand::with_seed(0)).unwrap();
+    let mut target = Vec::new();
+    pacer.idle(Duration::from_millis(5), &mut target).unwrap();
+    assert!(target.is_empty(), "No keep-alive before `initialized`");
+
+    pacer.record_sent("initialized");
+    pacer.idle(Duration::from_millis(20), &mut target).unwrap();
+    let (messages, malformed) = JsonRPCMessage::split_lsp_stream(&target);
+    assert!(malformed.is_none());
+    assert!(!messages.is_empty());
+    assert!(
+        messages
+            .iter()
+            .all(|it| it.method().is_some_and(|it| it == "$/setTrace"))
+    );
+
+    pacer.record_sent("shutdown");
+    let sent = target.len();
+    pacer.idle(Duration::from_millis(5), &mut target).unwrap();
+    assert_eq!(target.len(), sent, "No keep-alive after `shutdown`");
+
+    let pacer = Pacer::new(PacingConfig::default(), StdRand::with_seed(0)).unwrap();
+    let mut target = Vec::new();
+    pacer.idle(Duration::from_millis(1), &mut target).unwrap();
+    assert!(target.is_empty());
+}
+
+#[test]
+fn phase_of_timeouts() {
+    let request = |id: usize, method: &'static str| {
+        JsonRPCMessage::request(id, method.into(), json!({})).to_lsp_payload()
+    };
+    let response =
+        |id: usize| JsonRPCMessage::response(Some(id), Some(json!({})), None).to_lsp_payload();
+    let session = [
+        request(0, "initialize"),
+        request(1, "textDocument/hover"),
+        request(2, "shutdown"),
+    ]
+    .concat();
+
+    assert_eq!(ExecutionPhase::reached(&session, &[]), ExecutionPhase::Init);
+    assert_eq!(
+        ExecutionPhase::reached(&session, &response(0)),
+        ExecutionPhase::Requests
+    );
+    assert_eq!(
+        ExecutionPhase::reached(&session, &[response(0), response(2)].concat()),
+        ExecutionPhase::Requests
+    );
+    let shutdown_request = ExecutionPhase::reached(&session, &[response(0), response(1)].concat());
+    assert_eq!(shutdown_request, ExecutionPhase::ShutdownRequest);
+    assert!(shutdown_request.is_teardown());
+    assert!(!ExecutionPhase::Requests.is_teardown());
+    assert_eq!(
+        ExecutionPhase::reached(&session, &[response(0), response(1), response(2)].concat()),
+        ExecutionPhase::Shutdown
+    );
+
+    let timeouts = PhaseTimeouts {
+        init: Duration::from_secs(1),
+        per_message: Duration::from_millis(10),
+        shutdown: Duration::from_millis(100),
+    };
+    assert_eq!(timeouts.timeout_for(&session), Duration::from_millis(1130));
+}
+
+#[test]
+fn phase_of_timeouts_without_shutdown() {
+    let request = |id: usize, method: &'static str| {
+        JsonRPCMessage::request(id, method.into(), json!({})).to_lsp_payload()
+    };
+    let response =
+        |id: usize| JsonRPCMessage::response(Some(id), Some(json!({})), None).to_lsp_payload();
+    let session = [
+        request(0, "initialize"),
+        request(1, "textDocument/hover"),
+        request(2, "textDocument/definition"),
+    ]
+    .concat();
+
+    // The last request is not taken as `shutdown`.
+    assert_eq!(
+        ExecutionPhase::reached(&session, &[response(0), response(1)].concat()),
+        ExecutionPhase::Requests
+    );
+    assert_eq!(
+        ExecutionPhase::reached(&session, &[response(0), response(1), response(2)].concat()),
+        ExecutionPhase::Requests
+    );
+}
+
+#[test]
+fn timeout_counts_framed_messages() {
+    let timeouts = PhaseTimeouts {
+        init: Duration::ZERO,
+        per_message: Duration::from_millis(10),
+        shutdown: Duration::ZERO,
+    };
+    let did_open = JsonRPCMessage::notification(
+        "textDocument/didOpen".into(),
+        json!({"textDocument": {"text": "Content-Length: 42\r\n\r\nContent-Length: 1"}}),
+    );
+    let session = [
+        did_open.to_lsp_payload(),
+        b"Content-Length: 100\r\n\r\n{".to_vec(),
+    ]
+    .concat();
+    assert_eq!(timeouts.timeout_for(&session), Duration::from_millis(20));
+}
+
+#[test]
+fn crash_transcript_keeps_responses() {
+    let response = JsonRPCMessage::response(Some(0), Some(json!({})), None).to_lsp_payload();
+    // The target crashed while writing its second response.
+    let output = [response, b"Content-Length: 100\r\n\r\n{".to_vec()].concat();
+    let mut output_file = tempfile::tempfile().unwrap();
+    output_file.write_all(&output).unwrap();
+    let mut observer = LspOutputObserver::new();
+    capture_output(&mut output_file, &mut observer).unwrap();
+
+    let solution_dir = tempfile::tempdir().unwrap();
+    let mut transcript_feedback =
+        ResponseTranscriptFeedback::new(&observer, solution_dir.path().to_owned());
+    let mut framing_feedback = MalformedFramingFeedback::new(&observer);
+    let observers = tuple_list![observer];
+    let mut state = NopState::<()>::new();
+    assert!(
+        !framing_feedback
+            .is_interesting(&mut state, &mut (), &(), &observers, &ExitKind::Crash)
+            .unwrap(),
+        "The output of crashed targets is expected to be truncated"
+    );
+    let mut testcase = Testcase::new(());
+    testcase.set_filename("crash".to_owned());
+    transcript_feedback
+        .append_metadata(&mut state, &mut (), &observers, &mut testcase)
+        .unwrap();
+
+    let transcript = fs::read(transcript_path(&solution_dir.path().join("crash"))).unwrap();
+    let messages: Vec<serde_json::Value> = serde_json::from_slice(&transcript).unwrap();
+    assert_eq!(messages.len(), 1);
+}
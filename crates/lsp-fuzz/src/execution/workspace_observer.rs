@@ -1,6 +1,9 @@
 use std::{
     borrow::Cow,
+    collections::HashMap,
+    fs,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 use derive_new::new as New;
@@ -8,9 +11,35 @@ use libafl::observers::Observer;
 use libafl_bolts::Named;
 use serde::{Deserialize, Serialize};
 
+use crate::lsp_input::LspInput;
+
 #[derive(Debug, Serialize, Deserialize, New)]
 pub struct WorkspaceObserver {
     temp_dir: PathBuf,
+    instance_id: String,
+    #[new(default)]
+    track_changes: bool,
+    #[new(default)]
+    #[serde(skip)]
+    snapshot: HashMap<PathBuf, FileStamp>,
+    #[new(default)]
+    #[serde(skip)]
+    changed_files: Vec<ChangedFile>,
+}
+
+/// The size and modification time of a file, telling whether it was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// A file the target created or modified in the workspace during an execution.
+#[derive(Debug, Clone)]
+pub struct ChangedFile {
+    /// The path of the file relative to the workspace root.
+    pub path: PathBuf,
+    pub content: Vec<u8>,
 }
 
 impl Named for WorkspaceObserver {
@@ -32,19 +61,98 @@ pub trait HasWorkspace {
     fn setup_workspace(&self, workspace_root: &Path) -> Result<(), std::io::Error>;
 }
 
+impl WorkspaceObserver {
+    /// Files larger than this are not captured when diffing the workspace.
+    const MAX_CAPTURED_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Diffs the workspace before and after each execution,
+    /// capturing the files the target created or modified in [`Self::changed_files`].
+    #[must_use]
+    pub fn with_change_tracking(self, track_changes: bool) -> Self {
+        Self {
+            track_changes,
+            ..self
+        }
+    }
+
+    /// The files the target created or modified in the workspace during the last execution.
+    ///
+    /// Always empty unless change tracking is enabled.
+    #[must_use]
+    pub fn changed_files(&self) -> &[ChangedFile] {
+        &self.changed_files
+    }
+
+    fn workspace_dir(&self, input: &impl HasWorkspace) -> PathBuf {
+        let dir_name = LspInput::workspace_dir_name(&self.instance_id, input.workspace_hash());
+        self.temp_dir.join(dir_name)
+    }
+
+    fn diff_workspace(&mut self, workspace_dir: &Path) -> std::io::Result<()> {
+        let mut current = HashMap::new();
+        stamp_files(workspace_dir, workspace_dir, &mut current)?;
+        let mut changed: Vec<_> = current
+            .into_iter()
+            .filter(|(path, stamp)| self.snapshot.get(path) != Some(stamp))
+            .filter(|(_, stamp)| stamp.len <= Self::MAX_CAPTURED_FILE_SIZE)
+            .map(|(path, _)| path)
+            .collect();
+        changed.sort_unstable();
+        self.changed_files = changed
+            .into_iter()
+            .filter_map(|path| {
+                // The target may still be writing or removing files.
+                let content = fs::read(workspace_dir.join(&path)).ok()?;
+                Some(ChangedFile { path, content })
+            })
+            .collect();
+        Ok(())
+    }
+}
+
+/// Records the stamps of the files under `dir`, keyed by their paths relative to `root`,
+/// without following symbolic links.
+fn stamp_files(
+    root: &Path,
+    dir: &Path,
+    stamps: &mut HashMap<PathBuf, FileStamp>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let path = entry.path();
+        if metadata.is_dir() {
+            stamp_files(root, &path, stamps)?;
+        } else if metadata.is_file() {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_owned();
+            let stamp = FileStamp {
+                len: metadata.len(),
+                modified: metadata.modified().ok(),
+            };
+            stamps.insert(relative, stamp);
+        }
+    }
+    Ok(())
+}
+
 impl<Input, State> Observer<Input, State> for WorkspaceObserver
 where
     Input: HasWorkspace,
 {
     fn pre_exec(&mut self, _state: &mut State, input: &Input) -> Result<(), libafl::Error> {
-        let input_hash = input.workspace_hash();
-        let workspace_dir = self
-            .temp_dir
-            .join(format!("lsp-fuzz-workspace_{input_hash}"));
+        let workspace_dir = self.workspace_dir(input);
 
         std::fs::create_dir_all(&workspace_dir)?;
         input.setup_workspace(&workspace_dir)?;
 
+        self.snapshot.clear();
+        self.changed_files.clear();
+        if self.track_changes {
+            stamp_files(&workspace_dir, &workspace_dir, &mut self.snapshot)?;
+        }
+
         Ok(())
     }
 
@@ -54,11 +162,11 @@ where
         input: &Input,
         _exit_kind: &libafl::executors::ExitKind,
     ) -> Result<(), libafl::Error> {
-        let input_hash = input.workspace_hash();
-        let workspace_dir = self
-            .temp_dir
-            .join(format!("lsp-fuzz-workspace_{input_hash}"));
+        let workspace_dir = self.workspace_dir(input);
 
+        if self.track_changes {
+            self.diff_workspace(&workspace_dir)?;
+        }
         std::fs::remove_dir_all(workspace_dir)?;
 
         Ok(())
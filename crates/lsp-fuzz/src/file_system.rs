@@ -32,6 +32,42 @@ impl<F> FileSystemDirectory<F> {
         self.inner.is_empty()
     }
 
+    /// Adds an entry directly under this directory, returning the entry it replaces.
+    pub fn insert(
+        &mut self,
+        name: Utf8Input,
+        entry: FileSystemEntry<F>,
+    ) -> Option<FileSystemEntry<F>> {
+        self.inner.insert(name, entry)
+    }
+
+    /// Adds an entry at `path` relative to this directory, creating the missing parent
+    /// directories, and returns the entry it replaces.
+    ///
+    /// Nothing is added if one of the parents is a file.
+    pub fn insert_path(
+        &mut self,
+        path: &str,
+        entry: FileSystemEntry<F>,
+    ) -> Option<FileSystemEntry<F>> {
+        match path.split_once(path::MAIN_SEPARATOR) {
+            Some((dir_name, remainder)) if !remainder.is_empty() => {
+                let dir = self
+                    .inner
+                    .entry(Utf8Input::new(dir_name.to_owned()))
+                    .or_insert_with(|| FileSystemEntry::Directory(Self::default()));
+                match dir {
+                    FileSystemEntry::Directory(dir) => dir.insert_path(remainder, entry),
+                    FileSystemEntry::File(_) => None,
+                }
+            }
+            _ => {
+                let name = path.trim_end_matches(path::MAIN_SEPARATOR);
+                self.insert(Utf8Input::new(name.to_owned()), entry)
+            }
+        }
+    }
+
     #[must_use]
     pub fn get(&self, name: &str) -> Option<&FileSystemEntry<F>> {
         if let Some((dir_name, remainder)) = name.split_once(path::MAIN_SEPARATOR)
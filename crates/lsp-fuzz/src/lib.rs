@@ -3,6 +3,8 @@
 pub(crate) mod stolen;
 
 pub mod afl;
+pub mod attribution;
+pub mod campaign;
 pub mod corpus;
 pub mod debug;
 pub mod execution;
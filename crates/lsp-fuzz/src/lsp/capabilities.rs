@@ -1,12 +1,12 @@
 #[allow(clippy::wildcard_imports, reason = "There are too many")]
 use lsp_types::*;
 
-pub fn fuzzer_client_capabilities() -> ClientCapabilities {
+pub fn fuzzer_client_capabilities(position_encoding: PositionEncodingKind) -> ClientCapabilities {
     ClientCapabilities {
         workspace: Some(workspace_capabilities()),
         text_document: Some(text_document_capabilities()),
         general: Some(GeneralClientCapabilities {
-            position_encodings: Some(vec![PositionEncodingKind::UTF8]),
+            position_encodings: Some(vec![position_encoding]),
             stale_request_support: Some(StaleRequestSupportClientCapabilities {
                 cancel: true,
                 retry_on_content_modified: Vec::default(),
@@ -35,6 +35,18 @@ pub fn fuzzer_client_capabilities() -> ClientCapabilities {
     }
 }
 
+/// Capabilities advertising nothing but `position_encoding`, so that servers fall back to
+/// what they must support for any client.
+pub fn minimal_client_capabilities(position_encoding: PositionEncodingKind) -> ClientCapabilities {
+    ClientCapabilities {
+        general: Some(GeneralClientCapabilities {
+            position_encodings: Some(vec![position_encoding]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
 fn workspace_capabilities() -> WorkspaceClientCapabilities {
     WorkspaceClientCapabilities {
         workspace_folders: Some(true),
@@ -66,7 +78,10 @@ fn workspace_capabilities() -> WorkspaceClientCapabilities {
         apply_edit: None,
         workspace_edit: None,
         did_change_configuration: None,
-        did_change_watched_files: None,
+        did_change_watched_files: Some(DidChangeWatchedFilesClientCapabilities {
+            dynamic_registration: Some(true),
+            relative_pattern_support: Some(true),
+        }),
         execute_command: None,
         configuration: None,
         file_operations: None,
@@ -41,12 +41,3 @@ impl crate::lsp::Compose for SemanticTokensRangeParams {
         }
     }
 }
-
-compose! {
-    SemanticTokensDeltaParams {
-        work_done_progress_params: WorkDoneProgressParams,
-        partial_result_params: PartialResultParams,
-        text_document: TextDocumentIdentifier,
-        previous_result_id: String
-    }
-}
@@ -2,12 +2,16 @@ use std::{marker::PhantomData, result::Result, str::FromStr};
 
 use derive_new::new as New;
 use libafl::state::HasRand;
+use libafl_bolts::rands::Rand;
 use lsp_types::TextDocumentIdentifier;
+use serde::{Deserialize, Serialize};
 
-use super::{DynGenerator, GenerationError, LspParamsGenerator, boxed_generator};
+use super::{
+    DynGenerator, GenerationError, LspParamsGenerator, WeightedGeneratorList, boxed_generator,
+};
 use crate::{
     lsp::HasGenerators,
-    lsp_input::LspInput,
+    lsp_input::{LspInput, uri},
     text_document::mutations::{core::TextDocumentSelector, text_document_selectors::RandomDoc},
     utils::generate_random_uri_content,
 };
@@ -61,6 +65,50 @@ where
     }
 }
 
+/// Generates identifiers of documents that exist on disk but are never opened.
+///
+/// Servers have to read such documents from the file system instead of their open buffers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnopenedDocumentGen {
+    /// Whether to target the skeleton files of the workspace.
+    pub workspace_files: bool,
+    /// URIs of files outside the workspace.
+    pub external_uris: Vec<String>,
+    /// The relative weight of this generator against the generators targeting opened documents.
+    pub weight: usize,
+}
+
+impl<State> LspParamsGenerator<State> for UnopenedDocumentGen
+where
+    State: HasRand,
+{
+    type Output = TextDocumentIdentifier;
+
+    fn generate(
+        &self,
+        state: &mut State,
+        input: &LspInput,
+    ) -> Result<Self::Output, GenerationError> {
+        let skeleton_files = self
+            .workspace_files
+            .then(|| input.workspace.iter_files())
+            .into_iter()
+            .flatten()
+            .filter(|(_, entry)| entry.as_skeleton().is_some())
+            .filter_map(|(path, _)| uri::virtual_uri_for_path(&path));
+        let external_files = self
+            .external_uris
+            .iter()
+            .filter_map(|it| lsp_types::Uri::from_str(it).ok());
+        let candidates: Vec<_> = skeleton_files.chain(external_files).collect();
+        let uri = state
+            .rand_mut()
+            .choose(candidates)
+            .ok_or(GenerationError::NothingGenerated)?;
+        Ok(Self::Output { uri })
+    }
+}
+
 impl<State> HasGenerators<State> for TextDocumentIdentifier
 where
     State: HasRand,
@@ -70,16 +118,21 @@ where
     fn generators(
         config: &crate::lsp::GeneratorsConfig,
     ) -> impl IntoIterator<Item = Self::Generator> {
-        let mut generators: Vec<Self::Generator> = Vec::new();
+        let mut generators: WeightedGeneratorList<Self::Generator> =
+            WeightedGeneratorList::with_capacity(16);
         if config.use_context() {
-            generators.push(boxed_generator(
-                TextDocumentIdentifierGenerator::<RandomDoc>::new(),
-            ));
+            generators.push_weighted(
+                boxed_generator(TextDocumentIdentifierGenerator::<RandomDoc>::new()),
+                10,
+            );
+            if let Some(unopened) = config.unopened_documents() {
+                generators.push_weighted(boxed_generator(unopened.clone()), unopened.weight);
+            }
         } else {
             generators.push(boxed_generator(
                 RandomVirtualDocumentIdentifierGenerator::new(),
             ));
         }
-        generators
+        generators.finish()
     }
 }
@@ -121,6 +121,7 @@ where
                 boxed_generator(RINDGen::new(range_selectors::random_valid_range)),
                 2,
             );
+            generators.push(boxed_generator(RINDGen::new(range_selectors::edge_range)));
             if config.use_grammar_ops() {
                 generators.push_weighted(
                     boxed_generator(RINDGen::new(range_selectors::subtree_node_type)),
@@ -40,9 +40,9 @@ pub(super) fn random_valid_range<State: HasRand>(
     let rand = state.rand_mut();
     let lines: Vec<_> = doc.lines().collect();
     let start_line_idx = rand.below_or_zero(lines.len());
-    let start_line = lines[start_line_idx];
+    let start_line = &lines[start_line_idx];
     let end_line_idx = rand.between(start_line_idx, lines.len() - 1);
-    let end_line = lines[end_line_idx];
+    let end_line = &lines[end_line_idx];
     let start = Position {
         line: usize_to_u32(start_line_idx),
         character: usize_to_u32(rand.below_or_zero(start_line.len())),
@@ -187,3 +187,22 @@ pub(super) fn inverted_range<State>(_: &mut State, _: &Uri, doc: &TextDocument)
         end: start,
     }
 }
+
+/// Ranges between the edges of the document, see
+/// [`EdgePosition`](crate::lsp::generation::position_selectors::EdgePosition).
+pub(super) fn edge_range<State: HasRand>(state: &mut State, _: &Uri, doc: &TextDocument) -> Range {
+    let Range { start, end } = lsp_whole_range(doc);
+    let past_end = Position::new(end.line.saturating_add(1), 0);
+    let beyond_line_end = Position::new(end.line, end.character.saturating_add(1));
+    let candidates = [
+        Range::new(end, end),
+        Range::new(start, past_end),
+        Range::new(end, past_end),
+        Range::new(end, beyond_line_end),
+        Range::new(start, start),
+    ];
+    state
+        .rand_mut()
+        .choose(candidates)
+        .unwrap_or_else(|| lsp_whole_range(doc))
+}
@@ -11,8 +11,10 @@ pub mod numeric;
 pub mod position;
 pub(crate) mod position_selectors;
 pub mod registration;
+pub mod semantic_tokens;
 pub mod server_feedback;
 pub mod string;
+pub mod watched_files;
 
 pub use core::{
     combinators::{
@@ -11,8 +11,10 @@ use lsp_types::{TextDocumentIdentifier, TextDocumentPositionParams};
 use super::{
     DynGenerator, GenerationError, HasGenerators, LspParamsGenerator, WeightedGeneratorList,
     boxed_generator,
+    doc::UnopenedDocumentGen,
     position_selectors::{
-        HighlightSteer, NodeTypeBalancingSelection, PositionSelector, RandomPosition, ValidPosition,
+        CitationKeyPosition, EdgePosition, HighlightSteer, NodeTypeBalancingSelection,
+        PositionSelector, RandomPosition, ValidPosition,
     },
 };
 use crate::{
@@ -85,6 +87,10 @@ where
         if config.use_context() {
             let valid = boxed_generator(SelectInRandomDoc::new(ValidPosition::new()));
             generators.push_weighted(valid, 2);
+            generators.push(boxed_generator(SelectInRandomDoc::new(EdgePosition::new())));
+            generators.push(boxed_generator(SelectInRandomDoc::new(
+                CitationKeyPosition::new(),
+            )));
             if config.use_grammar_ops() {
                 generators.push_weighted(node_type.clone(), 3);
                 generators.push_weighted(steer.clone(), 3);
@@ -99,6 +105,12 @@ where
             if config.allow_invalid_positions() {
                 generators.push(random_position);
             }
+            if let Some(unopened) = config.unopened_documents() {
+                generators.push_weighted(
+                    boxed_generator(UnopenedDocPositionGenerator::new(unopened.clone())),
+                    unopened.weight,
+                );
+            }
         } else {
             generators.push_weighted(invalid_pos, 4);
         }
@@ -147,6 +159,43 @@ where
     }
 }
 
+/// Generates positions in documents that the server has to read from disk.
+///
+/// The contents of such documents are unknown to the fuzzer, so positions are picked
+/// within a small window at the beginning of the document.
+#[derive(Debug, Clone, New)]
+pub struct UnopenedDocPositionGenerator {
+    documents: UnopenedDocumentGen,
+}
+
+impl<State> LspParamsGenerator<State> for UnopenedDocPositionGenerator
+where
+    State: HasRand,
+{
+    type Output = TextDocumentPositionParams;
+
+    fn generate(
+        &self,
+        state: &mut State,
+        input: &LspInput,
+    ) -> Result<Self::Output, GenerationError> {
+        let text_document = self.documents.generate(state, input)?;
+        let rand = state.rand_mut();
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "The window is within the range of u32"
+        )]
+        let position = lsp_types::Position {
+            line: rand.below_or_zero(64) as u32,
+            character: rand.below_or_zero(80) as u32,
+        };
+        Ok(Self::Output {
+            text_document,
+            position,
+        })
+    }
+}
+
 #[derive(Debug, New)]
 pub struct FeedbackPositionsGenerator<D, F> {
     position_selector: F,
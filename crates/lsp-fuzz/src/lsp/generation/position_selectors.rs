@@ -4,12 +4,12 @@ use derive_more::derive::{Deref, DerefMut};
 use derive_new::new as New;
 use libafl::HasMetadata;
 use libafl_bolts::rands::Rand;
-use lsp_fuzz_grammars::WELL_KNOWN_HIGHLIGHT_CAPTURE_NAMES;
+use lsp_fuzz_grammars::{Language, WELL_KNOWN_HIGHLIGHT_CAPTURE_NAMES};
 use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::{
     text_document::{
-        TextDocument,
+        GrammarBasedMutation, TextDocument,
         grammar::tree_sitter::{CapturesIterator, TSNodeExt},
     },
     utils::{RandExt, ToLspPosition},
@@ -65,6 +65,141 @@ where
     }
 }
 
+/// Selects positions at the edges of a document.
+///
+/// The candidates are the beginning of the document, the end of the last line, one line past
+/// the end, the position of the final newline, and the end of a random line.
+/// A column beyond the end of a line is also selected sometimes, which the LSP specification
+/// asks servers to clamp to the line length.
+/// Empty documents only have the edges `(0, 0)` and `(1, 0)`.
+#[derive(Debug, Clone, Copy, New)]
+pub struct EdgePosition;
+
+impl EdgePosition {
+    const MAX_OVERSHOOT: usize = 64;
+}
+
+impl<State> PositionSelector<State> for EdgePosition
+where
+    State: libafl::state::HasRand,
+{
+    fn select_position(
+        &self,
+        state: &mut State,
+        doc: &TextDocument,
+    ) -> Option<lsp_types::Position> {
+        let lines: Vec<_> = doc.lines().map(|it| it.len()).collect();
+        let last_line = lines.len() - 1;
+        let mut edges = vec![(0, 0), (last_line, lines[last_line]), (last_line + 1, 0)];
+        if last_line > 0 && lines[last_line] == 0 {
+            // The document ends with a newline, which is at the end of the second last line.
+            edges.push((last_line - 1, lines[last_line - 1]));
+        }
+        let rand = state.rand_mut();
+        let line = rand.below_or_zero(lines.len());
+        edges.push((line, lines[line]));
+        edges.push((line, lines[line] + rand.between(1, Self::MAX_OVERSHOOT)));
+        let (line, character) = rand.choose(edges)?;
+        Some(lsp_types::Position {
+            line: u32::try_from(line).ok()?,
+            character: u32::try_from(character).ok()?,
+        })
+    }
+}
+
+/// Selects a position within a citation key, where completion and go-to-definition resolve
+/// the key against the bibliographies of the workspace.
+///
+/// In LaTeX documents, the candidates are the arguments of the `\cite`-like commands,
+/// including the position right before the closing brace. In BibTeX documents, they are the
+/// keys of the entries. Other documents have no candidates.
+#[derive(Debug, Clone, Copy, New)]
+pub struct CitationKeyPosition;
+
+impl<State> PositionSelector<State> for CitationKeyPosition
+where
+    State: libafl::state::HasRand,
+{
+    fn select_position(
+        &self,
+        state: &mut State,
+        doc: &TextDocument,
+    ) -> Option<lsp_types::Position> {
+        let content = doc.content();
+        let spans = match doc.language() {
+            Language::LaTeX => citation_arguments(&content),
+            Language::BibTeX => entry_keys(&content),
+            _ => return None,
+        };
+        let rand = state.rand_mut();
+        let (start, end) = *rand.choose(&spans)?;
+        let offset = rand.between(start, end);
+        let line_start = content[..offset]
+            .iter()
+            .rposition(|&it| it == b'\n')
+            .map_or(0, |it| it + 1);
+        let line = content[..line_start]
+            .iter()
+            .filter(|&&it| it == b'\n')
+            .count();
+        Some(lsp_types::Position {
+            line: u32::try_from(line).ok()?,
+            character: u32::try_from(offset - line_start).ok()?,
+        })
+    }
+}
+
+/// The byte spans of the braced arguments of the commands whose name contains `cite`,
+/// from the opening brace to the closing brace, skipping optional `[...]` arguments.
+fn citation_arguments(content: &[u8]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut idx = 0;
+    while let Some(offset) = content[idx..].iter().position(|&it| it == b'\\') {
+        let name_start = idx + offset + 1;
+        let name_len = content[name_start..]
+            .iter()
+            .take_while(|it| it.is_ascii_alphabetic())
+            .count();
+        idx = name_start + name_len;
+        if !content[name_start..idx].windows(4).any(|it| it == b"cite") {
+            continue;
+        }
+        while content.get(idx) == Some(&b'[') {
+            idx += content[idx..]
+                .iter()
+                .position(|&it| it == b']')
+                .map_or(content.len() - idx, |it| it + 1);
+        }
+        if content.get(idx) != Some(&b'{') {
+            continue;
+        }
+        let end = content[idx..]
+            .iter()
+            .position(|&it| matches!(it, b'}' | b'\n'))
+            .map_or(content.len(), |it| idx + it);
+        spans.push((idx + 1, end));
+        idx = end;
+    }
+    spans
+}
+
+/// The byte spans of the keys of the entries of a BibTeX document.
+fn entry_keys(content: &[u8]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    for (at, _) in content.iter().enumerate().filter(|(_, it)| **it == b'@') {
+        let Some(open) = content[at..].iter().position(|&it| it == b'{') else {
+            break;
+        };
+        let start = at + open + 1;
+        let key_len = content[start..]
+            .iter()
+            .take_while(|it| !matches!(it, b',' | b'}' | b'@'))
+            .count();
+        spans.push((start, start + key_len));
+    }
+    spans
+}
+
 #[derive(Debug, Clone, Copy, New)]
 pub struct NodeTypeBalancingSelection;
 
@@ -9,6 +9,7 @@ mod workspace;
 use std::{any::type_name, borrow::Cow, fmt::Debug};
 
 use libafl::{
+    HasMetadata,
     mutators::{MutationResult, Mutator},
     state::HasRand,
 };
@@ -19,7 +20,10 @@ use crate::{
         GeneratorsConfig, HasGenerators, LspMessage, LspMessageMeta, MessageParam,
         generation::LspParamsGenerator,
     },
-    lsp_input::LspInput,
+    lsp_input::{
+        LspInput, sequence_length::SequenceLengthPolicy,
+        server_response::supported_methods::SupportedMethods,
+    },
 };
 
 pub use diagnostics::append_diagnostic_messages;
@@ -37,6 +41,7 @@ where
 {
     name: Cow<'static, str>,
     generators: Vec<<M::Params as HasGenerators<State>>::Generator>,
+    unsupported_probability: Option<f64>,
 }
 
 impl<M: LspMessageMeta, State> Debug for AppendMessage<M, State>
@@ -56,8 +61,6 @@ where
     }
 }
 
-pub const MAX_MESSAGES: usize = 20;
-
 impl<M, State> AppendMessage<M, State>
 where
     M: LspMessageMeta,
@@ -73,7 +76,11 @@ where
         let name = Cow::Owned(format!("AppendRandomlyGenerated {}", M::METHOD));
         let generators: Vec<_> = M::Params::generators(config).into_iter().collect();
         assert!(!generators.is_empty(), "No generators for {}", M::METHOD);
-        Self { name, generators }
+        Self {
+            name,
+            generators,
+            unsupported_probability: config.unsupported_methods,
+        }
     }
 }
 
@@ -89,7 +96,7 @@ where
 
 impl<M, State> Mutator<LspInput, State> for AppendMessage<M, State>
 where
-    State: HasRand,
+    State: HasRand + HasMetadata,
     M: LspMessageMeta,
     M::Params: HasGenerators<State> + MessageParam<M>,
 {
@@ -98,6 +105,19 @@ where
         state: &mut State,
         input: &mut LspInput,
     ) -> Result<MutationResult, libafl::Error> {
+        let policy = SequenceLengthPolicy::of(state);
+        if !state.rand_mut().coinflip(policy.append_probability) {
+            return Ok(MutationResult::Skipped);
+        }
+        if let Some(probability) = self.unsupported_probability
+            && state
+                .metadata_map()
+                .get::<SupportedMethods>()
+                .is_some_and(|it| !it.is_supported(M::METHOD))
+            && !state.rand_mut().coinflip(probability)
+        {
+            return Ok(MutationResult::Skipped);
+        }
         let Some(generator) = state.rand_mut().choose(&self.generators) else {
             return Ok(MutationResult::Skipped);
         };
@@ -109,13 +129,14 @@ where
             Err(crate::lsp::generation::GenerationError::Error(err)) => return Err(err),
         };
         let message = LspMessage::from_params::<M>(params);
-        if input.messages.len() >= MAX_MESSAGES {
-            let being_replaced = state.rand_mut().choose(input.messages.iter_mut()).expect(
+        if input.messages.len() >= policy.max_messages {
+            let index = state.rand_mut().below_or_zero(input.messages.len());
+            let being_replaced = input.messages.message_mut(index).expect(
                 "There must be at least one message in the input when entering this branch",
             );
             *being_replaced = message;
         } else {
-            input.messages.push(message);
+            input.push_message(message);
         }
         Ok(MutationResult::Mutated)
     }
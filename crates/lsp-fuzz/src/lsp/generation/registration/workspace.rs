@@ -7,5 +7,6 @@ append_randoms! {
         request::ExecuteCommand,
         request::WorkspaceSymbolRequest,
         request::WorkspaceSymbolResolve,
+        notification::DidChangeWatchedFiles,
     }
 }
@@ -0,0 +1,123 @@
+use derive_new::new as New;
+use libafl::state::{HasCurrentTestcase, HasRand};
+use libafl_bolts::rands::Rand;
+use lsp_types::{
+    PartialResultParams, SemanticTokensDeltaParams, TextDocumentIdentifier, WorkDoneProgressParams,
+};
+
+use super::{DynGenerator, GenerationError, LspParamsGenerator, boxed_generator};
+use crate::{
+    lsp::{GeneratorsConfig, HasGenerators},
+    lsp_input::{LspInput, server_response::metadata::LspResponseInfo},
+    text_document::mutations::{core::TextDocumentSelector, text_document_selectors::RandomDoc},
+};
+
+/// Result ids that no server hands out, or that collide with common encodings of real ones.
+const FABRICATED_RESULT_IDS: &[&str] = &["", "0", "1", "-1", "18446744073709551616", "null", " "];
+
+/// How the previous result id of a delta request relates to the results of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PreviousResult {
+    /// The latest result of the same document, which the server likely has cached.
+    Latest,
+    /// An earlier result of the same document, which the server may have evicted.
+    Stale,
+    /// A result of another document.
+    Foreign,
+    /// A result id of the same document with a changed character or suffix.
+    Perturbed,
+    /// A result id the server never handed out.
+    Fabricated,
+}
+
+/// Generates `textDocument/semanticTokens/full/delta` requests relative to the results
+/// the server returned for the current test case, including deliberately inconsistent ones.
+///
+/// The result ids are recorded in [`LspResponseInfo::semantic_tokens`].
+#[derive(Debug, Clone, Copy, New)]
+pub struct SemanticTokensDeltaGenerator;
+
+impl<State> LspParamsGenerator<State> for SemanticTokensDeltaGenerator
+where
+    State: HasRand + HasCurrentTestcase<LspInput>,
+{
+    type Output = SemanticTokensDeltaParams;
+
+    fn generate(
+        &self,
+        state: &mut State,
+        input: &LspInput,
+    ) -> Result<Self::Output, GenerationError> {
+        let (uri, _) =
+            RandomDoc::select_document(state, input).ok_or(GenerationError::NothingGenerated)?;
+        let (own_ids, foreign_ids) = state
+            .current_testcase()
+            .ok()
+            .and_then(|testcase| {
+                let info = &testcase.metadata::<LspResponseInfo>().ok()?.semantic_tokens;
+                let own_ids = info.result_ids.get(&uri).cloned().unwrap_or_default();
+                let foreign_ids: Vec<_> = info.foreign_result_ids(&uri).cloned().collect();
+                Some((own_ids, foreign_ids))
+            })
+            .unwrap_or_default();
+
+        let rand = state.rand_mut();
+        let relation = *rand
+            .choose(&[
+                PreviousResult::Latest,
+                PreviousResult::Latest,
+                PreviousResult::Stale,
+                PreviousResult::Foreign,
+                PreviousResult::Perturbed,
+                PreviousResult::Fabricated,
+            ])
+            .expect("The choices are not empty");
+        let previous_result_id = match relation {
+            PreviousResult::Latest => own_ids.last().cloned(),
+            PreviousResult::Stale => rand.choose(own_ids.iter().rev().skip(1)).cloned(),
+            PreviousResult::Foreign => rand.choose(&foreign_ids).cloned(),
+            PreviousResult::Perturbed => rand.choose(&own_ids).map(|it| perturb(rand, it)),
+            PreviousResult::Fabricated => None,
+        }
+        .unwrap_or_else(|| {
+            (*rand
+                .choose(FABRICATED_RESULT_IDS)
+                .expect("The result ids are not empty"))
+            .to_owned()
+        });
+
+        Ok(SemanticTokensDeltaParams {
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            text_document: TextDocumentIdentifier { uri },
+            previous_result_id,
+        })
+    }
+}
+
+/// Changes `result_id` so that it likely names a result the server never returned,
+/// e.g., by incrementing a numeric id.
+fn perturb<R: Rand>(rand: &mut R, result_id: &str) -> String {
+    match (result_id.parse::<u64>(), rand.below_or_zero(3)) {
+        (Ok(id), 0) => id.wrapping_add(1).to_string(),
+        (Ok(id), 1) => id.wrapping_sub(1).to_string(),
+        (_, 0) => format!("{result_id}0"),
+        (_, 1) => result_id.chars().rev().collect(),
+        _ => {
+            let mut chars: Vec<_> = result_id.chars().collect();
+            chars.pop();
+            chars.into_iter().collect()
+        }
+    }
+}
+
+impl<State> HasGenerators<State> for SemanticTokensDeltaParams
+where
+    State: HasRand + HasCurrentTestcase<LspInput> + 'static,
+{
+    type Generator = DynGenerator<State, Self>;
+
+    fn generators(_config: &GeneratorsConfig) -> impl IntoIterator<Item = Self::Generator> {
+        [boxed_generator(SemanticTokensDeltaGenerator::new())]
+    }
+}
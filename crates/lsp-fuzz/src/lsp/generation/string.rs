@@ -6,7 +6,7 @@ use libafl_bolts::rands::Rand;
 use super::{DefaultGenerator, DynGenerator, GenerationError, LspParamsGenerator, boxed_generator};
 use crate::{
     lsp::HasGenerators,
-    lsp_input::LspInput,
+    lsp_input::{LspInput, server_response::dictionary::ResponseTokens},
     text_document::{
         GrammarBasedMutation,
         grammar::tree_sitter::TreeIter,
@@ -50,6 +50,43 @@ where
     }
 }
 
+/// Generates strings mined from the responses of the server,
+/// see [`crate::lsp_input::server_response::dictionary`].
+#[derive(Debug, Default)]
+pub struct ResponseTokensGenerator;
+
+impl ResponseTokensGenerator {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<State> LspParamsGenerator<State> for ResponseTokensGenerator
+where
+    State: HasMetadata + HasRand,
+{
+    type Output = String;
+
+    fn generate(
+        &self,
+        state: &mut State,
+        _input: &LspInput,
+    ) -> Result<Self::Output, GenerationError> {
+        let token_cnt = state
+            .metadata()
+            .map(ResponseTokens::len)
+            .ok()
+            .and_then(NonZeroUsize::new)
+            .ok_or(GenerationError::NothingGenerated)?;
+        let idx = state.rand_mut().below(token_cnt);
+        let tokens: &ResponseTokens = state
+            .metadata()
+            .map_err(|_| GenerationError::NothingGenerated)?;
+        Ok(tokens[idx].clone())
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct TerminalTextGenerator<DocSel> {
     pub(crate) _phantom: PhantomData<DocSel>,
@@ -78,11 +115,12 @@ where
         let doc = DocSel::select_document(state, input)
             .map(|it| it.1)
             .ok_or(GenerationError::NothingGenerated)?;
+        let content = doc.content();
         let terminal_text = doc
             .parse_tree()
             .iter()
             .filter(|it| it.child_count() == 0)
-            .filter_map(|node| node.utf8_text(doc.content()).ok());
+            .filter_map(|node| node.utf8_text(&content).ok());
         let text = state
             .rand_mut()
             .choose(terminal_text)
@@ -103,8 +141,13 @@ where
     ) -> impl IntoIterator<Item = Self::Generator> {
         const DEFAULT: DefaultGenerator<String> = DefaultGenerator::new();
         const TOKENS: UTF8TokensGenerator = UTF8TokensGenerator::new();
+        const RESPONSE_TOKENS: ResponseTokensGenerator = ResponseTokensGenerator::new();
         const TERMINAL_TEXT: TerminalTextGenerator<RandomDoc> = TerminalTextGenerator::new();
-        let mut generators = vec![boxed_generator(DEFAULT), boxed_generator(TOKENS)];
+        let mut generators = vec![
+            boxed_generator(DEFAULT),
+            boxed_generator(TOKENS),
+            boxed_generator(RESPONSE_TOKENS),
+        ];
         if config.use_context() {
             generators.push(boxed_generator(TERMINAL_TEXT));
         }
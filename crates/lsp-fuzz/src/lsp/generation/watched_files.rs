@@ -0,0 +1,223 @@
+use std::path::Path;
+
+use derive_new::new as New;
+use libafl::state::{HasCurrentTestcase, HasRand};
+use libafl_bolts::rands::Rand;
+use lsp_types::{DidChangeWatchedFilesParams, FileChangeType, FileEvent};
+
+use super::{
+    DynGenerator, GenerationError, LspParamsGenerator, WeightedGeneratorList, boxed_generator,
+};
+use crate::{
+    lsp::{GeneratorsConfig, HasGenerators},
+    lsp_input::{LspInput, server_response::metadata::LspResponseInfo, uri},
+};
+
+const CHANGE_TYPES: [FileChangeType; 3] = [
+    FileChangeType::CREATED,
+    FileChangeType::CHANGED,
+    FileChangeType::DELETED,
+];
+
+const MAX_EVENTS: usize = 4;
+
+/// Generates file events for the files of the workspace.
+#[derive(Debug, Clone, Copy, New)]
+pub struct WorkspaceFileEvents;
+
+impl<State> LspParamsGenerator<State> for WorkspaceFileEvents
+where
+    State: HasRand,
+{
+    type Output = DidChangeWatchedFilesParams;
+
+    fn generate(
+        &self,
+        state: &mut State,
+        input: &LspInput,
+    ) -> Result<Self::Output, GenerationError> {
+        let files: Vec<_> = input
+            .workspace
+            .iter_files()
+            .filter_map(|(path, _)| uri::virtual_uri_for_path(&path))
+            .collect();
+        let rand = state.rand_mut();
+        let changes: Vec<_> = (0..rand.between(1, MAX_EVENTS))
+            .filter_map(|_| {
+                let uri = rand.choose(&files)?.clone();
+                let typ = *rand.choose(&CHANGE_TYPES)?;
+                Some(FileEvent { uri, typ })
+            })
+            .collect();
+        if changes.is_empty() {
+            return Err(GenerationError::NothingGenerated);
+        }
+        Ok(DidChangeWatchedFilesParams { changes })
+    }
+}
+
+/// How a generated path relates to the glob pattern it is generated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GlobInstance {
+    /// The path matches the pattern.
+    Matching,
+    /// The path differs from a matching path in one character or one suffix.
+    NearlyMatching,
+    /// The path matches the pattern with huge expansions of the wildcards,
+    /// which are costly for naive glob matchers.
+    Pathological,
+}
+
+impl GlobInstance {
+    /// The maximum number of directories a `**` expands to.
+    const fn max_depth(self) -> usize {
+        match self {
+            Self::Matching | Self::NearlyMatching => 4,
+            Self::Pathological => 512,
+        }
+    }
+
+    /// The maximum length of the name a `*` expands to.
+    const fn max_name_len(self) -> usize {
+        match self {
+            Self::Matching | Self::NearlyMatching => 8,
+            Self::Pathological => 4096,
+        }
+    }
+}
+
+/// Generates file events for paths derived from the glob patterns of the file watchers
+/// registered by the server.
+///
+/// The patterns are recorded from the `client/registerCapability` requests of the server
+/// in [`LspResponseInfo::file_watchers`].
+#[derive(Debug, Clone, Copy, New)]
+pub struct WatchedFileEvents;
+
+impl<State> LspParamsGenerator<State> for WatchedFileEvents
+where
+    State: HasRand + HasCurrentTestcase<LspInput>,
+{
+    type Output = DidChangeWatchedFilesParams;
+
+    fn generate(
+        &self,
+        state: &mut State,
+        _input: &LspInput,
+    ) -> Result<Self::Output, GenerationError> {
+        let testcase = state
+            .current_testcase()
+            .map_err(|_| GenerationError::NothingGenerated)?;
+        let patterns: Vec<_> = testcase
+            .metadata::<LspResponseInfo>()
+            .map_err(|_| GenerationError::NothingGenerated)?
+            .file_watchers
+            .iter()
+            .cloned()
+            .collect();
+        drop(testcase);
+
+        let rand = state.rand_mut();
+        let instance = *rand
+            .choose(&[
+                GlobInstance::Matching,
+                GlobInstance::Matching,
+                GlobInstance::NearlyMatching,
+                GlobInstance::Pathological,
+            ])
+            .expect("The choices are not empty");
+        let changes: Vec<_> = (0..rand.between(1, MAX_EVENTS))
+            .filter_map(|_| {
+                let pattern = rand.choose(&patterns)?;
+                let mut path = instantiate_glob(rand, pattern, instance);
+                if instance == GlobInstance::NearlyMatching {
+                    perturb_path(rand, &mut path);
+                }
+                let uri = uri::virtual_uri_for_path(Path::new(&path))?;
+                let typ = *rand.choose(&CHANGE_TYPES)?;
+                Some(FileEvent { uri, typ })
+            })
+            .collect();
+        if changes.is_empty() {
+            return Err(GenerationError::NothingGenerated);
+        }
+        Ok(DidChangeWatchedFilesParams { changes })
+    }
+}
+
+/// Expands the wildcards of `pattern` into a path.
+///
+/// Character classes expand to their first character, or to `_` if they are negated.
+/// Nested alternatives are not supported and are kept as they are.
+fn instantiate_glob<R: Rand>(rand: &mut R, pattern: &str, instance: GlobInstance) -> String {
+    let mut path = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let trailing_slash = chars.next_if_eq(&'/').is_some();
+                let depth = rand.below_or_zero(instance.max_depth() + 1);
+                let dirs: Vec<_> = (0..depth).map(|it| format!("d{it}")).collect();
+                path.push_str(&dirs.join("/"));
+                if trailing_slash && depth > 0 {
+                    path.push('/');
+                }
+            }
+            '*' => {
+                let len = rand.between(1, instance.max_name_len());
+                path.extend((0..len).map(|_| random_name_char(rand)));
+            }
+            '?' => path.push(random_name_char(rand)),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|it| *it != ']').collect();
+                match class.chars().next() {
+                    Some('!' | '^') | None => path.push('_'),
+                    Some(first) => path.push(first),
+                }
+            }
+            '{' => {
+                let alternatives: String = chars.by_ref().take_while(|it| *it != '}').collect();
+                let alternatives: Vec<_> = alternatives.split(',').collect();
+                if let Some(alternative) = rand.choose(alternatives) {
+                    path.push_str(&instantiate_glob(rand, alternative, instance));
+                }
+            }
+            _ => path.push(ch),
+        }
+    }
+    path
+}
+
+/// Changes `path` so that it likely does not match the pattern it was generated from anymore.
+fn perturb_path<R: Rand>(rand: &mut R, path: &mut String) {
+    match rand.below_or_zero(4) {
+        0 => path.push('~'),
+        1 => path.push_str(".bak"),
+        2 => {
+            path.pop();
+        }
+        _ => *path = path.to_uppercase(),
+    }
+}
+
+fn random_name_char<R: Rand>(rand: &mut R) -> char {
+    const NAME_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789_-.";
+    char::from(NAME_CHARS[rand.below_or_zero(NAME_CHARS.len())])
+}
+
+impl<State> HasGenerators<State> for DidChangeWatchedFilesParams
+where
+    State: HasRand + HasCurrentTestcase<LspInput> + 'static,
+{
+    type Generator = DynGenerator<State, Self>;
+
+    fn generators(config: &GeneratorsConfig) -> impl IntoIterator<Item = Self::Generator> {
+        let mut generators = WeightedGeneratorList::with_capacity(2);
+        generators.push(boxed_generator(WorkspaceFileEvents::new()));
+        if config.use_feedback_guidance() {
+            generators.push_weighted(boxed_generator(WatchedFileEvents::new()), 3);
+        }
+        generators.finish()
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     borrow::Cow,
     fmt::{self, Display},
     io::{self, BufRead, Read},
+    ops::Range,
 };
 
 use serde::{Deserialize, Deserializer, Serialize};
@@ -112,9 +113,32 @@ pub struct ResponseError {
 }
 
 const CONTENT_LENGTH_HEADER: &str = "Content-Length";
+const CONTENT_TYPE_HEADER: &str = "Content-Type";
 const HEADER_SEP: &str = ": ";
 const HEADER_BODY_SEP: &str = "\r\n\r\n";
 
+/// The line ending terminating the header lines of an LSP payload.
+///
+/// The specification mandates `\r\n`, while lenient header parsers may accept the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum HeaderLineEnding {
+    #[default]
+    CrLf,
+    Lf,
+    Cr,
+}
+
+impl HeaderLineEnding {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::CrLf => "\r\n",
+            Self::Lf => "\n",
+            Self::Cr => "\r",
+        }
+    }
+}
+
 impl JsonRPCMessage {
     #[must_use]
     pub fn request(
@@ -178,16 +202,28 @@ impl JsonRPCMessage {
     /// Panics if `serde_json` fails to serialize the message.
     #[must_use]
     pub fn to_lsp_payload(&self) -> Vec<u8> {
+        self.to_lsp_payload_with(HeaderLineEnding::CrLf)
+    }
+
+    /// Serializes the message into an LSP payload whose header lines end with `line_ending`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `serde_json` fails to serialize the message.
+    #[must_use]
+    pub fn to_lsp_payload_with(&self, line_ending: HeaderLineEnding) -> Vec<u8> {
         let content =
             serde_json::to_vec(self).expect("Serialization of serde_json::Value cannot fail.");
         let content_length = content.len().to_string().into_bytes();
+        let line_ending = line_ending.as_str().as_bytes();
         CONTENT_LENGTH_HEADER
             .as_bytes()
             .iter()
             .copied()
             .chain(HEADER_SEP.as_bytes().iter().copied())
             .chain(content_length)
-            .chain(HEADER_BODY_SEP.as_bytes().iter().copied())
+            .chain(line_ending.iter().copied())
+            .chain(line_ending.iter().copied())
             .chain(content)
             .collect()
     }
@@ -241,6 +277,192 @@ impl JsonRPCMessage {
     }
 }
 
+/// The way in which an LSP payload violates the `Content-Length` framing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FramingErrorKind {
+    /// The headers are not terminated with an empty line.
+    MissingHeaderTerminator,
+    /// A header line is not a `key: value` pair, or its value is invalid.
+    InvalidHeader,
+    /// The headers do not contain `Content-Length`.
+    MissingContentLength,
+    /// The body is shorter or longer than the declared `Content-Length`.
+    LengthMismatch,
+    /// The body is not a JSON document.
+    NonJsonBody,
+}
+
+/// A payload that cannot be framed, together with the raw bytes starting from it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MalformedFrame {
+    pub kind: FramingErrorKind,
+    pub raw: Vec<u8>,
+}
+
+/// A byte stream split by [`JsonRPCMessage::scan_lsp_stream`].
+#[derive(Debug, Default)]
+pub struct ScannedLspStream {
+    pub messages: Vec<JsonRPCMessage>,
+    /// The runs of bytes outside the LSP payloads, in the order they appear.
+    pub noise: Vec<Vec<u8>>,
+    /// The first payload that cannot be framed, if any.
+    pub malformed: Option<MalformedFrame>,
+}
+
+impl JsonRPCMessage {
+    /// Splits a byte stream into LSP-framed JSON-RPC messages.
+    ///
+    /// Unlike [`Self::read_lsp_payload`], this tells malformed framing apart from the end of
+    /// the stream. Splitting stops at the first malformed payload since the stream cannot be
+    /// resynchronized afterwards. Well-framed JSON bodies that are not JSON-RPC messages are
+    /// skipped.
+    #[must_use]
+    pub fn split_lsp_stream(mut stream: &[u8]) -> (Vec<Self>, Option<MalformedFrame>) {
+        let mut messages = Vec::new();
+        while !stream.is_empty() {
+            let malformed = |kind| MalformedFrame {
+                kind,
+                raw: stream.to_vec(),
+            };
+            let body_range = match Self::frame_body(stream) {
+                Ok(range) => range,
+                Err(kind) => return (messages, Some(malformed(kind))),
+            };
+            let body = &stream[body_range.clone()];
+            match serde_json::from_slice::<serde_json::Value>(body) {
+                Ok(value) => messages.extend(serde_json::from_value(value).ok()),
+                Err(_) => return (messages, Some(malformed(Self::classify_body(body)))),
+            }
+            stream = &stream[body_range.end..];
+        }
+        (messages, None)
+    }
+
+    /// Splits a byte stream into LSP-framed JSON-RPC messages, skipping the bytes outside the
+    /// payloads.
+    ///
+    /// Some servers write progress text or banners to stdout alongside the LSP payloads.
+    /// Unlike [`Self::split_lsp_stream`], a payload is only expected where a header starts,
+    /// and the bytes before it are recorded as noise. A malformed payload starting with a header
+    /// is recorded (the first one only) and skipped up to the next header.
+    #[must_use]
+    pub fn scan_lsp_stream(mut stream: &[u8]) -> ScannedLspStream {
+        let mut scanned = ScannedLspStream::default();
+        while !stream.is_empty() {
+            let Some(header_start) = Self::next_header(stream) else {
+                scanned.noise.push(stream.to_vec());
+                break;
+            };
+            if header_start > 0 {
+                scanned.noise.push(stream[..header_start].to_vec());
+                stream = &stream[header_start..];
+            }
+            let frame_end = match Self::frame_body(stream) {
+                Ok(body_range) => {
+                    let body = &stream[body_range.clone()];
+                    match serde_json::from_slice::<serde_json::Value>(body) {
+                        Ok(value) => {
+                            scanned.messages.extend(serde_json::from_value(value).ok());
+                            Ok(body_range.end)
+                        }
+                        Err(_) => Err(Self::classify_body(body)),
+                    }
+                }
+                Err(kind) => Err(kind),
+            };
+            let frame_end = frame_end.unwrap_or_else(|kind| {
+                scanned.malformed.get_or_insert_with(|| MalformedFrame {
+                    kind,
+                    raw: stream.to_vec(),
+                });
+                Self::next_header(&stream[1..]).map_or(stream.len(), |it| it + 1)
+            });
+            stream = &stream[frame_end..];
+        }
+        scanned
+    }
+
+    /// Splits a byte stream at the headers of the LSP payloads without validating the framing.
+    ///
+    /// Unlike [`Self::scan_lsp_stream`], the bytes are kept as they are, including malformed
+    /// payloads. The first part holds the bytes before the first header and may be empty; each
+    /// other part starts with a header. A payload ends where the next header starts after its
+    /// body, which starts at its declared offset if the payload is well-framed, or at the first
+    /// `{` otherwise.
+    #[must_use]
+    pub fn split_at_headers(stream: &[u8]) -> Vec<&[u8]> {
+        let mut parts = Vec::new();
+        let mut part_start = 0;
+        let mut search_start = 0;
+        while let Some(header) = Self::next_header(&stream[search_start..]) {
+            let header_start = search_start + header;
+            parts.push(&stream[part_start..header_start]);
+            part_start = header_start;
+            let payload = &stream[header_start..];
+            let skipped = match Self::frame_body(payload) {
+                Ok(body_range) => body_range.end,
+                Err(_) => memchr::memchr(b'{', payload).unwrap_or(1),
+            };
+            search_start = header_start + skipped.max(1);
+        }
+        parts.push(&stream[part_start..]);
+        parts
+    }
+
+    /// The offset of the first header of an LSP payload in `stream`.
+    ///
+    /// Both headers share a prefix, so the stream is searched once for it.
+    fn next_header(stream: &[u8]) -> Option<usize> {
+        const HEADER_PREFIX: &[u8] = b"Content-";
+        memchr::memmem::find_iter(stream, HEADER_PREFIX).find(|&start| {
+            [CONTENT_LENGTH_HEADER, CONTENT_TYPE_HEADER]
+                .into_iter()
+                .any(|header| {
+                    stream[start..]
+                        .strip_prefix(header.as_bytes())
+                        .is_some_and(|rest| rest.starts_with(HEADER_SEP.as_bytes()))
+                })
+        })
+    }
+
+    fn frame_body(frame: &[u8]) -> Result<Range<usize>, FramingErrorKind> {
+        let header_end = frame
+            .windows(HEADER_BODY_SEP.len())
+            .position(|it| it == HEADER_BODY_SEP.as_bytes())
+            .ok_or(FramingErrorKind::MissingHeaderTerminator)?;
+        let headers = std::str::from_utf8(&frame[..header_end])
+            .map_err(|_| FramingErrorKind::InvalidHeader)?;
+        let mut content_length = None;
+        for line in headers.split("\r\n") {
+            let (key, value) = line
+                .split_once(HEADER_SEP)
+                .ok_or(FramingErrorKind::InvalidHeader)?;
+            if key == CONTENT_LENGTH_HEADER {
+                let value: usize = value.parse().map_err(|_| FramingErrorKind::InvalidHeader)?;
+                content_length = Some(value);
+            }
+        }
+        let content_length = content_length.ok_or(FramingErrorKind::MissingContentLength)?;
+        let body_start = header_end + HEADER_BODY_SEP.len();
+        let body_end = body_start
+            .checked_add(content_length)
+            .filter(|&it| it <= frame.len())
+            .ok_or(FramingErrorKind::LengthMismatch)?;
+        Ok(body_start..body_end)
+    }
+
+    fn classify_body(body: &[u8]) -> FramingErrorKind {
+        let mut values =
+            serde_json::Deserializer::from_slice(body).into_iter::<serde_json::Value>();
+        match values.next() {
+            // The JSON document ends before or after the declared length.
+            Some(Err(err)) if err.is_eof() => FramingErrorKind::LengthMismatch,
+            Some(Ok(_)) => FramingErrorKind::LengthMismatch,
+            _ => FramingErrorKind::NonJsonBody,
+        }
+    }
+}
+
 #[test]
 fn jsonrpc_version_serialize() {
     let jsonrpc = JsonRPC20;
@@ -312,3 +534,94 @@ fn sorbet_payload() {
     let mut payload_slice = PAYLOAD;
     JsonRPCMessage::read_lsp_payload(&mut payload_slice).unwrap();
 }
+
+#[test]
+fn split_lsp_stream_framing_errors() {
+    let message = JsonRPCMessage::notification("exit".into(), serde_json::Value::Null);
+    let payload = message.to_lsp_payload();
+    let (messages, malformed) = JsonRPCMessage::split_lsp_stream(&payload.repeat(2));
+    assert_eq!(messages.len(), 2);
+    assert!(malformed.is_none());
+
+    let cases: [(&[u8], FramingErrorKind); 6] = [
+        (
+            b"Content-Length: 2\r\n{}",
+            FramingErrorKind::MissingHeaderTerminator,
+        ),
+        (
+            b"Content-Length 2\r\n\r\n{}",
+            FramingErrorKind::InvalidHeader,
+        ),
+        (
+            b"Content-Type: json\r\n\r\n{}",
+            FramingErrorKind::MissingContentLength,
+        ),
+        (
+            b"Content-Length: 3\r\n\r\n{}",
+            FramingErrorKind::LengthMismatch,
+        ),
+        (
+            b"Content-Length: 2\r\n\r\n{\"a\": 1}",
+            FramingErrorKind::LengthMismatch,
+        ),
+        (
+            b"Content-Length: 18446744073709551615\r\n\r\n{}",
+            FramingErrorKind::LengthMismatch,
+        ),
+    ];
+    for (stream, kind) in cases {
+        let stream = [payload.as_slice(), stream].concat();
+        let (messages, malformed) = JsonRPCMessage::split_lsp_stream(&stream);
+        assert_eq!(messages.len(), 1);
+        let malformed = malformed.expect("The stream is malformed");
+        assert_eq!(malformed.kind, kind);
+        assert_eq!(malformed.raw, &stream[payload.len()..]);
+    }
+
+    let (_, malformed) = JsonRPCMessage::split_lsp_stream(b"Content-Length: 2\r\n\r\nhi");
+    assert_eq!(malformed.unwrap().kind, FramingErrorKind::NonJsonBody);
+}
+
+#[test]
+fn scan_lsp_stream_skips_noise() {
+    let message = JsonRPCMessage::notification("exit".into(), serde_json::Value::Null);
+    let payload = message.to_lsp_payload();
+    let stream = [
+        b"Starting server...\n".as_slice(),
+        &payload,
+        b"Indexed 3 files\n",
+        b"Content-Length: 2\r\n\r\nhi",
+        &payload,
+    ]
+    .concat();
+    let scanned = JsonRPCMessage::scan_lsp_stream(&stream);
+    assert_eq!(scanned.messages.len(), 2);
+    assert_eq!(
+        scanned.noise,
+        [
+            b"Starting server...\n".to_vec(),
+            b"Indexed 3 files\n".to_vec()
+        ]
+    );
+    let malformed = scanned
+        .malformed
+        .expect("The stream has a malformed payload");
+    assert_eq!(malformed.kind, FramingErrorKind::NonJsonBody);
+
+    let scanned = JsonRPCMessage::scan_lsp_stream(&payload.repeat(2));
+    assert_eq!(scanned.messages.len(), 2);
+    assert!(scanned.noise.is_empty() && scanned.malformed.is_none());
+}
+
+#[test]
+fn next_header_skips_other_content_prefixes() {
+    assert_eq!(
+        JsonRPCMessage::next_header(b"Content-Encoding: gzip\nContent-Type: json"),
+        Some(23)
+    );
+    assert_eq!(
+        JsonRPCMessage::next_header(b"Content-Typed Content-Length: 2"),
+        Some(14)
+    );
+    assert_eq!(JsonRPCMessage::next_header(b"Content-Length 2"), None);
+}
@@ -1,11 +1,20 @@
 pub(crate) mod capabilities;
 pub mod message;
 
-use generation::{LspParamsGenerator, numeric::TabSizeGen};
+use std::collections::HashMap;
+
+use generation::{LspParamsGenerator, doc::UnopenedDocumentGen, numeric::TabSizeGen};
 pub use message::LspMessage;
 use message::LspResponse;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    lsp_input::{
+        configuration::ConfigurationMatrix, experimental_capabilities::ExperimentalCapabilitiesGen,
+    },
+    text_document::{generation::RuleWeighting, mutations::node_filters::NodeWeighting},
+};
+
 pub mod code_context;
 pub mod compositions;
 pub mod generation;
@@ -62,6 +71,49 @@ pub struct GeneratorsConfig {
     pub invalid_input: InvalidInputConfig,
     pub tab_size: TabSizeGen,
     pub awareness: AwarenessConfig,
+    #[serde(default)]
+    pub unopened_documents: Option<UnopenedDocumentGen>,
+    /// How the nodes replaced by the mutations of text documents are chosen.
+    #[serde(default)]
+    pub node_weighting: NodeWeighting,
+    /// Weights of the mutations of text documents by name, overriding
+    /// [`DEFAULT_MUTATION_WEIGHTS`](crate::text_document::DEFAULT_MUTATION_WEIGHTS).
+    #[serde(default)]
+    pub mutation_weights: HashMap<String, usize>,
+    /// How the derivations of symbols are chosen when generating code from the grammar.
+    #[serde(default)]
+    pub rule_weighting: RuleWeighting,
+    /// Whether the identifiers of generated documents are rewritten to the identifiers declared
+    /// in them, see [`resolve_identifiers`](crate::text_document::scopes::resolve_identifiers).
+    #[serde(default = "resolve_identifiers_by_default")]
+    pub resolve_identifiers: bool,
+    /// The probability that an input advertises the minimal client capabilities,
+    /// see [`CapabilityProfile`](crate::lsp_input::messages::CapabilityProfile).
+    /// Zero keeps the full capabilities, and one uses the minimal ones throughout.
+    #[serde(default)]
+    pub minimal_capabilities: f64,
+    /// The generation of `capabilities.experimental` in the `initialize` request,
+    /// disabled when unset.
+    #[serde(default)]
+    pub experimental_capabilities: Option<ExperimentalCapabilitiesGen>,
+    /// The settings toggled across inputs, left to the target's defaults when unset.
+    #[serde(default)]
+    pub configuration_matrix: Option<ConfigurationMatrix>,
+    /// The probability of appending a request for a method the server declares unsupported,
+    /// see [`SupportedMethods`](crate::lsp_input::server_response::supported_methods::SupportedMethods).
+    /// Requests are appended regardless of the capabilities of the server when unset.
+    #[serde(default)]
+    pub unsupported_methods: Option<f64>,
+    /// The probability of building a pathological structure (e.g., deep nesting) in a document,
+    /// see [`stress_patterns`](crate::text_document::mutations::stress_patterns).
+    /// Zero disables the structures.
+    #[serde(default)]
+    pub stress_patterns: f64,
+}
+
+/// Identifiers are resolved unless disabled, as in [`GeneratorsConfig::full`].
+const fn resolve_identifiers_by_default() -> bool {
+    true
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -69,6 +121,23 @@ pub struct InvalidInputConfig {
     pub ranges: bool,
     pub positions: bool,
     pub code_frequency: f64,
+    /// The probability of breaking the syntax around a targeted node, see
+    /// [`InjectSyntaxError`](crate::text_document::mutations::syntax_errors::InjectSyntaxError).
+    #[serde(default)]
+    pub syntax_error_frequency: f64,
+    /// The fraction of the messages whose positions are sent in a unit other than
+    /// the negotiated position encoding. Zero disables the mismatches.
+    #[serde(default)]
+    pub position_encoding_mismatch: f64,
+    /// The probability of opening a source file as a language other than that of its content,
+    /// see [`MismatchLanguage`](crate::lsp_input::messages::MismatchLanguage).
+    #[serde(default)]
+    pub language_mismatch: f64,
+    /// The probability that a scheduled
+    /// [`InjectTransportFault`](crate::lsp_input::messages::InjectTransportFault)
+    /// ends the session early. Zero disables the faults.
+    #[serde(default)]
+    pub transport_fault: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -79,26 +148,49 @@ pub struct AwarenessConfig {
 }
 
 impl GeneratorsConfig {
-    fn defaults() -> (InvalidInputConfig, TabSizeGen) {
+    fn defaults() -> (InvalidInputConfig, TabSizeGen, Option<UnopenedDocumentGen>) {
         (
             InvalidInputConfig {
                 ranges: true,
                 positions: true,
                 code_frequency: 0.1,
+                syntax_error_frequency: 0.05,
+                position_encoding_mismatch: 0.0,
+                language_mismatch: 0.0,
+                transport_fault: 0.0,
             },
             TabSizeGen {
                 candidates: vec![0, 1, 2, 4, 8],
                 rand_prob: 0.2,
             },
+            Some(UnopenedDocumentGen {
+                workspace_files: true,
+                external_uris: vec![
+                    "file:///etc/hosts".to_owned(),
+                    "file:///etc/os-release".to_owned(),
+                    "file:///usr/include/stdio.h".to_owned(),
+                ],
+                weight: 1,
+            }),
         )
     }
 
     #[must_use]
     pub fn full() -> Self {
-        let (invalid_input, tab_size) = Self::defaults();
+        let (invalid_input, tab_size, unopened_documents) = Self::defaults();
         Self {
             invalid_input,
             tab_size,
+            unopened_documents,
+            node_weighting: NodeWeighting::default(),
+            mutation_weights: HashMap::new(),
+            rule_weighting: RuleWeighting::Uniform,
+            resolve_identifiers: true,
+            minimal_capabilities: 0.0,
+            experimental_capabilities: None,
+            configuration_matrix: None,
+            unsupported_methods: Some(0.1),
+            stress_patterns: 0.0,
             awareness: AwarenessConfig {
                 grammar_ops: true,
                 context: true,
@@ -109,10 +201,20 @@ impl GeneratorsConfig {
 
     #[must_use]
     pub fn no_server_feedback() -> Self {
-        let (invalid_input, tab_size) = Self::defaults();
+        let (invalid_input, tab_size, unopened_documents) = Self::defaults();
         Self {
             invalid_input,
             tab_size,
+            unopened_documents,
+            node_weighting: NodeWeighting::default(),
+            mutation_weights: HashMap::new(),
+            rule_weighting: RuleWeighting::Uniform,
+            resolve_identifiers: true,
+            minimal_capabilities: 0.0,
+            experimental_capabilities: None,
+            configuration_matrix: None,
+            unsupported_methods: None,
+            stress_patterns: 0.0,
             awareness: AwarenessConfig {
                 grammar_ops: false,
                 context: true,
@@ -123,10 +225,20 @@ impl GeneratorsConfig {
 
     #[must_use]
     pub fn no_context_awareness() -> Self {
-        let (invalid_input, tab_size) = Self::defaults();
+        let (invalid_input, tab_size, unopened_documents) = Self::defaults();
         Self {
             invalid_input,
             tab_size,
+            unopened_documents,
+            node_weighting: NodeWeighting::default(),
+            mutation_weights: HashMap::new(),
+            rule_weighting: RuleWeighting::Uniform,
+            resolve_identifiers: false,
+            minimal_capabilities: 0.0,
+            experimental_capabilities: None,
+            configuration_matrix: None,
+            unsupported_methods: Some(0.1),
+            stress_patterns: 0.0,
             awareness: AwarenessConfig {
                 grammar_ops: true,
                 context: false,
@@ -155,6 +267,16 @@ impl GeneratorsConfig {
         self.awareness.context && self.invalid_input.positions
     }
 
+    /// The generator of requests targeting documents that were never opened, if enabled.
+    #[must_use]
+    pub const fn unopened_documents(&self) -> Option<&UnopenedDocumentGen> {
+        if self.awareness.context {
+            self.unopened_documents.as_ref()
+        } else {
+            None
+        }
+    }
+
     #[must_use]
     pub const fn allow_invalid_ranges(&self) -> bool {
         self.awareness.context && self.invalid_input.ranges
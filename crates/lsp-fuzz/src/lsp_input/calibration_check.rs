@@ -0,0 +1,330 @@
+//! A consistency check of the calibration of message positions after document edits.
+//!
+//! Mutations of text documents shift the positions of the messages that follow the edit
+//! by the line/column arithmetic of [`InputEdit`] (see [`LspMessageSequence::calibrate`]).
+//! The check re-derives the expected positions from byte offsets instead, by locating each
+//! position in the old content, shifting its offset by the edited bytes, and converting it back
+//! to a line and a column in the new content.
+//!
+//! [`LspMessageSequence::calibrate`]: super::messages::LspMessageSequence::calibrate
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use libafl::{
+    HasMetadata,
+    corpus::CorpusId,
+    mutators::{MutationResult, Mutator},
+};
+use libafl_bolts::{Named, SerdeAny};
+use lsp_types::Position;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use tree_sitter::InputEdit;
+
+use super::{LspInput, uri};
+use crate::{lsp::code_context::CodeContextRef, utils::AflContext};
+
+thread_local! {
+    /// The byte ranges of the edits applied while a check is in progress.
+    static EDIT_LOG: RefCell<Option<Vec<ByteEdit>>> = const { RefCell::new(None) };
+}
+
+/// Records an edit of a text document for the check in progress on this thread, if any.
+pub(crate) fn record_edit(edit: &InputEdit) {
+    EDIT_LOG.with_borrow_mut(|log| {
+        if let Some(log) = log {
+            log.push(ByteEdit {
+                start: edit.start_byte,
+                old_end: edit.old_end_byte,
+                new_end: edit.new_end_byte,
+            });
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ByteEdit {
+    start: usize,
+    old_end: usize,
+    new_end: usize,
+}
+
+impl ByteEdit {
+    /// The offset of the byte at `offset` after the edit,
+    /// or `None` if the byte was inside the replaced range.
+    const fn map_offset(self, offset: usize) -> Option<usize> {
+        if offset >= self.old_end {
+            Some(offset - self.old_end + self.new_end)
+        } else if offset <= self.start {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+}
+
+/// A message position whose calibrated value differs from the one derived from byte offsets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationDivergence {
+    /// The URI of the edited document.
+    pub uri: String,
+    /// The index of the message in the sequence.
+    pub message: usize,
+    /// The method of the message.
+    pub method: String,
+    /// The position before the edit.
+    pub original: Position,
+    /// The position after calibration.
+    pub calibrated: Position,
+    /// The position derived from the byte offsets of the edit.
+    pub expected: Position,
+}
+
+/// The number of positions checked and the number of divergences found.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, SerdeAny)]
+pub struct CalibrationCheckStats {
+    pub checked: u64,
+    pub divergences: u64,
+}
+
+/// Wraps the mutator of text documents to check the calibrated positions after each mutation.
+///
+/// The check clones the input before each mutation, so it is meant for debug builds and QA
+/// campaigns. When disabled, the wrapper only forwards to the inner mutator.
+#[derive(Debug)]
+pub struct CalibrationCheck<M> {
+    inner: M,
+    enabled: bool,
+    divergence_log: Option<BufWriter<File>>,
+}
+
+impl<M> CalibrationCheck<M> {
+    pub const fn new(inner: M, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            divergence_log: None,
+        }
+    }
+
+    /// Appends the divergences found to `path` as JSON lines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn with_divergence_log(self, path: &Path) -> Result<Self, libafl::Error> {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .afl_context("Opening the calibration divergence log")?;
+        Ok(Self {
+            divergence_log: Some(BufWriter::new(file)),
+            ..self
+        })
+    }
+
+    fn report(&mut self, divergence: &CalibrationDivergence) -> Result<(), libafl::Error> {
+        warn!(
+            uri = %divergence.uri,
+            message = divergence.message,
+            method = %divergence.method,
+            original = ?divergence.original,
+            calibrated = ?divergence.calibrated,
+            expected = ?divergence.expected,
+            "Calibrated position diverges from the edited bytes"
+        );
+        if let Some(log) = &mut self.divergence_log {
+            serde_json::to_writer(&mut *log, divergence)
+                .afl_context("Serializing calibration divergence")?;
+            writeln!(log).afl_context("Writing calibration divergence")?;
+            log.flush()
+                .afl_context("Flushing calibration divergence log")?;
+        }
+        Ok(())
+    }
+}
+
+impl<M> Named for CalibrationCheck<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CalibrationCheck");
+        &NAME
+    }
+}
+
+impl<M, State> Mutator<LspInput, State> for CalibrationCheck<M>
+where
+    M: Mutator<LspInput, State>,
+    State: HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        if !self.enabled {
+            return self.inner.mutate(state, input);
+        }
+        let original = input.clone();
+        EDIT_LOG.with_borrow_mut(|log| *log = Some(Vec::new()));
+        let result = self.inner.mutate(state, input);
+        let edits = EDIT_LOG.with_borrow_mut(Option::take).unwrap_or_default();
+        if matches!(result, Ok(MutationResult::Mutated)) {
+            let (checked, divergences) = check_calibration(&original, input, &edits);
+            let stats = state.metadata_or_insert_with(CalibrationCheckStats::default);
+            stats.checked += checked;
+            stats.divergences += divergences.len() as u64;
+            for divergence in &divergences {
+                self.report(divergence)?;
+            }
+        }
+        result
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut State,
+        new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+/// Compares the positions of the messages of `mutated` with the positions of `original`
+/// moved by `edits`, returning the number of positions compared and the divergences.
+///
+/// The edits cannot be attributed to documents, so only mutations that changed a single
+/// document are checked. Positions that are not within the content of the document,
+/// or that were inside a replaced range, are skipped.
+fn check_calibration(
+    original: &LspInput,
+    mutated: &LspInput,
+    edits: &[ByteEdit],
+) -> (u64, Vec<CalibrationDivergence>) {
+    let mut changed = mutated.workspace.iter_files().filter_map(|(path, entry)| {
+        let new_doc = entry.as_source_file()?;
+        let doc_uri = uri::virtual_uri_for_path(&path)?;
+        let old_doc = original.get_text_document(&doc_uri)?;
+        (old_doc != new_doc).then_some((doc_uri, old_doc, new_doc))
+    });
+    let (Some((doc_uri, old_doc, new_doc)), None) = (changed.next(), changed.next()) else {
+        return (0, Vec::new());
+    };
+    if edits.is_empty() || original.messages.len() != mutated.messages.len() {
+        return (0, Vec::new());
+    }
+
+    let (old_content, new_content) = (old_doc.content(), new_doc.content());
+    let mut checked = 0;
+    let mut divergences = Vec::new();
+    let messages = original.messages.iter().zip(mutated.messages.iter());
+    for (idx, (old_msg, new_msg)) in messages.enumerate() {
+        if old_msg.document().is_none_or(|it| it.uri != doc_uri) {
+            continue;
+        }
+        let positions = message_positions(old_msg)
+            .into_iter()
+            .zip(message_positions(new_msg));
+        for (original_pos, calibrated) in positions {
+            let Some(expected) = offset_of(&old_content, original_pos)
+                .and_then(|offset| {
+                    edits
+                        .iter()
+                        .try_fold(offset, |offset, edit| edit.map_offset(offset))
+                })
+                .and_then(|offset| position_of(&new_content, offset))
+            else {
+                continue;
+            };
+            checked += 1;
+            if calibrated != expected {
+                divergences.push(CalibrationDivergence {
+                    uri: doc_uri.as_str().to_owned(),
+                    message: idx,
+                    method: old_msg.method().to_owned(),
+                    original: original_pos,
+                    calibrated,
+                    expected,
+                });
+            }
+        }
+    }
+    (checked, divergences)
+}
+
+fn message_positions(message: &impl CodeContextRef) -> Vec<Position> {
+    if let Some(pos) = message.position() {
+        vec![*pos]
+    } else if let Some(range) = message.range() {
+        vec![range.start, range.end]
+    } else {
+        Vec::new()
+    }
+}
+
+/// The byte offset of `pos` in `content`, counting columns in bytes,
+/// or `None` if `pos` is past the end of its line or of the content.
+fn offset_of(content: &[u8], pos: Position) -> Option<usize> {
+    let line_idx = usize::try_from(pos.line).ok()?;
+    let column = usize::try_from(pos.character).ok()?;
+    let mut line_start = 0;
+    for (idx, line) in content.split(|&it| it == b'\n').enumerate() {
+        if idx == line_idx {
+            return (column <= line.len()).then_some(line_start + column);
+        }
+        line_start += line.len() + 1;
+    }
+    None
+}
+
+/// The position of the byte at `offset` in `content`, counting columns in bytes.
+fn position_of(content: &[u8], offset: usize) -> Option<Position> {
+    let prefix = content.get(..offset)?;
+    let line = prefix.iter().filter(|&&it| it == b'\n').count();
+    let line_start = prefix
+        .iter()
+        .rposition(|&it| it == b'\n')
+        .map_or(0, |it| it + 1);
+    Some(Position {
+        line: u32::try_from(line).ok()?,
+        character: u32::try_from(offset - line_start).ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+
+    use super::{ByteEdit, offset_of, position_of};
+
+    #[test]
+    fn offsets_round_trip() {
+        let content = b"fn main() {\n    foo();\n}\n";
+        let pos = Position::new(1, 4);
+        let offset = offset_of(content, pos).unwrap();
+        assert_eq!(&content[offset..offset + 3], b"foo");
+        assert_eq!(position_of(content, offset), Some(pos));
+        assert_eq!(offset_of(content, Position::new(1, 11)), None);
+        assert_eq!(offset_of(content, Position::new(4, 0)), None);
+    }
+
+    #[test]
+    fn offsets_inside_edits_are_skipped() {
+        let edit = ByteEdit {
+            start: 4,
+            old_end: 8,
+            new_end: 10,
+        };
+        assert_eq!(edit.map_offset(2), Some(2));
+        assert_eq!(edit.map_offset(4), Some(4));
+        assert_eq!(edit.map_offset(6), None);
+        assert_eq!(edit.map_offset(8), Some(10));
+    }
+}
@@ -0,0 +1,511 @@
+//! Settings toggled across executions.
+//!
+//! Servers gate whole subsystems behind settings, e.g., rust-analyzer expands procedural macros
+//! only with `procMacro.enable`, and Deno leaves documents alone unless `enable` is set.
+//! A [`ConfigurationMatrix`] lists such settings of a target with the values to try.
+//! Each input carries a [`Configuration`] drawn from the matrix, sent as `initializationOptions`,
+//! in a `workspace/didChangeConfiguration` notification, or both.
+//! [`ConfigurationCoverageFeedback`] keeps the inputs reaching edges not yet covered with one of
+//! the values of their settings, so that coverage found with one value does not hide the same
+//! edges with the others.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet, hash_map::Entry},
+    marker::PhantomData,
+};
+
+use libafl::{
+    HasMetadata,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    mutators::{MutationResult, Mutator},
+    observers::MapObserver,
+    state::HasRand,
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    rands::Rand,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use lsp_types::DidChangeConfigurationParams;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use super::LspInput;
+use crate::{lsp, utils::AflContext};
+
+/// The settings of a target and the values to try for each of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigurationMatrix {
+    /// The section of the settings, e.g., `rust-analyzer`, under which
+    /// `workspace/didChangeConfiguration` sends them.
+    #[serde(default)]
+    pub section: Option<String>,
+    /// The values of each setting by its dotted path, e.g., `procMacro.enable`.
+    pub settings: BTreeMap<String, Vec<Value>>,
+}
+
+impl ConfigurationMatrix {
+    /// The names of the matrices returned by [`Self::builtin`].
+    pub const BUILTIN: [&str; 3] = ["rust-analyzer", "deno", "yaml-language-server"];
+
+    /// The matrix of a well-known server by its name in [`Self::BUILTIN`].
+    #[must_use]
+    pub fn builtin(name: &str) -> Option<Self> {
+        let toggles = |paths: &[&str]| {
+            paths
+                .iter()
+                .map(|path| ((*path).to_owned(), vec![json!(true), json!(false)]))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let (section, mut settings) = match name {
+            "rust-analyzer" => (
+                "rust-analyzer",
+                toggles(&[
+                    "procMacro.enable",
+                    "cargo.buildScripts.enable",
+                    "checkOnSave",
+                    "diagnostics.experimental.enable",
+                    "lens.enable",
+                    "inlayHints.closureReturnTypeHints.enable",
+                    "completion.autoimport.enable",
+                    "semanticHighlighting.strings.enable",
+                ]),
+            ),
+            "deno" => (
+                "deno",
+                toggles(&[
+                    "enable",
+                    "unstable",
+                    "lint",
+                    "codeLens.implementations",
+                    "codeLens.references",
+                    "codeLens.test",
+                ]),
+            ),
+            "yaml-language-server" => (
+                "yaml",
+                toggles(&[
+                    "validate",
+                    "hover",
+                    "completion",
+                    "format.enable",
+                    "keyOrdering",
+                ]),
+            ),
+            _ => return None,
+        };
+        if name == "rust-analyzer" {
+            settings.insert("cargo.features".to_owned(), vec![json!([]), json!("all")]);
+        } else if name == "yaml-language-server" {
+            // The schema store is fetched over the network when enabled.
+            settings.insert("schemaStore.enable".to_owned(), vec![json!(false)]);
+        }
+        Some(Self {
+            section: Some(section.to_owned()),
+            settings,
+        })
+    }
+
+    /// Picks a value for each setting and how the settings are sent.
+    pub fn draw<R: Rand>(&self, rand: &mut R) -> Configuration {
+        let mut settings = Value::Object(Map::new());
+        for (path, values) in &self.settings {
+            if let Some(value) = rand.choose(values) {
+                insert_dotted(&mut settings, path, value.clone());
+            }
+        }
+        let delivery = rand.choose(ConfigurationDelivery::ALL).unwrap_or_default();
+        Configuration {
+            section: self.section.clone(),
+            settings,
+            delivery,
+        }
+    }
+}
+
+/// How the settings of a [`Configuration`] are sent to the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ConfigurationDelivery {
+    /// As the `initializationOptions` of the `initialize` request.
+    #[default]
+    InitializationOptions,
+    /// In a `workspace/didChangeConfiguration` notification right after `initialized`.
+    DidChangeConfiguration,
+    Both,
+}
+
+impl ConfigurationDelivery {
+    const ALL: [Self; 3] = [
+        Self::InitializationOptions,
+        Self::DidChangeConfiguration,
+        Self::Both,
+    ];
+}
+
+/// The settings sent to the target in an execution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Configuration {
+    pub section: Option<String>,
+    /// The settings, nested by the components of their dotted paths.
+    pub settings: Value,
+    pub delivery: ConfigurationDelivery,
+}
+
+impl Configuration {
+    /// The `initializationOptions` of the `initialize` request, if sent there.
+    #[must_use]
+    pub fn initialization_options(&self) -> Option<Value> {
+        match self.delivery {
+            ConfigurationDelivery::InitializationOptions | ConfigurationDelivery::Both => {
+                Some(self.settings.clone())
+            }
+            ConfigurationDelivery::DidChangeConfiguration => None,
+        }
+    }
+
+    /// Whether the settings are sent in a `workspace/didChangeConfiguration` notification.
+    #[must_use]
+    pub const fn notifies(&self) -> bool {
+        matches!(
+            self.delivery,
+            ConfigurationDelivery::DidChangeConfiguration | ConfigurationDelivery::Both
+        )
+    }
+
+    /// The `workspace/didChangeConfiguration` notification, if sent there.
+    #[must_use]
+    pub fn did_change_configuration(&self) -> Option<lsp::LspMessage> {
+        match self.delivery {
+            ConfigurationDelivery::DidChangeConfiguration | ConfigurationDelivery::Both => {
+                let settings = match &self.section {
+                    Some(section) => json!({ section: self.settings }),
+                    None => self.settings.clone(),
+                };
+                Some(lsp::LspMessage::DidChangeConfiguration(
+                    DidChangeConfigurationParams { settings },
+                ))
+            }
+            ConfigurationDelivery::InitializationOptions => None,
+        }
+    }
+
+    /// Identifies the value of each setting in [`ConfigurationCoverage`], as `path=value` with
+    /// the dotted path of the setting.
+    ///
+    /// How the settings are sent is left out, since it hardly changes what the target covers.
+    #[must_use]
+    pub fn setting_keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        collect_setting_keys(&self.settings, "", &mut keys);
+        keys
+    }
+}
+
+fn collect_setting_keys(settings: &Value, path: &str, keys: &mut Vec<String>) {
+    match settings {
+        Value::Object(map) => {
+            for (component, value) in map {
+                let path = if path.is_empty() {
+                    component.clone()
+                } else {
+                    format!("{path}.{component}")
+                };
+                collect_setting_keys(value, &path, keys);
+            }
+        }
+        value => keys.push(format!("{path}={value}")),
+    }
+}
+
+/// Sets `value` at the dotted `path` of `settings`, creating the enclosing objects.
+fn insert_dotted(settings: &mut Value, path: &str, value: Value) {
+    let mut current = settings;
+    let mut components = path.split('.').peekable();
+    while let Some(component) = components.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let Value::Object(map) = current else {
+            unreachable!("The value was just replaced with an object");
+        };
+        if components.peek().is_none() {
+            map.insert(component.to_owned(), value);
+            return;
+        }
+        current = map
+            .entry(component.to_owned())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Picks another value for one setting of the configuration of the input,
+/// or draws a configuration for inputs without one.
+#[derive(Debug)]
+pub struct RedrawConfiguration<State> {
+    matrix: Option<ConfigurationMatrix>,
+    _state: PhantomData<State>,
+}
+
+impl<State> RedrawConfiguration<State> {
+    /// The mutation is skipped unless `matrix` is set.
+    #[must_use]
+    pub const fn new(matrix: Option<ConfigurationMatrix>) -> Self {
+        Self {
+            matrix,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<State> Named for RedrawConfiguration<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RedrawConfiguration");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for RedrawConfiguration<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some(matrix) = &self.matrix else {
+            return Ok(MutationResult::Skipped);
+        };
+        let rand = state.rand_mut();
+        let mut configuration = match input.messages.configuration() {
+            Some(current) if current.section == matrix.section => current.clone(),
+            _ => matrix.draw(rand),
+        };
+        if rand.coinflip(0.25) {
+            configuration.delivery = rand.choose(ConfigurationDelivery::ALL).unwrap_or_default();
+        } else if let Some((path, values)) = rand.choose(&matrix.settings)
+            && let Some(value) = rand.choose(values)
+        {
+            insert_dotted(&mut configuration.settings, path, value.clone());
+        }
+        if input.messages.configuration() == Some(&configuration) {
+            return Ok(MutationResult::Skipped);
+        }
+        input.messages.set_configuration(Some(configuration));
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// The executions and the edges covered with each value of a setting,
+/// by [`Configuration::setting_keys`].
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct ConfigurationCoverage {
+    pub configurations: HashMap<String, ConfigurationStats>,
+}
+
+impl ConfigurationCoverage {
+    /// The largest number of setting values tracked.
+    ///
+    /// The values drawn from a [`ConfigurationMatrix`] are far fewer, but those of imported
+    /// inputs are arbitrary. Values beyond the limit are not tracked.
+    pub const MAX_TRACKED_VALUES: usize = 1024;
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigurationStats {
+    pub executions: u64,
+    /// The indices of the coverage map hit with the setting value.
+    pub edges: HashSet<usize>,
+}
+
+/// Considers inputs interesting if they cover edges not yet covered with one of the values of
+/// their settings, recording the coverage of each value in [`ConfigurationCoverage`].
+///
+/// Inputs without a configuration are never interesting.
+#[derive(Debug)]
+pub struct ConfigurationCoverageFeedback<C, O> {
+    observer_handle: Handle<C>,
+    _observer: PhantomData<O>,
+}
+
+impl<C, O> ConfigurationCoverageFeedback<C, O>
+where
+    C: Named,
+{
+    #[must_use]
+    pub fn new(observer: &C) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            _observer: PhantomData,
+        }
+    }
+}
+
+impl<C, O> Named for ConfigurationCoverageFeedback<C, O> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ConfigurationCoverageFeedback");
+        &NAME
+    }
+}
+
+impl<C, O, State> StateInitializer<State> for ConfigurationCoverageFeedback<C, O> {}
+
+impl<C, O, EM, Observers, State> Feedback<EM, LspInput, Observers, State>
+    for ConfigurationCoverageFeedback<C, O>
+where
+    C: AsRef<O>,
+    O: MapObserver,
+    Observers: MatchNameRef,
+    State: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        input: &LspInput,
+        observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        let Some(configuration) = input.messages.configuration() else {
+            return Ok(false);
+        };
+        let setting_keys = configuration.setting_keys();
+        if setting_keys.is_empty() {
+            return Ok(false);
+        }
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("Coverage observer not attached")?
+            .as_ref();
+        let initial = observer.initial();
+        let covered: Vec<_> = (0..observer.usable_count())
+            .filter(|&idx| observer.get(idx) != initial)
+            .collect();
+        let coverage = state.metadata_or_insert_with(ConfigurationCoverage::default);
+        let mut novel = false;
+        for key in setting_keys {
+            let tracked = coverage.configurations.len();
+            let stats = match coverage.configurations.entry(key) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) if tracked < ConfigurationCoverage::MAX_TRACKED_VALUES => {
+                    entry.insert(ConfigurationStats::default())
+                }
+                Entry::Vacant(_) => continue,
+            };
+            stats.executions += 1;
+            for &idx in &covered {
+                novel |= stats.edges.insert(idx);
+            }
+        }
+        Ok(novel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+    use crate::{lsp::json_rpc::HeaderLineEnding, lsp_input::messages::FramingVariation};
+
+    #[test]
+    fn test_configuration_delivery() {
+        let matrix = ConfigurationMatrix::builtin("rust-analyzer").unwrap();
+        let mut rand = StdRand::with_seed(0);
+        let mut configuration = matrix.draw(&mut rand);
+        assert!(configuration.settings["procMacro"]["enable"].is_boolean());
+
+        configuration.delivery = ConfigurationDelivery::DidChangeConfiguration;
+        let mut input = LspInput::default();
+        input
+            .messages
+            .set_configuration(Some(configuration.clone()));
+        let messages: Vec<_> = input.message_sequence().collect();
+        let lsp::LspMessage::Initialize(params) = &messages[0] else {
+            panic!("The session starts with initialize");
+        };
+        assert!(params.initialization_options.is_none());
+        let lsp::LspMessage::DidChangeConfiguration(params) = &messages[2] else {
+            panic!("The settings are sent right after initialized");
+        };
+        assert_eq!(params.settings["rust-analyzer"], configuration.settings);
+
+        configuration.delivery = ConfigurationDelivery::InitializationOptions;
+        input
+            .messages
+            .set_configuration(Some(configuration.clone()));
+        let messages: Vec<_> = input.message_sequence().collect();
+        let lsp::LspMessage::Initialize(params) = &messages[0] else {
+            panic!("The session starts with initialize");
+        };
+        assert_eq!(params.initialization_options, Some(configuration.settings));
+        assert!(
+            !messages
+                .iter()
+                .any(|it| matches!(it, lsp::LspMessage::DidChangeConfiguration(_)))
+        );
+    }
+
+    #[test]
+    fn setting_keys_leave_out_delivery() {
+        let mut configuration = Configuration {
+            section: None,
+            settings: json!({ "lint": true, "codeLens": { "test": false } }),
+            delivery: ConfigurationDelivery::InitializationOptions,
+        };
+        let keys = configuration.setting_keys();
+        assert_eq!(keys, ["lint=true", "codeLens.test=false"]);
+        configuration.delivery = ConfigurationDelivery::Both;
+        assert_eq!(configuration.setting_keys(), keys);
+    }
+
+    #[test]
+    fn configuration_notification_shifts_framing() {
+        let mut configuration = Configuration {
+            section: None,
+            settings: json!({ "lint": true }),
+            delivery: ConfigurationDelivery::InitializationOptions,
+        };
+        let mut input = LspInput::default();
+        input
+            .messages
+            .set_configuration(Some(configuration.clone()));
+        let framing = [1, 2].map(|payload| FramingVariation {
+            payload,
+            line_ending: HeaderLineEnding::Lf,
+        });
+        input.messages.framing_mut().extend(framing);
+
+        configuration.delivery = ConfigurationDelivery::DidChangeConfiguration;
+        input
+            .messages
+            .set_configuration(Some(configuration.clone()));
+        let payloads: Vec<_> = input
+            .messages
+            .framing()
+            .iter()
+            .map(|it| it.payload)
+            .collect();
+        assert_eq!(payloads, [1, 3]);
+
+        input.messages.set_configuration(None);
+        let payloads: Vec<_> = input
+            .messages
+            .framing()
+            .iter()
+            .map(|it| it.payload)
+            .collect();
+        assert_eq!(payloads, [1, 2]);
+    }
+}
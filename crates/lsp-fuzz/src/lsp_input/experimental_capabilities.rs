@@ -0,0 +1,290 @@
+//! Arbitrary JSON under `capabilities.experimental` of the `initialize` request.
+//!
+//! Servers read their experimental and vendor-specific client capabilities with hand-rolled
+//! code rather than the typed capabilities of the protocol, so this section is a parser of its
+//! own. [`InjectExperimentalCapabilities`] fills it with the capabilities known servers look for,
+//! copies of them with values of unexpected types, and random structures.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+use libafl::{
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+};
+use libafl_bolts::{Named, rands::Rand};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use super::LspInput;
+
+/// The depth of the random structures.
+const MAX_DEPTH: usize = 4;
+
+/// The number of elements of the random arrays and objects.
+const MAX_ELEMENTS: usize = 4;
+
+/// The experimental client capabilities read by well-known servers.
+#[must_use]
+pub fn builtin_profiles() -> Vec<Value> {
+    vec![
+        // rust-analyzer
+        json!({
+            "snippetTextEdit": true,
+            "codeActionGroup": true,
+            "hoverActions": true,
+            "serverStatusNotification": true,
+            "colorDiagnosticOutput": true,
+            "openServerLogs": true,
+            "localDocs": true,
+            "testExplorer": true,
+            "commands": {
+                "commands": [
+                    "rust-analyzer.runSingle",
+                    "rust-analyzer.debugSingle",
+                    "rust-analyzer.showReferences",
+                    "rust-analyzer.gotoLocation",
+                    "editor.action.triggerParameterHints",
+                ],
+            },
+        }),
+        // Deno
+        json!({ "testingApi": true }),
+    ]
+}
+
+/// The configuration of [`InjectExperimentalCapabilities`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentalCapabilitiesGen {
+    /// The experimental capabilities of the targets, e.g., [`builtin_profiles`]
+    /// or the profile of a single target.
+    pub profiles: Vec<Value>,
+    /// The probability that the mutation is applied when scheduled.
+    pub probability: f64,
+}
+
+/// Replaces `capabilities.experimental` with a profile, a profile with a random value
+/// at a random position, or a random structure using the keys of the profiles,
+/// or removes it.
+#[derive(Debug)]
+pub struct InjectExperimentalCapabilities<State> {
+    config: Option<ExperimentalCapabilitiesGen>,
+    keys: Vec<String>,
+    _state: PhantomData<State>,
+}
+
+impl<State> InjectExperimentalCapabilities<State> {
+    /// The mutation is skipped unless `config` is set.
+    #[must_use]
+    pub fn new(config: Option<ExperimentalCapabilitiesGen>) -> Self {
+        let mut keys = Vec::new();
+        for profile in config.iter().flat_map(|it| &it.profiles) {
+            collect_keys(profile, &mut keys);
+        }
+        keys.sort_unstable();
+        keys.dedup();
+        Self {
+            config,
+            keys,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<State> Named for InjectExperimentalCapabilities<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("InjectExperimentalCapabilities");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for InjectExperimentalCapabilities<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some(config) = &self.config else {
+            return Ok(MutationResult::Skipped);
+        };
+        let rand = state.rand_mut();
+        if !rand.coinflip(config.probability) {
+            return Ok(MutationResult::Skipped);
+        }
+        let profile = rand.choose(&config.profiles).cloned();
+        let experimental = match rand.between(0, 3) {
+            0 => profile,
+            1 => profile.map(|mut it| {
+                perturb(rand, &mut it, &self.keys);
+                it
+            }),
+            2 => Some(random_object(rand, &self.keys, MAX_DEPTH)),
+            _ => None,
+        };
+        if input.messages.experimental_capabilities() == experimental.as_ref() {
+            return Ok(MutationResult::Skipped);
+        }
+        input.messages.set_experimental_capabilities(experimental);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+fn collect_keys(value: &Value, keys: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                keys.push(key.clone());
+                collect_keys(value, keys);
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|it| collect_keys(it, keys)),
+        _ => {}
+    }
+}
+
+/// Replaces a random value nested in `value`, including `value` itself, with a random one.
+fn perturb<R: Rand>(rand: &mut R, value: &mut Value, keys: &[String]) {
+    let mut pointers = vec![String::new()];
+    collect_pointers(value, "", &mut pointers);
+    let pointer = rand.choose(pointers).unwrap_or_default();
+    if let Some(target) = value.pointer_mut(&pointer) {
+        *target = random_value(rand, keys, MAX_DEPTH);
+    }
+}
+
+/// Collects the JSON pointers of the values nested in `value`.
+fn collect_pointers(value: &Value, prefix: &str, pointers: &mut Vec<String>) {
+    let children: Vec<(String, &Value)> = match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (key.replace('~', "~0").replace('/', "~1"), value))
+            .collect(),
+        Value::Array(values) => values
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| (idx.to_string(), value))
+            .collect(),
+        _ => Vec::new(),
+    };
+    for (token, child) in children {
+        let pointer = format!("{prefix}/{token}");
+        collect_pointers(child, &pointer, pointers);
+        pointers.push(pointer);
+    }
+}
+
+fn random_object<R: Rand>(rand: &mut R, keys: &[String], depth: usize) -> Value {
+    let len = rand.between(0, MAX_ELEMENTS);
+    let mut map = Map::new();
+    for _ in 0..len {
+        let key = if rand.coinflip(0.8) {
+            rand.choose(keys).cloned()
+        } else {
+            None
+        };
+        let key = key.unwrap_or_else(|| format!("x-{}", rand.between(0, 99)));
+        map.insert(key, random_value(rand, keys, depth.saturating_sub(1)));
+    }
+    Value::Object(map)
+}
+
+/// A random value of a random type, favoring the boundary values of each type.
+fn random_value<R: Rand>(rand: &mut R, keys: &[String], depth: usize) -> Value {
+    let kinds = if depth == 0 { 5 } else { 7 };
+    match rand.between(0, kinds - 1) {
+        0 => Value::Null,
+        1 => Value::Bool(rand.coinflip(0.5)),
+        2 => {
+            let integers = [
+                json!(0),
+                json!(1),
+                json!(-1),
+                json!(i64::MIN),
+                json!(u64::MAX),
+            ];
+            rand.choose(integers).unwrap_or_default()
+        }
+        3 => {
+            let floats = [json!(0.5), json!(-0.0), json!(1e308), json!(-1e-308)];
+            rand.choose(floats).unwrap_or_default()
+        }
+        4 => {
+            let strings = [
+                String::new(),
+                "true".to_owned(),
+                "\u{0}".to_owned(),
+                "\u{1F600}".to_owned(),
+                "a".repeat(4096),
+            ];
+            let key = rand.choose(keys).cloned();
+            let string = if let Some(key) = key
+                && rand.coinflip(0.5)
+            {
+                key
+            } else {
+                rand.choose(strings).unwrap_or_default()
+            };
+            Value::String(string)
+        }
+        5 => Value::Array(
+            (0..rand.between(0, MAX_ELEMENTS))
+                .map(|_| random_value(rand, keys, depth - 1))
+                .collect(),
+        ),
+        _ => random_object(rand, keys, depth),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+    use crate::lsp;
+
+    #[test]
+    fn test_perturb() {
+        let mut rand = StdRand::with_seed(0);
+        let profiles = builtin_profiles();
+        let mut keys = Vec::new();
+        collect_keys(&profiles[0], &mut keys);
+        assert!(keys.iter().any(|it| it == "commands"));
+
+        let mut pointers = Vec::new();
+        collect_pointers(&json!({ "a/b": [1, { "c~": 2 }] }), "", &mut pointers);
+        assert!(pointers.contains(&"/a~1b/1/c~0".to_owned()));
+
+        for _ in 0..100 {
+            let mut profile = profiles[0].clone();
+            perturb(&mut rand, &mut profile, &keys);
+            assert!(random_value(&mut rand, &keys, 0).as_array().is_none());
+        }
+    }
+
+    #[test]
+    fn test_experimental_in_initialize() {
+        let mut input = LspInput::default();
+        let experimental = json!({ "testingApi": "yes" });
+        input
+            .messages
+            .set_experimental_capabilities(Some(experimental.clone()));
+        let capabilities = input
+            .message_sequence()
+            .find_map(|it| match it {
+                lsp::LspMessage::Initialize(params) => Some(params.capabilities),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(capabilities.experimental, Some(experimental));
+    }
+}
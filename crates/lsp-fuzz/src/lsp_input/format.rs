@@ -0,0 +1,126 @@
+//! The versioned on-disk format of inputs.
+//!
+//! Inputs are stored as CBOR, wrapped in a map carrying the version of their format.
+//! Inputs written in older versions are upgraded on load by the migrations below,
+//! which operate on the untyped CBOR value before it is deserialized.
+
+use std::io::{Read, Write};
+
+use ciborium::Value;
+use serde::Serialize;
+use tracing::warn;
+
+use super::LspInput;
+
+/// The version of the format written by this version of the fuzzer.
+///
+/// Bump it whenever a change to [`LspInput`] or the types it contains breaks the deserialization
+/// of existing files, and add a migration from the previous version to [`MIGRATIONS`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Upgrades the value of an input from the version at its index to the next version.
+type Migration = fn(Value) -> Result<Value, String>;
+
+/// The migrations to [`FORMAT_VERSION`], one per version.
+///
+/// Version 0 is the unversioned format written before versioning was introduced,
+/// which has the same layout as version 1.
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [Ok];
+
+#[derive(Serialize)]
+struct VersionedRef<'a> {
+    version: u32,
+    input: &'a LspInput,
+}
+
+/// Writes `input` in the current format.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be serialized or written.
+pub fn write_input<W: Write>(input: &LspInput, writer: W) -> Result<(), libafl::Error> {
+    let versioned = VersionedRef {
+        version: FORMAT_VERSION,
+        input,
+    };
+    ciborium::into_writer(&versioned, writer)
+        .map_err(|e| libafl::Error::serialize(format!("{e:#?}")))
+}
+
+/// Reads an input in any supported format version, along with the version it was written in.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be read, was written by a newer version of the fuzzer,
+/// or cannot be migrated.
+pub fn read_input<R: Read>(reader: R) -> Result<(LspInput, u32), libafl::Error> {
+    let value: Value =
+        ciborium::from_reader(reader).map_err(|e| libafl::Error::serialize(format!("{e:#?}")))?;
+    let (version, mut value) = unwrap_versioned(value)?;
+    if version > FORMAT_VERSION {
+        return Err(libafl::Error::serialize(format!(
+            "The input is in format version {version}, \
+             but only versions up to {FORMAT_VERSION} are supported"
+        )));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        value = migration(value).map_err(|e| {
+            libafl::Error::serialize(format!(
+                "Migrating input from format version {from} to {}: {e}",
+                from + 1
+            ))
+        })?;
+    }
+    let input = value
+        .deserialized()
+        .map_err(|e| libafl::Error::serialize(format!("{e:#?}")))?;
+    Ok((input, version))
+}
+
+/// Reads an input like [`read_input`], warning if it had to be upgraded.
+pub(super) fn read_input_upgrading<R: Read>(reader: R) -> Result<LspInput, libafl::Error> {
+    let (input, version) = read_input(reader)?;
+    if version < FORMAT_VERSION {
+        warn!(
+            version,
+            current = FORMAT_VERSION,
+            "Loaded an input in an old format, run `corpus-upgrade` to rewrite it"
+        );
+    }
+    Ok(input)
+}
+
+/// Splits `value` into the version of its format and the value of the input.
+/// Values not wrapped in a versioned map are in the unversioned format, i.e., version 0.
+///
+/// The input is moved out of the wrapping map instead of being decoded again.
+fn unwrap_versioned(value: Value) -> Result<(u32, Value), libafl::Error> {
+    match value {
+        Value::Map(fields) if is_versioned(&fields) => {
+            let (mut version, mut input) = (None, None);
+            for (key, field) in fields {
+                match key.as_text() {
+                    Some("version") => version = Some(field),
+                    Some("input") => input = Some(field),
+                    _ => {}
+                }
+            }
+            let version = version
+                .and_then(|it| Value::as_integer(&it))
+                .and_then(|it| u32::try_from(it).ok())
+                .ok_or_else(|| libafl::Error::serialize("Invalid format version"))?;
+            Ok((version, input.expect("Checked by is_versioned")))
+        }
+        value => Ok((0, value)),
+    }
+}
+
+/// Whether `fields` are exactly the fields of [`VersionedRef`].
+fn is_versioned(fields: &[(Value, Value)]) -> bool {
+    let has_field = |name: &str| {
+        fields
+            .iter()
+            .any(|(key, _)| key.as_text().is_some_and(|it| it == name))
+    };
+    fields.len() == 2 && has_field("version") && has_field("input")
+}
@@ -0,0 +1,454 @@
+//! Reconstruction of inputs from the messages of an LSP session.
+
+use std::{borrow::Cow, path::Path};
+
+use lsp_fuzz_grammars::Language;
+use lsp_types::request::{Initialize, Request};
+
+use super::{LspInput, WorkspaceEntry, messages::ScheduledOpen, uri};
+use crate::{
+    file_system::FileSystemEntry,
+    lsp::{
+        LspMessage,
+        json_rpc::{FramingErrorKind, JsonRPCMessage, MessageId},
+    },
+    text_document::TextDocument,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("The bytes are not LSP-framed JSON-RPC messages ({0:?})")]
+    NotJsonRpc(FramingErrorKind),
+    #[error("The session contains no messages from the client")]
+    NoClientMessages,
+}
+
+/// The header of a message in the trace log of VS Code, e.g.,
+/// `[Trace - 10:23:45 AM] Sending request 'textDocument/hover - (5)'.`
+const TRACE_HEADER: &str = "[Trace - ";
+/// The prefix of the lines of the JSON trace log of VS Code (`"trace.server": { "format": "json" }`).
+const JSON_TRACE_MARKER: &str = r#"{"isLSPMessage":true"#;
+
+/// What was left out when importing a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Messages whose method is unknown or whose parameters do not match it.
+    pub undecodable: usize,
+    /// Responses, which are answers of the server or of the client rather than messages.
+    pub responses: usize,
+    /// Documents opened outside the workspace root.
+    pub foreign_documents: usize,
+    /// Whether the stream ends with a payload that cannot be framed.
+    pub truncated: bool,
+    /// `textDocument/didChange` notifications applied to the documents they change,
+    /// since they precede every request.
+    pub applied_changes: usize,
+}
+
+impl LspInput {
+    /// Reconstructs an input from a byte stream of LSP-framed JSON-RPC messages sent to a
+    /// server, e.g., an input of a byte-level fuzzer or the stream written by
+    /// [`Self::request_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream does not start with a framed message, or none of its
+    /// messages is sent by the client.
+    pub fn from_lsp_stream(bytes: &[u8]) -> Result<(Self, ImportReport), ImportError> {
+        let (messages, malformed) = JsonRPCMessage::split_lsp_stream(bytes);
+        if let Some(malformed) = &malformed
+            && messages.is_empty()
+        {
+            return Err(ImportError::NotJsonRpc(malformed.kind));
+        }
+        let (input, mut report) = Self::from_json_rpc_messages(messages)?;
+        report.truncated = malformed.is_some();
+        Ok((input, report))
+    }
+
+    /// Reconstructs an input from the trace log of a session recorded by VS Code with
+    /// `"<server>.trace.server": "verbose"`, in either the text or the JSON format.
+    ///
+    /// Only the messages sent by the editor are imported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log contains no messages sent by the editor.
+    pub fn from_trace_log(log: &str) -> Result<(Self, ImportReport), ImportError> {
+        Self::from_json_rpc_messages(messages_from_trace_log(log))
+    }
+
+    /// Reconstructs an input from the JSON-RPC messages of a session.
+    ///
+    /// The URIs under the root of the workspace announced by `initialize` are mapped into the
+    /// virtual workspace, where the documents of `textDocument/didOpen` become the source files.
+    /// The `textDocument/didChange` notifications sent before the first request are applied to
+    /// the documents, so that the requests see the documents they were sent for.
+    /// The messages sent by the fuzzer around each session (e.g., `initialize` and `shutdown`)
+    /// are dropped, and so are the responses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if none of the messages is sent by the client.
+    pub fn from_json_rpc_messages(
+        messages: impl IntoIterator<Item = JsonRPCMessage>,
+    ) -> Result<(Self, ImportReport), ImportError> {
+        let mut report = ImportReport::default();
+        let mut client_messages = Vec::new();
+        for message in messages {
+            match message {
+                JsonRPCMessage::Request { method, params, .. }
+                | JsonRPCMessage::Notification { method, params, .. } => {
+                    client_messages.push((method, params));
+                }
+                JsonRPCMessage::Response { .. } => report.responses += 1,
+            }
+        }
+        let root = workspace_root(&client_messages);
+
+        let mut input = Self::default();
+        let mut decoded_any = false;
+        let mut requested = false;
+        for (method, mut params) in client_messages {
+            if let Some(root) = &root {
+                virtualize_json_value(&mut params, root);
+            }
+            let Ok(message) = LspMessage::try_from_json(&method, params) else {
+                report.undecodable += 1;
+                continue;
+            };
+            decoded_any = true;
+            match message {
+                LspMessage::Initialize(_)
+                | LspMessage::Initialized(_)
+                | LspMessage::Shutdown(())
+                | LspMessage::Exit(()) => {}
+                LspMessage::DidOpenTextDocument(params) => {
+                    let item = params.text_document;
+                    let Some(path) = uri::path_from_virtual_uri(&item.uri)
+                        .filter(|it| !it.is_empty())
+                        .map(str::to_owned)
+                    else {
+                        report.foreign_documents += 1;
+                        continue;
+                    };
+                    let entry = workspace_entry(&path, item.text.into_bytes());
+                    if !input.messages.is_empty() {
+                        input.messages.open_schedule_mut().push(ScheduledOpen {
+                            path: path.clone(),
+                            before: input.messages.len(),
+                        });
+                    }
+                    input
+                        .workspace
+                        .insert_path(&path, FileSystemEntry::File(entry));
+                }
+                LspMessage::DidChangeTextDocument(ref params)
+                    if !requested && apply_changes(&mut input, params) =>
+                {
+                    report.applied_changes += 1;
+                }
+                message => {
+                    requested |= message.is_request();
+                    input.push_message(message);
+                }
+            }
+        }
+        if decoded_any {
+            Ok((input, report))
+        } else {
+            Err(ImportError::NoClientMessages)
+        }
+    }
+}
+
+/// Applies the content changes of `params` to the source file they change, if it exists.
+fn apply_changes(input: &mut LspInput, params: &lsp_types::DidChangeTextDocumentParams) -> bool {
+    let Some(path) = uri::path_from_virtual_uri(&params.text_document.uri) else {
+        return false;
+    };
+    let Some(FileSystemEntry::File(WorkspaceEntry::SourceFile(doc))) = input.workspace.get(path)
+    else {
+        return false;
+    };
+    let mut text = doc.to_string_lossy().into_owned();
+    for change in &params.content_changes {
+        match change.range {
+            Some(range) => {
+                let start = utf16_offset(&text, range.start);
+                let end = utf16_offset(&text, range.end).max(start);
+                text.replace_range(start..end, &change.text);
+            }
+            None => text.clone_from(&change.text),
+        }
+    }
+    let doc = TextDocument::new(doc.language(), text.into_bytes());
+    let path = path.to_owned();
+    input.workspace.insert_path(
+        &path,
+        FileSystemEntry::File(WorkspaceEntry::SourceFile(doc)),
+    );
+    true
+}
+
+/// The byte offset of `pos` in `text`, counting columns in UTF-16 code units as the LSP does
+/// by default, clamped to the end of the line or of the text.
+fn utf16_offset(text: &str, pos: lsp_types::Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..pos.line {
+        match text[line_start..].find('\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let column = usize::try_from(pos.character).unwrap_or(usize::MAX);
+    let mut units = 0;
+    for (idx, ch) in line.char_indices() {
+        if units >= column {
+            return line_start + idx;
+        }
+        units += ch.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// Extracts the messages sent by the editor from a trace log of VS Code.
+#[must_use]
+pub fn messages_from_trace_log(log: &str) -> Vec<JsonRPCMessage> {
+    let mut messages = Vec::new();
+    let mut lines = log.lines().peekable();
+    while let Some(line) = lines.next() {
+        if let Some(json_start) = line.find(JSON_TRACE_MARKER) {
+            messages.extend(json_trace_message(&line[json_start..]));
+            continue;
+        }
+        let Some(header) = line
+            .strip_prefix(TRACE_HEADER)
+            .and_then(|it| it.split_once("] "))
+            .map(|(_, it)| it)
+        else {
+            continue;
+        };
+        let mut body = String::new();
+        while let Some(next) = lines.next_if(|it| !it.starts_with(TRACE_HEADER)) {
+            body.push_str(next);
+            body.push('\n');
+        }
+        messages.extend(text_trace_message(header, &body));
+    }
+    messages
+}
+
+/// The message of a text trace entry, if it is sent by the editor.
+fn text_trace_message(header: &str, body: &str) -> Option<JsonRPCMessage> {
+    let (is_request, described) = if let Some(it) = header.strip_prefix("Sending request '") {
+        (true, it)
+    } else {
+        (false, header.strip_prefix("Sending notification '")?)
+    };
+    let described = &described[..described.rfind('\'')?];
+    let params = match body.trim().strip_prefix("Params: ") {
+        Some(json) => serde_json::from_str(json).ok()?,
+        None => serde_json::Value::Null,
+    };
+    if is_request {
+        let (method, id) = described.rsplit_once(" - (")?;
+        let id = id.strip_suffix(')')?;
+        let id = id
+            .parse::<usize>()
+            .map_or_else(|_| MessageId::from(id.to_owned()), MessageId::from);
+        Some(JsonRPCMessage::request(
+            id,
+            method.to_owned().into(),
+            params,
+        ))
+    } else {
+        Some(JsonRPCMessage::notification(
+            described.to_owned().into(),
+            params,
+        ))
+    }
+}
+
+/// The message of a JSON trace entry, if it is sent by the editor.
+fn json_trace_message(line: &str) -> Option<JsonRPCMessage> {
+    #[derive(serde::Deserialize)]
+    struct Entry {
+        #[serde(rename = "type")]
+        kind: String,
+        message: serde_json::Value,
+    }
+    let entry: Entry = serde_json::from_str(line).ok()?;
+    matches!(entry.kind.as_str(), "send-request" | "send-notification")
+        .then(|| serde_json::from_value(entry.message).ok())
+        .flatten()
+}
+
+/// The source file at `path`, or a skeleton file if its language is unknown.
+fn workspace_entry(path: &str, content: Vec<u8>) -> WorkspaceEntry {
+    let language = Path::new(path)
+        .extension()
+        .and_then(|it| it.to_str())
+        .and_then(Language::from_file_extension);
+    match language {
+        Some(language) => WorkspaceEntry::SourceFile(TextDocument::new(language, content)),
+        None => WorkspaceEntry::Skeleton(content),
+    }
+}
+
+/// The URI of the workspace root announced by `initialize`, with a trailing slash.
+#[allow(deprecated, reason = "Some clients only send `root_uri`.")]
+fn workspace_root(messages: &[(Cow<'static, str>, serde_json::Value)]) -> Option<String> {
+    let params = messages
+        .iter()
+        .filter(|(method, _)| method == Initialize::METHOD)
+        .find_map(|(_, params)| {
+            serde_json::from_value::<lsp_types::InitializeParams>(params.clone()).ok()
+        })?;
+    let root = params
+        .workspace_folders
+        .as_ref()
+        .and_then(|it| it.first())
+        .map(|it| &it.uri)
+        .or(params.root_uri.as_ref())?;
+    let root = root.as_str();
+    Some(if root.ends_with('/') {
+        root.to_owned()
+    } else {
+        format!("{root}/")
+    })
+}
+
+/// Maps the URIs under `root` into the virtual workspace.
+fn virtualize_json_value(value: &mut serde_json::Value, root: &str) {
+    use serde_json::Value::{Array, Object, String};
+    match value {
+        Object(inner) => inner
+            .values_mut()
+            .for_each(|it| virtualize_json_value(it, root)),
+        Array(items) => items
+            .iter_mut()
+            .for_each(|it| virtualize_json_value(it, root)),
+        String(str_val) if str_val.starts_with(root) => {
+            str_val.replace_range(..root.len(), LspInput::PROTOCOL_PREFIX);
+        }
+        String(str_val) if root.strip_suffix('/') == Some(str_val.as_str()) => {
+            LspInput::PROTOCOL_PREFIX.clone_into(str_val);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use lsp_fuzz_grammars::Language;
+
+    use crate::{
+        file_system::FileSystemEntry,
+        lsp::LspMessage,
+        lsp_input::{LspInput, WorkspaceEntry},
+        text_document::TextDocument,
+    };
+
+    #[test]
+    fn round_trip_through_lsp_stream() {
+        let mut input = LspInput::default();
+        input.workspace.insert_path(
+            "src/main.rs",
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(TextDocument::new(
+                Language::Rust,
+                b"fn main() {}".to_vec(),
+            ))),
+        );
+        let uri = "lsp-fuzz://src/main.rs".parse().unwrap();
+        input.push_message(LspMessage::DocumentSymbolRequest(
+            lsp_types::DocumentSymbolParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri },
+                work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+                partial_result_params: lsp_types::PartialResultParams::default(),
+            },
+        ));
+        let bytes = input.request_bytes(Path::new("/tmp/lsp-fuzz-workspace_test"));
+
+        let (imported, report) = LspInput::from_lsp_stream(&bytes).unwrap();
+        assert_eq!(report, super::ImportReport::default());
+        assert_eq!(imported.workspace, input.workspace);
+        assert_eq!(*imported.messages, *input.messages);
+    }
+
+    #[test]
+    fn import_vscode_trace() {
+        const TRACE: &str = r#"[Trace - 10:23:45 AM] Sending request 'initialize - (0)'.
+Params: {
+    "processId": 42,
+    "rootUri": "file:///home/user/project",
+    "capabilities": {}
+}
+
+
+[Trace - 10:23:45 AM] Received response 'initialize - (0)' in 12ms.
+Result: {
+    "capabilities": {}
+}
+
+
+[Trace - 10:23:46 AM] Sending notification 'textDocument/didOpen'.
+Params: {
+    "textDocument": {
+        "uri": "file:///home/user/project/src/main.rs",
+        "languageId": "rust",
+        "version": 1,
+        "text": "fn main() {}\n"
+    }
+}
+
+
+[Trace - 10:23:46 AM] Sending notification 'workspace/didChangeConfiguration'.
+Params: {
+    "settings": {}
+}
+
+
+[Trace - 10:23:46 AM] Sending notification 'textDocument/didChange'.
+Params: {
+    "textDocument": { "uri": "file:///home/user/project/src/main.rs", "version": 2 },
+    "contentChanges": [
+        { "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } }, "text": "start" }
+    ]
+}
+
+
+[Trace - 10:23:47 AM] Sending request 'textDocument/hover - (1)'.
+Params: {
+    "textDocument": { "uri": "file:///home/user/project/src/main.rs" },
+    "position": { "line": 0, "character": 4 }
+}
+"#;
+        let (input, report) = LspInput::from_trace_log(TRACE).unwrap();
+        assert_eq!(report.applied_changes, 1);
+        let WorkspaceEntry::SourceFile(doc) = input
+            .workspace
+            .iter_files()
+            .find(|(path, _)| path == Path::new("src/main.rs"))
+            .unwrap()
+            .1
+        else {
+            panic!("The document is a source file");
+        };
+        assert_eq!(&doc.content()[..], b"fn start() {}\n");
+        assert!(matches!(
+            input.messages.as_slice(),
+            [LspMessage::DidChangeConfiguration(_), LspMessage::HoverRequest(params)]
+                if params.text_document_position_params.text_document.uri.as_str()
+                    == "lsp-fuzz://src/main.rs"
+        ));
+    }
+
+    #[test]
+    fn reject_non_json_rpc() {
+        assert!(LspInput::from_lsp_stream(b"\x00\x01 not LSP").is_err());
+    }
+}
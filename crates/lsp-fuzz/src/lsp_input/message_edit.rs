@@ -9,6 +9,73 @@ pub fn calibrate_message(message: &mut LspMessage, input_edit: tree_sitter::Inpu
     }
 }
 
+/// Converts the columns of the position or range of `message` from UTF-8 code units
+/// to UTF-16 code units, using the lines of `content`.
+///
+/// Columns past the end of a line are shifted by the same amount as the end of the line.
+pub fn convert_columns_to_utf16(message: &mut LspMessage, content: &[u8]) {
+    if let Some(pos) = message.position_mut() {
+        *pos = utf16_position(*pos, content);
+    } else if let Some(range) = message.range_mut() {
+        range.start = utf16_position(range.start, content);
+        range.end = utf16_position(range.end, content);
+    }
+}
+
+fn utf16_position(pos: lsp_types::Position, content: &[u8]) -> lsp_types::Position {
+    let Some(line) = content
+        .split(|&it| it == b'\n')
+        .nth(usize::try_from(pos.line).expect("u32 fits into usize on supported targets"))
+    else {
+        return pos;
+    };
+    let column = usize::try_from(pos.character).expect("u32 fits into usize on supported targets");
+    let prefix = &line[..column.min(line.len())];
+    let utf16_len = String::from_utf8_lossy(prefix).encode_utf16().count();
+    let character = utf16_len + column.saturating_sub(line.len());
+    lsp_types::Position {
+        line: pos.line,
+        character: u32::try_from(character).unwrap_or(u32::MAX),
+    }
+}
+
+/// Converts the column of `pos` from UTF-16 code units to UTF-8 code units, using the lines of
+/// `content`, e.g., for the positions the server sends under the UTF-16 encoding.
+///
+/// Columns past the end of a line are shifted by the same amount as the end of the line.
+#[must_use]
+pub fn utf8_position(pos: lsp_types::Position, content: &[u8]) -> lsp_types::Position {
+    let Some(line) = content
+        .split(|&it| it == b'\n')
+        .nth(usize::try_from(pos.line).expect("u32 fits into usize on supported targets"))
+    else {
+        return pos;
+    };
+    let column = usize::try_from(pos.character).expect("u32 fits into usize on supported targets");
+    // Invalid sequences count as one replacement character, as in `utf16_position`.
+    let units = line.utf8_chunks().flat_map(|chunk| {
+        let invalid = (!chunk.invalid().is_empty()).then_some((1, chunk.invalid().len()));
+        chunk
+            .valid()
+            .chars()
+            .map(|it| (it.len_utf16(), it.len_utf8()))
+            .chain(invalid)
+    });
+    let (mut utf16_len, mut utf8_len) = (0, 0);
+    for (utf16, utf8) in units {
+        if utf16_len >= column {
+            break;
+        }
+        utf16_len += utf16;
+        utf8_len += utf8;
+    }
+    let character = utf8_len + column.saturating_sub(utf16_len);
+    lsp_types::Position {
+        line: pos.line,
+        character: u32::try_from(character).unwrap_or(u32::MAX),
+    }
+}
+
 fn is_after_edit(pos: lsp_types::Position, edit: &tree_sitter::InputEdit) -> bool {
     usize::try_from(pos.line)
         .expect("u32 fits into usize on supported targets")
@@ -51,7 +118,7 @@ mod tests {
         WorkDoneProgressParams,
     };
 
-    use super::calibrate_message;
+    use super::{calibrate_message, convert_columns_to_utf16};
     use crate::lsp::LspMessage;
 
     #[test]
@@ -84,4 +151,49 @@ mod tests {
             Position::new(4, 4)
         );
     }
+
+    #[test]
+    fn convert_columns_of_non_ascii_line() {
+        let mut message = LspMessage::HoverRequest(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: "lsp-fuzz://main.c".parse::<Uri>().unwrap(),
+                },
+                position: Position::new(1, 7),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+        // `é` takes two bytes but one UTF-16 code unit, and `😀` takes four bytes but two.
+        convert_columns_to_utf16(&mut message, "int x;\né😀 = 1;".as_bytes());
+
+        let LspMessage::HoverRequest(params) = message else {
+            panic!("expected hover request");
+        };
+        assert_eq!(
+            params.text_document_position_params.position,
+            Position::new(1, 4)
+        );
+    }
+
+    #[test]
+    fn convert_utf16_columns_back() {
+        let content = "int x;\né😀 = 1;".as_bytes();
+        assert_eq!(
+            utf8_position(Position::new(1, 4), content),
+            Position::new(1, 7)
+        );
+        assert_eq!(
+            utf8_position(Position::new(1, 1), content),
+            Position::new(1, 2)
+        );
+        // Past the end of the line.
+        assert_eq!(
+            utf8_position(Position::new(1, 9), content),
+            Position::new(1, 12)
+        );
+        assert_eq!(
+            utf8_position(Position::new(5, 3), content),
+            Position::new(5, 3)
+        );
+    }
 }
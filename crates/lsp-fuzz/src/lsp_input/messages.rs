@@ -1,23 +1,26 @@
-use std::{borrow::Cow, marker::PhantomData, mem};
+use std::{borrow::Cow, marker::PhantomData, mem, path::Path};
 
-use derive_more::derive::{Deref, DerefMut};
+use derive_more::derive::Deref;
 use derive_new::new as New;
+use itertools::Itertools;
 use libafl::{
     HasMetadata,
     mutators::{MutationResult, Mutator, MutatorsTuple},
     state::{HasCurrentTestcase, HasRand},
 };
 use libafl_bolts::{
-    HasLen, Named,
+    HasLen, Named, SerdeAny,
     rands::Rand,
     tuples::{Merge, NamedTuple},
 };
-use lsp_types::Uri;
+use lsp_fuzz_grammars::Language;
+use lsp_types::{Uri, WorkDoneProgressCancelParams};
 use serde::{Deserialize, Deserializer, Serialize};
 use tuple_list::{tuple_list, tuple_list_type};
 
-use super::LspInput;
+use super::{LspInput, WorkspaceEntry};
 use crate::{
+    file_system::FileSystemEntry,
     lsp::{
         self, GeneratorsConfig,
         code_context::CodeContextRef,
@@ -26,16 +29,237 @@ use crate::{
             append_navigation_messages, append_symbol_messages, append_tracing_misc_messages,
             append_workspace_messages,
         },
-        json_rpc::MessageId,
+        json_rpc::{HeaderLineEnding, MessageId},
     },
-    lsp_input::message_edit,
-    macros::prop_mutator,
-    mutators::SliceSwapMutator,
+    lsp_input::{
+        configuration::{Configuration, RedrawConfiguration},
+        experimental_capabilities::InjectExperimentalCapabilities,
+        message_edit,
+        sequence_length::SequenceLengthPolicy,
+        server_response::minted_ids::MintedIds,
+    },
+    mutators::WithProbability,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deref, DerefMut)]
+/// The messages sent by the client after initialization, and where the fuzzer adds its own
+/// messages and faults among them.
+///
+/// The messages are changed only through methods keeping the placements attached to the same
+/// messages, e.g., [`LspInput::insert_message`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deref)]
 pub struct LspMessageSequence {
+    #[deref]
     inner: Vec<lsp::LspMessage>,
+    opens: Vec<ScheduledOpen>,
+    divergences: Vec<Divergence>,
+    framing: Vec<FramingVariation>,
+    fault: Option<TransportFault>,
+    encoding: PositionEncoding,
+    language_ids: Vec<LanguageIdOverride>,
+    capabilities: CapabilityProfile,
+    experimental: Option<serde_json::Value>,
+    configuration: Option<Configuration>,
+}
+
+/// The placement of the `textDocument/didOpen` notification of a source file.
+///
+/// Source files without a scheduled open are opened right after initialization,
+/// in workspace order, followed by the scheduled opens in the order they are listed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScheduledOpen {
+    /// The path of the source file relative to the workspace root.
+    pub path: String,
+    /// The index of the message sent right after the notification.
+    pub before: usize,
+}
+
+/// Which copy of a document diverges from the original source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DivergentSide {
+    /// A `textDocument/didChange` replaces the open buffer,
+    /// while the file on disk keeps the original content.
+    InMemory,
+    /// The file on disk is written with the divergent content and announced with
+    /// `workspace/didChangeWatchedFiles`, while the open buffer keeps the original content.
+    ///
+    /// The file is written before the target starts, so only the notification is placed
+    /// in the message sequence.
+    OnDisk,
+}
+
+/// A source file whose in-memory and on-disk contents differ.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Divergence {
+    /// The path of the source file relative to the workspace root.
+    pub path: String,
+    /// The index of the message sent right after the notification.
+    pub before: usize,
+    pub side: DivergentSide,
+    pub content: Vec<u8>,
+}
+
+/// A payload of the session whose header lines do not end with `\r\n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FramingVariation {
+    /// The index of the payload in the session, counting from the `initialize` request.
+    ///
+    /// Unlike the other placements in the sequence, this index covers the messages
+    /// added by the fuzzer (e.g., `textDocument/didOpen`).
+    pub payload: usize,
+    pub line_ending: HeaderLineEnding,
+}
+
+/// A fault the client injects into the transport, which ends the session early.
+///
+/// Servers are expected to exit cleanly when their input ends, no matter where it ends.
+/// Nothing is sent after the fault, so the session lacks the `exit` notification
+/// and, unless stated otherwise, the `shutdown` request.
+/// Message indices past the last message refer to the `shutdown` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TransportFault {
+    /// The client closes the connection right before the message at `before`.
+    CloseEarly { before: usize },
+    /// The client closes the connection after writing the first `bytes` bytes
+    /// of the payload of the message at `message`.
+    DropMidMessage { message: usize, bytes: usize },
+    /// The client closes the connection after the `shutdown` request,
+    /// without sending the `exit` notification.
+    HalfCloseAfterShutdown,
+    /// The client closes the connection after writing the first `bytes` bytes
+    /// of the payload of the `exit` notification.
+    PartialFinalMessage { bytes: usize },
+}
+
+impl TransportFault {
+    /// The index of the message at which the fault happens,
+    /// or `None` for the faults happening after the `shutdown` request.
+    #[must_use]
+    pub const fn message(self) -> Option<usize> {
+        match self {
+            Self::CloseEarly { before: message } | Self::DropMidMessage { message, .. } => {
+                Some(message)
+            }
+            Self::HalfCloseAfterShutdown | Self::PartialFinalMessage { .. } => None,
+        }
+    }
+
+    const fn message_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            Self::CloseEarly { before: message } | Self::DropMidMessage { message, .. } => {
+                Some(message)
+            }
+            Self::HalfCloseAfterShutdown | Self::PartialFinalMessage { .. } => None,
+        }
+    }
+}
+
+/// The first `bytes` bytes of `payload`, leaving out at least its last byte.
+#[must_use]
+pub fn partial_payload(payload: &[u8], bytes: usize) -> &[u8] {
+    &payload[..bytes.min(payload.len().saturating_sub(1))]
+}
+
+/// A unit of the columns in positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ColumnUnit {
+    /// UTF-8 code units (i.e., bytes), in which the positions of the messages are stored.
+    #[default]
+    Utf8,
+    /// UTF-16 code units, which servers assume when the client does not negotiate an encoding.
+    Utf16,
+}
+
+impl ColumnUnit {
+    #[must_use]
+    pub fn encoding_kind(self) -> lsp_types::PositionEncodingKind {
+        match self {
+            Self::Utf8 => lsp_types::PositionEncodingKind::UTF8,
+            Self::Utf16 => lsp_types::PositionEncodingKind::UTF16,
+        }
+    }
+
+    #[must_use]
+    pub const fn other(self) -> Self {
+        match self {
+            Self::Utf8 => Self::Utf16,
+            Self::Utf16 => Self::Utf8,
+        }
+    }
+}
+
+/// The position encoding negotiated in the `initialize` request,
+/// and the messages whose positions deliberately use the other unit.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct PositionEncoding {
+    pub negotiated: ColumnUnit,
+    /// The indices of the messages whose columns are not in the negotiated unit.
+    pub mismatched: Vec<usize>,
+}
+
+/// The client capabilities advertised in the `initialize` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum CapabilityProfile {
+    /// Every capability the fuzzer can exercise.
+    #[default]
+    Full,
+    /// Next to nothing besides the position encoding, which forces servers down their
+    /// fallback paths, e.g., plain-text documentation, completions without snippets,
+    /// and flat document symbols.
+    Minimal,
+}
+
+impl CapabilityProfile {
+    /// Picks [`Self::Minimal`] with `minimal_probability`, and [`Self::Full`] otherwise.
+    pub fn draw<R: Rand>(rand: &mut R, minimal_probability: f64) -> Self {
+        if minimal_probability > 0.0 && rand.coinflip(minimal_probability) {
+            Self::Minimal
+        } else {
+            Self::Full
+        }
+    }
+}
+
+/// A `languageId` sent in the `textDocument/didOpen` notification of a source file
+/// in place of the identifier of the language of its content.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LanguageIdOverride {
+    /// The path of the source file relative to the workspace root.
+    pub path: String,
+    pub language_id: String,
+}
+
+/// An element of a message sequence, either a message or the end of the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceElement<M> {
+    Message(M),
+    /// The client closes its end of the connection, see [`TransportFault`].
+    ///
+    /// If `partial` is set, the first bytes of the payload of the message are written before,
+    /// see [`partial_payload`]. It is always the last element of a sequence.
+    Close {
+        partial: Option<(M, usize)>,
+    },
+}
+
+impl<M> SequenceElement<M> {
+    /// The message of the element if it is sent entirely.
+    #[must_use]
+    pub fn into_message(self) -> Option<M> {
+        match self {
+            Self::Message(message) => Some(message),
+            Self::Close { .. } => None,
+        }
+    }
+
+    /// Maps the messages of the element with `f`.
+    pub fn map<N>(self, f: impl FnOnce(M) -> N) -> SequenceElement<N> {
+        match self {
+            Self::Message(message) => SequenceElement::Message(f(message)),
+            Self::Close { partial } => SequenceElement::Close {
+                partial: partial.map(|(message, bytes)| (f(message), bytes)),
+            },
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for LspMessageSequence {
@@ -46,9 +270,38 @@ impl<'de> Deserialize<'de> for LspMessageSequence {
         #[derive(Deserialize)]
         struct LspMessageSequenceRepr {
             inner: Vec<lsp::LspMessage>,
+            #[serde(default)]
+            opens: Vec<ScheduledOpen>,
+            #[serde(default)]
+            divergences: Vec<Divergence>,
+            #[serde(default)]
+            framing: Vec<FramingVariation>,
+            #[serde(default)]
+            fault: Option<TransportFault>,
+            #[serde(default)]
+            encoding: PositionEncoding,
+            #[serde(default)]
+            language_ids: Vec<LanguageIdOverride>,
+            #[serde(default)]
+            capabilities: CapabilityProfile,
+            #[serde(default)]
+            experimental: Option<serde_json::Value>,
+            #[serde(default)]
+            configuration: Option<Configuration>,
         }
 
-        LspMessageSequenceRepr::deserialize(deserializer).map(|repr| Self { inner: repr.inner })
+        LspMessageSequenceRepr::deserialize(deserializer).map(|repr| Self {
+            inner: repr.inner,
+            opens: repr.opens,
+            divergences: repr.divergences,
+            framing: repr.framing,
+            fault: repr.fault,
+            encoding: repr.encoding,
+            language_ids: repr.language_ids,
+            capabilities: repr.capabilities,
+            experimental: repr.experimental,
+            configuration: repr.configuration,
+        })
     }
 }
 
@@ -76,6 +329,9 @@ impl<'a> Iterator for EnumMessages<'a> {
 }
 
 impl LspMessageSequence {
+    /// The index of the `workspace/didChangeConfiguration` payload, right after `initialized`.
+    const CONFIGURATION_PAYLOAD: usize = 2;
+
     #[must_use]
     pub fn enumerate_messages(&self) -> EnumMessages<'_> {
         EnumMessages {
@@ -84,12 +340,332 @@ impl LspMessageSequence {
         }
     }
 
+    /// Iterates over the messages.
+    ///
+    /// A [`TransportFault`] at a message yields [`SequenceElement::Close`] in its place,
+    /// and nothing after it.
+    pub fn elements(&self) -> impl Iterator<Item = SequenceElement<&lsp::LspMessage>> + use<'_> {
+        let len = self.inner.len();
+        let fault = self.fault.filter(|it| it.message().is_some());
+        let end = fault
+            .and_then(TransportFault::message)
+            .map_or(len, |it| it.min(len));
+        (0..=end).filter_map(move |idx| match fault {
+            Some(TransportFault::DropMidMessage { bytes, .. }) if idx == end => {
+                Some(SequenceElement::Close {
+                    partial: self.inner.get(idx).map(|it| (it, bytes)),
+                })
+            }
+            Some(_) if idx == end => Some(SequenceElement::Close { partial: None }),
+            _ => self.inner.get(idx).map(SequenceElement::Message),
+        })
+    }
+
+    #[must_use]
+    pub fn open_schedule(&self) -> &[ScheduledOpen] {
+        &self.opens
+    }
+
+    pub fn open_schedule_mut(&mut self) -> &mut Vec<ScheduledOpen> {
+        &mut self.opens
+    }
+
+    #[must_use]
+    pub fn divergences(&self) -> &[Divergence] {
+        &self.divergences
+    }
+
+    pub fn divergences_mut(&mut self) -> &mut Vec<Divergence> {
+        &mut self.divergences
+    }
+
+    #[must_use]
+    pub fn framing(&self) -> &[FramingVariation] {
+        &self.framing
+    }
+
+    pub fn framing_mut(&mut self) -> &mut Vec<FramingVariation> {
+        &mut self.framing
+    }
+
+    /// The fault injected into the transport of the session, if any.
+    #[must_use]
+    pub const fn transport_fault(&self) -> Option<TransportFault> {
+        self.fault
+    }
+
+    pub const fn set_transport_fault(&mut self, fault: Option<TransportFault>) {
+        self.fault = fault;
+    }
+
+    /// The line ending of the header lines of the `payload`-th payload in the session.
+    #[must_use]
+    pub fn header_line_ending(&self, payload: usize) -> HeaderLineEnding {
+        self.framing
+            .iter()
+            .find(|it| it.payload == payload)
+            .map(|it| it.line_ending)
+            .unwrap_or_default()
+    }
+
+    #[must_use]
+    pub const fn position_encoding(&self) -> &PositionEncoding {
+        &self.encoding
+    }
+
+    pub const fn position_encoding_mut(&mut self) -> &mut PositionEncoding {
+        &mut self.encoding
+    }
+
+    #[must_use]
+    pub const fn capability_profile(&self) -> CapabilityProfile {
+        self.capabilities
+    }
+
+    pub const fn set_capability_profile(&mut self, profile: CapabilityProfile) {
+        self.capabilities = profile;
+    }
+
+    /// The value of `capabilities.experimental` in the `initialize` request.
+    #[must_use]
+    pub const fn experimental_capabilities(&self) -> Option<&serde_json::Value> {
+        self.experimental.as_ref()
+    }
+
+    pub fn set_experimental_capabilities(&mut self, experimental: Option<serde_json::Value>) {
+        self.experimental = experimental;
+    }
+
+    /// The settings sent to the target, see [`Configuration`].
+    #[must_use]
+    pub const fn configuration(&self) -> Option<&Configuration> {
+        self.configuration.as_ref()
+    }
+
+    /// Sets the settings sent to the target, and keeps the framing variations attached to the
+    /// same payloads when the `workspace/didChangeConfiguration` notification comes or goes.
+    pub fn set_configuration(&mut self, configuration: Option<Configuration>) {
+        let notifies =
+            |it: &Option<Configuration>| it.as_ref().is_some_and(Configuration::notifies);
+        match (notifies(&self.configuration), notifies(&configuration)) {
+            (false, true) => self.insert_payload(Self::CONFIGURATION_PAYLOAD),
+            (true, false) => self.remove_payload(Self::CONFIGURATION_PAYLOAD),
+            _ => {}
+        }
+        self.configuration = configuration;
+    }
+
+    /// Whether the session starts like the one of a plain client, i.e., with the full capability
+    /// profile, neither experimental capabilities nor settings, and no transport fault,
+    /// so that the server answers `initialize` with the capabilities it declares by default.
+    #[must_use]
+    pub fn has_plain_initialize(&self) -> bool {
+        self.capabilities == CapabilityProfile::Full
+            && self.experimental.is_none()
+            && self.configuration.is_none()
+            && self.fault.is_none()
+    }
+
+    /// Makes the session start like the one of a plain client,
+    /// see [`Self::has_plain_initialize`].
+    pub fn make_initialize_plain(&mut self) {
+        self.set_capability_profile(CapabilityProfile::Full);
+        self.set_experimental_capabilities(None);
+        self.set_configuration(None);
+        self.set_transport_fault(None);
+    }
+
+    /// Keeps the framing variations attached to the same payloads after a payload is inserted
+    /// into the session at `payload`.
+    pub fn insert_payload(&mut self, payload: usize) {
+        self.framing
+            .iter_mut()
+            .filter(|it| it.payload >= payload)
+            .for_each(|it| it.payload += 1);
+    }
+
+    /// Keeps the framing variations attached to the same payloads after the payload at `from`
+    /// is moved to `to`, which shifts the payloads in between.
+    pub fn move_payload(&mut self, from: usize, to: usize) {
+        self.relocate_payloads(|it| {
+            if it == from {
+                to
+            } else if from < it && it <= to {
+                it - 1
+            } else if to <= it && it < from {
+                it + 1
+            } else {
+                it
+            }
+        });
+    }
+
+    /// Keeps the framing variations attached to the same payloads after the payload at
+    /// `payload` is removed from the session, dropping the variation of the removed one.
+    pub fn remove_payload(&mut self, payload: usize) {
+        self.framing.retain(|it| it.payload != payload);
+        self.framing
+            .iter_mut()
+            .filter(|it| it.payload > payload)
+            .for_each(|it| it.payload -= 1);
+    }
+
+    #[must_use]
+    pub fn language_id_overrides(&self) -> &[LanguageIdOverride] {
+        &self.language_ids
+    }
+
+    pub fn language_id_overrides_mut(&mut self) -> &mut Vec<LanguageIdOverride> {
+        &mut self.language_ids
+    }
+
+    /// The `languageId` sent for the source file at `path` instead of that of its language.
+    #[must_use]
+    pub fn language_id_override(&self, path: &Path) -> Option<&str> {
+        self.language_ids
+            .iter()
+            .find(|it| Path::new(&it.path) == path)
+            .map(|it| it.language_id.as_str())
+    }
+
+    /// The unit in which the columns of the message at `index` are sent.
+    #[must_use]
+    pub fn column_unit(&self, index: usize) -> ColumnUnit {
+        if self.encoding.mismatched.contains(&index) {
+            self.encoding.negotiated.other()
+        } else {
+            self.encoding.negotiated
+        }
+    }
+
+    /// Removes the message at `index` and keeps the scheduled opens, divergences,
+    /// transport fault, and position encoding mismatches attached to the same messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub(super) fn remove_message(&mut self, index: usize) -> lsp::LspMessage {
+        let removed = self.inner.remove(index);
+        self.opens
+            .iter_mut()
+            .filter(|it| it.before > index)
+            .for_each(|it| it.before -= 1);
+        self.divergences
+            .iter_mut()
+            .filter(|it| it.before > index)
+            .for_each(|it| it.before -= 1);
+        if let Some(message) = self.fault.as_mut().and_then(TransportFault::message_mut)
+            && *message > index
+        {
+            *message -= 1;
+        }
+        self.encoding.mismatched.retain(|&it| it != index);
+        self.encoding
+            .mismatched
+            .iter_mut()
+            .filter(|it| **it > index)
+            .for_each(|it| *it -= 1);
+        removed
+    }
+
+    /// Inserts `message` at `index` and keeps the scheduled opens, divergences,
+    /// transport fault, and position encoding mismatches attached to the same messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub(super) fn insert_message(&mut self, index: usize, message: lsp::LspMessage) {
+        self.inner.insert(index, message);
+        self.opens
+            .iter_mut()
+            .filter(|it| it.before >= index)
+            .for_each(|it| it.before += 1);
+        self.divergences
+            .iter_mut()
+            .filter(|it| it.before >= index)
+            .for_each(|it| it.before += 1);
+        if let Some(message) = self.fault.as_mut().and_then(TransportFault::message_mut)
+            && *message >= index
+        {
+            *message += 1;
+        }
+        self.encoding
+            .mismatched
+            .iter_mut()
+            .filter(|it| **it >= index)
+            .for_each(|it| *it += 1);
+    }
+
+    /// Reorders the messages from `start` on, so that the `k`-th of them is the one previously
+    /// at `start + order[k]`, and keeps the scheduled opens, divergences, transport fault,
+    /// and position encoding mismatches attached to the same messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is not a permutation of `0..order.len()` or if the reordered messages
+    /// are out of bounds.
+    pub(super) fn permute_messages(&mut self, start: usize, order: &[usize]) {
+        let window = start..start + order.len();
+        let mut moved: Vec<_> = self.inner.drain(window.clone()).map(Some).collect();
+        let reordered: Vec<_> = order
+            .iter()
+            .map(|&it| moved[it].take().expect("`order` must be a permutation"))
+            .collect();
+        self.inner.splice(start..start, reordered);
+
+        let mut destinations = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            destinations[old] = start + new;
+        }
+        let relocate = |index: &mut usize| {
+            if window.contains(index) {
+                *index = destinations[*index - start];
+            }
+        };
+        self.opens
+            .iter_mut()
+            .for_each(|it| relocate(&mut it.before));
+        self.divergences
+            .iter_mut()
+            .for_each(|it| relocate(&mut it.before));
+        if let Some(message) = self.fault.as_mut().and_then(TransportFault::message_mut) {
+            relocate(message);
+        }
+        self.encoding.mismatched.iter_mut().for_each(relocate);
+    }
+
+    /// The message at `index`, to be changed in place.
+    pub fn message_mut(&mut self, index: usize) -> Option<&mut lsp::LspMessage> {
+        self.inner.get_mut(index)
+    }
+
+    /// Moves the framing variations along with their payloads, which `relocate` maps from their
+    /// previous to their current index in the session.
+    pub fn relocate_payloads(&mut self, relocate: impl Fn(usize) -> usize) {
+        self.framing
+            .iter_mut()
+            .for_each(|it| it.payload = relocate(it.payload));
+    }
+
     pub fn calibrate(&mut self, doc_uri: &Uri, input_edit: tree_sitter::InputEdit) {
         self.inner
             .iter_mut()
             .filter(|it| it.document().is_some_and(|it| &it.uri == doc_uri))
             .for_each(|message| message_edit::calibrate_message(message, input_edit));
     }
+
+    /// Calibrates the messages targeting `doc_uri` after several edits of the document,
+    /// in the order they were applied, in a single pass over the messages.
+    pub fn calibrate_many(&mut self, doc_uri: &Uri, input_edits: &[tree_sitter::InputEdit]) {
+        self.inner
+            .iter_mut()
+            .filter(|it| it.document().is_some_and(|it| &it.uri == doc_uri))
+            .for_each(|message| {
+                for &input_edit in input_edits {
+                    message_edit::calibrate_message(message, input_edit);
+                }
+            });
+    }
 }
 
 impl HasLen for LspMessageSequence {
@@ -112,16 +688,20 @@ impl<State> Named for DropRandomMessage<State> {
 
 impl<State> Mutator<LspInput, State> for DropRandomMessage<State>
 where
-    State: HasRand,
+    State: HasRand + HasMetadata,
 {
     fn mutate(
         &mut self,
         state: &mut State,
         input: &mut LspInput,
     ) -> Result<MutationResult, libafl::Error> {
+        let policy = SequenceLengthPolicy::of(state);
         let rand = state.rand_mut();
+        if !rand.coinflip(policy.drop_probability) {
+            return Ok(MutationResult::Skipped);
+        }
         if let Some(index) = rand.choose(0..input.messages.len()) {
-            input.messages.remove(index);
+            input.remove_message(index);
             Ok(MutationResult::Mutated)
         } else {
             Ok(MutationResult::Skipped)
@@ -137,33 +717,1000 @@ where
     }
 }
 
-prop_mutator!(pub impl MessagesMutator for LspInput::messages type Vec<lsp::LspMessage>);
+/// Schedules every source file to be opened up front in workspace order,
+/// unless the input already has an open schedule.
+fn ensure_open_schedule(input: &mut LspInput) -> &mut Vec<ScheduledOpen> {
+    if input.messages.open_schedule().is_empty() {
+        let opens = input
+            .workspace
+            .iter_files()
+            .filter(|(_, entry)| entry.as_source_file().is_some())
+            .filter_map(|(path, _)| path.to_str().map(ToOwned::to_owned))
+            .map(|path| ScheduledOpen { path, before: 0 });
+        input.messages.opens.extend(opens);
+    }
+    input.messages.open_schedule_mut()
+}
 
-pub type SwapRequests<State> = MessagesMutator<SliceSwapMutator<lsp::LspMessage, State>>;
+/// Swaps the positions of two source files in the order they are opened.
+#[derive(Debug, New)]
+pub struct PermuteOpenOrder<State> {
+    _state: PhantomData<State>,
+}
 
-#[must_use]
-pub fn message_mutations<State>(
-    config: &GeneratorsConfig,
-) -> impl MutatorsTuple<LspInput, State> + NamedTuple + use<State>
+impl<State> Named for PermuteOpenOrder<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PermuteOpenOrder");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for PermuteOpenOrder<State>
 where
-    State: HasRand + HasMetadata + HasCurrentTestcase<LspInput> + 'static,
+    State: HasRand,
 {
-    let swap = tuple_list![SwapRequests::new(SliceSwapMutator::new())];
-    append_navigation_messages(config)
-        .merge(append_symbol_messages(config))
-        .merge(append_formatting_messages(config))
-        .merge(append_hierarchy_messages(config))
-        .merge(append_workspace_messages(config))
-        .merge(append_diagnostic_messages(config))
-        .merge(append_tracing_misc_messages(config))
-        .merge(swap)
-        .merge(message_reductions())
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let open_count = ensure_open_schedule(input).len();
+        if open_count < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let rand = state.rand_mut();
+        let (Some(lhs), Some(rhs)) = (rand.choose(0..open_count), rand.choose(0..open_count))
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        if lhs == rhs {
+            return Ok(MutationResult::Skipped);
+        }
+        input.swap_opens(lhs, rhs);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
 }
 
-#[must_use]
-pub fn message_reductions<State>() -> tuple_list_type![DropRandomMessage<State>]
+/// Moves the open of a random source file in between the other messages.
+#[derive(Debug, New)]
+pub struct DelayOpen<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for DelayOpen<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("DelayOpen");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for DelayOpen<State>
 where
     State: HasRand,
 {
-    tuple_list![DropRandomMessage::new()]
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let message_count = input.messages.len();
+        let open_count = ensure_open_schedule(input).len();
+        let rand = state.rand_mut();
+        let Some(index) = rand.choose(0..open_count) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let before = rand.below_or_zero(message_count + 1);
+        if before == input.messages.open_schedule()[index].before {
+            return Ok(MutationResult::Skipped);
+        }
+        input.move_open(index, before);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// The maximum number of divergences in a message sequence.
+const MAX_DIVERGENCES: usize = 4;
+
+/// Makes the in-memory and on-disk contents of a random source file differ.
+///
+/// The divergent content is the original content with a random span either removed or repeated.
+#[derive(Debug, New)]
+pub struct DivergeDocument<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for DivergeDocument<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("DivergeDocument");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for DivergeDocument<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        if input.messages.divergences().len() >= MAX_DIVERGENCES {
+            return Ok(MutationResult::Skipped);
+        }
+        let rand = state.rand_mut();
+        let source_files = input.workspace.iter_files().filter_map(|(path, entry)| {
+            let doc = entry.as_source_file()?;
+            Some((path.to_str()?.to_owned(), doc))
+        });
+        let Some((path, doc)) = rand.choose(source_files.collect::<Vec<_>>()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let original = doc.content();
+        let start = rand.below_or_zero(original.len() + 1);
+        let end = rand.between(start, original.len());
+        let mut content = original[..end].to_vec();
+        if rand.coinflip(0.5) {
+            content.extend_from_slice(&original[start..]);
+        } else {
+            content.truncate(start);
+            content.extend_from_slice(&original[end..]);
+        }
+        let side = if rand.coinflip(0.5) {
+            DivergentSide::InMemory
+        } else {
+            DivergentSide::OnDisk
+        };
+        let before = rand.below_or_zero(input.messages.len() + 1);
+        input.push_divergence(Divergence {
+            path,
+            before,
+            side,
+            content,
+        });
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, New)]
+pub struct RemoveDivergence<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for RemoveDivergence<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RemoveDivergence");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for RemoveDivergence<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        if let Some(index) = state
+            .rand_mut()
+            .choose(0..input.messages.divergences().len())
+        {
+            input.remove_divergence(index);
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// The maximum number of framing variations in a message sequence.
+const MAX_FRAMING_VARIATIONS: usize = 4;
+
+/// Terminates the header lines of a random payload with `\n` or `\r`.
+#[derive(Debug, New)]
+pub struct VaryHeaderLineEnding<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for VaryHeaderLineEnding<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("VaryHeaderLineEnding");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for VaryHeaderLineEnding<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        if input.messages.framing().len() >= MAX_FRAMING_VARIATIONS {
+            return Ok(MutationResult::Skipped);
+        }
+        let payloads = input.message_sequence().count();
+        let rand = state.rand_mut();
+        let payload = rand.below_or_zero(payloads);
+        let line_ending = if rand.coinflip(0.5) {
+            HeaderLineEnding::Lf
+        } else {
+            HeaderLineEnding::Cr
+        };
+        let framing = input.messages.framing_mut();
+        framing.retain(|it| it.payload != payload);
+        framing.push(FramingVariation {
+            payload,
+            line_ending,
+        });
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, New)]
+pub struct RestoreHeaderLineEnding<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for RestoreHeaderLineEnding<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RestoreHeaderLineEnding");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for RestoreHeaderLineEnding<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let framing = input.messages.framing_mut();
+        if let Some(index) = state.rand_mut().choose(0..framing.len()) {
+            framing.remove(index);
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// The largest number of bytes of a payload written before a [`TransportFault`],
+/// which covers the header and the start of the content.
+const MAX_PARTIAL_BYTES: usize = 64;
+
+/// Injects a random [`TransportFault`], replacing the one of the input.
+#[derive(Debug)]
+pub struct InjectTransportFault<State> {
+    /// The probability that the mutation is applied when scheduled.
+    probability: f64,
+    _state: PhantomData<State>,
+}
+
+impl<State> InjectTransportFault<State> {
+    #[must_use]
+    pub const fn new(probability: f64) -> Self {
+        Self {
+            probability,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<State> Named for InjectTransportFault<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("InjectTransportFault");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for InjectTransportFault<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let messages = input.messages.len();
+        let rand = state.rand_mut();
+        if self.probability <= 0.0 || !rand.coinflip(self.probability) {
+            return Ok(MutationResult::Skipped);
+        }
+        let message = rand.below_or_zero(messages + 1);
+        // Writing no byte of the payload would be the same as closing early.
+        let bytes = rand.between(1, MAX_PARTIAL_BYTES - 1);
+        let fault = match rand.below_or_zero(4) {
+            0 => TransportFault::CloseEarly { before: message },
+            1 => TransportFault::DropMidMessage { message, bytes },
+            2 => TransportFault::HalfCloseAfterShutdown,
+            _ => TransportFault::PartialFinalMessage { bytes },
+        };
+        if input.messages.transport_fault() == Some(fault) {
+            return Ok(MutationResult::Skipped);
+        }
+        input.messages.set_transport_fault(Some(fault));
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Removes the [`TransportFault`] of the input, completing its session again.
+#[derive(Debug, New)]
+pub struct RemoveTransportFault<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for RemoveTransportFault<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RemoveTransportFault");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for RemoveTransportFault<State> {
+    fn mutate(
+        &mut self,
+        _state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        if input.messages.transport_fault().is_none() {
+            return Ok(MutationResult::Skipped);
+        }
+        input.messages.set_transport_fault(None);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Negotiates a random position encoding and sends the positions of a fraction of the messages
+/// in the other unit, probing the assumptions servers make about column units.
+#[derive(Debug)]
+pub struct MismatchPositionEncoding<State> {
+    /// The probability that the positions of a message are sent in the other unit.
+    fraction: f64,
+    _state: PhantomData<State>,
+}
+
+impl<State> MismatchPositionEncoding<State> {
+    #[must_use]
+    pub const fn new(fraction: f64) -> Self {
+        Self {
+            fraction,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<State> Named for MismatchPositionEncoding<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MismatchPositionEncoding");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for MismatchPositionEncoding<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        if self.fraction <= 0.0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let rand = state.rand_mut();
+        let negotiated = if rand.coinflip(0.5) {
+            ColumnUnit::Utf8
+        } else {
+            ColumnUnit::Utf16
+        };
+        let mismatched = input
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, it)| it.position().is_some() || it.range().is_some())
+            .filter(|_| rand.coinflip(self.fraction))
+            .map(|(idx, _)| idx)
+            .collect();
+        let encoding = PositionEncoding {
+            negotiated,
+            mismatched,
+        };
+        if *input.messages.position_encoding() == encoding {
+            return Ok(MutationResult::Skipped);
+        }
+        *input.messages.position_encoding_mut() = encoding;
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Draws the client capabilities of the session again, picking [`CapabilityProfile::Minimal`]
+/// with the given probability.
+///
+/// A probability of one or zero pins every input to one profile.
+#[derive(Debug)]
+pub struct RedrawCapabilityProfile<State> {
+    minimal_probability: f64,
+    _state: PhantomData<State>,
+}
+
+impl<State> RedrawCapabilityProfile<State> {
+    #[must_use]
+    pub const fn new(minimal_probability: f64) -> Self {
+        Self {
+            minimal_probability,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<State> Named for RedrawCapabilityProfile<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RedrawCapabilityProfile");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for RedrawCapabilityProfile<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let profile = CapabilityProfile::draw(state.rand_mut(), self.minimal_probability);
+        if input.messages.capability_profile() == profile {
+            return Ok(MutationResult::Skipped);
+        }
+        input.messages.set_capability_profile(profile);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Opens a source file as a language other than that of its content, either by sending
+/// the `languageId` of another language or by adding a copy of the file with the extension
+/// of another language.
+#[derive(Debug, New)]
+pub struct MismatchLanguage<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for MismatchLanguage<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MismatchLanguage");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for MismatchLanguage<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let rand = state.rand_mut();
+        let source_files = input.workspace.iter_files().filter_map(|(path, entry)| {
+            let doc = entry.as_source_file()?;
+            Some((path.to_str()?.to_owned(), doc))
+        });
+        let Some((path, doc)) = rand.choose(source_files.collect::<Vec<_>>()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let other_languages = Language::ALL
+            .into_iter()
+            .chain(Language::registered())
+            .filter(|&it| it != doc.language());
+        let other = rand
+            .choose(other_languages)
+            .expect("There are other languages");
+        if rand.coinflip(0.5) {
+            let language_id = other.lsp_language_id().to_owned();
+            let overrides = input.messages.language_id_overrides_mut();
+            overrides.retain(|it| it.path != path);
+            overrides.push(LanguageIdOverride { path, language_id });
+        } else {
+            let ext = rand
+                .choose(other.file_extensions())
+                .expect("Languages have extensions");
+            let copy = FileSystemEntry::File(WorkspaceEntry::SourceFile(doc.clone()));
+            if !input
+                .workspace
+                .insert_mutable(&format!("mismatched.{ext}"), copy)
+            {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Puts an identifier the server minted in an earlier execution into a message:
+/// a result id into a delta or diagnostic request, or a progress token
+/// into a `window/workDoneProgress/cancel` notification.
+///
+/// The server was restarted since it minted the identifier, so it refers to a result
+/// or progress the server does not know. The notification is appended
+/// if no message takes an identifier.
+#[derive(Debug, New)]
+pub struct ReuseMintedId<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for ReuseMintedId<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ReuseMintedId");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for ReuseMintedId<State>
+where
+    State: HasRand + HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some(pool) = state.metadata_map().get::<MintedIds>() else {
+            return Ok(MutationResult::Skipped);
+        };
+        let pool_sizes = (pool.result_ids.len(), pool.progress_tokens.len());
+        let max_messages = SequenceLengthPolicy::of(state).max_messages;
+        // Only the chosen identifiers are copied out of the pool.
+        let rand = state.rand_mut();
+        let result_idx = (pool_sizes.0 > 0).then(|| rand.below_or_zero(pool_sizes.0));
+        let token_idx = (pool_sizes.1 > 0).then(|| rand.below_or_zero(pool_sizes.1));
+        let pool = state
+            .metadata_map()
+            .get::<MintedIds>()
+            .expect("The pool was found above");
+        let result_id = result_idx.map(|it| pool.result_ids[it].clone());
+        let token = token_idx.map(|it| pool.progress_tokens[it].clone());
+        let rand = state.rand_mut();
+
+        let slots = input.messages.iter().positions(|it| match it {
+            lsp::LspMessage::SemanticTokensFullDeltaRequest(_)
+            | lsp::LspMessage::DocumentDiagnosticRequest(_) => result_id.is_some(),
+            lsp::LspMessage::WorkspaceDiagnosticRequest(params) => {
+                result_id.is_some() && !params.previous_result_ids.is_empty()
+            }
+            lsp::LspMessage::WorkDoneProgressCancel(_) => token.is_some(),
+            _ => false,
+        });
+        if let Some(index) = rand.choose(slots) {
+            match input
+                .messages
+                .message_mut(index)
+                .expect("The slots are indices of messages")
+            {
+                lsp::LspMessage::SemanticTokensFullDeltaRequest(params) => {
+                    params.previous_result_id = result_id.unwrap_or_default();
+                }
+                lsp::LspMessage::DocumentDiagnosticRequest(params) => {
+                    params.previous_result_id = result_id;
+                }
+                lsp::LspMessage::WorkspaceDiagnosticRequest(params) => {
+                    if let Some(previous) = rand.choose(params.previous_result_ids.iter_mut()) {
+                        previous.value = result_id.unwrap_or_default();
+                    }
+                }
+                lsp::LspMessage::WorkDoneProgressCancel(params) => {
+                    params.token = token.expect("The slot takes a token only if there is one");
+                }
+                _ => unreachable!("Only messages taking identifiers are selected"),
+            }
+            Ok(MutationResult::Mutated)
+        } else if let Some(token) = token
+            && input.messages.len() < max_messages
+        {
+            let cancel = WorkDoneProgressCancelParams { token };
+            input.push_message(lsp::LspMessage::WorkDoneProgressCancel(cancel));
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+}
+
+/// The number of inputs mutated by [`DuplicateRequest`] and how many of them were added to
+/// the corpus, telling whether probing repeated requests finds new behavior.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, SerdeAny)]
+pub struct DuplicationStats {
+    pub executions: u64,
+    pub new_corpus_entries: u64,
+}
+
+/// Repeats a request back to back, probing caching and idempotency bugs
+/// such as double frees on repeated resolves or duplicated registrations.
+///
+/// The copies get fresh IDs as the messages are numbered when the session is sent.
+#[derive(Debug, New)]
+pub struct DuplicateRequest<State> {
+    /// Whether the input of the current execution was mutated by this mutator.
+    #[new(default)]
+    applied: bool,
+    _state: PhantomData<State>,
+}
+
+impl<State> DuplicateRequest<State> {
+    /// The largest number of copies added at once.
+    const MAX_COPIES: usize = 4;
+}
+
+impl<State> Named for DuplicateRequest<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("DuplicateRequest");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for DuplicateRequest<State>
+where
+    State: HasRand + HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let rand = state.rand_mut();
+        let requests = input.messages.iter().positions(lsp::LspMessage::is_request);
+        let Some(index) = rand.choose(requests) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let copies = rand.between(1, Self::MAX_COPIES);
+        let request = input.messages[index].clone();
+        for _ in 0..copies {
+            input.insert_message(index + 1, request.clone());
+        }
+        self.applied = true;
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut State,
+        new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        if mem::take(&mut self.applied) {
+            let stats = state.metadata_or_insert_with(DuplicationStats::default);
+            stats.executions += 1;
+            if new_corpus_id.is_some() {
+                stats.new_corpus_entries += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Swaps two random messages.
+#[derive(Debug, New)]
+pub struct SwapRequests<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for SwapRequests<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SwapRequests");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for SwapRequests<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let len = input.messages.len();
+        if len < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let rand = state.rand_mut();
+        let a = rand.below_or_zero(len);
+        let b = rand.below_or_zero(len);
+        input.swap_messages(a, b);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Shuffles a random window spanning at least half of the messages.
+#[derive(Debug, New)]
+pub struct ShuffleRequests<State> {
+    _state: PhantomData<State>,
+}
+
+impl<State> Named for ShuffleRequests<State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ShuffleRequests");
+        &NAME
+    }
+}
+
+impl<State> Mutator<LspInput, State> for ShuffleRequests<State>
+where
+    State: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let len = input.messages.len();
+        if len < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let rand = state.rand_mut();
+        let window_len = len.div_ceil(2) + rand.below_or_zero(len / 2 + 1);
+        let start = rand.below_or_zero(len - window_len + 1);
+        let mut order: Vec<_> = (0..window_len).collect();
+        for idx in (1..order.len()).rev() {
+            order.swap(idx, rand.below_or_zero(idx + 1));
+        }
+        input.permute_messages(start, &order);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+#[must_use]
+pub fn message_mutations<State>(
+    config: &GeneratorsConfig,
+) -> impl MutatorsTuple<LspInput, State> + NamedTuple + use<State>
+where
+    State: HasRand + HasMetadata + HasCurrentTestcase<LspInput> + 'static,
+{
+    let swap = tuple_list![SwapRequests::new()];
+    append_navigation_messages(config)
+        .merge(append_symbol_messages(config))
+        .merge(append_formatting_messages(config))
+        .merge(append_hierarchy_messages(config))
+        .merge(append_workspace_messages(config))
+        .merge(append_diagnostic_messages(config))
+        .merge(append_tracing_misc_messages(config))
+        .merge(swap)
+        .merge(tuple_list![DuplicateRequest::new(), ReuseMintedId::new()])
+        .merge(open_order_mutations())
+        .merge(divergence_mutations())
+        .merge(framing_mutations())
+        .merge(transport_fault_mutations(
+            config.invalid_input.transport_fault,
+        ))
+        .merge(tuple_list![
+            MismatchPositionEncoding::new(config.invalid_input.position_encoding_mismatch),
+            MismatchLanguage::new().with_probability(config.invalid_input.language_mismatch),
+            RedrawCapabilityProfile::new(config.minimal_capabilities),
+            InjectExperimentalCapabilities::new(config.experimental_capabilities.clone()),
+            RedrawConfiguration::new(config.configuration_matrix.clone()),
+        ])
+        .merge(message_reductions())
+}
+
+#[must_use]
+pub fn divergence_mutations<State>()
+-> tuple_list_type![DivergeDocument<State>, RemoveDivergence<State>]
+where
+    State: HasRand,
+{
+    tuple_list![DivergeDocument::new(), RemoveDivergence::new()]
+}
+
+#[must_use]
+pub fn framing_mutations<State>()
+-> tuple_list_type![VaryHeaderLineEnding<State>, RestoreHeaderLineEnding<State>]
+where
+    State: HasRand,
+{
+    tuple_list![VaryHeaderLineEnding::new(), RestoreHeaderLineEnding::new()]
+}
+
+#[must_use]
+pub fn transport_fault_mutations<State>(
+    probability: f64,
+) -> tuple_list_type![InjectTransportFault<State>, RemoveTransportFault<State>]
+where
+    State: HasRand,
+{
+    tuple_list![
+        InjectTransportFault::new(probability),
+        RemoveTransportFault::new()
+    ]
+}
+
+#[must_use]
+pub fn open_order_mutations<State>() -> tuple_list_type![PermuteOpenOrder<State>, DelayOpen<State>]
+where
+    State: HasRand,
+{
+    tuple_list![PermuteOpenOrder::new(), DelayOpen::new()]
+}
+
+#[must_use]
+pub fn message_reductions<State>() -> tuple_list_type![DropRandomMessage<State>]
+where
+    State: HasRand + HasMetadata,
+{
+    tuple_list![DropRandomMessage::new()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence() -> LspMessageSequence {
+        let mut sequence = LspMessageSequence {
+            inner: vec![
+                lsp::LspMessage::Initialized(lsp_types::InitializedParams {}),
+                lsp::LspMessage::Shutdown(()),
+            ],
+            ..LspMessageSequence::default()
+        };
+        sequence.opens.push(ScheduledOpen {
+            path: "a.c".to_owned(),
+            before: 1,
+        });
+        sequence.fault = Some(TransportFault::CloseEarly { before: 1 });
+        sequence.encoding.mismatched.push(1);
+        sequence
+    }
+
+    #[test]
+    fn insert_message_keeps_placements_on_the_shifted_message() {
+        let mut sequence = sequence();
+        sequence.insert_message(1, lsp::LspMessage::Exit(()));
+
+        assert_eq!(sequence[2], lsp::LspMessage::Shutdown(()));
+        assert_eq!(sequence.opens[0].before, 2);
+        assert_eq!(
+            sequence.fault,
+            Some(TransportFault::CloseEarly { before: 2 })
+        );
+        assert_eq!(sequence.encoding.mismatched, [2]);
+        assert_eq!(sequence.column_unit(1), sequence.encoding.negotiated);
+    }
+
+    #[test]
+    fn insert_message_after_placements_keeps_them() {
+        let mut sequence = sequence();
+        sequence.insert_message(2, lsp::LspMessage::Exit(()));
+
+        assert_eq!(sequence.opens[0].before, 1);
+        assert_eq!(sequence.encoding.mismatched, [1]);
+    }
+
+    #[test]
+    fn remove_message_moves_placements_to_the_next_message() {
+        let mut sequence = sequence();
+        sequence.insert_message(0, lsp::LspMessage::Exit(()));
+        let removed = sequence.remove_message(0);
+
+        assert_eq!(removed, lsp::LspMessage::Exit(()));
+        assert_eq!(sequence, self::sequence());
+
+        sequence.remove_message(1);
+        assert_eq!(sequence.opens[0].before, 1);
+        assert!(sequence.encoding.mismatched.is_empty());
+    }
 }
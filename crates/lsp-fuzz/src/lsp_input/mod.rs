@@ -1,11 +1,14 @@
 use std::{
     borrow::Cow,
+    collections::HashMap,
     fs::File,
     hash::{DefaultHasher, Hash, Hasher},
     io::BufWriter,
+    mem,
     path::{Path, PathBuf},
 };
 
+use configuration::ConfigurationMatrix;
 use derive_new::new as New;
 use libafl::{
     HasMetadata,
@@ -18,8 +21,9 @@ use libafl::{
 use libafl_bolts::{HasLen, Named, ownedref::OwnedSlice, rands::Rand};
 use lsp_fuzz_grammars::Language;
 use lsp_types::Uri;
-use messages::LspMessageSequence;
+use messages::{CapabilityProfile, DivergentSide, LspMessageSequence};
 use serde::{Deserialize, Serialize};
+use tuple_list::{tuple_list, tuple_list_type};
 
 use crate::{
     execution::workspace_observer::HasWorkspace,
@@ -27,16 +31,27 @@ use crate::{
     lsp,
     text_document::{
         GrammarBasedMutation, TextDocument,
-        generation::{GrammarContextLookup, NamedNodeGenerator, RandomRuleSelectionStrategy},
+        diversity::SeedDiversity,
+        generation::{
+            GenerationFailureStats, GrammarContextLookup, NamedNodeGenerator, RuleWeighting,
+        },
+        mutations::{havoc::HavocBytes, text_document_selectors::RandomDoc},
+        scopes,
     },
-    utils::AflContext,
+    utils::{AflContext, RandExt},
 };
 
 pub type FileContentInput = BytesInput;
 
+pub mod calibration_check;
+pub mod configuration;
+pub mod experimental_capabilities;
+pub mod format;
+pub mod import;
 pub mod message_edit;
 pub mod messages;
 pub mod ops_curiosity;
+pub mod sequence_length;
 pub mod server_response;
 mod session;
 pub mod uri;
@@ -55,18 +70,42 @@ pub enum WorkspaceEntry {
     /// It is only written to the workspace directory for LSP servers that needs it.
     /// (e.g., `package.json`, `Cargo.toml`).
     Skeleton(Vec<u8>),
+
+    /// An entry that mutations must not modify, replace, or remove
+    ///
+    /// Used for the files every input of a workspace depends on, e.g., the `rust-project.json`
+    /// without which the server does not analyze the documents. The entry is still read,
+    /// written to the workspace directory, and opened like the wrapped one.
+    Immutable(Box<WorkspaceEntry>),
 }
 
 impl WorkspaceEntry {
+    /// Marks the entry immutable.
     #[must_use]
-    pub const fn as_source_file(&self) -> Option<&TextDocument> {
-        if let WorkspaceEntry::SourceFile(doc) = self {
-            Some(doc)
+    pub fn into_immutable(self) -> Self {
+        if self.is_immutable() {
+            self
         } else {
-            None
+            WorkspaceEntry::Immutable(Box::new(self))
         }
     }
 
+    /// Returns if mutations must leave the entry intact.
+    #[must_use]
+    pub const fn is_immutable(&self) -> bool {
+        matches!(self, WorkspaceEntry::Immutable(_))
+    }
+
+    #[must_use]
+    pub fn as_source_file(&self) -> Option<&TextDocument> {
+        match self {
+            WorkspaceEntry::SourceFile(doc) => Some(doc),
+            WorkspaceEntry::Immutable(entry) => entry.as_source_file(),
+            WorkspaceEntry::Skeleton(_) => None,
+        }
+    }
+
+    /// Returns the document of a mutable source file.
     #[must_use]
     pub const fn as_source_file_mut(&mut self) -> Option<&mut TextDocument> {
         if let WorkspaceEntry::SourceFile(doc) = self {
@@ -78,13 +117,14 @@ impl WorkspaceEntry {
 
     #[must_use]
     pub fn as_skeleton(&self) -> Option<&[u8]> {
-        if let WorkspaceEntry::Skeleton(bytes) = self {
-            Some(bytes.as_slice())
-        } else {
-            None
+        match self {
+            WorkspaceEntry::Skeleton(bytes) => Some(bytes.as_slice()),
+            WorkspaceEntry::Immutable(entry) => entry.as_skeleton(),
+            WorkspaceEntry::SourceFile(_) => None,
         }
     }
 
+    /// Returns the content of a mutable skeleton file.
     #[must_use]
     pub const fn as_skeleton_mut(&mut self) -> Option<&mut Vec<u8>> {
         if let WorkspaceEntry::Skeleton(bytes) = self {
@@ -100,6 +140,7 @@ impl HasLen for WorkspaceEntry {
         match self {
             WorkspaceEntry::SourceFile(doc) => doc.len(),
             WorkspaceEntry::Skeleton(bytes) => bytes.len(),
+            WorkspaceEntry::Immutable(entry) => entry.len(),
         }
     }
 }
@@ -109,10 +150,32 @@ impl HasTargetBytes for WorkspaceEntry {
         match self {
             WorkspaceEntry::SourceFile(doc) => doc.target_bytes(),
             WorkspaceEntry::Skeleton(bytes) => bytes.as_slice().into(),
+            WorkspaceEntry::Immutable(entry) => entry.target_bytes(),
         }
     }
 }
 
+impl FileSystemDirectory<WorkspaceEntry> {
+    /// Adds `entry` at `path` like [`Self::insert_path`], unless it would replace an immutable
+    /// entry or a directory containing one. Mutations add entries through this method.
+    ///
+    /// Returns `false` if the entry is left out to protect an immutable entry.
+    pub fn insert_mutable(&mut self, path: &str, entry: FileSystemEntry<WorkspaceEntry>) -> bool {
+        let protected = match self.get(path) {
+            Some(FileSystemEntry::File(file)) => file.is_immutable(),
+            Some(FileSystemEntry::Directory(dir)) => {
+                dir.iter_files().any(|(_, file)| file.is_immutable())
+            }
+            None => false,
+        };
+        if protected {
+            return false;
+        }
+        self.insert_path(path, entry);
+        true
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub struct LspInput {
     pub messages: LspMessageSequence,
@@ -180,9 +243,7 @@ impl Input for LspInput {
         P: AsRef<Path>,
     {
         let file = File::create(path)?;
-        let buf_writer = BufWriter::new(file);
-        ciborium::into_writer(self, buf_writer)
-            .map_err(|e| libafl::Error::serialize(format!("{e:#?}")))
+        format::write_input(self, BufWriter::new(file))
     }
 
     fn from_file<P>(path: P) -> Result<Self, libafl::Error>
@@ -190,8 +251,7 @@ impl Input for LspInput {
         P: AsRef<Path>,
     {
         let file = File::open(path)?;
-        let buf_reader = std::io::BufReader::new(file);
-        ciborium::from_reader(buf_reader).map_err(|e| libafl::Error::serialize(format!("{e:#?}")))
+        format::read_input_upgrading(std::io::BufReader::new(file))
     }
 }
 
@@ -204,14 +264,13 @@ impl HasLen for LspInput {
 #[derive(Debug, New)]
 pub struct LspInputBytesConverter {
     workspace_root: PathBuf,
+    instance_id: String,
 }
 
 impl ToTargetBytes<LspInput> for LspInputBytesConverter {
     fn to_target_bytes<'a>(&mut self, input: &'a LspInput) -> OwnedSlice<'a, u8> {
-        let input_hash = input.workspace_hash();
-        let workspace_dir = self
-            .workspace_root
-            .join(format!("{}{input_hash}", LspInput::WORKSPACE_DIR_PREFIX));
+        let dir_name = LspInput::workspace_dir_name(&self.instance_id, input.workspace_hash());
+        let workspace_dir = self.workspace_root.join(dir_name);
         input.request_bytes(&workspace_dir).into()
     }
 }
@@ -220,17 +279,43 @@ impl HasWorkspace for LspInput {
     fn workspace_hash(&self) -> u64 {
         let mut hasher = ahash::AHasher::default();
         self.workspace.hash(&mut hasher);
+        // Files diverging on disk change the content of the workspace directory.
+        self.messages
+            .divergences()
+            .iter()
+            .filter(|it| it.side == DivergentSide::OnDisk)
+            .for_each(|it| it.hash(&mut hasher));
         hasher.finish()
     }
 
     fn setup_workspace(&self, workspace_root: &Path) -> Result<(), std::io::Error> {
-        self.workspace.write_to_fs(workspace_root)
+        self.workspace.write_to_fs(workspace_root)?;
+        session::write_divergent_files(self, workspace_root)
     }
 }
 
 impl LspInput {
     pub const WORKSPACE_DIR_PREFIX: &str = "lsp-fuzz-workspace_";
 
+    /// The name of the directory in which the fuzzer instance `instance_id` sets up
+    /// a workspace with `workspace_hash`.
+    ///
+    /// Parallel instances sharing a temporary directory use distinct instance IDs,
+    /// so that they never write into the workspaces of each other.
+    #[must_use]
+    pub fn workspace_dir_name(instance_id: &str, workspace_hash: u64) -> String {
+        format!(
+            "{}{workspace_hash}",
+            Self::workspace_dir_prefix(instance_id)
+        )
+    }
+
+    /// The prefix of the names of all workspace directories of the fuzzer instance `instance_id`.
+    #[must_use]
+    pub fn workspace_dir_prefix(instance_id: &str) -> String {
+        format!("{}{instance_id}_", Self::WORKSPACE_DIR_PREFIX)
+    }
+
     /// Converts a localized `file://` workspace URI back into the virtual `lsp-fuzz://` form.
     ///
     /// # Panics
@@ -253,6 +338,9 @@ impl LspInput {
 
     /// Expands the stored input into the complete LSP session message stream.
     ///
+    /// The messages after a [`messages::TransportFault`], including the one partially written,
+    /// are not part of the stream.
+    ///
     /// # Panics
     ///
     /// Panics if a workspace source file path is not valid UTF-8 or if a generated virtual URI
@@ -260,6 +348,168 @@ impl LspInput {
     pub fn message_sequence(&self) -> impl Iterator<Item = lsp::LspMessage> + use<'_> {
         session::message_sequence(self)
     }
+
+    /// The full message sequence of the session, including where the client closes the connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Self::message_sequence`].
+    pub fn session_elements(
+        &self,
+    ) -> impl Iterator<Item = messages::SequenceElement<lsp::LspMessage>> + use<'_> {
+        session::session_elements(self)
+    }
+
+    /// Inserts `message` into the message sequence at `index`, keeping the placements and
+    /// framing variations attached to the same messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of messages.
+    pub fn insert_message(&mut self, index: usize, message: lsp::LspMessage) {
+        self.messages.insert_message(index, message);
+        let payload = *session::message_payloads(self)[index].end();
+        self.messages.insert_payload(payload);
+    }
+
+    /// Appends `message` to the message sequence.
+    pub fn push_message(&mut self, message: lsp::LspMessage) {
+        self.insert_message(self.messages.len(), message);
+    }
+
+    /// Removes the message at `index` from the message sequence, keeping the placements and
+    /// framing variations attached to the same messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_message(&mut self, index: usize) -> lsp::LspMessage {
+        let payload = *session::message_payloads(self)[index].end();
+        let removed = self.messages.remove_message(index);
+        self.messages.remove_payload(payload);
+        removed
+    }
+
+    /// Swaps the messages at `a` and `b`, keeping the placements and framing variations
+    /// attached to the same messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` or `b` is out of bounds.
+    pub fn swap_messages(&mut self, a: usize, b: usize) {
+        let (start, end) = (a.min(b), a.max(b));
+        let mut order: Vec<_> = (0..=end - start).collect();
+        order.swap(0, end - start);
+        self.permute_messages(start, &order);
+    }
+
+    /// Swaps the source files opened by the `a`-th and `b`-th scheduled opens, keeping the
+    /// framing variations attached to the same opens.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` or `b` is out of bounds.
+    pub fn swap_opens(&mut self, a: usize, b: usize) {
+        let previous = [
+            session::open_payload(self, a),
+            session::open_payload(self, b),
+        ];
+        let opens = self.messages.open_schedule_mut();
+        let path = mem::take(&mut opens[a].path);
+        opens[a].path = mem::replace(&mut opens[b].path, path);
+        // The file previously opened by `a` is now opened by `b`, and vice versa.
+        let current = [
+            session::open_payload(self, b),
+            session::open_payload(self, a),
+        ];
+        match (previous, current) {
+            ([Some(a_from), Some(b_from)], [Some(a_to), Some(b_to)]) => {
+                self.messages.relocate_payloads(|it| {
+                    if it == a_from {
+                        a_to
+                    } else if it == b_from {
+                        b_to
+                    } else {
+                        it
+                    }
+                });
+            }
+            ([Some(from), None], [Some(to), _]) | ([None, Some(from)], [_, Some(to)]) => {
+                self.messages.move_payload(from, to);
+            }
+            _ => {}
+        }
+    }
+
+    /// Places the `index`-th scheduled open before the message at `before`, keeping the framing
+    /// variations attached to the same payloads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn move_open(&mut self, index: usize, before: usize) {
+        let previous = session::open_payload(self, index);
+        self.messages.open_schedule_mut()[index].before = before;
+        if let (Some(from), Some(to)) = (previous, session::open_payload(self, index)) {
+            self.messages.move_payload(from, to);
+        }
+    }
+
+    /// Adds `divergence` to the message sequence, keeping the framing variations attached to
+    /// the same payloads.
+    pub fn push_divergence(&mut self, divergence: messages::Divergence) {
+        self.messages.divergences_mut().push(divergence);
+        let index = self.messages.divergences().len() - 1;
+        if let Some(payload) = session::divergence_payload(self, index) {
+            self.messages.insert_payload(payload);
+        }
+    }
+
+    /// Removes the `index`-th divergence from the message sequence, keeping the framing
+    /// variations attached to the same payloads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_divergence(&mut self, index: usize) -> messages::Divergence {
+        let payload = session::divergence_payload(self, index);
+        let removed = self.messages.divergences_mut().remove(index);
+        if let Some(payload) = payload {
+            self.messages.remove_payload(payload);
+        }
+        removed
+    }
+
+    /// Reorders the messages from `start` on, so that the `k`-th of them is the one previously
+    /// at `start + order[k]`, keeping the placements and framing variations attached to the same
+    /// messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is not a permutation of `0..order.len()` or if the reordered messages
+    /// are out of bounds.
+    pub fn permute_messages(&mut self, start: usize, order: &[usize]) {
+        let previous = session::message_payloads(self);
+        self.messages.permute_messages(start, order);
+        let current = session::message_payloads(self);
+        // Each message moves along with the opens and divergences placed before it,
+        // so the payloads of a message keep their offsets from the message.
+        let moves: Vec<_> = order
+            .iter()
+            .enumerate()
+            .map(|(new, &old)| {
+                let payloads = previous[start + old].clone();
+                let offset = current[start + new].end().wrapping_sub(*payloads.end());
+                (payloads, offset)
+            })
+            .collect();
+        self.messages.relocate_payloads(|payload| {
+            moves
+                .iter()
+                .find(|(payloads, _)| payloads.contains(&payload))
+                .map_or(payload, |(_, offset)| payload.wrapping_add(*offset))
+        });
+    }
 }
 
 #[derive(Debug, derive_more::Constructor)]
@@ -309,38 +559,256 @@ where
     }
 }
 
+/// Mutations that disregard the structure of inputs, for campaigns stuck on a coverage plateau:
+/// byte havoc on documents, header framing variations, and shuffles of large parts of
+/// the message sequence.
+///
+/// See [`crate::stages::StagnationFallbackStage`].
+#[must_use]
+pub fn stagnation_mutations<State>() -> tuple_list_type![
+    HavocBytes<RandomDoc>,
+    messages::VaryHeaderLineEnding<State>,
+    messages::ShuffleRequests<State>,
+    messages::DropRandomMessage<State>,
+]
+where
+    State: HasRand + HasMetadata,
+{
+    tuple_list![
+        HavocBytes::new(),
+        messages::VaryHeaderLineEnding::new(),
+        messages::ShuffleRequests::new(),
+        messages::DropRandomMessage::new(),
+    ]
+}
+
+/// A file added to every generated workspace, e.g., a discovery file such as `.bsp/sbt.json`
+/// that a server reads at startup.
+///
+/// The files are added as skeleton entries, so they are written before the target starts
+/// and left alone by the mutators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceTemplate {
+    /// The path of the file relative to the workspace root.
+    pub path: String,
+    /// The content of the file, where `{language_id}` and `{main_file}` are replaced with
+    /// the language identifier and the path of the generated document.
+    pub content: String,
+}
+
+impl WorkspaceTemplate {
+    #[must_use]
+    pub fn render(&self, language: Language, main_file: &str) -> Vec<u8> {
+        self.content
+            .replace("{language_id}", language.lsp_language_id())
+            .replace("{main_file}", main_file)
+            .into_bytes()
+    }
+}
+
 #[derive(Debug, New)]
 pub struct LspInputGenerator<'a> {
     grammar_lookup: &'a GrammarContextLookup,
+    #[new(default)]
+    language_weights: HashMap<Language, usize>,
+    #[new(default)]
+    workspace_templates: Vec<WorkspaceTemplate>,
+    #[new(value = "DEFAULT_RETRY_BUDGET")]
+    retry_budget: usize,
+    #[new(default)]
+    minimal_capabilities: f64,
+    #[new(default)]
+    configuration_matrix: Option<ConfigurationMatrix>,
+    #[new(default)]
+    rule_weighting: RuleWeighting,
+    #[new(default)]
+    diversity: Option<SeedDiversity>,
+    #[new(default)]
+    resolve_identifiers: bool,
+}
+
+/// The default number of derivations tried before giving up on generating a document.
+///
+/// Derivations fail when a symbol has neither a rule nor a fragment at the recursion limit.
+/// Pruning the grammar removes the derivations that always fail, so this only guards against
+/// grammars whose remaining derivations fail by chance most of the time.
+pub const DEFAULT_RETRY_BUDGET: usize = 1000;
+
+impl LspInputGenerator<'_> {
+    /// Picks languages in proportion to `weights` instead of uniformly.
+    ///
+    /// Languages without a weight are never picked, so the weights also act as an allowlist.
+    /// An empty map picks all the languages uniformly.
+    #[must_use]
+    pub fn with_language_weights(self, weights: HashMap<Language, usize>) -> Self {
+        Self {
+            language_weights: weights,
+            ..self
+        }
+    }
+
+    /// Adds the files rendered from `templates` to every generated workspace.
+    #[must_use]
+    pub fn with_workspace_templates(self, templates: Vec<WorkspaceTemplate>) -> Self {
+        Self {
+            workspace_templates: templates,
+            ..self
+        }
+    }
+
+    /// Advertises the minimal client capabilities in a fraction of the generated inputs,
+    /// see [`CapabilityProfile`].
+    #[must_use]
+    pub fn with_minimal_capabilities(self, probability: f64) -> Self {
+        Self {
+            minimal_capabilities: probability,
+            ..self
+        }
+    }
+
+    /// Draws the configuration of every generated input from `matrix`.
+    #[must_use]
+    pub fn with_configuration_matrix(self, matrix: Option<ConfigurationMatrix>) -> Self {
+        Self {
+            configuration_matrix: matrix,
+            ..self
+        }
+    }
+
+    /// Chooses the derivations of the generated documents by `rule_weighting`.
+    #[must_use]
+    pub fn with_rule_weighting(self, rule_weighting: RuleWeighting) -> Self {
+        Self {
+            rule_weighting,
+            ..self
+        }
+    }
+
+    /// Regenerates documents with a [`ShapeFingerprint::similarity`] of at least `threshold`
+    /// to a document generated before, within the retry budget.
+    ///
+    /// A document too similar to the earlier ones is still generated when the retry budget runs
+    /// out, since a duplicate is more useful than no document.
+    ///
+    /// [`ShapeFingerprint::similarity`]: crate::text_document::diversity::ShapeFingerprint::similarity
+    #[must_use]
+    pub fn with_seed_similarity(self, threshold: Option<f64>) -> Self {
+        Self {
+            diversity: threshold.map(SeedDiversity::new),
+            ..self
+        }
+    }
+
+    /// Rewrites the identifiers of the generated documents to the identifiers declared in them,
+    /// see [`resolve_identifiers`](crate::text_document::scopes::resolve_identifiers).
+    #[must_use]
+    pub fn with_identifier_resolution(self, resolve_identifiers: bool) -> Self {
+        Self {
+            resolve_identifiers,
+            ..self
+        }
+    }
+
+    /// Sets the number of derivations tried for each document before failing the generation.
+    ///
+    /// The failed derivations are counted in [`GenerationFailureStats`].
+    #[must_use]
+    pub fn with_retry_budget(self, retry_budget: usize) -> Self {
+        Self {
+            retry_budget,
+            ..self
+        }
+    }
 }
 
 impl<State> Generator<LspInput, State> for LspInputGenerator<'_>
 where
-    State: HasRand,
+    State: HasRand + HasMetadata,
 {
     fn generate(&mut self, state: &mut State) -> Result<LspInput, libafl::Error> {
         let rand = state.rand_mut();
-        let grammar = rand
-            .choose(self.grammar_lookup.iter())
-            .afl_context("The grammar lookup context is empry")?;
+        let grammar = if self.language_weights.is_empty() {
+            rand.choose(self.grammar_lookup.iter())
+        } else {
+            rand.weighted_choose(self.grammar_lookup.iter().map(|it| {
+                let weight = self.language_weights.get(&it.language());
+                (it, weight.copied().unwrap_or(0))
+            }))
+        }
+        .afl_context("No language available for generation")?;
         let language = grammar.language();
         let ext = rand
             .choose(language.file_extensions())
             .afl_context("The language has no extensions")?;
-        let document_content = loop {
-            let selection_strategy = RandomRuleSelectionStrategy;
-            let generator = NamedNodeGenerator::new(grammar, selection_strategy);
-            let generate_node = generator.generate(grammar.start_symbol(), state);
-            if let Ok(code) = generate_node {
-                break code;
+        let generator = NamedNodeGenerator::new(grammar, &self.rule_weighting);
+        let mut document = None;
+        let mut similar_document = None;
+        for _ in 0..self.retry_budget {
+            let generated = generator.generate(grammar.start_symbol(), state);
+            let stats = state.metadata_or_insert_with(GenerationFailureStats::default);
+            stats.attempts += 1;
+            match generated {
+                Ok(code) => {
+                    let mut text_document = TextDocument::new(language, code);
+                    if self.resolve_identifiers {
+                        scopes::resolve_identifiers(&mut text_document, state.rand_mut());
+                    }
+                    let admitted = self
+                        .diversity
+                        .as_mut()
+                        .is_none_or(|it| it.admit(&text_document));
+                    generator.conclude(state, admitted);
+                    if admitted {
+                        document = Some(text_document);
+                        break;
+                    }
+                    state
+                        .metadata_or_insert_with(GenerationFailureStats::default)
+                        .similar += 1;
+                    similar_document = Some(text_document);
+                }
+                Err(error) => {
+                    stats.record(language, &error);
+                    generator.conclude(state, false);
+                }
             }
+        }
+        let Some(text_document) = document.or(similar_document) else {
+            state
+                .metadata_or_insert_with(GenerationFailureStats::default)
+                .exhausted += 1;
+            return Err(libafl::Error::illegal_state(format!(
+                "Fail to generate a {language} document in {} attempts",
+                self.retry_budget
+            )));
         };
-        let mut text_document = TextDocument::new(language, document_content.clone());
-        text_document.update_metadata();
 
-        let workspace = session::workspace_for_document(language, text_document, ext);
+        let mut workspace =
+            session::workspace_for_document(state.rand_mut(), language, text_document, ext);
+        if !self.workspace_templates.is_empty() {
+            let main_file = workspace
+                .iter_files()
+                .find_map(|(path, entry)| entry.as_source_file().map(|_| path))
+                .map(|it| it.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            for template in &self.workspace_templates {
+                let content = template.render(language, &main_file);
+                workspace.insert_path(
+                    &template.path,
+                    FileSystemEntry::File(WorkspaceEntry::Skeleton(content)),
+                );
+            }
+        }
+        let mut messages = LspMessageSequence::default();
+        messages.set_capability_profile(CapabilityProfile::draw(
+            state.rand_mut(),
+            self.minimal_capabilities,
+        ));
+        if let Some(matrix) = &self.configuration_matrix {
+            messages.set_configuration(Some(matrix.draw(state.rand_mut())));
+        }
         Ok(LspInput {
-            messages: LspMessageSequence::default(),
+            messages,
             workspace,
         })
     }
@@ -349,6 +817,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utf8::Utf8Input;
+    use libafl_bolts::rands::StdRand;
     use std::str::FromStr;
 
     #[test]
@@ -375,4 +845,551 @@ mod tests {
         // Then the original URI is returned unchanged
         assert_eq!(lifted.as_str(), "file:///other/path");
     }
+
+    #[test]
+    fn test_scheduled_opens() {
+        let source_file = |content: &str| {
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(TextDocument::new(
+                Language::C,
+                content.as_bytes().to_vec(),
+            )))
+        };
+        let mut input = LspInput {
+            messages: LspMessageSequence::default(),
+            workspace: FileSystemDirectory::from([
+                (Utf8Input::new("a.c".to_owned()), source_file("int a;")),
+                (Utf8Input::new("b.c".to_owned()), source_file("int b;")),
+            ]),
+        };
+        input.push_message(lsp::LspMessage::Initialized(
+            lsp_types::InitializedParams {},
+        ));
+        input
+            .messages
+            .open_schedule_mut()
+            .push(messages::ScheduledOpen {
+                path: "a.c".to_owned(),
+                before: 1,
+            });
+
+        let methods: Vec<_> = input
+            .message_sequence()
+            .map(|it| match it {
+                lsp::LspMessage::DidOpenTextDocument(params) => {
+                    params.text_document.uri.as_str().to_owned()
+                }
+                other => other.method().to_owned(),
+            })
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                "initialize",
+                "initialized",
+                "lsp-fuzz://b.c",
+                "initialized",
+                "lsp-fuzz://a.c",
+                "shutdown",
+                "exit",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_swap_messages_moves_placements() {
+        let mut input = LspInput {
+            messages: LspMessageSequence::default(),
+            workspace: FileSystemDirectory::from([(
+                Utf8Input::new("a.c".to_owned()),
+                FileSystemEntry::File(WorkspaceEntry::SourceFile(TextDocument::new(
+                    Language::C,
+                    b"int a;".to_vec(),
+                ))),
+            )]),
+        };
+        input.push_message(lsp::LspMessage::Initialized(
+            lsp_types::InitializedParams {},
+        ));
+        input.push_message(lsp::LspMessage::Shutdown(()));
+        input
+            .messages
+            .open_schedule_mut()
+            .push(messages::ScheduledOpen {
+                path: "a.c".to_owned(),
+                before: 1,
+            });
+        // initialize, initialized, initialized, didOpen, shutdown
+        input
+            .messages
+            .framing_mut()
+            .push(messages::FramingVariation {
+                payload: 4,
+                line_ending: lsp::json_rpc::HeaderLineEnding::Lf,
+            });
+
+        input.swap_messages(0, 1);
+
+        assert_eq!(input.messages[0], lsp::LspMessage::Shutdown(()));
+        assert_eq!(input.messages.open_schedule()[0].before, 0);
+        // initialize, initialized, didOpen, shutdown, initialized
+        assert_eq!(input.messages.framing()[0].payload, 3);
+    }
+
+    #[test]
+    fn test_moving_opens_keeps_framing_variations() {
+        let source_file = |code: &[u8]| {
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(TextDocument::new(
+                Language::C,
+                code.to_vec(),
+            )))
+        };
+        let mut input = LspInput {
+            messages: LspMessageSequence::default(),
+            workspace: FileSystemDirectory::from([
+                (Utf8Input::new("a.c".to_owned()), source_file(b"int a;")),
+                (Utf8Input::new("b.c".to_owned()), source_file(b"int b;")),
+            ]),
+        };
+        input.push_message(lsp::LspMessage::Initialized(
+            lsp_types::InitializedParams {},
+        ));
+        input.push_message(lsp::LspMessage::Shutdown(()));
+        for (path, before) in [("a.c", 0), ("b.c", 1)] {
+            input
+                .messages
+                .open_schedule_mut()
+                .push(messages::ScheduledOpen {
+                    path: path.to_owned(),
+                    before,
+                });
+        }
+        // initialize, initialized, didOpen a.c, initialized, didOpen b.c, shutdown, shutdown
+        for payload in [2, 5] {
+            input
+                .messages
+                .framing_mut()
+                .push(messages::FramingVariation {
+                    payload,
+                    line_ending: lsp::json_rpc::HeaderLineEnding::Lf,
+                });
+        }
+        let framed = |input: &LspInput| -> Vec<_> {
+            input
+                .messages
+                .framing()
+                .iter()
+                .map(|it| it.payload)
+                .collect()
+        };
+
+        input.swap_opens(0, 1);
+        // initialize, initialized, didOpen b.c, initialized, didOpen a.c, shutdown, shutdown
+        assert_eq!(input.messages.open_schedule()[1].path, "a.c");
+        assert_eq!(framed(&input), [4, 5]);
+
+        input.move_open(1, 0);
+        // initialize, initialized, didOpen b.c, didOpen a.c, initialized, shutdown, shutdown
+        assert_eq!(framed(&input), [3, 5]);
+        assert_eq!(*session::message_payloads(&input)[1].end(), 5);
+    }
+
+    #[test]
+    fn test_divergences_keep_framing_variations() {
+        let mut input = LspInput {
+            messages: LspMessageSequence::default(),
+            workspace: FileSystemDirectory::from([(
+                Utf8Input::new("a.c".to_owned()),
+                FileSystemEntry::File(WorkspaceEntry::SourceFile(TextDocument::new(
+                    Language::C,
+                    b"int a;".to_vec(),
+                ))),
+            )]),
+        };
+        input.push_message(lsp::LspMessage::Initialized(
+            lsp_types::InitializedParams {},
+        ));
+        input.push_message(lsp::LspMessage::Shutdown(()));
+        // initialize, initialized, didOpen, initialized, shutdown, shutdown
+        input
+            .messages
+            .framing_mut()
+            .push(messages::FramingVariation {
+                payload: 4,
+                line_ending: lsp::json_rpc::HeaderLineEnding::Lf,
+            });
+
+        input.push_divergence(messages::Divergence {
+            path: "a.c".to_owned(),
+            before: 1,
+            side: DivergentSide::InMemory,
+            content: b"int b;".to_vec(),
+        });
+        // initialize, initialized, didOpen, initialized, didChange, shutdown, shutdown
+        assert_eq!(input.messages.framing()[0].payload, 5);
+        assert_eq!(*session::message_payloads(&input)[1].end(), 5);
+
+        input.remove_divergence(0);
+        assert_eq!(input.messages.framing()[0].payload, 4);
+        assert_eq!(*session::message_payloads(&input)[1].end(), 4);
+    }
+
+    #[test]
+    fn test_permute_messages_moves_placements() {
+        let mut input = LspInput::default();
+        for message in [
+            lsp::LspMessage::Initialized(lsp_types::InitializedParams {}),
+            lsp::LspMessage::Shutdown(()),
+            lsp::LspMessage::Exit(()),
+            lsp::LspMessage::WorkDoneProgressCancel(lsp_types::WorkDoneProgressCancelParams {
+                token: lsp_types::NumberOrString::Number(0),
+            }),
+        ] {
+            input.push_message(message);
+        }
+        input
+            .messages
+            .open_schedule_mut()
+            .push(messages::ScheduledOpen {
+                path: "a.c".to_owned(),
+                before: 3,
+            });
+        input
+            .messages
+            .set_transport_fault(Some(messages::TransportFault::DropMidMessage {
+                message: 2,
+                bytes: 1,
+            }));
+        input.messages.position_encoding_mut().mismatched.push(1);
+
+        input.permute_messages(1, &[2, 0, 1]);
+
+        let methods: Vec<_> = input.messages.iter().map(|it| it.method()).collect();
+        assert_eq!(
+            methods,
+            [
+                "initialized",
+                "window/workDoneProgress/cancel",
+                "shutdown",
+                "exit"
+            ]
+        );
+        assert_eq!(input.messages.open_schedule()[0].before, 1);
+        assert_eq!(
+            input.messages.transport_fault(),
+            Some(messages::TransportFault::DropMidMessage {
+                message: 3,
+                bytes: 1
+            })
+        );
+        assert_eq!(input.messages.position_encoding().mismatched, [2]);
+    }
+
+    #[test]
+    fn test_transport_faults() {
+        let mut input = LspInput {
+            messages: LspMessageSequence::default(),
+            workspace: FileSystemDirectory::default(),
+        };
+        input.push_message(lsp::LspMessage::Initialized(
+            lsp_types::InitializedParams {},
+        ));
+        let workspace_dir = Path::new("/tmp/lsp-fuzz-workspace_test");
+        let complete = input.request_bytes(workspace_dir);
+        let methods = |input: &LspInput| -> Vec<_> {
+            input.message_sequence().map(|it| it.method()).collect()
+        };
+
+        input
+            .messages
+            .set_transport_fault(Some(messages::TransportFault::DropMidMessage {
+                message: 0,
+                bytes: 10,
+            }));
+        assert_eq!(methods(&input), ["initialize", "initialized"]);
+        let dropped = input.request_bytes(workspace_dir);
+        let (sent, malformed) = lsp::json_rpc::JsonRPCMessage::split_lsp_stream(&dropped);
+        assert_eq!(sent.len(), 2);
+        assert_eq!(malformed.map(|it| it.raw.len()), Some(10));
+        assert!(complete.starts_with(&dropped));
+
+        input
+            .messages
+            .set_transport_fault(Some(messages::TransportFault::HalfCloseAfterShutdown));
+        assert_eq!(
+            methods(&input),
+            ["initialize", "initialized", "initialized", "shutdown"]
+        );
+
+        input
+            .messages
+            .set_transport_fault(Some(messages::TransportFault::PartialFinalMessage {
+                bytes: usize::MAX,
+            }));
+        let partial = input.request_bytes(workspace_dir);
+        assert_eq!(partial.len(), complete.len() - 1);
+
+        input.remove_message(0);
+        input
+            .messages
+            .set_transport_fault(Some(messages::TransportFault::CloseEarly { before: 3 }));
+        assert_eq!(methods(&input), ["initialize", "initialized"]);
+    }
+
+    #[test]
+    fn test_language_id_override() {
+        let mut input = LspInput {
+            messages: LspMessageSequence::default(),
+            workspace: FileSystemDirectory::from([(
+                Utf8Input::new("a.c".to_owned()),
+                FileSystemEntry::File(WorkspaceEntry::SourceFile(TextDocument::new(
+                    Language::C,
+                    b"int a;".to_vec(),
+                ))),
+            )]),
+        };
+        input
+            .messages
+            .language_id_overrides_mut()
+            .push(messages::LanguageIdOverride {
+                path: "a.c".to_owned(),
+                language_id: "rust".to_owned(),
+            });
+
+        let language_ids: Vec<_> = input
+            .message_sequence()
+            .filter_map(|it| match it {
+                lsp::LspMessage::DidOpenTextDocument(params) => {
+                    Some(params.text_document.language_id)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(language_ids, ["rust"]);
+    }
+
+    #[test]
+    fn test_minimal_capabilities() {
+        let mut input = LspInput {
+            messages: LspMessageSequence::default(),
+            workspace: FileSystemDirectory::default(),
+        };
+        let capabilities = |input: &LspInput| {
+            input
+                .message_sequence()
+                .find_map(|it| match it {
+                    lsp::LspMessage::Initialize(params) => Some(params.capabilities),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        assert!(capabilities(&input).text_document.is_some());
+
+        input
+            .messages
+            .set_capability_profile(CapabilityProfile::Minimal);
+        let minimal = capabilities(&input);
+        assert!(minimal.text_document.is_none());
+        assert!(minimal.workspace.is_none());
+        let general = minimal.general.unwrap();
+        assert!(general.markdown.is_none());
+        assert_eq!(
+            general.position_encodings,
+            Some(vec![lsp_types::PositionEncodingKind::UTF8])
+        );
+    }
+
+    #[test]
+    fn test_workspace_template() {
+        let template = WorkspaceTemplate {
+            path: ".bsp/server.json".to_owned(),
+            content: r#"{"languages":["{language_id}"],"main":"{main_file}"}"#.to_owned(),
+        };
+        let mut workspace: FileSystemDirectory<WorkspaceEntry> = FileSystemDirectory::default();
+        workspace.insert_path(
+            &template.path,
+            FileSystemEntry::File(WorkspaceEntry::Skeleton(
+                template.render(Language::C, "main.c"),
+            )),
+        );
+
+        let Some(FileSystemEntry::File(entry)) = workspace.get(".bsp/server.json") else {
+            panic!("The template file is not in the workspace");
+        };
+        assert_eq!(
+            entry.as_skeleton(),
+            Some(br#"{"languages":["c"],"main":"main.c"}"#.as_slice())
+        );
+    }
+
+    #[test]
+    fn test_format_versions() {
+        let mut input = LspInput::default();
+        input.workspace.insert_path(
+            "Cargo.toml",
+            FileSystemEntry::File(WorkspaceEntry::Skeleton(b"[package]".to_vec())),
+        );
+
+        let mut current = Vec::new();
+        format::write_input(&input, &mut current).unwrap();
+        let (loaded, version) = format::read_input(current.as_slice()).unwrap();
+        assert_eq!(version, format::FORMAT_VERSION);
+        assert_eq!(loaded, input);
+
+        // Inputs written before versioning are not wrapped.
+        let mut unversioned = Vec::new();
+        ciborium::into_writer(&input, &mut unversioned).unwrap();
+        let (loaded, version) = format::read_input(unversioned.as_slice()).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(loaded, input);
+    }
+
+    #[test]
+    fn test_latex_workspaces() {
+        let doc = TextDocument::new(Language::LaTeX, b"\\section{A}".to_vec());
+        let mut rand = StdRand::with_seed(0);
+        let workspace = session::workspace_for_document(&mut rand, Language::LaTeX, doc, "tex");
+        let paths: Vec<_> = workspace.iter_files().map(|(path, _)| path).collect();
+        assert!(paths.contains(&PathBuf::from("refs.bib")));
+        assert!(paths.contains(&PathBuf::from("chapters/intro.tex")));
+
+        let bib = b"@article{knuth1984,\n  year = {1984},\n}\n@misc{ lamport , note = {x}}";
+        assert_eq!(session::citation_keys(bib), ["knuth1984", "lamport"]);
+        let doc = TextDocument::new(Language::BibTeX, bib.to_vec());
+        let workspace = session::workspace_for_document(&mut rand, Language::BibTeX, doc, "bib");
+        let main = workspace
+            .iter_files()
+            .find_map(|(path, entry)| (path == Path::new("main.tex")).then_some(entry))
+            .and_then(WorkspaceEntry::as_source_file)
+            .expect("The workspace has a main document");
+        assert!(main.to_string_lossy().contains("\\cite{knuth1984,lamport}"));
+    }
+
+    #[test]
+    fn test_solidity_workspace() {
+        let doc = TextDocument::new(Language::Solidity, b"contract C {}".to_vec());
+        let mut rand = StdRand::with_seed(0);
+        let workspace = session::workspace_for_document(&mut rand, Language::Solidity, doc, "sol");
+        let paths: Vec<_> = workspace.iter_files().map(|(path, _)| path).collect();
+        assert!(paths.contains(&PathBuf::from("src/Main.sol")));
+        assert!(paths.contains(&PathBuf::from("src/Token.sol")));
+        assert!(paths.contains(&PathBuf::from("src/interfaces/IToken.sol")));
+    }
+
+    #[test]
+    fn test_markdown_workspace() {
+        let doc = TextDocument::new(Language::Markdown, b"# Heading\n".to_vec());
+        let mut rand = StdRand::with_seed(0);
+        let workspace = session::workspace_for_document(&mut rand, Language::Markdown, doc, "md");
+        let notes: Vec<_> = workspace
+            .iter_files()
+            .filter(|(_, entry)| entry.as_source_file().is_some())
+            .map(|(path, _)| path)
+            .collect();
+        assert!(notes.contains(&PathBuf::from("main.md")));
+        assert!(notes.contains(&PathBuf::from("other.md")));
+        assert!(
+            workspace
+                .iter_files()
+                .any(|(path, _)| path == Path::new(".marksman.toml"))
+        );
+    }
+
+    #[test]
+    fn test_go_workspace() {
+        let doc = TextDocument::new(Language::Go, b"package main".to_vec());
+        let mut rand = StdRand::with_seed(0);
+        let workspace = session::workspace_for_document(&mut rand, Language::Go, doc, "go");
+        let go_mod = workspace
+            .iter_files()
+            .find_map(|(path, entry)| (path == Path::new("go.mod")).then_some(entry))
+            .and_then(WorkspaceEntry::as_skeleton)
+            .expect("The workspace has a module file");
+        assert!(go_mod.starts_with(b"module "));
+        assert!(
+            workspace
+                .iter_files()
+                .any(|(path, entry)| path == Path::new("main.go")
+                    && entry.as_source_file().is_some())
+        );
+    }
+
+    #[test]
+    fn test_zig_workspace() {
+        let doc = TextDocument::new(Language::Zig, b"pub fn main() void {}".to_vec());
+        let mut rand = StdRand::with_seed(0);
+        let workspace = session::workspace_for_document(&mut rand, Language::Zig, doc, "zig");
+        let build_zig = workspace
+            .iter_files()
+            .find_map(|(path, entry)| (path == Path::new("build.zig")).then_some(entry))
+            .and_then(WorkspaceEntry::as_skeleton)
+            .expect("The workspace has a build script");
+        assert!(String::from_utf8_lossy(build_zig).contains("src/main.zig"));
+        assert!(
+            workspace
+                .iter_files()
+                .any(|(path, entry)| path == Path::new("src/main.zig")
+                    && entry.as_source_file().is_some())
+        );
+    }
+
+    #[test]
+    fn test_java_workspace() {
+        let mut rand = StdRand::with_seed(0);
+        let mut build_files = std::collections::HashSet::new();
+        for _ in 0..16 {
+            let doc = TextDocument::new(Language::Java, b"class Main {}".to_vec());
+            let workspace = session::workspace_for_document(&mut rand, Language::Java, doc, "java");
+            let paths: Vec<_> = workspace.iter_files().map(|(path, _)| path).collect();
+            assert!(paths.contains(&PathBuf::from("src/main/java/Main.java")));
+            build_files.extend(
+                ["pom.xml", "build.gradle"]
+                    .into_iter()
+                    .filter(|it| paths.contains(&PathBuf::from(it))),
+            );
+        }
+        assert_eq!(build_files.len(), 2);
+    }
+
+    #[test]
+    fn test_immutable_skeletons() {
+        let doc = TextDocument::new(Language::Rust, b"fn main() {}".to_vec());
+        let mut rand = StdRand::with_seed(0);
+        let mut workspace = session::workspace_for_document(&mut rand, Language::Rust, doc, "rs");
+        let project = FileSystemEntry::File(WorkspaceEntry::Skeleton(b"{}".to_vec()));
+        assert!(!workspace.insert_mutable("rust-project.json", project.clone()));
+        let Some(FileSystemEntry::File(entry)) = workspace.get("rust-project.json") else {
+            panic!("The workspace has a project file");
+        };
+        assert!(entry.is_immutable());
+        assert!(entry.as_skeleton().is_some_and(|it| it != b"{}"));
+        assert!(workspace.insert_mutable("other.json", project));
+    }
+
+    #[test]
+    fn write_divergences_of_current_files_only() {
+        let mut input = LspInput::default();
+        input.workspace.insert_path(
+            "main.c",
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(TextDocument::new(
+                Language::C,
+                b"int main() {}".to_vec(),
+            ))),
+        );
+        for path in ["main.c", "removed.c"] {
+            input.messages.divergences_mut().push(messages::Divergence {
+                path: path.to_owned(),
+                before: 0,
+                side: DivergentSide::OnDisk,
+                content: b"int x;".to_vec(),
+            });
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        input.setup_workspace(temp_dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("main.c")).unwrap(),
+            b"int x;"
+        );
+        assert!(!temp_dir.path().join("removed.c").exists());
+    }
 }
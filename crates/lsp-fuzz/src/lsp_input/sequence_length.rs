@@ -0,0 +1,247 @@
+//! Adapting the length of message sequences to the target.
+//!
+//! Stateless servers answer each request on its own, so long sequences only slow down the
+//! executions, whereas stateful servers reach new code only after many messages.
+//! [`SequenceLengthStats`] relates the lengths of the executed sequences to the corpus entries
+//! they produced, and [`SequenceLengthPolicy`] derives from it how long sequences may grow
+//! and how often messages are added or removed.
+
+use std::borrow::Cow;
+
+use libafl::{
+    HasMetadata,
+    corpus::CorpusId,
+    mutators::{MutationResult, Mutator},
+};
+use libafl_bolts::{Named, SerdeAny};
+use serde::{Deserialize, Serialize};
+
+use super::LspInput;
+
+/// The maximum number of messages in a sequence until enough corpus entries were found.
+pub const DEFAULT_MAX_MESSAGES: usize = 20;
+
+/// The lower bound of the adapted maximum number of messages.
+pub const MIN_MAX_MESSAGES: usize = 4;
+
+/// The upper bound of the adapted maximum number of messages.
+/// Longer sequences are counted as sequences of this length.
+pub const MAX_MAX_MESSAGES: usize = 128;
+
+/// The number of new corpus entries needed before the policy departs from the defaults.
+const MIN_NEW_ENTRIES: u64 = 16;
+
+/// The number of executions and new corpus entries for each message sequence length.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct SequenceLengthStats {
+    executions: Vec<u64>,
+    new_entries: Vec<u64>,
+}
+
+impl SequenceLengthStats {
+    /// Records an execution of `len` messages and whether the input was added to the corpus.
+    pub fn record(&mut self, len: usize, new_entry: bool) {
+        let len = len.min(MAX_MAX_MESSAGES);
+        if self.executions.len() <= len {
+            self.executions.resize(len + 1, 0);
+            self.new_entries.resize(len + 1, 0);
+        }
+        self.executions[len] += 1;
+        if new_entry {
+            self.new_entries[len] += 1;
+        }
+    }
+
+    #[must_use]
+    pub fn executions(&self) -> u64 {
+        self.executions.iter().sum()
+    }
+
+    #[must_use]
+    pub fn new_entries(&self) -> u64 {
+        self.new_entries.iter().sum()
+    }
+
+    /// The mean sequence length weighted by `counts`.
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "The counts and lengths are far smaller than 2^52"
+    )]
+    fn mean_length(counts: &[u64]) -> Option<f64> {
+        let total: u64 = counts.iter().sum();
+        let weighted: u64 = counts
+            .iter()
+            .enumerate()
+            .map(|(len, &count)| len as u64 * count)
+            .sum();
+        (total > 0).then(|| weighted as f64 / total as f64)
+    }
+
+    /// The mean lengths of the sequences that were added to the corpus and of all executed
+    /// sequences, once enough corpus entries were found.
+    fn mean_lengths(&self) -> Option<(f64, f64)> {
+        if self.new_entries() < MIN_NEW_ENTRIES {
+            return None;
+        }
+        Some((
+            Self::mean_length(&self.new_entries)?,
+            Self::mean_length(&self.executions)?,
+        ))
+    }
+}
+
+/// How long message sequences may grow and how often messages are added or removed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceLengthPolicy {
+    /// The number of messages above which appending replaces a message instead.
+    pub max_messages: usize,
+    /// The probability that a mutation appending a message is applied.
+    pub append_probability: f64,
+    /// The probability that a mutation removing a message is applied.
+    pub drop_probability: f64,
+}
+
+impl Default for SequenceLengthPolicy {
+    fn default() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            append_probability: 1.0,
+            drop_probability: 1.0,
+        }
+    }
+}
+
+impl SequenceLengthPolicy {
+    /// Derives the policy from the sequence lengths of the corpus entries found so far.
+    ///
+    /// The maximum length is twice the mean length of the sequences added to the corpus,
+    /// so that it grows as long as longer sequences keep finding new coverage.
+    /// If these sequences are longer than the executed ones on average, messages are
+    /// removed less often, and if they are shorter, messages are added less often.
+    #[must_use]
+    pub fn from_stats(stats: &SequenceLengthStats) -> Self {
+        let Some((found, executed)) = stats.mean_lengths() else {
+            return Self::default();
+        };
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "The mean length is a small, non-negative number"
+        )]
+        let max_messages =
+            ((found * 2.0).ceil() as usize).clamp(MIN_MAX_MESSAGES, MAX_MAX_MESSAGES);
+        let growth_bias = if found + executed > 0.0 {
+            found / (found + executed)
+        } else {
+            0.5
+        };
+        Self {
+            max_messages,
+            append_probability: (2.0 * growth_bias).min(1.0),
+            drop_probability: (2.0 * (1.0 - growth_bias)).min(1.0),
+        }
+    }
+
+    /// The policy for the current campaign.
+    pub fn of<State: HasMetadata>(state: &State) -> Self {
+        state
+            .metadata_map()
+            .get::<SequenceLengthStats>()
+            .map(Self::from_stats)
+            .unwrap_or_default()
+    }
+}
+
+/// Records the sequence length of each input mutated by the inner mutator
+/// in [`SequenceLengthStats`], together with whether it was added to the corpus.
+#[derive(Debug)]
+pub struct TrackSequenceLength<M> {
+    inner: M,
+    /// The number of messages of the input of the current execution.
+    length: Option<usize>,
+}
+
+impl<M> TrackSequenceLength<M> {
+    pub const fn new(inner: M) -> Self {
+        Self {
+            inner,
+            length: None,
+        }
+    }
+}
+
+impl<M> Named for TrackSequenceLength<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TrackSequenceLength");
+        &NAME
+    }
+}
+
+impl<M, State> Mutator<LspInput, State> for TrackSequenceLength<M>
+where
+    M: Mutator<LspInput, State>,
+    State: HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let result = self.inner.mutate(state, input)?;
+        self.length = Some(input.messages.len());
+        Ok(result)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut State,
+        new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        if let Some(len) = self.length.take() {
+            state
+                .metadata_or_insert_with(SequenceLengthStats::default)
+                .record(len, new_corpus_id.is_some());
+        }
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_MAX_MESSAGES, SequenceLengthPolicy, SequenceLengthStats};
+
+    fn stats(executed: &[(usize, u64)], found: &[(usize, u64)]) -> SequenceLengthStats {
+        let mut stats = SequenceLengthStats::default();
+        for &(len, count) in executed {
+            (0..count).for_each(|_| stats.record(len, false));
+        }
+        for &(len, count) in found {
+            (0..count).for_each(|_| stats.record(len, true));
+        }
+        stats
+    }
+
+    #[test]
+    fn defaults_without_enough_entries() {
+        let policy = SequenceLengthPolicy::from_stats(&stats(&[(30, 100)], &[(30, 3)]));
+        assert_eq!(policy, SequenceLengthPolicy::default());
+        assert_eq!(policy.max_messages, DEFAULT_MAX_MESSAGES);
+    }
+
+    #[test]
+    fn grows_for_stateful_targets() {
+        let policy = SequenceLengthPolicy::from_stats(&stats(&[(5, 1000)], &[(18, 20)]));
+        assert!(policy.max_messages > DEFAULT_MAX_MESSAGES);
+        assert!((policy.append_probability - 1.0).abs() < f64::EPSILON);
+        assert!(policy.drop_probability < 1.0);
+    }
+
+    #[test]
+    fn shrinks_for_stateless_targets() {
+        let policy = SequenceLengthPolicy::from_stats(&stats(&[(15, 1000)], &[(2, 20)]));
+        assert!(policy.max_messages < DEFAULT_MAX_MESSAGES);
+        assert!(policy.append_probability < 1.0);
+        assert!((policy.drop_probability - 1.0).abs() < f64::EPSILON);
+    }
+}
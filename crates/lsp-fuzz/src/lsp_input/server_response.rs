@@ -18,12 +18,23 @@ use tracing::warn;
 use super::LspInput;
 use crate::{
     execution::responses::LspOutputObserver,
-    lsp_input::server_response::collector::collect_response_info, utils::AflContext,
+    lsp_input::server_response::{
+        collector::collect_response_info, semantic_tokens::SemanticTokensInfo,
+    },
+    utils::AflContext,
 };
 
 mod collector;
+pub mod conformance;
+pub mod dictionary;
+pub mod learned_fragments;
 pub mod matching;
 pub mod metadata;
+pub mod minted_ids;
+pub mod notifications;
+pub mod recovery;
+pub mod semantic_tokens;
+pub mod supported_methods;
 
 #[derive(Debug)]
 pub struct LspResponseFeedback {
@@ -93,7 +104,10 @@ where
             return Ok(());
         };
 
-        let response_info = collect_response_info(matching);
+        let semantic_tokens =
+            SemanticTokensInfo::collect(input.messages.iter(), received_messages, &matching);
+        let mut response_info = collect_response_info(matching);
+        response_info.semantic_tokens = semantic_tokens;
         testcase.add_metadata(response_info);
         Ok(())
     }
@@ -1,16 +1,19 @@
 use std::collections::{HashSet, VecDeque};
 
-use lsp_types::notification::PublishDiagnostics;
+use lsp_types::notification::{DidChangeWatchedFiles, Notification, PublishDiagnostics};
+use serde_json::Value;
 
 use super::{
     LspInput,
     matching::RequestResponseMatching,
     metadata::{Diagnostic, LspResponseInfo, ParamFragments, SymbolRange},
+    semantic_tokens::SemanticTokensInfo,
 };
 use crate::lsp::{LspMessage, message::LspResponse};
 
 pub fn collect_response_info(matching: RequestResponseMatching<'_>) -> LspResponseInfo {
     let diagnostics = collect_diagnostics(&matching);
+    let file_watchers = collect_file_watchers(&matching);
     let mut param_fragments = ParamFragments::default();
     let mut symbol_ranges = HashSet::new();
 
@@ -22,6 +25,8 @@ pub fn collect_response_info(matching: RequestResponseMatching<'_>) -> LspRespon
         diagnostics,
         param_fragments,
         symbol_ranges,
+        file_watchers,
+        semantic_tokens: SemanticTokensInfo::default(),
     }
 }
 
@@ -41,6 +46,49 @@ fn collect_diagnostics(matching: &RequestResponseMatching<'_>) -> HashSet<Diagno
     diagnostics
 }
 
+const FILE_SCHEME: &str = "file://";
+
+/// Collects the glob patterns of the `workspace/didChangeWatchedFiles` registrations.
+///
+/// Relative patterns are rebased onto the workspace root if their base is within it,
+/// and dropped otherwise. The scheme of absolute `file://` patterns is stripped, so that
+/// they match the paths of the workspace like the patterns in the workspace root.
+fn collect_file_watchers(matching: &RequestResponseMatching<'_>) -> HashSet<String> {
+    let registrations = matching
+        .requests_from_server
+        .iter()
+        .filter_map(|it| match it {
+            LspMessage::RegisterCapability(params) => Some(&params.registrations),
+            _ => None,
+        })
+        .flatten()
+        .filter(|it| it.method == DidChangeWatchedFiles::METHOD);
+    let watchers = registrations
+        .filter_map(|it| it.register_options.as_ref()?.get("watchers")?.as_array())
+        .flatten();
+    watchers
+        .filter_map(|watcher| match watcher.get("globPattern")? {
+            Value::String(pattern) => Some(pattern.clone()),
+            Value::Object(relative) => {
+                // The base is either a URI or a workspace folder.
+                let base = relative.get("baseUri")?;
+                let base = base.as_str().or_else(|| base.get("uri")?.as_str())?;
+                let pattern = relative.get("pattern")?.as_str()?;
+                let base = base.strip_prefix(LspInput::PROTOCOL_PREFIX)?;
+                Some(format!("{base}/{pattern}"))
+            }
+            _ => None,
+        })
+        .map(|pattern| {
+            let pattern = [LspInput::PROTOCOL_PREFIX, FILE_SCHEME]
+                .into_iter()
+                .find_map(|scheme| pattern.strip_prefix(scheme))
+                .unwrap_or(&pattern);
+            pattern.trim_start_matches('/').to_owned()
+        })
+        .collect()
+}
+
 fn collect_response_fragments(
     req: &LspMessage,
     res: LspResponse,
@@ -0,0 +1,318 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    execution::responses::LspOutputObserver,
+    lsp::{
+        LspMessage,
+        json_rpc::{JsonRPCMessage, MessageId},
+    },
+    lsp_input::LspInput,
+    utils::AflContext,
+};
+
+/// The kind of JSON-RPC discipline the server failed to follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ViolationKind {
+    /// More than one response carries the id of the same request.
+    DuplicateResponse,
+    /// A response carries an id that was never used by a request.
+    UnknownRequestId,
+    /// The server acknowledged `shutdown` without answering an earlier request.
+    MissingResponse,
+    /// A response answers a request sent after the `exit` notification.
+    ///
+    /// The server must exit upon `exit`, so nothing sent past that point should be answered.
+    /// Responses to earlier requests may still arrive after that of `shutdown`, as responses
+    /// need not be in the order of the requests.
+    ResponseAfterExit,
+    /// A response echoes the request id with a different JSON type (e.g., `"1"` for `1`).
+    MismatchedIdType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConformanceViolation {
+    pub kind: ViolationKind,
+    pub id: MessageId,
+    pub method: Option<Cow<'static, str>>,
+}
+
+impl ConformanceViolation {
+    fn signature(&self) -> (ViolationKind, Option<Cow<'static, str>>) {
+        (self.kind, self.method.clone())
+    }
+}
+
+/// Checks the messages received from the server against the requests sent to it.
+///
+/// `sent_messages` must be the complete message sequence, as request ids are assigned
+/// the same way as [`LspMessage::into_json_rpc`] does, i.e., counting from zero.
+pub fn check_conformance(
+    sent_messages: impl IntoIterator<Item = LspMessage>,
+    received_messages: &[JsonRPCMessage],
+) -> Vec<ConformanceViolation> {
+    let mut requests: HashMap<usize, &'static str> = HashMap::new();
+    let mut requests_after_exit = HashSet::new();
+    let mut exit_sent = false;
+    for msg in sent_messages {
+        exit_sent |= msg.method() == "exit";
+        if msg.is_request() {
+            let id = requests.len();
+            requests.insert(id, msg.method());
+            if exit_sent {
+                requests_after_exit.insert(id);
+            }
+        }
+    }
+
+    let mut violations = Vec::new();
+    let mut answered = HashSet::new();
+    let mut shutdown_acknowledged = false;
+    for recv in received_messages {
+        let JsonRPCMessage::Response { id: Some(id), .. } = recv else {
+            continue;
+        };
+        let numeric_id = match id {
+            MessageId::Number(id) => Some(*id),
+            MessageId::String(id) => id.parse().ok(),
+        };
+        let method = numeric_id
+            .and_then(|it| requests.get(&it))
+            .map(|&it| Cow::Borrowed(it));
+        let mut report = |kind| {
+            violations.push(ConformanceViolation {
+                kind,
+                id: id.clone(),
+                method: method.clone(),
+            });
+        };
+        let Some(numeric_id) = numeric_id.filter(|it| requests.contains_key(it)) else {
+            report(ViolationKind::UnknownRequestId);
+            continue;
+        };
+        if matches!(id, MessageId::String(_)) {
+            report(ViolationKind::MismatchedIdType);
+        }
+        if !answered.insert(numeric_id) {
+            report(ViolationKind::DuplicateResponse);
+        }
+        if requests_after_exit.contains(&numeric_id) {
+            report(ViolationKind::ResponseAfterExit);
+        }
+        if method.as_deref() == Some("shutdown") {
+            shutdown_acknowledged = true;
+        }
+    }
+
+    if shutdown_acknowledged {
+        let mut missing: Vec<_> = requests
+            .iter()
+            .filter(|(id, _)| !answered.contains(*id) && !requests_after_exit.contains(*id))
+            .collect();
+        missing.sort_unstable();
+        violations.extend(
+            missing
+                .into_iter()
+                .map(|(&id, &method)| ConformanceViolation {
+                    kind: ViolationKind::MissingResponse,
+                    id: MessageId::Number(id),
+                    method: Some(Cow::Borrowed(method)),
+                }),
+        );
+    }
+    violations
+}
+
+/// A conformance finding attached to the solution that triggered it.
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct ConformanceFinding {
+    pub violations: Vec<ConformanceViolation>,
+    pub transcript: Vec<JsonRPCMessage>,
+}
+
+/// Violations that have already been reported, keyed by kind and method.
+#[derive(Debug, Default, Serialize, Deserialize, SerdeAny)]
+pub struct ReportedViolations {
+    seen: HashSet<(ViolationKind, Option<Cow<'static, str>>)>,
+}
+
+/// An objective flagging inputs on which the server breaks JSON-RPC discipline.
+///
+/// Only executions that exit normally are checked,
+/// and each kind of violation is reported once per method.
+#[derive(Debug)]
+pub struct ConformanceFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+    violations: Vec<ConformanceViolation>,
+}
+
+impl ConformanceFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            violations: Vec::new(),
+        }
+    }
+}
+
+impl Named for ConformanceFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ConformanceFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for ConformanceFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        state.add_metadata(ReportedViolations::default());
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for ConformanceFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        input: &LspInput,
+        observers: &Observers,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        self.violations.clear();
+        if *exit_kind != ExitKind::Ok {
+            return Ok(false);
+        }
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        self.violations = check_conformance(input.message_sequence(), observer.captured_messages());
+        let reported: &ReportedViolations = state
+            .metadata()
+            .afl_context("ReportedViolations not initialized")?;
+        Ok(self
+            .violations
+            .iter()
+            .any(|it| !reported.seen.contains(&it.signature())))
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        observers: &Observers,
+        testcase: &mut Testcase<LspInput>,
+    ) -> Result<(), libafl::Error> {
+        if self.violations.is_empty() {
+            return Ok(());
+        }
+        let reported: &mut ReportedViolations = state
+            .metadata_mut()
+            .afl_context("ReportedViolations not initialized")?;
+        reported
+            .seen
+            .extend(self.violations.iter().map(ConformanceViolation::signature));
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        testcase.add_metadata(ConformanceFinding {
+            violations: std::mem::take(&mut self.violations),
+            transcript: observer.captured_messages().to_vec(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn session() -> Vec<LspMessage> {
+        vec![
+            LspMessage::Initialize(lsp_types::InitializeParams::default()),
+            LspMessage::Initialized(lsp_types::InitializedParams {}),
+            LspMessage::Shutdown(()),
+            LspMessage::Exit(()),
+        ]
+    }
+
+    fn response(id: usize) -> JsonRPCMessage {
+        JsonRPCMessage::response(Some(id), Some(Value::Null), None)
+    }
+
+    fn kinds(violations: &[ConformanceViolation]) -> Vec<ViolationKind> {
+        violations.iter().map(|it| it.kind).collect()
+    }
+
+    #[test]
+    fn well_behaved_server() {
+        let received = [response(0), response(1)];
+        assert!(check_conformance(session(), &received).is_empty());
+    }
+
+    #[test]
+    fn duplicate_and_unknown_responses() {
+        let received = [response(0), response(0), response(42), response(1)];
+        assert_eq!(
+            kinds(&check_conformance(session(), &received)),
+            [
+                ViolationKind::DuplicateResponse,
+                ViolationKind::UnknownRequestId
+            ]
+        );
+    }
+
+    #[test]
+    fn responses_after_shutdown() {
+        // Responses may arrive out of order.
+        let received = [response(1), response(0)];
+        assert!(check_conformance(session(), &received).is_empty());
+    }
+
+    #[test]
+    fn responses_after_exit() {
+        let mut session = session();
+        session.push(LspMessage::Shutdown(()));
+        let received = [response(0), response(1), response(2)];
+        assert_eq!(
+            kinds(&check_conformance(session.clone(), &received)),
+            [ViolationKind::ResponseAfterExit]
+        );
+        assert!(check_conformance(session, &received[..2]).is_empty());
+    }
+
+    #[test]
+    fn missing_response_and_id_type() {
+        let received = [JsonRPCMessage::response(Some("1"), Some(Value::Null), None)];
+        assert_eq!(
+            kinds(&check_conformance(session(), &received)),
+            [
+                ViolationKind::MismatchedIdType,
+                ViolationKind::MissingResponse
+            ]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Mining the vocabulary of the server from its responses.
+//!
+//! Completion labels, command names, symbol names, and the semantic token legend are strings
+//! the server knows how to handle, so string parameters drawn from them reach deeper than
+//! random ones.
+
+use std::{borrow::Cow, ops::Index};
+
+use indexmap::IndexSet;
+use libafl::{
+    HasMetadata,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    execution::responses::LspOutputObserver, lsp::json_rpc::JsonRPCMessage, lsp_input::LspInput,
+    utils::AflContext,
+};
+
+/// The keys of response objects whose string values, or arrays of strings, are mined.
+const MINED_KEYS: &[&str] = &[
+    // Completion items
+    "label",
+    "insertText",
+    "filterText",
+    // Commands and code actions
+    "command",
+    "commands",
+    // Document and workspace symbols
+    "name",
+    "containerName",
+    // Semantic token legend
+    "tokenTypes",
+    "tokenModifiers",
+    // Text edits
+    "newText",
+];
+
+/// The longest string added to the dictionary, in bytes.
+const MAX_TOKEN_LEN: usize = 64;
+
+/// The maximum number of strings in the dictionary.
+pub const MAX_RESPONSE_TOKENS: usize = 4096;
+
+/// Strings mined from the responses of the server, in the order they were first seen.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct ResponseTokens {
+    content: IndexSet<String>,
+}
+
+impl ResponseTokens {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.content.len() >= MAX_RESPONSE_TOKENS
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.content.iter()
+    }
+
+    /// Adds `token` unless the dictionary is full or the token is empty, too long,
+    /// or contains control characters.
+    pub fn add_token(&mut self, token: &str) {
+        if self.is_full()
+            || token.is_empty()
+            || token.len() > MAX_TOKEN_LEN
+            || token.chars().any(char::is_control)
+            || self.content.contains(token)
+        {
+            return;
+        }
+        self.content.insert(token.to_owned());
+    }
+
+    /// Adds the strings under [`MINED_KEYS`] anywhere in `value`.
+    pub fn mine(&mut self, value: &serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                for (key, value) in object {
+                    if MINED_KEYS.contains(&key.as_str()) {
+                        match value {
+                            serde_json::Value::String(token) => self.add_token(token),
+                            serde_json::Value::Array(items) => items
+                                .iter()
+                                .filter_map(serde_json::Value::as_str)
+                                .for_each(|it| self.add_token(it)),
+                            _ => {}
+                        }
+                    }
+                    self.mine(value);
+                }
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|it| self.mine(it)),
+            _ => {}
+        }
+    }
+}
+
+impl Index<usize> for ResponseTokens {
+    type Output = String;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.content.index(index)
+    }
+}
+
+/// Mines the responses and requests of the server into [`ResponseTokens`] after each execution.
+///
+/// The feedback never deems an input interesting on its own.
+#[derive(Debug)]
+pub struct ResponseDictionaryFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+}
+
+impl ResponseDictionaryFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+        }
+    }
+}
+
+impl Named for ResponseDictionaryFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ResponseDictionaryFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for ResponseDictionaryFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        if !state.has_metadata::<ResponseTokens>() {
+            state.add_metadata(ResponseTokens::default());
+        }
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for ResponseDictionaryFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _input: &LspInput,
+        observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        let tokens = state.metadata_or_insert_with(ResponseTokens::default);
+        if tokens.is_full() {
+            return Ok(false);
+        }
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        for message in observer.captured_messages() {
+            match message {
+                JsonRPCMessage::Response {
+                    result: Some(result),
+                    ..
+                } => tokens.mine(result),
+                JsonRPCMessage::Request { params, .. } => tokens.mine(params),
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::ResponseTokens;
+
+    #[test]
+    fn mine_completions_and_legend() {
+        let mut tokens = ResponseTokens::default();
+        tokens.mine(&json!({
+            "capabilities": {
+                "semanticTokensProvider": {
+                    "legend": { "tokenTypes": ["namespace", "type"], "tokenModifiers": [] }
+                },
+                "executeCommandProvider": { "commands": ["rust-analyzer.runSingle"] }
+            }
+        }));
+        tokens.mine(&json!([
+            { "label": "println!", "kind": 3 },
+            { "label": "multi\nline" },
+            { "label": "namespace" }
+        ]));
+        let mut mined: Vec<_> = tokens.iter().map(String::as_str).collect();
+        mined.sort_unstable();
+        assert_eq!(
+            mined,
+            ["namespace", "println!", "rust-analyzer.runSingle", "type"]
+        );
+    }
+}
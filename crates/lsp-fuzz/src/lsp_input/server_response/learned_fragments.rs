@@ -0,0 +1,321 @@
+//! Learning code fragments from the edits suggested by the server.
+//!
+//! Completions, formatting edits, and `workspace/applyEdit` requests carry code the server wrote
+//! for a location in a document of the input, e.g., the snippets of its completion engine,
+//! which rarely appear among the mined fragments. [`LearnedFragmentsFeedback`] records such code
+//! in [`LearnedFragments`] under the kind of the node at the location, and
+//! [`ChooseFromLearnedFragments`] puts it in place of other nodes of that kind.
+//!
+//! [`ChooseFromLearnedFragments`]: crate::text_document::mutations::node_generators::ChooseFromLearnedFragments
+
+use std::{borrow::Cow, collections::HashMap};
+
+use indexmap::IndexSet;
+use libafl::{
+    HasMetadata,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use lsp_fuzz_grammars::Language;
+use lsp_types::{
+    CompletionResponse, CompletionTextEdit, DocumentChangeOperation, DocumentChanges,
+    InsertTextFormat, OneOf, Range, TextDocumentEdit, TextEdit, Uri, WorkspaceEdit,
+};
+use serde::{Deserialize, Serialize};
+
+use super::matching::RequestResponseMatching;
+use crate::{
+    execution::responses::LspOutputObserver,
+    lsp::{LspMessage, code_context::CodeContextRef, message::LspResponse},
+    lsp_input::{LspInput, message_edit::utf8_position, messages::ColumnUnit, uri},
+    text_document::{GrammarBasedMutation, TextDocument},
+    utils::AflContext,
+};
+
+/// The longest fragment learned, in bytes.
+const MAX_FRAGMENT_LEN: usize = 256;
+
+/// The maximum number of fragments learned for a node kind.
+const MAX_FRAGMENTS_PER_KIND: usize = 64;
+
+/// Code suggested by the server, by language and by the kind of the node it was suggested for,
+/// in the order it was first seen.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, SerdeAny)]
+pub struct LearnedFragments {
+    fragments: HashMap<Language, HashMap<String, IndexSet<String>>>,
+}
+
+impl LearnedFragments {
+    /// The fragments learned for the nodes of `node_kind` in `language`.
+    #[must_use]
+    pub fn get(&self, language: Language, node_kind: &str) -> Option<&IndexSet<String>> {
+        self.fragments.get(&language)?.get(node_kind)
+    }
+
+    /// The number of fragments learned, for all languages and node kinds.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.fragments
+            .values()
+            .flat_map(HashMap::values)
+            .map(IndexSet::len)
+            .sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Learns `fragment` for the smallest named node of `doc` spanning `range`, whose columns
+    /// are in bytes, returning whether it was not known yet.
+    ///
+    /// Blank fragments, e.g., the whitespace of formatting edits, and fragments suggested for
+    /// the whole document or for erroneous nodes are not learned.
+    pub fn learn(&mut self, doc: &TextDocument, range: Range, fragment: &str) -> bool {
+        if fragment.trim().is_empty() || fragment.len() > MAX_FRAGMENT_LEN {
+            return false;
+        }
+        let point = |position: lsp_types::Position| tree_sitter::Point {
+            row: position.line as usize,
+            column: position.character as usize,
+        };
+        let root = doc.parse_tree().root_node();
+        let Some(node) =
+            root.named_descendant_for_point_range(point(range.start), point(range.end))
+        else {
+            return false;
+        };
+        if node == root || node.is_error() || node.is_missing() {
+            return false;
+        }
+        let fragments = self
+            .fragments
+            .entry(doc.language())
+            .or_default()
+            .entry(node.kind().to_owned())
+            .or_default();
+        fragments.len() < MAX_FRAGMENTS_PER_KIND && fragments.insert(fragment.to_owned())
+    }
+}
+
+/// Learns the code in the completions, the formatting edits, and the `workspace/applyEdit`
+/// requests of the server into [`LearnedFragments`] after each execution.
+///
+/// The feedback never deems an input interesting on its own.
+#[derive(Debug)]
+pub struct LearnedFragmentsFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+}
+
+impl LearnedFragmentsFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+        }
+    }
+}
+
+impl Named for LearnedFragmentsFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("LearnedFragmentsFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for LearnedFragmentsFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        if !state.has_metadata::<LearnedFragments>() {
+            state.add_metadata(LearnedFragments::default());
+        }
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for LearnedFragmentsFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        input: &LspInput,
+        observers: &Observers,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        if *exit_kind != ExitKind::Ok {
+            return Ok(false);
+        }
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        let Ok(matching) = RequestResponseMatching::match_messages(
+            input.messages.iter(),
+            observer.captured_messages().iter(),
+        ) else {
+            return Ok(false);
+        };
+
+        // The server sends positions in the negotiated encoding, while documents are indexed
+        // by bytes.
+        let unit = input.messages.position_encoding().negotiated;
+        let learned = state.metadata_or_insert_with(LearnedFragments::default);
+        for (request, response) in &matching.responses {
+            let Some(doc) = request
+                .document()
+                .and_then(|it| document_of(input, &it.uri))
+            else {
+                continue;
+            };
+            for (range, fragment) in suggested_edits(request, response, unit, doc) {
+                learned.learn(doc, range, fragment);
+            }
+        }
+        for request in &matching.requests_from_server {
+            let LspMessage::ApplyWorkspaceEdit(params) = request else {
+                continue;
+            };
+            for (uri, edit) in workspace_edits(&params.edit) {
+                if let Some(doc) = document_of(input, uri) {
+                    learned.learn(doc, utf8_range(edit.range, unit, doc), &edit.new_text);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// The source file of `input` at `uri`, which may be localized to the workspace directory.
+fn document_of<'a>(input: &'a LspInput, uri: &Uri) -> Option<&'a TextDocument> {
+    let uri = LspInput::lift_uri(uri);
+    uri::path_from_virtual_uri(&uri)?;
+    input.get_text_document(&uri)
+}
+
+/// Converts the columns of `range`, which the server sent in `unit`, to the bytes of `doc`.
+fn utf8_range(range: Range, unit: ColumnUnit, doc: &TextDocument) -> Range {
+    match unit {
+        ColumnUnit::Utf8 => range,
+        ColumnUnit::Utf16 => {
+            let content = doc.content();
+            Range::new(
+                utf8_position(range.start, &content),
+                utf8_position(range.end, &content),
+            )
+        }
+    }
+}
+
+/// The code the server suggested in `response` for the document of `request`, with the ranges
+/// in the bytes of `doc`, the document of `request`.
+///
+/// Completions in the snippet format are skipped, as their placeholders are not code.
+fn suggested_edits<'a>(
+    request: &LspMessage,
+    response: &'a LspResponse,
+    unit: ColumnUnit,
+    doc: &TextDocument,
+) -> Vec<(Range, &'a str)> {
+    match response {
+        LspResponse::Completion(Some(completion)) => {
+            let items = match completion {
+                CompletionResponse::Array(items) => items,
+                CompletionResponse::List(list) => &list.items,
+            };
+            items
+                .iter()
+                .filter(|it| it.insert_text_format != Some(InsertTextFormat::SNIPPET))
+                .filter_map(|item| match &item.text_edit {
+                    Some(CompletionTextEdit::Edit(edit)) => {
+                        Some((utf8_range(edit.range, unit, doc), &*edit.new_text))
+                    }
+                    Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+                        Some((utf8_range(edit.replace, unit, doc), &*edit.new_text))
+                    }
+                    // The positions of the requests are kept in bytes.
+                    None => {
+                        let position = *request.position()?;
+                        Some((Range::new(position, position), item.insert_text.as_deref()?))
+                    }
+                })
+                .collect()
+        }
+        LspResponse::Formatting(Some(edits))
+        | LspResponse::RangeFormatting(Some(edits))
+        | LspResponse::OnTypeFormatting(Some(edits)) => edits
+            .iter()
+            .map(|it| (utf8_range(it.range, unit, doc), it.new_text.as_str()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The text edits of `edit` with the URIs of their documents.
+fn workspace_edits(edit: &WorkspaceEdit) -> Vec<(&Uri, &TextEdit)> {
+    let document_edits: Vec<&TextDocumentEdit> = match &edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.iter().collect(),
+        Some(DocumentChanges::Operations(operations)) => operations
+            .iter()
+            .filter_map(|it| match it {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let document_edits = document_edits.into_iter().flat_map(|document_edit| {
+        let uri = &document_edit.text_document.uri;
+        document_edit.edits.iter().map(move |edit| match edit {
+            OneOf::Left(edit) => (uri, edit),
+            OneOf::Right(annotated) => (uri, &annotated.text_edit),
+        })
+    });
+    edit.changes
+        .iter()
+        .flatten()
+        .flat_map(|(uri, edits)| edits.iter().map(move |edit| (uri, edit)))
+        .chain(document_edits)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+
+    use super::*;
+
+    #[test]
+    fn learn_fragments_by_node_kind() {
+        let doc = TextDocument::new(Language::C, b"int main(void) { return 0; }".to_vec());
+        let zero = Range::new(Position::new(0, 24), Position::new(0, 25));
+        let mut learned = LearnedFragments::default();
+        assert!(learned.learn(&doc, zero, "42"));
+        assert!(!learned.learn(&doc, zero, "42"));
+        assert!(!learned.learn(&doc, zero, " \n"));
+        let fragments = learned.get(Language::C, "number_literal").unwrap();
+        assert_eq!(fragments.iter().collect::<Vec<_>>(), ["42"]);
+        assert_eq!(learned.len(), 1);
+    }
+
+    #[test]
+    fn convert_utf16_ranges_to_bytes() {
+        let doc = TextDocument::new(Language::C, "int é = 0;".as_bytes().to_vec());
+        let zero = Range::new(Position::new(0, 8), Position::new(0, 9));
+        assert_eq!(
+            utf8_range(zero, ColumnUnit::Utf16, &doc),
+            Range::new(Position::new(0, 9), Position::new(0, 10))
+        );
+        assert_eq!(utf8_range(zero, ColumnUnit::Utf8, &doc), zero);
+    }
+}
@@ -9,12 +9,20 @@ use lsp_types::{
 };
 use serde::{Deserialize, Serialize};
 
+use super::semantic_tokens::SemanticTokensInfo;
+
 #[allow(clippy::unsafe_derive_deserialize)]
 #[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
 pub struct LspResponseInfo {
     pub diagnostics: HashSet<Diagnostic>,
     pub param_fragments: ParamFragments,
     pub symbol_ranges: HashSet<SymbolRange>,
+    /// The glob patterns of the file watchers registered by the server,
+    /// relative to the workspace root.
+    #[serde(default)]
+    pub file_watchers: HashSet<String>,
+    #[serde(default)]
+    pub semantic_tokens: SemanticTokensInfo,
 }
 
 #[allow(clippy::unsafe_derive_deserialize)]
@@ -0,0 +1,202 @@
+//! Identifiers minted by the server, kept across inputs.
+//!
+//! Result ids and progress tokens only make sense within the session
+//! that produced them. Reusing them in a later session, i.e., after a restart of the server,
+//! exercises how the server handles stale handles.
+
+use std::{borrow::Cow, collections::VecDeque};
+
+use libafl::{
+    HasMetadata,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use lsp_types::{
+    NumberOrString,
+    request::{Request, WorkDoneProgressCreate},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    execution::responses::LspOutputObserver, lsp::json_rpc::JsonRPCMessage, lsp_input::LspInput,
+    utils::AflContext,
+};
+
+/// The number of identifiers of each kind kept in the pool.
+const POOL_CAPACITY: usize = 32;
+
+/// The identifiers most recently minted by the server, oldest first.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct MintedIds {
+    /// The `resultId`s of semantic tokens and diagnostic reports.
+    pub result_ids: VecDeque<String>,
+    /// The tokens of `window/workDoneProgress/create` requests.
+    pub progress_tokens: VecDeque<NumberOrString>,
+}
+
+impl MintedIds {
+    fn remember<T: PartialEq>(pool: &mut VecDeque<T>, id: T) {
+        if pool.contains(&id) {
+            return;
+        }
+        if pool.len() >= POOL_CAPACITY {
+            pool.pop_front();
+        }
+        pool.push_back(id);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.result_ids.is_empty() && self.progress_tokens.is_empty()
+    }
+
+    /// Records the identifiers minted by the server in `message`.
+    pub fn record(&mut self, message: &JsonRPCMessage) {
+        match message {
+            JsonRPCMessage::Request { method, params, .. }
+                if method == WorkDoneProgressCreate::METHOD =>
+            {
+                if let Some(token) = params
+                    .get("token")
+                    .and_then(|it| serde_json::from_value(it.clone()).ok())
+                {
+                    Self::remember(&mut self.progress_tokens, token);
+                }
+            }
+            JsonRPCMessage::Response {
+                result: Some(result),
+                ..
+            } => self.record_result_ids(result),
+            _ => {}
+        }
+    }
+
+    fn record_result_ids(&mut self, value: &serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                for (key, value) in object {
+                    if let ("resultId", serde_json::Value::String(id)) = (key.as_str(), value) {
+                        Self::remember(&mut self.result_ids, id.clone());
+                    } else {
+                        self.record_result_ids(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                items.iter().for_each(|it| self.record_result_ids(it));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Records the identifiers minted by the server in [`MintedIds`] after each execution.
+///
+/// The feedback never deems an input interesting on its own.
+#[derive(Debug)]
+pub struct MintedIdsFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+}
+
+impl MintedIdsFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+        }
+    }
+}
+
+impl Named for MintedIdsFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MintedIdsFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for MintedIdsFeedback where State: HasMetadata {}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for MintedIdsFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _input: &LspInput,
+        observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        let pool = state.metadata_or_insert_with(MintedIds::default);
+        observer
+            .captured_messages()
+            .iter()
+            .for_each(|it| pool.record(it));
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use lsp_types::NumberOrString;
+    use serde_json::json;
+
+    use super::{MintedIds, POOL_CAPACITY};
+    use crate::lsp::json_rpc::{JsonRPC20, JsonRPCMessage, MessageId};
+
+    fn request(method: &'static str, params: serde_json::Value) -> JsonRPCMessage {
+        JsonRPCMessage::Request {
+            jsonrpc: JsonRPC20,
+            id: MessageId::Number(0),
+            method: Cow::Borrowed(method),
+            params,
+        }
+    }
+
+    #[test]
+    fn record_minted_ids() {
+        let mut pool = MintedIds::default();
+        pool.record(&request(
+            "window/workDoneProgress/create",
+            json!({ "token": "rustAnalyzer/Indexing" }),
+        ));
+        // Registration ids are not progress tokens.
+        pool.record(&request(
+            "client/registerCapability",
+            json!({ "registrations": [{ "id": "watch-1", "method": "workspace/didChangeWatchedFiles" }] }),
+        ));
+        pool.record(&JsonRPCMessage::Response {
+            jsonrpc: JsonRPC20,
+            id: Some(MessageId::Number(3)),
+            result: Some(json!({ "resultId": "7", "data": [] })),
+            error: None,
+        });
+        assert_eq!(
+            pool.progress_tokens,
+            [NumberOrString::String("rustAnalyzer/Indexing".to_owned())]
+        );
+        assert_eq!(pool.result_ids, ["7"]);
+    }
+
+    #[test]
+    fn pool_keeps_newest() {
+        let mut pool = MintedIds::default();
+        for id in 0..=POOL_CAPACITY {
+            MintedIds::remember(&mut pool.result_ids, id.to_string());
+        }
+        assert_eq!(pool.result_ids.len(), POOL_CAPACITY);
+        assert_eq!(pool.result_ids.front().map(String::as_str), Some("1"));
+    }
+}
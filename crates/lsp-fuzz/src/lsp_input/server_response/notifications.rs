@@ -0,0 +1,148 @@
+//! Counting the notifications of the server and logging the messages of selected methods
+//! and the output of selected side channels.
+
+use std::borrow::Cow;
+
+use ahash::{HashMap, HashSet};
+use libafl::{
+    HasMetadata,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    execution::responses::LspOutputObserver, lsp::json_rpc::JsonRPCMessage, lsp_input::LspInput,
+    utils::AflContext,
+};
+
+/// The number of notifications sent by the server, by method, since they were last reported
+/// by [`NotificationStatsStage`](crate::stages::NotificationStatsStage).
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, SerdeAny)]
+pub struct NotificationCounts {
+    pub methods: HashMap<String, u64>,
+}
+
+impl NotificationCounts {
+    /// The `n` most frequent methods with their counts, the most frequent first.
+    #[must_use]
+    pub fn top(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut methods: Vec<_> = self
+            .methods
+            .iter()
+            .map(|(method, &count)| (method.as_str(), count))
+            .collect();
+        methods.sort_unstable_by(|(a_method, a), (b_method, b)| {
+            b.cmp(a).then_with(|| a_method.cmp(b_method))
+        });
+        methods.truncate(n);
+        methods
+    }
+}
+
+/// Counts the notifications of the server in [`NotificationCounts`], and logs the full
+/// messages of the server whose methods are selected with [`Self::with_logged_methods`]
+/// and the lines of the side channels selected with [`Self::with_logged_side_channels`].
+///
+/// The feedback never deems an input interesting on its own.
+#[derive(Debug)]
+pub struct NotificationLogFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+    logged_methods: HashSet<String>,
+    logged_side_channels: HashSet<String>,
+}
+
+impl NotificationLogFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            logged_methods: HashSet::default(),
+            logged_side_channels: HashSet::default(),
+        }
+    }
+
+    /// Logs the requests and notifications of the server with one of `methods`.
+    #[must_use]
+    pub fn with_logged_methods(self, methods: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            logged_methods: methods.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Logs the lines the target wrote to one of the side `channels`, see
+    /// [`crate::execution::side_channel`].
+    #[must_use]
+    pub fn with_logged_side_channels(self, channels: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            logged_side_channels: channels.into_iter().collect(),
+            ..self
+        }
+    }
+}
+
+impl Named for NotificationLogFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("NotificationLogFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for NotificationLogFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        state.add_metadata(NotificationCounts::default());
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for NotificationLogFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _input: &LspInput,
+        observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        let counts = state.metadata_or_insert_with(NotificationCounts::default);
+        for message in observer.captured_messages() {
+            if let JsonRPCMessage::Notification { method, .. } = message {
+                // The method is only allocated the first time it is seen.
+                if let Some(count) = counts.methods.get_mut(method.as_ref()) {
+                    *count += 1;
+                } else {
+                    counts.methods.insert(method.to_string(), 1);
+                }
+            }
+            if let Some(method) = message.method()
+                && self.logged_methods.contains(method.as_ref())
+            {
+                let payload = serde_json::to_string(message).unwrap_or_default();
+                info!(%method, %payload, "Server message");
+            }
+        }
+        for line in observer.side_output() {
+            if self.logged_side_channels.contains(&line.channel) {
+                info!(channel = %line.channel, text = %line.text, "Side-channel output");
+            }
+        }
+        Ok(false)
+    }
+}
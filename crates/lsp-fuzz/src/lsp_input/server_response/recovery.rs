@@ -0,0 +1,192 @@
+//! Comparing the error recovery of the server with that of tree-sitter.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+};
+
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use lsp_types::{Uri, notification::PublishDiagnostics};
+use serde::{Deserialize, Serialize};
+
+use super::matching::RequestResponseMatching;
+use crate::{
+    execution::responses::LspOutputObserver,
+    lsp_input::{LspInput, uri},
+    text_document::{GrammarBasedMutation, grammar::tree_sitter::TreeIter},
+    utils::AflContext,
+};
+
+/// How the diagnostics of the server relate to the syntax errors found by tree-sitter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecoveryMismatchKind {
+    /// The server published an empty list of diagnostics for the document.
+    Silent,
+    /// None of the diagnostics of the server is on a line with a syntax error.
+    Displaced,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryMismatch {
+    pub uri: Uri,
+    pub kind: RecoveryMismatchKind,
+    /// Zero-based lines of the `ERROR` and `MISSING` nodes.
+    pub error_lines: BTreeSet<u32>,
+}
+
+/// The documents of a test case on which the server recovered differently from tree-sitter.
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct RecoveryMismatches {
+    pub mismatches: Vec<RecoveryMismatch>,
+}
+
+/// Counts of documents with syntax errors, by how the server reported them.
+///
+/// Only documents for which the server published diagnostics are counted.
+#[derive(Debug, Default, Serialize, Deserialize, SerdeAny)]
+pub struct ParserRecoveryStats {
+    pub agreed: u64,
+    pub silent: u64,
+    pub displaced: u64,
+}
+
+/// Tracks whether the server recovers from syntax errors in the way tree-sitter does.
+///
+/// Tallies are kept in [`ParserRecoveryStats`], and test cases get [`RecoveryMismatches`]
+/// attached. The feedback never deems an input interesting on its own.
+#[derive(Debug)]
+pub struct ParserRecoveryFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+    mismatches: Vec<RecoveryMismatch>,
+}
+
+impl ParserRecoveryFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            mismatches: Vec::new(),
+        }
+    }
+}
+
+impl Named for ParserRecoveryFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ParserRecoveryFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for ParserRecoveryFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        state.add_metadata(ParserRecoveryStats::default());
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for ParserRecoveryFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        input: &LspInput,
+        observers: &Observers,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        self.mismatches.clear();
+        if *exit_kind != ExitKind::Ok {
+            return Ok(false);
+        }
+        let erroneous_docs: Vec<_> = input
+            .workspace
+            .iter_files()
+            .filter_map(|(path, entry)| entry.as_source_file().map(|doc| (path, doc)))
+            .filter(|(_, doc)| doc.parse_tree().root_node().has_error())
+            .collect();
+        if erroneous_docs.is_empty() {
+            return Ok(false);
+        }
+
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        let Ok(matching) = RequestResponseMatching::match_messages(
+            input.messages.iter(),
+            observer.captured_messages().iter(),
+        ) else {
+            return Ok(false);
+        };
+        // Later notifications supersede earlier ones for the same document.
+        let mut published: HashMap<Uri, Vec<u32>> = HashMap::new();
+        for params in matching.find_notifications::<PublishDiagnostics>() {
+            let lines = params.diagnostics.iter().map(|it| it.range.start.line);
+            published.insert(
+                LspInput::lift_uri(&params.uri).into_owned(),
+                lines.collect(),
+            );
+        }
+
+        let stats = state.metadata_or_insert_with(ParserRecoveryStats::default);
+        for (path, doc) in erroneous_docs {
+            let Some(uri) = uri::virtual_uri_for_path(&path) else {
+                continue;
+            };
+            let Some(diagnostic_lines) = published.get(&uri) else {
+                continue;
+            };
+            let error_lines: BTreeSet<u32> = doc
+                .parse_tree()
+                .iter()
+                .filter(|node| node.is_error() || node.is_missing())
+                .filter_map(|node| u32::try_from(node.start_position().row).ok())
+                .collect();
+            let kind = if diagnostic_lines.is_empty() {
+                stats.silent += 1;
+                RecoveryMismatchKind::Silent
+            } else if diagnostic_lines.iter().all(|it| !error_lines.contains(it)) {
+                stats.displaced += 1;
+                RecoveryMismatchKind::Displaced
+            } else {
+                stats.agreed += 1;
+                continue;
+            };
+            self.mismatches.push(RecoveryMismatch {
+                uri,
+                kind,
+                error_lines,
+            });
+        }
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        _observers: &Observers,
+        testcase: &mut Testcase<LspInput>,
+    ) -> Result<(), libafl::Error> {
+        if !self.mismatches.is_empty() {
+            testcase.add_metadata(RecoveryMismatches {
+                mismatches: std::mem::take(&mut self.mismatches),
+            });
+        }
+        Ok(())
+    }
+}
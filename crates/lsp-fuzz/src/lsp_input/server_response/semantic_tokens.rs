@@ -0,0 +1,255 @@
+//! Semantic tokens legend and result ids observed in the responses of the server.
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use lsp_types::{
+    SemanticToken, SemanticTokensDeltaParams, SemanticTokensEdit, SemanticTokensFullDeltaResult,
+    SemanticTokensLegend, SemanticTokensParams, SemanticTokensRangeResult, SemanticTokensResult,
+    TextDocumentIdentifier, Uri,
+};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::matching::RequestResponseMatching;
+use crate::lsp::{LspMessage, json_rpc::JsonRPCMessage, message::LspResponse};
+
+/// What the server told about semantic tokens during an execution.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SemanticTokensInfo {
+    /// The legend advertised in the capabilities of the server.
+    pub legend: Option<SemanticTokensLegend>,
+    /// The result ids of the semantic tokens of each document, in the order they were received.
+    pub result_ids: HashMap<Uri, Vec<String>>,
+    /// The number of tokens and delta edits inconsistent with the legend or the earlier result.
+    pub inconsistencies: usize,
+}
+
+impl SemanticTokensInfo {
+    /// Collects the legend from the initialization response and the result ids and
+    /// inconsistencies from the responses to the semantic tokens requests in `sent_messages`.
+    pub fn collect<'a>(
+        sent_messages: impl Iterator<Item = &'a LspMessage>,
+        received_messages: &[JsonRPCMessage],
+        matching: &RequestResponseMatching<'_>,
+    ) -> Self {
+        let mut info = Self {
+            legend: received_messages.iter().find_map(advertised_legend),
+            ..Self::default()
+        };
+        // The number of tokens of each result, to check the edits of later deltas.
+        let mut token_counts: HashMap<String, usize> = HashMap::new();
+        for request in sent_messages {
+            let Some(response) = matching.find_response_of(request) else {
+                continue;
+            };
+            let (uri, result_id, token_count) = match (request, response) {
+                (
+                    LspMessage::SemanticTokensFullRequest(SemanticTokensParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        ..
+                    }),
+                    LspResponse::SemanticTokensFullRequest(Some(SemanticTokensResult::Tokens(it))),
+                )
+                | (
+                    LspMessage::SemanticTokensFullDeltaRequest(SemanticTokensDeltaParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        ..
+                    }),
+                    LspResponse::SemanticTokensFullDeltaRequest(Some(
+                        SemanticTokensFullDeltaResult::Tokens(it),
+                    )),
+                ) => {
+                    info.check_tokens(&it.data);
+                    (uri, &it.result_id, Some(it.data.len()))
+                }
+                (
+                    LspMessage::SemanticTokensRangeRequest(_),
+                    LspResponse::SemanticTokensRangeRequest(Some(
+                        SemanticTokensRangeResult::Tokens(it),
+                    )),
+                ) => {
+                    info.check_tokens(&it.data);
+                    continue;
+                }
+                (
+                    LspMessage::SemanticTokensFullDeltaRequest(params),
+                    LspResponse::SemanticTokensFullDeltaRequest(Some(
+                        SemanticTokensFullDeltaResult::TokensDelta(delta),
+                    )),
+                ) => {
+                    let previous = token_counts.get(&params.previous_result_id).copied();
+                    let token_count = info.check_edits(previous, &delta.edits);
+                    (&params.text_document.uri, &delta.result_id, token_count)
+                }
+                _ => continue,
+            };
+            if let Some(result_id) = result_id {
+                if let Some(token_count) = token_count {
+                    token_counts.insert(result_id.clone(), token_count);
+                }
+                info.result_ids
+                    .entry(uri.clone())
+                    .or_default()
+                    .push(result_id.clone());
+            }
+        }
+        if info.inconsistencies > 0 {
+            debug!(
+                inconsistencies = info.inconsistencies,
+                "Semantic tokens inconsistent with the legend or earlier results"
+            );
+        }
+        info
+    }
+
+    /// Counts the tokens whose type or modifiers are not in the legend.
+    fn check_tokens(&mut self, tokens: &[SemanticToken]) {
+        let Some(legend) = &self.legend else {
+            return;
+        };
+        let types = legend.token_types.len();
+        let modifiers = legend.token_modifiers.len();
+        self.inconsistencies += tokens
+            .iter()
+            .filter(|it| {
+                it.token_type as usize >= types
+                    || it
+                        .token_modifiers_bitset
+                        .checked_shr(u32::try_from(modifiers).unwrap_or(u32::MAX))
+                        .is_some_and(|it| it != 0)
+            })
+            .count();
+    }
+
+    /// Checks the edits of a delta against the legend and the number of tokens of the result
+    /// it is relative to, if known, and returns the number of tokens after the edits.
+    ///
+    /// The offsets of all edits refer to the array of the earlier result, so the edits are
+    /// applied from the back, where they do not shift the offsets of the edits before them.
+    fn check_edits(
+        &mut self,
+        mut token_count: Option<usize>,
+        edits: &[SemanticTokensEdit],
+    ) -> Option<usize> {
+        let mut edits: Vec<_> = edits.iter().collect();
+        edits.sort_by_key(|it| Reverse(it.start));
+        // Edits index the flat array of integers, five per token.
+        let mut unedited_end = token_count.map(|count| count * 5);
+        for edit in edits {
+            let data = edit.data.as_deref().unwrap_or_default();
+            self.check_tokens(data);
+            let (start, deleted) = (edit.start as usize, edit.delete_count as usize);
+            // The edits must not overlap the ones after them.
+            let Some(end) = unedited_end else {
+                continue;
+            };
+            if start % 5 != 0 || deleted % 5 != 0 || start + deleted > end {
+                self.inconsistencies += 1;
+                unedited_end = None;
+                token_count = None;
+            } else {
+                unedited_end = Some(start);
+                token_count = token_count.map(|count| count - deleted / 5 + data.len());
+            }
+        }
+        token_count
+    }
+
+    /// All result ids of the documents other than `uri`.
+    pub fn foreign_result_ids<'a>(&'a self, uri: &'a Uri) -> impl Iterator<Item = &'a String> {
+        self.result_ids
+            .iter()
+            .filter(move |(it, _)| *it != uri)
+            .flat_map(|(_, ids)| ids)
+    }
+}
+
+/// The semantic tokens legend in the `initialize` response in `message`, if any.
+fn advertised_legend(message: &JsonRPCMessage) -> Option<SemanticTokensLegend> {
+    let JsonRPCMessage::Response {
+        result: Some(result),
+        ..
+    } = message
+    else {
+        return None;
+    };
+    let legend = result
+        .get("capabilities")?
+        .get("semanticTokensProvider")?
+        .get("legend")?;
+    serde_json::from_value(legend.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{SemanticToken, SemanticTokenType, SemanticTokensEdit, SemanticTokensLegend};
+
+    use super::SemanticTokensInfo;
+
+    #[test]
+    fn tokens_outside_legend() {
+        let mut info = SemanticTokensInfo {
+            legend: Some(SemanticTokensLegend {
+                token_types: vec![SemanticTokenType::TYPE, SemanticTokenType::VARIABLE],
+                token_modifiers: vec![lsp_types::SemanticTokenModifier::STATIC],
+            }),
+            ..SemanticTokensInfo::default()
+        };
+        let token = |token_type, token_modifiers_bitset| SemanticToken {
+            delta_line: 0,
+            delta_start: 0,
+            length: 1,
+            token_type,
+            token_modifiers_bitset,
+        };
+        info.check_tokens(&[token(0, 0), token(1, 1), token(2, 0), token(0, 2)]);
+        assert_eq!(info.inconsistencies, 2);
+    }
+
+    fn edit(start: u32, delete_count: u32, tokens: usize) -> SemanticTokensEdit {
+        let token = SemanticToken {
+            delta_line: 0,
+            delta_start: 0,
+            length: 1,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        };
+        SemanticTokensEdit {
+            start,
+            delete_count,
+            data: Some(vec![token; tokens]),
+        }
+    }
+
+    #[test]
+    fn edits_refer_to_the_earlier_result() {
+        let mut info = SemanticTokensInfo::default();
+        // Removes the first token and replaces the last of four with two, in either order.
+        let edits = [edit(0, 5, 0), edit(15, 5, 2)];
+        assert_eq!(info.check_edits(Some(4), &edits), Some(5));
+        let edits = [edit(15, 5, 2), edit(0, 5, 0)];
+        assert_eq!(info.check_edits(Some(4), &edits), Some(5));
+        // Only valid in the earlier result, not after the first edit.
+        let edits = [edit(0, 10, 0), edit(10, 10, 0)];
+        assert_eq!(info.check_edits(Some(4), &edits), Some(0));
+        assert_eq!(info.inconsistencies, 0);
+    }
+
+    #[test]
+    fn inconsistent_edits() {
+        let mut info = SemanticTokensInfo::default();
+        // Past the end of the earlier result.
+        assert_eq!(info.check_edits(Some(2), &[edit(5, 10, 0)]), None);
+        // Not aligned to tokens.
+        assert_eq!(info.check_edits(Some(2), &[edit(3, 5, 0)]), None);
+        // Overlapping.
+        assert_eq!(
+            info.check_edits(Some(4), &[edit(0, 10, 0), edit(5, 5, 0)]),
+            None
+        );
+        assert_eq!(info.inconsistencies, 3);
+        // Without the earlier result, the edits cannot be checked.
+        assert_eq!(info.check_edits(None, &[edit(5, 10, 0)]), None);
+        assert_eq!(info.inconsistencies, 3);
+    }
+}
@@ -0,0 +1,331 @@
+//! Detecting the methods the server implements from the capabilities it declares.
+//!
+//! Servers answer requests for methods without a provider in their `InitializeResult` with
+//! errors or empty results, so the appended requests favor the methods that are declared.
+
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl::{
+    HasMetadata,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    execution::responses::LspOutputObserver,
+    lsp::json_rpc::JsonRPCMessage,
+    lsp_input::{LspInput, messages::LspMessageSequence},
+    utils::AflContext,
+};
+
+/// The fields of `ServerCapabilities` declaring each method, from the provider down to
+/// the nested option of the method, e.g., `resolveProvider` for the resolve requests.
+const METHOD_PROVIDERS: &[(&str, &[&str])] = &[
+    ("textDocument/hover", &["hoverProvider"]),
+    ("textDocument/completion", &["completionProvider"]),
+    (
+        "completionItem/resolve",
+        &["completionProvider", "resolveProvider"],
+    ),
+    ("textDocument/signatureHelp", &["signatureHelpProvider"]),
+    ("textDocument/declaration", &["declarationProvider"]),
+    ("textDocument/definition", &["definitionProvider"]),
+    ("textDocument/typeDefinition", &["typeDefinitionProvider"]),
+    ("textDocument/implementation", &["implementationProvider"]),
+    ("textDocument/references", &["referencesProvider"]),
+    (
+        "textDocument/documentHighlight",
+        &["documentHighlightProvider"],
+    ),
+    ("textDocument/documentSymbol", &["documentSymbolProvider"]),
+    ("textDocument/codeAction", &["codeActionProvider"]),
+    (
+        "codeAction/resolve",
+        &["codeActionProvider", "resolveProvider"],
+    ),
+    ("textDocument/codeLens", &["codeLensProvider"]),
+    ("codeLens/resolve", &["codeLensProvider", "resolveProvider"]),
+    ("textDocument/documentLink", &["documentLinkProvider"]),
+    (
+        "documentLink/resolve",
+        &["documentLinkProvider", "resolveProvider"],
+    ),
+    ("textDocument/documentColor", &["colorProvider"]),
+    ("textDocument/colorPresentation", &["colorProvider"]),
+    ("textDocument/formatting", &["documentFormattingProvider"]),
+    (
+        "textDocument/rangeFormatting",
+        &["documentRangeFormattingProvider"],
+    ),
+    (
+        "textDocument/onTypeFormatting",
+        &["documentOnTypeFormattingProvider"],
+    ),
+    ("textDocument/rename", &["renameProvider"]),
+    (
+        "textDocument/prepareRename",
+        &["renameProvider", "prepareProvider"],
+    ),
+    ("textDocument/foldingRange", &["foldingRangeProvider"]),
+    ("textDocument/selectionRange", &["selectionRangeProvider"]),
+    (
+        "textDocument/linkedEditingRange",
+        &["linkedEditingRangeProvider"],
+    ),
+    (
+        "textDocument/prepareCallHierarchy",
+        &["callHierarchyProvider"],
+    ),
+    ("callHierarchy/incomingCalls", &["callHierarchyProvider"]),
+    ("callHierarchy/outgoingCalls", &["callHierarchyProvider"]),
+    (
+        "textDocument/prepareTypeHierarchy",
+        &["typeHierarchyProvider"],
+    ),
+    ("typeHierarchy/supertypes", &["typeHierarchyProvider"]),
+    ("typeHierarchy/subtypes", &["typeHierarchyProvider"]),
+    (
+        "textDocument/semanticTokens/full",
+        &["semanticTokensProvider", "full"],
+    ),
+    (
+        "textDocument/semanticTokens/full/delta",
+        &["semanticTokensProvider", "full", "delta"],
+    ),
+    (
+        "textDocument/semanticTokens/range",
+        &["semanticTokensProvider", "range"],
+    ),
+    ("textDocument/moniker", &["monikerProvider"]),
+    ("textDocument/inlayHint", &["inlayHintProvider"]),
+    (
+        "inlayHint/resolve",
+        &["inlayHintProvider", "resolveProvider"],
+    ),
+    ("textDocument/inlineValue", &["inlineValueProvider"]),
+    ("textDocument/diagnostic", &["diagnosticProvider"]),
+    (
+        "workspace/diagnostic",
+        &["diagnosticProvider", "workspaceDiagnostics"],
+    ),
+    ("workspace/symbol", &["workspaceSymbolProvider"]),
+    (
+        "workspaceSymbol/resolve",
+        &["workspaceSymbolProvider", "resolveProvider"],
+    ),
+    ("workspace/executeCommand", &["executeCommandProvider"]),
+];
+
+/// The `capabilities` of the `InitializeResult` of the server.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct SupportedMethods {
+    capabilities: serde_json::Value,
+}
+
+impl SupportedMethods {
+    #[must_use]
+    pub const fn new(capabilities: serde_json::Value) -> Self {
+        Self { capabilities }
+    }
+
+    #[must_use]
+    pub const fn capabilities(&self) -> &serde_json::Value {
+        &self.capabilities
+    }
+
+    /// Whether the server declares a provider for `method`, along with the nested option
+    /// of the method, e.g., `renameProvider.prepareProvider` for `textDocument/prepareRename`.
+    ///
+    /// Methods that are not tied to a provider, e.g., notifications, are always supported.
+    #[must_use]
+    pub fn is_supported(&self, method: &str) -> bool {
+        let Some((_, fields)) = METHOD_PROVIDERS.iter().find(|(it, _)| *it == method) else {
+            return true;
+        };
+        fields
+            .iter()
+            .try_fold(&self.capabilities, |options, field| {
+                options.get(field).filter(|it| {
+                    !matches!(it, serde_json::Value::Null | serde_json::Value::Bool(false))
+                })
+            })
+            .is_some()
+    }
+
+    /// The methods for which the server declares no provider.
+    pub fn unsupported_methods(&self) -> impl Iterator<Item = &'static str> + use<'_> {
+        METHOD_PROVIDERS
+            .iter()
+            .map(|(method, _)| *method)
+            .filter(|it| !self.is_supported(it))
+    }
+
+    /// Reads the capabilities saved by [`Self::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or does not contain JSON.
+    pub fn load(path: &Path) -> Result<Self, libafl::Error> {
+        let content = fs::read(path).afl_context("Reading server capabilities")?;
+        let capabilities =
+            serde_json::from_slice(&content).afl_context("Parsing server capabilities")?;
+        Ok(Self::new(capabilities))
+    }
+
+    /// Writes the capabilities to `path` as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), libafl::Error> {
+        let content = serde_json::to_vec_pretty(&self.capabilities)
+            .afl_context("Serializing server capabilities")?;
+        fs::write(path, content).afl_context("Writing server capabilities")
+    }
+}
+
+/// Records the capabilities of the server in [`SupportedMethods`] from the first
+/// `InitializeResult` it sends to a plain client
+/// (see [`LspMessageSequence::has_plain_initialize`]).
+///
+/// With a path, the capabilities are saved there once detected and loaded from there when
+/// a campaign against the same target resumes. The feedback never deems an input interesting.
+#[derive(Debug)]
+pub struct SupportedMethodsFeedback {
+    observer_handle: Handle<LspOutputObserver>,
+    path: Option<PathBuf>,
+}
+
+impl SupportedMethodsFeedback {
+    #[must_use]
+    pub fn new(observer: &LspOutputObserver, path: Option<PathBuf>) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            path,
+        }
+    }
+}
+
+impl Named for SupportedMethodsFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SupportedMethodsFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for SupportedMethodsFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        if let Some(path) = self.path.as_deref().filter(|it| it.exists())
+            && !state.has_metadata::<SupportedMethods>()
+        {
+            state.add_metadata(SupportedMethods::load(path)?);
+        }
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for SupportedMethodsFeedback
+where
+    State: HasMetadata,
+    Observers: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        input: &LspInput,
+        observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        // The capabilities declared to a client with another profile, experimental capabilities
+        // or settings may differ from the default ones.
+        if state.has_metadata::<SupportedMethods>() || !input.messages.has_plain_initialize() {
+            return Ok(false);
+        }
+        let observer = observers
+            .get(&self.observer_handle)
+            .afl_context("LspOutputObserver not attached")?;
+        let capabilities = observer.captured_messages().iter().find_map(|it| match it {
+            JsonRPCMessage::Response {
+                result: Some(result),
+                ..
+            } => result.get("capabilities").filter(|it| it.is_object()),
+            _ => None,
+        });
+        let Some(capabilities) = capabilities else {
+            return Ok(false);
+        };
+        let supported_methods = SupportedMethods::new(capabilities.clone());
+        let unsupported: Vec<_> = supported_methods.unsupported_methods().collect();
+        info!(
+            unsupported = unsupported.len(),
+            "Detected the server capabilities. Unsupported methods: {}",
+            unsupported.join(", ")
+        );
+        if let Some(path) = &self.path
+            && let Err(err) = supported_methods.save(path)
+        {
+            warn!("Failed to save the server capabilities: {err}");
+        }
+        state.add_metadata(supported_methods);
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::SupportedMethods;
+
+    #[test]
+    fn detect_unsupported_methods() {
+        let supported = SupportedMethods::new(json!({
+            "hoverProvider": true,
+            "renameProvider": { "prepareProvider": true },
+            "referencesProvider": false,
+        }));
+        assert!(supported.is_supported("textDocument/hover"));
+        assert!(supported.is_supported("textDocument/prepareRename"));
+        assert!(!supported.is_supported("textDocument/references"));
+        assert!(!supported.is_supported("textDocument/completion"));
+        assert!(supported.is_supported("textDocument/didOpen"));
+        assert!(
+            supported
+                .unsupported_methods()
+                .all(|it| it != "textDocument/hover")
+        );
+    }
+
+    #[test]
+    fn detect_unsupported_sub_capabilities() {
+        let supported = SupportedMethods::new(json!({
+            "completionProvider": { "triggerCharacters": ["."] },
+            "codeLensProvider": { "resolveProvider": true },
+            "renameProvider": true,
+            "semanticTokensProvider": { "full": true, "range": false },
+        }));
+        assert!(supported.is_supported("textDocument/completion"));
+        assert!(!supported.is_supported("completionItem/resolve"));
+        assert!(supported.is_supported("codeLens/resolve"));
+        assert!(supported.is_supported("textDocument/rename"));
+        assert!(!supported.is_supported("textDocument/prepareRename"));
+        assert!(supported.is_supported("textDocument/semanticTokens/full"));
+        assert!(!supported.is_supported("textDocument/semanticTokens/full/delta"));
+        assert!(!supported.is_supported("textDocument/semanticTokens/range"));
+    }
+}
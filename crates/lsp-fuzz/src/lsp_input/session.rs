@@ -1,12 +1,26 @@
-use std::{iter::once, path::Path};
+use std::{collections::HashMap, ops::RangeInclusive, path::Path};
 
+use libafl_bolts::rands::Rand;
 use lsp_fuzz_grammars::Language;
 use lsp_types::{ClientInfo, InitializedParams, TraceValue};
 
-use super::{LspInput, WorkspaceEntry, uri};
+use super::{
+    LspInput, WorkspaceEntry,
+    configuration::Configuration,
+    message_edit,
+    messages::{
+        CapabilityProfile, ColumnUnit, Divergence, DivergentSide, ScheduledOpen, SequenceElement,
+        TransportFault, partial_payload,
+    },
+    uri,
+};
 use crate::{
     file_system::{FileSystemDirectory, FileSystemEntry},
-    lsp::{self, capabilities::fuzzer_client_capabilities},
+    lsp::{
+        self,
+        capabilities::{fuzzer_client_capabilities, minimal_client_capabilities},
+        code_context::CodeContextRef,
+    },
     text_document::{GrammarBasedMutation, TextDocument},
     utf8::Utf8Input,
 };
@@ -17,19 +31,48 @@ pub fn request_bytes(input: &LspInput, workspace_dir: &Path) -> Vec<u8> {
     let workspace_uri = format!("file://{workspace_dir}");
 
     let mut id = 0;
-    message_sequence(input)
-        .flat_map(|msg| {
-            let message = msg.into_json_rpc(&mut id, Some(&workspace_uri));
-            message.to_lsp_payload()
-        })
-        .collect()
+    let mut payload_idx = 0;
+    let mut bytes = Vec::new();
+    for element in session_elements(input) {
+        let (msg, partial) = match element {
+            SequenceElement::Message(msg) => (msg, None),
+            SequenceElement::Close { partial: None } => break,
+            SequenceElement::Close {
+                partial: Some((msg, len)),
+            } => (msg, Some(len)),
+        };
+        let message = msg.into_json_rpc(&mut id, Some(&workspace_uri));
+        let payload = message.to_lsp_payload_with(input.messages.header_line_ending(payload_idx));
+        payload_idx += 1;
+        match partial {
+            Some(len) => bytes.extend_from_slice(partial_payload(&payload, len)),
+            None => bytes.extend_from_slice(&payload),
+        }
+    }
+    bytes
 }
 
 pub fn message_sequence(input: &LspInput) -> impl Iterator<Item = lsp::LspMessage> + use<'_> {
+    session_elements(input).filter_map(SequenceElement::into_message)
+}
+
+pub fn session_elements(
+    input: &LspInput,
+) -> impl Iterator<Item = SequenceElement<lsp::LspMessage>> + use<'_> {
     #[allow(
         deprecated,
         reason = "Some language servers (e.g., rust-analyzer) still rely on `root_uri`."
     )]
+    let position_encoding = input
+        .messages
+        .position_encoding()
+        .negotiated
+        .encoding_kind();
+    let mut capabilities = match input.messages.capability_profile() {
+        CapabilityProfile::Full => fuzzer_client_capabilities(position_encoding),
+        CapabilityProfile::Minimal => minimal_client_capabilities(position_encoding),
+    };
+    capabilities.experimental = input.messages.experimental_capabilities().cloned();
     let init_request = lsp::LspMessage::Initialize(lsp_types::InitializeParams {
         process_id: None,
         client_info: Some(ClientInfo {
@@ -41,46 +84,330 @@ pub fn message_sequence(input: &LspInput) -> impl Iterator<Item = lsp::LspMessag
             uri: LspInput::root_uri(),
             name: "default_workspace".to_owned(),
         }]),
-        capabilities: fuzzer_client_capabilities(),
+        capabilities,
+        initialization_options: input
+            .messages
+            .configuration()
+            .and_then(Configuration::initialization_options),
         trace: Some(TraceValue::Off),
         ..Default::default()
     });
     let initialized_req = lsp::LspMessage::Initialized(InitializedParams {});
 
-    let did_open_notifications = input
+    let shutdown = lsp::LspMessage::Shutdown(());
+    let exit = lsp::LspMessage::Exit(());
+
+    let source_files: Vec<_> = input
         .workspace
         .iter_files()
         .filter_map(|(path, entry)| entry.as_source_file().map(|doc| (path, doc)))
-        .map(|(path, doc)| {
-            let uri = uri::virtual_uri_for_path(&path).expect("Path should contain valid UTF-8");
-            lsp::LspMessage::DidOpenTextDocument(lsp_types::DidOpenTextDocumentParams {
-                text_document: lsp_types::TextDocumentItem {
-                    uri: uri.clone(),
-                    language_id: doc.language().lsp_language_id().to_owned(),
-                    version: 1,
-                    text: doc.to_string_lossy().into_owned(),
-                },
+        .collect();
+    let source_files = source_files.as_slice();
+    let schedule = input.messages.open_schedule();
+    let is_scheduled = |path: &Path| schedule.iter().any(|it| path == Path::new(&it.path));
+    let message_count = input.messages.len();
+    let opens_before = move |idx: usize| {
+        schedule
+            .iter()
+            .filter(move |it| it.before.min(message_count) == idx)
+            .filter_map(move |open| {
+                source_files
+                    .iter()
+                    .find(|(path, _)| path.as_path() == Path::new(&open.path))
             })
-        });
-    let shutdown = lsp::LspMessage::Shutdown(());
-    let exit = lsp::LspMessage::Exit(());
+            .map(|(path, doc)| SequenceElement::Message(did_open_notification(input, path, doc)))
+    };
+
+    let mut versions = HashMap::new();
+    let mut divergences_before = |idx: usize| -> Vec<_> {
+        input
+            .messages
+            .divergences()
+            .iter()
+            .filter(|it| it.before.min(message_count) == idx)
+            .filter_map(|it| {
+                let version = versions.entry(it.path.as_str()).or_insert(1);
+                *version += 1;
+                divergence_notification(it, *version).map(SequenceElement::Message)
+            })
+            .collect()
+    };
+
+    let mut elements = vec![
+        SequenceElement::Message(init_request),
+        SequenceElement::Message(initialized_req),
+    ];
+    elements.extend(
+        input
+            .messages
+            .configuration()
+            .and_then(Configuration::did_change_configuration)
+            .map(SequenceElement::Message),
+    );
+    elements.extend(
+        source_files
+            .iter()
+            .filter(|(path, _)| !is_scheduled(path.as_path()))
+            .map(|(path, doc)| SequenceElement::Message(did_open_notification(input, path, doc))),
+    );
+    elements.extend(opens_before(0));
+    elements.extend(divergences_before(0));
+    let mut message_idx = 0;
+    for element in input.messages.elements() {
+        match element {
+            SequenceElement::Message(message) => {
+                if message_idx > 0 {
+                    elements.extend(opens_before(message_idx));
+                    elements.extend(divergences_before(message_idx));
+                }
+                let message = encode_positions(input, message_idx, message);
+                message_idx += 1;
+                elements.push(SequenceElement::Message(message));
+            }
+            SequenceElement::Close { partial } => {
+                if message_idx > 0 {
+                    elements.extend(opens_before(message_idx));
+                    elements.extend(divergences_before(message_idx));
+                }
+                let partial = partial
+                    .map(|(message, len)| (encode_positions(input, message_idx, message), len));
+                elements.push(SequenceElement::Close { partial });
+                return elements.into_iter();
+            }
+        }
+    }
+    if message_count > 0 {
+        elements.extend(opens_before(message_count));
+        elements.extend(divergences_before(message_count));
+    }
+    elements.push(SequenceElement::Message(shutdown));
+    elements.push(match input.messages.transport_fault() {
+        Some(TransportFault::HalfCloseAfterShutdown) => SequenceElement::Close { partial: None },
+        Some(TransportFault::PartialFinalMessage { bytes }) => SequenceElement::Close {
+            partial: Some((exit, bytes)),
+        },
+        _ => SequenceElement::Message(exit),
+    });
+    elements.into_iter()
+}
+
+/// The indices of the payloads sent for each message of the sequence, followed by those sent for
+/// the `shutdown` request.
+///
+/// The payloads of a message are the opens and divergences placed before it and the message
+/// itself, see [`session_elements`].
+pub fn message_payloads(input: &LspInput) -> Vec<RangeInclusive<usize>> {
+    let source_files: Vec<_> = input
+        .workspace
+        .iter_files()
+        .filter_map(|(path, entry)| entry.as_source_file().map(|_| path))
+        .collect();
+    let schedule = input.messages.open_schedule();
+    let message_count = input.messages.len();
+    let placed_before = |idx: usize| {
+        let opens = schedule
+            .iter()
+            .filter(|it| it.before.min(message_count) == idx)
+            .filter(|it| {
+                source_files
+                    .iter()
+                    .any(|path| path.as_path() == Path::new(&it.path))
+            })
+            .count();
+        let divergences = input
+            .messages
+            .divergences()
+            .iter()
+            .filter(|it| it.before.min(message_count) == idx)
+            .filter(|it| uri::virtual_uri_for_path(Path::new(&it.path)).is_some())
+            .count();
+        opens + divergences
+    };
+    let unscheduled = source_files
+        .iter()
+        .filter(|path| {
+            !schedule
+                .iter()
+                .any(|it| path.as_path() == Path::new(&it.path))
+        })
+        .count();
+    let configuration = input
+        .messages
+        .configuration()
+        .and_then(Configuration::did_change_configuration)
+        .is_some();
+
+    // The `initialize` request and the `initialized` notification come first.
+    let mut next = 2 + usize::from(configuration) + unscheduled;
+    (0..=message_count)
+        .map(|idx| {
+            let first = next;
+            next += placed_before(idx) + 1;
+            first..=next - 1
+        })
+        .collect()
+}
+
+/// The index of the payload of the `index`-th scheduled open, or `None` if the file it opens
+/// is not a source file of the workspace and nothing is sent for it.
+pub fn open_payload(input: &LspInput, index: usize) -> Option<usize> {
+    let schedule = input.messages.open_schedule();
+    let open = schedule.get(index)?;
+    if !is_opened(input, open) {
+        return None;
+    }
+    let message_count = input.messages.len();
+    let slot = open.before.min(message_count);
+    let earlier = schedule[..index]
+        .iter()
+        .filter(|it| it.before.min(message_count) == slot && is_opened(input, it))
+        .count();
+    Some(message_payloads(input)[slot].start() + earlier)
+}
+
+/// The index of the payload of the `index`-th divergence, or `None` if its path has no URI
+/// and nothing is sent for it.
+///
+/// The divergences placed before a message come after the opens placed before it,
+/// see [`session_elements`].
+pub fn divergence_payload(input: &LspInput, index: usize) -> Option<usize> {
+    let divergences = input.messages.divergences();
+    let divergence = divergences.get(index)?;
+    let is_sent =
+        |divergence: &Divergence| uri::virtual_uri_for_path(Path::new(&divergence.path)).is_some();
+    if !is_sent(divergence) {
+        return None;
+    }
+    let message_count = input.messages.len();
+    let slot = divergence.before.min(message_count);
+    let opens = input
+        .messages
+        .open_schedule()
+        .iter()
+        .filter(|it| it.before.min(message_count) == slot && is_opened(input, it))
+        .count();
+    let earlier = divergences[..index]
+        .iter()
+        .filter(|it| it.before.min(message_count) == slot && is_sent(it))
+        .count();
+    Some(message_payloads(input)[slot].start() + opens + earlier)
+}
+
+/// Whether a `textDocument/didOpen` notification is sent for `open`,
+/// i.e., whether it opens a source file of the workspace.
+fn is_opened(input: &LspInput, open: &ScheduledOpen) -> bool {
+    input
+        .workspace
+        .iter_files()
+        .any(|(path, entry)| entry.as_source_file().is_some() && path == Path::new(&open.path))
+}
 
-    once(init_request)
-        .chain(once(initialized_req))
-        .chain(did_open_notifications)
-        .chain(input.messages.iter().cloned())
-        .chain(once(shutdown))
-        .chain(once(exit))
+/// Expresses the columns of the `index`-th message in the unit it is sent in.
+fn encode_positions(input: &LspInput, index: usize, message: &lsp::LspMessage) -> lsp::LspMessage {
+    let mut message = message.clone();
+    if input.messages.column_unit(index) == ColumnUnit::Utf16
+        && let Some(doc) = message
+            .document()
+            .filter(|it| uri::path_from_virtual_uri(&it.uri).is_some())
+            .and_then(|it| input.get_text_document(&it.uri))
+    {
+        message_edit::convert_columns_to_utf16(&mut message, &doc.content());
+    }
+    message
+}
+
+fn did_open_notification(input: &LspInput, path: &Path, doc: &TextDocument) -> lsp::LspMessage {
+    let uri = uri::virtual_uri_for_path(path).expect("Path should contain valid UTF-8");
+    let language_id = input
+        .messages
+        .language_id_override(path)
+        .unwrap_or_else(|| doc.language().lsp_language_id());
+    lsp::LspMessage::DidOpenTextDocument(lsp_types::DidOpenTextDocumentParams {
+        text_document: lsp_types::TextDocumentItem {
+            uri,
+            language_id: language_id.to_owned(),
+            version: 1,
+            text: doc.to_string_lossy().into_owned(),
+        },
+    })
+}
+
+fn divergence_notification(divergence: &Divergence, version: i32) -> Option<lsp::LspMessage> {
+    let uri = uri::virtual_uri_for_path(Path::new(&divergence.path))?;
+    let message = match divergence.side {
+        DivergentSide::InMemory => {
+            lsp::LspMessage::DidChangeTextDocument(lsp_types::DidChangeTextDocumentParams {
+                text_document: lsp_types::VersionedTextDocumentIdentifier { uri, version },
+                content_changes: vec![lsp_types::TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: String::from_utf8_lossy(&divergence.content).into_owned(),
+                }],
+            })
+        }
+        DivergentSide::OnDisk => {
+            lsp::LspMessage::DidChangeWatchedFiles(lsp_types::DidChangeWatchedFilesParams {
+                changes: vec![lsp_types::FileEvent {
+                    uri,
+                    typ: lsp_types::FileChangeType::CHANGED,
+                }],
+            })
+        }
+    };
+    Some(message)
 }
 
-pub fn workspace_for_document(
+/// Overwrites the files that diverge on disk with their divergent content.
+///
+/// The paths are resolved from the current workspace of `input`, so divergences of files
+/// that mutations removed or renamed since are not written.
+pub fn write_divergent_files(input: &LspInput, workspace_root: &Path) -> std::io::Result<()> {
+    let source_files: Vec<_> = input
+        .workspace
+        .iter_files()
+        .filter(|(_, entry)| entry.as_source_file().is_some())
+        .map(|(path, _)| path)
+        .collect();
+    let on_disk = input
+        .messages
+        .divergences()
+        .iter()
+        .filter(|it| it.side == DivergentSide::OnDisk);
+    for divergence in on_disk {
+        if let Some(path) = source_files
+            .iter()
+            .find(|path| path.as_path() == Path::new(&divergence.path))
+        {
+            std::fs::write(workspace_root.join(path), &divergence.content)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn workspace_for_document<R: Rand>(
+    rand: &mut R,
     language: Language,
     doc: TextDocument,
     extension: &str,
 ) -> FileSystemDirectory<WorkspaceEntry> {
     match language {
         Language::Rust => rust_workspace(doc),
-        _ => main_file_workspace(doc, extension),
+        Language::LaTeX => latex_workspace(doc, extension),
+        Language::BibTeX => bibtex_workspace(doc, extension),
+        Language::Solidity => solidity_workspace(rand, doc, extension),
+        Language::Go => go_workspace(doc, extension),
+        Language::Java => java_workspace(rand, doc, extension),
+        Language::Markdown => markdown_workspace(doc, extension),
+        Language::Zig => zig_workspace(doc, extension),
+        _ => {
+            let mut workspace = main_file_workspace(doc, extension);
+            for &(path, content) in language.skeleton_files() {
+                let skeleton =
+                    WorkspaceEntry::Skeleton(content.as_bytes().to_vec()).into_immutable();
+                workspace.insert_path(path, FileSystemEntry::File(skeleton));
+            }
+            workspace
+        }
     }
 }
 
@@ -91,6 +418,331 @@ fn main_file_workspace(doc: TextDocument, extension: &str) -> FileSystemDirector
     )])
 }
 
+// LaTeX servers such as texlab resolve citations, `\input`s, and labels across the files
+// of a project, so LaTeX and BibTeX documents are generated together with the files
+// they reference or are referenced by.
+const BIBLIOGRAPHY: &str = "@article{knuth1984,
+  author = {Donald E. Knuth},
+  title = {Literate Programming},
+  journal = {The Computer Journal},
+  year = {1984},
+}
+
+@book{lamport1994,
+  author = {Leslie Lamport},
+  title = {{\\LaTeX}: A Document Preparation System},
+  publisher = {Addison-Wesley},
+  year = {1994},
+  crossref = {knuth1984},
+}
+";
+
+const LATEX_CROSS_REFERENCES: &str = "
+\\input{chapters/intro}
+See \\cite{knuth1984,lamport1994} and \\ref{sec:intro}.
+\\bibliographystyle{plain}
+\\bibliography{refs}
+";
+
+const LATEX_CHAPTER: &str = "\\section{Introduction}\\label{sec:intro}
+As shown by \\citet{lamport1994}, see also \\cite[p.~1]{knuth1984}.
+";
+
+fn latex_workspace(doc: TextDocument, extension: &str) -> FileSystemDirectory<WorkspaceEntry> {
+    let content = [&doc.content()[..], LATEX_CROSS_REFERENCES.as_bytes()].concat();
+    let main = TextDocument::new(Language::LaTeX, content);
+    let bibliography = TextDocument::new(Language::BibTeX, BIBLIOGRAPHY.as_bytes().to_vec());
+    let chapter = TextDocument::new(Language::LaTeX, LATEX_CHAPTER.as_bytes().to_vec());
+    FileSystemDirectory::from([
+        (
+            Utf8Input::new(format!("main.{extension}")),
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(main)),
+        ),
+        (
+            Utf8Input::new("refs.bib".to_owned()),
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(bibliography)),
+        ),
+        (
+            Utf8Input::new("chapters".to_owned()),
+            FileSystemEntry::Directory(FileSystemDirectory::from([(
+                Utf8Input::new("intro.tex".to_owned()),
+                FileSystemEntry::File(WorkspaceEntry::SourceFile(chapter)),
+            )])),
+        ),
+    ])
+}
+
+fn bibtex_workspace(doc: TextDocument, extension: &str) -> FileSystemDirectory<WorkspaceEntry> {
+    let keys = citation_keys(&doc.content());
+    let cite = if keys.is_empty() {
+        "\\nocite{*}".to_owned()
+    } else {
+        format!("\\cite{{{}}}", keys.join(","))
+    };
+    let main = format!(
+        "\\documentclass{{article}}\n\\begin{{document}}\n{cite}\n\\bibliography{{refs}}\n\\end{{document}}\n"
+    );
+    let main = TextDocument::new(Language::LaTeX, main.into_bytes());
+    FileSystemDirectory::from([
+        (
+            Utf8Input::new(format!("refs.{extension}")),
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(doc)),
+        ),
+        (
+            Utf8Input::new("main.tex".to_owned()),
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(main)),
+        ),
+    ])
+}
+
+/// The keys of the entries of a BibTeX document, i.e., the names between `@type{` and `,`.
+pub(crate) fn citation_keys(content: &[u8]) -> Vec<String> {
+    let content = String::from_utf8_lossy(content);
+    content
+        .split('@')
+        .skip(1)
+        .filter_map(|entry| {
+            let (_, rest) = entry.split_once('{')?;
+            let (key, _) = rest.split_once(',')?;
+            let key = key.trim();
+            let is_key = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|it| !it.is_whitespace() && !matches!(it, '{' | '}' | '"' | '=' | '#'));
+            is_key.then(|| key.to_owned())
+        })
+        .collect()
+}
+
+// Solidity servers resolve imports relative to the project root found from `foundry.toml`
+// or `hardhat.config.js`, so the generated contract is placed in a project with both, either,
+// or none of them, importing and imported by other contracts.
+const SOLIDITY_IMPORTS: &str = "
+import \"./Token.sol\";
+import {IToken} from \"./interfaces/IToken.sol\";
+";
+
+const SOLIDITY_TOKEN: &str = "// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import {IToken} from \"./interfaces/IToken.sol\";
+import \"./Main.sol\";
+
+contract Token is IToken {
+    mapping(address => uint256) public balances;
+
+    function transfer(address to, uint256 amount) external override returns (bool) {
+        balances[msg.sender] -= amount;
+        balances[to] += amount;
+        return true;
+    }
+}
+";
+
+const SOLIDITY_INTERFACE: &str = "// SPDX-License-Identifier: MIT
+pragma solidity >=0.7.0 <0.9.0;
+
+interface IToken {
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+";
+
+const FOUNDRY_TOML: &str = r#"[profile.default]
+src = "src"
+out = "out"
+libs = ["lib"]
+solc_version = "0.8.26"
+remappings = ["@openzeppelin/=lib/openzeppelin-contracts/"]
+"#;
+
+const HARDHAT_CONFIG: &str = r#"module.exports = {
+  solidity: "0.8.26",
+  paths: { sources: "./src" },
+};
+"#;
+
+fn solidity_workspace<R: Rand>(
+    rand: &mut R,
+    doc: TextDocument,
+    extension: &str,
+) -> FileSystemDirectory<WorkspaceEntry> {
+    let content = [&doc.content()[..], SOLIDITY_IMPORTS.as_bytes()].concat();
+    let main = TextDocument::new(Language::Solidity, content);
+    let token = TextDocument::new(Language::Solidity, SOLIDITY_TOKEN.as_bytes().to_vec());
+    let interface = TextDocument::new(Language::Solidity, SOLIDITY_INTERFACE.as_bytes().to_vec());
+    let mut workspace = FileSystemDirectory::from([(
+        Utf8Input::new("src".to_owned()),
+        FileSystemEntry::Directory(FileSystemDirectory::from([
+            (
+                Utf8Input::new(format!("Main.{extension}")),
+                FileSystemEntry::File(WorkspaceEntry::SourceFile(main)),
+            ),
+            (
+                Utf8Input::new("Token.sol".to_owned()),
+                FileSystemEntry::File(WorkspaceEntry::SourceFile(token)),
+            ),
+            (
+                Utf8Input::new("interfaces".to_owned()),
+                FileSystemEntry::Directory(FileSystemDirectory::from([(
+                    Utf8Input::new("IToken.sol".to_owned()),
+                    FileSystemEntry::File(WorkspaceEntry::SourceFile(interface)),
+                )])),
+            ),
+        ])),
+    )]);
+    let skeleton = |content: &str| {
+        FileSystemEntry::File(WorkspaceEntry::Skeleton(content.into()).into_immutable())
+    };
+    match rand.below_or_zero(4) {
+        0 => {
+            workspace.insert_path("foundry.toml", skeleton(FOUNDRY_TOML));
+        }
+        1 => {
+            workspace.insert_path("hardhat.config.js", skeleton(HARDHAT_CONFIG));
+        }
+        2 => {
+            workspace.insert_path("foundry.toml", skeleton(FOUNDRY_TOML));
+            workspace.insert_path("hardhat.config.js", skeleton(HARDHAT_CONFIG));
+        }
+        _ => {}
+    }
+    workspace
+}
+
+// gopls only loads packages that belong to a module, and does not index a lone file
+// outside of one.
+const GO_MOD: &str = "module example.com/fuzz
+
+go 1.21
+";
+
+fn go_workspace(doc: TextDocument, extension: &str) -> FileSystemDirectory<WorkspaceEntry> {
+    FileSystemDirectory::from([
+        (
+            Utf8Input::new("go.mod".to_owned()),
+            FileSystemEntry::File(
+                WorkspaceEntry::Skeleton(GO_MOD.as_bytes().to_vec()).into_immutable(),
+            ),
+        ),
+        (
+            Utf8Input::new(format!("main.{extension}")),
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(doc)),
+        ),
+    ])
+}
+
+// Eclipse JDT-LS only indexes folders it can import as a Maven or Gradle project,
+// so the generated class is placed in the source folder of either build.
+const POM_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0"
+         xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+         xsi:schemaLocation="http://maven.apache.org/POM/4.0.0 http://maven.apache.org/xsd/maven-4.0.0.xsd">
+  <modelVersion>4.0.0</modelVersion>
+  <groupId>com.example</groupId>
+  <artifactId>fuzz</artifactId>
+  <version>1.0</version>
+  <properties>
+    <maven.compiler.release>21</maven.compiler.release>
+  </properties>
+</project>
+"#;
+
+const BUILD_GRADLE: &str = "plugins {
+    id 'java'
+}
+
+java {
+    toolchain {
+        languageVersion = JavaLanguageVersion.of(21)
+    }
+}
+";
+
+const SETTINGS_GRADLE: &str = "rootProject.name = 'fuzz'\n";
+
+fn java_workspace<R: Rand>(
+    rand: &mut R,
+    doc: TextDocument,
+    extension: &str,
+) -> FileSystemDirectory<WorkspaceEntry> {
+    let mut workspace = FileSystemDirectory::default();
+    workspace.insert_path(
+        &format!("src/main/java/Main.{extension}"),
+        FileSystemEntry::File(WorkspaceEntry::SourceFile(doc)),
+    );
+    let skeleton = |content: &str| {
+        FileSystemEntry::File(WorkspaceEntry::Skeleton(content.into()).into_immutable())
+    };
+    if rand.coinflip(0.5) {
+        workspace.insert_path("pom.xml", skeleton(POM_XML));
+    } else {
+        workspace.insert_path("build.gradle", skeleton(BUILD_GRADLE));
+        workspace.insert_path("settings.gradle", skeleton(SETTINGS_GRADLE));
+    }
+    workspace
+}
+
+// Markdown servers such as marksman resolve links and wiki links between the notes of a folder,
+// which `.marksman.toml` marks as the root of a project.
+const MARKDOWN_OTHER_NOTE: &str = "# Other Note
+
+## Section
+
+Back to [main](main.md#heading), [[main]], and [the section](#section).
+
+[ref]: other.md#section \"Reference\"
+";
+
+fn markdown_workspace(doc: TextDocument, extension: &str) -> FileSystemDirectory<WorkspaceEntry> {
+    let other = TextDocument::new(Language::Markdown, MARKDOWN_OTHER_NOTE.as_bytes().to_vec());
+    FileSystemDirectory::from([
+        (
+            Utf8Input::new(".marksman.toml".to_owned()),
+            FileSystemEntry::File(WorkspaceEntry::Skeleton(Vec::new()).into_immutable()),
+        ),
+        (
+            Utf8Input::new(format!("main.{extension}")),
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(doc)),
+        ),
+        (
+            Utf8Input::new("other.md".to_owned()),
+            FileSystemEntry::File(WorkspaceEntry::SourceFile(other)),
+        ),
+    ])
+}
+
+// zls resolves the imports of a file through the build graph of the enclosing `build.zig`,
+// so the generated document is the root module of an executable.
+const BUILD_ZIG: &str = r#"const std = @import("std");
+
+pub fn build(b: *std.Build) void {
+    const exe = b.addExecutable(.{
+        .name = "fuzz",
+        .root_module = b.createModule(.{
+            .root_source_file = b.path("src/main.zig"),
+            .target = b.standardTargetOptions(.{}),
+            .optimize = b.standardOptimizeOption(.{}),
+        }),
+    });
+    b.installArtifact(exe);
+}
+"#;
+
+fn zig_workspace(doc: TextDocument, extension: &str) -> FileSystemDirectory<WorkspaceEntry> {
+    let mut workspace = FileSystemDirectory::default();
+    workspace.insert_path(
+        "build.zig",
+        FileSystemEntry::File(
+            WorkspaceEntry::Skeleton(BUILD_ZIG.as_bytes().to_vec()).into_immutable(),
+        ),
+    );
+    workspace.insert_path(
+        &format!("src/main.{extension}"),
+        FileSystemEntry::File(WorkspaceEntry::SourceFile(doc)),
+    );
+    workspace
+}
+
 // rust-analyzer runs faster when configured with a `rust-project.json` file.
 const RUST_PROJECT_JSON: &str = r#"
 {
@@ -109,9 +761,9 @@ fn rust_workspace(doc: TextDocument) -> FileSystemDirectory<WorkspaceEntry> {
     FileSystemDirectory::from([
         (
             Utf8Input::new("rust-project.json".to_owned()),
-            FileSystemEntry::File(WorkspaceEntry::Skeleton(
-                RUST_PROJECT_JSON.as_bytes().to_vec(),
-            )),
+            FileSystemEntry::File(
+                WorkspaceEntry::Skeleton(RUST_PROJECT_JSON.as_bytes().to_vec()).into_immutable(),
+            ),
         ),
         (
             Utf8Input::new("src".to_owned()),
@@ -1,12 +1,16 @@
-use std::{borrow::Cow, marker::PhantomData, num::NonZero, sync::OnceLock};
+use std::{borrow::Cow, collections::HashMap, sync::OnceLock};
 
 use derive_new::new as New;
+use itertools::Itertools;
 use libafl::{
+    HasMetadata,
     corpus::CorpusId,
-    mutators::{ComposedByMutations, MutationResult, Mutator},
+    mutators::{ComposedByMutations, MutationId, MutationResult, Mutator, MutatorsTuple},
     state::HasRand,
 };
-use libafl_bolts::{Named, rands::Rand};
+use libafl_bolts::{Named, rands::Rand, tuples::NamedTuple};
+
+use crate::{attribution::MutationTrail, utils::RandExt};
 
 #[derive(Debug)]
 pub struct FallbackMutator<First, Second> {
@@ -117,52 +121,6 @@ where
     }
 }
 
-#[derive(Debug, New)]
-pub struct SliceSwapMutator<T, State> {
-    _item: PhantomData<T>,
-    _state: PhantomData<State>,
-}
-
-impl<State, T> Named for SliceSwapMutator<T, State> {
-    fn name(&self) -> &Cow<'static, str> {
-        static NAME: Cow<'static, str> = Cow::Borrowed("SliceSwapMutator");
-        &NAME
-    }
-}
-
-impl<I, T, State> Mutator<I, State> for SliceSwapMutator<T, State>
-where
-    I: AsMut<[T]>,
-    State: HasRand,
-{
-    fn mutate(
-        &mut self,
-        state: &mut State,
-        input: &mut I,
-    ) -> Result<MutationResult, libafl::Error> {
-        let input = input.as_mut();
-        let len = input.len();
-        if len < 2 {
-            return Ok(MutationResult::Skipped);
-        }
-        // Safety: We just checked that len >= 2
-        let len = unsafe { NonZero::new_unchecked(len) };
-        let rand = state.rand_mut();
-        let idx1 = rand.below(len);
-        let idx2 = rand.below(len);
-        input.swap(idx1, idx2);
-        Ok(MutationResult::Mutated)
-    }
-
-    fn post_exec(
-        &mut self,
-        _state: &mut State,
-        _new_corpus_id: Option<CorpusId>,
-    ) -> Result<(), libafl::Error> {
-        Ok(())
-    }
-}
-
 pub trait HasMutProp<const OFFSET: usize> {
     type PropType;
 
@@ -268,3 +226,344 @@ where
         Ok(())
     }
 }
+
+struct RegisteredMutation<'a, I, State> {
+    name: Cow<'static, str>,
+    weight: usize,
+    mutator: Box<dyn Mutator<I, State> + 'a>,
+}
+
+/// Mutations registered by name at runtime, one of which is chosen in proportion to its weight
+/// on each mutation, or several in a row if stacked (see [`Self::with_max_stack_pow`]).
+///
+/// This lets code outside the crate add mutations to the ones built into a campaign
+/// without naming their types (see [`text_document_mutator`]).
+///
+/// [`text_document_mutator`]: crate::text_document::text_document_mutator
+pub struct MutationRegistry<'a, I, State> {
+    entries: Vec<RegisteredMutation<'a, I, State>>,
+    max_stack_pow: Option<usize>,
+    last_chosen: Vec<usize>,
+}
+
+impl<I, State> Default for MutationRegistry<'_, I, State> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            max_stack_pow: None,
+            last_chosen: Vec::new(),
+        }
+    }
+}
+
+impl<I, State> std::fmt::Debug for MutationRegistry<'_, I, State> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|it| (&it.name, it.weight)))
+            .finish()
+    }
+}
+
+impl<'a, I, State> MutationRegistry<'a, I, State> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stacks up to `1 << max_stack_pow` mutations on each mutation, like
+    /// [`WeightedHavocMutator`].
+    #[must_use]
+    pub fn with_max_stack_pow(self, max_stack_pow: usize) -> Self {
+        Self {
+            max_stack_pow: Some(max_stack_pow),
+            ..self
+        }
+    }
+
+    /// Registers `mutator` under `name`, to be chosen in proportion to `weight`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a mutation is already registered under `name`.
+    pub fn register<M>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        weight: usize,
+        mutator: M,
+    ) -> Result<(), libafl::Error>
+    where
+        M: Mutator<I, State> + 'a,
+    {
+        let name = name.into();
+        if self.entries.iter().any(|it| it.name == name) {
+            return Err(libafl::Error::illegal_argument(format!(
+                "Mutation {name} is already registered"
+            )));
+        }
+        self.entries.push(RegisteredMutation {
+            name,
+            weight,
+            mutator: Box::new(mutator),
+        });
+        Ok(())
+    }
+
+    /// Registers each of `mutations` under its own name, weighted like the mutations of a
+    /// [`WeightedHavocMutator`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `weights` names none of `mutations`, or if a mutation is already
+    /// registered under the name of one of them.
+    pub fn register_each<MT>(
+        &mut self,
+        mutations: MT,
+        default_weights: &[(&str, usize)],
+        weights: &HashMap<String, usize>,
+    ) -> Result<(), libafl::Error>
+    where
+        MT: RegisterEach<'a, I, State> + NamedTuple,
+    {
+        let weights = resolve_weights(&mutations.names(), default_weights, weights)?;
+        mutations.register_each(self, &mut weights.into_iter())
+    }
+
+    /// Registers the mutations of `other` in this registry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a mutation of `other` has the name of a mutation of this registry.
+    pub fn merge(&mut self, other: Self) -> Result<(), libafl::Error> {
+        for entry in other.entries {
+            if self.entries.iter().any(|it| it.name == entry.name) {
+                return Err(libafl::Error::illegal_argument(format!(
+                    "Mutation {} is already registered",
+                    entry.name
+                )));
+            }
+            self.entries.push(entry);
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The names and the weights of the registered mutations.
+    pub fn weights(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.entries.iter().map(|it| (it.name.as_ref(), it.weight))
+    }
+}
+
+impl<I, State> Named for MutationRegistry<'_, I, State> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MutationRegistry");
+        &NAME
+    }
+}
+
+impl<I, State> Mutator<I, State> for MutationRegistry<'_, I, State>
+where
+    State: HasRand + HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut I,
+    ) -> Result<MutationResult, libafl::Error> {
+        let iterations = self.max_stack_pow.map_or(1, |max_stack_pow| {
+            1 << (1 + state.rand_mut().below_or_zero(max_stack_pow))
+        });
+        self.last_chosen.clear();
+        let mut result = MutationResult::Skipped;
+        for _ in 0..iterations {
+            let weights = self.entries.iter().map(|it| it.weight).enumerate();
+            let Some(idx) = state.rand_mut().weighted_choose(weights) else {
+                break;
+            };
+            if !self.last_chosen.contains(&idx) {
+                self.last_chosen.push(idx);
+            }
+            let entry = &mut self.entries[idx];
+            if entry.mutator.mutate(state, input)? == MutationResult::Mutated {
+                MutationTrail::record(state, &entry.name);
+                result = MutationResult::Mutated;
+            }
+        }
+        Ok(result)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut State,
+        new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        for idx in self.last_chosen.drain(..) {
+            self.entries[idx].mutator.post_exec(state, new_corpus_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Tuple lists of mutations that can be registered one by one, see
+/// [`MutationRegistry::register_each`].
+pub trait RegisterEach<'a, I, State> {
+    /// Registers the mutations under their names, with the weights taken from `weights`
+    /// in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a mutation is already registered under the name of one of them.
+    fn register_each(
+        self,
+        registry: &mut MutationRegistry<'a, I, State>,
+        weights: &mut impl Iterator<Item = usize>,
+    ) -> Result<(), libafl::Error>;
+}
+
+impl<'a, I, State> RegisterEach<'a, I, State> for () {
+    fn register_each(
+        self,
+        _registry: &mut MutationRegistry<'a, I, State>,
+        _weights: &mut impl Iterator<Item = usize>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<'a, I, State, Head, Tail> RegisterEach<'a, I, State> for (Head, Tail)
+where
+    Head: Mutator<I, State> + Named + 'a,
+    Tail: RegisterEach<'a, I, State>,
+{
+    fn register_each(
+        self,
+        registry: &mut MutationRegistry<'a, I, State>,
+        weights: &mut impl Iterator<Item = usize>,
+    ) -> Result<(), libafl::Error> {
+        let (head, tail) = self;
+        let weight = weights.next().unwrap_or(1);
+        registry.register(head.name().clone(), weight, head)?;
+        tail.register_each(registry, weights)
+    }
+}
+
+/// The weights of the mutations named `names`, by the entry of `weights` named after each of
+/// them, or else by the entry of `default_weights`, or else 1.
+///
+/// # Errors
+///
+/// Returns an error if `weights` names no mutation, which is most likely a typo.
+fn resolve_weights(
+    names: &[&Cow<'static, str>],
+    default_weights: &[(&str, usize)],
+    weights: &HashMap<String, usize>,
+) -> Result<Vec<usize>, libafl::Error> {
+    if let Some(unknown) = weights
+        .keys()
+        .find(|name| !names.iter().any(|it| it == name.as_str()))
+    {
+        let known = names.iter().unique().join(", ");
+        return Err(libafl::Error::illegal_argument(format!(
+            "Unknown mutation {unknown}, expected one of: {known}"
+        )));
+    }
+    Ok(names
+        .iter()
+        .map(|name| {
+            weights.get(name.as_ref()).copied().unwrap_or_else(|| {
+                default_weights
+                    .iter()
+                    .find_map(|&(it, weight)| (it == name.as_ref()).then_some(weight))
+                    .unwrap_or(1)
+            })
+        })
+        .collect())
+}
+
+/// Stacks mutations like libafl's havoc scheduler, but chooses each mutation in proportion
+/// to a weight resolved from its name instead of uniformly.
+#[derive(Debug)]
+pub struct WeightedHavocMutator<MT> {
+    mutations: MT,
+    names: Vec<Cow<'static, str>>,
+    weights: Vec<usize>,
+    max_stack_pow: usize,
+}
+
+impl<MT> WeightedHavocMutator<MT>
+where
+    MT: NamedTuple,
+{
+    /// Creates a mutator stacking up to `1 << max_stack_pow` mutations of `mutations`.
+    ///
+    /// Each mutation is weighted by the entry of `weights` named after it, or else by the entry
+    /// of `default_weights`, or else by 1. A weight of 0 disables the mutation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `weights` names no mutation, which is most likely a typo.
+    pub fn new(
+        mutations: MT,
+        max_stack_pow: usize,
+        default_weights: &[(&str, usize)],
+        weights: &HashMap<String, usize>,
+    ) -> Result<Self, libafl::Error> {
+        let names = mutations.names();
+        let weights = resolve_weights(&names, default_weights, weights)?;
+        let names = names.into_iter().cloned().collect();
+        Ok(Self {
+            mutations,
+            names,
+            weights,
+            max_stack_pow,
+        })
+    }
+}
+
+impl<MT> Named for WeightedHavocMutator<MT> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("WeightedHavocMutator");
+        &NAME
+    }
+}
+
+impl<I, MT, State> Mutator<I, State> for WeightedHavocMutator<MT>
+where
+    MT: MutatorsTuple<I, State>,
+    State: HasRand + HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut I,
+    ) -> Result<MutationResult, libafl::Error> {
+        let mut result = MutationResult::Skipped;
+        let iterations = 1 << (1 + state.rand_mut().below_or_zero(self.max_stack_pow));
+        for _ in 0..iterations {
+            let weights = self.weights.iter().copied().enumerate();
+            let Some(idx) = state.rand_mut().weighted_choose(weights) else {
+                break;
+            };
+            if self
+                .mutations
+                .get_and_mutate(MutationId::from(idx), state, input)?
+                == MutationResult::Mutated
+            {
+                MutationTrail::record(state, &self.names[idx]);
+                result = MutationResult::Mutated;
+            }
+        }
+        Ok(result)
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut State,
+        new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        self.mutations.post_exec_all(state, new_corpus_id)
+    }
+}
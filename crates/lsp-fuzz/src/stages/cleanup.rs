@@ -1,27 +1,42 @@
-use std::{mem, path::PathBuf, thread};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
 
 use derive_new::new as New;
 use libafl::{
     HasNamedMetadata,
+    corpus::Corpus,
     events::{EventFirer, LogSeverity},
     stages::{Restartable, Stage},
-    state::HasExecutions,
+    state::{HasCorpus, HasExecutions},
 };
 use libafl_bolts::SerdeAny;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use crate::lsp_input::LspInput;
+use crate::{execution::workspace_observer::HasWorkspace, lsp_input::LspInput};
 
 #[allow(clippy::unsafe_derive_deserialize)]
 #[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, SerdeAny)]
 #[repr(transparent)]
 pub struct LastCleanupDir(u64);
 
+/// Removes the stale workspace directories of a fuzzer instance.
+///
+/// Workspaces are normally removed after each execution, but they are left behind when
+/// the executor fails in between. Directories found in `cleanup_dir` are removed once they
+/// are older than `max_age`, unless they belong to an input in the corpus.
 #[derive(Debug, New)]
 pub struct CleanupWorkspaceDirs {
     cleanup_dir: String,
+    /// Only the workspace directories of this fuzzer instance are removed.
+    instance_id: String,
     cleanup_threshold: u64,
+    max_age: Duration,
 }
 
 impl<State> Restartable<State> for CleanupWorkspaceDirs
@@ -41,7 +56,7 @@ where
 
 impl<E, M, Z, State> Stage<E, M, State, Z> for CleanupWorkspaceDirs
 where
-    State: HasExecutions + HasNamedMetadata,
+    State: HasExecutions + HasNamedMetadata + HasCorpus<LspInput>,
     M: EventFirer<LspInput, State>,
 {
     fn perform(
@@ -53,20 +68,31 @@ where
     ) -> Result<(), libafl::Error> {
         let executions = *state.executions();
         let LastCleanupDir(last_cleanup) = state.named_metadata_mut(&self.cleanup_dir)?;
-        let cleanup_range = mem::replace(last_cleanup, executions)..executions;
+        *last_cleanup = executions;
         manager.log(
             state,
             LogSeverity::Info,
-            format!(
-                "Cleaning up workspace directories from {} to {}",
-                cleanup_range.start, cleanup_range.end
-            ),
+            format!("Cleaning up workspace directories in {}", self.cleanup_dir),
         )?;
+        let live_workspaces = live_workspace_hashes(state.corpus());
         let workspace_path = PathBuf::from(&self.cleanup_dir);
+        let dir_prefix = LspInput::workspace_dir_prefix(&self.instance_id);
+        let max_age = self.max_age;
         thread::spawn(move || {
-            for exec_num in cleanup_range {
-                let workspace_dir = workspace_path.join(format!("lsp-fuzz-workspace_{exec_num}"));
-                std::fs::remove_dir_all(&workspace_dir).unwrap_or_else(|err| {
+            let stale_dirs = stale_workspace_dirs(&workspace_path, &dir_prefix, max_age)
+                .unwrap_or_else(|err| {
+                    warn!(
+                        dir = %workspace_path.display(),
+                        err = %err,
+                        "Failed to list workspace directories"
+                    );
+                    Vec::new()
+                });
+            for (workspace_dir, workspace_hash) in stale_dirs {
+                if live_workspaces.contains(&workspace_hash) {
+                    continue;
+                }
+                fs::remove_dir_all(&workspace_dir).unwrap_or_else(|err| {
                     warn!(
                         dir = %workspace_dir.display(),
                         err = %err,
@@ -78,3 +104,44 @@ where
         Ok(())
     }
 }
+
+/// The workspace hashes of the corpus entries whose inputs are loaded in memory.
+fn live_workspace_hashes(corpus: &impl Corpus<LspInput>) -> HashSet<u64> {
+    corpus
+        .ids()
+        .filter_map(|id| {
+            let testcase = corpus.get(id).ok()?.borrow();
+            testcase.input().as_ref().map(HasWorkspace::workspace_hash)
+        })
+        .collect()
+}
+
+/// The directories in `workspace_path` named with `dir_prefix` and a workspace hash
+/// that were last modified more than `max_age` ago, along with their hashes.
+fn stale_workspace_dirs(
+    workspace_path: &Path,
+    dir_prefix: &str,
+    max_age: Duration,
+) -> std::io::Result<Vec<(PathBuf, u64)>> {
+    let mut stale_dirs = Vec::new();
+    for entry in fs::read_dir(workspace_path)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(workspace_hash) = file_name
+            .to_str()
+            .and_then(|it| it.strip_prefix(dir_prefix))
+            .and_then(|it| it.parse().ok())
+        else {
+            continue;
+        };
+        // The directory may be removed by its execution in the meantime.
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let age = metadata.modified()?.elapsed().unwrap_or_default();
+        if metadata.is_dir() && age >= max_age {
+            stale_dirs.push((entry.path(), workspace_hash));
+        }
+    }
+    Ok(stale_dirs)
+}
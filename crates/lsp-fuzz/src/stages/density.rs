@@ -0,0 +1,99 @@
+use std::{borrow::Cow, marker::PhantomData};
+
+use libafl::{
+    HasNamedMetadata,
+    events::{Event, EventFirer, EventWithStats},
+    feedbacks::{MapFeedback, MapFeedbackMetadata},
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    observers::MapObserver,
+    stages::{Restartable, Stage},
+    state::HasExecutions,
+};
+use libafl_bolts::{Named, serdeany::SerdeAny};
+use tracing::warn;
+
+/// Reports the fraction of the coverage map hit so far to the monitor as `map_density`,
+/// and warns once when the map is saturated.
+///
+/// Edges of a saturated map collide often, so new coverage goes unnoticed.
+/// A larger map should be configured when the warning appears.
+#[derive(Debug)]
+pub struct MapDensityStage<O, I> {
+    coverage_feedback_name: String,
+    warned: bool,
+    _phantom: PhantomData<(O, I)>,
+}
+
+impl<O, I> MapDensityStage<O, I> {
+    /// The density above which the map is considered saturated.
+    const SATURATION_THRESHOLD: f64 = 0.7;
+
+    pub fn new<C, N, R>(map_feedback: &MapFeedback<C, N, O, R>) -> Self {
+        Self {
+            coverage_feedback_name: map_feedback.name().clone().into_owned(),
+            warned: false,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<O, I, State> Restartable<State> for MapDensityStage<O, I> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<E, EM, State, Z, O, I> Stage<E, EM, State, Z> for MapDensityStage<O, I>
+where
+    State: HasExecutions + HasNamedMetadata,
+    EM: EventFirer<I, State>,
+    O: MapObserver,
+    MapFeedbackMetadata<O::Entry>: SerdeAny,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut State,
+        manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let cov_feedback_meta =
+            state.named_metadata::<MapFeedbackMetadata<O::Entry>>(&self.coverage_feedback_name)?;
+        let covered = cov_feedback_meta.num_covered_map_indexes;
+        let map_size = cov_feedback_meta.history_map.len();
+        if map_size == 0 {
+            return Ok(());
+        }
+
+        #[allow(
+            clippy::cast_precision_loss,
+            reason = "Coverage maps are far smaller than 2^52 entries"
+        )]
+        let density = covered as f64 / map_size as f64;
+        if density > Self::SATURATION_THRESHOLD && !self.warned {
+            self.warned = true;
+            warn!(
+                covered,
+                map_size,
+                "The coverage map is {:.0}% full. Consider configuring a larger map size.",
+                density * 100.0
+            );
+        }
+
+        let event = Event::UpdateUserStats {
+            name: Cow::Borrowed("map_density"),
+            value: UserStats::new(
+                UserStatsValue::Ratio(covered as u64, map_size as u64),
+                AggregatorOps::Avg,
+            ),
+            phantom: PhantomData,
+        };
+        let executions = *state.executions();
+        manager.fire(state, EventWithStats::with_current_time(event, executions))?;
+        Ok(())
+    }
+}
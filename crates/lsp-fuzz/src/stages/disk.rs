@@ -0,0 +1,209 @@
+use std::{
+    fs,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use libafl::{
+    events::{EventFirer, LogSeverity},
+    stages::{Restartable, Stage},
+    state::{HasExecutions, HasStartTime},
+};
+use libafl_bolts::current_time;
+use tracing::{info, warn};
+
+use super::{CampaignEvent, Webhook};
+use crate::lsp_input::LspInput;
+
+/// Disk usage limits of a campaign.
+#[derive(Debug, Clone, Copy)]
+pub struct DiskLimits {
+    /// The free space below which the campaign is throttled, on the file system of the state
+    /// directory or of the workspace root.
+    pub min_free_bytes: u64,
+    /// The total size of the state directory and of the workspaces of this fuzzer instance
+    /// above which the campaign is throttled.
+    pub max_total_bytes: Option<u64>,
+}
+
+/// Throttles the campaign while the disk usage exceeds the [`DiskLimits`].
+///
+/// Filling the file system makes the fuzzer and the target fail in obscure ways,
+/// e.g., with corpus entries that cannot be written. While a limit is exceeded,
+/// the stage sleeps on every iteration, which leaves time to free some space.
+/// A warning is logged and sent to the webhook when a limit is first exceeded.
+///
+/// The workspace root is usually shared with other processes, e.g., the system temporary
+/// directory, so only the workspace directories of this fuzzer instance count towards the
+/// total size.
+#[derive(Debug)]
+pub struct DiskWatchdogStage<I> {
+    state_dir: PathBuf,
+    workspace_root: PathBuf,
+    workspace_prefix: String,
+    limits: DiskLimits,
+    webhook: Option<Webhook>,
+    last_check: Duration,
+    exceeded: bool,
+    _input: PhantomData<I>,
+}
+
+impl<I> DiskWatchdogStage<I> {
+    const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+    const THROTTLE_DELAY: Duration = Duration::from_secs(10);
+
+    #[must_use]
+    pub fn new(
+        state_dir: PathBuf,
+        workspace_root: PathBuf,
+        instance_id: &str,
+        limits: DiskLimits,
+        webhook: Option<Webhook>,
+    ) -> Self {
+        Self {
+            state_dir,
+            workspace_root,
+            workspace_prefix: LspInput::workspace_dir_prefix(instance_id),
+            limits,
+            webhook,
+            last_check: Duration::ZERO,
+            exceeded: false,
+            _input: PhantomData,
+        }
+    }
+
+    /// Describes the first limit exceeded by the campaign, if any.
+    fn exceeded_limit(&self) -> std::io::Result<Option<String>> {
+        for dir in [&self.state_dir, &self.workspace_root] {
+            let free_bytes = free_space(dir)?;
+            if free_bytes < self.limits.min_free_bytes {
+                return Ok(Some(format!(
+                    "only {free_bytes} bytes are free on the file system of {}",
+                    dir.display()
+                )));
+            }
+        }
+        if let Some(max_total_bytes) = self.limits.max_total_bytes {
+            let total_bytes = dir_size(&self.state_dir)?
+                + workspaces_size(&self.workspace_root, &self.workspace_prefix)?;
+            if total_bytes > max_total_bytes {
+                return Ok(Some(format!(
+                    "the campaign directories take {total_bytes} bytes"
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<I, State> Restartable<State> for DiskWatchdogStage<I> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<E, M, Z, I, State> Stage<E, M, State, Z> for DiskWatchdogStage<I>
+where
+    State: HasExecutions + HasStartTime,
+    M: EventFirer<I, State>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut State,
+        manager: &mut M,
+    ) -> Result<(), libafl::Error> {
+        let now = current_time();
+        if !self.exceeded && now.saturating_sub(self.last_check) < Self::CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.last_check = now;
+        let exceeded_limit = match self.exceeded_limit() {
+            Ok(it) => it,
+            Err(err) => {
+                // A transient failure, e.g., a directory removed while it is measured,
+                // must not stop the campaign.
+                warn!(err = %err, "Failed to check the disk usage");
+                return Ok(());
+            }
+        };
+        match exceeded_limit {
+            Some(reason) => {
+                if !self.exceeded {
+                    self.exceeded = true;
+                    manager.log(
+                        state,
+                        LogSeverity::Warn,
+                        format!("Disk limit exceeded, throttling the campaign: {reason}"),
+                    )?;
+                    if let Some(webhook) = self.webhook.as_ref() {
+                        let elapsed_secs = now.checked_sub(*state.start_time()).unwrap_or_default();
+                        webhook.notify(&CampaignEvent::DiskLimitExceeded {
+                            reason,
+                            executions: *state.executions(),
+                            elapsed_secs: elapsed_secs.as_secs(),
+                        });
+                    }
+                }
+                thread::sleep(Self::THROTTLE_DELAY);
+            }
+            None if self.exceeded => {
+                self.exceeded = false;
+                info!("Disk usage is back within limits, resuming the campaign");
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// The space available to unprivileged users on the file system of `path`.
+#[allow(
+    clippy::useless_conversion,
+    reason = "The types of the fields of `statvfs` differ across platforms"
+)]
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    let stat = nix::sys::statvfs::statvfs(path)?;
+    Ok(u64::from(stat.blocks_available()) * u64::from(stat.fragment_size()))
+}
+
+/// The total size of the directories in `workspace_root` named with `workspace_prefix`.
+fn workspaces_size(workspace_root: &Path, workspace_prefix: &str) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(workspace_root)? {
+        let entry = entry?;
+        let is_workspace = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|it| it.starts_with(workspace_prefix));
+        if is_workspace {
+            size += dir_size(&entry.path()).unwrap_or_default();
+        }
+    }
+    Ok(size)
+}
+
+/// The total size of the files in `dir`, without following symbolic links.
+fn dir_size(dir: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        // Entries may be removed while they are listed, e.g., workspaces of finished executions.
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            size += dir_size(&entry.path()).unwrap_or_default();
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
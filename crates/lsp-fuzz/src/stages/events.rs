@@ -0,0 +1,224 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    marker::PhantomData,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use libafl::{
+    HasNamedMetadata,
+    corpus::{Corpus, CorpusId},
+    feedbacks::{MapFeedback, MapFeedbackMetadata},
+    observers::MapObserver,
+    stages::{Restartable, Stage},
+    state::{HasCorpus, HasExecutions, HasSolutions, HasStartTime},
+};
+use libafl_bolts::{Named, current_time, serdeany::SerdeAny};
+use tracing::warn;
+
+use super::CampaignEvent;
+
+/// A file receiving [`CampaignEvent`]s as JSON lines, for CI pipelines parsing the progress of
+/// bounded campaigns.
+///
+/// Clones write to the same file, so events can be emitted both from the fuzz loop
+/// and around it.
+#[derive(Debug, Clone)]
+pub struct EventStream {
+    writer: Arc<Mutex<BufWriter<File>>>,
+    /// The first IDs of the corpus entries and solutions not yet in the file.
+    first_ids: (usize, usize),
+}
+
+impl EventStream {
+    /// Opens the file at `path`, creating it if it does not exist.
+    ///
+    /// The events of a resumed campaign are appended to those of the earlier runs, and the IDs of
+    /// its corpus entries and solutions continue from theirs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or opened for appending.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let first_ids = match File::open(path) {
+            Ok(file) => next_ids(BufReader::new(file))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (0, 0),
+            Err(err) => return Err(err),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+            first_ids,
+        })
+    }
+
+    /// Appends `event` to the stream and flushes it, so that readers see complete lines.
+    ///
+    /// Write failures are logged instead of interrupting the campaign.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while emitting an event.
+    pub fn emit(&self, event: &CampaignEvent) {
+        let mut writer = self.writer.lock().expect("The event stream is poisoned");
+        let result = serde_json::to_writer(&mut *writer, event)
+            .map_err(io::Error::from)
+            .and_then(|()| writer.write_all(b"\n"))
+            .and_then(|()| writer.flush());
+        if let Err(err) = result {
+            warn!("Failed to write event: {err}");
+        }
+    }
+}
+
+/// The IDs following the largest corpus entry and solution IDs in the events of `reader`.
+///
+/// Lines that are not events, e.g., one cut short by a crash, are skipped.
+fn next_ids(reader: impl BufRead) -> io::Result<(usize, usize)> {
+    let mut next_ids = (0, 0);
+    for line in reader.lines() {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(&line?) else {
+            continue;
+        };
+        let id = |key| {
+            event
+                .get(key)
+                .and_then(serde_json::Value::as_u64)
+                .and_then(|it| usize::try_from(it).ok())
+        };
+        if let Some(id) = id("corpus_id") {
+            next_ids.0 = next_ids.0.max(id + 1);
+        }
+        if let Some(id) = id("solution_id") {
+            next_ids.1 = next_ids.1.max(id + 1);
+        }
+    }
+    Ok(next_ids)
+}
+
+/// Emits new corpus entries, new solutions, and periodic stats samples to an [`EventStream`].
+///
+/// Solutions are deduplicated by the objective, so each of them starts a new crash bucket.
+/// The IDs in the events continue from those already in the stream, see [`EventStream::open`].
+/// The stage does nothing if no stream is given.
+#[derive(Debug)]
+pub struct EventStreamStage<O, I> {
+    stream: Option<EventStream>,
+    coverage_feedback_name: String,
+    sample_interval: Duration,
+    last_sample: Duration,
+    next_corpus_id: usize,
+    next_solution_id: usize,
+    first_ids: (usize, usize),
+    _phantom: PhantomData<(O, I)>,
+}
+
+impl<O, I> EventStreamStage<O, I> {
+    pub fn new<C, N, R>(
+        stream: Option<EventStream>,
+        map_feedback: &MapFeedback<C, N, O, R>,
+        sample_interval: Duration,
+    ) -> Self {
+        Self {
+            stream,
+            coverage_feedback_name: map_feedback.name().clone().into_owned(),
+            sample_interval,
+            last_sample: Duration::ZERO,
+            next_corpus_id: 0,
+            next_solution_id: 0,
+            first_ids: stream.as_ref().map_or((0, 0), |it| it.first_ids),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<O, I, State> Restartable<State> for EventStreamStage<O, I> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Emits an event for each entry of `corpus` from `next_id` on.
+fn emit_new_testcases<I>(
+    stream: &EventStream,
+    corpus: &impl Corpus<I>,
+    next_id: &mut usize,
+    to_event: impl Fn(CorpusId, Option<String>) -> CampaignEvent,
+) -> Result<(), libafl::Error> {
+    if corpus.last().is_none_or(|it| it.0 < *next_id) {
+        return Ok(());
+    }
+    for id in corpus.ids().filter(|it| it.0 >= *next_id) {
+        let filename = corpus.get(id)?.borrow().filename().clone();
+        stream.emit(&to_event(id, filename));
+        *next_id = id.0 + 1;
+    }
+    Ok(())
+}
+
+impl<E, EM, State, Z, O, I> Stage<E, EM, State, Z> for EventStreamStage<O, I>
+where
+    State: HasCorpus<I> + HasSolutions<I> + HasExecutions + HasStartTime + HasNamedMetadata,
+    O: MapObserver,
+    MapFeedbackMetadata<O::Entry>: SerdeAny,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut State,
+        _manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let Some(stream) = self.stream.as_ref() else {
+            return Ok(());
+        };
+        let elapsed = current_time()
+            .checked_sub(*state.start_time())
+            .unwrap_or_default();
+        let elapsed_secs = elapsed.as_secs();
+        let executions = *state.executions();
+
+        emit_new_testcases(
+            stream,
+            state.corpus(),
+            &mut self.next_corpus_id,
+            |id, filename| CampaignEvent::NewCorpusEntry {
+                corpus_id: self.first_ids.0 + id.0,
+                filename,
+                executions,
+                elapsed_secs,
+            },
+        )?;
+        emit_new_testcases(
+            stream,
+            state.solutions(),
+            &mut self.next_solution_id,
+            |id, filename| CampaignEvent::NewSolution {
+                solution_id: self.first_ids.1 + id.0,
+                filename,
+                executions,
+                elapsed_secs,
+            },
+        )?;
+
+        if elapsed.saturating_sub(self.last_sample) >= self.sample_interval {
+            self.last_sample = elapsed;
+            let cov_feedback_meta = state
+                .named_metadata::<MapFeedbackMetadata<O::Entry>>(&self.coverage_feedback_name)?;
+            stream.emit(&CampaignEvent::Stats {
+                corpus: state.corpus().count(),
+                solutions: state.solutions().count(),
+                executions,
+                edges: cov_feedback_meta.num_covered_map_indexes,
+                elapsed_secs,
+            });
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,23 @@
 mod cleanup;
+mod density;
+mod disk;
+mod events;
+mod notifications;
+mod regeneration;
+mod sqlite;
+mod stagnation;
 mod stats;
 mod stop;
+mod webhook;
 
 pub use cleanup::CleanupWorkspaceDirs;
+pub use density::MapDensityStage;
+pub use disk::{DiskLimits, DiskWatchdogStage};
+pub use events::{EventStream, EventStreamStage};
+pub use notifications::NotificationStatsStage;
+pub use regeneration::{LastRegeneration, RegenerationStage};
+pub use sqlite::{CampaignDatabase, CampaignRecorderStage};
+pub use stagnation::{CoverageStagnation, StagnationFallbackStage};
 pub use stats::StatsStage;
-pub use stop::{StopOnReceived, TimeoutStopStage};
+pub use stop::{ExecutionsStopStage, StopOnReceived, TimeoutStopStage};
+pub use webhook::{CampaignEvent, CampaignPhase, Webhook, WebhookStage};
@@ -0,0 +1,90 @@
+use std::{
+    borrow::Cow,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+use libafl::{
+    HasMetadata,
+    events::{Event, EventFirer, EventWithStats},
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    stages::{Restartable, Stage},
+    state::HasExecutions,
+};
+
+use crate::lsp_input::server_response::notifications::NotificationCounts;
+
+/// Reports the most frequent notifications of the server since the last report to the monitor
+/// as `notifications`, at most once per `interval`.
+///
+/// The counts are collected by
+/// [`NotificationLogFeedback`](crate::lsp_input::server_response::notifications::NotificationLogFeedback).
+#[derive(Debug)]
+pub struct NotificationStatsStage<I> {
+    interval: Duration,
+    last_report: Instant,
+    _input: PhantomData<I>,
+}
+
+impl<I> NotificationStatsStage<I> {
+    /// The number of methods reported.
+    const TOP_METHODS: usize = 5;
+
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_report: Instant::now(),
+            _input: PhantomData,
+        }
+    }
+}
+
+impl<I, State> Restartable<State> for NotificationStatsStage<I> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<E, EM, State, Z, I> Stage<E, EM, State, Z> for NotificationStatsStage<I>
+where
+    State: HasExecutions + HasMetadata,
+    EM: EventFirer<I, State>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut State,
+        manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        if self.last_report.elapsed() < self.interval {
+            return Ok(());
+        }
+        self.last_report = Instant::now();
+        let counts = state.metadata_or_insert_with(NotificationCounts::default);
+        let summary = counts
+            .top(Self::TOP_METHODS)
+            .into_iter()
+            .map(|(method, count)| format!("{method}={count}"))
+            .join(" ");
+        counts.methods.clear();
+        if summary.is_empty() {
+            return Ok(());
+        }
+
+        let event = Event::UpdateUserStats {
+            name: Cow::Borrowed("notifications"),
+            value: UserStats::new(UserStatsValue::String(summary.into()), AggregatorOps::None),
+            phantom: PhantomData,
+        };
+        let executions = *state.executions();
+        manager.fire(state, EventWithStats::with_current_time(event, executions))?;
+        Ok(())
+    }
+}
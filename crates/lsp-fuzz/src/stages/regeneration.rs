@@ -0,0 +1,124 @@
+use derive_new::new as New;
+use libafl::{
+    Evaluator, HasMetadata,
+    events::{EventFirer, LogSeverity},
+    generators::Generator,
+    stages::{Restartable, Stage},
+    state::HasExecutions,
+};
+use libafl_bolts::SerdeAny;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::lsp_input::LspInput;
+
+/// The number of executions when inputs were last regenerated.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, SerdeAny)]
+#[repr(transparent)]
+pub struct LastRegeneration(u64);
+
+impl LastRegeneration {
+    /// Whether `interval` executions passed since the last regeneration,
+    /// in which case `executions` becomes the last one.
+    const fn advance(&mut self, executions: u64, interval: u64) -> bool {
+        if executions.saturating_sub(self.0) < interval {
+            return false;
+        }
+        self.0 = executions;
+        true
+    }
+}
+
+/// Evaluates freshly generated inputs every `interval` executions.
+///
+/// After the initial corpus, inputs are only derived from corpus entries by mutations,
+/// which keeps the campaign around the constructs of its seeds. Inputs generated later on are
+/// added to the corpus when they are interesting, like any other input, and are generated with
+/// the derivation rule weights learned so far when the generator schedules rules by coverage.
+#[derive(Debug, New)]
+pub struct RegenerationStage<G> {
+    generator: G,
+    interval: u64,
+    batch_size: usize,
+}
+
+impl<G, State> Restartable<State> for RegenerationStage<G> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<E, M, Z, G, State> Stage<E, M, State, Z> for RegenerationStage<G>
+where
+    G: Generator<LspInput, State>,
+    Z: Evaluator<E, M, LspInput, State>,
+    State: HasExecutions + HasMetadata,
+    M: EventFirer<LspInput, State>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut State,
+        manager: &mut M,
+    ) -> Result<(), libafl::Error> {
+        let executions = *state.executions();
+        if !state
+            .metadata_or_insert_with(|| LastRegeneration(executions))
+            .advance(executions, self.interval)
+        {
+            return Ok(());
+        }
+
+        let mut generated = 0;
+        let mut added = 0;
+        for _ in 0..self.batch_size {
+            // The failures are counted in the generation statistics.
+            let input = match self.generator.generate(state) {
+                Ok(input) => input,
+                Err(err) => {
+                    debug!(%err, "Failed to regenerate an input");
+                    continue;
+                }
+            };
+            generated += 1;
+            let (_, corpus_id) = fuzzer.evaluate_input(state, executor, manager, &input)?;
+            if corpus_id.is_some() {
+                added += 1;
+            }
+        }
+        manager.log(
+            state,
+            LogSeverity::Info,
+            format!("Regenerated {generated} inputs, {added} added to the corpus"),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LastRegeneration;
+
+    #[test]
+    fn regenerate_every_interval() {
+        let mut last = LastRegeneration(100);
+        assert!(!last.advance(150, 100));
+        assert!(last.advance(200, 100));
+        assert_eq!(last.0, 200);
+        assert!(!last.advance(299, 100));
+        assert!(last.advance(350, 100));
+    }
+
+    #[test]
+    fn tolerate_execution_count_below_last_regeneration() {
+        let mut last = LastRegeneration(1_000);
+        assert!(!last.advance(10, 100));
+        assert_eq!(last.0, 1_000);
+    }
+}
@@ -0,0 +1,249 @@
+use std::{marker::PhantomData, path::Path, time::Duration};
+
+use libafl::{
+    HasNamedMetadata,
+    corpus::{Corpus, CorpusId, Testcase},
+    feedbacks::{MapFeedback, MapFeedbackMetadata},
+    observers::MapObserver,
+    stages::{Restartable, Stage},
+    state::{HasCorpus, HasExecutions, HasSolutions, HasStartTime},
+};
+use libafl_bolts::{Named, current_time, serdeany::SerdeAny};
+use rusqlite::{Connection, params};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS testcases (
+    kind TEXT NOT NULL,
+    corpus_id INTEGER NOT NULL,
+    recorded_at INTEGER NOT NULL,
+    elapsed INTEGER NOT NULL,
+    filename TEXT,
+    executions INTEGER NOT NULL,
+    exec_time_us INTEGER,
+    metadata TEXT,
+    instance TEXT
+);
+CREATE TABLE IF NOT EXISTS stats (
+    recorded_at INTEGER NOT NULL,
+    elapsed INTEGER NOT NULL,
+    corpus INTEGER NOT NULL,
+    solutions INTEGER NOT NULL,
+    executions INTEGER NOT NULL,
+    edges INTEGER NOT NULL,
+    instance TEXT
+);
+";
+
+/// A SQLite database recording the progress of a fuzzing campaign.
+///
+/// Timestamps are stored as seconds since the Unix epoch (`recorded_at`)
+/// and seconds since the start of the campaign (`elapsed`).
+/// Each row records the `instance` of the fuzzer writing it, as several instances may share the
+/// same database.
+#[derive(Debug)]
+pub struct CampaignDatabase {
+    connection: Connection,
+    instance: String,
+}
+
+impl CampaignDatabase {
+    /// Opens the database at `path` for the fuzzer instance `instance` and creates the tables if
+    /// they do not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or initialized.
+    pub fn open(path: &Path, instance: String) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        // Several fuzzer instances may share the same database.
+        connection.busy_timeout(Duration::from_secs(10))?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+        // Databases created before instances were recorded lack the column.
+        for table in ["testcases", "stats"] {
+            let has_instance: bool = connection.query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = 'instance'",
+                [table],
+                |row| row.get(0),
+            )?;
+            if !has_instance {
+                connection
+                    .execute_batch(&format!("ALTER TABLE {table} ADD COLUMN instance TEXT"))?;
+            }
+        }
+        Ok(Self {
+            connection,
+            instance,
+        })
+    }
+
+    fn record_testcase<I>(
+        &self,
+        kind: &str,
+        id: CorpusId,
+        elapsed: u64,
+        testcase: &Testcase<I>,
+    ) -> rusqlite::Result<()> {
+        let metadata = serde_json::to_string(testcase.metadata_map()).ok();
+        let exec_time = testcase
+            .exec_time()
+            .as_ref()
+            .map(|it| u64::try_from(it.as_micros()).unwrap_or(u64::MAX));
+        self.connection.execute(
+            "INSERT INTO testcases (kind, corpus_id, recorded_at, elapsed, filename, executions, \
+             exec_time_us, metadata, instance) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                kind,
+                id.0,
+                current_time().as_secs(),
+                elapsed,
+                testcase.filename(),
+                testcase.executions(),
+                exec_time,
+                metadata,
+                self.instance
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn record_stats(
+        &self,
+        elapsed: u64,
+        corpus: usize,
+        solutions: usize,
+        executions: u64,
+        edges: usize,
+    ) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT INTO stats (recorded_at, elapsed, corpus, solutions, executions, edges, \
+             instance) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                current_time().as_secs(),
+                elapsed,
+                corpus,
+                solutions,
+                executions,
+                edges,
+                self.instance
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// Records new corpus entries, new solutions, and periodic stats samples to a [`CampaignDatabase`].
+///
+/// The stage does nothing if no database is given.
+#[derive(Debug)]
+pub struct CampaignRecorderStage<O, I> {
+    database: Option<CampaignDatabase>,
+    coverage_feedback_name: String,
+    sample_interval: Duration,
+    last_sample: Duration,
+    next_corpus_id: usize,
+    next_solution_id: usize,
+    _phantom: PhantomData<(O, I)>,
+}
+
+impl<O, I> CampaignRecorderStage<O, I> {
+    pub fn new<C, N, R>(
+        database: Option<CampaignDatabase>,
+        map_feedback: &MapFeedback<C, N, O, R>,
+        sample_interval: Duration,
+    ) -> Self {
+        Self {
+            database,
+            coverage_feedback_name: map_feedback.name().clone().into_owned(),
+            sample_interval,
+            last_sample: Duration::ZERO,
+            next_corpus_id: 0,
+            next_solution_id: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<O, I, State> Restartable<State> for CampaignRecorderStage<O, I> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+fn record_new_testcases<I>(
+    database: &CampaignDatabase,
+    kind: &str,
+    corpus: &impl Corpus<I>,
+    next_id: &mut usize,
+    elapsed: u64,
+) -> Result<(), libafl::Error> {
+    if corpus.last().is_none_or(|it| it.0 < *next_id) {
+        return Ok(());
+    }
+    for id in corpus.ids().filter(|it| it.0 >= *next_id) {
+        let testcase = corpus.get(id)?.borrow();
+        database
+            .record_testcase(kind, id, elapsed, &testcase)
+            .map_err(|err| libafl::Error::unknown(format!("Recording {kind}: {err}")))?;
+        *next_id = id.0 + 1;
+    }
+    Ok(())
+}
+
+impl<E, EM, State, Z, O, I> Stage<E, EM, State, Z> for CampaignRecorderStage<O, I>
+where
+    State: HasCorpus<I> + HasSolutions<I> + HasExecutions + HasStartTime + HasNamedMetadata,
+    O: MapObserver,
+    MapFeedbackMetadata<O::Entry>: SerdeAny,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut State,
+        _manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let Some(database) = self.database.as_ref() else {
+            return Ok(());
+        };
+        let elapsed = current_time()
+            .checked_sub(*state.start_time())
+            .unwrap_or_default();
+        let elapsed_secs = elapsed.as_secs();
+
+        record_new_testcases(
+            database,
+            "corpus",
+            state.corpus(),
+            &mut self.next_corpus_id,
+            elapsed_secs,
+        )?;
+        record_new_testcases(
+            database,
+            "solution",
+            state.solutions(),
+            &mut self.next_solution_id,
+            elapsed_secs,
+        )?;
+
+        if elapsed.saturating_sub(self.last_sample) >= self.sample_interval {
+            self.last_sample = elapsed;
+            let cov_feedback_meta = state
+                .named_metadata::<MapFeedbackMetadata<O::Entry>>(&self.coverage_feedback_name)?;
+            database
+                .record_stats(
+                    elapsed_secs,
+                    state.corpus().count(),
+                    state.solutions().count(),
+                    *state.executions(),
+                    cov_feedback_meta.num_covered_map_indexes,
+                )
+                .map_err(|err| libafl::Error::unknown(format!("Recording stats: {err}")))?;
+        }
+        Ok(())
+    }
+}
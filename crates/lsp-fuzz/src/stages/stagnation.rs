@@ -0,0 +1,163 @@
+use std::{
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+use libafl::{
+    HasNamedMetadata,
+    feedbacks::{MapFeedback, MapFeedbackMetadata},
+    observers::MapObserver,
+    stages::{Restartable, Stage},
+};
+use libafl_bolts::{Named, serdeany::SerdeAny};
+use tracing::{info, warn};
+
+/// Tracks the growth of the coverage map to tell when a campaign is stagnating.
+///
+/// The campaign is stagnating when the coverage map has not grown for `stagnation_threshold`.
+/// It then stays in the fallback regime for `fallback_duration`,
+/// after which the stagnation timer starts over.
+#[derive(Debug)]
+pub struct CoverageStagnation<O> {
+    coverage_feedback_name: String,
+    stagnation_threshold: Duration,
+    fallback_duration: Duration,
+    covered: usize,
+    last_progress: Instant,
+    fallback_since: Option<(Instant, usize)>,
+    _phantom: PhantomData<O>,
+}
+
+impl<O> CoverageStagnation<O> {
+    pub fn new<C, N, R>(
+        map_feedback: &MapFeedback<C, N, O, R>,
+        stagnation_threshold: Duration,
+        fallback_duration: Duration,
+    ) -> Self {
+        Self {
+            coverage_feedback_name: map_feedback.name().clone().into_owned(),
+            stagnation_threshold,
+            fallback_duration,
+            covered: 0,
+            last_progress: Instant::now(),
+            fallback_since: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Whether the campaign is in the fallback regime.
+    #[must_use]
+    pub const fn in_fallback(&self) -> bool {
+        self.fallback_since.is_some()
+    }
+
+    /// Enters or leaves the fallback regime depending on the coverage progress,
+    /// and logs the switches with the coverage at the time.
+    fn update<State>(&mut self, state: &State) -> Result<(), libafl::Error>
+    where
+        State: HasNamedMetadata,
+        O: MapObserver,
+        MapFeedbackMetadata<O::Entry>: SerdeAny,
+    {
+        let covered = state
+            .named_metadata::<MapFeedbackMetadata<O::Entry>>(&self.coverage_feedback_name)?
+            .num_covered_map_indexes;
+        if covered > self.covered {
+            self.covered = covered;
+            self.last_progress = Instant::now();
+        }
+
+        match self.fallback_since {
+            Some((since, covered_before)) if since.elapsed() >= self.fallback_duration => {
+                info!(
+                    covered,
+                    new_edges = covered - covered_before,
+                    "Leaving the stagnation fallback after {}s",
+                    since.elapsed().as_secs()
+                );
+                self.fallback_since = None;
+                self.last_progress = Instant::now();
+            }
+            None if self.last_progress.elapsed() >= self.stagnation_threshold => {
+                warn!(
+                    covered,
+                    "No new coverage for {}s, switching to the stagnation fallback for {}s",
+                    self.last_progress.elapsed().as_secs(),
+                    self.fallback_duration.as_secs()
+                );
+                self.fallback_since = Some((Instant::now(), covered));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Runs a regular stage, or a fallback stage while the campaign is stagnating.
+///
+/// The fallback stage is meant to run aggressive mutations that the regular one avoids
+/// because they mostly produce garbage, e.g., [`crate::lsp_input::stagnation_mutations`].
+#[derive(Debug)]
+pub struct StagnationFallbackStage<S, F, O> {
+    regular: S,
+    fallback: F,
+    stagnation: CoverageStagnation<O>,
+}
+
+impl<S, F, O> StagnationFallbackStage<S, F, O> {
+    pub const fn new(regular: S, fallback: F, stagnation: CoverageStagnation<O>) -> Self {
+        Self {
+            regular,
+            fallback,
+            stagnation,
+        }
+    }
+}
+
+impl<S, F, O, State> Restartable<State> for StagnationFallbackStage<S, F, O>
+where
+    S: Restartable<State>,
+    F: Restartable<State>,
+    State: HasNamedMetadata,
+    O: MapObserver,
+    MapFeedbackMetadata<O::Entry>: SerdeAny,
+{
+    fn should_restart(&mut self, state: &mut State) -> Result<bool, libafl::Error> {
+        // The regime only changes here, so that the stage whose progress is cleared
+        // is the one that was performed.
+        self.stagnation.update(state)?;
+        if self.stagnation.in_fallback() {
+            self.fallback.should_restart(state)
+        } else {
+            self.regular.should_restart(state)
+        }
+    }
+
+    fn clear_progress(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        if self.stagnation.in_fallback() {
+            self.fallback.clear_progress(state)
+        } else {
+            self.regular.clear_progress(state)
+        }
+    }
+}
+
+impl<E, EM, State, Z, S, F, O> Stage<E, EM, State, Z> for StagnationFallbackStage<S, F, O>
+where
+    S: Stage<E, EM, State, Z>,
+    F: Stage<E, EM, State, Z>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut State,
+        manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        if self.stagnation.in_fallback() {
+            self.fallback.perform(fuzzer, executor, state, manager)
+        } else {
+            self.regular.perform(fuzzer, executor, state, manager)
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::{
 };
 
 use libafl::{
-    HasNamedMetadata,
+    HasMetadata, HasNamedMetadata,
     corpus::Corpus,
     feedbacks::{MapFeedback, MapFeedbackMetadata},
     observers::MapObserver,
@@ -13,6 +13,11 @@ use libafl::{
 };
 use libafl_bolts::{Named, current_time, serdeany::SerdeAny};
 
+use crate::text_document::grammar_coverage::GrammarCoverage;
+
+/// Appends a line of comma-separated statistics to the stats writer each time it is performed:
+/// the sizes of the corpus and the solutions, the seconds since the start, the executions,
+/// the edges found, and the derivation rules exercised by the corpus (see [`GrammarCoverage`]).
 #[derive(Debug)]
 pub struct StatsStage<W, O, I> {
     stats_writer: W,
@@ -33,7 +38,12 @@ impl<W, O, I, State> Restartable<State> for StatsStage<W, O, I> {
 impl<E, EM, State, Z, W, I, O> Stage<E, EM, State, Z> for StatsStage<W, O, I>
 where
     W: Write,
-    State: HasCorpus<I> + HasSolutions<I> + HasExecutions + HasStartTime + HasNamedMetadata,
+    State: HasCorpus<I>
+        + HasSolutions<I>
+        + HasExecutions
+        + HasStartTime
+        + HasMetadata
+        + HasNamedMetadata,
     O: MapObserver,
     MapFeedbackMetadata<O::Entry>: SerdeAny,
 {
@@ -55,9 +65,19 @@ where
         let cov_feedback_meta =
             state.named_metadata::<MapFeedbackMetadata<O::Entry>>(&self.coverage_feedback_name)?;
         let edges_found = cov_feedback_meta.num_covered_map_indexes;
+        let rules_covered = state
+            .metadata::<GrammarCoverage>()
+            .map_or(0, GrammarCoverage::total_covered);
 
-        self.write_stat(corpus_count, solutions_count, time, exec, edges_found)
-            .map_err(|err| libafl::Error::unknown(format!("Writing stat: {err}")))?;
+        self.write_stat(
+            corpus_count,
+            solutions_count,
+            time,
+            exec,
+            edges_found,
+            rules_covered,
+        )
+        .map_err(|err| libafl::Error::unknown(format!("Writing stat: {err}")))?;
         Ok(())
     }
 }
@@ -78,13 +98,14 @@ impl<W, O, I> StatsStage<W, O, I> {
         time: u64,
         exec: u64,
         edges_found: usize,
+        rules_covered: usize,
     ) -> io::Result<()>
     where
         W: Write,
     {
         writeln!(
             self.stats_writer,
-            "{corpus_count},{solutions_count},{time},{exec},{edges_found}"
+            "{corpus_count},{solutions_count},{time},{exec},{edges_found},{rules_covered}"
         )?;
         self.stats_writer.flush()?;
         Ok(())
@@ -82,3 +82,41 @@ where
         Ok(())
     }
 }
+
+/// Stops the campaign once the number of executions reaches a budget.
+#[derive(Debug, New)]
+pub struct ExecutionsStopStage<I> {
+    max_executions: u64,
+    _input: PhantomData<I>,
+}
+
+impl<I, State> Restartable<State> for ExecutionsStopStage<I> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<E, M, Z, I, State> Stage<E, M, State, Z> for ExecutionsStopStage<I>
+where
+    State: HasExecutions,
+    M: EventFirer<I, State>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut State,
+        manager: &mut M,
+    ) -> Result<(), libafl::Error> {
+        let executions = *state.executions();
+        if executions >= self.max_executions {
+            let event = EventWithStats::with_current_time(Event::Stop, executions);
+            manager.fire(state, event)?;
+        }
+        Ok(())
+    }
+}
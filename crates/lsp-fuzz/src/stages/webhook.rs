@@ -0,0 +1,250 @@
+use std::{
+    marker::PhantomData,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use libafl::{
+    corpus::Corpus,
+    stages::{Restartable, Stage},
+    state::{HasExecutions, HasSolutions, HasStartTime},
+};
+use libafl_bolts::current_time;
+use serde::Serialize;
+use tracing::warn;
+
+/// An event of a fuzzing campaign, reported to a [`Webhook`] or an
+/// [`EventStream`](super::EventStream).
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CampaignEvent {
+    /// The campaign entered a new phase.
+    PhaseStarted {
+        phase: CampaignPhase,
+        elapsed_secs: u64,
+    },
+    /// An input was added to the corpus.
+    NewCorpusEntry {
+        corpus_id: usize,
+        filename: Option<String>,
+        executions: u64,
+        elapsed_secs: u64,
+    },
+    /// A solution was added, i.e., a crash that is not a duplicate of an earlier one.
+    NewSolution {
+        solution_id: usize,
+        filename: Option<String>,
+        executions: u64,
+        elapsed_secs: u64,
+    },
+    /// A periodic sample of the progress of the campaign.
+    Stats {
+        corpus: usize,
+        solutions: usize,
+        executions: u64,
+        edges: usize,
+        elapsed_secs: u64,
+    },
+    /// The disk usage exceeded a limit, and the campaign is throttled until it recovers.
+    DiskLimitExceeded {
+        reason: String,
+        executions: u64,
+        elapsed_secs: u64,
+    },
+    /// The campaign stopped.
+    CampaignStopped {
+        reason: String,
+        solutions: usize,
+        executions: u64,
+        elapsed_secs: u64,
+    },
+}
+
+/// The phases of a fuzzing campaign.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignPhase {
+    SeedGeneration,
+    Fuzzing,
+}
+
+impl CampaignEvent {
+    fn summary(&self, campaign: &str) -> String {
+        match self {
+            Self::PhaseStarted { phase, .. } => format!("[{campaign}] Entered phase {phase:?}"),
+            Self::NewCorpusEntry { corpus_id, .. } => {
+                format!("[{campaign}] New corpus entry #{corpus_id}")
+            }
+            Self::NewSolution {
+                solution_id,
+                filename,
+                ..
+            } => {
+                let filename = filename.as_deref().unwrap_or("<unnamed>");
+                format!("[{campaign}] New solution #{solution_id}: {filename}")
+            }
+            Self::Stats {
+                corpus, solutions, ..
+            } => format!("[{campaign}] {corpus} corpus entries, {solutions} solution(s)"),
+            Self::DiskLimitExceeded { reason, .. } => {
+                format!("[{campaign}] Disk limit exceeded, throttling the campaign: {reason}")
+            }
+            Self::CampaignStopped {
+                reason, solutions, ..
+            } => format!("[{campaign}] Campaign stopped ({reason}) with {solutions} solution(s)"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    /// A human-readable message, shown by chat services such as Slack.
+    text: String,
+    campaign: &'a str,
+    #[serde(flatten)]
+    event: &'a CampaignEvent,
+}
+
+/// An HTTP endpoint receiving [`CampaignEvent`]s as JSON.
+///
+/// The payload carries a `text` field, so it can be sent to Slack incoming webhooks as is.
+/// The notifications are posted in order from a background thread, so a slow endpoint does not
+/// stall the fuzz loop.
+#[derive(Debug)]
+pub struct Webhook {
+    campaign: String,
+    sender: Option<mpsc::Sender<serde_json::Value>>,
+    delivery: Option<JoinHandle<()>>,
+}
+
+impl Webhook {
+    /// How long the delivery of a notification may take before it is given up.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[must_use]
+    pub fn new(url: String, campaign: String) -> Self {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Self::TIMEOUT))
+            .build()
+            .into();
+        let (sender, receiver) = mpsc::channel::<serde_json::Value>();
+        let delivery = thread::spawn(move || {
+            for payload in receiver {
+                if let Err(err) = agent.post(&url).send_json(&payload) {
+                    warn!(url = %url, "Failed to deliver webhook notification: {err}");
+                }
+            }
+        });
+        Self {
+            campaign,
+            sender: Some(sender),
+            delivery: Some(delivery),
+        }
+    }
+
+    /// Queues `event` to be posted to the webhook.
+    ///
+    /// Delivery failures are logged instead of interrupting the campaign.
+    pub fn notify(&self, event: &CampaignEvent) {
+        let payload = WebhookPayload {
+            text: event.summary(&self.campaign),
+            campaign: &self.campaign,
+            event,
+        };
+        let queued = serde_json::to_value(&payload)
+            .map_err(|err| err.to_string())
+            .and_then(|payload| {
+                self.sender
+                    .as_ref()
+                    .expect("The sender is only taken on drop")
+                    .send(payload)
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(err) = queued {
+            warn!("Failed to queue webhook notification: {err}");
+        }
+    }
+}
+
+impl Drop for Webhook {
+    /// Waits for the queued notifications, e.g., that the campaign stopped, to be delivered.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(delivery) = self.delivery.take()
+            && delivery.join().is_err()
+        {
+            warn!("The webhook delivery thread panicked");
+        }
+    }
+}
+
+/// Notifies a [`Webhook`] of every new solution.
+///
+/// The stage does nothing if no webhook is given.
+#[derive(Debug)]
+pub struct WebhookStage<I> {
+    webhook: Option<Webhook>,
+    next_solution_id: usize,
+    _input: PhantomData<I>,
+}
+
+impl<I> WebhookStage<I> {
+    #[must_use]
+    pub const fn new(webhook: Option<Webhook>) -> Self {
+        Self {
+            webhook,
+            next_solution_id: 0,
+            _input: PhantomData,
+        }
+    }
+}
+
+impl<I, State> Restartable<State> for WebhookStage<I> {
+    fn should_restart(&mut self, _state: &mut State) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut State) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<E, EM, State, Z, I> Stage<E, EM, State, Z> for WebhookStage<I>
+where
+    State: HasSolutions<I> + HasExecutions + HasStartTime,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut State,
+        _manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let Some(webhook) = self.webhook.as_ref() else {
+            return Ok(());
+        };
+        let solutions = state.solutions();
+        if solutions
+            .last()
+            .is_none_or(|it| it.0 < self.next_solution_id)
+        {
+            return Ok(());
+        }
+        let elapsed_secs = current_time()
+            .checked_sub(*state.start_time())
+            .unwrap_or_default()
+            .as_secs();
+        for id in solutions.ids().filter(|it| it.0 >= self.next_solution_id) {
+            let filename = solutions.get(id)?.borrow().filename().clone();
+            webhook.notify(&CampaignEvent::NewSolution {
+                solution_id: id.0,
+                filename,
+                executions: *state.executions(),
+                elapsed_secs,
+            });
+            self.next_solution_id = id.0 + 1;
+        }
+        Ok(())
+    }
+}
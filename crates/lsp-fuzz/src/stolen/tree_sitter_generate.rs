@@ -52,7 +52,17 @@ impl Grammar {
                 convert_rule(syntax_variable, syntax_grammar, lexical_grammar, alias_map)
             })
             .try_collect()?;
-        Ok(Self::new(language, start_symbol, derivation_rules))
+        let supertypes = syntax_grammar
+            .supertype_symbols
+            .iter()
+            .map(|symbol| syntax_grammar.variables[symbol.index].name.clone())
+            .collect();
+        Ok(Self::new(
+            language,
+            start_symbol,
+            derivation_rules,
+            supertypes,
+        ))
     }
 }
 
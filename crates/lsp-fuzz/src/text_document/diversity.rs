@@ -0,0 +1,127 @@
+//! Keeping generated documents apart from each other.
+//!
+//! Generating from a grammar often yields the same trivial program over and over, so that most
+//! of an initial corpus covers the same few constructs. [`SeedDiversity`] rejects documents whose
+//! [`ShapeFingerprint`] is nearly identical to one of the documents accepted before.
+
+use ahash::HashMap;
+use lsp_fuzz_grammars::Language;
+
+use super::{GrammarBasedMutation, TextDocument, grammar::tree_sitter::TreeIter};
+
+/// The default [`SeedDiversity`] threshold.
+pub const DEFAULT_SEED_SIMILARITY: f64 = 0.9;
+
+/// The multiset of the node kinds in the parse tree of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeFingerprint {
+    language: Language,
+    kinds: HashMap<u16, usize>,
+}
+
+impl ShapeFingerprint {
+    #[must_use]
+    pub fn of(doc: &TextDocument) -> Self {
+        let mut kinds = HashMap::default();
+        for node in doc.parse_tree().iter() {
+            *kinds.entry(node.kind_id()).or_default() += 1;
+        }
+        Self {
+            language: doc.language(),
+            kinds,
+        }
+    }
+
+    /// The weighted Jaccard similarity of the multisets, from 0 (no node kind in common, or
+    /// documents of different languages) to 1 (the same number of nodes of each kind).
+    #[must_use]
+    pub fn similarity(&self, other: &Self) -> f64 {
+        if self.language != other.language {
+            return 0.0;
+        }
+        let (mut intersection, mut union) = (0, 0);
+        for (kind, &count) in &self.kinds {
+            let other_count = other.kinds.get(kind).copied().unwrap_or(0);
+            intersection += count.min(other_count);
+            union += count.max(other_count);
+        }
+        union += other
+            .kinds
+            .iter()
+            .filter(|(kind, _)| !self.kinds.contains_key(kind))
+            .map(|(_, &count)| count)
+            .sum::<usize>();
+        if union == 0 {
+            return 1.0;
+        }
+        #[allow(clippy::cast_precision_loss, reason = "Node counts are far below 2^52")]
+        let similarity = intersection as f64 / union as f64;
+        similarity
+    }
+}
+
+/// The fingerprints of the documents accepted so far, rejecting documents too similar to them.
+#[derive(Debug, Clone)]
+pub struct SeedDiversity {
+    threshold: f64,
+    accepted: Vec<ShapeFingerprint>,
+}
+
+impl SeedDiversity {
+    /// Rejects documents with a [`ShapeFingerprint::similarity`] of at least `threshold`
+    /// to an accepted one.
+    #[must_use]
+    pub const fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            accepted: Vec::new(),
+        }
+    }
+
+    /// Accepts `doc` if it is not too similar to the documents accepted before.
+    pub fn admit(&mut self, doc: &TextDocument) -> bool {
+        let fingerprint = ShapeFingerprint::of(doc);
+        let too_similar = self
+            .accepted
+            .iter()
+            .any(|it| it.similarity(&fingerprint) >= self.threshold);
+        if !too_similar {
+            self.accepted.push(fingerprint);
+        }
+        !too_similar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_fuzz_grammars::Language;
+
+    use super::{SeedDiversity, ShapeFingerprint};
+    use crate::text_document::TextDocument;
+
+    fn doc(language: Language, content: &str) -> TextDocument {
+        TextDocument::new(language, content.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn similarity_of_shapes() {
+        let one = ShapeFingerprint::of(&doc(Language::C, "int x = 1;"));
+        let renamed = ShapeFingerprint::of(&doc(Language::C, "int y = 2;"));
+        let other = ShapeFingerprint::of(&doc(
+            Language::C,
+            "int main(void) { while (1) { return 0; } }",
+        ));
+        assert!((one.similarity(&renamed) - 1.0).abs() < f64::EPSILON);
+        assert!(one.similarity(&other) < 0.5);
+        let elsewhere = ShapeFingerprint::of(&doc(Language::CPlusPlus, "int x = 1;"));
+        assert!(one.similarity(&elsewhere) < f64::EPSILON);
+    }
+
+    #[test]
+    fn reject_near_duplicates() {
+        let mut diversity = SeedDiversity::new(0.9);
+        assert!(diversity.admit(&doc(Language::C, "int x = 1;")));
+        assert!(!diversity.admit(&doc(Language::C, "int y = 2;")));
+        assert!(diversity.admit(&doc(Language::C, "void f(void) { for (;;) {} }")));
+    }
+}
@@ -0,0 +1,145 @@
+use std::{borrow::Cow, ops::Range};
+
+/// The minimum size of the gap allocated when the buffer grows.
+const MIN_GAP: usize = 4096;
+
+/// The content of a text document, stored with a gap at the position of the last edit.
+///
+/// Consecutive edits around the same place only move the bytes between them,
+/// instead of the whole tail of the document as splicing a `Vec` does.
+/// The content is borrowed as the slices around the gap; only reading it as one slice
+/// while the gap is not at the end copies it.
+#[derive(Debug, Clone, Default)]
+pub struct GapBuffer {
+    buf: Vec<u8>,
+    gap: Range<usize>,
+}
+
+impl From<Vec<u8>> for GapBuffer {
+    fn from(buf: Vec<u8>) -> Self {
+        let len = buf.len();
+        Self { buf, gap: len..len }
+    }
+}
+
+impl GapBuffer {
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.buf.len() - (self.gap.end - self.gap.start)
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The content before and after the gap.
+    #[must_use]
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        (&self.buf[..self.gap.start], &self.buf[self.gap.end..])
+    }
+
+    /// The whole content as one slice, which is copied if the gap is not at the end.
+    #[must_use]
+    pub fn contiguous(&self) -> Cow<'_, [u8]> {
+        match self.as_slices() {
+            (front, []) => Cow::Borrowed(front),
+            (front, back) => Cow::Owned([front, back].concat()),
+        }
+    }
+
+    /// The bytes in `range` before and after the gap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    #[must_use]
+    pub fn slices(&self, range: Range<usize>) -> (&[u8], &[u8]) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "Range out of bounds"
+        );
+        let (front, back) = self.as_slices();
+        let gap = front.len();
+        (
+            &front[range.start.min(gap)..range.end.min(gap)],
+            &back[range.start.max(gap) - gap..range.end.max(gap) - gap],
+        )
+    }
+
+    /// The bytes in `range`, which are copied only if the range spans the gap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    #[must_use]
+    pub fn get(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        match self.slices(range) {
+            (front, []) => Cow::Borrowed(front),
+            ([], back) => Cow::Borrowed(back),
+            (front, back) => Cow::Owned([front, back].concat()),
+        }
+    }
+
+    /// The contiguous bytes starting at `offset`, which end at the gap or at the end.
+    #[must_use]
+    pub fn chunk_at(&self, offset: usize) -> &[u8] {
+        if offset < self.gap.start {
+            &self.buf[offset..self.gap.start]
+        } else {
+            let gap_len = self.gap.end - self.gap.start;
+            self.buf.get(offset + gap_len..).unwrap_or_default()
+        }
+    }
+
+    /// Replaces the bytes in `range` with `replacement`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn splice(&mut self, range: Range<usize>, replacement: &[u8]) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "Range out of bounds"
+        );
+        self.move_gap(range.start);
+        self.gap.end += range.end - range.start;
+        if self.gap.end - self.gap.start < replacement.len() {
+            self.grow(replacement.len());
+        }
+        let start = self.gap.start;
+        self.buf[start..start + replacement.len()].copy_from_slice(replacement);
+        self.gap.start += replacement.len();
+    }
+
+    #[must_use]
+    pub fn into_vec(mut self) -> Vec<u8> {
+        let len = self.len();
+        self.move_gap(len);
+        self.buf.truncate(len);
+        self.buf
+    }
+
+    fn move_gap(&mut self, offset: usize) {
+        let Range { start, end } = self.gap;
+        if offset < start {
+            let moved = start - offset;
+            self.buf.copy_within(offset..start, end - moved);
+            self.gap = offset..end - moved;
+        } else if offset > start {
+            let moved = offset - start;
+            self.buf.copy_within(end..end + moved, start);
+            self.gap = offset..end + moved;
+        }
+    }
+
+    /// Widens the gap so that it holds at least `min_len` bytes.
+    fn grow(&mut self, min_len: usize) {
+        let extra = min_len.max(MIN_GAP).max(self.len() / 8);
+        let old_len = self.buf.len();
+        self.buf.resize(old_len + extra, 0);
+        self.buf
+            .copy_within(self.gap.end..old_len, self.gap.end + extra);
+        self.gap.end += extra;
+    }
+}
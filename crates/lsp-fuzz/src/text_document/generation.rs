@@ -1,14 +1,26 @@
-use std::{borrow::Cow, cmp::max, collections::HashMap, marker::PhantomData, ops::Range};
+use std::{
+    borrow::Cow,
+    cmp::max,
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Write},
+    marker::PhantomData,
+    num::NonZero,
+    ops::Range,
+};
 
 use itertools::Itertools;
 use libafl::{HasMetadata, state::HasRand};
 use libafl_bolts::rands::Rand;
 use lsp_fuzz_grammars::Language;
 use serde::{Deserialize, Serialize};
+use tracing::debug;
 
 use super::{
-    grammar::{DerivationSequence, Grammar, Symbol, Terminal},
+    grammar::{
+        DerivationSequence, Grammar, Symbol, Terminal, minimal_expansion::MinimalExpansions,
+    },
     mutations::MAX_DOCUMENT_SIZE,
+    rule_scheduling::CoverageGuidedRuleSelection,
 };
 use crate::utils::RandExt;
 
@@ -36,13 +48,50 @@ impl FromIterator<GrammarContext> for GrammarContextLookup {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, derive_more::Constructor)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GrammarContext {
     pub grammar: Grammar,
     pub node_fragments: DerivationFragments,
+    #[serde(default)]
+    pub start_symbols: StartSymbolOverrides,
+    #[serde(default)]
+    pub minimal_expansions: MinimalExpansions,
 }
 
 impl GrammarContext {
+    /// Creates a grammar context with the start-symbol overrides derived from `grammar`.
+    ///
+    /// The derivations of `grammar` that cannot be generated within [`RECURSION_LIMIT`]
+    /// given the fragments are pruned (see [`Grammar::prune`]), and the
+    /// [minimal expansions](Grammar::minimal_expansions) of the remaining rules are computed.
+    #[must_use]
+    pub fn new(mut grammar: Grammar, node_fragments: DerivationFragments) -> Self {
+        let report = grammar.prune(RECURSION_LIMIT, |kind| {
+            node_fragments
+                .get(kind)
+                .is_some_and(|mut it| it.next().is_some())
+        });
+        debug!(
+            language = %grammar.language(),
+            pruned_derivations = report.pruned_derivations,
+            empty_rules = report.empty_rules.len(),
+            unreachable_rules = report.unreachable_rules.len(),
+            "Pruned grammar"
+        );
+        let start_symbols = StartSymbolOverrides::for_grammar(&grammar);
+        let minimal_expansions = grammar.minimal_expansions(|kind| {
+            node_fragments
+                .get(kind)
+                .is_some_and(|mut it| it.next().is_some())
+        });
+        Self {
+            grammar,
+            node_fragments,
+            start_symbols,
+            minimal_expansions,
+        }
+    }
+
     /// # Panics
     ///
     /// Panics if the configured tree-sitter language cannot be installed into a parser.
@@ -85,8 +134,114 @@ impl GrammarContext {
     pub fn start_symbol(&self) -> &str {
         self.grammar.start_symbol()
     }
+
+    /// Returns the symbols from which a replacement of `node` can be generated.
+    ///
+    /// Besides the kind of the node itself (if it has derivation rules),
+    /// these include the name of the node in the grammar before aliasing and the
+    /// [start-symbol overrides](StartSymbolOverrides) of the node kind.
+    #[must_use]
+    pub fn replacement_symbols<'a>(&'a self, node: tree_sitter::Node<'_>) -> Vec<&'a str> {
+        let rules = self.grammar.derivation_rules();
+        [node.kind(), node.grammar_name()]
+            .into_iter()
+            .filter_map(|it| rules.get_key_value(it).map(|(key, _)| key.as_str()))
+            .chain(self.start_symbols.get(node.kind()))
+            .unique()
+            .collect()
+    }
+}
+
+/// Alternative start symbols for generating replacements of nodes, keyed by node kind.
+///
+/// A node can be replaced by code generated from a more general symbol, e.g., a binary
+/// expression by any expression. This lets generation produce code matching the context of the
+/// node instead of falling back to fragments of the same kind.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartSymbolOverrides {
+    inner: HashMap<String, Vec<String>>,
 }
 
+impl StartSymbolOverrides {
+    /// Derives the overrides of a grammar.
+    ///
+    /// Every supertype of the grammar (e.g., `_expression`) becomes a start symbol for the kinds
+    /// it derives directly.
+    /// Overrides for embedded fragments of the language are added on top.
+    #[must_use]
+    pub fn for_grammar(grammar: &Grammar) -> Self {
+        let mut overrides = Self::default();
+        let rules = grammar.derivation_rules();
+        for (name, derivations) in grammar
+            .supertypes()
+            .iter()
+            .filter_map(|it| rules.get_key_value(it))
+        {
+            for derivation in derivations {
+                if let [Symbol::NonTerminal(kind) | Symbol::Terminal(Terminal::Named(kind))] =
+                    derivation.symbols()
+                {
+                    overrides.insert(kind, name);
+                }
+            }
+        }
+        for &(kind, symbols) in embedded_start_symbols(grammar.language()) {
+            for symbol in symbols {
+                if grammar.derivation_rules().contains_key(*symbol) {
+                    overrides.insert(kind, symbol);
+                }
+            }
+        }
+        overrides
+    }
+
+    /// Adds `symbol` as a start symbol for replacing nodes of `node_kind`.
+    pub fn insert(&mut self, node_kind: &str, symbol: &str) {
+        let symbols = self.inner.entry(node_kind.to_owned()).or_default();
+        if node_kind != symbol && !symbols.iter().any(|it| it == symbol) {
+            symbols.push(symbol.to_owned());
+        }
+    }
+
+    /// Returns the start symbols for replacing nodes of `node_kind`.
+    pub fn get(&self, node_kind: &str) -> impl Iterator<Item = &str> {
+        self.inner
+            .get(node_kind)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+}
+
+/// Start symbols of fragments embedded in documents that the grammar does not group together.
+const fn embedded_start_symbols(
+    language: Language,
+) -> &'static [(&'static str, &'static [&'static str])] {
+    match language {
+        Language::LaTeX => &[
+            (
+                "inline_formula",
+                &["displayed_equation", "math_environment"],
+            ),
+            (
+                "displayed_equation",
+                &["inline_formula", "math_environment"],
+            ),
+            (
+                "math_environment",
+                &["displayed_equation", "inline_formula"],
+            ),
+            ("generic_environment", &["math_environment"]),
+        ],
+        _ => &[],
+    }
+}
+
+/// The levels of derivation rules expanded by [`NamedNodeGenerator`]
+/// before falling back to fragments, or to the minimal expansions of the rules
+/// for symbols without fragments.
+pub const RECURSION_LIMIT: usize = 5;
+
 #[derive(Debug)]
 pub struct NamedNodeGenerator<'a, State, Sel> {
     grammar_context: &'a GrammarContext,
@@ -108,14 +263,20 @@ impl<State, Sel> NamedNodeGenerator<'_, State, Sel>
 where
     Sel: RuleSelectionStrategy<State>,
 {
-    const DEFAULT_REDURSION_LIMIT: usize = 5;
-
     /// # Errors
     ///
-    /// Returns [`DerivationError::NoFragmentAvailable`] when neither a derivation rule nor a
-    /// fallback fragment can be selected for `node_kind`.
+    /// Returns [`DerivationError::NoRuleAvailable`] or [`DerivationError::NoFragmentAvailable`]
+    /// when neither a derivation rule nor a fallback fragment can be selected for `node_kind`
+    /// or one of the symbols it derives, and `node_kind` has no minimal expansion either.
+    ///
+    /// The caller tells through [`Self::conclude`] whether the generated code is kept.
     pub fn generate(&self, node_kind: &str, state: &mut State) -> Result<Vec<u8>, DerivationError> {
-        self.generate_recursively(node_kind, state, Some(Self::DEFAULT_REDURSION_LIMIT))
+        self.generate_recursively(node_kind, state, Some(RECURSION_LIMIT))
+    }
+
+    /// Concludes the last generation, see [`RuleSelectionStrategy::conclude_generation`].
+    pub fn conclude(&self, state: &mut State, kept: bool) {
+        self.selection_strategy.conclude_generation(state, kept);
     }
 
     fn generate_recursively(
@@ -139,14 +300,57 @@ where
                 })
                 .flatten_ok()
                 .collect::<Result<Vec<_>, _>>()
-        } else {
+        } else if let Some(fragment) =
             self.selection_strategy
                 .select_fragment(state, node_kind, self.grammar_context)
-                .map(<[u8]>::to_vec)
-                .ok_or(DerivationError::NoFragmentAvailable)
+        {
+            Ok(fragment.to_vec())
+        } else {
+            self.generate_minimal(node_kind)
         }
     }
 
+    /// Generates the shallowest code for `node_kind`, using the shortest fragment of every
+    /// symbol with fragments and the [minimal expansion](MinimalExpansions) of the others.
+    fn generate_minimal(&self, node_kind: &str) -> Result<Vec<u8>, DerivationError> {
+        if let Some(fragment) = self.shortest_fragment(node_kind) {
+            return Ok(fragment.to_vec());
+        }
+        let rules = self
+            .grammar_context
+            .grammar
+            .derivation_rules()
+            .get(node_kind);
+        let Some(expansion) = self.grammar_context.minimal_expansions.get(node_kind) else {
+            return Err(if rules.is_some_and(|it| !it.is_empty()) {
+                DerivationError::NoFragmentAvailable(node_kind.to_owned())
+            } else {
+                DerivationError::NoRuleAvailable(node_kind.to_owned())
+            });
+        };
+        let rule = rules
+            .and_then(|it| it.get_index(expansion.derivation))
+            .ok_or(DerivationError::InvalidGrammar)?;
+        rule.into_iter()
+            .map(|symbol| match symbol {
+                Symbol::NonTerminal(name) => self.generate_minimal(name),
+                Symbol::Terminal(Terminal::Immediate(content)) => Ok(content.clone()),
+                Symbol::Terminal(Terminal::Named(name) | Terminal::Auxiliary(name)) => self
+                    .shortest_fragment(name)
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| DerivationError::NoFragmentAvailable(name.clone())),
+                Symbol::Eof => Ok(Vec::new()),
+            })
+            .flatten_ok()
+            .collect()
+    }
+
+    fn shortest_fragment(&self, node_kind: &str) -> Option<&[u8]> {
+        self.grammar_context
+            .node_fragments(node_kind)
+            .min_by_key(|it| it.len())
+    }
+
     fn generate_terminal(
         &self,
         state: &mut State,
@@ -158,7 +362,7 @@ where
                 .selection_strategy
                 .select_fragment(state, name, self.grammar_context)
                 .map(<[u8]>::to_vec)
-                .ok_or(DerivationError::NoFragmentAvailable),
+                .ok_or_else(|| DerivationError::NoFragmentAvailable(name.clone())),
         }
     }
 }
@@ -177,9 +381,40 @@ pub trait RuleSelectionStrategy<State> {
         node_kind: &str,
         grammar_context: &'a GrammarContext,
     ) -> Option<&'a DerivationSequence>;
+
+    /// Concludes the derivation of a piece of code from the rules selected since the last
+    /// conclusion, where `kept` tells whether the code is used or rejected.
+    fn conclude_generation(&self, _state: &mut State, _kept: bool) {}
 }
 
-#[derive(Debug)]
+impl<State, Sel> RuleSelectionStrategy<State> for &Sel
+where
+    Sel: RuleSelectionStrategy<State>,
+{
+    fn select_fragment<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a [u8]> {
+        (**self).select_fragment(state, node_kind, grammar_context)
+    }
+
+    fn select_rule<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a DerivationSequence> {
+        (**self).select_rule(state, node_kind, grammar_context)
+    }
+
+    fn conclude_generation(&self, state: &mut State, kept: bool) {
+        (**self).conclude_generation(state, kept);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
 pub struct RandomRuleSelectionStrategy;
 
 impl<State> RuleSelectionStrategy<State> for RandomRuleSelectionStrategy
@@ -207,6 +442,217 @@ where
     }
 }
 
+/// The weight of the derivations of at most one non-terminal, see [`arity_weight`].
+pub const MAX_ARITY_WEIGHT: usize = 8;
+
+/// The default weight of `derivation` in [`WeightedRuleSelectionStrategy`].
+///
+/// The weight halves with every non-terminal beyond the first, down to 1. Derivations of only
+/// terminals are weighted like those of a single non-terminal, so generation is not pulled
+/// towards trivial code, while wide derivations, which multiply the size of the output at
+/// every level of recursion, are picked less often.
+#[must_use]
+pub fn arity_weight(derivation: &DerivationSequence) -> usize {
+    let non_terminals = derivation
+        .symbols()
+        .iter()
+        .filter(|it| matches!(it, Symbol::NonTerminal(_)))
+        .count();
+    let halvings = u32::try_from(non_terminals.saturating_sub(1)).unwrap_or(u32::MAX);
+    max(
+        1,
+        MAX_ARITY_WEIGHT.checked_shr(halvings).unwrap_or_default(),
+    )
+}
+
+/// Chooses derivations in proportion to their weights instead of uniformly.
+///
+/// The weights of the derivations of a symbol are configured per language and symbol, with the
+/// derivations written as they are displayed in the grammar, e.g.,
+/// `{"Rust": {"_expression": {"<binary_expression>": 4, "<unary_expression>": 0}}}` in JSON.
+/// Derivations without a configured weight are weighted by [`arity_weight`]. A weight of 0
+/// disables a derivation, and a symbol whose derivations are all disabled is generated from
+/// fragments.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WeightedRuleSelectionStrategy {
+    overrides: HashMap<Language, HashMap<String, HashMap<String, usize>>>,
+}
+
+impl WeightedRuleSelectionStrategy {
+    /// Weighs the derivations of `node_kind` in `language` by `weights`, keyed by derivation.
+    #[must_use]
+    pub fn with_weights(
+        mut self,
+        language: Language,
+        node_kind: &str,
+        weights: HashMap<String, usize>,
+    ) -> Self {
+        self.overrides
+            .entry(language)
+            .or_default()
+            .insert(node_kind.to_owned(), weights);
+        self
+    }
+
+    /// The weights of the derivations of `node_kind`, in the order of the grammar.
+    pub fn rule_weights<'a>(
+        &'a self,
+        grammar_context: &'a GrammarContext,
+        node_kind: &str,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let rules = grammar_context.grammar.derivation_rules().get(node_kind);
+        let overrides = self
+            .overrides
+            .get(&grammar_context.language())
+            .and_then(|it| it.get(node_kind));
+        rules.into_iter().flatten().map(move |rule| {
+            overrides
+                .and_then(|it| {
+                    it.iter()
+                        .find(|(derivation, _)| displays_as(rule, derivation))
+                })
+                .map_or_else(|| arity_weight(rule), |(_, &weight)| weight)
+        })
+    }
+
+    /// The configured derivations that are not derivations of their symbols in
+    /// `grammar_lookup`, e.g., after a revision of the grammar, as language, symbol,
+    /// and derivation.
+    pub fn unmatched_overrides<'a>(
+        &'a self,
+        grammar_lookup: &'a GrammarContextLookup,
+    ) -> impl Iterator<Item = (Language, &'a str, &'a str)> + 'a {
+        self.overrides
+            .iter()
+            .filter_map(|(&language, symbols)| {
+                Some((language, symbols, grammar_lookup.get(language)?))
+            })
+            .flat_map(|(language, symbols, grammar_context)| {
+                symbols.iter().flat_map(move |(node_kind, weights)| {
+                    let rules = grammar_context.grammar.derivation_rules().get(node_kind);
+                    weights
+                        .keys()
+                        .filter(move |derivation| {
+                            !rules.is_some_and(|it| {
+                                it.iter().any(|rule| displays_as(rule, derivation))
+                            })
+                        })
+                        .map(move |derivation| (language, node_kind.as_str(), derivation.as_str()))
+                })
+            })
+    }
+}
+
+/// Whether `value` is displayed as `expected`, without allocating the displayed string.
+fn displays_as(value: &impl Display, expected: &str) -> bool {
+    struct Remaining<'a>(&'a str);
+
+    impl Write for Remaining<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+            Ok(())
+        }
+    }
+
+    let mut remaining = Remaining(expected);
+    write!(remaining, "{value}").is_ok() && remaining.0.is_empty()
+}
+
+impl<State> RuleSelectionStrategy<State> for WeightedRuleSelectionStrategy
+where
+    State: HasRand,
+{
+    fn select_fragment<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a [u8]> {
+        RandomRuleSelectionStrategy.select_fragment(state, node_kind, grammar_context)
+    }
+
+    fn select_rule<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a DerivationSequence> {
+        let rules = grammar_context.grammar.derivation_rules().get(node_kind)?;
+        // The weights are computed twice rather than collected, as this runs for every symbol.
+        let total = self.rule_weights(grammar_context, node_kind).sum();
+        let mut point = state.rand_mut().below(NonZero::new(total)?);
+        let chosen_idx = self
+            .rule_weights(grammar_context, node_kind)
+            .position(|weight| {
+                if point < weight {
+                    return true;
+                }
+                point -= weight;
+                false
+            })?;
+        rules.get_index(chosen_idx)
+    }
+}
+
+/// How the derivations of symbols are chosen when generating code from the grammar.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum RuleWeighting {
+    /// All derivations are equally likely, see [`RandomRuleSelectionStrategy`].
+    #[default]
+    Uniform,
+    /// Derivations are chosen by [`WeightedRuleSelectionStrategy`].
+    Weighted(WeightedRuleSelectionStrategy),
+    /// Derivations are chosen by [`CoverageGuidedRuleSelection`], which needs a
+    /// [`RuleCoverageFeedback`](super::rule_scheduling::RuleCoverageFeedback) to learn from.
+    CoverageGuided,
+}
+
+impl<State> RuleSelectionStrategy<State> for RuleWeighting
+where
+    State: HasRand + HasMetadata,
+{
+    fn select_fragment<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a [u8]> {
+        match self {
+            Self::Uniform => {
+                RandomRuleSelectionStrategy.select_fragment(state, node_kind, grammar_context)
+            }
+            Self::Weighted(strategy) => strategy.select_fragment(state, node_kind, grammar_context),
+            Self::CoverageGuided => {
+                CoverageGuidedRuleSelection.select_fragment(state, node_kind, grammar_context)
+            }
+        }
+    }
+
+    fn select_rule<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a DerivationSequence> {
+        match self {
+            Self::Uniform => {
+                RandomRuleSelectionStrategy.select_rule(state, node_kind, grammar_context)
+            }
+            Self::Weighted(strategy) => strategy.select_rule(state, node_kind, grammar_context),
+            Self::CoverageGuided => {
+                CoverageGuidedRuleSelection.select_rule(state, node_kind, grammar_context)
+            }
+        }
+    }
+
+    fn conclude_generation(&self, state: &mut State, kept: bool) {
+        if let Self::CoverageGuided = self {
+            CoverageGuidedRuleSelection.conclude_generation(state, kept);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RuleUsageSteer;
 
@@ -272,10 +718,66 @@ pub enum DerivationError {
     DepthLimitReached,
     #[error("The grammar is invalid")]
     InvalidGrammar,
-    #[error("No rule available for the given node kind")]
-    NoRuleAvailable,
-    #[error("No fragment available for the given node kind")]
-    NoFragmentAvailable,
+    /// The node kind has neither derivation rules nor fragments.
+    #[error("No rule or fragment available for node kind `{0}`")]
+    NoRuleAvailable(String),
+    /// The node kind reached the recursion limit, or is a terminal, and has no fragments.
+    #[error("No fragment available for node kind `{0}`")]
+    NoFragmentAvailable(String),
+}
+
+/// The number of failed derivations of each node kind, by cause.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DerivationFailures {
+    pub no_rule: u64,
+    pub no_fragment: u64,
+}
+
+/// The outcomes of the attempts to generate documents.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, libafl_bolts::SerdeAny)]
+pub struct GenerationFailureStats {
+    /// The number of attempts to derive a document.
+    pub attempts: u64,
+    /// The number of documents abandoned after exhausting the retry budget.
+    pub exhausted: u64,
+    /// The number of documents regenerated for being too similar to earlier ones,
+    /// see [`SeedDiversity`](super::diversity::SeedDiversity).
+    #[serde(default)]
+    pub similar: u64,
+    failures: ahash::HashMap<(Language, String), DerivationFailures>,
+}
+
+impl GenerationFailureStats {
+    /// Counts a failed derivation of a document in `language`.
+    pub fn record(&mut self, language: Language, error: &DerivationError) {
+        let (node_kind, no_rule) = match error {
+            DerivationError::NoRuleAvailable(kind) => (kind, true),
+            DerivationError::NoFragmentAvailable(kind) => (kind, false),
+            DerivationError::DepthLimitReached | DerivationError::InvalidGrammar => return,
+        };
+        let failures = self
+            .failures
+            .entry((language, node_kind.clone()))
+            .or_default();
+        if no_rule {
+            failures.no_rule += 1;
+        } else {
+            failures.no_fragment += 1;
+        }
+    }
+
+    /// The failures of each node kind, the most frequent first.
+    #[must_use]
+    pub fn failures(&self) -> Vec<(Language, &str, DerivationFailures)> {
+        let mut failures: Vec<_> = self
+            .failures
+            .iter()
+            .map(|((language, kind), counts)| (*language, kind.as_str(), *counts))
+            .collect();
+        failures.sort_by_key(|(_, _, it)| std::cmp::Reverse(it.no_rule + it.no_fragment));
+        failures
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, derive_more::Constructor)]
@@ -299,6 +801,23 @@ impl DerivationFragments {
             ranges: ranges.iter(),
         })
     }
+
+    /// Adds the fragments of `other` to the fragments of each node kind,
+    /// skipping the ones that are already present.
+    pub fn merge(&mut self, other: Self) {
+        let offset = self.code.len();
+        self.code.extend_from_slice(&other.code);
+        for (kind, ranges) in other.fragments {
+            let existing = self.fragments.entry(kind).or_default();
+            let known: HashSet<&[u8]> = existing.iter().map(|it| &self.code[it.clone()]).collect();
+            let new_ranges: Vec<_> = ranges
+                .into_iter()
+                .map(|it| it.start + offset..it.end + offset)
+                .filter(|it| !known.contains(&self.code[it.clone()]))
+                .collect();
+            existing.extend(new_ranges);
+        }
+    }
 }
 
 impl<'a> Iterator for FragmentsIter<'a> {
@@ -314,3 +833,128 @@ impl ExactSizeIterator for FragmentsIter<'_> {
         self.ranges.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use indexmap::{IndexMap, IndexSet};
+    use libafl::state::NopState;
+    use lsp_fuzz_grammars::Language;
+
+    use super::{
+        DerivationFragments, GrammarContext, GrammarContextLookup, MAX_ARITY_WEIGHT,
+        RuleSelectionStrategy, WeightedRuleSelectionStrategy, arity_weight, displays_as,
+    };
+    use crate::{
+        lsp_input::LspInput,
+        text_document::grammar::{DerivationSequence, Grammar, Symbol, Terminal},
+    };
+
+    fn derivation(non_terminals: usize) -> DerivationSequence {
+        let mut symbols = vec![Symbol::Terminal(Terminal::Immediate(b"(".to_vec()))];
+        symbols.extend((0..non_terminals).map(|it| Symbol::NonTerminal(format!("_symbol{it}"))));
+        DerivationSequence::new(symbols)
+    }
+
+    #[test]
+    fn arity_weight_favors_narrow_derivations() {
+        assert_eq!(arity_weight(&derivation(0)), MAX_ARITY_WEIGHT);
+        assert_eq!(arity_weight(&derivation(1)), MAX_ARITY_WEIGHT);
+        assert_eq!(arity_weight(&derivation(2)), MAX_ARITY_WEIGHT / 2);
+        assert_eq!(arity_weight(&derivation(100)), 1);
+    }
+
+    fn grammar_context() -> GrammarContext {
+        let nt = |name: &str| Symbol::NonTerminal(name.to_owned());
+        let imm = |text: &str| Symbol::Terminal(Terminal::Immediate(text.as_bytes().to_vec()));
+        let rules = |derivations: Vec<Vec<Symbol>>| {
+            derivations
+                .into_iter()
+                .map(DerivationSequence::new)
+                .collect::<IndexSet<_>>()
+        };
+        let grammar = Grammar::new(
+            Language::Rust,
+            "start".to_owned(),
+            IndexMap::from([
+                (
+                    "start".to_owned(),
+                    rules(vec![vec![nt("a")], vec![nt("a"), nt("b")], vec![imm("x")]]),
+                ),
+                ("a".to_owned(), rules(vec![vec![imm("y")]])),
+                ("b".to_owned(), rules(vec![vec![imm("z")]])),
+            ]),
+            Vec::new(),
+        );
+        GrammarContext::new(
+            grammar,
+            DerivationFragments::new(Vec::new(), HashMap::new()),
+        )
+    }
+
+    #[test]
+    fn match_derivations_by_display() {
+        let derivation = DerivationSequence::new(vec![
+            Symbol::NonTerminal("a".to_owned()),
+            Symbol::Terminal(Terminal::Immediate(b"x".to_vec())),
+        ]);
+        assert!(displays_as(&derivation, "<a> \"x\""));
+        assert!(!displays_as(&derivation, "<a>"));
+        assert!(!displays_as(&derivation, "<a> \"x\" <b>"));
+    }
+
+    #[test]
+    fn override_weights_by_derivation() {
+        let grammar_context = grammar_context();
+        let default = WeightedRuleSelectionStrategy::default();
+        assert_eq!(
+            default
+                .rule_weights(&grammar_context, "start")
+                .collect::<Vec<_>>(),
+            [MAX_ARITY_WEIGHT, MAX_ARITY_WEIGHT / 2, MAX_ARITY_WEIGHT]
+        );
+
+        let weights = HashMap::from([
+            ("\"x\"".to_owned(), 0),
+            ("<a> <b>".to_owned(), 3),
+            ("<removed>".to_owned(), 1),
+        ]);
+        let strategy = default.with_weights(Language::Rust, "start", weights);
+        assert_eq!(
+            strategy
+                .rule_weights(&grammar_context, "start")
+                .collect::<Vec<_>>(),
+            [MAX_ARITY_WEIGHT, 3, 0]
+        );
+        let grammar_lookup = GrammarContextLookup::from_iter([grammar_context]);
+        assert_eq!(
+            strategy
+                .unmatched_overrides(&grammar_lookup)
+                .collect::<Vec<_>>(),
+            [(Language::Rust, "start", "<removed>")]
+        );
+    }
+
+    #[test]
+    fn select_only_weighted_derivations() {
+        let grammar_context = grammar_context();
+        let weights = HashMap::from([("<a>".to_owned(), 0), ("<a> <b>".to_owned(), 0)]);
+        let strategy =
+            WeightedRuleSelectionStrategy::default().with_weights(Language::Rust, "start", weights);
+        let mut state = NopState::<LspInput>::new();
+        for _ in 0..16 {
+            let rule = strategy
+                .select_rule(&mut state, "start", &grammar_context)
+                .unwrap();
+            assert!(displays_as(rule, "\"x\""));
+        }
+        let disabled = HashMap::from([("\"y\"".to_owned(), 0)]);
+        let strategy = strategy.with_weights(Language::Rust, "a", disabled);
+        assert!(
+            strategy
+                .select_rule(&mut state, "a", &grammar_context)
+                .is_none()
+        );
+    }
+}
@@ -9,6 +9,7 @@ use std::{
 
 use dot_structures::{Attribute, Edge, EdgeTy, Graph, Id, Node, NodeId, Stmt, Vertex};
 use itertools::{Either, Itertools};
+use lsp_fuzz_grammars::Language;
 
 use super::tree_sitter::TreeIter;
 
@@ -38,6 +39,57 @@ pub fn extract_derivation_fragments<'n>(
     parser: &mut tree_sitter::Parser,
 ) -> Result<HashMap<Cow<'n, str>, Vec<Range<usize>>>, Error> {
     let tree = parser.parse(code, None).ok_or(Error::TreeSitterParsing)?;
+    tree_fragments(tree)
+}
+
+/// Extracts derivation fragments from the given source code written in `language`.
+///
+/// Unlike [`extract_derivation_fragments`], the content of the leaves left to the inline
+/// grammar of the language (see [`Language::inline_grammar`]) is parsed with it as well,
+/// so that, e.g., the emphasis and links of Markdown paragraphs have fragments.
+///
+/// # Errors
+///
+/// Returns [`Error`] under the same conditions as [`extract_derivation_fragments`].
+pub fn extract_language_fragments<'n>(
+    code: &[u8],
+    language: Language,
+) -> Result<HashMap<Cow<'n, str>, Vec<Range<usize>>>, Error> {
+    let mut parser = language.tree_sitter_parser();
+    let tree = parser.parse(code, None).ok_or(Error::TreeSitterParsing)?;
+    let Some((node_kind, inline_language)) = language.inline_grammar() else {
+        return tree_fragments(tree);
+    };
+    let mut inline_ranges: Vec<_> = tree
+        .iter()
+        .filter(|it| it.kind() == node_kind)
+        .map(|it| it.range())
+        .collect();
+    inline_ranges.sort_unstable_by_key(|it| it.start_byte);
+    let mut fragments = tree_fragments(tree)?;
+    if inline_ranges.is_empty() {
+        return Ok(fragments);
+    }
+
+    let mut inline_parser = tree_sitter::Parser::new();
+    inline_parser
+        .set_language(&inline_language)
+        .map_err(|_| Error::TreeSitterParsing)?;
+    inline_parser
+        .set_included_ranges(&inline_ranges)
+        .map_err(|_| Error::TreeSitterParsing)?;
+    let inline_tree = inline_parser
+        .parse(code, None)
+        .ok_or(Error::TreeSitterParsing)?;
+    for (kind, ranges) in tree_fragments(inline_tree)? {
+        fragments.entry(kind).or_default().extend(ranges);
+    }
+    Ok(fragments)
+}
+
+fn tree_fragments<'n>(
+    tree: tree_sitter::Tree,
+) -> Result<HashMap<Cow<'n, str>, Vec<Range<usize>>>, Error> {
     let (named, unnamed): (Vec<_>, Vec<_>) = tree
         .iter()
         // .filter(|it| !it.is_error())
@@ -196,8 +248,6 @@ pub enum Error {
 #[cfg(test)]
 mod test {
 
-    use lsp_fuzz_grammars::Language;
-
     use super::*;
 
     const C_CODE: &str = r#"
@@ -232,4 +282,23 @@ mod test {
             assert!(fragments.contains_key(key), "{key} not found");
         }
     }
+
+    #[test]
+    fn markdown_inline_content_is_parsed() {
+        let code = "# A *title*\n\nSome `code` and [a link](other.md).\n";
+        let fragments = extract_language_fragments(code.as_bytes(), Language::Markdown).unwrap();
+        for key in [
+            "atx_heading",
+            "paragraph",
+            "emphasis",
+            "code_span",
+            "inline_link",
+        ] {
+            assert!(fragments.contains_key(key), "{key} not found");
+        }
+        let [link] = &fragments["inline_link"][..] else {
+            panic!("Expected a single inline link");
+        };
+        assert_eq!(&code[link.clone()], "[a link](other.md)");
+    }
 }
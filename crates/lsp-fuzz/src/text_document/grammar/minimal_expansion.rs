@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{DerivationSequence, Grammar, Symbol, Terminal};
+
+/// The shallowest derivation of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MinimalExpansion {
+    /// The levels of recursion the derivation needs, counting a symbol with fragments as 0.
+    pub depth: usize,
+    /// The index of the derivation among the derivations of the rule.
+    pub derivation: usize,
+}
+
+/// The [`MinimalExpansion`] of every rule that can be derived at all.
+///
+/// Following the minimal expansions, and using fragments where a symbol has some, always
+/// finishes a derivation, since the depth strictly decreases from a rule to its symbols.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MinimalExpansions {
+    rules: HashMap<String, MinimalExpansion>,
+}
+
+impl MinimalExpansions {
+    #[must_use]
+    pub fn get(&self, rule: &str) -> Option<MinimalExpansion> {
+        self.rules.get(rule).copied()
+    }
+}
+
+impl Grammar {
+    /// Computes the [`MinimalExpansions`] of the rules, where a symbol is generated either
+    /// from a derivation rule or from a fragment if `has_fragments` holds for it.
+    pub fn minimal_expansions(&self, has_fragments: impl Fn(&str) -> bool) -> MinimalExpansions {
+        let mut expansions = MinimalExpansions::default();
+        // The depths of the symbols, counting the ones with fragments as 0.
+        let mut depths: HashMap<&str, usize> = self
+            .derivation_rules
+            .keys()
+            .filter(|it| has_fragments(it))
+            .map(|it| (it.as_str(), 0))
+            .collect();
+        // Relaxes the depths until none of them decreases any more.
+        loop {
+            let mut changed = false;
+            for (name, derivations) in &self.derivation_rules {
+                for (idx, derivation) in derivations.iter().enumerate() {
+                    let Some(depth) = derivation_depth(derivation, &depths, &has_fragments) else {
+                        continue;
+                    };
+                    let current = expansions.rules.get(name).map(|it| it.depth);
+                    if current.is_none_or(|it| depth < it) {
+                        expansions.rules.insert(
+                            name.clone(),
+                            MinimalExpansion {
+                                depth,
+                                derivation: idx,
+                            },
+                        );
+                        let symbol_depth = depths.entry(name.as_str()).or_insert(depth);
+                        *symbol_depth = (*symbol_depth).min(depth);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        expansions
+    }
+}
+
+/// The depth of `derivation` given the depths of the symbols known so far,
+/// or `None` if one of its symbols cannot be derived yet.
+fn derivation_depth(
+    derivation: &DerivationSequence,
+    depths: &HashMap<&str, usize>,
+    has_fragments: impl Fn(&str) -> bool,
+) -> Option<usize> {
+    derivation
+        .symbols
+        .iter()
+        .map(|symbol| match symbol {
+            Symbol::NonTerminal(name) => depths.get(name.as_str()).copied(),
+            Symbol::Terminal(Terminal::Named(name) | Terminal::Auxiliary(name)) => {
+                has_fragments(name).then_some(0)
+            }
+            Symbol::Terminal(Terminal::Immediate(_)) | Symbol::Eof => Some(0),
+        })
+        .try_fold(0, |depth, it| it.map(|it| depth.max(it)))
+        .map(|it| it + 1)
+}
@@ -10,6 +10,8 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 pub mod fragment_extraction;
+pub mod minimal_expansion;
+pub mod pruning;
 pub mod tree_sitter;
 
 use super::Language;
@@ -98,6 +100,9 @@ pub struct Grammar {
     start_symbol: String,
     /// The production rules of the grammar, mapping non-terminal names to their possible derivation sequences
     derivation_rules: IndexMap<String, IndexSet<DerivationSequence>>,
+    /// The non-terminals the grammar declares as supertypes, e.g., `_expression`
+    #[serde(default)]
+    supertypes: Vec<String>,
 }
 
 impl Display for Grammar {
@@ -134,6 +139,11 @@ impl Grammar {
         &self.derivation_rules
     }
 
+    #[must_use]
+    pub fn supertypes(&self) -> &[String] {
+        &self.supertypes
+    }
+
     /// Validates that every referenced non-terminal has a corresponding production rule.
     ///
     /// # Errors
@@ -167,7 +177,10 @@ pub enum CreationError {
 mod tests {
 
     use super::*;
-    use crate::text_document::{TextDocument, grammar::tree_sitter::CapturesIterator};
+    use crate::text_document::{
+        GrammarBasedMutation, TextDocument, generation::StartSymbolOverrides,
+        grammar::tree_sitter::CapturesIterator,
+    };
 
     #[test]
     fn load_all_derivation_grammars() {
@@ -180,6 +193,16 @@ mod tests {
             Language::LaTeX,
             Language::BibTeX,
             Language::Solidity,
+            Language::Go,
+            Language::TypeScript,
+            Language::TSX,
+            Language::Java,
+            Language::Json,
+            Language::Yaml,
+            Language::Markdown,
+            Language::Zig,
+            Language::Html,
+            Language::Css,
         ];
         for language in languages {
             let grammar =
@@ -191,6 +214,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn prune_degenerate_rules() {
+        let nt = |name: &str| Symbol::NonTerminal(name.to_owned());
+        let imm = |text: &str| Symbol::Terminal(Terminal::Immediate(text.as_bytes().to_vec()));
+        let rules = |derivations: Vec<Vec<Symbol>>| {
+            derivations
+                .into_iter()
+                .map(DerivationSequence::new)
+                .collect::<IndexSet<_>>()
+        };
+        let mut grammar = Grammar::new(
+            Language::C,
+            "start".to_owned(),
+            IndexMap::from([
+                (
+                    "start".to_owned(),
+                    rules(vec![vec![nt("loop")], vec![nt("empty"), imm("x")]]),
+                ),
+                ("loop".to_owned(), rules(vec![vec![nt("loop"), imm("y")]])),
+                ("empty".to_owned(), rules(vec![vec![]])),
+                ("orphan".to_owned(), rules(vec![vec![imm("z")]])),
+            ]),
+            Vec::new(),
+        );
+        let report = grammar.prune(5, |kind| kind == "empty");
+        assert_eq!(report.pruned_derivations, 2);
+        assert!(grammar.derivation_rules()["loop"].is_empty());
+        assert_eq!(grammar.derivation_rules()["start"].len(), 1);
+        assert_eq!(report.empty_rules, ["empty"]);
+        assert!(grammar.derivation_rules()["empty"].is_empty());
+        assert_eq!(report.unreachable_rules, ["loop", "orphan"]);
+        grammar.validate().unwrap();
+    }
+
+    #[test]
+    fn minimal_expansions_terminate() {
+        let nt = |name: &str| Symbol::NonTerminal(name.to_owned());
+        let imm = |text: &str| Symbol::Terminal(Terminal::Immediate(text.as_bytes().to_vec()));
+        let rules = |derivations: Vec<Vec<Symbol>>| {
+            derivations
+                .into_iter()
+                .map(DerivationSequence::new)
+                .collect::<IndexSet<_>>()
+        };
+        let grammar = Grammar::new(
+            Language::C,
+            "start".to_owned(),
+            IndexMap::from([
+                (
+                    "start".to_owned(),
+                    rules(vec![vec![nt("list")], vec![nt("item"), imm(";")]]),
+                ),
+                (
+                    "list".to_owned(),
+                    rules(vec![vec![nt("list"), nt("item")], vec![nt("item")]]),
+                ),
+                ("item".to_owned(), rules(vec![vec![nt("item")]])),
+                ("loop".to_owned(), rules(vec![vec![nt("loop"), imm("y")]])),
+            ]),
+            Vec::new(),
+        );
+        let expansions = grammar.minimal_expansions(|kind| kind == "item");
+        let list = expansions.get("list").unwrap();
+        assert_eq!((list.depth, list.derivation), (1, 1));
+        let start = expansions.get("start").unwrap();
+        assert_eq!((start.depth, start.derivation), (1, 1));
+        assert!(expansions.get("loop").is_none());
+    }
+
+    #[test]
+    fn start_symbols_from_supertypes() {
+        let grammar =
+            Grammar::from_tree_sitter_grammar_json(Language::Rust, Language::Rust.grammar_json())
+                .unwrap();
+        let overrides = StartSymbolOverrides::for_grammar(&grammar);
+        assert!(
+            overrides
+                .get("range_expression")
+                .any(|it| it == "_expression")
+        );
+        assert!(overrides.get("_expression").next().is_none());
+        assert!(overrides.get("line_comment").all(|it| it != "comment"));
+    }
+
     #[test]
     fn capture_rust() {
         const RUST_CODE: &str = r#"
@@ -202,14 +309,14 @@ mod tests {
         let doc = TextDocument::new(Language::Rust, RUST_CODE.as_bytes().to_vec());
         let mut capture_iter = CapturesIterator::new(&doc, "comment").unwrap();
         let node = capture_iter.next().expect("There is one comment node");
-        let text = &doc.content()[node.byte_range()];
-        assert_eq!(text, b"// Hello");
+        let text = doc.fragment(node.byte_range());
+        assert_eq!(*text, *b"// Hello");
         assert!(dbg!(capture_iter.next()).is_none());
 
         let mut capture_iter = CapturesIterator::new(&doc, "keyword").unwrap();
         let node = capture_iter.next().expect("There is one keyword node");
-        let text = &doc.content()[node.byte_range()];
-        assert_eq!(text, b"fn");
+        let text = doc.fragment(node.byte_range());
+        assert_eq!(*text, *b"fn");
         assert!(capture_iter.next().is_none());
     }
 }
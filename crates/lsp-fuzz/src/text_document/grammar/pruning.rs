@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet};
+
+use tracing::debug;
+
+use super::{DerivationSequence, Grammar, Symbol, Terminal};
+
+/// What [`Grammar::prune`] found and removed.
+#[derive(Debug, Default)]
+pub struct PruningReport {
+    /// The number of derivations removed because they cannot be generated within the depth bound.
+    pub pruned_derivations: usize,
+    /// The rules whose only derivations are empty.
+    pub empty_rules: Vec<String>,
+    /// The rules that cannot be reached from the start symbol.
+    pub unreachable_rules: Vec<String>,
+}
+
+impl Grammar {
+    /// Removes the derivations that can never be generated within `depth_bound` levels
+    /// of recursion, where a symbol is generated either from a derivation rule or,
+    /// at the depth bound or without rules, from a fragment.
+    ///
+    /// Rules whose only derivations are empty are cleared if `has_fragments` holds for them,
+    /// so that fragments are used instead of empty code.
+    /// Unreachable rules are only reported, since mutations replace nodes of their kinds.
+    pub fn prune(
+        &mut self,
+        depth_bound: usize,
+        has_fragments: impl Fn(&str) -> bool,
+    ) -> PruningReport {
+        let mut report = PruningReport::default();
+        loop {
+            let feasible = Feasibility::compute(self, depth_bound, &has_fragments);
+            let mut pruned = 0;
+            for (name, derivations) in &mut self.derivation_rules {
+                let before = derivations.len();
+                derivations.retain(|it| {
+                    (1..=depth_bound).any(|depth| feasible.derivation(it, depth, &has_fragments))
+                });
+                pruned += before - derivations.len();
+                if derivations.is_empty() && before > 0 {
+                    debug!(rule = %name, "All derivations of rule pruned");
+                }
+            }
+            if pruned == 0 {
+                break;
+            }
+            report.pruned_derivations += pruned;
+        }
+
+        for (name, derivations) in &mut self.derivation_rules {
+            if !derivations.is_empty() && derivations.iter().all(|it| it.symbols.is_empty()) {
+                report.empty_rules.push(name.clone());
+                if has_fragments(name) {
+                    derivations.clear();
+                }
+            }
+        }
+
+        let reachable = self.reachable_rules();
+        report.unreachable_rules = self
+            .derivation_rules
+            .keys()
+            .filter(|it| !reachable.contains(it.as_str()))
+            .cloned()
+            .collect();
+        report
+    }
+
+    fn reachable_rules(&self) -> HashSet<&str> {
+        let mut reachable = HashSet::from([self.start_symbol.as_str()]);
+        let mut pending = vec![self.start_symbol.as_str()];
+        while let Some(name) = pending.pop() {
+            let symbols = self
+                .derivation_rules
+                .get(name)
+                .into_iter()
+                .flatten()
+                .flatten();
+            for symbol in symbols {
+                if let Symbol::NonTerminal(next) = symbol
+                    && reachable.insert(next.as_str())
+                {
+                    pending.push(next);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+/// Whether each rule can be generated with a given number of levels of recursion left.
+struct Feasibility {
+    /// `levels[name][depth]` holds if `name` can be generated with `depth` levels left.
+    levels: HashMap<String, Vec<bool>>,
+}
+
+impl Feasibility {
+    fn compute(
+        grammar: &Grammar,
+        depth_bound: usize,
+        has_fragments: impl Fn(&str) -> bool,
+    ) -> Self {
+        let mut feasible = Self {
+            levels: HashMap::with_capacity(grammar.derivation_rules.len()),
+        };
+        for depth in 0..=depth_bound {
+            let level: Vec<_> = grammar
+                .derivation_rules
+                .iter()
+                .map(|(name, derivations)| {
+                    let generated = if depth == 0 || derivations.is_empty() {
+                        has_fragments(name)
+                    } else {
+                        derivations
+                            .iter()
+                            .any(|it| feasible.derivation(it, depth, &has_fragments))
+                    };
+                    (name, generated)
+                })
+                .collect();
+            for (name, generated) in level {
+                feasible
+                    .levels
+                    .entry(name.clone())
+                    .or_default()
+                    .push(generated);
+            }
+        }
+        feasible
+    }
+
+    /// Whether `derivation` can be generated with `depth` levels left, i.e.,
+    /// its symbols can be generated with `depth - 1` levels left.
+    fn derivation(
+        &self,
+        derivation: &DerivationSequence,
+        depth: usize,
+        has_fragments: impl Fn(&str) -> bool,
+    ) -> bool {
+        derivation.symbols.iter().all(|symbol| match symbol {
+            Symbol::NonTerminal(name) => self
+                .levels
+                .get(name)
+                .and_then(|it| it.get(depth - 1))
+                .copied()
+                .unwrap_or(false),
+            Symbol::Terminal(Terminal::Named(name) | Terminal::Auxiliary(name)) => {
+                has_fragments(name)
+            }
+            Symbol::Terminal(Terminal::Immediate(_)) | Symbol::Eof => true,
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     fmt::{self, Debug},
     iter::FusedIterator,
     ops::Range,
@@ -95,17 +96,17 @@ impl ExactSizeIterator for TreeIterator<'_> {
 
 impl FusedIterator for TreeIterator<'_> {}
 
-impl<'a> TextProvider<&'a [u8]> for &'a TextDocument {
-    type I = std::iter::Once<&'a [u8]>;
+impl<'a> TextProvider<Cow<'a, [u8]>> for &'a TextDocument {
+    type I = std::iter::Once<Cow<'a, [u8]>>;
 
     fn text(&mut self, node: tree_sitter::Node<'_>) -> Self::I {
-        std::iter::once(&self.content()[node.byte_range()])
+        std::iter::once(self.fragment(node.byte_range()))
     }
 }
 
 pub struct CapturesIterator<'doc> {
     cursor_ptr: NonNull<tree_sitter::ffi::TSQueryCursor>,
-    captures: QueryCaptures<'doc, 'doc, &'doc TextDocument, &'doc [u8]>,
+    captures: QueryCaptures<'doc, 'doc, &'doc TextDocument, Cow<'doc, [u8]>>,
     capture_index: u32,
 }
 
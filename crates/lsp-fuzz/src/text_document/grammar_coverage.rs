@@ -0,0 +1,312 @@
+//! Measuring which derivation rules of the grammars the corpus exercises.
+//!
+//! The documents are mapped back to the derivations of their [`Grammar`] by matching the
+//! children of each node against the derivations of its symbol, see [`matched_derivation`].
+//! Hidden symbols (e.g., `_expression` or the `*_repeat1` helpers of tree-sitter) never appear
+//! in parse trees, so their derivations are neither matched nor counted.
+//! [`GrammarCoverageFeedback`] records the derivations of the inputs added to the corpus in
+//! [`GrammarCoverage`].
+
+use std::borrow::Cow;
+
+use ahash::{HashMap, HashSet};
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+};
+use libafl_bolts::{Named, SerdeAny};
+use lsp_fuzz_grammars::Language;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    GrammarBasedMutation, TextDocument,
+    generation::GrammarContextLookup,
+    grammar::{DerivationSequence, Grammar, Symbol, Terminal, tree_sitter::TreeIter},
+};
+use crate::lsp_input::LspInput;
+
+/// Whether the nodes of the symbol `name` are hidden from parse trees.
+///
+/// Tree-sitter hides the rules starting with `_` and the auxiliary rules it creates,
+/// which are named `<rule>_repeat<n>` and `<rule>_token<n>`.
+#[must_use]
+pub fn is_hidden_symbol(name: &str) -> bool {
+    let auxiliary = ["_repeat", "_token"].iter().any(|infix| {
+        name.rsplit_once(infix)
+            .is_some_and(|(_, n)| !n.is_empty() && n.bytes().all(|it| it.is_ascii_digit()))
+    });
+    name.starts_with('_') || auxiliary
+}
+
+/// How a symbol of a derivation matches the children of a node.
+enum SymbolMatch<'a> {
+    /// The symbol is a node of the given name.
+    Node(&'a str),
+    /// The symbol is an anonymous token with the given text.
+    Token(&'a [u8]),
+    /// The symbol is hidden and matches any number of children.
+    Hidden,
+}
+
+impl<'a> SymbolMatch<'a> {
+    fn of(symbol: &'a Symbol) -> Self {
+        match symbol {
+            Symbol::Terminal(Terminal::Immediate(text)) => Self::Token(text),
+            Symbol::Terminal(Terminal::Named(name)) | Symbol::NonTerminal(name)
+                if !is_hidden_symbol(name) =>
+            {
+                Self::Node(name)
+            }
+            Symbol::Terminal(_) | Symbol::NonTerminal(_) | Symbol::Eof => Self::Hidden,
+        }
+    }
+
+    fn matches(&self, node: tree_sitter::Node<'_>) -> bool {
+        match self {
+            Self::Node(name) => node.kind() == *name || node.grammar_name() == *name,
+            Self::Token(text) => !node.is_named() && node.kind().as_bytes() == *text,
+            Self::Hidden => true,
+        }
+    }
+}
+
+/// Whether `derivation` derives exactly `children`, with hidden symbols deriving any run of them.
+fn derives(derivation: &DerivationSequence, children: &[tree_sitter::Node<'_>]) -> bool {
+    // `reachable[i]` is whether the symbols so far can derive the first `i` children.
+    let mut reachable = vec![false; children.len() + 1];
+    reachable[0] = true;
+    for symbol in derivation.symbols() {
+        let symbol = SymbolMatch::of(symbol);
+        let mut next = vec![false; children.len() + 1];
+        if matches!(symbol, SymbolMatch::Hidden) {
+            let mut any = false;
+            for (next, reachable) in next.iter_mut().zip(&reachable) {
+                any |= reachable;
+                *next = any;
+            }
+        } else {
+            for (idx, child) in children.iter().enumerate() {
+                next[idx + 1] = reachable[idx] && symbol.matches(*child);
+            }
+        }
+        reachable = next;
+    }
+    reachable[children.len()]
+}
+
+/// The symbol of `node` in `grammar` and the index of the first of its derivations that derives
+/// the children of `node`.
+///
+/// Returns `None` for nodes with syntax errors, for nodes of symbols without derivations
+/// (e.g., tokens), and for nodes that none of the derivations matches.
+#[must_use]
+pub fn matched_derivation<'g>(
+    grammar: &'g Grammar,
+    node: tree_sitter::Node<'_>,
+) -> Option<(&'g str, usize)> {
+    if !node.is_named() || node.has_error() {
+        return None;
+    }
+    let rules = grammar.derivation_rules();
+    let (symbol, derivations) = rules
+        .get_key_value(node.grammar_name())
+        .or_else(|| rules.get_key_value(node.kind()))?;
+    let mut cursor = node.walk();
+    let children: Vec<_> = node
+        .children(&mut cursor)
+        .filter(|it| !it.is_extra())
+        .collect();
+    let idx = derivations.iter().position(|it| derives(it, &children))?;
+    Some((symbol.as_str(), idx))
+}
+
+/// The derivation rules exercised by the corpus, by language, as symbols and the indices of
+/// their derivations in the grammar.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, SerdeAny)]
+pub struct GrammarCoverage {
+    rules: HashMap<Language, HashSet<(String, usize)>>,
+}
+
+impl GrammarCoverage {
+    /// Records the derivations of the nodes of `doc`, returning the number of them not recorded
+    /// before.
+    pub fn record(&mut self, grammar: &Grammar, doc: &TextDocument) -> usize {
+        let covered = self.rules.entry(grammar.language()).or_default();
+        doc.parse_tree()
+            .iter()
+            .filter_map(|node| matched_derivation(grammar, node))
+            .filter(|&(symbol, idx)| covered.insert((symbol.to_owned(), idx)))
+            .count()
+    }
+
+    /// Whether the derivation `idx` of `symbol` in `language` was recorded.
+    #[must_use]
+    pub fn is_covered(&self, language: Language, symbol: &str, idx: usize) -> bool {
+        self.rules
+            .get(&language)
+            .is_some_and(|it| it.contains(&(symbol.to_owned(), idx)))
+    }
+
+    /// The number of derivations recorded for `language`.
+    #[must_use]
+    pub fn covered(&self, language: Language) -> usize {
+        self.rules.get(&language).map_or(0, HashSet::len)
+    }
+
+    /// The number of derivations recorded for all languages.
+    #[must_use]
+    pub fn total_covered(&self) -> usize {
+        self.rules.values().map(HashSet::len).sum()
+    }
+
+    /// The derivations of the visible symbols of `grammar` that were not recorded,
+    /// in the order of the grammar.
+    #[must_use]
+    pub fn uncovered<'g>(&self, grammar: &'g Grammar) -> Vec<(&'g str, &'g DerivationSequence)> {
+        visible_rules(grammar)
+            .filter(|(symbol, idx, _)| !self.is_covered(grammar.language(), symbol, *idx))
+            .map(|(symbol, _, derivation)| (symbol, derivation))
+            .collect()
+    }
+}
+
+/// The derivations of the symbols of `grammar` that can appear in parse trees,
+/// with their symbols and indices.
+pub fn visible_rules(
+    grammar: &Grammar,
+) -> impl Iterator<Item = (&str, usize, &DerivationSequence)> {
+    grammar
+        .derivation_rules()
+        .iter()
+        .filter(|(symbol, _)| !is_hidden_symbol(symbol))
+        .flat_map(|(symbol, derivations)| {
+            derivations
+                .iter()
+                .enumerate()
+                .map(move |(idx, derivation)| (symbol.as_str(), idx, derivation))
+        })
+}
+
+/// Records the derivations of the documents of the inputs added to the corpus in
+/// [`GrammarCoverage`], see the [module](self).
+///
+/// The feedback never considers an input interesting by itself.
+#[derive(Debug)]
+pub struct GrammarCoverageFeedback<'g> {
+    grammar_lookup: &'g GrammarContextLookup,
+}
+
+impl<'g> GrammarCoverageFeedback<'g> {
+    #[must_use]
+    pub const fn new(grammar_lookup: &'g GrammarContextLookup) -> Self {
+        Self { grammar_lookup }
+    }
+}
+
+impl Named for GrammarCoverageFeedback<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("GrammarCoverageFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for GrammarCoverageFeedback<'_>
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        if !state.has_metadata::<GrammarCoverage>() {
+            state.add_metadata(GrammarCoverage::default());
+        }
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for GrammarCoverageFeedback<'_>
+where
+    State: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        _input: &LspInput,
+        _observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _observers: &Observers,
+        testcase: &mut Testcase<LspInput>,
+    ) -> Result<(), libafl::Error> {
+        let Some(input) = testcase.input() else {
+            return Ok(());
+        };
+        let coverage = state.metadata_or_insert_with(GrammarCoverage::default);
+        for (_, entry) in input.workspace.iter_files() {
+            let Some(doc) = entry.as_source_file() else {
+                continue;
+            };
+            if let Some(grammar_context) = self.grammar_lookup.get(doc.language()) {
+                coverage.record(&grammar_context.grammar, doc);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auxiliary_symbols_are_hidden() {
+        assert!(is_hidden_symbol("_expression"));
+        assert!(is_hidden_symbol("translation_unit_repeat1"));
+        assert!(is_hidden_symbol("preproc_include_token1"));
+        assert!(!is_hidden_symbol("delim_token_tree"));
+        assert!(!is_hidden_symbol("if_statement"));
+    }
+
+    #[test]
+    fn record_c_derivations() {
+        let grammar =
+            Grammar::from_tree_sitter_grammar_json(Language::C, Language::C.grammar_json())
+                .unwrap();
+        let doc = TextDocument::new(
+            Language::C,
+            b"int main(void) { if (1) return 0; return 1; }".to_vec(),
+        );
+        let mut coverage = GrammarCoverage::default();
+        let recorded = coverage.record(&grammar, &doc);
+        assert!(recorded > 0);
+        assert_eq!(coverage.record(&grammar, &doc), 0);
+        assert_eq!(coverage.covered(Language::C), recorded);
+
+        let derivations = &grammar.derivation_rules()["if_statement"];
+        assert!((0..derivations.len()).any(|idx| coverage.is_covered(
+            Language::C,
+            "if_statement",
+            idx
+        )));
+        let uncovered = coverage.uncovered(&grammar);
+        assert!(
+            uncovered
+                .iter()
+                .all(|(symbol, _)| !is_hidden_symbol(symbol))
+        );
+        assert!(
+            uncovered
+                .iter()
+                .any(|(symbol, _)| *symbol == "while_statement")
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use std::{borrow::Cow, hash::Hash, ops::Range};
 
 use ahash::{HashMap, HashSet};
+use gap_buffer::GapBuffer;
 use generation::{GrammarContext, GrammarContextLookup};
 use grammar::tree_sitter::TreeIter;
 use itertools::Itertools;
@@ -17,20 +18,37 @@ use libafl_bolts::{
 };
 use lsp_fuzz_grammars::Language;
 use mutations::{
-    NodeContentMutation, NodeTruncation, ReplaceNodeMutation,
+    NodeContentMutation, NodeTruncation, ReplaceAllOccurrences, ReplaceNodeMutation,
+    line_endings::{MixLineEndings, ToggleByteOrderMark},
     node_filters::HighlightedNodes,
-    node_generators::{ChooseFromDerivations, EmptyNode, ExpandGrammar, MismatchedNode},
+    node_generators::{
+        ChooseFromDerivations, ChooseFromLearnedFragments, EmptyNode, ExpandGrammar, MismatchedNode,
+    },
+    pragmas::MutatePragmaVersion,
+    stress_patterns::ApplyStressPattern,
+    syntax_errors::InjectSyntaxError,
     text_document_selectors::RandomDoc,
 };
+use node_index::{NodeClass, NodeIndex};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use tuple_list::tuple_list;
 
-use crate::{lsp::GeneratorsConfig, lsp_input::LspInput, mutators::WithProbability};
+use crate::{
+    lsp::GeneratorsConfig,
+    lsp_input::{LspInput, calibration_check},
+    mutators::{MutationRegistry, RegisterEach, WithProbability},
+};
 
+pub mod diversity;
+pub mod gap_buffer;
 pub mod generation;
 pub mod grammar;
+pub mod grammar_coverage;
 pub mod mutations;
+pub mod node_index;
+pub mod rule_scheduling;
+pub mod scopes;
 
 pub const LINE_SEP: u8 = b'\n';
 
@@ -38,7 +56,7 @@ pub const LINE_SEP: u8 = b'\n';
 #[serde(from = "TextDocumentSerialized", into = "TextDocumentSerialized")]
 pub struct TextDocument {
     language: Language,
-    content: Vec<u8>,
+    content: GapBuffer,
     // Skipped for serialization
     metadata: Metadata,
 }
@@ -50,6 +68,7 @@ pub struct Metadata {
     pub parse_tree: tree_sitter::Tree,
     pub node_type_ranges: HashMap<u16, HashSet<tree_sitter::Range>>,
     pub node_signatures: HashMap<SmallVec<[u16; SIGNATURE_LEVEL]>, HashSet<tree_sitter::Point>>,
+    pub node_index: NodeIndex,
 }
 
 impl Metadata {
@@ -68,7 +87,9 @@ impl Metadata {
             parse_tree,
             node_type_ranges: HashMap::default(),
             node_signatures: HashMap::default(),
+            node_index: NodeIndex::default(),
         };
+        result.node_index = NodeIndex::build(&result.parse_tree);
         result.update_node_info();
         result
     }
@@ -101,7 +122,14 @@ impl Metadata {
 
 impl PartialEq for TextDocument {
     fn eq(&self, other: &Self) -> bool {
-        self.language == other.language && self.content == other.content
+        let (front, back) = self.content_slices();
+        let (other_front, other_back) = other.content_slices();
+        self.language == other.language
+            && self.content.len() == other.content.len()
+            && front
+                .iter()
+                .chain(back)
+                .eq(other_front.iter().chain(other_back))
     }
 }
 
@@ -110,7 +138,7 @@ impl Eq for TextDocument {}
 impl Hash for TextDocument {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.language.hash(state);
-        self.content.hash(state);
+        self.content().hash(state);
     }
 }
 
@@ -125,7 +153,7 @@ impl TextDocument {
         let metadata = Metadata::generate(language, &content);
         Self {
             language,
-            content,
+            content: GapBuffer::from(content),
             metadata,
         }
     }
@@ -135,32 +163,76 @@ impl TextDocument {
         &self.metadata
     }
 
-    /// Reparses the current content and refreshes cached node indexes.
+    /// Reparses the current content after `edits` and refreshes cached node indexes.
     ///
     /// # Panics
     ///
     /// Panics if incremental reparsing fails.
-    pub fn update_metadata(&mut self) {
+    pub fn update_metadata(&mut self, edits: &[tree_sitter::InputEdit]) {
         let mut parser = self.language.tree_sitter_parser();
-        self.metadata.parse_tree = parser
-            .parse(&self.content, Some(&self.metadata.parse_tree))
+        // Parse the content around the gap without making it contiguous.
+        let parse_tree = parser
+            .parse_with_options(
+                &mut |offset, _| self.content.chunk_at(offset),
+                Some(&self.metadata.parse_tree),
+                None,
+            )
             .expect("Parsing should not fail");
+        let changed_ranges = self.metadata.parse_tree.changed_ranges(&parse_tree);
+        self.metadata
+            .node_index
+            .update(edits, changed_ranges, &parse_tree);
+        self.metadata.parse_tree = parse_tree;
         self.metadata.update_node_info();
     }
 
     #[must_use]
     pub fn to_string_lossy(&self) -> Cow<'_, str> {
-        String::from_utf8_lossy(&self.content)
+        match self.content() {
+            Cow::Borrowed(content) => String::from_utf8_lossy(content),
+            Cow::Owned(content) => Cow::Owned(
+                String::from_utf8(content)
+                    .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()),
+            ),
+        }
+    }
+
+    /// The lines of the document, of which only the one spanning the gap is copied.
+    #[must_use]
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = Cow<'_, [u8]>> {
+        let (front, back) = self.content_slices();
+        let mut lines: Vec<_> = front
+            .split(|&it| it == LINE_SEP)
+            .map(Cow::Borrowed)
+            .collect();
+        if !back.is_empty() {
+            let mut back_lines = back.split(|&it| it == LINE_SEP);
+            let last_front = lines.pop().expect("Splitting yields at least one line");
+            let first_back = back_lines
+                .next()
+                .expect("Splitting yields at least one line");
+            lines.push(if last_front.is_empty() {
+                Cow::Borrowed(first_back)
+            } else {
+                Cow::Owned([&last_front, first_back].concat())
+            });
+            lines.extend(back_lines.map(Cow::Borrowed));
+        }
+        lines.into_iter()
     }
 
+    /// The content as one slice, which is copied if the last edit is not at the end.
+    ///
+    /// Use [`Self::content_slices`] or [`GrammarBasedMutation::fragment`] to avoid the copy.
     #[must_use]
-    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &[u8]> {
-        self.content.as_slice().split(|&it| it == LINE_SEP)
+    pub fn content(&self) -> Cow<'_, [u8]> {
+        self.content.contiguous()
     }
 
+    /// The content before and after the position of the last edit.
     #[must_use]
-    pub const fn content(&self) -> &[u8] {
-        self.content.as_slice()
+    pub fn content_slices(&self) -> (&[u8], &[u8]) {
+        self.content.as_slices()
     }
 
     #[must_use]
@@ -187,10 +259,41 @@ impl TextDocument {
 pub trait GrammarBasedMutation {
     fn language(&self) -> Language;
     fn parse_tree(&self) -> &tree_sitter::Tree;
-    fn fragment(&self, range: Range<usize>) -> &[u8];
+    fn fragment(&self, range: Range<usize>) -> Cow<'_, [u8]>;
     fn edit<E>(&mut self, edit: E) -> tree_sitter::InputEdit
     where
-        E: FnOnce(&mut Vec<u8>) -> tree_sitter::InputEdit;
+        E: FnOnce(&mut GapBuffer) -> tree_sitter::InputEdit;
+
+    /// Applies several edits at once, reparsing the document only after the last one.
+    ///
+    /// `edit` returns the edits in the order it applied them.
+    fn edit_many<E>(&mut self, edit: E) -> Vec<tree_sitter::InputEdit>
+    where
+        E: FnOnce(&mut GapBuffer) -> Vec<tree_sitter::InputEdit>;
+
+    /// Replaces several nodes at once, returning the edits in the order they were applied.
+    ///
+    /// The replacements are applied from the end of the document, so that the ranges of the
+    /// remaining nodes stay valid. Replacements overlapping a later one are dropped.
+    fn splice_many(
+        &mut self,
+        mut replacements: Vec<(tree_sitter::Range, Vec<u8>)>,
+    ) -> Vec<tree_sitter::InputEdit> {
+        replacements.sort_by_key(|(range, _)| std::cmp::Reverse(range.start_byte));
+        self.edit_many(|content| {
+            let mut applied_from = usize::MAX;
+            let mut input_edits = Vec::with_capacity(replacements.len());
+            for (range, new_content) in replacements {
+                if range.end_byte > applied_from {
+                    continue;
+                }
+                content.splice(range.start_byte..range.end_byte, &new_content);
+                input_edits.push(edit_for_node_replacement(range, &new_content));
+                applied_from = range.start_byte;
+            }
+            input_edits
+        })
+    }
 
     fn splice(
         &mut self,
@@ -198,13 +301,8 @@ pub trait GrammarBasedMutation {
         new_content: Vec<u8>,
     ) -> tree_sitter::InputEdit {
         self.edit(|content| {
-            let byte_range = range.start_byte..range.end_byte;
-            let new_content_len = new_content.len();
-            // Update the content
-            let _ = content.splice(byte_range, new_content);
-            let replacement = &content[range.start_byte..][..new_content_len];
-
-            edit_for_node_replacement(range, replacement)
+            content.splice(range.start_byte..range.end_byte, &new_content);
+            edit_for_node_replacement(range, &new_content)
         })
     }
 }
@@ -212,20 +310,34 @@ pub trait GrammarBasedMutation {
 impl GrammarBasedMutation for TextDocument {
     fn edit<E>(&mut self, edit: E) -> tree_sitter::InputEdit
     where
-        E: FnOnce(&mut Vec<u8>) -> tree_sitter::InputEdit,
+        E: FnOnce(&mut GapBuffer) -> tree_sitter::InputEdit,
     {
         let input_edit = edit(&mut self.content);
+        calibration_check::record_edit(&input_edit);
         self.metadata.parse_tree.edit(&input_edit);
-        self.update_metadata();
+        self.update_metadata(std::slice::from_ref(&input_edit));
         input_edit
     }
 
+    fn edit_many<E>(&mut self, edit: E) -> Vec<tree_sitter::InputEdit>
+    where
+        E: FnOnce(&mut GapBuffer) -> Vec<tree_sitter::InputEdit>,
+    {
+        let input_edits = edit(&mut self.content);
+        for input_edit in &input_edits {
+            calibration_check::record_edit(input_edit);
+            self.metadata.parse_tree.edit(input_edit);
+        }
+        self.update_metadata(&input_edits);
+        input_edits
+    }
+
     fn language(&self) -> Language {
         self.language
     }
 
-    fn fragment(&self, range: Range<usize>) -> &[u8] {
-        &self.content[range]
+    fn fragment(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        self.content.get(range)
     }
 
     fn parse_tree(&self) -> &tree_sitter::Tree {
@@ -274,7 +386,10 @@ pub fn measure_fragment<const LINE_SEP: u8>(fragment: &[u8]) -> (usize, usize) {
 
 impl HasTargetBytes for TextDocument {
     fn target_bytes(&self) -> OwnedSlice<'_, u8> {
-        OwnedSlice::from(&self.content)
+        match self.content() {
+            Cow::Borrowed(content) => OwnedSlice::from(content),
+            Cow::Owned(content) => OwnedSlice::from(content),
+        }
     }
 }
 
@@ -288,52 +403,112 @@ type ReplaceNodeInRandomRoc<'a, NodeSel, NodeGen> =
     ReplaceNodeMutation<'a, RandomDoc, NodeSel, NodeGen>;
 type NodeMutationInRandomDoc<'a, Mut, NodeSel> = NodeContentMutation<'a, Mut, RandomDoc, NodeSel>;
 
+/// The weights of the built-in mutations of text documents that are not weighted 1, by name.
+///
+/// They can be overridden with [`GeneratorsConfig::mutation_weights`].
+pub const DEFAULT_MUTATION_WEIGHTS: &[(&str, usize)] = &[
+    ("ReplaceNode:ChooseFromDerivations", 2),
+    ("ReplaceNode:ExpandGrammar", 4),
+    ("RemoveComment", 3),
+    ("DropTerminal", 2),
+    ("MixLineEndings", 2),
+];
+
+/// The maximum number of text document mutations stacked by [`text_document_mutator`],
+/// as a power of 2.
+pub const TEXT_DOCUMENT_MUTATION_STACK_POW: usize = 6;
+
+/// The mutator of the text documents of campaigns.
+///
+/// Each of the built-in [`text_document_mutations`] is registered under its own name, weighted by
+/// [`GeneratorsConfig::mutation_weights`] and [`DEFAULT_MUTATION_WEIGHTS`], along with the
+/// mutations of `extra`. Up to `1 << TEXT_DOCUMENT_MUTATION_STACK_POW` of them are stacked.
+///
+/// # Errors
+///
+/// Returns an error if the configured weights name an unknown mutation,
+/// or if a mutation of `extra` has the name of a built-in mutation.
+pub fn text_document_mutator<'g, State>(
+    grammar_lookup: &'g GrammarContextLookup,
+    generators_config: &GeneratorsConfig,
+    extra: MutationRegistry<'g, LspInput, State>,
+) -> Result<MutationRegistry<'g, LspInput, State>, libafl::Error>
+where
+    State: HasRand + HasMaxSize + HasMetadata + 'g,
+{
+    let mut registry = MutationRegistry::new().with_max_stack_pow(TEXT_DOCUMENT_MUTATION_STACK_POW);
+    registry.register_each(
+        text_document_mutations(grammar_lookup, generators_config),
+        DEFAULT_MUTATION_WEIGHTS,
+        &generators_config.mutation_weights,
+    )?;
+    registry.merge(extra)?;
+    Ok(registry)
+}
+
 #[must_use]
 pub fn text_document_mutations<'g, State>(
     grammar_lookup: &'g GrammarContextLookup,
     generators_config: &GeneratorsConfig,
-) -> impl MutatorsTuple<LspInput, State> + NamedTuple + use<'g, State>
+) -> impl MutatorsTuple<LspInput, State> + NamedTuple + RegisterEach<'g, LspInput, State> + use<'g, State>
 where
-    State: HasRand + HasMaxSize + HasMetadata,
+    State: HasRand + HasMaxSize + HasMetadata + 'g,
 {
-    use mutations::node_filters::NodesThat;
+    use mutations::node_filters::{NodeWeighting, NodesOfClass};
 
-    let any_node = NodesThat::new(|_: &tree_sitter::Node<'_>| true);
-    let terminal_node = NodesThat::new(|it: &tree_sitter::Node<'_>| it.child_count() == 0);
+    let weighting = generators_config.node_weighting;
+    let any_node = NodesOfClass::new(NodeClass::Any, weighting);
+    let terminal_node = NodesOfClass::new(NodeClass::Terminal, weighting);
     let remove_comment = ReplaceNodeInRandomRoc::new(
         grammar_lookup,
         HighlightedNodes::new("comment".to_owned()),
         EmptyNode,
-    );
+    )
+    .with_name("RemoveComment");
     let correct_code_mutations = tuple_list![
         ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, ChooseFromDerivations),
-        ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, ChooseFromDerivations),
-        ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, ExpandGrammar),
-        ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, ExpandGrammar),
-        ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, ExpandGrammar),
-        ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, ExpandGrammar),
-        remove_comment.clone(),
-        remove_comment.clone(),
+        ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, ChooseFromLearnedFragments),
+        ReplaceNodeInRandomRoc::new(
+            grammar_lookup,
+            any_node,
+            ExpandGrammar::new(generators_config.rule_weighting.clone())
+        )
+        .with_name("ReplaceNode:ExpandGrammar"),
         remove_comment,
+        ReplaceAllOccurrences::<RandomDoc, _, _>::new(
+            grammar_lookup,
+            terminal_node,
+            ChooseFromDerivations
+        ),
     ];
     let incorrect_code_mutations = {
         let recover_from_error = ReplaceNodeInRandomRoc::new(
             grammar_lookup,
-            NodesThat::new(|it: &tree_sitter::Node<'_>| it.is_error()),
+            NodesOfClass::new(NodeClass::Error, NodeWeighting::Uniform),
             ChooseFromDerivations,
-        );
+        )
+        .with_name("RecoverFromError");
         let produce_missing_node = ReplaceNodeInRandomRoc::new(
             grammar_lookup,
-            NodesThat::new(|it: &tree_sitter::Node<'_>| it.is_missing()),
+            NodesOfClass::new(NodeClass::Missing, NodeWeighting::Uniform),
             ChooseFromDerivations,
-        );
+        )
+        .with_name("ProduceMissingNode");
         let generate_mismatched =
             ReplaceNodeInRandomRoc::new(grammar_lookup, any_node, MismatchedNode);
         let terminal_truncation =
             NodeMutationInRandomDoc::new(NodeTruncation, grammar_lookup, terminal_node);
         // let terminal_char_mutation =
         //     NodeMutationInRandomDoc::new(NodeUTF8Mutation, grammar_lookup, terminal_node);
-        let drop_terminal = ReplaceNodeInRandomRoc::new(grammar_lookup, terminal_node, EmptyNode);
+        let drop_terminal = ReplaceNodeInRandomRoc::new(grammar_lookup, terminal_node, EmptyNode)
+            .with_name("DropTerminal");
+        let drop_all_occurrences =
+            ReplaceAllOccurrences::<RandomDoc, _, _>::new(grammar_lookup, any_node, EmptyNode)
+                .with_name("DropAllOccurrences");
+        let inject_syntax_error = InjectSyntaxError::<RandomDoc, _>::new(
+            grammar_lookup,
+            NodesOfClass::new(NodeClass::Named, weighting),
+        );
 
         tuple_list![
             recover_from_error,
@@ -341,13 +516,24 @@ where
             generate_mismatched.with_probability(generators_config.invalid_input.code_frequency),
             terminal_truncation.with_probability(generators_config.invalid_input.code_frequency),
             // terminal_char_mutation.with_probability(generators_config.invalid_input.code_frequency),
-            drop_terminal
-                .clone()
-                .with_probability(generators_config.invalid_input.code_frequency),
             drop_terminal.with_probability(generators_config.invalid_input.code_frequency),
+            drop_all_occurrences.with_probability(generators_config.invalid_input.code_frequency),
+            inject_syntax_error
+                .with_probability(generators_config.invalid_input.syntax_error_frequency),
         ]
     };
-    correct_code_mutations.merge(incorrect_code_mutations)
+    let encoding_mutations = tuple_list![
+        ToggleByteOrderMark::<RandomDoc>::new(),
+        MixLineEndings::<RandomDoc>::new(),
+        MutatePragmaVersion::<RandomDoc>::new(),
+    ];
+    let stress_mutations = tuple_list![
+        ApplyStressPattern::<RandomDoc>::new().with_probability(generators_config.stress_patterns)
+    ];
+    correct_code_mutations
+        .merge(incorrect_code_mutations)
+        .merge(encoding_mutations)
+        .merge(stress_mutations)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -361,7 +547,7 @@ impl From<TextDocument> for TextDocumentSerialized {
     fn from(document: TextDocument) -> Self {
         Self {
             language: document.language,
-            content: document.content,
+            content: document.content.into_vec(),
         }
     }
 }
@@ -415,9 +601,9 @@ mod tests {
         let content = b"hello\nworld\nrust";
         let doc = TextDocument::new(Language::Rust, content.to_vec());
         let mut lines = doc.lines();
-        assert_eq!(lines.next(), Some(b"hello".as_slice()));
-        assert_eq!(lines.next(), Some(b"world".as_slice()));
-        assert_eq!(lines.next(), Some(b"rust".as_slice()));
+        assert_eq!(lines.next().as_deref(), Some(b"hello".as_slice()));
+        assert_eq!(lines.next().as_deref(), Some(b"world".as_slice()));
+        assert_eq!(lines.next().as_deref(), Some(b"rust".as_slice()));
         assert_eq!(lines.next(), None);
     }
 
@@ -426,10 +612,172 @@ mod tests {
         let content = b"hello\nworld\nrust\n";
         let doc = TextDocument::new(Language::Rust, content.to_vec());
         let mut lines = doc.lines();
-        assert_eq!(lines.next(), Some(b"hello".as_slice()));
-        assert_eq!(lines.next(), Some(b"world".as_slice()));
-        assert_eq!(lines.next(), Some(b"rust".as_slice()));
-        assert_eq!(lines.next(), Some(b"".as_slice()));
+        assert_eq!(lines.next().as_deref(), Some(b"hello".as_slice()));
+        assert_eq!(lines.next().as_deref(), Some(b"world".as_slice()));
+        assert_eq!(lines.next().as_deref(), Some(b"rust".as_slice()));
+        assert_eq!(lines.next().as_deref(), Some(b"".as_slice()));
         assert_eq!(lines.next(), None);
     }
+
+    #[test]
+    fn text_doc_lines_around_gap() {
+        let mut doc = TextDocument::new(Language::Rust, b"hello\nworld\nrust".to_vec());
+        doc.edit(|content| {
+            content.splice(8..8, b"\n");
+            edit_for_node_replacement(
+                tree_sitter::Range {
+                    start_byte: 8,
+                    end_byte: 8,
+                    start_point: tree_sitter::Point { row: 1, column: 2 },
+                    end_point: tree_sitter::Point { row: 1, column: 2 },
+                },
+                b"\n",
+            )
+        });
+        assert!(!doc.content_slices().1.is_empty());
+        let lines: Vec<_> = doc.lines().collect();
+        assert_eq!(lines, [&b"hello"[..], b"wo", b"rld", b"rust"]);
+        assert_eq!(
+            doc,
+            TextDocument::new(Language::Rust, b"hello\nwo\nrld\nrust".to_vec())
+        );
+    }
+
+    #[test]
+    fn gap_buffer_splices_like_vec() {
+        let mut expected = b"fn main() {\n    foo();\n}\n".to_vec();
+        let mut buffer = GapBuffer::from(expected.clone());
+        let edits: [(Range<usize>, &[u8]); 4] = [
+            (16..19, b"bar_baz"),
+            (4..8, b""),
+            (0..0, b"// header\n"),
+            (20..22, &[b'x'; 5000]),
+        ];
+        for (range, replacement) in edits {
+            let _ = expected.splice(range.clone(), replacement.iter().copied());
+            buffer.splice(range, replacement);
+            assert_eq!(buffer.contiguous(), expected);
+            assert_eq!(buffer.get(2..12), &expected[2..12]);
+            let (front, back) = buffer.slices(1..expected.len() - 1);
+            assert_eq!([front, back].concat(), &expected[1..expected.len() - 1]);
+        }
+        assert_eq!(buffer.into_vec(), expected);
+    }
+
+    #[test]
+    fn reparse_after_splice() {
+        let mut doc = TextDocument::new(Language::C, b"int main() { return 0; }".to_vec());
+        let range = doc
+            .parse_tree()
+            .root_node()
+            .descendant_for_byte_range(20, 21)
+            .unwrap()
+            .range();
+        let _ = doc.splice(range, b"1 + 2".to_vec());
+        assert_eq!(&doc.content()[..], b"int main() { return 1 + 2; }");
+        let fresh = TextDocument::new(Language::C, doc.content().to_vec());
+        assert_eq!(
+            doc.parse_tree().root_node().to_sexp(),
+            fresh.parse_tree().root_node().to_sexp()
+        );
+    }
+
+    #[test]
+    fn node_index_matches_tree() {
+        let doc = TextDocument::new(
+            Language::C,
+            b"/* a */ int main() { return 0 } // b".to_vec(),
+        );
+        let index = &doc.metadata().node_index;
+        for class in [NodeClass::Any, NodeClass::Terminal, NodeClass::Missing] {
+            let expected: Vec<_> = doc
+                .parse_tree()
+                .iter()
+                .enumerate()
+                .filter(|(_, it)| class.matches(it))
+                .map(|(idx, _)| idx)
+                .collect();
+            assert_eq!(index.nodes_of(class).iter().collect::<Vec<_>>(), expected);
+        }
+        assert!(!index.nodes_of(NodeClass::Missing).is_empty());
+
+        let comments: Vec<_> = index
+            .captured_as(&doc, "comment")
+            .iter()
+            .map(|&idx| node_index::node_at(doc.parse_tree(), idx).byte_range())
+            .collect();
+        let expected: Vec<_> = grammar::tree_sitter::CapturesIterator::new(&doc, "comment")
+            .unwrap()
+            .map(|it| it.byte_range())
+            .collect();
+        assert_eq!(comments, expected);
+        assert!(index.captured_as(&doc, "no-such-capture").is_empty());
+    }
+
+    #[test]
+    fn node_index_updates_like_rebuild() {
+        let mut doc = TextDocument::new(
+            Language::C,
+            b"int foo(void);\nint main() {\n  return foo() + 1;\n}\nint bar;".to_vec(),
+        );
+        let replacements: [(&[u8], &[u8]); 4] = [
+            (b"1", b"2 * (3 + 4)"),
+            (b"foo", b"foo_bar"),
+            (b"{", b"{ {"),
+            (b"int bar;", b""),
+        ];
+        for (text, replacement) in replacements {
+            let range = doc
+                .parse_tree()
+                .iter()
+                .find(|it| &doc.content()[it.byte_range()] == text)
+                .unwrap()
+                .range();
+            let _ = doc.splice(range, replacement.to_vec());
+            let index = &doc.metadata().node_index;
+            let rebuilt = NodeIndex::build(doc.parse_tree());
+            assert_eq!(index.nodes(), rebuilt.nodes());
+            for class in [
+                NodeClass::Any,
+                NodeClass::Terminal,
+                NodeClass::Named,
+                NodeClass::Error,
+                NodeClass::Missing,
+            ] {
+                assert!(
+                    index
+                        .nodes_of(class)
+                        .iter()
+                        .eq(rebuilt.nodes_of(class).iter())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn splice_many_from_the_end() {
+        let mut doc = TextDocument::new(
+            Language::C,
+            b"int foo(void);\nint main() {\n  return foo();\n}".to_vec(),
+        );
+        let ranges: Vec<_> = doc
+            .parse_tree()
+            .iter()
+            .filter(|it| it.kind() == "identifier" && &doc.content()[it.byte_range()] == b"foo")
+            .map(|it| it.range())
+            .collect();
+        assert_eq!(ranges.len(), 2);
+        let edits = doc.splice_many(
+            ranges
+                .into_iter()
+                .map(|it| (it, b"barbaz".to_vec()))
+                .collect(),
+        );
+        assert_eq!(
+            &doc.content()[..],
+            b"int barbaz(void);\nint main() {\n  return barbaz();\n}"
+        );
+        assert_eq!(edits.len(), 2);
+        assert!(edits[0].start_byte > edits[1].start_byte);
+    }
 }
@@ -0,0 +1,100 @@
+//! Byte-level mutations of documents that disregard their syntax.
+//!
+//! These are too destructive for regular fuzzing, but help a campaign out of a plateau
+//! where the grammar-based mutations stopped finding new coverage.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+use derive_new::new as New;
+use libafl::{
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+};
+use libafl_bolts::{Named, rands::Rand};
+
+use super::{MAX_DOCUMENT_SIZE, core::TextDocumentSelector, line_endings::replace_bytes};
+use crate::{lsp_input::LspInput, text_document::GrammarBasedMutation};
+
+/// The maximum number of byte edits stacked onto a document at once.
+const MAX_STACKED_EDITS: usize = 16;
+
+/// The maximum number of bytes deleted, duplicated, or inserted by a single edit.
+const MAX_CHUNK_LEN: usize = 64;
+
+/// Stacks random byte edits, like AFL's havoc stage, onto a random document:
+/// bit flips, random bytes, and deleted, duplicated, or inserted chunks.
+#[derive(Debug, New)]
+pub struct HavocBytes<TS> {
+    _phantom: PhantomData<TS>,
+}
+
+impl<TS> Named for HavocBytes<TS> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("HavocBytes");
+        &NAME
+    }
+}
+
+impl<State, DocSel> Mutator<LspInput, State> for HavocBytes<DocSel>
+where
+    State: HasRand,
+    DocSel: TextDocumentSelector<State>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some((ref doc_uri, doc)) = DocSel::select_document_mut(state, input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let rand = state.rand_mut();
+        let stacked = 1 + rand.below_or_zero(MAX_STACKED_EDITS);
+        let input_edits = doc.edit_many(|content| {
+            let mut input_edits = Vec::with_capacity(stacked);
+            for _ in 0..stacked {
+                let len = content.len();
+                let pos = rand.below_or_zero(len);
+                let chunk_len = 1 + rand.below_or_zero(MAX_CHUNK_LEN.min(len.max(1)));
+                let end = (pos + chunk_len).min(len);
+                let input_edit = match rand.below_or_zero(5) {
+                    0 if pos < len => {
+                        let flipped = content.get(pos..pos + 1)[0] ^ (1 << rand.below_or_zero(8));
+                        replace_bytes(content, pos, pos + 1, &[flipped])
+                    }
+                    1 if pos < len => {
+                        let byte = u8::try_from(rand.below_or_zero(256)).unwrap_or_default();
+                        replace_bytes(content, pos, pos + 1, &[byte])
+                    }
+                    2 if pos < end => replace_bytes(content, pos, end, b""),
+                    3 if pos < end && len + (end - pos) <= MAX_DOCUMENT_SIZE => {
+                        let chunk = content.get(pos..end).into_owned();
+                        replace_bytes(content, end, end, &chunk)
+                    }
+                    _ if len + chunk_len <= MAX_DOCUMENT_SIZE => {
+                        let chunk: Vec<u8> = (0..chunk_len)
+                            .map(|_| u8::try_from(rand.below_or_zero(256)).unwrap_or_default())
+                            .collect();
+                        replace_bytes(content, pos, pos, &chunk)
+                    }
+                    _ => continue,
+                };
+                input_edits.push(input_edit);
+            }
+            input_edits
+        });
+        if input_edits.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        input.messages.calibrate_many(doc_uri, &input_edits);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
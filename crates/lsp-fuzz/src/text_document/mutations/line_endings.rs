@@ -0,0 +1,205 @@
+//! Mutations of the byte-order mark and the line endings of documents.
+//!
+//! Servers convert between byte offsets and line/column positions in many places,
+//! and these conversions often assume a single kind of line ending.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+use derive_new::new as New;
+use libafl::{
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+};
+use libafl_bolts::{Named, rands::Rand};
+
+use super::core::TextDocumentSelector;
+use crate::{
+    lsp_input::LspInput,
+    text_document::{GrammarBasedMutation, gap_buffer::GapBuffer},
+};
+
+const UTF8_BOM: &[u8] = "\u{feff}".as_bytes();
+const LINE_ENDINGS: [&[u8]; 3] = [b"\r\n", b"\n", b"\r"];
+
+/// Returns the position after `fragment` if it starts at `start`.
+///
+/// As in tree-sitter, only `\n` starts a new row.
+fn point_after(start: tree_sitter::Point, fragment: &[u8]) -> tree_sitter::Point {
+    fragment.iter().fold(start, |point, &byte| match byte {
+        b'\n' => tree_sitter::Point {
+            row: point.row + 1,
+            column: 0,
+        },
+        _ => tree_sitter::Point {
+            row: point.row,
+            column: point.column + 1,
+        },
+    })
+}
+
+/// Replaces `content[start..end]` with `replacement` and describes the edit.
+pub(super) fn replace_bytes(
+    content: &mut GapBuffer,
+    start: usize,
+    end: usize,
+    replacement: &[u8],
+) -> tree_sitter::InputEdit {
+    let (front, back) = content.slices(0..start);
+    let start_position = point_after(point_after(tree_sitter::Point::default(), front), back);
+    let (front, back) = content.slices(start..end);
+    let old_end_position = point_after(point_after(start_position, front), back);
+    let new_end_position = point_after(start_position, replacement);
+    content.splice(start..end, replacement);
+    tree_sitter::InputEdit {
+        start_byte: start,
+        old_end_byte: end,
+        new_end_byte: start + replacement.len(),
+        start_position,
+        old_end_position,
+        new_end_position,
+    }
+}
+
+/// Adds a UTF-8 byte-order mark to the beginning of a document, or removes it if present.
+#[derive(Debug, New)]
+pub struct ToggleByteOrderMark<TS> {
+    _phantom: PhantomData<TS>,
+}
+
+impl<TS> Named for ToggleByteOrderMark<TS> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ToggleByteOrderMark");
+        &NAME
+    }
+}
+
+impl<State, DocSel> Mutator<LspInput, State> for ToggleByteOrderMark<DocSel>
+where
+    DocSel: TextDocumentSelector<State>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some((ref doc_uri, doc)) = DocSel::select_document_mut(state, input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let input_edit = doc.edit(|content| {
+            if content.get(0..UTF8_BOM.len().min(content.len())) == UTF8_BOM {
+                replace_bytes(content, 0, UTF8_BOM.len(), b"")
+            } else {
+                replace_bytes(content, 0, 0, UTF8_BOM)
+            }
+        });
+        input.messages.calibrate(doc_uri, input_edit);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Replaces a random line ending of a document with a different one among
+/// `\r\n`, `\n`, and `\r`, so that the document mixes line endings.
+#[derive(Debug, New)]
+pub struct MixLineEndings<TS> {
+    _phantom: PhantomData<TS>,
+}
+
+impl<TS> Named for MixLineEndings<TS> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MixLineEndings");
+        &NAME
+    }
+}
+
+/// The byte ranges of the line endings in `content`.
+fn line_endings(content: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut idx = 0;
+    while idx < content.len() {
+        match &content[idx..] {
+            [b'\r', b'\n', ..] => {
+                ranges.push((idx, idx + 2));
+                idx += 2;
+            }
+            [b'\r' | b'\n', ..] => {
+                ranges.push((idx, idx + 1));
+                idx += 1;
+            }
+            _ => idx += 1,
+        }
+    }
+    ranges
+}
+
+impl<State, DocSel> Mutator<LspInput, State> for MixLineEndings<DocSel>
+where
+    State: HasRand,
+    DocSel: TextDocumentSelector<State>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some((ref doc_uri, doc)) = DocSel::select_document_mut(state, input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let rand = state.rand_mut();
+        let Some((start, end)) = rand.choose(line_endings(&doc.content())) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let replacement = {
+            let original = doc.fragment(start..end);
+            rand.choose(LINE_ENDINGS.iter().filter(|&&it| it != &*original))
+                .copied()
+                .expect("There are other line endings")
+        };
+        let input_edit = doc.edit(|content| replace_bytes(content, start, end, replacement));
+        input.messages.calibrate(doc_uri, input_edit);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_line_endings() {
+        assert_eq!(
+            line_endings(b"a\r\nb\nc\rd\n\r"),
+            vec![(1, 3), (4, 5), (6, 7), (8, 9), (9, 10)]
+        );
+    }
+
+    #[test]
+    fn replace_line_ending() {
+        let mut content = b"ab\ncd".to_vec();
+        let edit = replace_bytes(&mut content, 2, 3, b"\r");
+        assert_eq!(content, b"ab\rcd");
+        assert_eq!(
+            edit.old_end_position,
+            tree_sitter::Point { row: 1, column: 0 }
+        );
+        assert_eq!(
+            edit.new_end_position,
+            tree_sitter::Point { row: 0, column: 3 }
+        );
+    }
+}
@@ -8,12 +8,17 @@ use libafl::{
 };
 use libafl_bolts::{HasLen, Named, rands::Rand};
 
-use super::{GrammarBasedMutation, GrammarContextLookup};
+use super::{GrammarBasedMutation, GrammarContextLookup, node_index};
 use crate::lsp_input::LspInput;
 
 pub mod core;
+pub mod havoc;
+pub mod line_endings;
 pub mod node_filters;
 pub mod node_generators;
+pub mod pragmas;
+pub mod stress_patterns;
+pub mod syntax_errors;
 pub mod text_document_selectors;
 
 pub const MAX_DOCUMENT_SIZE: usize = 100_000;
@@ -45,7 +50,11 @@ impl<'a, TS, NodeSel, NodeGen> ReplaceNodeMutation<'a, TS, NodeSel, NodeGen> {
         node_selector: NodeSel,
         node_generator: NodeGen,
     ) -> Self {
-        let name = Cow::Owned("ReplaceNode".to_owned());
+        let generator_name = std::any::type_name::<NodeGen>()
+            .rsplit("::")
+            .next()
+            .unwrap_or_default();
+        let name = Cow::Owned(format!("ReplaceNode:{generator_name}"));
         Self {
             grammar_lookup,
             name,
@@ -54,6 +63,16 @@ impl<'a, TS, NodeSel, NodeGen> ReplaceNodeMutation<'a, TS, NodeSel, NodeGen> {
             _phantom: PhantomData,
         }
     }
+
+    /// Names the mutation `name` instead of after its node generator,
+    /// e.g., to weight it apart from other mutations with the same generator.
+    #[must_use]
+    pub fn with_name(self, name: &'static str) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            ..self
+        }
+    }
 }
 
 impl<TS, NodeSel, NodeGen> Named for ReplaceNodeMutation<'_, TS, NodeSel, NodeGen> {
@@ -108,6 +127,131 @@ where
     }
 }
 
+/// The maximum number of nodes replaced by one [`ReplaceAllOccurrences`].
+const MAX_OCCURRENCES: usize = 16;
+
+/// Replaces a node and the other nodes of the same kind with the same text with one
+/// generated replacement, e.g., renaming a declaration together with its uses.
+///
+/// The edits are applied and the messages calibrated in one step, since some states of a
+/// server are only reachable through such correlated edits.
+#[derive(Debug, Clone)]
+pub struct ReplaceAllOccurrences<'a, TS, NodeSel, NodeGen> {
+    grammar_lookup: &'a GrammarContextLookup,
+    name: Cow<'static, str>,
+    node_selector: NodeSel,
+    node_generator: NodeGen,
+    _phantom: PhantomData<TS>,
+}
+
+impl<'a, TS, NodeSel, NodeGen> ReplaceAllOccurrences<'a, TS, NodeSel, NodeGen> {
+    pub const fn new(
+        grammar_lookup: &'a GrammarContextLookup,
+        node_selector: NodeSel,
+        node_generator: NodeGen,
+    ) -> Self {
+        Self {
+            grammar_lookup,
+            name: Cow::Borrowed("ReplaceAllOccurrences"),
+            node_selector,
+            node_generator,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Names the mutation `name` instead of `ReplaceAllOccurrences`,
+    /// e.g., to weight it apart from the same mutation with another node generator.
+    #[must_use]
+    pub fn with_name(self, name: &'static str) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            ..self
+        }
+    }
+}
+
+impl<TS, NodeSel, NodeGen> Named for ReplaceAllOccurrences<'_, TS, NodeSel, NodeGen> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<State, DocSel, Sel, Gen> Mutator<LspInput, State>
+    for ReplaceAllOccurrences<'_, DocSel, Sel, Gen>
+where
+    State: HasRand,
+    DocSel: TextDocumentSelector<State>,
+    Sel: NodeSelector<State>,
+    Gen: NodeGenerator<State>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some((ref doc_uri, doc)) = DocSel::select_document_mut(state, input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let Some(grammar_ctx) = self.grammar_lookup.get(doc.language()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let Some(selected_node) = self.node_selector.select_node(doc, grammar_ctx, state) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let (kind_id, byte_range) = (selected_node.kind_id(), selected_node.byte_range());
+        let Some(replacement) =
+            self.node_generator
+                .generate_node(selected_node, grammar_ctx, state)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let text_len = byte_range.len();
+        let mut occurrences: Vec<_> = {
+            let text = doc.fragment(byte_range);
+            doc.metadata()
+                .node_index
+                .nodes()
+                .iter()
+                .enumerate()
+                .filter(|(_, it)| it.kind_id == kind_id && it.byte_len == text_len)
+                .map(|(idx, _)| node_index::node_at(doc.parse_tree(), idx))
+                .filter(|it| doc.fragment(it.byte_range()) == text)
+                .map(|it| it.range())
+                .collect()
+        };
+        if occurrences.len() > MAX_OCCURRENCES {
+            let rand = state.rand_mut();
+            for idx in 0..MAX_OCCURRENCES {
+                let other = idx + rand.below_or_zero(occurrences.len() - idx);
+                occurrences.swap(idx, other);
+            }
+            occurrences.truncate(MAX_OCCURRENCES);
+        }
+        let new_len = (doc.len() + occurrences.len() * replacement.len())
+            .saturating_sub(occurrences.len() * text_len);
+        if occurrences.len() < 2 || new_len > MAX_DOCUMENT_SIZE {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let replacements = occurrences
+            .into_iter()
+            .map(|range| (range, replacement.clone()))
+            .collect();
+        let input_edits = doc.splice_many(replacements);
+        input.messages.calibrate_many(doc_uri, &input_edits);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, New)]
 pub struct NodeContentMutation<'a, Mut, TS, NodeSel> {
     mutator: Mut,
@@ -146,11 +290,7 @@ where
         };
         let byte_range = selected_node.byte_range();
         let node_range = selected_node.range();
-        let mut node_content = doc
-            .content
-            .get(byte_range)
-            .expect("The node is within the document")
-            .to_vec();
+        let mut node_content = doc.content.get(byte_range).into_owned();
         let doc_len = doc.content.len();
         let node_len = node_content.len();
         self.mutator.mutate(&mut node_content, state);
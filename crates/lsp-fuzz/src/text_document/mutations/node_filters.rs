@@ -1,11 +1,28 @@
+use std::borrow::Cow;
+
+use ahash::HashMap;
+use derive_more::{Display, FromStr};
 use derive_new::new as New;
-use libafl::state::HasRand;
-use libafl_bolts::rands::Rand;
+use itertools::Itertools;
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    state::HasRand,
+};
+use libafl_bolts::{Named, SerdeAny, rands::Rand};
+use lsp_fuzz_grammars::Language;
+use serde::{Deserialize, Serialize};
 
 use super::NodeSelector;
-use crate::text_document::{
-    GrammarBasedMutation, GrammarContext, TextDocument,
-    grammar::tree_sitter::{CapturesIterator, TreeIter},
+use crate::{
+    lsp_input::LspInput,
+    text_document::{
+        GrammarBasedMutation, GrammarContext, TextDocument,
+        grammar::tree_sitter::TreeIter,
+        node_index::{ClassNodes, NodeClass, NodeInfo, node_at},
+    },
 };
 
 #[derive(Debug, Clone, Copy, New)]
@@ -32,6 +49,200 @@ where
     }
 }
 
+/// How [`NodesOfClass`] weights the candidate nodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Display, FromStr)]
+pub enum NodeWeighting {
+    /// All nodes are equally likely, which favors the leaves since most nodes are leaves.
+    #[default]
+    Uniform,
+    /// Nodes are weighted by the number of bytes they span, which favors large subtrees.
+    SubtreeSize,
+    /// Each band of depths is equally likely, and the nodes within a band too.
+    /// The bands double in width with depth: 0, 1..3, 3..7, and so on.
+    DepthBand,
+    /// Nodes are weighted by how rare their kind is among the documents in the corpus.
+    ///
+    /// The frequencies are recorded by [`NodeKindFrequencyFeedback`].
+    KindRarity,
+}
+
+/// The number of nodes of each kind in the documents in the corpus,
+/// per language since kind identifiers are specific to a grammar.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct NodeKindFrequencies {
+    counts: HashMap<Language, HashMap<u16, u64>>,
+}
+
+impl NodeKindFrequencies {
+    fn record(&mut self, language: Language, nodes: &[NodeInfo]) {
+        let counts = self.counts.entry(language).or_default();
+        for node in nodes {
+            *counts.entry(node.kind_id).or_default() += 1;
+        }
+    }
+
+    /// The weight of each of `candidates`, inversely proportional to the frequency of its kind.
+    fn rarity_weights(
+        &self,
+        language: Language,
+        nodes: &[NodeInfo],
+        candidates: ClassNodes<'_>,
+    ) -> Vec<usize> {
+        let counts = self.counts.get(&language);
+        let count_of = |kind_id| counts.and_then(|it| it.get(&kind_id)).copied().unwrap_or(1);
+        let most_frequent = counts
+            .and_then(|it| it.values().copied().max())
+            .unwrap_or(1);
+        candidates
+            .iter()
+            .map(|it| {
+                let count = count_of(nodes[it].kind_id);
+                usize::try_from(most_frequent / count.max(1)).unwrap_or(usize::MAX)
+            })
+            .collect()
+    }
+}
+
+/// Records the [`NodeKindFrequencies`] of the documents of each input added to the corpus,
+/// so that they are counted once per corpus entry rather than once per mutation.
+///
+/// The feedback never considers an input interesting by itself.
+#[derive(Debug, Default)]
+pub struct NodeKindFrequencyFeedback;
+
+impl NodeKindFrequencyFeedback {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Named for NodeKindFrequencyFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("NodeKindFrequencyFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for NodeKindFrequencyFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        state.metadata_or_insert_with(NodeKindFrequencies::default);
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for NodeKindFrequencyFeedback
+where
+    State: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut State,
+        _manager: &mut EM,
+        _input: &LspInput,
+        _observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _observers: &Observers,
+        testcase: &mut Testcase<LspInput>,
+    ) -> Result<(), libafl::Error> {
+        let Some(input) = testcase.input() else {
+            return Ok(());
+        };
+        let frequencies = state.metadata_or_insert_with(NodeKindFrequencies::default);
+        for (_, entry) in input.workspace.iter_files() {
+            if let Some(doc) = entry.as_source_file() {
+                frequencies.record(doc.language(), doc.metadata().node_index.nodes());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Selects a node of a class from the node index of the document,
+/// without walking the parse tree.
+#[derive(Debug, Clone, Copy, New)]
+pub struct NodesOfClass {
+    class: NodeClass,
+    weighting: NodeWeighting,
+}
+
+impl<State> NodeSelector<State> for NodesOfClass
+where
+    State: HasRand + HasMetadata,
+{
+    const NAME: &'static str = "NodesOfClass";
+
+    fn select_node<'t>(
+        &self,
+        doc: &'t mut TextDocument,
+        _grammar_context: &GrammarContext,
+        state: &mut State,
+    ) -> Option<tree_sitter::Node<'t>> {
+        let doc: &'t TextDocument = doc;
+        let index = &doc.metadata().node_index;
+        let nodes = index.nodes();
+        let candidates = index.nodes_of(self.class);
+        let idx = match self.weighting {
+            NodeWeighting::Uniform => state.rand_mut().choose(candidates.iter())?,
+            NodeWeighting::SubtreeSize => {
+                let weight = |pos: usize| nodes[candidates.get(pos)].byte_len + 1;
+                candidates.get(choose_weighted(state.rand_mut(), candidates.len(), weight)?)
+            }
+            NodeWeighting::DepthBand => {
+                let band = |idx: usize| (nodes[idx].depth + 1).ilog2();
+                let bands: Vec<_> = candidates.iter().map(band).unique().collect();
+                let rand = state.rand_mut();
+                let chosen = *rand.choose(&bands)?;
+                rand.choose(candidates.iter().filter(|&it| band(it) == chosen))?
+            }
+            NodeWeighting::KindRarity => {
+                let weights = state.metadata::<NodeKindFrequencies>().map_or_else(
+                    |_| vec![1; candidates.len()],
+                    |it| it.rarity_weights(doc.language(), nodes, candidates),
+                );
+                candidates.get(choose_weighted(state.rand_mut(), weights.len(), |pos| {
+                    weights[pos]
+                })?)
+            }
+        };
+        Some(node_at(doc.parse_tree(), idx))
+    }
+}
+
+/// Chooses a position below `len` with a probability proportional to its weight.
+fn choose_weighted<R: Rand>(
+    rand: &mut R,
+    len: usize,
+    weight: impl Fn(usize) -> usize,
+) -> Option<usize> {
+    let total: usize = (0..len).map(&weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut pick = rand.below_or_zero(total);
+    (0..len).find(|&pos| {
+        let weight = weight(pos);
+        if pick < weight {
+            true
+        } else {
+            pick -= weight;
+            false
+        }
+    })
+}
+
 #[derive(Debug, Clone, New)]
 pub struct HighlightedNodes {
     capture_group_name: String,
@@ -49,7 +260,12 @@ where
         _grammar_context: &GrammarContext,
         state: &mut State,
     ) -> Option<tree_sitter::Node<'t>> {
-        let captured_nodes = CapturesIterator::new(doc, &self.capture_group_name)?;
-        state.rand_mut().choose(captured_nodes)
+        let doc: &'t TextDocument = doc;
+        let captured_nodes = doc
+            .metadata()
+            .node_index
+            .captured_as(doc, &self.capture_group_name);
+        let idx = *state.rand_mut().choose(captured_nodes)?;
+        Some(node_at(doc.parse_tree(), idx))
     }
 }
@@ -1,11 +1,14 @@
-use std::{option::Option, vec::Vec};
+use std::{num::NonZero, option::Option, vec::Vec};
 
 use libafl::{HasMetadata, state::HasRand};
 use libafl_bolts::rands::Rand;
 
 use super::NodeGenerator;
-use crate::text_document::generation::{
-    GrammarContext, NamedNodeGenerator, RandomRuleSelectionStrategy,
+use crate::{
+    lsp_input::server_response::learned_fragments::LearnedFragments,
+    text_document::generation::{
+        GrammarContext, NamedNodeGenerator, RandomRuleSelectionStrategy, RuleSelectionStrategy,
+    },
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -42,12 +45,49 @@ where
     }
 }
 
+/// Chooses a replacement of the node from the code the server suggested for nodes of its kind.
+///
+/// See [`LearnedFragments`].
 #[derive(Debug)]
-pub struct ExpandGrammar;
+pub struct ChooseFromLearnedFragments;
 
-impl<State> NodeGenerator<State> for ExpandGrammar
+impl<State> NodeGenerator<State> for ChooseFromLearnedFragments
 where
     State: HasRand + HasMetadata,
+{
+    const NAME: &'static str = "LearnedFragment";
+    fn generate_node(
+        &self,
+        node: tree_sitter::Node<'_>,
+        grammar_context: &GrammarContext,
+        state: &mut State,
+    ) -> Option<Vec<u8>> {
+        let language = grammar_context.language();
+        let learned = state.metadata::<LearnedFragments>().ok()?;
+        let count = NonZero::new(learned.get(language, node.kind())?.len())?;
+        let idx = state.rand_mut().below(count);
+        let learned = state.metadata::<LearnedFragments>().ok()?;
+        let fragment = learned.get(language, node.kind())?.get_index(idx)?;
+        Some(fragment.as_bytes().to_vec())
+    }
+}
+
+/// Generates a replacement of the node from the grammar, choosing derivations with `Sel`.
+#[derive(Debug, Default)]
+pub struct ExpandGrammar<Sel = RandomRuleSelectionStrategy> {
+    selection_strategy: Sel,
+}
+
+impl<Sel> ExpandGrammar<Sel> {
+    pub const fn new(selection_strategy: Sel) -> Self {
+        Self { selection_strategy }
+    }
+}
+
+impl<State, Sel> NodeGenerator<State> for ExpandGrammar<Sel>
+where
+    State: HasRand + HasMetadata,
+    Sel: RuleSelectionStrategy<State>,
 {
     const NAME: &'static str = "RandomGeneration";
     fn generate_node(
@@ -56,10 +96,15 @@ where
         grammar_context: &GrammarContext,
         state: &mut State,
     ) -> Option<Vec<u8>> {
-        let selection_strategy = RandomRuleSelectionStrategy;
-        let generator = NamedNodeGenerator::new(grammar_context, selection_strategy);
-        let fragment = generator.generate(node.kind(), state).ok()?;
-        Some(fragment)
+        let symbols = grammar_context.replacement_symbols(node);
+        let start_symbol = state
+            .rand_mut()
+            .choose(symbols)
+            .unwrap_or_else(|| node.kind());
+        let generator = NamedNodeGenerator::new(grammar_context, &self.selection_strategy);
+        let fragment = generator.generate(start_symbol, state);
+        generator.conclude(state, fragment.is_ok());
+        fragment.ok()
     }
 }
 
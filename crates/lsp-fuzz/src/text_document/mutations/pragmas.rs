@@ -0,0 +1,128 @@
+//! Mutations of the version pragmas of Solidity documents.
+//!
+//! Solidity servers select the compiler, and sometimes the whole analysis pipeline,
+//! from the version constraint of `pragma solidity`, so the constraint is mutated
+//! on its own rather than left to the grammar-based mutations.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+use derive_new::new as New;
+use libafl::{
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+};
+use libafl_bolts::{Named, rands::Rand};
+use lsp_fuzz_grammars::Language;
+
+use super::{core::TextDocumentSelector, line_endings::replace_bytes};
+use crate::{lsp_input::LspInput, text_document::GrammarBasedMutation};
+
+const PRAGMA_PREFIX: &[u8] = b"pragma solidity";
+
+/// Version constraints that are unusual, conflicting, or malformed.
+const VERSION_CONSTRAINTS: &[&str] = &[
+    "^0.8.0",
+    "0.8.26",
+    ">=0.4.22 <0.9.0",
+    "~0.7.6",
+    "*",
+    "^0.8.0 || ^0.7.0",
+    "=0.5.0",
+    "0.8",
+    "^99.99.99",
+    ">0.8.0 <0.7.0",
+    "^0.8.0-nightly.2024.1.1",
+    "^0.8.0 ^0.8.1",
+    ">=",
+    "0.8.00000000000000000000000000000001",
+    "^0.4.0 || >=0.6.0 <0.7.0 || ^0.8.0",
+    "",
+];
+
+/// Replaces the version constraint of a `pragma solidity` directive,
+/// or adds a directive at the beginning of a Solidity document without one.
+#[derive(Debug, New)]
+pub struct MutatePragmaVersion<TS> {
+    _phantom: PhantomData<TS>,
+}
+
+impl<TS> Named for MutatePragmaVersion<TS> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("MutatePragmaVersion");
+        &NAME
+    }
+}
+
+/// The byte ranges of the version constraints of the `pragma solidity` directives in `content`.
+fn version_constraints(content: &[u8]) -> Vec<(usize, usize)> {
+    content
+        .windows(PRAGMA_PREFIX.len())
+        .enumerate()
+        .filter(|(_, it)| *it == PRAGMA_PREFIX)
+        .filter_map(|(idx, _)| {
+            let start = idx + PRAGMA_PREFIX.len();
+            let len = content[start..].iter().position(|&it| it == b';')?;
+            Some((start, start + len))
+        })
+        .collect()
+}
+
+/// A constraint from [`VERSION_CONSTRAINTS`], or a random version with large components.
+fn random_constraint<R: Rand>(rand: &mut R) -> String {
+    if rand.coinflip(0.2) {
+        let component = |rand: &mut R| rand.below_or_zero(1 << rand.below_or_zero(33));
+        format!(
+            "^{}.{}.{}",
+            component(rand),
+            component(rand),
+            component(rand)
+        )
+    } else {
+        (*rand
+            .choose(VERSION_CONSTRAINTS)
+            .expect("The constraints are not empty"))
+        .to_owned()
+    }
+}
+
+impl<State, DocSel> Mutator<LspInput, State> for MutatePragmaVersion<DocSel>
+where
+    State: HasRand,
+    DocSel: TextDocumentSelector<State>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some((ref doc_uri, doc)) = DocSel::select_document_mut(state, input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        if doc.language() != Language::Solidity {
+            return Ok(MutationResult::Skipped);
+        }
+        let rand = state.rand_mut();
+        let constraint = random_constraint(rand);
+        let constraints = version_constraints(&doc.content());
+        let input_edit = match rand.choose(constraints) {
+            Some((start, end)) => {
+                let replacement = format!(" {constraint}");
+                doc.edit(|content| replace_bytes(content, start, end, replacement.as_bytes()))
+            }
+            None => {
+                let directive = format!("pragma solidity {constraint};\n");
+                doc.edit(|content| replace_bytes(content, 0, 0, directive.as_bytes()))
+            }
+        };
+        input.messages.calibrate(doc_uri, input_edit);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,443 @@
+//! Pathological but valid structures, e.g., deeply nested expressions, functions with thousands
+//! of parameters, and very long identifiers.
+//!
+//! Such structures routinely exhaust the stacks of recursive analyses in servers, but uniform
+//! random derivation essentially never produces them. The [`StressTemplate`]s of each language
+//! name the node kinds a structure can replace and how it is built from the node, and
+//! [`ApplyStressPattern`] builds one in place of a random node of those kinds.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+use derive_new::new as New;
+use libafl::{
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+};
+use libafl_bolts::{Named, rands::Rand};
+use lsp_fuzz_grammars::Language;
+
+use super::{MAX_DOCUMENT_SIZE, core::TextDocumentSelector};
+use crate::{
+    lsp_input::LspInput,
+    text_document::{GrammarBasedMutation, grammar::tree_sitter::TreeIter},
+};
+
+/// The smallest size of a structure is `2^MIN_EXPONENT`.
+const MIN_EXPONENT: usize = 4;
+
+/// The largest size of a structure is `2^MAX_EXPONENT`.
+const MAX_EXPONENT: usize = 14;
+
+/// How a structure is built in place of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StressShape {
+    /// The node wrapped in `open` and `close` as many times as the size.
+    Nest {
+        open: &'static str,
+        close: &'static str,
+    },
+    /// A list of as many items as the size between `open` and `close`.
+    ///
+    /// The `{}` in `item` is replaced by the index of the item.
+    List {
+        open: &'static str,
+        item: &'static str,
+        separator: &'static str,
+        close: &'static str,
+    },
+    /// The node extended to as many bytes as the size, e.g., a long identifier.
+    Lengthen,
+}
+
+impl StressShape {
+    /// Builds the structure of `size` in place of a node with the text `node`.
+    #[must_use]
+    pub fn expand(self, node: &[u8], size: usize) -> Vec<u8> {
+        match self {
+            Self::Nest { open, close } => [
+                open.repeat(size).as_bytes(),
+                node,
+                close.repeat(size).as_bytes(),
+            ]
+            .concat(),
+            Self::List {
+                open,
+                item,
+                separator,
+                close,
+            } => {
+                let items: Vec<_> = (0..size)
+                    .map(|idx| item.replace("{}", &idx.to_string()))
+                    .collect();
+                format!("{open}{}{close}", items.join(separator)).into_bytes()
+            }
+            Self::Lengthen => {
+                let mut extended = node.to_vec();
+                extended.resize(size.max(node.len()), b'x');
+                extended
+            }
+        }
+    }
+}
+
+/// A structure built in place of the nodes of some kinds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StressTemplate {
+    pub node_kinds: &'static [&'static str],
+    /// The prefix of the text of the nodes the template applies to, e.g., `(` for argument
+    /// lists that can also be written without parentheses.
+    pub node_prefix: &'static str,
+    pub shape: StressShape,
+}
+
+impl StressTemplate {
+    /// Applies the template only to the nodes whose text starts with `node_prefix`.
+    #[must_use]
+    pub const fn with_node_prefix(self, node_prefix: &'static str) -> Self {
+        Self {
+            node_prefix,
+            ..self
+        }
+    }
+
+    /// Whether the template applies to `node` of `doc`.
+    fn applies_to(&self, doc: &impl GrammarBasedMutation, node: tree_sitter::Node<'_>) -> bool {
+        let range = node.byte_range();
+        let prefix_end = (range.start + self.node_prefix.len()).min(range.end);
+        self.node_kinds.contains(&node.kind())
+            && *doc.fragment(range.start..prefix_end) == *self.node_prefix.as_bytes()
+    }
+}
+
+const fn nest(
+    node_kinds: &'static [&'static str],
+    open: &'static str,
+    close: &'static str,
+) -> StressTemplate {
+    StressTemplate {
+        node_kinds,
+        node_prefix: "",
+        shape: StressShape::Nest { open, close },
+    }
+}
+
+const fn list(node_kinds: &'static [&'static str], item: &'static str) -> StressTemplate {
+    StressTemplate {
+        node_kinds,
+        node_prefix: "",
+        shape: StressShape::List {
+            open: "(",
+            item,
+            separator: ", ",
+            close: ")",
+        },
+    }
+}
+
+const fn lengthen(node_kinds: &'static [&'static str]) -> StressTemplate {
+    StressTemplate {
+        node_kinds,
+        node_prefix: "",
+        shape: StressShape::Lengthen,
+    }
+}
+
+const C_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    nest(&["compound_statement"], "{", "}"),
+    list(&["parameter_list"], "int p{}"),
+    list(&["argument_list"], "0"),
+    lengthen(&["identifier"]),
+];
+
+const GO_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    nest(&["block"], "{", "}"),
+    list(&["parameter_list"], "p{} int"),
+    list(&["argument_list"], "0"),
+    lengthen(&["identifier"]),
+];
+
+const RUST_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    nest(&["block"], "{ ", " }"),
+    list(&["parameters"], "p{}: i32"),
+    list(&["arguments"], "0"),
+    lengthen(&["identifier"]),
+];
+
+const JAVA_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    nest(&["block"], "{", "}"),
+    list(&["formal_parameters"], "int p{}"),
+    list(&["argument_list"], "0"),
+    lengthen(&["identifier"]),
+];
+
+const JAVASCRIPT_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    nest(&["statement_block"], "{", "}"),
+    nest(&["array"], "[", "]"),
+    list(&["formal_parameters"], "p{}"),
+    list(&["arguments"], "0"),
+    lengthen(&["identifier"]),
+];
+
+const ZIG_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    nest(&["block"], "{", "}"),
+    list(&["parameters"], "p{}: i32"),
+    list(&["arguments"], "0"),
+    lengthen(&["identifier"]),
+];
+
+// The parameters of methods are aliased, and the arguments of a call may go without parentheses.
+const RUBY_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_statements"], "(", ")"),
+    nest(&["array"], "[", "]"),
+    list(&["method_parameters"], "p{}"),
+    list(&["argument_list"], "0").with_node_prefix("("),
+    lengthen(&["identifier"]),
+];
+
+const SOLIDITY_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    lengthen(&["identifier"]),
+];
+
+const QML_TEMPLATES: &[StressTemplate] = &[
+    nest(&["parenthesized_expression"], "(", ")"),
+    nest(&["statement_block"], "{", "}"),
+    nest(&["array"], "[", "]"),
+    lengthen(&["identifier"]),
+];
+
+const JSON_TEMPLATES: &[StressTemplate] = &[
+    nest(&["array", "object"], "[", "]"),
+    nest(&["array", "object"], "{\"k\": ", "}"),
+    StressTemplate {
+        node_kinds: &["array"],
+        node_prefix: "",
+        shape: StressShape::List {
+            open: "[",
+            item: "{}",
+            separator: ", ",
+            close: "]",
+        },
+    },
+];
+
+const TOML_TEMPLATES: &[StressTemplate] = &[nest(&["array"], "[", "]")];
+
+const LATEX_TEMPLATES: &[StressTemplate] = &[nest(&["curly_group"], "{", "}")];
+
+const HTML_TEMPLATES: &[StressTemplate] = &[nest(&["element"], "<div>", "</div>")];
+
+/// The stress templates of `language`, which are empty for languages without any.
+#[must_use]
+pub const fn stress_templates(language: Language) -> &'static [StressTemplate] {
+    match language {
+        Language::C | Language::CPlusPlus => C_TEMPLATES,
+        Language::Go => GO_TEMPLATES,
+        Language::Rust => RUST_TEMPLATES,
+        Language::Java => JAVA_TEMPLATES,
+        Language::JavaScript | Language::TypeScript | Language::TSX => JAVASCRIPT_TEMPLATES,
+        Language::Zig => ZIG_TEMPLATES,
+        Language::Ruby => RUBY_TEMPLATES,
+        Language::Solidity => SOLIDITY_TEMPLATES,
+        Language::QML => QML_TEMPLATES,
+        Language::Json => JSON_TEMPLATES,
+        Language::Toml => TOML_TEMPLATES,
+        Language::LaTeX => LATEX_TEMPLATES,
+        Language::Html => HTML_TEMPLATES,
+        _ => &[],
+    }
+}
+
+fn has_template(doc: &impl GrammarBasedMutation, node: tree_sitter::Node<'_>) -> bool {
+    stress_templates(doc.language())
+        .iter()
+        .any(|it| it.applies_to(doc, node))
+}
+
+/// Builds the structure of a random [`StressTemplate`] in place of a node of a random document,
+/// with a size between `2^MIN_EXPONENT` and `2^MAX_EXPONENT`.
+#[derive(Debug, New)]
+pub struct ApplyStressPattern<TS> {
+    _phantom: PhantomData<TS>,
+}
+
+impl<TS> Named for ApplyStressPattern<TS> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ApplyStressPattern");
+        &NAME
+    }
+}
+
+impl<State, TS> Mutator<LspInput, State> for ApplyStressPattern<TS>
+where
+    State: HasRand,
+    TS: TextDocumentSelector<State>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some((ref doc_uri, doc)) = TS::select_document_mut(state, input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let language = doc.language();
+        let candidates = doc
+            .parse_tree()
+            .iter()
+            .filter(|it| !it.has_error() && has_template(&*doc, *it));
+        let rand = state.rand_mut();
+        let Some(node) = rand.choose(candidates) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let templates = stress_templates(language)
+            .iter()
+            .filter(|it| it.applies_to(&*doc, node));
+        let template = rand
+            .choose(templates)
+            .expect("The node is chosen for having a template");
+        let size = 1 << (MIN_EXPONENT + rand.below_or_zero(MAX_EXPONENT - MIN_EXPONENT + 1));
+        let node_range = node.range();
+        let stressed = template
+            .shape
+            .expand(&doc.fragment(node.byte_range()), size);
+        if doc.content.len() - node.byte_range().len() + stressed.len() > MAX_DOCUMENT_SIZE {
+            return Ok(MutationResult::Skipped);
+        }
+        let input_edit = doc.splice(node_range, stressed);
+        input.messages.calibrate(doc_uri, input_edit);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_document::TextDocument;
+
+    /// A document of each language with templates, with a node for each of its templates.
+    const SAMPLES: &[(Language, &str)] = &[
+        (Language::C, "int f(int a) { return (a) + f(a); }"),
+        (Language::CPlusPlus, "int f(int a) { return (a) + f(a); }"),
+        (
+            Language::Go,
+            "package main\n\nfunc f(a int) int { return (a) + f(a) }\n",
+        ),
+        (Language::Rust, "fn f(a: i32) -> i32 { (a) + f(a) }"),
+        (
+            Language::Java,
+            "class A { int f(int a) { return (a) + f(a); } }",
+        ),
+        (
+            Language::JavaScript,
+            "function f(a) { return [a] + (a) + f(a); }",
+        ),
+        (
+            Language::TypeScript,
+            "function f(a) { return [a] + (a) + f(a); }",
+        ),
+        (Language::TSX, "function f(a) { return [a] + (a) + f(a); }"),
+        (
+            Language::Zig,
+            "fn f(a: i32) i32 {\n    return (a) + f(a);\n}\n",
+        ),
+        (
+            Language::Ruby,
+            "def f(a)\n  puts a\n  [a] + (a) + f(a)\nend\n",
+        ),
+        (
+            Language::Solidity,
+            "contract C {\n    function f(uint a) public returns (uint) { return (a) + f(a); }\n}\n",
+        ),
+        (
+            Language::QML,
+            "import QtQuick 2.0\n\nItem {\n    function f(a) { return [a] + (a) + f(a); }\n}\n",
+        ),
+        (Language::Json, "{\"a\": [1, {\"b\": 2}]}"),
+        (Language::Toml, "a = [1, [2]]\n"),
+        (Language::LaTeX, "Some {text} here.\n"),
+        (Language::Html, "<div><p>a</p></div>\n"),
+    ];
+
+    #[test]
+    fn every_language_with_templates_has_a_sample() {
+        for language in Language::ALL {
+            assert!(
+                stress_templates(language).is_empty()
+                    || SAMPLES.iter().any(|(it, _)| *it == language),
+                "{language:?} has no sample"
+            );
+        }
+    }
+
+    #[test]
+    fn templates_keep_documents_valid() {
+        for &(language, sample) in SAMPLES {
+            let doc = TextDocument::new(language, sample.as_bytes().to_vec());
+            assert!(
+                !doc.parse_tree().root_node().has_error(),
+                "The sample of {language:?} is invalid"
+            );
+            for template in stress_templates(language) {
+                let targets: Vec<_> = doc
+                    .parse_tree()
+                    .iter()
+                    .filter(|it| template.applies_to(&doc, *it))
+                    .map(|it| it.byte_range())
+                    .collect();
+                assert!(
+                    !targets.is_empty(),
+                    "{template:?} is not applied to {language:?}"
+                );
+                for range in targets {
+                    let content = doc.content();
+                    let stressed = template.shape.expand(&doc.fragment(range.clone()), 64);
+                    let content = [
+                        &content[..range.start],
+                        &stressed[..],
+                        &content[range.end..],
+                    ]
+                    .concat();
+                    let stressed_doc = TextDocument::new(language, content);
+                    assert!(
+                        !stressed_doc.parse_tree().root_node().has_error(),
+                        "{template:?} breaks {language:?} at {range:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ruby_arguments_without_parentheses_are_skipped() {
+        let doc = TextDocument::new(Language::Ruby, b"puts a\nputs(a)\n".to_vec());
+        let arguments: Vec<_> = doc
+            .parse_tree()
+            .iter()
+            .filter(|it| it.kind() == "argument_list")
+            .map(|it| has_template(&doc, it))
+            .collect();
+        assert_eq!(arguments, [false, true]);
+    }
+
+    #[test]
+    fn lengthen_identifiers() {
+        let long = StressShape::Lengthen.expand(b"foo", 16);
+        assert_eq!(long, b"fooxxxxxxxxxxxxx");
+        assert_eq!(StressShape::Lengthen.expand(b"foo", 2), b"foo");
+    }
+}
@@ -0,0 +1,139 @@
+//! Mutations that break the syntax around a selected node.
+//!
+//! Unlike the generic invalid-code mutations, these aim at a specific node,
+//! so that tree-sitter reports `ERROR` or `MISSING` nodes at a known location
+//! and the error recovery of the server can be compared against it.
+
+use std::{borrow::Cow, marker::PhantomData};
+
+use itertools::Itertools;
+use libafl::{
+    mutators::{MutationResult, Mutator},
+    state::HasRand,
+};
+use libafl_bolts::{Named, rands::Rand};
+
+use super::{
+    MAX_DOCUMENT_SIZE,
+    core::{NodeSelector, TextDocumentSelector},
+};
+use crate::{
+    lsp_input::LspInput,
+    text_document::{GrammarBasedMutation, generation::GrammarContextLookup},
+};
+
+const DELIMITERS: [(u8, u8); 4] = [(b'(', b')'), (b'[', b']'), (b'{', b'}'), (b'"', b'"')];
+
+#[derive(Debug)]
+pub struct InjectSyntaxError<'a, TS, NodeSel> {
+    grammar_lookup: &'a GrammarContextLookup,
+    node_selector: NodeSel,
+    _phantom: PhantomData<TS>,
+}
+
+impl<'a, TS, NodeSel> InjectSyntaxError<'a, TS, NodeSel> {
+    pub const fn new(grammar_lookup: &'a GrammarContextLookup, node_selector: NodeSel) -> Self {
+        Self {
+            grammar_lookup,
+            node_selector,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<TS, NodeSel: Clone> Clone for InjectSyntaxError<'_, TS, NodeSel> {
+    fn clone(&self) -> Self {
+        Self::new(self.grammar_lookup, self.node_selector.clone())
+    }
+}
+
+impl<TS, NodeSel> Named for InjectSyntaxError<'_, TS, NodeSel> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("InjectSyntaxError");
+        &NAME
+    }
+}
+
+/// Drops a delimiter of the node or inserts a stray one, leaving its counterpart unmatched.
+fn unbalance_delimiter<R: Rand>(content: &mut Vec<u8>, rand: &mut R) {
+    let delimiter_positions = content
+        .iter()
+        .positions(|byte| DELIMITERS.iter().any(|(l, r)| byte == l || byte == r));
+    if let Some(idx) = rand.choose(delimiter_positions)
+        && rand.coinflip(0.5)
+    {
+        content.remove(idx);
+    } else {
+        let (open, close) = rand.choose(DELIMITERS).expect("DELIMITERS is not empty");
+        let delimiter = if rand.coinflip(0.5) { open } else { close };
+        let idx = rand.below_or_zero(content.len() + 1);
+        content.insert(idx, delimiter);
+    }
+}
+
+/// Cuts the node after one of its children except the last one,
+/// leaving the construct unfinished (e.g., a block without its closing brace).
+///
+/// `child_ends` are the end offsets of the children relative to the start of the node.
+fn truncate_construct<R: Rand>(child_ends: &[usize], content: &mut Vec<u8>, rand: &mut R) -> bool {
+    let kept_children = child_ends.len().saturating_sub(1);
+    let Some(&end) = rand.choose(&child_ends[..kept_children]) else {
+        return false;
+    };
+    content.truncate(end);
+    true
+}
+
+impl<State, DocSel, NodeSel> Mutator<LspInput, State> for InjectSyntaxError<'_, DocSel, NodeSel>
+where
+    State: HasRand,
+    DocSel: TextDocumentSelector<State>,
+    NodeSel: NodeSelector<State>,
+{
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut LspInput,
+    ) -> Result<MutationResult, libafl::Error> {
+        let Some((ref doc_uri, doc)) = DocSel::select_document_mut(state, input) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let Some(grammar_ctx) = self.grammar_lookup.get(doc.language()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let doc_len = doc.content.len();
+        let Some(selected_node) = self.node_selector.select_node(doc, grammar_ctx, state) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let node_range = selected_node.range();
+        let byte_range = selected_node.byte_range();
+        let child_ends: Vec<_> = {
+            let mut cursor = selected_node.walk();
+            selected_node
+                .children(&mut cursor)
+                .map(|it| it.end_byte() - byte_range.start)
+                .collect()
+        };
+        let mut node_content = doc.content.get(byte_range.clone()).into_owned();
+        let rand = state.rand_mut();
+        if !(rand.coinflip(0.5) && truncate_construct(&child_ends, &mut node_content, rand)) {
+            unbalance_delimiter(&mut node_content, rand);
+        }
+        if doc.content.get(byte_range.clone()) == node_content
+            || doc_len - byte_range.len() + node_content.len() > MAX_DOCUMENT_SIZE
+        {
+            return Ok(MutationResult::Skipped);
+        }
+        let input_edit = doc.splice(node_range, node_content);
+        input.messages.calibrate(doc_uri, input_edit);
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut State,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::OnceLock;
+
+use ahash::HashMap;
+use itertools::Either;
+use tree_sitter::{QueryCursor, StreamingIterator};
+
+use super::{TextDocument, grammar::tree_sitter::TreeIter};
+
+/// A class of nodes that mutations select from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeClass {
+    Any,
+    /// Nodes without children.
+    Terminal,
+    Named,
+    Error,
+    Missing,
+}
+
+impl NodeClass {
+    /// The classes whose nodes are listed in the [`NodeIndex`].
+    const LISTED: [Self; 4] = [Self::Terminal, Self::Named, Self::Error, Self::Missing];
+
+    #[must_use]
+    pub fn matches(self, node: &tree_sitter::Node<'_>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Terminal => node.child_count() == 0,
+            Self::Named => node.is_named(),
+            Self::Error => node.is_error(),
+            Self::Missing => node.is_missing(),
+        }
+    }
+}
+
+/// The shape of a node, as used to weight the selection of nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub kind_id: u16,
+    pub start_byte: usize,
+    pub byte_len: usize,
+    /// The depth of the node, where the root has depth 0.
+    pub depth: u32,
+}
+
+/// The descendant indices of the nodes of a class, see [`NodeIndex::nodes_of`].
+#[derive(Debug, Clone, Copy)]
+pub enum ClassNodes<'a> {
+    /// All nodes, i.e., the indices below the number of nodes.
+    All(usize),
+    Listed(&'a [usize]),
+}
+
+impl ClassNodes<'_> {
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        match self {
+            Self::All(len) => *len,
+            Self::Listed(indices) => indices.len(),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The descendant index of the node at `pos` among the nodes of the class.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` is out of bounds.
+    #[must_use]
+    pub fn get(&self, pos: usize) -> usize {
+        match self {
+            Self::All(len) => {
+                assert!(pos < *len, "The position is out of bounds");
+                pos
+            }
+            Self::Listed(indices) => indices[pos],
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        match self {
+            Self::All(len) => Either::Left(0..*len),
+            Self::Listed(indices) => Either::Right(indices.iter().copied()),
+        }
+    }
+}
+
+/// The nodes of a parse tree grouped by class and by highlight capture,
+/// identified by their descendant indices in the tree.
+///
+/// The classes are indexed when the document is parsed and updated around the edited range
+/// after each edit, while the captures of the highlight query are indexed on first use.
+/// All nodes belong to [`NodeClass::Any`], so it is not listed.
+#[derive(Debug, Clone, Default)]
+pub struct NodeIndex {
+    nodes: Vec<NodeInfo>,
+    classes: HashMap<NodeClass, Vec<usize>>,
+    captures: OnceLock<HashMap<u32, Vec<usize>>>,
+}
+
+impl NodeIndex {
+    pub(super) fn build(tree: &tree_sitter::Tree) -> Self {
+        let mut index = Self::default();
+        let node_count = tree.root_node().descendant_count();
+        index.nodes.reserve(node_count);
+        index.index_walk(&mut tree.walk(), node_count);
+        index
+    }
+
+    /// Indexes `count` nodes in a pre-order walk from the node of `cursor`, appending them
+    /// after the nodes already indexed.
+    fn index_walk(&mut self, cursor: &mut tree_sitter::TreeCursor<'_>, count: usize) {
+        // The descendant indices of the nodes are their positions in a pre-order walk.
+        for _ in 0..count {
+            let node = cursor.node();
+            let idx = self.nodes.len();
+            self.nodes.push(NodeInfo {
+                kind_id: node.kind_id(),
+                start_byte: node.start_byte(),
+                byte_len: node.byte_range().len(),
+                depth: cursor.depth(),
+            });
+            for class in NodeClass::LISTED.into_iter().filter(|it| it.matches(&node)) {
+                self.classes.entry(class).or_default().push(idx);
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Updates the index of the tree before `edits` to `tree`, reparsed after them.
+    ///
+    /// Since the nodes are in pre-order, which is ordered by the start byte, the nodes starting
+    /// before the edited range and those starting after it are kept, and only the nodes
+    /// in between are indexed from `tree`. The edited range includes the ranges of `tree`
+    /// whose structure changed.
+    pub(super) fn update(
+        &mut self,
+        edits: &[tree_sitter::InputEdit],
+        changed_ranges: impl Iterator<Item = tree_sitter::Range>,
+        tree: &tree_sitter::Tree,
+    ) {
+        let node_count = tree.root_node().descendant_count();
+        let Some(edited) = EditedRange::new(edits, changed_ranges) else {
+            *self = Self::build(tree);
+            return;
+        };
+        let kept_before = self
+            .nodes
+            .partition_point(|it| it.start_byte < edited.start);
+        let kept_after = self.nodes.len()
+            - self
+                .nodes
+                .partition_point(|it| it.start_byte < edited.old_end);
+        if kept_before + kept_after > node_count {
+            *self = Self::build(tree);
+            return;
+        }
+        let old_after = self.nodes.len() - kept_after;
+        let new_after = node_count - kept_after;
+
+        let mut after = self.nodes.split_off(old_after);
+        self.nodes.truncate(kept_before);
+        // The ancestors of the edited range start before it but end after it.
+        let mut cursor = tree.walk();
+        for (idx, node) in self.nodes.iter_mut().enumerate() {
+            if node.start_byte + node.byte_len > edited.start {
+                cursor.goto_descendant(idx);
+                node.byte_len = cursor.node().byte_range().len();
+            }
+        }
+        let mut classes = std::mem::take(&mut self.classes);
+        let after_classes: Vec<_> = classes
+            .iter_mut()
+            .map(|(&class, indices)| {
+                let split = indices.partition_point(|&it| it < old_after);
+                let after = indices.split_off(split);
+                indices.truncate(indices.partition_point(|&it| it < kept_before));
+                (class, after)
+            })
+            .collect();
+        self.classes = classes;
+
+        self.nodes.reserve(node_count - kept_before);
+        if new_after > kept_before {
+            cursor.goto_descendant(kept_before);
+            self.index_walk(&mut cursor, new_after - kept_before);
+        }
+        for node in &mut after {
+            node.start_byte = node
+                .start_byte
+                .checked_add_signed(edited.delta)
+                .expect("The nodes after the edits are not moved before the start");
+        }
+        self.nodes.append(&mut after);
+        for (class, indices) in after_classes {
+            self.classes
+                .entry(class)
+                .or_default()
+                .extend(indices.into_iter().map(|it| it - old_after + new_after));
+        }
+        self.captures = OnceLock::new();
+    }
+
+    /// The shapes of the nodes, by descendant index.
+    #[must_use]
+    pub fn nodes(&self) -> &[NodeInfo] {
+        &self.nodes
+    }
+
+    /// The descendant indices of the nodes of `class`.
+    #[must_use]
+    pub fn nodes_of(&self, class: NodeClass) -> ClassNodes<'_> {
+        match class {
+            NodeClass::Any => ClassNodes::All(self.nodes.len()),
+            class => ClassNodes::Listed(self.classes.get(&class).map_or(&[], Vec::as_slice)),
+        }
+    }
+
+    /// The descendant indices of the nodes captured as `capture_name` by the highlight query
+    /// of the language of `doc`, which must be the document of this index.
+    #[must_use]
+    pub fn captured_as(&self, doc: &TextDocument, capture_name: &str) -> &[usize] {
+        let query = doc.language.ts_highlight_query();
+        let Some(capture_index) = query.capture_index_for_name(capture_name) else {
+            return &[];
+        };
+        self.captures
+            .get_or_init(|| index_captures(doc))
+            .get(&capture_index)
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The byte range covering a sequence of edits, in the content before and after them.
+struct EditedRange {
+    start: usize,
+    /// The end of the range before the edits.
+    old_end: usize,
+    /// The change of the length of the content by the edits.
+    delta: isize,
+}
+
+impl EditedRange {
+    /// The range covering `edits`, applied one after another, and `changed_ranges` of the tree
+    /// after them, or `None` if there are no edits.
+    fn new(
+        edits: &[tree_sitter::InputEdit],
+        changed_ranges: impl Iterator<Item = tree_sitter::Range>,
+    ) -> Option<Self> {
+        let (first, rest) = edits.split_first()?;
+        let mut start = first.start_byte;
+        let mut new_end = first.new_end_byte;
+        let mut delta = 0;
+        for edit in edits {
+            delta += isize::try_from(edit.new_end_byte).ok()?
+                - isize::try_from(edit.old_end_byte).ok()?;
+        }
+        // Each edit refers to the content after the ones before it.
+        for edit in rest {
+            start = start.min(edit.start_byte);
+            new_end = if new_end >= edit.old_end_byte {
+                new_end - edit.old_end_byte + edit.new_end_byte
+            } else {
+                new_end.max(edit.new_end_byte)
+            };
+        }
+        for range in changed_ranges {
+            start = start.min(range.start_byte);
+            new_end = new_end.max(range.end_byte);
+        }
+        let old_end = new_end.checked_add_signed(-delta)?;
+        Some(Self {
+            start,
+            old_end,
+            delta,
+        })
+    }
+}
+
+fn index_captures(doc: &TextDocument) -> HashMap<u32, Vec<usize>> {
+    let tree = &doc.metadata.parse_tree;
+    let descendant_indices: HashMap<usize, usize> = tree
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.id(), idx))
+        .collect();
+    let mut captures: HashMap<u32, Vec<usize>> = HashMap::default();
+    let mut cursor = QueryCursor::new();
+    let query = doc.language.ts_highlight_query();
+    let mut matches = cursor.captures(query, tree.root_node(), doc);
+    while let Some((query_match, index)) = matches.next() {
+        let capture = query_match.captures[*index];
+        if let Some(&idx) = descendant_indices.get(&capture.node.id()) {
+            captures.entry(capture.index).or_default().push(idx);
+        }
+    }
+    captures
+}
+
+/// The node of `tree` at the descendant index `idx`.
+#[must_use]
+pub fn node_at(tree: &tree_sitter::Tree, idx: usize) -> tree_sitter::Node<'_> {
+    let mut cursor = tree.walk();
+    cursor.goto_descendant(idx);
+    cursor.node()
+}
@@ -0,0 +1,318 @@
+//! Scheduling derivation rules by the coverage they led to.
+//!
+//! [`CoverageGuidedRuleSelection`] records the derivation rules it selects for the current input
+//! in a [`RuleTrail`], once the code derived with them is kept. After the execution, [`RuleCoverageFeedback`] credits the rules of the
+//! inputs added to the corpus in [`RuleCoverageStats`] and attaches them to the corpus entries as
+//! [`DerivationRules`], so that later selections favor the rules that produced new coverage.
+
+use std::{borrow::Cow, mem};
+
+use ahash::HashSet;
+use libafl::{
+    HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    state::HasRand,
+};
+use libafl_bolts::{Named, SerdeAny};
+use lsp_fuzz_grammars::Language;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    generation::{GrammarContext, RandomRuleSelectionStrategy, RuleSelectionStrategy},
+    grammar::DerivationSequence,
+};
+use crate::{lsp_input::LspInput, utils::RandExt};
+
+/// A derivation rule, identified by its language, the symbol it derives,
+/// and its index among the derivations of the symbol in the grammar.
+pub type RuleRef = (Language, String, usize);
+
+/// The weight of a derivation never selected, see [`RuleCoverage::weight`].
+pub const UNEXPLORED_RULE_WEIGHT: u64 = 1024;
+
+/// How often a derivation rule was selected and how often it contributed to a corpus entry.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RuleCoverage {
+    pub selections: u64,
+    pub productive: u64,
+}
+
+impl RuleCoverage {
+    /// The weight of the rule in [`CoverageGuidedRuleSelection`].
+    ///
+    /// The weight is proportional to the fraction of the selections that were productive,
+    /// smoothed so that rules never selected weigh [`UNEXPLORED_RULE_WEIGHT`] and unproductive
+    /// rules are still selected from time to time.
+    #[must_use]
+    pub fn weight(&self) -> usize {
+        let weight = UNEXPLORED_RULE_WEIGHT * (self.productive + 1) / (self.selections + 1);
+        usize::try_from(weight).unwrap_or(usize::MAX).max(1)
+    }
+}
+
+/// The [`RuleCoverage`] of the derivation rules, by language and symbol.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct RuleCoverageStats {
+    pub rules: ahash::HashMap<Language, ahash::HashMap<String, Vec<RuleCoverage>>>,
+    /// The rules selected for the code being derived, counted once the code is kept.
+    #[serde(skip)]
+    pending: Vec<RuleRef>,
+}
+
+impl RuleCoverageStats {
+    fn rule_stats(
+        &mut self,
+        language: Language,
+        node_kind: &str,
+        len: usize,
+    ) -> &mut [RuleCoverage] {
+        let rules = self.rules.entry(language).or_default();
+        if !rules.contains_key(node_kind) {
+            rules.insert(node_kind.to_owned(), Vec::new());
+        }
+        let stats = rules.get_mut(node_kind).expect("We inserted it above");
+        // The grammar may change between runs resuming the same state.
+        stats.resize(len, RuleCoverage::default());
+        stats
+    }
+
+    fn rule_mut(&mut self, (language, node_kind, idx): &RuleRef) -> Option<&mut RuleCoverage> {
+        self.rules
+            .get_mut(language)?
+            .get_mut(node_kind.as_str())?
+            .get_mut(*idx)
+    }
+}
+
+/// The derivation rules selected to produce the current input.
+///
+/// The metadata is present only while a [`RuleCoverageFeedback`] is attached, so that rules are
+/// recorded only when they are credited.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct RuleTrail {
+    pub rules: HashSet<RuleRef>,
+}
+
+/// The derivation rules selected to produce a corpus entry.
+#[allow(clippy::unsafe_derive_deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, SerdeAny)]
+pub struct DerivationRules {
+    pub rules: Vec<RuleRef>,
+}
+
+/// Chooses derivations in proportion to the coverage they led to, see [`RuleCoverage::weight`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoverageGuidedRuleSelection;
+
+impl<State> RuleSelectionStrategy<State> for CoverageGuidedRuleSelection
+where
+    State: HasRand + HasMetadata,
+{
+    fn select_fragment<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a [u8]> {
+        RandomRuleSelectionStrategy.select_fragment(state, node_kind, grammar_context)
+    }
+
+    fn select_rule<'a>(
+        &self,
+        state: &mut State,
+        node_kind: &str,
+        grammar_context: &'a GrammarContext,
+    ) -> Option<&'a DerivationSequence> {
+        let language = grammar_context.language();
+        let rules = grammar_context.grammar.derivation_rules().get(node_kind)?;
+        let weights: Vec<_> = state
+            .metadata_or_insert_with(RuleCoverageStats::default)
+            .rule_stats(language, node_kind, rules.len())
+            .iter()
+            .map(RuleCoverage::weight)
+            .collect();
+        let chosen_idx = state
+            .rand_mut()
+            .weighted_choose(weights.into_iter().enumerate())?;
+
+        state
+            .metadata_mut::<RuleCoverageStats>()
+            .expect("We inserted it before")
+            .pending
+            .push((language, node_kind.to_owned(), chosen_idx));
+        rules.get_index(chosen_idx)
+    }
+
+    fn conclude_generation(&self, state: &mut State, kept: bool) {
+        let Some(stats) = state.metadata_map_mut().get_mut::<RuleCoverageStats>() else {
+            return;
+        };
+        let pending = mem::take(&mut stats.pending);
+        if !kept {
+            return;
+        }
+        for rule in &pending {
+            if let Some(coverage) = stats.rule_mut(rule) {
+                coverage.selections += 1;
+            }
+        }
+        if let Some(trail) = state.metadata_map_mut().get_mut::<RuleTrail>() {
+            trail.rules.extend(pending);
+        }
+    }
+}
+
+/// Credits the derivation rules of the inputs added to the corpus, see the [module](self).
+///
+/// The feedback never considers an input interesting by itself.
+#[derive(Debug, Default)]
+pub struct RuleCoverageFeedback {
+    rules: Vec<RuleRef>,
+}
+
+impl RuleCoverageFeedback {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Named for RuleCoverageFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RuleCoverageFeedback");
+        &NAME
+    }
+}
+
+impl<State> StateInitializer<State> for RuleCoverageFeedback
+where
+    State: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut State) -> Result<(), libafl::Error> {
+        state.add_metadata(RuleTrail::default());
+        Ok(())
+    }
+}
+
+impl<EM, Observers, State> Feedback<EM, LspInput, Observers, State> for RuleCoverageFeedback
+where
+    State: HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _input: &LspInput,
+        _observers: &Observers,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        self.rules = state
+            .metadata_map_mut()
+            .get_mut::<RuleTrail>()
+            .map(|it| it.rules.drain().collect())
+            .unwrap_or_default();
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut State,
+        _manager: &mut EM,
+        _observers: &Observers,
+        testcase: &mut Testcase<LspInput>,
+    ) -> Result<(), libafl::Error> {
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+        let rules = mem::take(&mut self.rules);
+        let stats = state.metadata_or_insert_with(RuleCoverageStats::default);
+        for rule in &rules {
+            if let Some(coverage) = stats.rule_mut(rule) {
+                coverage.productive += 1;
+            }
+        }
+        testcase.add_metadata(DerivationRules { rules });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{
+        HasMetadata,
+        corpus::Testcase,
+        executors::ExitKind,
+        feedbacks::{Feedback, StateInitializer},
+        state::NopState,
+    };
+    use lsp_fuzz_grammars::Language;
+
+    use super::{
+        CoverageGuidedRuleSelection, DerivationRules, RuleCoverage, RuleCoverageFeedback,
+        RuleCoverageStats, UNEXPLORED_RULE_WEIGHT,
+    };
+    use crate::{lsp_input::LspInput, text_document::generation::RuleSelectionStrategy};
+
+    #[test]
+    fn productive_rules_outweigh_unproductive_ones() {
+        let unexplored = RuleCoverage::default();
+        let productive = RuleCoverage {
+            selections: 10,
+            productive: 5,
+        };
+        let unproductive = RuleCoverage {
+            selections: 10_000,
+            productive: 0,
+        };
+        assert_eq!(
+            unexplored.weight(),
+            usize::try_from(UNEXPLORED_RULE_WEIGHT).unwrap()
+        );
+        assert!(productive.weight() > unproductive.weight());
+        assert_eq!(unproductive.weight(), 1);
+    }
+
+    #[test]
+    fn credit_rules_of_kept_generations() {
+        let mut state = NopState::<LspInput>::new();
+        let mut feedback = RuleCoverageFeedback::new();
+        feedback.init_state(&mut state).unwrap();
+        let mut stats = RuleCoverageStats::default();
+        stats.rule_stats(Language::Rust, "expression", 2);
+        state.add_metadata(stats);
+
+        let select = |state: &mut NopState<LspInput>, idx: usize, kept: bool| {
+            state
+                .metadata_mut::<RuleCoverageStats>()
+                .unwrap()
+                .pending
+                .push((Language::Rust, "expression".to_owned(), idx));
+            CoverageGuidedRuleSelection.conclude_generation(state, kept);
+        };
+        select(&mut state, 0, true);
+        select(&mut state, 1, false);
+
+        let input = LspInput::default();
+        let interesting = feedback
+            .is_interesting(&mut state, &mut (), &input, &(), &ExitKind::Ok)
+            .unwrap();
+        assert!(!interesting);
+        let mut testcase = Testcase::new(input);
+        feedback
+            .append_metadata(&mut state, &mut (), &(), &mut testcase)
+            .unwrap();
+
+        let rules = &testcase.metadata::<DerivationRules>().unwrap().rules;
+        assert_eq!(rules, &[(Language::Rust, "expression".to_owned(), 0)]);
+        let stats = state
+            .metadata_mut::<RuleCoverageStats>()
+            .unwrap()
+            .rule_stats(Language::Rust, "expression", 2);
+        assert_eq!((stats[0].selections, stats[0].productive), (1, 1));
+        assert_eq!((stats[1].selections, stats[1].productive), (0, 0));
+    }
+}
@@ -0,0 +1,450 @@
+//! Rewriting the identifiers of generated documents to the identifiers declared in them.
+//!
+//! Code generated from a grammar takes its identifiers from fragments, which are rarely declared
+//! in the same scope, so that servers give up early in semantic analysis.
+//! [`resolve_identifiers`] replaces the identifiers declared in the document but not in an
+//! enclosing scope with ones that are, following the [`ScopeRules`] of the language.
+
+use std::{borrow::Cow, collections::HashSet};
+
+use itertools::Itertools;
+use libafl_bolts::rands::Rand;
+use lsp_fuzz_grammars::Language;
+use tree_sitter::Node;
+
+use super::{GrammarBasedMutation, TextDocument, grammar::tree_sitter::TreeIter};
+
+/// A child of a node of the given kind, in the given field or in any position if `None`.
+pub type ChildPattern = (&'static str, Option<&'static str>);
+
+/// How identifiers are declared and scoped in the syntax tree of a language.
+#[derive(Debug, Clone, Copy)]
+pub struct ScopeRules {
+    /// The kinds of the identifier nodes.
+    pub identifiers: &'static [&'static str],
+    /// The kinds of the nodes opening a scope, besides the root of the tree.
+    pub scopes: &'static [&'static str],
+    /// Identifiers visible in the nearest scope after their declaration, e.g., variables.
+    pub locals: &'static [ChildPattern],
+    /// Identifiers visible anywhere in the scope around the nearest one, e.g., functions,
+    /// whose nearest scope is their own body.
+    pub items: &'static [ChildPattern],
+    /// The kinds of the nodes between a declared identifier and its declaration,
+    /// e.g., the pointer declarators in C.
+    pub transparent: &'static [&'static str],
+    /// Identifiers that are neither declarations nor references, e.g., macro names.
+    pub ignored: &'static [ChildPattern],
+    /// The kinds of the nodes whose identifiers are all left alone, e.g., imports.
+    pub opaque: &'static [&'static str],
+}
+
+impl ScopeRules {
+    /// The rules of `language`, or `None` if identifiers are not resolved in the language.
+    #[must_use]
+    pub const fn of(language: Language) -> Option<&'static Self> {
+        let rules = match language {
+            Language::C => &Self {
+                identifiers: &["identifier"],
+                scopes: &["compound_statement", "for_statement", "function_definition"],
+                locals: &[
+                    ("declaration", Some("declarator")),
+                    ("init_declarator", Some("declarator")),
+                    ("parameter_declaration", Some("declarator")),
+                ],
+                items: &[
+                    ("function_declarator", Some("declarator")),
+                    ("preproc_def", Some("name")),
+                    ("preproc_function_def", Some("name")),
+                ],
+                transparent: &[
+                    "pointer_declarator",
+                    "array_declarator",
+                    "parenthesized_declarator",
+                ],
+                ignored: &[],
+                opaque: &["preproc_include"],
+            },
+            Language::CPlusPlus => &Self {
+                identifiers: &["identifier"],
+                scopes: &[
+                    "compound_statement",
+                    "for_statement",
+                    "for_range_loop",
+                    "function_definition",
+                    "lambda_expression",
+                ],
+                locals: &[
+                    ("declaration", Some("declarator")),
+                    ("init_declarator", Some("declarator")),
+                    ("parameter_declaration", Some("declarator")),
+                    ("for_range_loop", Some("declarator")),
+                ],
+                items: &[
+                    ("function_declarator", Some("declarator")),
+                    ("preproc_def", Some("name")),
+                    ("preproc_function_def", Some("name")),
+                ],
+                transparent: &[
+                    "pointer_declarator",
+                    "reference_declarator",
+                    "array_declarator",
+                    "parenthesized_declarator",
+                ],
+                ignored: &[],
+                opaque: &[
+                    "preproc_include",
+                    "qualified_identifier",
+                    "using_declaration",
+                ],
+            },
+            Language::Rust => &Self {
+                identifiers: &["identifier"],
+                scopes: &[
+                    "block",
+                    "function_item",
+                    "closure_expression",
+                    "for_expression",
+                    "match_arm",
+                ],
+                locals: &[
+                    ("let_declaration", Some("pattern")),
+                    ("parameter", Some("pattern")),
+                    ("for_expression", Some("pattern")),
+                    ("closure_parameters", None),
+                    ("match_pattern", None),
+                ],
+                items: &[
+                    ("function_item", Some("name")),
+                    ("const_item", Some("name")),
+                    ("static_item", Some("name")),
+                ],
+                transparent: &["tuple_pattern", "mut_pattern", "reference_pattern"],
+                ignored: &[
+                    ("macro_invocation", Some("macro")),
+                    ("tuple_struct_pattern", Some("type")),
+                ],
+                opaque: &[
+                    "attribute_item",
+                    "inner_attribute_item",
+                    "use_declaration",
+                    "scoped_identifier",
+                    "macro_definition",
+                ],
+            },
+            Language::Go => &Self {
+                identifiers: &["identifier"],
+                scopes: &[
+                    "block",
+                    "function_declaration",
+                    "method_declaration",
+                    "func_literal",
+                    "for_statement",
+                    "if_statement",
+                ],
+                locals: &[
+                    ("short_var_declaration", Some("left")),
+                    ("var_spec", Some("name")),
+                    ("const_spec", Some("name")),
+                    ("parameter_declaration", Some("name")),
+                    ("range_clause", Some("left")),
+                ],
+                items: &[("function_declaration", Some("name"))],
+                transparent: &["expression_list"],
+                ignored: &[("selector_expression", Some("operand"))],
+                opaque: &["import_declaration"],
+            },
+            Language::JavaScript | Language::TypeScript | Language::TSX => &Self {
+                identifiers: &["identifier"],
+                scopes: &[
+                    "statement_block",
+                    "function_declaration",
+                    "function_expression",
+                    "generator_function_declaration",
+                    "arrow_function",
+                    "for_statement",
+                    "for_in_statement",
+                ],
+                locals: &[
+                    ("variable_declarator", Some("name")),
+                    ("formal_parameters", None),
+                    ("required_parameter", Some("pattern")),
+                    ("optional_parameter", Some("pattern")),
+                    ("arrow_function", Some("parameter")),
+                    ("for_in_statement", Some("left")),
+                    ("catch_clause", Some("parameter")),
+                ],
+                items: &[
+                    ("function_declaration", Some("name")),
+                    ("generator_function_declaration", Some("name")),
+                    ("class_declaration", Some("name")),
+                ],
+                transparent: &[],
+                ignored: &[("member_expression", Some("object"))],
+                opaque: &["import_statement", "export_clause"],
+            },
+            Language::Java => &Self {
+                identifiers: &["identifier"],
+                scopes: &[
+                    "block",
+                    "class_body",
+                    "method_declaration",
+                    "constructor_declaration",
+                    "lambda_expression",
+                    "for_statement",
+                    "enhanced_for_statement",
+                ],
+                locals: &[
+                    ("variable_declarator", Some("name")),
+                    ("formal_parameter", Some("name")),
+                    ("catch_formal_parameter", Some("name")),
+                    ("enhanced_for_statement", Some("name")),
+                    ("inferred_parameters", None),
+                ],
+                items: &[
+                    ("method_declaration", Some("name")),
+                    ("class_declaration", Some("name")),
+                ],
+                transparent: &[],
+                ignored: &[
+                    ("field_access", Some("object")),
+                    ("field_access", Some("field")),
+                    ("method_invocation", Some("object")),
+                ],
+                opaque: &[
+                    "import_declaration",
+                    "package_declaration",
+                    "scoped_identifier",
+                    "annotation",
+                    "marker_annotation",
+                ],
+            },
+            _ => return None,
+        };
+        Some(rules)
+    }
+
+    /// Whether `node` declares an identifier, and if so whether it is an item.
+    fn declaration(&self, node: Node<'_>) -> Option<bool> {
+        let mut child = node;
+        let mut parent = node.parent()?;
+        while self.transparent.contains(&parent.kind()) {
+            child = parent;
+            parent = parent.parent()?;
+        }
+        if matches_any(self.items, parent, child) {
+            Some(true)
+        } else if matches_any(self.locals, parent, child) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn is_ignored(&self, node: Node<'_>) -> bool {
+        let Some(parent) = node.parent() else {
+            return false;
+        };
+        matches_any(self.ignored, parent, node)
+            || std::iter::successors(Some(parent), Node::parent)
+                .any(|it| self.opaque.contains(&it.kind()))
+    }
+
+    /// The scope in which the identifier `node` is visible.
+    fn scope<'tree>(&self, node: Node<'tree>, is_item: bool) -> Node<'tree> {
+        let mut scopes = std::iter::successors(node.parent(), Node::parent)
+            .filter(|it| self.scopes.contains(&it.kind()) || it.parent().is_none());
+        let nearest = scopes.next();
+        let scope = if is_item {
+            scopes.next().or(nearest)
+        } else {
+            nearest
+        };
+        scope.unwrap_or(node)
+    }
+}
+
+fn matches_any(patterns: &[ChildPattern], parent: Node<'_>, child: Node<'_>) -> bool {
+    patterns.iter().any(|&(kind, field)| {
+        kind == parent.kind()
+            && field.is_none_or(|field| {
+                parent
+                    .children_by_field_name(field, &mut parent.walk())
+                    .any(|it| it == child)
+            })
+    })
+}
+
+#[derive(Debug)]
+struct Declaration<'a> {
+    name: Cow<'a, [u8]>,
+    scope: tree_sitter::Range,
+    start_byte: usize,
+    is_item: bool,
+}
+
+impl Declaration<'_> {
+    fn is_visible_at(&self, node: Node<'_>) -> bool {
+        self.scope.start_byte <= node.start_byte()
+            && node.end_byte() <= self.scope.end_byte
+            && (self.is_item || self.start_byte < node.start_byte())
+    }
+}
+
+/// Replaces the identifiers of `doc` declared in the document but not in an enclosing scope
+/// with randomly chosen identifiers that are, returning the number of identifiers replaced.
+///
+/// Identifiers declared nowhere in the document, e.g., library functions and globals such as
+/// `printf` or `console`, are left alone, and so are the documents of languages without
+/// [`ScopeRules`].
+pub fn resolve_identifiers<R: Rand>(doc: &mut TextDocument, rand: &mut R) -> usize {
+    let Some(rules) = ScopeRules::of(doc.language()) else {
+        return 0;
+    };
+    let replacements = unresolved_references(doc, rules, rand);
+    let replaced = replacements.len();
+    if replaced > 0 {
+        doc.splice_many(replacements);
+    }
+    replaced
+}
+
+/// The ranges of the identifiers of `doc` declared in the document but not in an enclosing scope,
+/// along with randomly chosen identifiers that are.
+fn unresolved_references<R: Rand>(
+    doc: &TextDocument,
+    rules: &ScopeRules,
+    rand: &mut R,
+) -> Vec<(tree_sitter::Range, Vec<u8>)> {
+    let mut declarations = Vec::new();
+    let mut references = Vec::new();
+    for node in doc.parse_tree().iter() {
+        if !rules.identifiers.contains(&node.kind())
+            || node.byte_range().is_empty()
+            || rules.is_ignored(node)
+        {
+            continue;
+        }
+        match rules.declaration(node) {
+            Some(is_item) => declarations.push(Declaration {
+                name: doc.fragment(node.byte_range()),
+                scope: rules.scope(node, is_item).range(),
+                start_byte: node.start_byte(),
+                is_item,
+            }),
+            None => references.push(node),
+        }
+    }
+    let declared: HashSet<_> = declarations.iter().map(|it| &it.name).collect();
+    references
+        .into_iter()
+        .filter_map(|node| {
+            let name = doc.fragment(node.byte_range());
+            if !declared.contains(&name) {
+                return None;
+            }
+            let visible: Vec<_> = declarations
+                .iter()
+                .filter(|it| it.is_visible_at(node))
+                .map(|it| &it.name)
+                .unique()
+                .collect();
+            if visible.contains(&&name) {
+                return None;
+            }
+            let replacement = rand.choose(visible)?;
+            Some((node.range(), replacement.to_vec()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+
+    fn resolve(language: Language, code: &str) -> (usize, String) {
+        let mut rand = StdRand::with_seed(0);
+        let mut doc = TextDocument::new(language, code.as_bytes().to_vec());
+        let replaced = resolve_identifiers(&mut doc, &mut rand);
+        (replaced, doc.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn resolve_c_identifiers() {
+        let (replaced, content) = resolve(
+            Language::C,
+            "int f(void) { int a; return 0; } int g(void) { return a; }",
+        );
+        assert_eq!(replaced, 1);
+        assert!(
+            content.ends_with("return f; }") || content.ends_with("return g; }"),
+            "{content}"
+        );
+        assert_eq!(resolve(Language::C, "int f(int a) { return a; }").0, 0);
+        let code = "int main(void) { printf(\"%d\", errno); }";
+        assert_eq!(resolve(Language::C, code), (0, code.to_owned()));
+    }
+
+    #[test]
+    fn resolve_cpp_identifiers() {
+        let (replaced, content) = resolve(
+            Language::CPlusPlus,
+            "int f() { for (int i : v) {} std::cout << v; return i; }",
+        );
+        assert_eq!(replaced, 1);
+        assert!(
+            content.ends_with("std::cout << v; return f; }"),
+            "{content}"
+        );
+    }
+
+    #[test]
+    fn resolve_rust_identifiers() {
+        let (replaced, content) = resolve(
+            Language::Rust,
+            "fn f() -> i32 { { let a = 1; } let b = 2; a + b + c }",
+        );
+        assert_eq!(replaced, 1);
+        assert!(
+            content.ends_with("f + b + c }") || content.ends_with("b + b + c }"),
+            "{content}"
+        );
+    }
+
+    #[test]
+    fn resolve_go_identifiers() {
+        let (replaced, content) = resolve(
+            Language::Go,
+            "package main\n\nfunc f() int {\n\tif true {\n\t\ta := 1\n\t}\n\treturn a + fmt.Len\n}\n",
+        );
+        assert_eq!(replaced, 1);
+        assert!(content.contains("return f + fmt.Len"), "{content}");
+    }
+
+    #[test]
+    fn resolve_javascript_identifiers() {
+        for language in [Language::JavaScript, Language::TypeScript, Language::TSX] {
+            let (replaced, content) = resolve(
+                language,
+                "function f() { { let a = 1; } console.log(a, Math.PI); }",
+            );
+            assert_eq!(replaced, 1, "{language}");
+            assert!(content.ends_with("console.log(f, Math.PI); }"), "{content}");
+        }
+    }
+
+    #[test]
+    fn resolve_java_identifiers() {
+        let (replaced, content) = resolve(
+            Language::Java,
+            "class A { void f() { { int a = 1; } System.out.println(a); } }",
+        );
+        assert_eq!(replaced, 1);
+        assert!(
+            content.ends_with("System.out.println(f); } }")
+                || content.ends_with("System.out.println(A); } }"),
+            "{content}"
+        );
+    }
+}